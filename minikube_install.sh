@@ -15,7 +15,7 @@ docker exec $MINIKUBE_DOCKER_ID chmod 755 /usr/local/bin/qkernel_d.bin
 
 # copy config
 docker exec $MINIKUBE_DOCKER_ID mkdir -p /etc/quark/
-minikube cp /etc/quark/config.json minikube:/etc/quark/config.json
+minikube cp /etc/quark/config.toml minikube:/etc/quark/config.toml
 
 ## create quark log directory if haven't
 docker exec $MINIKUBE_DOCKER_ID mkdir -p /var/log/quark
@@ -13,13 +13,16 @@
 // limitations under the License.
 
 mod idt;
+pub mod oops;
 
 use super::asm::*;
 use super::qlib::addr::*;
+use super::qlib::backtracer;
 use super::qlib::common::*;
+use super::qlib::kernel::arch::x86_64::async_pf::*;
+use super::qlib::kernel::arch::x86_64::tsc_timer::*;
 use super::qlib::kernel::TSC;
 use super::qlib::linux_def::*;
-use super::qlib::backtracer;
 use super::qlib::singleton::*;
 use super::qlib::vcpu_mgr::*;
 use super::task::*;
@@ -81,6 +84,9 @@ extern "C" {
     pub fn simd_fp_handler();
     pub fn virtualization_handler();
     pub fn security_handler();
+    pub fn tsc_deadline_handler();
+    pub fn spurious_handler();
+    pub fn async_pf_handler();
 }
 
 pub static IDT: Singleton<idt::Idt> = Singleton::<idt::Idt>::New();
@@ -127,6 +133,13 @@ pub unsafe fn InitSingleton() {
 
     idt.set_handler(30, security_handler).set_stack_index(0);
 
+    idt.set_handler(TSC_DEADLINE_VECTOR, tsc_deadline_handler)
+        .set_stack_index(0);
+    idt.set_handler(SPURIOUS_VECTOR, spurious_handler)
+        .set_stack_index(0);
+    idt.set_handler(ASYNC_PF_VECTOR, async_pf_handler)
+        .set_stack_index(0);
+
     IDT.Init(idt);
 }
 
@@ -159,6 +172,7 @@ pub fn ExceptionHandler(ev: ExceptionStackVec, ptRegs: &mut PtRegs, errorCode: u
             print!("CPU#{} is {:#x?}", i, super::CPU_LOCAL[i]);
         }
 
+        oops::Oops(&format!("exception {:?} in kernel", ev), Some(&*ptRegs));
         panic!("Get on page fault exception from kernel");
     };
 
@@ -421,6 +435,7 @@ pub extern "C" fn PageFaultHandler(ptRegs: &mut PtRegs, errorCode: u64) {
     unsafe { llvm_asm!("mov %cr2, $0" : "=r" (cr2) ) };
     let cr3: u64;
     unsafe { llvm_asm!("mov %cr3, $0" : "=r" (cr3) ) };
+    let asyncPfToken = AsyncPageNotPresent(cr2);
 
     let ss: u16 = 16;
     unsafe {
@@ -451,6 +466,20 @@ pub extern "C" fn PageFaultHandler(ptRegs: &mut PtRegs, errorCode: u64) {
         panic!("PageFaultHandler full restore wrong...");
     }
 
+    if let Some(token) = asyncPfToken {
+        // the host page is not in yet, the task waits for it and goes back
+        // to the faulting instruction
+        AsyncPfWaitPage(currTask, token);
+        currTask.RestoreFp();
+        currTask.AccountTaskEnter(SchedState::RunningApp);
+        if SHARESPACE.config.read().KernelPagetable {
+            currTask.SwitchPageTable();
+        }
+        CPULocal::Myself().SetMode(VcpuMode::User);
+        currTask.mm.HandleTlbShootdown();
+        return;
+    }
+
     if !fromUser {
         print!(
             "Get pagefault from kernel ... {:#x?}/cr2 is {:x}/cr3 is {:x}",
@@ -460,6 +489,10 @@ pub extern "C" fn PageFaultHandler(ptRegs: &mut PtRegs, errorCode: u64) {
             print!("pagefault frame is {:#x?}", frame);
             true
         });
+        oops::Oops(
+            &format!("page fault at {:x} in kernel", cr2),
+            Some(&*ptRegs),
+        );
         panic!("Get pagefault from kernel .");
     }
 
@@ -616,6 +649,7 @@ pub fn HandleFault(
         print!("unhandle EXCEPTION: page_fault FAULT\n{:#?}, error code is {:?}, cr2 is {:x}, registers is {:#x?}",
                sf, errorCode, cr2, task.GetPtRegs());
         print!("the map 3 is {}", &map);
+        oops::Oops(&format!("page fault at {:x} in kernel", cr2), Some(&*sf));
         panic!();
     }
 
@@ -699,33 +733,77 @@ pub extern "C" fn VirtualizationHandler(ptRegs: &mut PtRegs) {
         return;
     } else if CPULocal::InterruptByThreadTimeout(mask) {
         if ptRegs.ss & 0x3 != 0 {
-            // from user
-            let mut rflags = ptRegs.eflags;
-            rflags &= !USER_FLAGS_CLEAR;
-            rflags |= USER_FLAGS_SET;
-            ptRegs.eflags = rflags;
+            PreemptApp(ptRegs, currTask);
+        }
+    }
+}
 
-            if SHARESPACE.config.read().KernelPagetable {
-                Task::SetKernelPageTable();
-            }
+// PreemptApp yields the vcpu of the task interrupted in the application, the
+// task goes back to the application when it is scheduled again.
+pub fn PreemptApp(ptRegs: &mut PtRegs, currTask: &mut Task) {
+    let mut rflags = ptRegs.eflags;
+    rflags &= !USER_FLAGS_CLEAR;
+    rflags |= USER_FLAGS_SET;
+    ptRegs.eflags = rflags;
+
+    if SHARESPACE.config.read().KernelPagetable {
+        Task::SetKernelPageTable();
+    }
 
-            currTask.AccountTaskLeave(SchedState::RunningApp);
-            //currTask.SaveFp();
+    currTask.AccountTaskLeave(SchedState::RunningApp);
+    //currTask.SaveFp();
 
-            super::qlib::kernel::taskMgr::Yield();
-            MainRun(currTask, TaskRunState::RunApp);
+    super::qlib::kernel::taskMgr::Yield();
+    MainRun(currTask, TaskRunState::RunApp);
+    CPULocal::Myself().SetMode(VcpuMode::User);
+    currTask.mm.HandleTlbShootdown();
+    currTask.RestoreFp();
+    CPULocal::Myself().SetEnterAppTimestamp(TSC.Rdtsc());
+    ArmTscDeadline();
+    CPULocal::SetKernelStack(currTask.GetKernelSp());
+    let kernalRsp = ptRegs as *const _ as u64;
+    if !(ptRegs.rip == ptRegs.rcx && ptRegs.r11 == ptRegs.eflags) {
+        IRet(kernalRsp)
+    } else {
+        SyscallRet(kernalRsp)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TscDeadlineHandler(ptRegs: &mut PtRegs) {
+    let fromUser = ptRegs.ss & 0x3 != 0;
+    CPULocal::Myself().SetMode(VcpuMode::Kernel);
+    let enterAppTimestamp = if fromUser {
+        CPULocal::Myself().EnterAppTimestamp()
+    } else {
+        0
+    };
+
+    if !TscDeadlineExpired(enterAppTimestamp, TSC.Rdtsc()) {
+        if fromUser {
             CPULocal::Myself().SetMode(VcpuMode::User);
-            currTask.mm.HandleTlbShootdown();
-            currTask.RestoreFp();
-            CPULocal::Myself().SetEnterAppTimestamp(TSC.Rdtsc());
-            CPULocal::SetKernelStack(currTask.GetKernelSp());
-            let kernalRsp = ptRegs as *const _ as u64;
-            if !(ptRegs.rip == ptRegs.rcx && ptRegs.r11 == ptRegs.eflags) {
-                IRet(kernalRsp)
-            } else {
-                SyscallRet(kernalRsp)
-            }
         }
+        return;
+    }
+
+    PreemptApp(ptRegs, Task::Current());
+}
+
+#[no_mangle]
+pub extern "C" fn AsyncPfHandler(ptRegs: &mut PtRegs) {
+    CPULocal::Myself().SetMode(VcpuMode::Kernel);
+    AsyncPageReady();
+    ApicEoi();
+    if ptRegs.ss & 0x3 != 0 {
+        CPULocal::Myself().SetMode(VcpuMode::User);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn SpuriousHandler(ptRegs: &mut PtRegs) {
+    // the spurious interrupt of the APIC has no EOI
+    if ptRegs.ss & 0x3 != 0 {
+        CPULocal::Myself().SetMode(VcpuMode::User);
     }
 }
 
@@ -0,0 +1,125 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::qlib::backtracer;
+use super::super::qlib::common::*;
+use super::super::qlib::linux_def::*;
+use super::super::qlib::vcpu_mgr::*;
+use super::super::task::*;
+use super::super::threadmgr::task_exit::*;
+use super::super::MainRun;
+use super::super::SignalDef::*;
+use super::super::SHARESPACE;
+
+// The oops of the kernel. An unrecoverable fault of the kernel, i.e. a fault
+// from the kernel mode or a panic, takes down the whole sandbox. The task
+// exits on top of the abandoned stack of the fault, the locks held there are
+// never released, so only a fault in the user copy of CopyDataIn/CopyDataOut,
+// which holds nothing but the read lock of the mappings, is confined: the
+// oops releases that lock, kills the thread group of the task with a
+// synthetic SIGKILL and lets the task exit, the other processes keep running.
+// The oops is enabled with Config.OopsKill. The other faults, the faults in
+// the exit path or during another oops panic as before.
+
+// the task in the oops, only one oops is handled at a time
+static OOPS_TASK: AtomicU64 = AtomicU64::new(0);
+static OOPS_COUNT: AtomicU64 = AtomicU64::new(0);
+
+// Oops kills the thread group of the current task and never returns, it
+// returns if the fault can't be confined to the task.
+pub fn Oops(reason: &str, ptRegs: Option<&PtRegs>) {
+    if !SHARESPACE.config.read().OopsKill {
+        return;
+    }
+
+    let task = Task::Current();
+    if !task.userCopy {
+        print!("oops: task {:x} may hold locks, {}", task.taskId, reason);
+        return;
+    }
+
+    let thread = match &task.thread {
+        None => return,
+        Some(t) => t.clone(),
+    };
+
+    if OOPS_TASK
+        .compare_exchange(0, task.taskId, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        print!("oops: nested oops in task {:x}, {}", task.taskId, reason);
+        return;
+    }
+
+    // the fault may be in the thread itself, don't wait for its lock
+    let (name, containerID, exiting) = match thread.try_lock() {
+        None => {
+            OOPS_TASK.store(0, Ordering::SeqCst);
+            print!(
+                "oops: thread of task {:x} is locked, {}",
+                task.taskId, reason
+            );
+            return;
+        }
+        Some(t) => (
+            t.name.to_string(),
+            t.containerID.to_string(),
+            t.exitState != TaskExitState::TaskExitNone,
+        ),
+    };
+
+    if exiting {
+        OOPS_TASK.store(0, Ordering::SeqCst);
+        print!(
+            "oops: task {:x} faults in its exit path, {}",
+            task.taskId, reason
+        );
+        return;
+    }
+
+    let tid = thread.ThreadID();
+    let count = OOPS_COUNT.fetch_add(1, Ordering::SeqCst) + 1;
+    print!(
+        "oops #{}: {} on cpu {} in task {:x} tid {} ({}) of container {}",
+        count,
+        reason,
+        CPULocal::CpuId(),
+        task.taskId,
+        tid,
+        name,
+        containerID
+    );
+    if let Some(regs) = ptRegs {
+        print!("oops: registers {:#x?}", regs);
+        backtracer::trace(regs.rip, regs.rsp, regs.rbp, &mut |frame| {
+            print!("oops: frame {:#x?}", frame);
+            true
+        });
+    }
+    print!("oops: killing the thread group of tid {}", tid);
+
+    thread.PrepareGroupExit(ExitStatus::New(0, Signal::SIGKILL));
+    OOPS_TASK.store(0, Ordering::SeqCst);
+
+    // the kernel stack of the fault is abandoned with the guard of the read
+    // lock on it, the task exits on top of it
+    task.userCopy = false;
+    task.mm.mappingLock.ForceDecrRead();
+    CPULocal::Myself().SetMode(VcpuMode::Kernel);
+    MainRun(task, TaskRunState::RunExit);
+    panic!("oops: task {:x} returns from its exit", task.taskId);
+}
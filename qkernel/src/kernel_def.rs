@@ -20,6 +20,8 @@ use core::sync::atomic::Ordering;
 
 use crate::qlib::fileinfo::*;
 
+use super::qlib::kernel::arch::x86_64::mitigations::*;
+use super::qlib::kernel::arch::x86_64::tsc_timer::ArmTscDeadline;
 use super::qlib::kernel::asm::*;
 use super::qlib::kernel::taskMgr::*;
 use super::qlib::kernel::threadmgr::task_sched::*;
@@ -220,6 +222,10 @@ pub fn switch(from: TaskId, to: TaskId) {
     }
     toCtx.SetFS();
 
+    if fromCtx.mm.ID() != toCtx.mm.ID() {
+        AddressSpaceSwitch();
+    }
+
     fromCtx.mm.VcpuLeave();
     toCtx.mm.VcpuEnter();
 
@@ -333,6 +339,7 @@ pub fn child_clone(userSp: u64) {
 
     let kernalRsp = pt as *const _ as u64;
     CPULocal::Myself().SetEnterAppTimestamp(TSC.Rdtsc());
+    ArmTscDeadline();
     currTask.mm.HandleTlbShootdown();
     SyscallRet(kernalRsp)
 }
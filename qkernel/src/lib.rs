@@ -47,6 +47,7 @@ extern crate scopeguard;
 
 //extern crate rusty_asm;
 extern crate bit_field;
+extern crate lz4_flex;
 
 #[macro_use]
 extern crate lazy_static;
@@ -104,7 +105,7 @@ use qlib::mutex::*;
 
 //use linked_list_allocator::LockedHeap;
 //use buddy_system_allocator::LockedHeap;
-use self::qlib::{ShareSpaceRef, SysCallID};
+use self::qlib::{ShareSpace, ShareSpaceRef, SysCallID};
 use taskMgr::{CreateTask, IOWait, WaitFn};
 //use self::qlib::buddyallocator::*;
 use self::asm::*;
@@ -120,9 +121,11 @@ use self::qlib::linux::time::*;
 use self::qlib::linux_def::MemoryDef;
 use self::qlib::loader::*;
 use self::qlib::mem::list_allocator::*;
+use self::qlib::negotiation::*;
 use self::qlib::pagetable::*;
 //use self::qlib::perf_tunning::*;
 use self::qlib::vcpu_mgr::*;
+use self::syscalls::audit::*;
 use self::syscalls::syscalls::*;
 use self::task::*;
 use self::threadmgr::task_sched::*;
@@ -201,7 +204,6 @@ pub fn SingletonInit() {
         kernel::timer::InitSingleton();
         loader::vdso::InitSingleton();
         socket::socket::InitSingleton();
-        syscalls::sys_rlimit::InitSingleton();
         task::InitSingleton();
 
         qlib::InitSingleton();
@@ -248,15 +250,15 @@ pub extern "C" fn syscall_handler(
     pt.r11 = rflags;
     pt.rip = pt.rcx;
 
+    // an unknown syscall returns ENOSYS in SysCall
     let nr = pt.orig_rax;
-    assert!(
-        nr < SysCallID::maxsupport as u64,
-        "get unsupported syscall id {:x}",
-        nr
-    );
 
     //SHARESPACE.SetValue(CPULocal::CpuId(), 0, nr);
-    let callId: SysCallID = unsafe { mem::transmute(nr as u64) };
+    let callId: SysCallID = if nr < SysCallID::maxsupport as u64 {
+        unsafe { mem::transmute(nr as u64) }
+    } else {
+        SysCallID::maxsupport
+    };
 
     //let tid = currTask.Thread().lock().id;
     let mut tid = 0;
@@ -299,11 +301,21 @@ pub extern "C" fn syscall_handler(
     let currTask = task::Task::Current();
     currTask.DoStop();
 
+    let auditClasses = SHARESPACE.config.read().AuditClasses;
+    let audit = if auditClasses != 0 {
+        AuditEnter(currTask, auditClasses, callId, &args)
+    } else {
+        None
+    };
+
     //currTask.PerfGoto(PerfType::SysCall);
     let state = SysCall(currTask, nr, &args);
     //currTask.PerfGofrom(PerfType::SysCall);
 
     res = currTask.Return();
+    if let Some(event) = audit {
+        AuditExit(event, res);
+    }
     //HostInputProcess();
     //ProcessOne();
 
@@ -340,6 +352,7 @@ pub extern "C" fn syscall_handler(
     CPULocal::Myself().SetMode(VcpuMode::User);
     currTask.mm.HandleTlbShootdown();
     CPULocal::Myself().SetEnterAppTimestamp(TSC.Rdtsc());
+    arch::x86_64::tsc_timer::ArmTscDeadline();
     if !(pt.rip == pt.rcx && pt.r11 == pt.eflags) {
         //error!("iret *****, pt is {:x?}", pt);
         IRet(kernalRsp)
@@ -448,6 +461,69 @@ pub fn InitTsc() {
     VcpuFreqInit();
 }
 
+// InitVcpuTimer sets up the TSC deadline timer and the pvclock of the vcpu.
+pub fn InitVcpuTimer(boot: bool) {
+    if !arch::x86_64::tsc_timer::InitTscDeadlineTimer(SHARESPACE.config.read().TscDeadlineTimer) {
+        return;
+    }
+
+    arch::x86_64::tsc_timer::InitPvClock(boot, &|| {
+        Kernel::HostSpace::KernelGetTime(kernel::timer::MONOTONIC).unwrap_or(0)
+    });
+}
+
+// NegotiateFeatures does the boot time handshake with qvisor. It runs before the heap and
+// SHARESPACE are initialized, so it can only use the stack and the early console.
+// The config in SHARESPACE can't be read before the layout is agreed, qkernel offers
+// the features it implements and qvisor agrees to the ones its config enables.
+pub fn NegotiateFeatures() -> u64 {
+    let negotiation = FeatureNegotiation::New(mem::size_of::<ShareSpace>() as u64, Feature::ALL);
+    HyperCall64(
+        qlib::HYPERCALL_NEGOTIATE,
+        &negotiation as *const _ as u64,
+        0,
+        0,
+    );
+
+    let negotiation = unsafe { ptr::read_volatile(&negotiation as *const FeatureNegotiation) };
+    if negotiation.status != NegotiationStatus::Ok {
+        early_print!(
+            "qkernel: feature negotiation fail {:?}, qkernel sharespace version {} qcall version {}",
+            negotiation.status,
+            SHARESPACE_VERSION,
+            QCALL_VERSION
+        );
+        Kernel::HostSpace::Panic("qkernel and qvisor version mismatch");
+        loop {}
+    }
+
+    early_print!(
+        "qkernel: features guest {:x} host {:x} agreed {:x}",
+        negotiation.guestFeatures,
+        negotiation.hostFeatures,
+        negotiation.agreedFeatures
+    );
+    return negotiation.agreedFeatures;
+}
+
+// ApplyNegotiatedFeatures disables the optional subsystems which qvisor doesn't support.
+pub fn ApplyNegotiatedFeatures(features: u64) {
+    SetNegotiatedFeatures(features);
+
+    let mut config = SHARESPACE.config.write();
+    if !FeatureEnabled(Feature::RDMA) {
+        config.EnableRDMA = false;
+    }
+
+    if !FeatureEnabled(Feature::URING_IO) {
+        config.UringIO = false;
+    }
+
+    if !FeatureEnabled(Feature::INOTIFY) {
+        config.EnableInotify = false;
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn rust_main(
     heapStart: u64,
@@ -459,12 +535,25 @@ pub extern "C" fn rust_main(
 ) {
     self::qlib::kernel::asm::fninit();
     if id == 0 {
+        early_print!(
+            "qkernel: boot vcpu#0 heap {:x} sharespace {:x} vcpu count {}",
+            heapStart,
+            shareSpaceAddr,
+            vcpuCnt
+        );
+        let features = NegotiateFeatures();
         GLOBAL_ALLOCATOR.Init(heapStart);
         SHARESPACE.SetValue(shareSpaceAddr);
+        ApplyNegotiatedFeatures(features);
+        memmgr::mem_high::SetMemoryHigh(SHARESPACE.config.read().MemoryHigh);
         SingletonInit();
+        kernel::entropy::InitEntropy();
 
         VCPU_ALLOCATOR.Initializated();
         InitTsc();
+        InitVcpuTimer(true);
+        arch::x86_64::async_pf::InitAsyncPageFault(SHARESPACE.config.read().AsyncPageFault);
+        arch::x86_64::steal_time::InitStealTime(SHARESPACE.config.read().StealTime);
         InitTimeKeeper(vdsoParamAddr);
 
         {
@@ -478,16 +567,21 @@ pub extern "C" fn rust_main(
         SetVCPCount(vcpuCnt as usize);
         VDSO.Initialization(vdsoParamAddr);
 
+        early_print!("qkernel: kernel initialized, release other vcpus");
         // release other vcpus
         HyperCall64(qlib::HYPERCALL_RELEASE_VCPU, 0, 0, 0);
     } else {
         InitGs(id);
+        InitVcpuTimer(false);
+        arch::x86_64::async_pf::InitAsyncPageFault(SHARESPACE.config.read().AsyncPageFault);
+        arch::x86_64::steal_time::InitStealTime(SHARESPACE.config.read().StealTime);
         //PerfGoto(PerfType::Kernel);
     }
 
     SHARESPACE.IncrVcpuSearching();
     taskMgr::AddNewCpu();
     RegisterSysCall(syscall_entry as u64);
+    SetTscAux(id);
 
     //interrupts::init_idt();
     interrupt::init();
@@ -583,7 +677,16 @@ fn StartRootContainer(_para: *const u8) {
     };
 
     let (_tid, entry, userStackAddr, kernelStackAddr) = {
-        let mut processArgs = LOADER.Lock(task).unwrap().Init(process);
+        let mut processArgs = match LOADER.Lock(task).unwrap().Init(process) {
+            Ok(args) => args,
+            Err(e) => {
+                error!(
+                    "StartRootContainer: failed to init the root container, cause: {:?}",
+                    e
+                );
+                panic!("failed to init the root container");
+            }
+        };
         LOADER.LoadRootProcess(&mut processArgs).unwrap()
     };
 
@@ -607,6 +710,18 @@ fn panic(info: &PanicInfo) -> ! {
         true
     });*/
 
+    // the print! path needs heap and SHARESPACE, which might be the reason of the panic,
+    // so log it through the early console first.
+    match info.location() {
+        Some(location) => early_print!(
+            "qkernel panic: {:?} at {}:{}",
+            info.message(),
+            location.file(),
+            location.line()
+        ),
+        None => early_print!("qkernel panic: {:?}", info.message()),
+    }
+
     print!("get panic : {:?}", info.message());
     if let Some(location) = info.location() {
         print!(
@@ -618,6 +733,8 @@ fn panic(info: &PanicInfo) -> ! {
         print!("panic occurred but can't get location information...");
     }
 
+    interrupt::oops::Oops("panic", None);
+
     /*for i in 0..CPU_LOCAL.len() {
         error!("CPU  #{} is {:#x?}", i, CPU_LOCAL[i]);
     }*/
@@ -12,10 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::qlib::kernel::asm::*;
 use super::qlib::kernel::Timestamp;
 use super::qlib::vcpu_mgr::*;
+use super::qlib::HYPERCALL_CONSOLE;
 use super::task::*;
 use alloc::string::String;
+use core::fmt;
 
 pub fn PrintPrefix() -> String {
     let now = if super::SHARESPACE.config.read().PerfDebug {
@@ -32,6 +35,70 @@ pub fn PrintPrefix() -> String {
     );
 }
 
+pub const EARLY_CONSOLE_BUF_SIZE: usize = 256;
+
+// EarlyConsole is a serial like console which writes to qvisor through HYPERCALL_CONSOLE
+// directly. It only uses the stack buffer, doesn't depend on the heap, SHARESPACE or any
+// lock, so it can be used before the qmsg channel is up and in the panic/exception path.
+pub struct EarlyConsole {
+    buf: [u8; EARLY_CONSOLE_BUF_SIZE],
+    len: usize,
+}
+
+impl EarlyConsole {
+    pub const fn New() -> Self {
+        return Self {
+            buf: [0; EARLY_CONSOLE_BUF_SIZE],
+            len: 0,
+        };
+    }
+
+    pub fn Flush(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+
+        HyperCall64(
+            HYPERCALL_CONSOLE,
+            &self.buf[0] as *const _ as u64,
+            self.len as u64,
+            0,
+        );
+        self.len = 0;
+    }
+}
+
+impl fmt::Write for EarlyConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &b in s.as_bytes() {
+            if self.len == self.buf.len() {
+                self.Flush();
+            }
+
+            self.buf[self.len] = b;
+            self.len += 1;
+        }
+
+        return Ok(());
+    }
+}
+
+impl Drop for EarlyConsole {
+    fn drop(&mut self) {
+        self.Flush();
+    }
+}
+
+#[macro_export]
+macro_rules! early_print {
+    ($($arg:tt)*) => ({
+        use core::fmt::Write;
+        let mut console = $crate::print::EarlyConsole::New();
+        let _ = write!(console, $($arg)*);
+        let _ = console.write_str("\n");
+    });
+}
+
 #[macro_export]
 macro_rules! raw {
     // macth like arm for macro
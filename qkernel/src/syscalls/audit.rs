@@ -0,0 +1,136 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+
+use super::super::kernel::timer::*;
+use super::super::qlib::audit::*;
+use super::super::qlib::kernel::boot::process::SockAddrString;
+use super::super::qlib::linux_def::*;
+use super::super::qlib::SysCallID;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+use super::super::SHARESPACE;
+use super::sys_file::*;
+use super::sys_socket::*;
+
+// The audit of the syscalls of the classes of AuditClasses, see qlib/audit.rs.
+// The path and the address arguments are read at the entry of the syscall as
+// an execve replaces the memory they are in, the event is queued with the
+// result at the return.
+
+enum AuditArg {
+    None,
+    Path(u64),
+    // an open with its flags, audited only for a write or a create
+    Open(u64, i32),
+    // a socket address and its length
+    Addr(u64, u64),
+}
+
+fn AuditClass(callId: SysCallID, args: &SyscallArguments) -> Option<(u64, AuditArg)> {
+    use self::SysCallID::*;
+
+    let ret = match callId {
+        sys_execve => (AUDIT_EXEC, AuditArg::Path(args.arg0)),
+        sys_stub_execveat => (AUDIT_EXEC, AuditArg::Path(args.arg1)),
+
+        sys_open => (AUDIT_FILE, AuditArg::Open(args.arg0, args.arg1 as i32)),
+        sys_openat => (AUDIT_FILE, AuditArg::Open(args.arg1, args.arg2 as i32)),
+        sys_creat | sys_unlink | sys_rmdir | sys_chmod | sys_chown | sys_lchown | sys_truncate
+        | sys_rename | sys_link => (AUDIT_FILE, AuditArg::Path(args.arg0)),
+        sys_unlinkat | sys_fchmodat | sys_fchownat | sys_renameat | sys_renameat2 | sys_linkat => {
+            (AUDIT_FILE, AuditArg::Path(args.arg1))
+        }
+        sys_symlink => (AUDIT_FILE, AuditArg::Path(args.arg1)),
+        sys_symlinkat => (AUDIT_FILE, AuditArg::Path(args.arg2)),
+
+        sys_connect | sys_bind => (AUDIT_NET, AuditArg::Addr(args.arg1, args.arg2)),
+        sys_accept | sys_accept4 | sys_listen => (AUDIT_NET, AuditArg::None),
+
+        sys_setuid | sys_setgid | sys_setreuid | sys_setregid | sys_setresuid | sys_setresgid
+        | sys_setfsuid | sys_setfsgid | sys_setgroups | sys_capset | sys_unshare | sys_setns => {
+            (AUDIT_CRED, AuditArg::None)
+        }
+
+        sys_mount => (AUDIT_MOUNT, AuditArg::Path(args.arg1)),
+        sys_umount2 | sys_pivot_root | sys_chroot => (AUDIT_MOUNT, AuditArg::Path(args.arg0)),
+
+        sys_ptrace | sys_process_vm_readv | sys_process_vm_writev => (AUDIT_PTRACE, AuditArg::None),
+        _ => return None,
+    };
+
+    return Some(ret);
+}
+
+// AuditEnter returns the event of the syscall if it is audited, without its
+// result.
+pub fn AuditEnter(
+    task: &Task,
+    classes: u64,
+    callId: SysCallID,
+    args: &SyscallArguments,
+) -> Option<AuditEvent> {
+    let (class, arg) = AuditClass(callId, args)?;
+    if class & classes == 0 {
+        return None;
+    }
+
+    let arg = match arg {
+        AuditArg::None => None,
+        AuditArg::Path(addr) => copyInPath(task, addr, true).ok().map(|(p, _)| p),
+        AuditArg::Open(addr, flags) => {
+            if flags & (Flags::O_WRONLY | Flags::O_RDWR | Flags::O_CREAT | Flags::O_TRUNC) == 0 {
+                return None;
+            }
+
+            copyInPath(task, addr, true).ok().map(|(p, _)| p)
+        }
+        AuditArg::Addr(addr, len) => match CaptureAddress(task, addr, len as u32) {
+            Err(_) => None,
+            Ok(addr) => {
+                let (family, addr) = SockAddrString(&addr);
+                Some(format!("{} {}", family, addr))
+            }
+        },
+    };
+
+    let name = format!("{:?}", callId);
+    let syscall = match callId {
+        SysCallID::sys_stub_execveat => "execveat",
+        _ => name.trim_start_matches("sys_"),
+    };
+
+    let thread = task.Thread();
+    return Some(AuditEvent {
+        time: 0,
+        container: thread.ContainerID(),
+        pid: thread.ThreadGroup().ID(),
+        tid: thread.lock().id,
+        uid: task.creds.lock().EffectiveKUID.0,
+        comm: thread.Name(),
+        syscall: String::from(syscall),
+        args: [
+            args.arg0, args.arg1, args.arg2, args.arg3, args.arg4, args.arg5,
+        ],
+        arg: arg,
+        ret: 0,
+    });
+}
+
+pub fn AuditExit(mut event: AuditEvent, res: u64) {
+    event.time = RealNow();
+    event.ret = res as i64;
+    SHARESPACE.audit.Push(event);
+}
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod audit;
 pub mod sys_aio;
 pub mod sys_capability;
 pub mod sys_chmod;
@@ -24,6 +25,7 @@ pub mod sys_identity;
 pub mod sys_membarrier;
 pub mod sys_mempolicy;
 pub mod sys_mmap;
+pub mod sys_mount;
 pub mod sys_pipe;
 pub mod sys_poll;
 pub mod sys_prctl;
@@ -70,7 +70,7 @@ pub fn SysIoGetevents(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let id = args.arg0 as u64;
     let minEvents = args.arg1 as i32;
     let events = args.arg2 as i32 as usize;
-    let mut eventsAddr = args.arg3 as u64;
+    let eventsAddr = args.arg3 as u64;
     let timespecAddr = args.arg4 as u64;
 
     // Sanity check arguments.
@@ -111,7 +111,7 @@ pub fn SysIoGetevents(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             }
         }
 
-        match task.CopyOutObj(&event, eventsAddr) {
+        match task.CopyOutObjAt(&event, eventsAddr, count) {
             Err(e) => {
                 if count > 0 {
                     return Ok(count as i64);
@@ -121,9 +121,6 @@ pub fn SysIoGetevents(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             }
             Ok(()) => (),
         };
-
-        //*eventPtr = event;
-        eventsAddr += IOEVENT_SIZE;
     }
 
     return Ok(events as i64);
@@ -160,7 +157,7 @@ pub fn WaitForRequest(ctx: &AIOContext, task: &Task, dealine: Option<Time>) -> R
 pub fn SysIOSubmit(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let id = args.arg0 as u64;
     let nrEvents = args.arg1 as i32;
-    let mut addr = args.arg2 as u64;
+    let addr = args.arg2 as u64;
 
     // Sanity check arguments.
     if nrEvents < 0 {
@@ -168,7 +165,7 @@ pub fn SysIOSubmit(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     }
 
     for i in 0..nrEvents as usize {
-        let cbAddr: u64 = match task.CopyInObj(addr) {
+        let cbAddr: u64 = match task.CopyInObjAt(addr, i) {
             Err(e) => {
                 if i > 0 {
                     // Some successful.
@@ -203,8 +200,6 @@ pub fn SysIOSubmit(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             }
             Ok(()) => (),
         }
-
-        addr += 8;
     }
 
     return Ok(nrEvents as i64);
@@ -255,7 +250,7 @@ pub fn PerformFileOp(
 ) -> Result<i64> {
     let ret = match cb.opcode {
         IOCB_CMD_PREAD => {
-            let iov = IoVec::NewFromAddr(cb.buf, cb.bytes as usize);
+            let iov = task.UserIoVec(cb.buf, cb.bytes as usize)?;
             let mut iovs = vec![iov];
             file.Preadv(task, &mut iovs, cb.offset)
         }
@@ -264,7 +259,7 @@ pub fn PerformFileOp(
             file.Preadv(task, &mut iovs, cb.offset)
         }
         IOCB_CMD_PWRITE => {
-            let iov = IoVec::NewFromAddr(cb.buf, cb.bytes as usize);
+            let iov = task.UserIoVec(cb.buf, cb.bytes as usize)?;
             let iovs: [IoVec; 1] = [iov];
             file.Pwritev(task, &iovs, cb.offset)
         }
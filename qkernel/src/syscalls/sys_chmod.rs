@@ -29,8 +29,17 @@ fn Chmod(task: &Task, d: &Dirent, mode: FileMode) -> Result<()> {
         return Err(Error::SysError(SysErr::EPERM));
     }
 
-    let mode = FileMode(mode.0 & (!task.Umask() as u16));
-    let p = FilePermissions::FromMode(mode);
+    // umask doesn't apply to chmod. Same as Linux, the setgid bit is cleared when the
+    // caller is not in the file's group and doesn't have CAP_FSETID.
+    let mut p = FilePermissions::FromMode(mode);
+    if p.SetGid {
+        let uattr = inode.UnstableAttr(task)?;
+        let creds = task.creds.clone();
+        let inGroup = creds.lock().InGroup(uattr.Owner.GID);
+        if !inGroup && !inode.CheckCapability(task, Capability::CAP_FSETID) {
+            p.SetGid = false;
+        }
+    }
     if !inode.SetPermissions(task, d, p) {
         return Err(Error::SysError(SysErr::EPERM));
     }
@@ -44,7 +53,6 @@ fn Chmod(task: &Task, d: &Dirent, mode: FileMode) -> Result<()> {
 fn ChmodAt(task: &Task, fd: i32, addr: u64, mode: FileMode) -> Result<()> {
     let (path, _) = copyInPath(task, addr, false)?;
 
-    let mode = FileMode(mode.0 & !(task.Umask() as u16));
     return fileOpOn(task, fd, &path, true, &mut |_root: &Dirent,
                                                  d: &Dirent,
                                                  _remainingTraversals: u32|
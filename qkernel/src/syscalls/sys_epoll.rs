@@ -20,7 +20,6 @@ use super::super::kernel::fd_table::*;
 use super::super::kernel::time::*;
 use super::super::kernel::timer::*;
 use super::super::kernel::waiter::*;
-use super::super::qlib::addr::*;
 use super::super::qlib::common::*;
 use super::super::qlib::linux::time::*;
 use super::super::qlib::linux_def::*;
@@ -297,22 +296,10 @@ pub fn SysEpollCtl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     }
 }
 
-// copyOutEvents copies epoll events from the kernel to user memory.
-pub fn CopyOutEvents(task: &Task, addr: u64, e: &[Event]) -> Result<()> {
-    let itemLen: usize = 12;
-
-    Addr(addr).AddLen((itemLen * e.len()) as u64)?;
-
-    //error!("epool CopyOutEvents events is {:x?}", e);
-    for i in 0..e.len() {
-        /*let output : &mut Event = task.GetTypeMut(addr + (i * itemLen) as u64)?;
-        output.Events = e[i].Events;
-        output.Data[0] = e[i].Data[0];
-        output.Data[1] = e[i].Data[1];*/
-        task.CopyOutObj(&e[i], addr + (i * itemLen) as u64)?;
-    }
-
-    return Ok(());
+// copyOutEvents copies epoll events from the kernel to user memory and returns
+// the count copied.
+pub fn CopyOutEvents(task: &Task, addr: u64, e: &[Event]) -> Result<usize> {
+    return task.CopyOutObjs(e, addr);
 }
 
 // EpollWait implements the epoll_wait(2) linux syscall.
@@ -329,10 +316,11 @@ pub fn SysEpollWait(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     };
 
     if r.len() != 0 {
-        CopyOutEvents(task, eventAddr, &r)?;
+        let n = CopyOutEvents(task, eventAddr, &r)?;
+        return Ok(n as i64);
     }
 
-    return Ok(r.len() as i64);
+    return Ok(0);
 }
 
 // EpollPwait implements the epoll_pwait(2) linux syscall.
@@ -384,8 +372,9 @@ pub fn SysPwait2(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     };
 
     if r.len() != 0 {
-        CopyOutEvents(task, eventAddr, &r)?;
+        let n = CopyOutEvents(task, eventAddr, &r)?;
+        return Ok(n as i64);
     }
 
-    return Ok(r.len() as i64);
+    return Ok(0);
 }
\ No newline at end of file
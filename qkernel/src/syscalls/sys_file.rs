@@ -25,6 +25,7 @@ use super::super::fs::lock::*;
 use super::super::kernel::fasync::*;
 use super::super::kernel::fd_table::*;
 use super::super::kernel::pipe::reader::*;
+use super::super::kernel::seccomp::*;
 use super::super::kernel::pipe::reader_writer::*;
 use super::super::kernel::pipe::writer::*;
 use super::super::kernel::time::*;
@@ -655,6 +656,11 @@ pub fn SysIoctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let request = args.arg1 as u64;
     let val = args.arg2 as u64;
 
+    // the only ioctl returning a value other than 0
+    if request == SECCOMP_IOCTL_NOTIF_ADDFD {
+        return SeccompAddFd(task, fd, val);
+    }
+
     Ioctl(task, fd, request, val)?;
     return Ok(0);
 }
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use alloc::vec::Vec;
+use core::slice;
 
 use super::super::fd::*;
 use super::super::fs::inotify::*;
@@ -82,6 +83,8 @@ fn getDents(
 
     let len = size; // writer.Len() as i32;
     let mut ds = HostDirentSerializer::New(f, &mut writer, WIDTH, len);
+    // d_off is the offset of the next entry, which could be passed to lseek
+    ds.offset = dir.Offset(task)? as u64;
     let err = dir.ReadDir(task, &mut ds);
     match err {
         Ok(()) => {
@@ -155,25 +158,25 @@ impl Dirent {
     }
 }
 
-fn Serialize64(task: &Task, dir: &Dirent, w: &mut IOWriter) -> Result<i32> {
+fn Serialize64(_task: &Task, dir: &Dirent, w: &mut IOWriter) -> Result<i32> {
     let addr = &dir.Hdr as *const _ as u64;
     let size = 18; //mem::size_of::<DirentHdr>();
                    //let slice = task.GetSlice::<u8>(addr, size)?;
-    let buf = task.CopyInVec::<u8>(addr, size)?;
+    let buf = unsafe { slice::from_raw_parts(addr as *const u8, size) };
 
-    let n1 = w.Write(&buf)?;
+    let n1 = w.Write(buf)?;
     let n3 = w.Write(&[dir.Hdr.Type; 1])?;
     let n2 = w.Write(&dir.Name)?;
     return Ok((n1 + n2 + n3) as i32);
 }
 
-fn Serialize(task: &Task, dir: &Dirent, w: &mut IOWriter) -> Result<i32> {
+fn Serialize(_task: &Task, dir: &Dirent, w: &mut IOWriter) -> Result<i32> {
     let addr = &dir.Hdr as *const _ as u64;
     let size = 18; //mem::size_of::<OldDirentHdr>();
                    //let slice = task.GetSlice::<u8>(addr, size)?;
-    let buf = task.CopyInVec::<u8>(addr, size)?;
+    let buf = unsafe { slice::from_raw_parts(addr as *const u8, size) };
 
-    let n1 = w.Write(&buf)?;
+    let n1 = w.Write(buf)?;
     let n2 = w.Write(&dir.Name)?;
     let n3 = w.Write(&[dir.Hdr.Type; 1])?;
     return Ok((n1 + n2 + n3) as i32);
@@ -79,13 +79,9 @@ pub fn CopyOutNodemask(task: &Task, addr: u64, maxnode: u32, val: u64) -> Result
 
     // Zero out remaining unsigned longs in the nodemask.
     if bits > 64 {
-        let mut remAddr = addr + 8;
-
         let remU64 = (bits - 65) / 64;
-        for _i in 0..remU64 as usize {
-            //*task.GetTypeMut(remAddr)? = 0;
-            task.CopyOutObj(&(0 as u64), remAddr)?;
-            remAddr += 8;
+        for i in 0..remU64 as usize {
+            task.CopyOutObjAt(&(0 as u64), addr, i + 1)?;
         }
     }
 
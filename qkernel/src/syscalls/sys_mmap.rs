@@ -167,7 +167,9 @@ pub fn SysMadvise(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     };
 
     match adv {
-        MAdviseOp::MADV_DONTNEED => {
+        // the lazy free is laundered right away: the pages go back to the page
+        // pool and count no more against memory.high
+        MAdviseOp::MADV_DONTNEED | MAdviseOp::MADV_FREE => {
             task.mm.MDontneed(task, addr, length, adv)?;
         }
         MAdviseOp::MADV_HUGEPAGE | MAdviseOp::MADV_NOHUGEPAGE => {
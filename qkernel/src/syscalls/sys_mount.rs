@@ -0,0 +1,428 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::fs::dirent::*;
+use super::super::fs::filesystems::*;
+use super::super::fs::host::fs::FILESYSTEM_NAME as HOST_FILESYSTEM_NAME;
+use super::super::fs::inode::*;
+use super::super::fs::mount::*;
+use super::super::fs::overlay::*;
+use super::super::kernel::kernel::GetKernel;
+use super::super::qlib::common::*;
+use super::super::qlib::linux_def::*;
+use super::super::qlib::mutex::*;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+use super::sys_file::*;
+
+// Mount implements linux syscall mount(2).
+pub fn SysMount(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let sourceAddr = args.arg0 as u64;
+    let targetAddr = args.arg1 as u64;
+    let typeAddr = args.arg2 as u64;
+    let mut flags = args.arg3 as u64;
+    let dataAddr = args.arg4 as u64;
+
+    // Ignore magic value that was required before Linux 2.4.
+    if flags & LibcConst::MS_MGC_MSK == LibcConst::MS_MGC_VAL {
+        flags &= !LibcConst::MS_MGC_MSK;
+    }
+
+    // Must have CAP_SYS_ADMIN in the mount namespace's associated user
+    // namespace.
+    let userns = task.mountNS.UserNamespace();
+    if !task.Thread().HasCapabilityIn(Capability::CAP_SYS_ADMIN, &userns) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let (sourcePath, _) = copyInPath(task, sourceAddr, true)?;
+    let (targetPath, _) = copyInPath(task, targetAddr, false)?;
+
+    // The mount namespaces don't propagate their mounts, so all the mounts
+    // are already private. Container runtimes make their mounts
+    // private or slave before building the rootfs; accept it as a no-op.
+    const PROPAGATION: u64 = LibcConst::MS_SHARED
+        | LibcConst::MS_PRIVATE
+        | LibcConst::MS_SLAVE
+        | LibcConst::MS_UNBINDABLE;
+    if flags & PROPAGATION != 0 {
+        return Ok(0);
+    }
+
+    // A remount changes the options of the mount source, which all the mounts
+    // of a filesystem share as the superblock of Linux. A bind mount shares the
+    // mount source of its source, a remount with MS_BIND gives it a mount
+    // source of its own with the options.
+    if flags & LibcConst::MS_REMOUNT != 0 {
+        let readOnly = flags & LibcConst::MS_RDONLY != 0;
+        let noExec = flags & LibcConst::MS_NOEXEC != 0;
+        let noAtime = flags & LibcConst::MS_NOATIME != 0;
+        fileOpOn(
+            task,
+            ATType::AT_FDCWD,
+            &targetPath,
+            true,
+            &mut |_root: &Dirent, d: &Dirent, _remainingTraversals: u32| -> Result<()> {
+                let m = match task.mountNS.FindMount(d) {
+                    Some(m) if m.lock().Root() == *d => m,
+                    _ => return Err(Error::SysError(SysErr::EINVAL)),
+                };
+
+                let msrc = d.Inode().lock().MountSource.clone();
+                if flags & LibcConst::MS_BIND != 0 {
+                    let superFlags = {
+                        let msrc = msrc.lock();
+                        if msrc.Flags.ReadOnly == readOnly
+                            && msrc.Flags.NoExec == noExec
+                            && msrc.Flags.NoAtime == noAtime
+                        {
+                            return Ok(());
+                        }
+
+                        MountSourceFlags {
+                            ReadOnly: readOnly,
+                            NoExec: noExec,
+                            NoAtime: noAtime,
+                            ..msrc.Flags
+                        }
+                    };
+                    return RemountBind(task, d, &m, &superFlags);
+                }
+
+                let mut msrc = msrc.lock();
+                msrc.Flags.ReadOnly = readOnly;
+                msrc.Flags.NoExec = noExec;
+                msrc.Flags.NoAtime = noAtime;
+                return Ok(());
+            },
+        )?;
+        return Ok(0);
+    }
+
+    if flags & LibcConst::MS_MOVE != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let superFlags = MountSourceFlags {
+        ReadOnly: flags & LibcConst::MS_RDONLY != 0,
+        NoAtime: flags & LibcConst::MS_NOATIME != 0,
+        NoExec: flags & LibcConst::MS_NOEXEC != 0,
+        ..Default::default()
+    };
+
+    let rootInode = if flags & LibcConst::MS_BIND != 0 {
+        BindSource(task, &sourcePath)?
+    } else {
+        let (fsType, err) = task.CopyInString(typeAddr, MemoryDef::PAGE_SIZE as usize);
+        err?;
+
+        let data = if dataAddr != 0 {
+            // In Linux, a full page is always copied in regardless of null
+            // character placement, and the address is passed to each file system.
+            // Most file systems always treat this data as a string, though, and so
+            // do all of the ones we implement.
+            let (data, err) = task.CopyInString(dataAddr, MemoryDef::PAGE_SIZE as usize);
+            err?;
+            data
+        } else {
+            "".to_string()
+        };
+
+        if fsType.as_str() == "overlay" {
+            OverlaySource(task, &data, &superFlags)?
+        } else {
+            let rsys = FindFilesystemForMount(&fsType)?;
+
+            if !rsys.lock().AllowUserMount() {
+                return Err(Error::SysError(SysErr::EPERM));
+            }
+
+            match rsys.lock().Mount(task, &sourcePath, &superFlags, &data) {
+                Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+                Ok(inode) => inode,
+            }
+        }
+    };
+
+    fileOpOn(
+        task,
+        ATType::AT_FDCWD,
+        &targetPath,
+        true,
+        &mut |_root: &Dirent, d: &Dirent, _remainingTraversals: u32| -> Result<()> {
+            // the root of the mount namespace has no mount point
+            if d.Parent().is_none() {
+                return Err(Error::SysError(SysErr::EBUSY));
+            }
+
+            let isDir = d.Inode().StableAttr().IsDir();
+            if isDir != rootInode.StableAttr().IsDir() {
+                if isDir {
+                    return Err(Error::SysError(SysErr::EISDIR));
+                }
+                return Err(Error::SysError(SysErr::ENOTDIR));
+            }
+
+            return task.mountNS.Mount(d, &rootInode);
+        },
+    )?;
+
+    return Ok(0);
+}
+
+// BindSource returns the inode that a MS_BIND mount of path attaches. The bind
+// mount shares the inode, so the changes are visible from both places.
+fn BindSource(task: &Task, path: &str) -> Result<Inode> {
+    if path.len() == 0 {
+        return Err(Error::SysError(SysErr::ENOENT));
+    }
+
+    let mut inode = None;
+    fileOpOn(
+        task,
+        ATType::AT_FDCWD,
+        path,
+        true,
+        &mut |_root: &Dirent, d: &Dirent, _remainingTraversals: u32| -> Result<()> {
+            inode = Some(d.Inode());
+            return Ok(());
+        },
+    )?;
+
+    return Ok(inode.unwrap());
+}
+
+// RemountBind gives the bind mount at d the options flags. The root of the
+// mount gets a mount source of its own with the options, the files looked up
+// under it inherit the mount source of their parent. A filesystem which keeps
+// its inodes in memory, e.g. tmpfs, hands out the ones of its own mount
+// source, so only the bind mounts of the host and the overlay filesystems get
+// options of their own.
+fn RemountBind(
+    task: &Task,
+    d: &Dirent,
+    m: &Arc<QMutex<Mount>>,
+    flags: &MountSourceFlags,
+) -> Result<()> {
+    let inode = d.Inode();
+    let msrc = inode.lock().MountSource.clone();
+    let fsType = msrc.lock().FileSystemType.clone();
+    if fsType.as_str() != HOST_FILESYSTEM_NAME && fsType.as_str() != "overlayfs" {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    // the mounts under it would be detached with it
+    if task.mountNS.AllMountsUnder(m).len() > 1 {
+        return Err(Error::SysError(SysErr::EBUSY));
+    }
+
+    let bindMsrc = Arc::new(QMutex::new(msrc.lock().Fork(flags)));
+    return task.mountNS.Remount(d, &inode.WithMountSource(&bindMsrc));
+}
+
+// OverlaySource builds the overlay root from the "lowerdir=,upperdir=,workdir="
+// options. Several lower layers are stacked from the bottom up, each of them
+// is read only.
+fn OverlaySource(task: &Task, data: &str, flags: &MountSourceFlags) -> Result<Inode> {
+    let mut lowerdirs: Vec<String> = Vec::new();
+    let mut upperdir = None;
+
+    for opt in data.split(',') {
+        let kv: Vec<&str> = opt.splitn(2, '=').collect();
+        match kv[0] {
+            "lowerdir" if kv.len() == 2 => {
+                lowerdirs = kv[1].split(':').map(|s| s.to_string()).collect();
+            }
+            "upperdir" if kv.len() == 2 => upperdir = Some(kv[1].to_string()),
+            // the copy up is done in the upper layer directly
+            "workdir" => (),
+            "" => (),
+            _ => info!("overlay: ignoring unknown mount option {}", opt),
+        }
+    }
+
+    if lowerdirs.len() == 0 || lowerdirs.iter().any(|l| l.len() == 0) {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let mut layers = Vec::with_capacity(lowerdirs.len());
+    for l in &lowerdirs {
+        layers.push(BindSource(task, l)?);
+    }
+
+    // lowerdir is ordered from the top layer to the bottom one
+    let mut lower = layers.pop().unwrap();
+    let readOnly = MountSourceFlags {
+        ReadOnly: true,
+        ..*flags
+    };
+    while let Some(layer) = layers.pop() {
+        lower = NewOverlayRoot(task, &layer, &lower, &readOnly)
+            .map_err(|_| Error::SysError(SysErr::EINVAL))?;
+    }
+
+    let upper = match upperdir {
+        // an overlay without upperdir is read only
+        None => return Ok(lower),
+        Some(u) => BindSource(task, &u)?,
+    };
+
+    return NewOverlayRoot(task, &upper, &lower, flags).map_err(|_| Error::SysError(SysErr::EINVAL));
+}
+
+// Umount2 implements linux syscall umount2(2).
+pub fn SysUmount2(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let addr = args.arg0 as u64;
+    let flags = args.arg1 as u64;
+
+    const UNSUPPORTED: u64 = LibcConst::MNT_FORCE | LibcConst::MNT_EXPIRE;
+    if flags & UNSUPPORTED != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let (path, _) = copyInPath(task, addr, false)?;
+
+    let userns = task.mountNS.UserNamespace();
+    if !task.Thread().HasCapabilityIn(Capability::CAP_SYS_ADMIN, &userns) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let resolve = flags & LibcConst::UMOUNT_NOFOLLOW == 0;
+    let detachOnly = flags & LibcConst::MNT_DETACH != 0;
+
+    fileOpOn(
+        task,
+        ATType::AT_FDCWD,
+        &path,
+        resolve,
+        &mut |_root: &Dirent, d: &Dirent, _remainingTraversals: u32| -> Result<()> {
+            // the initial mount can't be unmounted
+            if d.Parent().is_none() {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            // the old root stacked by pivot_root(".", ".") is not mounted on
+            // the new root, the lazy unmount of "." detaches the mounts of the
+            // old root left out of reach
+            if *d == task.Root() {
+                if detachOnly {
+                    task.mountNS.DetachOutside(&task.mountNS.Root());
+                    return Ok(());
+                }
+                return Err(Error::SysError(SysErr::EBUSY));
+            }
+
+            task.mountNS.Unmount(d, detachOnly)?;
+            if detachOnly {
+                // the mounts of an old root detached from put_old go with it
+                task.mountNS.DetachOutside(&task.mountNS.Root());
+            }
+            return Ok(());
+        },
+    )?;
+
+    return Ok(0);
+}
+
+// PivotRoot implements linux syscall pivot_root(2).
+//
+// The root of the mount namespace of the task is moved to putOld and newRoot becomes the root of the
+// namespace. As in Linux, the tasks whose root or working directory is the
+// old root move to newRoot.
+pub fn SysPivotRoot(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let newRootAddr = args.arg0 as u64;
+    let putOldAddr = args.arg1 as u64;
+
+    let userns = task.mountNS.UserNamespace();
+    if !task.Thread().HasCapabilityIn(Capability::CAP_SYS_ADMIN, &userns) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let (newRootPath, _) = copyInPath(task, newRootAddr, false)?;
+    let (putOldPath, _) = copyInPath(task, putOldAddr, false)?;
+
+    let lookup = |path: &str| -> Result<Dirent> {
+        let mut dirent = None;
+        fileOpOn(
+            task,
+            ATType::AT_FDCWD,
+            path,
+            true,
+            &mut |_root: &Dirent, d: &Dirent, _remainingTraversals: u32| -> Result<()> {
+                if !d.Inode().StableAttr().IsDir() {
+                    return Err(Error::SysError(SysErr::ENOTDIR));
+                }
+
+                dirent = Some(d.clone());
+                return Ok(());
+            },
+        )?;
+
+        return Ok(dirent.unwrap());
+    };
+
+    let newRoot = lookup(&newRootPath)?;
+    let putOld = lookup(&putOldPath)?;
+    let oldRoot = task.Root();
+
+    // newRoot and putOld must not be the current root, newRoot has to be a
+    // mount point below the current root and putOld must be under newRoot.
+    if newRoot == oldRoot || putOld == oldRoot {
+        return Err(Error::SysError(SysErr::EBUSY));
+    }
+
+    if !newRoot.DescendantOf(&oldRoot) || !putOld.DescendantOf(&newRoot) {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    match task.mountNS.FindMount(&newRoot) {
+        Some(m) if m.lock().Root() == newRoot => (),
+        _ => return Err(Error::SysError(SysErr::EINVAL)),
+    }
+
+    // pivot_root(".", ".") stacks the old root on top of newRoot and the caller
+    // detaches it right away with umount2(".", MNT_DETACH). The dirent of newRoot
+    // has to stay the root, so the old root is not mounted in that case, see
+    // SysUmount2.
+    if putOld != newRoot {
+        task.mountNS.Mount(&putOld, &oldRoot.Inode())?;
+    }
+
+    if task.mountNS.Root() == oldRoot {
+        task.mountNS.SetRoot(&newRoot);
+    }
+
+    // the dirent tree of a mount namespace is its own, so only the tasks of
+    // the namespace have the root dirent
+    let mut fscs = vec![task.fsContext.clone()];
+    for t in GetKernel().TaskSet().Root().Tasks() {
+        fscs.push(t.lock().fsc.clone());
+    }
+
+    for fsc in fscs {
+        if fsc.RootDirectory() == oldRoot {
+            fsc.SetRootDirectory(&newRoot);
+        }
+        if fsc.WorkDirectory() == oldRoot {
+            fsc.SetWorkDirectory(&newRoot);
+        }
+    }
+
+    return Ok(0);
+}
@@ -12,18 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::super::kernel::seccomp::*;
 use super::super::loader::loader::*;
 use super::super::memmgr::metadata::*;
-use super::super::threadmgr::pid_namespace::*;
 use super::super::qlib::auth::cap_set::*;
 use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::SHARESPACE;
 use super::sys_seccomp::*;
 
 // PR_* flags, from <linux/pcrtl.h> for prctl(2).
 
+// PR_QUARK_SYSCALL_FUZZ runs the syscall fuzzing hooks, see FuzzRun.
+pub const PR_QUARK_SYSCALL_FUZZ: i32 = 0x51465a00;
+
 // PR_SET_PDEATHSIG sets the process' death signal.
 pub const PR_SET_PDEATHSIG: i32 = 1;
 
@@ -315,17 +319,19 @@ pub fn SysPrctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             return Ok(1);
         }
         PR_SET_SECCOMP => {
-            if args.arg1 as i32 != SECCOMP_MODE_FILTER {
+            match args.arg1 as i32 {
+                SECCOMP_MODE_STRICT => {
+                    return seccomp(task, SECCOMP_SET_MODE_STRICT, 0, 0);
+                }
+                SECCOMP_MODE_FILTER => {
+                    return seccomp(task, SECCOMP_SET_MODE_FILTER, 0, args.arg2);
+                }
                 // Unsupported mode.
-                return Err(Error::SysError(SysErr::EINVAL));
+                _ => return Err(Error::SysError(SysErr::EINVAL)),
             }
-
-            panic!("SysPrctl::PR_SET_SECCOMP doesn't support.... ");
-            //return seccomp(task, SECCOMP_SET_MODE_FILTER as u64, 0, args.arg2 as u64)
         }
         PR_GET_SECCOMP => {
-            panic!("SysPrctl::PR_GET_SECCOMP doesn't support.... ");
-            //return Err(Error::SysError(SysErr::ENOSYS))
+            return Ok(SeccompMode(&task.Thread()) as i64);
         }
         PR_CAPBSET_READ => {
             let cap = args.arg1 as i32;
@@ -353,15 +359,26 @@ pub fn SysPrctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         PR_SET_CHILD_SUBREAPER => {
             // "If arg2 is nonzero, set the "child subreaper" attribute of
             // the calling process; if arg2 is zero, unset the attribute."
-            //
-            // only if the task is already TID 1 in the PID namespace,
-            // because it already acts as a subreaper in that case.
-            let isInitTid = thread.PIDNamespace().IDOfTask(&thread) == INIT_TID;
-            if args.arg1 != 0 && isInitTid {
-                return Ok(0)
+            thread.ThreadGroup().SetChildSubreaper(args.arg1 != 0);
+            return Ok(0);
+        }
+        PR_GET_CHILD_SUBREAPER => {
+            // "Return the "child subreaper" setting of the caller, in the
+            // location pointed to by (int *) arg2."
+            let isSubreaper: i32 = if thread.ThreadGroup().IsChildSubreaper() {
+                1
+            } else {
+                0
+            };
+            task.CopyOutObj(&isSubreaper, args.arg1)?;
+            return Ok(0);
+        }
+        PR_QUARK_SYSCALL_FUZZ => {
+            if !SHARESPACE.config.read().SyscallFuzz {
+                return Err(Error::SysError(SysErr::EINVAL));
             }
 
-            return Err(Error::SysError(SysErr::EINVAL));
+            return FuzzRun(task, args.arg1, args.arg2);
         }
         PR_GET_TIMING
         | PR_SET_TIMING
@@ -374,7 +391,6 @@ pub fn SysPrctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         | PR_MCE_KILL
         | PR_MCE_KILL_GET
         | PR_GET_TID_ADDRESS
-        | PR_GET_CHILD_SUBREAPER
         | PR_GET_THP_DISABLE
         | PR_SET_THP_DISABLE
         | PR_MPX_ENABLE_MANAGEMENT
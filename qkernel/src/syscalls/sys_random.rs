@@ -16,7 +16,7 @@ use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::Task;
-use super::super::Kernel::HostSpace;
+use super::super::kernel::entropy::*;
 
 pub fn SysGetRandom(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let addr = args.arg0;
@@ -32,14 +32,13 @@ pub fn SysGetRandom(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         length = core::i32::MAX as u32;
     }
 
-    let buf = DataBuff::New(length as usize);
+    // GRND_RANDOM reads from the same pool. The pool is seeded at boot, it
+    // only waits when the host seed is missing.
+    WaitReady(task, flags & _GRND_NONBLOCK != 0)?;
+    let mut buf = DataBuff::New(length as usize);
+    GetRandom(&mut buf.buf)?;
 
-    let ret = HostSpace::GetRandom(buf.Ptr(), buf.Len() as u64, flags as u32);
-    if ret < 0 {
-        return Err(Error::SysError(-ret as i32));
-    }
-
-    task.CopyOutSlice(&buf.buf[0..ret as usize], addr, length as usize)?;
+    task.CopyOutSlice(&buf.buf[..], addr, length as usize)?;
 
-    return Ok(ret as i64);
+    return Ok(length as i64);
 }
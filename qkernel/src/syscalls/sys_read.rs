@@ -52,7 +52,7 @@ pub fn Read(task: &Task, fd: i32, addr: u64, size: i64) -> Result<i64> {
         return Ok(0);
     }
 
-    let iov = IoVec::NewFromAddr(addr, size as usize);
+    let iov = task.UserIoVec(addr, size as usize)?;
 
     let mut iovs: [IoVec; 1] = [iov];
 
@@ -134,7 +134,7 @@ pub fn Pread64(task: &Task, fd: i32, addr: u64, size: i64, offset: i64) -> Resul
         return Ok(0);
     }
 
-    let iov = IoVec::NewFromAddr(addr, size as usize);
+    let iov = task.UserIoVec(addr, size as usize)?;
     let mut iovs: [IoVec; 1] = [iov];
     return preadv(task, &file, &mut iovs, offset);
 }
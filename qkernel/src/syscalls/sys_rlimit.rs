@@ -12,40 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloc::collections::btree_set::BTreeSet;
-
 //use super::super::Kernel;
 use super::super::qlib::common::*;
 use super::super::qlib::limits::*;
 use super::super::qlib::linux_def::*;
-use super::super::qlib::singleton::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::Task;
 use super::super::threadmgr::thread::*;
 
-pub static SETABLE_LIMITS: Singleton<BTreeSet<LimitType>> = Singleton::<BTreeSet<LimitType>>::New();
-
-pub unsafe fn InitSingleton() {
-    SETABLE_LIMITS.Init(
-        [
-            LimitType::NumberOfFiles,
-            LimitType::AS,
-            LimitType::CPU,
-            LimitType::Data,
-            LimitType::FileSize,
-            LimitType::MemoryLocked,
-            LimitType::Stack,
-            // These are not enforced, but we include them here to avoid returning
-            // EPERM, since some apps expect them to succeed.
-            LimitType::Core,
-            LimitType::ProcessCount,
-        ]
-        .iter()
-        .cloned()
-        .collect(),
-    );
-}
-
 pub fn PrLimit64(thread: &Thread, resource: LimitType, newLimit: Option<Limit>) -> Result<Limit> {
     let tg = thread.ThreadGroup();
     let limits = tg.Limits();
@@ -12,21 +12,325 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::kernel::fd_table::*;
+use super::super::kernel::seccomp::*;
+use super::super::qlib::bpf::*;
 use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
+use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_exit::*;
+use super::super::threadmgr::thread::*;
+use super::super::SignalDef::*;
+
+// SockFprog is struct sock_fprog.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SockFprog {
+    pub Len: u16,
+    pub pad: [u16; 3],
+    pub Filter: u64,
+}
+
+// BPF_MAXINSNS is the limit of the instructions of a filter.
+pub const BPF_MAXINSNS: usize = 4096;
+
+// SysSeccomp implements linux syscall seccomp(2).
+pub fn SysSeccomp(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    return seccomp(task, args.arg0, args.arg1, args.arg2);
+}
+
+pub fn seccomp(task: &mut Task, op: u64, flags: u64, addr: u64) -> Result<i64> {
+    match op {
+        SECCOMP_SET_MODE_STRICT => {
+            if flags != 0 || addr != 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            return InstallFilter(task, SeccompFilter::StrictProgram(), true, 0);
+        }
+        SECCOMP_SET_MODE_FILTER => {
+            let valid = SECCOMP_FILTER_FLAG_TSYNC
+                | SECCOMP_FILTER_FLAG_LOG
+                | SECCOMP_FILTER_FLAG_SPEC_ALLOW
+                | SECCOMP_FILTER_FLAG_NEW_LISTENER
+                | SECCOMP_FILTER_FLAG_TSYNC_ESRCH;
+            if flags & !valid != 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            // the tid of a failed TSYNC would be taken for the listener fd
+            if flags & SECCOMP_FILTER_FLAG_TSYNC != 0
+                && flags & SECCOMP_FILTER_FLAG_NEW_LISTENER != 0
+                && flags & SECCOMP_FILTER_FLAG_TSYNC_ESRCH == 0
+            {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let fprog: SockFprog = task.CopyInObj(addr)?;
+            let len = fprog.Len as usize;
+            if len == 0 || len > BPF_MAXINSNS {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let prog: Vec<BPFInstruction> = task.CopyInVec(fprog.Filter, len)?;
+            ValidateSeccomp(&prog, core::mem::size_of::<SeccompData>())?;
+
+            // no_new_privs is always set, any task can install a filter
+            return InstallFilter(task, prog, false, flags);
+        }
+        SECCOMP_GET_ACTION_AVAIL => {
+            if flags != 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let action: u32 = task.CopyInObj(addr)?;
+            if !ActionAvailable(action) {
+                return Err(Error::SysError(SysErr::EOPNOTSUPP));
+            }
+
+            return Ok(0);
+        }
+        SECCOMP_GET_NOTIF_SIZES => {
+            if flags != 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let sizes = SeccompNotifSizes {
+                Notif: core::mem::size_of::<SeccompNotif>() as u16,
+                Resp: core::mem::size_of::<SeccompNotifResp>() as u16,
+                Data: core::mem::size_of::<SeccompData>() as u16,
+            };
+            task.CopyOutObj(&sizes, addr)?;
+            return Ok(0);
+        }
+        _ => return Err(Error::SysError(SysErr::EINVAL)),
+    }
+}
+
+// SeccompMode returns the mode of PR_GET_SECCOMP.
+pub fn SeccompMode(thread: &Thread) -> i32 {
+    match &thread.lock().seccomp {
+        None => return SECCOMP_MODE_NONE,
+        Some(f) if f.strict => return SECCOMP_MODE_STRICT,
+        Some(_) => return SECCOMP_MODE_FILTER,
+    }
+}
+
+fn InstallFilter(
+    task: &mut Task,
+    prog: Vec<BPFInstruction>,
+    strict: bool,
+    flags: u64,
+) -> Result<i64> {
+    let mut notifier = None;
+    let mut fd = 0;
+    if flags & SECCOMP_FILTER_FLAG_NEW_LISTENER != 0 {
+        let n = Arc::new(SeccompNotifier::default());
+        let file = SeccompListener::NewFile(task, &n);
+        fd = task.NewFDFrom(0, &file, &FDFlags { CloseOnExec: true })?;
+        notifier = Some(n);
+    }
+
+    let ret = InstallFilterLocked(task, prog, strict, flags, notifier);
+    if flags & SECCOMP_FILTER_FLAG_NEW_LISTENER != 0 {
+        match ret {
+            Ok(0) => return Ok(fd as i64),
+            _ => {
+                task.RemoveFile(fd).ok();
+            }
+        }
+    }
+
+    return ret;
+}
+
+// InstallFilterLocked installs the filter under the lock of the thread group,
+// so that no other thread changes its filter or is cloned between the TSYNC
+// check and the install.
+fn InstallFilterLocked(
+    task: &Task,
+    prog: Vec<BPFInstruction>,
+    strict: bool,
+    flags: u64,
+    notifier: Option<Arc<SeccompNotifier>>,
+) -> Result<i64> {
+    let thread = task.Thread();
+    let tg = thread.ThreadGroup();
+    let tglock = tg.lock();
+    let current = thread.lock().seccomp.clone();
+
+    // the mode can't change once set, and the strict mode is final
+    if let Some(f) = &current {
+        if f.strict || strict {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+    }
+
+    // TSYNC needs the filters of the other threads to be the ones of the task
+    // or a part of them
+    let mut threads = Vec::new();
+    let mut failed = None;
+    if flags & SECCOMP_FILTER_FLAG_TSYNC != 0 {
+        for t in tglock.tasks.iter() {
+            if *t == thread {
+                continue;
+            }
+
+            let other = t.lock().seccomp.clone();
+            if !SeccompFilter::IsAncestor(&current, &other) {
+                if flags & SECCOMP_FILTER_FLAG_TSYNC_ESRCH != 0 {
+                    return Err(Error::SysError(SysErr::ESRCH));
+                }
+
+                failed = Some(t.clone());
+                break;
+            }
+
+            threads.push(t.clone());
+        }
+    }
+
+    if let Some(t) = failed {
+        // the pid namespace lock is taken out of the thread group lock
+        core::mem::drop(tglock);
+        return Ok(thread.PIDNamespace().IDOfTask(&t) as i64);
+    }
+
+    if notifier.is_some() && SeccompFilter::HasListener(&current) {
+        return Err(Error::SysError(SysErr::EBUSY));
+    }
+
+    let filter = SeccompFilter::New(prog, current, strict, notifier)?;
+    thread.lock().seccomp = Some(filter.clone());
+    for t in threads {
+        t.lock().seccomp = Some(filter.clone());
+    }
+
+    return Ok(0);
+}
+
+// SeccompAddFd implements the SECCOMP_IOCTL_NOTIF_ADDFD ioctl of the
+// listener fd, it returns the fd installed in the notifying task.
+pub fn SeccompAddFd(task: &mut Task, fd: i32, val: u64) -> Result<i64> {
+    let file = task.GetFile(fd)?;
+    let listener = match file.FileOp.as_any().downcast_ref::<SeccompListener>() {
+        None => return Err(Error::SysError(SysErr::ENOTTY)),
+        Some(l) => l,
+    };
+
+    let addfd: SeccompNotifAddfd = task.CopyInObj(val)?;
+    let newfd = listener.notifier.AddFd(task, &addfd)?;
+    return Ok(newfd as i64);
+}
+
+// CheckSeccomp runs the seccomp filters of the task on the syscall nr. It
+// returns None if the syscall is to run, or the state to move the task to
+// otherwise.
+pub fn CheckSeccomp(task: &mut Task, nr: u64, args: &SyscallArguments) -> Option<TaskRunState> {
+    let filter = match task.Thread().lock().seccomp.clone() {
+        None => return None,
+        Some(f) => f,
+    };
+
+    let data = SeccompData {
+        Nr: nr as i32,
+        Arch: AUDIT_ARCH_X86_64,
+        InstructionPointer: task.GetPtRegs().rcx,
+        Args: [
+            args.arg0, args.arg1, args.arg2, args.arg3, args.arg4, args.arg5,
+        ],
+    };
+
+    let (ret, matched) = SeccompFilter::Evaluate(&filter, &data);
+    let action = ret & SECCOMP_RET_ACTION_FULL;
+    let value = ret & SECCOMP_RET_DATA;
+    match action {
+        SECCOMP_RET_ALLOW | SECCOMP_RET_LOG => return None,
+        SECCOMP_RET_ERRNO => {
+            task.haveSyscallReturn = true;
+            let errno = core::cmp::min(value, MAX_ERRNO);
+            task.SetReturn(-(errno as i64) as u64);
+            return Some(TaskRunState::RunApp);
+        }
+        SECCOMP_RET_TRAP => {
+            let info = SignalInfo {
+                Signo: Signal::SIGSYS,
+                Errno: value as i32,
+                Code: SignalInfo::SYS_SECCOMP,
+                ..Default::default()
+            };
+
+            let sigsys = info.SigSys();
+            sigsys.callAddr = data.InstructionPointer;
+            sigsys.syscall = data.Nr;
+            sigsys.arch = data.Arch;
+
+            let thread = task.Thread();
+            thread.forceSignal(Signal(info.Signo), false);
+            thread
+                .SendSignal(&info)
+                .expect("CheckSeccomp send signal fail");
 
-pub const SECCOMP_MODE_NONE: i32 = 0;
-pub const SECCOMP_MODE_FILTER: i32 = 2;
+            task.haveSyscallReturn = true;
+            task.SetReturn(-SysErr::ENOSYS as u64);
+            return Some(TaskRunState::RunApp);
+        }
+        SECCOMP_RET_USER_NOTIF => {
+            let notifier = matched.notifier.clone();
+            let res = match notifier {
+                None => Err(Error::SysError(SysErr::ENOSYS)),
+                Some(n) => n.Notify(task, &data),
+            };
 
-pub const SECCOMP_RET_ACTION_FULL: u64 = 0xffff0000;
-pub const SECCOMP_RET_ACTION: u64 = 0x7fff0000;
-pub const SECCOMP_RET_DATA: u64 = 0x0000ffff;
+            match res {
+                Ok(resp) => {
+                    if resp.Flags & SECCOMP_USER_NOTIF_FLAG_CONTINUE != 0 {
+                        return None;
+                    }
 
-pub const SECCOMP_SET_MODE_FILTER: u64 = 1;
-pub const SECCOMP_FILTER_FLAG_TSYNC: u64 = 1;
-pub const SECCOMP_GET_ACTION_AVAIL: u64 = 2;
+                    task.haveSyscallReturn = true;
+                    if resp.Error != 0 {
+                        task.SetReturn(resp.Error as i64 as u64);
+                    } else {
+                        task.SetReturn(resp.Val as u64);
+                    }
+                }
+                Err(Error::SysError(e)) => {
+                    task.haveSyscallReturn = true;
+                    task.SetReturn(-e as u64);
+                }
+                Err(e) => {
+                    // the notification can't complete, the process is
+                    // killed as by SECCOMP_RET_KILL_PROCESS
+                    info!("CheckSeccomp notify fail {:?}", e);
+                    task.Thread()
+                        .PrepareGroupExit(ExitStatus::New(0, Signal::SIGSYS));
+                    return Some(TaskRunState::RunExit);
+                }
+            }
 
-pub fn seccomp(_task: &mut Task, _mode: u64, _flags: u64, _addr: u64) -> Result<i64> {
-    return Err(Error::SysError(SysErr::ENOSYS));
+            return Some(TaskRunState::RunApp);
+        }
+        SECCOMP_RET_TRACE => {
+            // there is no ptrace tracer, the syscall fails as on Linux
+            task.haveSyscallReturn = true;
+            task.SetReturn(-SysErr::ENOSYS as u64);
+            return Some(TaskRunState::RunApp);
+        }
+        SECCOMP_RET_KILL_THREAD => {
+            task.Thread()
+                .PrepareExit(ExitStatus::New(0, Signal::SIGSYS));
+            return Some(TaskRunState::RunThreadExit);
+        }
+        _ => {
+            // SECCOMP_RET_KILL_PROCESS and the unknown actions
+            task.Thread()
+                .PrepareGroupExit(ExitStatus::New(0, Signal::SIGSYS));
+            return Some(TaskRunState::RunExit);
+        }
+    }
 }
@@ -413,16 +413,15 @@ pub fn SysGetPeerName(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     return Ok(0);
 }
 
+// recvSingleMsg receives a message of the msghdr copied in from the user, the
+// caller copies the msghdr out on success.
 fn recvSingleMsg(
     task: &Task,
     sock: &Arc<FileOperations>,
-    msgPtr: u64,
+    msg: &mut MsgHdr,
     flags: i32,
     deadline: Option<Time>,
 ) -> Result<i64> {
-    //let msg = task.GetTypeMut::<MsgHdr>(msgPtr)?;
-
-    let mut msg: MsgHdr = task.CopyInObj(msgPtr)?;
     if msg.iovLen > UIO_MAXIOV {
         return Err(Error::SysError(SysErr::EMSGSIZE));
     }
@@ -437,8 +436,9 @@ fn recvSingleMsg(
 
     let mut dst = task.IovsFromAddr(msg.iov, msg.iovLen)?;
 
-    if flags & MsgType::MSG_ERRQUEUE != 0 {
-        // Pretend we have an empty error queue.
+    // the host sockets have the error queue of their host socket, the others
+    // have an empty one
+    if flags & MsgType::MSG_ERRQUEUE != 0 && sock.FopsType() != FileOpsType::SocketOperations {
         return Err(Error::SysError(SysErr::EAGAIN));
     }
 
@@ -452,8 +452,6 @@ fn recvSingleMsg(
         }
 
         msg.msgFlags = mflags;
-        task.CopyOutObj(&msg, msgPtr)?;
-
         return Ok(n as i64);
     }
 
@@ -543,20 +541,17 @@ fn recvSingleMsg(
     }
 
     msg.msgFlags = mflags;
-
-    task.CopyOutObj(&msg, msgPtr)?;
     return Ok(n);
 }
 
+// sendSingleMsg sends a message of the msghdr copied in from the user.
 fn sendSingleMsg(
     task: &Task,
     sock: &Arc<FileOperations>,
-    msgPtr: u64,
+    msg: &MsgHdr,
     flags: i32,
     deadline: Option<Time>,
 ) -> Result<i64> {
-    let msg = task.CopyInObj::<MsgHdr>(msgPtr)?;
-
     if msg.msgControlLen > MAX_CONTROL_LEN as usize {
         return Err(Error::SysError(SysErr::ENOBUFS));
     }
@@ -568,7 +563,7 @@ fn sendSingleMsg(
     let msgVec: Vec<u8> = task.CopyInVec(msg.msgName, msg.nameLen as usize)?;
     let controlVec: Vec<u8> = task.CopyInVec(msg.msgControl, msg.msgControlLen as usize)?;
 
-    let mut pMsg = msg;
+    let mut pMsg = *msg;
     if msg.nameLen > 0 {
         pMsg.msgName = &msgVec[0] as *const _ as u64;
     }
@@ -580,7 +575,6 @@ fn sendSingleMsg(
     let src = task.IovsFromAddr(msg.iov, msg.iovLen)?;
 
     let res = sock.SendMsg(task, &src, flags, &mut pMsg, deadline)?;
-    task.CopyOutObj(&msg, msgPtr)?;
     return Ok(res);
 }
 
@@ -616,7 +610,9 @@ pub fn SysRecvMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         flags |= MsgType::MSG_DONTWAIT
     }
 
-    let res = recvSingleMsg(task, &sock, msgPtr, flags, deadline)?;
+    let mut msg: MsgHdr = task.CopyInObj(msgPtr)?;
+    let res = recvSingleMsg(task, &sock, &mut msg, flags, deadline)?;
+    task.CopyOutObj(&msg, msgPtr)?;
     return Ok(res);
 }
 
@@ -671,13 +667,7 @@ pub fn SysRecvMMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
     //info!("SysRecvMMsg 1 vlen is {}", vlen);
     for i in 0..vlen as usize {
-        res = match recvSingleMsg(
-            task,
-            &sock,
-            &(msgs[i].msgHdr) as *const MsgHdr as u64,
-            flags,
-            deadline,
-        ) {
+        res = match recvSingleMsg(task, &sock, &mut msgs[i].msgHdr, flags, deadline) {
             Err(e) => {
                 if count > 0 {
                     break;
@@ -738,7 +728,7 @@ pub fn SysRecvFrom(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         flags |= MsgType::MSG_DONTWAIT
     }
 
-    let iov = IoVec::NewFromAddr(bufPtr, buflen as usize);
+    let iov = task.UserIoVec(bufPtr, buflen as usize)?;
     let mut iovs: [IoVec; 1] = [iov];
 
     let mut pMsg = MsgHdr::default();
@@ -802,7 +792,11 @@ pub fn SysSendMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let sock = file.FileOp.clone();
 
     if flags
-        & !(MsgType::MSG_DONTWAIT | MsgType::MSG_EOR | MsgType::MSG_MORE | MsgType::MSG_NOSIGNAL)
+        & !(MsgType::MSG_DONTWAIT
+            | MsgType::MSG_EOR
+            | MsgType::MSG_MORE
+            | MsgType::MSG_NOSIGNAL
+            | MsgType::MSG_ZEROCOPY)
         != 0
     {
         return Err(Error::SysError(SysErr::EINVAL));
@@ -821,7 +815,8 @@ pub fn SysSendMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         flags |= MsgType::MSG_DONTWAIT
     }
 
-    let res = sendSingleMsg(task, &sock, msgPtr, flags, deadline)?;
+    let msg: MsgHdr = task.CopyInObj(msgPtr)?;
+    let res = sendSingleMsg(task, &sock, &msg, flags, deadline)?;
     return Ok(res);
 }
 
@@ -836,7 +831,11 @@ pub fn SysSendMMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let sock = file.FileOp.clone();
 
     if flags
-        & !(MsgType::MSG_DONTWAIT | MsgType::MSG_EOR | MsgType::MSG_MORE | MsgType::MSG_NOSIGNAL)
+        & !(MsgType::MSG_DONTWAIT
+            | MsgType::MSG_EOR
+            | MsgType::MSG_MORE
+            | MsgType::MSG_NOSIGNAL
+            | MsgType::MSG_ZEROCOPY)
         != 0
     {
         return Err(Error::SysError(SysErr::EINVAL));
@@ -869,14 +868,17 @@ pub fn SysSendMMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let mut res = 0;
     //let msgs = task.GetSliceMut::<MMsgHdr>(msgPtr, vlen as usize)?;
     let mut msgs = task.CopyInVec::<MMsgHdr>(msgPtr, vlen as usize)?;
-    for i in 0..vlen as usize {
-        res = sendSingleMsg(
-            task,
-            &sock,
-            &(msgs[i].msgHdr) as *const MsgHdr as u64,
-            flags,
-            deadline,
-        )?;
+    let mut i = 0;
+    while i < vlen as usize {
+        // the datagrams of the same size and address go to the host as one
+        let n = sock.SendSegments(task, &mut msgs[i..], flags, deadline)?;
+        if n > 0 {
+            i += n;
+            count += n as i64;
+            continue;
+        }
+
+        res = sendSingleMsg(task, &sock, &msgs[i].msgHdr, flags, deadline)?;
 
         if res < 0 {
             break;
@@ -884,6 +886,7 @@ pub fn SysSendMMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
         msgs[i].msgLen = res as u32;
         count += 1;
+        i += 1;
     }
 
     if count == 0 {
@@ -912,7 +915,7 @@ pub fn SysSendTo(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     }
 
     task.CheckPermission(bufPtr, buflen as u64, false, false)?;
-    let iov = IoVec::NewFromAddr(bufPtr, buflen as usize);
+    let iov = task.UserIoVec(bufPtr, buflen as usize)?;
     let iovs: [IoVec; 1] = [iov];
 
     let mut pMsg = MsgHdr::default();
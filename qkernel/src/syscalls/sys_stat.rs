@@ -333,17 +333,35 @@ fn statfsImpl(task: &Task, d: &Dirent, addr: u64) -> Result<()> {
     let sattr = inode.lock().StableAttr().clone();
 
     let info = inode.StatFS(task)?;
+    let blockSize = if info.BlockSize > 0 {
+        info.BlockSize
+    } else {
+        sattr.BlockSize
+    };
+
+    let mflags = inode.lock().MountSource.lock().Flags;
+    let mut flags = 0;
+    if mflags.ReadOnly {
+        flags |= StatfsFlags::ST_RDONLY;
+    }
+    if mflags.NoExec {
+        flags |= StatfsFlags::ST_NOEXEC;
+    }
+    if mflags.NoAtime {
+        flags |= StatfsFlags::ST_NOATIME;
+    }
 
     let statfs = LibcStatfs {
         Type: info.Type,
-        BlockSize: sattr.BlockSize,
+        BlockSize: blockSize,
         Blocks: info.TotalBlocks,
         BlocksFree: info.FreeBlocks,
-        BlocksAvailable: info.FreeBlocks,
+        BlocksAvailable: info.AvailableBlocks,
         Files: info.TotalFiles,
         FilesFree: info.FreeFiles,
         NameLength: NAME_MAX as u64,
-        FragmentSize: sattr.BlockSize,
+        FragmentSize: blockSize,
+        Flags: flags,
         ..Default::default()
     };
 
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use super::super::fs::file::*;
+use super::super::fs::host::diriops::*;
 use super::super::fs::host::hostinodeop::*;
 use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
@@ -35,15 +36,28 @@ pub fn SysSyncFs(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         return Err(Error::SysError(SysErr::EBADF));
     }
 
-    let inode = file.Dirent.Inode();
+    // the file of an overlay lives in the filesystem of its upper or lower inode
+    let mut inode = file.Dirent.Inode();
+    let overlay = inode.lock().Overlay.clone();
+    if let Some(overlay) = overlay {
+        inode = overlay.read().Inode();
+    }
+
     let iops = inode.lock().InodeOp.clone();
-    match iops.as_any().downcast_ref::<HostInodeOp>() {
-        None => return Ok(0),
-        Some(h) => {
-            h.SyncFs()?;
-            return Ok(0);
+    if let Some(h) = iops.as_any().downcast_ref::<HostInodeOp>() {
+        h.SyncFs()?;
+        return Ok(0);
+    }
+
+    if let Some(d) = iops.as_any().downcast_ref::<HostDirOp>() {
+        let ret = HostSpace::SyncFs(d.HostFd());
+        if ret < 0 {
+            return Err(Error::SysError(-ret as i32));
         }
     }
+
+    // the memory backed filesystems have nothing to sync
+    return Ok(0);
 }
 
 // SyncFileRange implements linux syscall sync_file_rage(2)
@@ -12,43 +12,42 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::super::kernel::kernel::*;
+use super::super::kernel::loadavg::*;
+use super::super::memmgr::mem_high::*;
 use super::super::qlib::common::*;
+use super::super::qlib::linux::time::*;
 use super::super::qlib::linux_def::*;
-use super::super::qlib::qmsg::qcall::StatmInfo;
 use super::super::qlib::usage::memory::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
 
+// SysInfo implements the linux syscall sysinfo(2). The numbers are the ones of
+// the sandbox rather than of the host, the memory is the one the sandbox is
+// sized for and there is no swap.
 pub fn SysInfo(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let addr = args.arg0 as u64;
 
     let mut info: LibcSysinfo = LibcSysinfo::default();
 
-    /*let ret = Kernel::HostSpace::Sysinfo(&mut info as * mut _ as u64);
-    if ret < 0 {
-        return Err(Error::SysError(-ret as i32))
-    }*/
+    let usage = MemoryUsage();
+    let totalSize = TotalMemory(SandboxMemory(), usage);
 
-    let statm: StatmInfo = StatmInfo::default();
-    // TODO(Cong): bypassing this issue for now, fix this...
-    //Kernel::HostSpace::Statm(&mut statm);
-    info!("pass to here, rss, {}", statm.rss);
+    // the uptime is the boottime clock of the time namespace
+    let boottime = GetKernel().Uptime() + task.Thread().TimeNamespace().BoottimeOffset();
 
-    let totalUsage = statm.rss;
-    let totalSize = TotalMemory(0, totalUsage);
-
-    //let sysInfo: &mut LibcSysinfo = task.GetTypeMut(addr)?;
     info.procs = task.Thread().PIDNamespace().Tasks().len() as u16;
-    info.uptime = Task::MonoTimeNow().Seconds() as i64;
-    info.totalram = totalSize; //super::super::ALLOCATOR.Total() as u64;
-    info.freeram = totalSize - totalUsage; // super::super::ALLOCATOR.Free() as u64;
+    info.uptime = boottime / SECOND;
+    let loads = LOAD_AVG.Loads();
+    for i in 0..3 {
+        info.loads[i] = loads[i] << (SI_LOAD_SHIFT - FSHIFT);
+    }
+    info.totalram = totalSize;
+    info.freeram = totalSize - usage;
+    info.totalswap = 0;
+    info.freeswap = 0;
     info.mem_unit = 1;
 
-    //*sysInfo = info;
     task.CopyOutObj(&info, addr)?;
-    //error!("SysInfo output is {:?}", &info);
-
-    //return Ok(ret)
-
     return Ok(0);
 }
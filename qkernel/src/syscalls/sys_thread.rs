@@ -23,10 +23,15 @@ pub use xmas_elf::symbol_table::{Entry, Entry64};
 pub use xmas_elf::{P32, P64};
 
 use super::super::asm::*;
+use super::super::fs::flags::*;
 use super::super::kernel::cpuset::*;
+use super::super::kernel::fd_table::*;
+use super::super::kernel::pidfd::*;
 use super::super::loader::loader::*;
 use super::super::memmgr::mm::*;
 use super::super::qlib::common::*;
+use super::super::qlib::linux::rusage::*;
+use super::super::qlib::linux::time::*;
 use super::super::qlib::linux_def::*;
 use super::super::qlib::path::*;
 use super::super::qlib::LoadAddr;
@@ -438,7 +443,7 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let id = args.arg1 as i32;
     let infop = args.arg2;
     let options = args.arg3 as u32;
-    let _rusageAddr = args.arg4;
+    let rusageAddr = args.arg4;
 
     if options
         & !(WaitOption::WNOHANG
@@ -464,12 +469,51 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         ..Default::default()
     };
 
+    // a pidfd opened with PIDFD_NONBLOCK makes the wait nonblocking
+    let mut pidfdNonBlocking = false;
     match idtype {
-        IDType::P_ALL | IDType::P_PID => {
+        IDType::P_ALL => (),
+        IDType::P_PID => {
+            if id <= 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
             wopts.SpecificTID = id;
         }
         IDType::P_PGID => {
-            wopts.SpecificPGID = id;
+            if id < 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            if id == 0 {
+                // since Linux 5.4, the process group of the caller
+                let pg = task.Thread().ThreadGroup().ProcessGroup();
+                let pidns = task.Thread().PIDNamespace();
+                wopts.SpecificPGID = pidns.IDOfProcessGroup(&pg.unwrap());
+            } else {
+                wopts.SpecificPGID = id;
+            }
+        }
+        IDType::P_PIDFD => {
+            if id < 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let file = task.GetFile(id)?;
+            let tg = match file.FileOp.as_any().downcast_ref::<PidfdOperation>() {
+                None => return Err(Error::SysError(SysErr::EINVAL)),
+                Some(pidfd) => pidfd.ThreadGroup(),
+            };
+
+            // the thread group is reaped or out of the PID namespace of the
+            // caller, so it is not a waitable child
+            let pidns = task.Thread().PIDNamespace();
+            let tgid = pidns.IDOfThreadGroup(&tg);
+            if tgid == 0 {
+                return Err(Error::SysError(SysErr::ECHILD));
+            }
+
+            wopts.SpecificTID = tgid;
+            pidfdNonBlocking = file.Flags().NonBlocking;
         }
         _ => return Err(Error::SysError(SysErr::EINVAL)),
     }
@@ -483,8 +527,16 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         wopts.Events |= EVENT_CHILD_GROUP_STOP;
     }
 
+    if pidfdNonBlocking {
+        wopts.BlockInterruptErr = None;
+    }
+
     let wr = match task.Wait(&wopts) {
         Err(Error::ErrNoWaitableEvent) => {
+            if pidfdNonBlocking && options & WaitOption::WNOHANG == 0 {
+                return Err(Error::SysError(SysErr::EAGAIN));
+            }
+
             // "If WNOHANG was specified in options and there were no children
             // in a waitable state, then waitid() returns 0 immediately and the
             // state of the siginfo_t structure pointed to by infop is
@@ -502,10 +554,9 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         Ok(wr) => wr,
     };
 
-    //todo: handle rusageAddr
-    /*if rusageAddr != 0 {
-
-    }*/
+    if rusageAddr != 0 {
+        copyOutWaitRusage(task, &wr, rusageAddr)?;
+    }
 
     if infop == 0 {
         return Ok(0);
@@ -528,17 +579,18 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         siCode = SignalInfo::CLD_EXITED;
         sigChld.status = s.ExitStatus();
     } else if s.Signaled() {
-        siCode = SignalInfo::CLD_KILLED;
-        sigChld.status = s.Signal();
-    } else if s.CoreDump() {
-        siCode = SignalInfo::CLD_DUMPED;
+        if s.CoreDump() {
+            siCode = SignalInfo::CLD_DUMPED;
+        } else {
+            siCode = SignalInfo::CLD_KILLED;
+        }
         sigChld.status = s.Signal();
     } else if s.Stopped() {
         if wr.Event == EVENT_TRACEE_STOP {
             siCode = SignalInfo::CLD_TRAPPED;
             sigChld.status = s.TrapCause();
         } else {
-            siCode = SignalInfo::CLD_TRAPPED;
+            siCode = SignalInfo::CLD_STOPPED;
             sigChld.status = s.StopSignal();
         }
     } else if s.Continued() {
@@ -554,7 +606,60 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     return Ok(0);
 }
 
-pub fn wait4(task: &Task, pid: i32, statusAddr: u64, options: u32, _rusage: u64) -> Result<i64> {
+// copyOutWaitRusage copies out the resource usage of the waited child, which
+// includes the usage of its waited children, as getrusage(RUSAGE_BOTH) of it.
+fn copyOutWaitRusage(task: &Task, wr: &WaitResult, addr: u64) -> Result<()> {
+    let tg = wr.Thread.ThreadGroup();
+    let mut cs = tg.CPUStats();
+    cs.Accumulate(&tg.JoinedChildCPUStats());
+
+    let ru = Rusage {
+        UTime: Timeval::FromNs(cs.UserTime),
+        STime: Timeval::FromNs(cs.SysTime),
+        NVCSw: cs.VoluntarySwitches as i64,
+        ..Default::default()
+    };
+    task.CopyOutObj(&ru, addr)?;
+    return Ok(());
+}
+
+// PidfdOpen implements linux syscall pidfd_open(2).
+pub fn SysPidfdOpen(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let pid = args.arg0 as i32;
+    let flags = args.arg1 as i32;
+
+    if flags & !PIDFD_NONBLOCK != 0 || pid <= 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let pidns = task.Thread().PIDNamespace();
+    let tg = match pidns.ThreadGroupWithID(pid) {
+        Some(tg) => tg,
+        None => {
+            // a pidfd refers to a process, not to a thread of it
+            if pidns.TaskWithID(pid).is_some() {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            return Err(Error::SysError(SysErr::ESRCH));
+        }
+    };
+
+    let file = PidfdOperation::NewPidfdFile(task, &tg);
+    file.SetFlags(
+        task,
+        SettableFileFlags {
+            NonBlocking: flags & PIDFD_NONBLOCK != 0,
+            ..Default::default()
+        },
+    );
+
+    // "The close-on-exec flag is set on the file descriptor." - pidfd_open(2)
+    let fd = task.NewFDFrom(0, &file, &FDFlags { CloseOnExec: true })?;
+    return Ok(fd as i64);
+}
+
+pub fn wait4(task: &Task, pid: i32, statusAddr: u64, options: u32, rusage: u64) -> Result<i64> {
     if options
         & !(WaitOption::WNOHANG
             | WaitOption::WUNTRACED
@@ -607,10 +712,10 @@ pub fn wait4(task: &Task, pid: i32, statusAddr: u64, options: u32, _rusage: u64)
         //task.CopyInObject(statusAddr, &wr.Status as * const _ as u64, 4)?;
         task.CopyOutObj(&wr.Status, statusAddr)?;
     }
-    //todo: handle rusageAddr
-    /*if rusageAddr != 0 {
 
-    }*/
+    if rusage != 0 {
+        copyOutWaitRusage(task, &wr, rusage)?;
+    }
 
     return Ok(wr.TID as i64);
 }
@@ -667,6 +772,8 @@ pub fn SysUnshare(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         NewFSContext: flags & CloneOp::CLONE_FS == CloneOp::CLONE_FS,
         NewUTSNamespace: flags & CloneOp::CLONE_NEWUTS == CloneOp::CLONE_NEWUTS,
         NewIPCNamespace: flags & CloneOp::CLONE_NEWIPC == CloneOp::CLONE_NEWIPC,
+        NewMountNamespace: flags & CloneOp::CLONE_NEWNS == CloneOp::CLONE_NEWNS,
+        NewTimeNamespace: flags & CloneOp::CLONE_NEWTIME == CloneOp::CLONE_NEWTIME,
         ..Default::default()
     };
 
@@ -680,7 +787,11 @@ pub fn SysUnshare(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     if opts.NewUserNamespace {
         opts.NewThreadGroup = true;
         opts.NewFSContext = true;
-        panic!("Doesn't support create new usernamespace...");
+    }
+
+    // "CLONE_NEWNS automatically implies CLONE_FS as well."
+    if opts.NewMountNamespace {
+        opts.NewFSContext = true;
     }
 
     task.Unshare(&opts)?;
@@ -807,15 +918,26 @@ pub fn SysSetpgid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             return Err(Error::SysError(SysErr::EINVAL));
         }
 
-        // Setpgid only operates on child threadgroups.
-        if tg != task.Thread().ThreadGroup()
-            && (tgLeader.Parent().is_none()
-                || tgLeader.Parent().unwrap().ThreadGroup() != task.Thread().ThreadGroup())
-        {
-            return Err(Error::SysError(SysErr::EINVAL));
+        // Setpgid only operates on the caller and its child threadgroups.
+        let localtg = task.Thread().ThreadGroup();
+        if tg != localtg {
+            if tgLeader.Parent().is_none() || tgLeader.Parent().unwrap().ThreadGroup() != localtg {
+                return Err(Error::SysError(SysErr::ESRCH));
+            }
+
+            // The child has to be in the session of the caller.
+            if tg.Session() != localtg.Session() {
+                return Err(Error::SysError(SysErr::EPERM));
+            }
         }
     }
 
+    // A session leader can't change its process group.
+    let session = tg.Session().unwrap();
+    if session.lock().leader == tg {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
     // "If pgid is zero, then the PGID of the process specified by pid is made
     // the same as its process ID."
     let defaultPGID = pidns.IDOfThreadGroup(&tg);
@@ -892,7 +1014,10 @@ pub fn SysGetpgid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 pub fn SysSetsid(task: &mut Task, _args: &SyscallArguments) -> Result<i64> {
     let tg = task.Thread().ThreadGroup();
     tg.CreateSessoin()?;
-    return Ok(0);
+
+    // The session ID is the thread group ID of its leader.
+    let pidns = task.Thread().PIDNamespace();
+    return Ok(pidns.IDOfThreadGroup(&tg) as i64);
 }
 
 // Getsid implements the linux syscall getsid(2).
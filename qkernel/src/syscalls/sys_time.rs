@@ -14,6 +14,7 @@
 
 use alloc::boxed::Box;
 
+use super::super::kernel::time_namespace::OffsetsInUse;
 use super::super::kernel::timer::timer::*;
 use super::super::kernel::timer::*;
 use super::super::qlib::common::*;
@@ -149,7 +150,13 @@ pub fn SysClockGetTime(task: &mut Task, args: &SyscallArguments) -> Result<i64>
     //let ts : &mut Timespec = task.GetTypeMut(addr)?;
     //*ts = clock.Now().Timespec();
 
-    let ts = clock.Now().Timespec();
+    let mut now = clock.Now();
+    if OffsetsInUse() {
+        // the monotonic and the boottime clocks of the time namespace
+        now = now.Add(task.Thread().TimeNamespace().Offset(clockID));
+    }
+
+    let ts = now.Timespec();
     task.CopyOutObj(&ts, addr)?;
     //info!("SysClockGetTime: output is {:?}", ts);
 
@@ -229,7 +236,11 @@ pub fn SysClockNanosleep(task: &mut Task, args: &SyscallArguments) -> Result<i64
 
     let end;
     if flags & TIMER_ABSTIME != 0 {
-        end = input;
+        end = if OffsetsInUse() {
+            task.Thread().TimeNamespace().KernelTime(clockID, input)
+        } else {
+            input
+        };
     } else {
         let now = clock.Now();
         end = now.0 + input;
@@ -109,7 +109,7 @@ pub fn SysTimerCreate(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         sev = task.CopyInObj(sevp)?;
     }
 
-    let id = task.Thread().IntervalTimerCreate(&c, &mut sev)?;
+    let id = task.Thread().IntervalTimerCreate(&c, clockID, &mut sev)?;
 
     //let timerID = task.GetTypeMut(timerIDp)?;
     //*timerID = id;
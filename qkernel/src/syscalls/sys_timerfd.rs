@@ -15,6 +15,7 @@
 use super::super::fs::flags::*;
 use super::super::fs::timerfd::*;
 use super::super::kernel::fd_table::*;
+use super::super::kernel::time_namespace::OffsetsInUse;
 use super::super::kernel::timer::timer::*;
 use super::super::qlib::common::*;
 use super::super::qlib::linux::time::*;
@@ -71,7 +72,14 @@ pub fn SysTimerfdSettime(task: &mut Task, args: &SyscallArguments) -> Result<i64
         None => return Err(Error::SysError(SysErr::EINVAL)),
     };
 
-    let newVal: Itimerspec = task.CopyInObj(newValAddr)?;
+    let mut newVal: Itimerspec = task.CopyInObj(newValAddr)?;
+    if flags & TFD_TIMER_ABSTIME != 0 && OffsetsInUse() {
+        newVal = task
+            .Thread()
+            .TimeNamespace()
+            .KernelItimerspec(tf.clockId, &newVal)?;
+    }
+
     let clock = tf.Clock();
     let newS = Setting::FromItimerspec(&newVal, flags & TFD_TIMER_ABSTIME != 0, &clock)?;
 
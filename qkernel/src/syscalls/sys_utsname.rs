@@ -13,6 +13,8 @@
 // limitations under the License.
 
 use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::min;
 
 use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
@@ -20,10 +22,6 @@ use super::super::syscalls::syscalls::*;
 use super::super::task::*;
 use super::super::version::*;
 
-// UTSLen is the maximum length of strings contained in fields of
-// UtsName.
-pub const UTS_LEN: usize = 64;
-
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
 pub struct UtsName {
@@ -66,13 +64,15 @@ impl UtsName {
 pub fn UtsNameString(s: &[u8; UTS_LEN + 1]) -> String {
     // The NUL bytes will remain even in a cast to string. We must
     // explicitly strip them.
-    let mut i = UTS_LEN;
-    while s[i] == 0 {
-        i -= 1;
-    }
+    let end = s.iter().position(|c| *c == 0).unwrap_or(s.len());
+    return String::from_utf8_lossy(&s[..end]).into_owned();
+}
 
-    let s = &s[..i + 1];
-    return String::from_utf8(s.to_vec()).unwrap();
+// CopyUtsField copies at most UTS_LEN bytes of src to the field, the last byte
+// of the field is always the terminating NUL.
+fn CopyUtsField(field: &mut [u8; UTS_LEN + 1], src: &[u8]) {
+    let len = min(src.len(), UTS_LEN);
+    field[0..len].clone_from_slice(&src[0..len]);
 }
 
 pub fn SysUname(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
@@ -80,15 +80,19 @@ pub fn SysUname(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
     let version = &VERSION;
     let uts = task.Thread().UTSNamespace();
+    let hostname = uts.HostName();
+    let domainname = uts.DomainName();
+    let release = KernelRelease();
+    let kversion = KernelVersion();
 
     let mut u = UtsName::default();
 
-    u.Sysname[0..version.Sysname.len()].clone_from_slice(version.Sysname.as_bytes());
-    u.Nodename[0..uts.HostName().len()].clone_from_slice(uts.HostName().as_bytes());
-    u.Release[0..version.Release.len()].clone_from_slice(version.Release.as_bytes());
-    u.Version[0..version.Version.len()].clone_from_slice(version.Version.as_bytes());
-    u.Machine[0.."x86_64".len()].clone_from_slice("x86_64".as_bytes());
-    u.Domainname[0..uts.DomainName().len()].clone_from_slice(uts.DomainName().as_bytes());
+    CopyUtsField(&mut u.Sysname, version.Sysname.as_bytes());
+    CopyUtsField(&mut u.Nodename, &hostname);
+    CopyUtsField(&mut u.Release, release.as_bytes());
+    CopyUtsField(&mut u.Version, kversion.as_bytes());
+    CopyUtsField(&mut u.Machine, "x86_64".as_bytes());
+    CopyUtsField(&mut u.Domainname, &domainname);
 
     //let va : &mut UtsName = task.GetTypeMut(va)?;
     //*va = u;
@@ -105,16 +109,15 @@ pub fn SysUname(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     return Ok(0);
 }
 
-// Setdomainname implements Linux syscall setdomainname.
-pub fn SysSetdomainname(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
-    let nameAddr = args.arg0 as u64;
-    let size = args.arg1 as i32;
-
+// CopyInUtsName copies in the name of sethostname(2) and setdomainname(2), the
+// size bytes of it are the name, they don't have to end with NUL.
+fn CopyInUtsName(task: &Task, addr: u64, size: i32) -> Result<Vec<u8>> {
     let utsns = task.Thread().UTSNamespace();
     {
+        // the capability is checked in the user namespace owning the uts
+        // namespace, not in the one of the caller
         let creds = task.Creds();
-        let userNS = creds.lock().UserNamespace.clone();
-        if !creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &userNS) {
+        if !creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &utsns.UserNamespace()) {
             return Err(Error::SysError(SysErr::EPERM));
         }
     }
@@ -123,13 +126,25 @@ pub fn SysSetdomainname(task: &mut Task, args: &SyscallArguments) -> Result<i64>
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
-    let (name, err) = task.CopyInString(nameAddr, UTS_LEN - 1);
-    match err {
-        Ok(()) => (),
-        Err(e) => return Err(e),
-    };
+    let mut buf: Vec<u8> = task.CopyInVec(addr, size as usize)?;
+    if let Some(end) = buf.iter().position(|c| *c == 0) {
+        buf.truncate(end);
+    }
+
+    if buf.len() > UTS_LEN {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
 
-    utsns.SetDomainName(name);
+    return Ok(buf);
+}
+
+// Setdomainname implements Linux syscall setdomainname.
+pub fn SysSetdomainname(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let nameAddr = args.arg0 as u64;
+    let size = args.arg1 as i32;
+
+    let name = CopyInUtsName(task, nameAddr, size)?;
+    task.Thread().UTSNamespace().SetDomainName(name);
     return Ok(0);
 }
 
@@ -138,25 +153,7 @@ pub fn SysSethostname(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let nameAddr = args.arg0 as u64;
     let size = args.arg1 as i32;
 
-    let utsns = task.Thread().UTSNamespace();
-    {
-        let creds = task.Creds();
-        let userNS = creds.lock().UserNamespace.clone();
-        if !creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &userNS) {
-            return Err(Error::SysError(SysErr::EPERM));
-        }
-    }
-
-    if size < 0 || size > UTS_LEN as i32 {
-        return Err(Error::SysError(SysErr::EINVAL));
-    }
-
-    let (name, err) = task.CopyInString(nameAddr, UTS_LEN - 1);
-    match err {
-        Ok(()) => (),
-        Err(e) => return Err(e),
-    };
-
-    utsns.SetHostName(name);
+    let name = CopyInUtsName(task, nameAddr, size)?;
+    task.Thread().UTSNamespace().SetHostName(name);
     return Ok(0);
 }
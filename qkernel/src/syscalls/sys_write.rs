@@ -69,7 +69,7 @@ pub fn Write(task: &Task, fd: i32, addr: u64, size: i64) -> Result<i64> {
     }
     */
 
-    let iov = IoVec::NewFromAddr(addr, size as usize);
+    let iov = task.UserIoVec(addr, size as usize)?;
     let iovs: [IoVec; 1] = [iov];
 
     return writev(task, &file, &iovs);
@@ -112,7 +112,7 @@ pub fn Pwrite64(task: &Task, fd: i32, addr: u64, size: i64, offset: i64) -> Resu
         return Ok(0);
     }
 
-    let iov = IoVec::NewFromAddr(addr, size as usize);
+    let iov = task.UserIoVec(addr, size as usize)?;
     let iovs: [IoVec; 1] = [iov];
 
     return pwritev(task, &file, &iovs, offset);
@@ -140,22 +140,31 @@ pub fn SysPWritev2(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
-    // Check that flags are supported. RWF_DSYNC/RWF_SYNC can be ignored since
-    // all state is in-memory.
-
+    // Check that flags are supported.
     // doens't support Flags::RWF_APPEND
     if flags & !(Flags::RWF_HIPRI | Flags::RWF_DSYNC | Flags::RWF_SYNC) != 0 {
         return Err(Error::SysError(SysErr::EOPNOTSUPP));
     }
 
-    if offset == -1 {
-        let n = Writev(task, fd, addr, iovcnt)?;
-        task.ioUsage.AccountWriteSyscall(n);
-        return Ok(n);
+    let n = if offset == -1 {
+        Writev(task, fd, addr, iovcnt)?
+    } else {
+        Pwritev(task, fd, addr, iovcnt, offset)?
+    };
+    task.ioUsage.AccountWriteSyscall(n);
+
+    // RWF_DSYNC/RWF_SYNC are O_DSYNC/O_SYNC of this write
+    if n > 0 && flags & (Flags::RWF_DSYNC | Flags::RWF_SYNC) != 0 {
+        let syncType = if flags & Flags::RWF_SYNC != 0 {
+            SyncType::SyncAll
+        } else {
+            SyncType::SyncData
+        };
+
+        let file = task.GetFile(fd)?;
+        file.Fsync(task, 0, FILE_MAX_OFFSET, syncType)?;
     }
 
-    let n = Pwritev(task, fd, addr, iovcnt, offset)?;
-    task.ioUsage.AccountWriteSyscall(n);
     return Ok(n);
 }
 
@@ -204,7 +204,7 @@ pub fn SetXAttr(task: &Task, d: &Dirent, nameAddr: u64, valueAddr: u64, size: us
 }
 
 pub fn CopyInXattrName(task: &Task, nameAddr: u64) -> Result<String> {
-    let (name, err) = task.mm.CopyInString(task, nameAddr, Xattr::XATTR_NAME_MAX + 1);
+    let (name, err) = task.CopyInString(nameAddr, Xattr::XATTR_NAME_MAX + 1);
     match err {
         Err(Error::SysError(SysErr::ENAMETOOLONG)) => return Err(Error::SysError(SysErr::ERANGE)),
         Err(e) => return Err(e),
@@ -24,6 +24,7 @@ use super::super::syscalls::sys_identity::*;
 use super::super::syscalls::sys_membarrier::*;
 use super::super::syscalls::sys_mempolicy::*;
 use super::super::syscalls::sys_mmap::*;
+use super::super::syscalls::sys_mount::*;
 use super::super::syscalls::sys_pipe::*;
 use super::super::syscalls::sys_poll::*;
 use super::super::syscalls::sys_prctl::*;
@@ -46,6 +47,7 @@ use super::super::syscalls::sys_utsname::*;
 use super::super::syscalls::sys_write::*;
 use super::super::syscalls::sys_memfd::*;
 use super::super::syscalls::sys_sched::*;
+use super::super::syscalls::sys_seccomp::*;
 use super::super::syscalls::sys_inotify::*;
 use super::super::syscalls::sys_xattr::*;
 use super::super::syscalls::sys_sem::*;
@@ -53,6 +55,7 @@ use super::super::syscalls::sys_shm::*;
 use super::super::syscalls::sys_msgqueue::*;
 use super::super::syscalls::sys_syslog::*;
 
+use super::super::kernel::seccomp::*;
 use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 use super::super::qlib::SysCallID;
@@ -71,8 +74,22 @@ pub struct SyscallArguments {
 
 #[inline]
 pub fn SysCall(task: &mut Task, nr: u64, args: &SyscallArguments) -> TaskRunState {
+    if SeccompInUse() {
+        if let Some(state) = CheckSeccomp(task, nr, args) {
+            return state;
+        }
+    }
+
     let idx = nr as usize;
-    let func = SYS_CALL_TABLE.get(idx).unwrap();
+    let func = match SYS_CALL_TABLE.get(idx) {
+        None => {
+            task.haveSyscallReturn = true;
+            task.SetReturn(-SysErr::ENOSYS as u64);
+            return TaskRunState::RunApp;
+        }
+        Some(f) => f,
+    };
+
     match func(task, args) {
         Err(Error::SysCallRetCtrlWithRet(state, ret)) => {
             task.SetReturn(ret);
@@ -107,8 +124,12 @@ pub fn SysCall(task: &mut Task, nr: u64, args: &SyscallArguments) -> TaskRunStat
             return TaskRunState::RunApp;
         }
         Err(Error::SysCallNotImplement) => {
+            // the application can't take down the sandbox with a syscall
             let callId: SysCallID = unsafe { core::mem::transmute(nr as u64) };
-            panic!("Sycall not implement syscall is {:?}", callId);
+            error!("Sycall not implement syscall is {:?}", callId);
+            task.haveSyscallReturn = true;
+            task.SetReturn(-SysErr::ENOSYS as u64);
+            return TaskRunState::RunApp;
         }
         Err(e) => {
             panic!("Syscall[{}]: get unexpected error {:x?}", nr, e);
@@ -119,6 +140,186 @@ pub fn SysCall(task: &mut Task, nr: u64, args: &SyscallArguments) -> TaskRunStat
 
 pub type SyscallFn = fn(task: &mut Task, args: &SyscallArguments) -> Result<i64>;
 
+// The fuzzing hooks of the syscall dispatcher. With debug.syscall_fuzz in the
+// config, a fuzzer in a task of the sandbox calls
+// prctl(PR_QUARK_SYSCALL_FUZZ, seed, count) and FuzzRun runs count syscalls of
+// the seed in the task, with the arguments of FuzzArguments, which mix random
+// values with the boundary values of the argument checks, e.g. the kernel
+// memory, the top of the user space and the overflowing lengths. The syscalls
+// which don't return to the fuzzer, change its memory, its signal handling or
+// its syscall filter, or sleep on an arbitrary timeout are skipped. An
+// unexpected error panics the sandbox as in the dispatcher, with the syscall
+// and its arguments, the seed and the count reproduce it.
+pub const FUZZ_SKIP_SYSCALLS: &[SysCallID] = &[
+    SysCallID::sys_exit,
+    SysCallID::sys_exit_group,
+    SysCallID::sys_execve,
+    SysCallID::sys_stub_execveat,
+    SysCallID::sys_clone,
+    SysCallID::sys_clone3,
+    SysCallID::sys_fork,
+    SysCallID::sys_vfork,
+    SysCallID::sys_rt_sigreturn,
+    SysCallID::sys_rt_sigsuspend,
+    SysCallID::sys_rt_sigprocmask,
+    SysCallID::sys_rt_sigaction,
+    SysCallID::sys_rt_sigtimedwait,
+    SysCallID::sys_sigaltstack,
+    SysCallID::sys_prctl,
+    SysCallID::sys_seccomp,
+    SysCallID::sys_pause,
+    SysCallID::sys_kill,
+    SysCallID::sys_tkill,
+    SysCallID::sys_tgkill,
+    SysCallID::sys_rt_sigqueueinfo,
+    SysCallID::sys_rt_tgsigqueueinfo,
+    SysCallID::sys_pidfd_send_signal,
+    SysCallID::sys_reboot,
+    SysCallID::sys_mmap,
+    SysCallID::sys_munmap,
+    SysCallID::sys_mremap,
+    SysCallID::sys_mprotect,
+    SysCallID::sys_madvise,
+    SysCallID::sys_brk,
+    SysCallID::sys_shmat,
+    SysCallID::sys_shmdt,
+    SysCallID::sys_remap_file_pages,
+    SysCallID::sys_arch_prctl,
+    SysCallID::sys_set_tid_address,
+    SysCallID::sys_setrlimit,
+    SysCallID::sys_prlimit64,
+    SysCallID::sys_nanosleep,
+    SysCallID::sys_clock_nanosleep,
+    SysCallID::sys_wait4,
+    SysCallID::sys_waitid,
+    SysCallID::sys_select,
+    SysCallID::sys_pselect6,
+    SysCallID::sys_poll,
+    SysCallID::sys_ppoll,
+    SysCallID::sys_epoll_wait,
+    SysCallID::sys_futex,
+];
+
+pub const FUZZ_ARG_VALUES: &[u64] = &[
+    0,
+    1,
+    !0,
+    core::i32::MAX as u64,
+    core::i64::MAX as u64,
+    MemoryDef::PAGE_SIZE - 1,
+    MemoryDef::KVM_IOEVENTFD_BASEADDR,
+    MemoryDef::PHY_LOWER_ADDR,
+    MemoryDef::PHY_UPPER_ADDR - 1,
+    MemoryDef::LOWER_TOP - 1,
+    MemoryDef::LOWER_TOP,
+    MemoryDef::UPPER_BOTTOM,
+];
+
+// the syscalls one prctl(PR_QUARK_SYSCALL_FUZZ) runs at most
+pub const FUZZ_MAX_COUNT: u64 = 1 << 16;
+
+// FuzzNext returns the next value of the seed, a xorshift state.
+pub fn FuzzNext(seed: &mut u64) -> u64 {
+    let mut x = *seed;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    *seed = x;
+    return x;
+}
+
+// FuzzArguments returns the arguments of the seed.
+pub fn FuzzArguments(seed: &mut u64) -> SyscallArguments {
+    let mut args = [0u64; 6];
+    for i in 0..args.len() {
+        let x = FuzzNext(seed);
+
+        args[i] = match x % 4 {
+            0 => FUZZ_ARG_VALUES[(x >> 8) as usize % FUZZ_ARG_VALUES.len()],
+            // a small value, e.g. a fd, a flag or a length
+            1 => (x >> 8) & 0xfff,
+            _ => x,
+        };
+    }
+
+    return SyscallArguments {
+        arg0: args[0],
+        arg1: args[1],
+        arg2: args[2],
+        arg3: args[3],
+        arg4: args[4],
+        arg5: args[5],
+    };
+}
+
+// FuzzSysCall runs the syscall nr and returns its result as the dispatcher
+// does, the skipped and the unknown syscalls return ENOSYS. The seccomp
+// filters of the task apply as in the dispatcher.
+pub fn FuzzSysCall(task: &mut Task, nr: u64, args: &SyscallArguments) -> Result<i64> {
+    if SeccompInUse() {
+        match CheckSeccomp(task, nr, args) {
+            None => (),
+            Some(TaskRunState::RunApp) => {
+                task.haveSyscallReturn = false;
+                let ret = task.Return() as i64;
+                if ret < 0 && ret >= -(MAX_ERRNO as i64) {
+                    return Err(Error::SysError(-ret as i32));
+                }
+
+                return Ok(ret);
+            }
+            // the filter kills the task, the fuzzing stops there
+            Some(state) => return Err(Error::SysCallRetCtrl(state)),
+        }
+    }
+
+    let func = match SYS_CALL_TABLE.get(nr as usize) {
+        None => return Err(Error::SysError(SysErr::ENOSYS)),
+        Some(f) => f,
+    };
+
+    for id in FUZZ_SKIP_SYSCALLS {
+        if *id as u64 == nr {
+            return Err(Error::SysError(SysErr::ENOSYS));
+        }
+    }
+
+    match func(task, args) {
+        Err(Error::SysCallNotImplement) => return Err(Error::SysError(SysErr::ENOSYS)),
+        // the restart of an interrupted syscall is up to the fuzzer
+        Err(Error::SysCallRetCtrlWithRet(_, ret)) => return Ok(ret as i64),
+        Err(Error::SysCallRetCtrl(_)) => return Err(Error::SysError(SysErr::EINTR)),
+        r => return r,
+    }
+}
+
+// FuzzRun runs count syscalls of the seed in the task, it returns the count
+// of the syscalls which are not skipped.
+pub fn FuzzRun(task: &mut Task, seed: u64, count: u64) -> Result<i64> {
+    // 0 is a fixed point of xorshift
+    let mut seed = if seed == 0 { 1 } else { seed };
+    let mut ran = 0;
+    for _ in 0..core::cmp::min(count, FUZZ_MAX_COUNT) {
+        let nr = FuzzNext(&mut seed) % SYS_CALL_TABLE.len() as u64;
+        let args = FuzzArguments(&mut seed);
+        match FuzzSysCall(task, nr, &args) {
+            Ok(_) => ran += 1,
+            Err(Error::SysError(SysErr::ENOSYS)) => (),
+            Err(Error::SysError(_)) => ran += 1,
+            Err(Error::SysCallRetCtrl(state)) => return Err(Error::SysCallRetCtrl(state)),
+            Err(e) => {
+                let callId: SysCallID = unsafe { core::mem::transmute(nr as u64) };
+                panic!(
+                    "FuzzRun: {:?} {:x?} get unexpected error {:x?}",
+                    callId, args, e
+                );
+            }
+        }
+    }
+
+    return Ok(ran);
+}
+
 pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysRead,             // 000 sys_read
     SysWrite,            // 001 sys_write,
@@ -275,7 +476,7 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysMunlockall,       // 152 sys_munlockall,
     SysCapErr,           // 153 sys_vhangup,
     SysNoPermission,     // 154 sys_modify_ldt,
-    SysNoPermission,     // 155 sys_pivot_root,
+    SysPivotRoot,        // 155 sys_pivot_root,
     SysNoPermission,     // 156 sys__sysctl,
     SysPrctl,            // 157 sys_prctl,
     SysArchPrctl,        // 158 sys_arch_prctl,
@@ -285,8 +486,8 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysSync,             // 162 sys_sync,
     SysCapErr,           // 163 sys_acct,
     SysCapErr,           // 164 sys_settimeofday,
-    NotImplementSyscall, // 165 sys_mount,
-    NotImplementSyscall, // 166 sys_umount2,
+    SysMount,            // 165 sys_mount,
+    SysUmount2,          // 166 sys_umount2,
     SysCapErr,           // 167 sys_swapon,
     SysCapErr,           // 168 sys_swapoff,
     SysCapErr,           // 169 sys_reboot,
@@ -392,7 +593,7 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysFaccessat,        // 269 sys_faccessat,
     SysPSelect,          // 270 sys_pselect6,
     SysPpoll,            // 271 sys_ppoll,
-    NotImplementSyscall, // 272 sys_unshare,
+    SysUnshare,          // 272 sys_unshare,
     SysSetRobustList,    // 273 sys_set_robust_list,
     SysGetRobustList,    // 274 sys_get_robust_list,
     SysSplice,           // 275 sys_splice,
@@ -437,7 +638,7 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysNoSys,            //	314 sys_sched_setattr,       implement scheduler?
     SysNoSys,            //	315 sys_sched_getattr,       implement scheduler?
     SysNoSupport,        //	316 sys_renameat2,
-    NotImplementSyscall, //	317 sys_seccomp,
+    SysSeccomp,          //	317 sys_seccomp,
     SysGetRandom,        //	318 sys_getrandom,
     SysMemfdCreate,      //	319 sys_memfd_create,
     SysCapErr,           //	320 sys_kexec_file_load    CAP_SYS_BOOT
@@ -562,7 +763,7 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     NotImplementSyscall, //	431 sys_fsconfig
     NotImplementSyscall, //	432 sys_fsmount
     NotImplementSyscall, //	433 sys_fspick
-    NotImplementSyscall, //	434 sys_pidfd_open
+    SysPidfdOpen,        //	434 sys_pidfd_open
     SysNoSys,            //	435 sys_clone3
     SysCloseRange,       //	436 sys_close_range
     NotImplementSyscall, //	437 sys_openat2
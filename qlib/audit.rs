@@ -0,0 +1,90 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::vec_deque::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::mutex::*;
+
+// The audit of the guest syscalls for the host security pipelines, which see
+// only qvisor from the host. qkernel records the syscalls of the classes of
+// AuditClasses in the config when they return, into a bounded queue of the
+// share space. qvisor drains the queue and streams the events as json lines
+// to the clients which sent UCallReq::AuditStream on the control socket, the
+// format a Falco json plugin or a log shipper can take as is. The audit never
+// blocks a task: the events of a full queue are dropped and counted.
+
+// execve and execveat, with the path of the program
+pub const AUDIT_EXEC: u64 = 1 << 0;
+// the opens for a write or a create, and the syscalls changing the namespace
+// or the attributes of a file, with the path
+pub const AUDIT_FILE: u64 = 1 << 1;
+// connect and bind with the address, accept and listen
+pub const AUDIT_NET: u64 = 1 << 2;
+// the changes of the credentials and of the namespaces of a task
+pub const AUDIT_CRED: u64 = 1 << 3;
+// mount, umount, pivot_root and chroot
+pub const AUDIT_MOUNT: u64 = 1 << 4;
+// ptrace and the accesses to the memory of another process
+pub const AUDIT_PTRACE: u64 = 1 << 5;
+
+pub const AUDIT_QUEUE_LEN: usize = 4096;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct AuditEvent {
+    // the guest CLOCK_REALTIME of the return of the syscall in ns
+    pub time: i64,
+    pub container: String,
+    // the ids of the root pid namespace of the guest
+    pub pid: i32,
+    pub tid: i32,
+    pub uid: u32,
+    pub comm: String,
+    pub syscall: String,
+    pub args: [u64; 6],
+    // the path or the socket address argument
+    pub arg: Option<String>,
+    // the result, -errno on a failure
+    pub ret: i64,
+}
+
+#[derive(Default)]
+pub struct AuditQueue {
+    pub events: QMutex<VecDeque<AuditEvent>>,
+    pub dropped: AtomicU64,
+}
+
+impl AuditQueue {
+    pub fn Push(&self, event: AuditEvent) {
+        let mut events = self.events.lock();
+        if events.len() >= AUDIT_QUEUE_LEN {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        events.push_back(event);
+    }
+
+    pub fn Drain(&self) -> Vec<AuditEvent> {
+        return self.events.lock().drain(..).collect();
+    }
+
+    // Dropped returns the events dropped since the last call.
+    pub fn Dropped(&self) -> u64 {
+        return self.dropped.swap(0, Ordering::Relaxed);
+    }
+}
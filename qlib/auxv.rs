@@ -72,6 +72,10 @@ pub enum AuxVec {
 
     // AT_SYSINFO_EHDR is the address of the VDSO.
     AT_SYSINFO_EHDR = 33,
+
+    // AT_MINSIGSTKSZ is the minimal stack size required by the kernel to
+    // deliver a signal, including the extended cpu state.
+    AT_MINSIGSTKSZ = 51,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -0,0 +1,491 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::common::*;
+
+// Classic BPF, see include/uapi/linux/filter.h.
+
+// MAX_INSNS is the maximum number of instructions in a program.
+pub const MAX_INSNS: usize = 4096;
+
+// SCRATCH_MEM_SLOTS is the number of words of the scratch memory M[].
+pub const SCRATCH_MEM_SLOTS: usize = 16;
+
+// Instruction classes
+pub const LD: u16 = 0x00;
+pub const LDX: u16 = 0x01;
+pub const ST: u16 = 0x02;
+pub const STX: u16 = 0x03;
+pub const ALU: u16 = 0x04;
+pub const JMP: u16 = 0x05;
+pub const RET: u16 = 0x06;
+pub const MISC: u16 = 0x07;
+
+// Size of load
+pub const W: u16 = 0x00;
+pub const H: u16 = 0x08;
+pub const B: u16 = 0x10;
+
+// Addressing mode
+pub const IMM: u16 = 0x00;
+pub const ABS: u16 = 0x20;
+pub const IND: u16 = 0x40;
+pub const MEM: u16 = 0x60;
+pub const LEN: u16 = 0x80;
+pub const MSH: u16 = 0xa0;
+
+// ALU and JMP operations
+pub const ADD: u16 = 0x00;
+pub const SUB: u16 = 0x10;
+pub const MUL: u16 = 0x20;
+pub const DIV: u16 = 0x30;
+pub const OR: u16 = 0x40;
+pub const AND: u16 = 0x50;
+pub const LSH: u16 = 0x60;
+pub const RSH: u16 = 0x70;
+pub const NEG: u16 = 0x80;
+pub const MOD: u16 = 0x90;
+pub const XOR: u16 = 0xa0;
+
+pub const JA: u16 = 0x00;
+pub const JEQ: u16 = 0x10;
+pub const JGT: u16 = 0x20;
+pub const JGE: u16 = 0x30;
+pub const JSET: u16 = 0x40;
+
+// Source operand
+pub const K: u16 = 0x00;
+pub const X: u16 = 0x08;
+
+// RET source
+pub const A: u16 = 0x10;
+
+// MISC operations
+pub const TAX: u16 = 0x00;
+pub const TXA: u16 = 0x80;
+
+// BPFInstruction has the layout of struct sock_filter.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct BPFInstruction {
+    pub OpCode: u16,
+    pub JumpIfTrue: u8,
+    pub JumpIfFalse: u8,
+    pub K: u32,
+}
+
+impl BPFInstruction {
+    pub fn Stmt(code: u16, k: u32) -> Self {
+        return Self {
+            OpCode: code,
+            JumpIfTrue: 0,
+            JumpIfFalse: 0,
+            K: k,
+        };
+    }
+
+    pub fn Jump(code: u16, k: u32, jt: u8, jf: u8) -> Self {
+        return Self {
+            OpCode: code,
+            JumpIfTrue: jt,
+            JumpIfFalse: jf,
+            K: k,
+        };
+    }
+}
+
+fn Class(code: u16) -> u16 {
+    return code & 0x07;
+}
+
+fn Size(code: u16) -> u16 {
+    return code & 0x18;
+}
+
+fn Mode(code: u16) -> u16 {
+    return code & 0xe0;
+}
+
+fn Op(code: u16) -> u16 {
+    return code & 0xf0;
+}
+
+fn Src(code: u16) -> u16 {
+    return code & 0x08;
+}
+
+fn RVal(code: u16) -> u16 {
+    return code & 0x18;
+}
+
+// Validate checks the program the same way as the kernel does before attaching
+// it: the opcodes are known, the jumps stay in the program, the scratch memory
+// index is valid and the last instruction is a return.
+pub fn Validate(prog: &[BPFInstruction]) -> Result<()> {
+    if prog.len() == 0 || prog.len() > MAX_INSNS {
+        return Err(Error::Common(format!("bpf: invalid program length {}", prog.len())));
+    }
+
+    for (pc, ins) in prog.iter().enumerate() {
+        let code = ins.OpCode;
+        let valid = match Class(code) {
+            LD | LDX => match Mode(code) {
+                IMM | LEN => true,
+                ABS | IND => Class(code) == LD && Size(code) != 0x18,
+                MEM => (ins.K as usize) < SCRATCH_MEM_SLOTS,
+                MSH => Class(code) == LDX && Size(code) == B,
+                _ => false,
+            },
+            ST | STX => (ins.K as usize) < SCRATCH_MEM_SLOTS,
+            ALU => match Op(code) {
+                ADD | SUB | MUL | OR | AND | LSH | RSH | NEG | XOR => true,
+                // a constant divisor of zero is rejected up front
+                DIV | MOD => Src(code) == X || ins.K != 0,
+                _ => false,
+            },
+            JMP => {
+                let remain = (prog.len() - pc - 1) as u64;
+                match Op(code) {
+                    JA => (ins.K as u64) < remain,
+                    JEQ | JGT | JGE | JSET => {
+                        (ins.JumpIfTrue as u64) < remain && (ins.JumpIfFalse as u64) < remain
+                    }
+                    _ => false,
+                }
+            }
+            RET => RVal(code) != 0x18,
+            MISC => Op(code) == TAX || Op(code) == TXA,
+            _ => false,
+        };
+
+        if !valid {
+            return Err(Error::Common(format!(
+                "bpf: invalid instruction {} {:?}",
+                pc, ins
+            )));
+        }
+    }
+
+    if Class(prog[prog.len() - 1].OpCode) != RET {
+        return Err(Error::Common("bpf: program doesn't end with a return".to_string()));
+    }
+
+    return Ok(());
+}
+
+// ValidateSeccomp checks a seccomp program over the seccomp_data of dataLen
+// bytes: the loads from the data are aligned words, as in seccomp_check_filter.
+pub fn ValidateSeccomp(prog: &[BPFInstruction], dataLen: usize) -> Result<()> {
+    Validate(prog)?;
+
+    for (pc, ins) in prog.iter().enumerate() {
+        let code = ins.OpCode;
+        let valid = match Class(code) {
+            LD | LDX => match Mode(code) {
+                IMM | MEM => true,
+                LEN => Size(code) == W,
+                ABS => {
+                    Class(code) == LD
+                        && Size(code) == W
+                        && ins.K % 4 == 0
+                        && (ins.K as usize) + 4 <= dataLen
+                }
+                _ => false,
+            },
+            _ => true,
+        };
+
+        if !valid {
+            return Err(Error::Common(format!(
+                "bpf: invalid seccomp instruction {} {:?}",
+                pc, ins
+            )));
+        }
+    }
+
+    return Ok(());
+}
+
+fn Load(data: &[u8], offset: u32, size: u16, bigEndian: bool) -> Option<u32> {
+    let offset = offset as usize;
+    let len = match size {
+        W => 4,
+        H => 2,
+        _ => 1,
+    };
+
+    if offset.checked_add(len)? > data.len() {
+        return None;
+    }
+
+    let mut v = 0u32;
+    if bigEndian {
+        for b in &data[offset..offset + len] {
+            v = (v << 8) | *b as u32;
+        }
+    } else {
+        for b in data[offset..offset + len].iter().rev() {
+            v = (v << 8) | *b as u32;
+        }
+    }
+
+    return Some(v);
+}
+
+// Exec runs a validated program over the packet and returns its verdict. The
+// loads are big endian and a load out of the packet returns 0, i.e. drop, as
+// in the kernel.
+pub fn Exec(prog: &[BPFInstruction], data: &[u8]) -> u32 {
+    return Run(prog, data, true);
+}
+
+// ExecNative runs a validated program over a struct of the host byte order,
+// e.g. the seccomp_data of a syscall.
+pub fn ExecNative(prog: &[BPFInstruction], data: &[u8]) -> u32 {
+    return Run(prog, data, false);
+}
+
+fn Run(prog: &[BPFInstruction], data: &[u8], bigEndian: bool) -> u32 {
+    let mut a: u32 = 0;
+    let mut x: u32 = 0;
+    let mut mem = [0u32; SCRATCH_MEM_SLOTS];
+    let mut pc = 0;
+
+    while pc < prog.len() {
+        let ins = prog[pc];
+        let code = ins.OpCode;
+        pc += 1;
+
+        match Class(code) {
+            LD => {
+                a = match Mode(code) {
+                    IMM => ins.K,
+                    LEN => data.len() as u32,
+                    MEM => mem[ins.K as usize],
+                    ABS => match Load(data, ins.K, Size(code), bigEndian) {
+                        None => return 0,
+                        Some(v) => v,
+                    },
+                    _ => match Load(data, x.wrapping_add(ins.K), Size(code), bigEndian) {
+                        None => return 0,
+                        Some(v) => v,
+                    },
+                }
+            }
+            LDX => {
+                x = match Mode(code) {
+                    IMM => ins.K,
+                    LEN => data.len() as u32,
+                    MEM => mem[ins.K as usize],
+                    // the IPv4 header length
+                    _ => match Load(data, ins.K, B, bigEndian) {
+                        None => return 0,
+                        Some(v) => (v & 0xf) << 2,
+                    },
+                }
+            }
+            ST => mem[ins.K as usize] = a,
+            STX => mem[ins.K as usize] = x,
+            ALU => {
+                let v = if Src(code) == X { x } else { ins.K };
+                a = match Op(code) {
+                    ADD => a.wrapping_add(v),
+                    SUB => a.wrapping_sub(v),
+                    MUL => a.wrapping_mul(v),
+                    DIV => {
+                        if v == 0 {
+                            return 0;
+                        }
+                        a / v
+                    }
+                    MOD => {
+                        if v == 0 {
+                            return 0;
+                        }
+                        a % v
+                    }
+                    OR => a | v,
+                    AND => a & v,
+                    LSH => a.checked_shl(v).unwrap_or(0),
+                    RSH => a.checked_shr(v).unwrap_or(0),
+                    NEG => (a as i32).wrapping_neg() as u32,
+                    _ => a ^ v,
+                }
+            }
+            JMP => {
+                if Op(code) == JA {
+                    pc += ins.K as usize;
+                    continue;
+                }
+
+                let v = if Src(code) == X { x } else { ins.K };
+                let cond = match Op(code) {
+                    JEQ => a == v,
+                    JGT => a > v,
+                    JGE => a >= v,
+                    _ => a & v != 0,
+                };
+
+                pc += if cond {
+                    ins.JumpIfTrue as usize
+                } else {
+                    ins.JumpIfFalse as usize
+                };
+            }
+            RET => {
+                return match RVal(code) {
+                    X => x,
+                    A => a,
+                    _ => ins.K,
+                }
+            }
+            _ => {
+                if Op(code) == TXA {
+                    a = x;
+                } else {
+                    x = a;
+                }
+            }
+        }
+    }
+
+    return 0;
+}
+
+// Parse reads a program in the "tcpdump -ddd" format: the number of
+// instructions on the first line, then one "code jt jf k" line each.
+pub fn Parse(text: &str) -> Result<Vec<BPFInstruction>> {
+    let mut lines = text.lines().map(|l| l.trim()).filter(|l| l.len() > 0);
+    let count = match lines.next().map(|l| l.parse::<usize>()) {
+        Some(Ok(count)) => count,
+        _ => return Err(Error::Common("bpf: missing instruction count".to_string())),
+    };
+
+    let mut prog = Vec::with_capacity(count);
+    for line in lines {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let ins = match fields.as_slice() {
+            [code, jt, jf, k] => match (code.parse(), jt.parse(), jf.parse(), k.parse()) {
+                (Ok(code), Ok(jt), Ok(jf), Ok(k)) => BPFInstruction::Jump(code, k, jt, jf),
+                _ => return Err(Error::Common(format!("bpf: invalid instruction {}", line))),
+            },
+            _ => return Err(Error::Common(format!("bpf: invalid instruction {}", line))),
+        };
+        prog.push(ins);
+    }
+
+    if prog.len() != count {
+        return Err(Error::Common(format!(
+            "bpf: expect {} instructions, got {}",
+            count,
+            prog.len()
+        )));
+    }
+
+    Validate(&prog)?;
+    return Ok(prog);
+}
+
+pub fn Dump(prog: &[BPFInstruction]) -> String {
+    let mut s = format!("{}\n", prog.len());
+    for ins in prog {
+        s += &format!("{} {} {} {}\n", ins.OpCode, ins.JumpIfTrue, ins.JumpIfFalse, ins.K);
+    }
+    return s;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // tcpdump -y RAW -ddd 'tcp dst port 22'
+    const TCP_PORT_22: &str = "12
+48 0 0 0
+84 0 0 240
+21 0 8 64
+48 0 0 9
+21 0 6 6
+40 0 0 6
+69 4 0 8191
+177 0 0 0
+72 0 0 2
+21 0 1 22
+6 0 0 262144
+6 0 0 0
+";
+
+    fn Packet(proto: u8, dport: u16) -> Vec<u8> {
+        let mut p = vec![0u8; 40];
+        p[0] = 0x45;
+        p[9] = proto;
+        p[22..24].copy_from_slice(&dport.to_be_bytes());
+        return p;
+    }
+
+    #[test]
+    fn TestExec() {
+        let prog = Parse(TCP_PORT_22).unwrap();
+        assert_eq!(Exec(&prog, &Packet(6, 22)), 262144);
+        assert_eq!(Exec(&prog, &Packet(6, 80)), 0);
+        assert_eq!(Exec(&prog, &Packet(17, 22)), 0);
+        // a truncated packet is dropped
+        assert_eq!(Exec(&prog, &Packet(6, 22)[..21]), 0);
+    }
+
+    #[test]
+    fn TestValidate() {
+        assert!(Validate(&[]).is_err());
+        // jump out of the program
+        assert!(Validate(&[BPFInstruction::Jump(JMP | JEQ | K, 0, 1, 0)]).is_err());
+        // division by constant zero
+        assert!(Validate(&[
+            BPFInstruction::Stmt(ALU | DIV | K, 0),
+            BPFInstruction::Stmt(RET | K, 0)
+        ])
+        .is_err());
+        // no return at the end
+        assert!(Validate(&[BPFInstruction::Stmt(LD | IMM, 1)]).is_err());
+
+        let prog = Parse(TCP_PORT_22).unwrap();
+        assert_eq!(Parse(&Dump(&prog)).unwrap(), prog);
+    }
+
+    #[test]
+    fn TestSeccomp() {
+        // return errno 1 for the syscall 39, allow the others
+        let prog = [
+            BPFInstruction::Stmt(LD | W | ABS, 0),
+            BPFInstruction::Jump(JMP | JEQ | K, 39, 0, 1),
+            BPFInstruction::Stmt(RET | K, 0x50001),
+            BPFInstruction::Stmt(RET | K, 0x7fff0000),
+        ];
+        assert!(ValidateSeccomp(&prog, 64).is_ok());
+
+        let mut data = [0u8; 64];
+        data[0..4].copy_from_slice(&39u32.to_ne_bytes());
+        assert_eq!(ExecNative(&prog, &data), 0x50001);
+        data[0..4].copy_from_slice(&40u32.to_ne_bytes());
+        assert_eq!(ExecNative(&prog, &data), 0x7fff0000);
+
+        // a load out of the data or not aligned
+        let mut bad = prog;
+        bad[0] = BPFInstruction::Stmt(LD | W | ABS, 64);
+        assert!(ValidateSeccomp(&bad, 64).is_err());
+        bad[0] = BPFInstruction::Stmt(LD | H | ABS, 2);
+        assert!(ValidateSeccomp(&bad, 64).is_err());
+    }
+}
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     pub DebugLevel: DebugLevel,
     pub KernelMemSize: u64,
@@ -35,6 +35,114 @@ pub struct Config {
     pub ShimMode: bool,
     pub EnableInotify: bool,
     pub ReaddirCache: bool,
+    pub PosixAcl: bool,
+    // MemoryHigh is the memory.high of the sandbox in MB, 0 disables it.
+    pub MemoryHigh: u64,
+    // the percent of the sandbox memory dirty in the host files at which the
+    // background writeback starts, and at which the writers are throttled.
+    pub DirtyBackgroundRatio: u64,
+    pub DirtyRatio: u64,
+    // StrictDurability turns off the buffered file write so every write
+    // returns its own error, and a failed write fails all the later fsync.
+    pub StrictDurability: bool,
+    // EphemeralStorage is the limit in MB of the root filesystem and the tmpfs
+    // writes of the sandbox, 0 is unlimited.
+    pub EphemeralStorage: u64,
+    // EphemeralStorageUsed is the bytes the writable layer of the root
+    // filesystem holds at the boot, they are charged to the ephemeral storage.
+    pub EphemeralStorageUsed: u64,
+    // UringWaitUs is how long in us the idle IO thread waits in io_uring_enter
+    // for a completion before it polls again, 0 keeps it spinning.
+    pub UringWaitUs: u64,
+    // the threads of the latency and the bulk lanes of the host pool for the
+    // blocking qcalls, 0 latency threads processes them in place.
+    pub QcallLatencyThreads: usize,
+    pub QcallBulkThreads: usize,
+    // DirtyRingSize is the entries of the per vcpu KVM dirty ring tracking the
+    // guest pages written, for the pre-copy of the hibernation image. It is
+    // rounded up to a power of 2, 0 turns the tracking off.
+    pub DirtyRingSize: u32,
+    // OopsKill kills only the process of the task hitting a fault of the
+    // kernel in its copy of the user memory, instead of the whole sandbox.
+    pub OopsKill: bool,
+    // SyscallFuzz lets a task of the sandbox run the syscall fuzzing hooks
+    // with prctl(PR_QUARK_SYSCALL_FUZZ), for the fuzzing of the kernel only.
+    pub SyscallFuzz: bool,
+    // BuiltinInit has the kernel reap the orphans reparented to the init
+    // process of the sandbox and forward the signals of the host to the
+    // process group of the init, for the images whose entrypoint doesn't reap
+    // the zombies and pass on the signals as an init does.
+    pub BuiltinInit: bool,
+    // HaltPollNs is the longest time in ns an idle vcpu spins for a task
+    // before it blocks, 0 turns the halt polling off. The poll time of a vcpu
+    // adapts to its wakeups as the halt polling of KVM, it grows by
+    // HaltPollGrow times when the vcpu is woken up soon after it blocks, and
+    // is divided by HaltPollShrink, or reset with 0, when it sleeps longer.
+    pub HaltPollNs: u64,
+    pub HaltPollGrow: u64,
+    pub HaltPollShrink: u64,
+    // TscDeadlineTimer has the vcpus preempt the tasks with the TSC deadline
+    // timer of their local APIC kept in KVM, instead of qvisor kicking them,
+    // and sample the host clock with the KVM pvclock instead of a hypercall.
+    pub TscDeadlineTimer: bool,
+    // AsyncPageFault has KVM send the page faults of the application on the
+    // host pages swapped out to the guest, the faulting task waits for the
+    // page instead of its vcpu.
+    pub AsyncPageFault: bool,
+    // StealTime has KVM account the time the host didn't run the vcpus, the
+    // scheduler places the tasks on the vcpus the host runs the most.
+    pub StealTime: bool,
+    // UdpOffload has the host segment the sendmmsg batches of the udp sockets
+    // with UDP_SEGMENT and coalesce their receives with UDP_GRO.
+    pub UdpOffload: bool,
+    // ChecksumOffload lets a sandbox turn on the tx and rx checksum offload of
+    // the interfaces of its network namespace, see quark.network.checksum_offload.
+    pub ChecksumOffload: bool,
+    // RecvSteering spreads the processing of the host socket events over the
+    // idle vcpus by the flow hash of the socket instead of the io thread.
+    pub RecvSteering: bool,
+    // TimerDispatch fires the due timers on the least loaded idle vcpus in
+    // the order of their deadlines instead of the io thread.
+    pub TimerDispatch: bool,
+    // SoMaxConn caps the listen backlog of the sandbox sockets, it is also the
+    // net.core.somaxconn of the sandbox network namespace.
+    pub SoMaxConn: i32,
+    // SynCookies turns on the SYN cookies of the sandbox network namespace, a
+    // SYN flood overflowing the backlog of a listener doesn't drop the new
+    // connections then.
+    pub SynCookies: bool,
+    // PauseKeepalive hands the keepalives of the idle connections with
+    // SO_KEEPALIVE to the host while the sandbox is paused, every
+    // PauseKeepaliveIdle seconds, so that they survive the pause.
+    pub PauseKeepalive: bool,
+    pub PauseKeepaliveIdle: i32,
+    // UsagePeriodMs is how often in ms the resource usage page of the sandbox
+    // is updated for the autoscaling agents, 0 turns it off.
+    pub UsagePeriodMs: u64,
+    // HibernateIdleSec is how long in seconds the sandbox stays idle before
+    // its guest memory is written to disk and released, 0 turns it off.
+    pub HibernateIdleSec: u64,
+    // ZswapMaxPoolPercent is the percent of the sandbox memory the compressed
+    // tier of the cold pages can take when the guest is over memory.high, 0
+    // turns it off.
+    pub ZswapMaxPoolPercent: u64,
+    // Ksm marks the guest memory holding the private copies of the image file
+    // pages mergeable for the KSM of the host, see qlib/mem/ksm.rs.
+    pub Ksm: bool,
+    // KsmAnon marks the guest memory holding the anonymous pages mergeable
+    // too.
+    pub KsmAnon: bool,
+    // PrefetchMaxMb bounds the MB of the entrypoint of a container and of its
+    // libraries read ahead at its creation, 0 turns the read ahead off.
+    pub PrefetchMaxMb: u64,
+    // AuditClasses is the mask of the AUDIT_* classes of qlib/audit.rs of the
+    // guest syscalls streamed to the audit clients of the control socket, 0
+    // turns the audit off.
+    pub AuditClasses: u64,
+    // MitigationsOff relaxes the speculation controls of the vcpu threads of
+    // the sandboxes which don't ask for mitigations, see
+    // qvisor/src/runc/runtime/mitigations.rs.
+    pub MitigationsOff: bool,
 }
 
 impl Config {
@@ -47,7 +155,96 @@ impl Config {
     }
 }
 
-impl Config {}
+impl Config {
+    // Update applies the runtime reloadable fields, returns whether anything changed.
+    pub fn Update(&mut self, update: &ConfigUpdate) -> bool {
+        let old = *self;
+
+        if let Some(v) = update.DebugLevel {
+            self.DebugLevel = v;
+        }
+
+        if let Some(v) = update.LogLevel {
+            self.LogLevel = v;
+        }
+
+        if let Some(v) = update.PerfDebug {
+            self.PerfDebug = v;
+        }
+
+        if let Some(v) = update.PrintException {
+            self.PrintException = v;
+        }
+
+        if let Some(v) = update.ReaddirCache {
+            self.ReaddirCache = v;
+        }
+
+        if let Some(v) = update.PosixAcl {
+            self.PosixAcl = v;
+        }
+
+        if let Some(v) = update.MemoryHigh {
+            self.MemoryHigh = v;
+        }
+
+        if let Some(v) = update.HaltPollNs {
+            self.HaltPollNs = v;
+        }
+
+        if let Some(v) = update.HaltPollGrow {
+            self.HaltPollGrow = v;
+        }
+
+        if let Some(v) = update.HaltPollShrink {
+            self.HaltPollShrink = v;
+        }
+
+        if let Some(v) = update.UringWaitUs {
+            self.UringWaitUs = v;
+        }
+
+        if let Some(v) = update.DirtyBackgroundRatio {
+            self.DirtyBackgroundRatio = v;
+        }
+
+        if let Some(v) = update.DirtyRatio {
+            self.DirtyRatio = v;
+        }
+
+        if let Some(v) = update.Ksm {
+            self.Ksm = v;
+        }
+
+        if let Some(v) = update.KsmAnon {
+            self.KsmAnon = v;
+        }
+
+        return old != *self;
+    }
+}
+
+// ConfigUpdate is the subset of Config which can be reloaded through the control
+// socket while the sandbox is running. The other fields are only used at boot time
+// or change the layout of shared structures.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct ConfigUpdate {
+    pub DebugLevel: Option<DebugLevel>,
+    pub LogLevel: Option<LogLevel>,
+    pub PerfDebug: Option<bool>,
+    pub PrintException: Option<bool>,
+    pub ReaddirCache: Option<bool>,
+    pub PosixAcl: Option<bool>,
+    pub MemoryHigh: Option<u64>,
+    pub HaltPollNs: Option<u64>,
+    pub HaltPollGrow: Option<u64>,
+    pub HaltPollShrink: Option<u64>,
+    pub UringWaitUs: Option<u64>,
+    pub DirtyBackgroundRatio: Option<u64>,
+    pub DirtyRatio: Option<u64>,
+    pub Ksm: Option<bool>,
+    pub KsmAnon: Option<bool>,
+}
 
 impl Default for Config {
     fn default() -> Self {
@@ -73,6 +270,42 @@ impl Default for Config {
             ShimMode: false,
             EnableInotify: false,
             ReaddirCache: true,
+            PosixAcl: false,
+            MemoryHigh: 0,
+            DirtyBackgroundRatio: 10,
+            DirtyRatio: 20,
+            StrictDurability: false,
+            EphemeralStorage: 0,
+            EphemeralStorageUsed: 0,
+            UringWaitUs: 0,
+            QcallLatencyThreads: 4,
+            QcallBulkThreads: 2,
+            DirtyRingSize: 0,
+            OopsKill: false,
+            SyscallFuzz: false,
+            BuiltinInit: false,
+            HaltPollNs: 0,
+            HaltPollGrow: 2,
+            HaltPollShrink: 0,
+            TscDeadlineTimer: false,
+            AsyncPageFault: false,
+            StealTime: false,
+            UdpOffload: false,
+            ChecksumOffload: false,
+            RecvSteering: false,
+            TimerDispatch: false,
+            SoMaxConn: 4096,
+            SynCookies: true,
+            PauseKeepalive: false,
+            PauseKeepaliveIdle: 30,
+            UsagePeriodMs: 1000,
+            HibernateIdleSec: 0,
+            ZswapMaxPoolPercent: 0,
+            Ksm: false,
+            KsmAnon: false,
+            PrefetchMaxMb: 0,
+            AuditClasses: 0,
+            MitigationsOff: false,
         };
     }
 }
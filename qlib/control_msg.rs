@@ -18,8 +18,11 @@ use core::sync::atomic::AtomicU64;
 use core::sync::atomic::Ordering;
 
 use super::auth::id::*;
+use super::config::*;
+use super::limits::*;
 use super::loader::*;
 use super::singleton::*;
+use super::usage::sandbox::SandboxUsage;
 
 type Cid = String;
 
@@ -103,6 +106,50 @@ pub struct StartArgs {
     pub process: Process,
 }
 
+/// TlsAcceptArgs is payload for TlsAccept control msg to quark sandbox, it
+/// hands a connection of the TLS termination of qvisor to the guest listener
+/// of the plain port.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TlsAcceptArgs {
+    // port is the plain port the guest listens on
+    pub port: u16,
+
+    // fd is the plaintext end of the connection
+    pub fd: i32,
+
+    // addr is the sockaddr of the TLS client
+    pub addr: Vec<u8>,
+}
+
+/// ProcessResourcesArgs is payload for ProcessResources control msg to quark
+/// sandbox, it gets the resources of a process and sets the given ones.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProcessResourcesArgs {
+    // cid is the container of the process, empty is the root container
+    pub cid: String,
+
+    // pid is the process ID in the container, 0 is the container init process
+    pub pid: i32,
+
+    // the rlimits to set
+    pub limits: Vec<(LimitType, Limit)>,
+
+    // the nice value to set to all the threads of the process
+    pub nice: Option<i32>,
+
+    pub oomScoreAdj: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessResources {
+    pub pid: i32,
+    pub limits: Vec<(LimitType, Limit)>,
+
+    // the nice value of the thread group leader
+    pub nice: i32,
+    pub oomScoreAdj: i32,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Payload {
     RootContainerStart(RootProcessStart),
@@ -112,11 +159,18 @@ pub enum Payload {
     WaitContainer(Cid),
     WaitPid(WaitPid),
     Ps(Cid),
+    Tasks(Cid),
+    Sockets(Cid),
     Signal(SignalArgs),
     ContainerDestroy(Cid),
     CreateSubContainer(CreateArgs),
     StartSubContainer(StartArgs),
     WaitAll,
+    UpdateConfig(ConfigUpdate),
+    UpdateEtcFiles(EtcFiles),
+    ProcessResources(ProcessResourcesArgs),
+    FlushRootfs(Cid),
+    TlsAccept(TlsAcceptArgs),
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -137,6 +191,8 @@ pub enum UCallResp {
     PauseResp,
     UnpauseResp,
     PsResp(Vec<ProcessInfo>),
+    TasksResp(Vec<TaskInfo>),
+    SocketsResp(Vec<SocketInfo>),
     WaitContainerResp(u32),
     WaitPidResp(u32),
     SignalResp,
@@ -144,6 +200,15 @@ pub enum UCallResp {
     CreateSubContainerResp,
     StartSubContainerResp,
     WaitAllResp(WaitAllResp),
+    UpdateConfigResp(Config),
+    UpdateEtcFilesResp,
+    ProcessResourcesResp(ProcessResources),
+    VersionResp(u32),
+    StatsResp(SandboxUsage),
+    ConfigResp(Config),
+    AuditStreamResp,
+    FlushRootfsResp,
+    TlsAcceptResp,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -160,6 +225,34 @@ pub struct ProcessInfo {
     pub Time: i64,
     // Executable shortname (e.g. "sh" for /bin/sh)
     pub Cmd: String,
+    // Resident set size in bytes
+    pub RSS: u64,
+    // Virtual memory size in bytes
+    pub VSZ: u64,
+    // Thread count
+    pub Threads: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TaskInfo {
+    pub PID: i32,
+    pub TID: i32,
+    // Task state, same as the State field of /proc/[pid]/status
+    pub State: String,
+    // User and system CPU time in nanoseconds
+    pub UserTime: i64,
+    pub SysTime: i64,
+    pub Cmd: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SocketInfo {
+    pub PID: i32,
+    pub FD: i32,
+    pub Family: String,
+    pub Type: String,
+    pub LocalAddr: String,
+    pub PeerAddr: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -384,6 +384,15 @@ pub enum X86Feature {
 // block.
 const LINUX_BLOCK_ORDER: [Block; 7] = [1, 6, 0, 5, 2, 4, 3];
 
+// HWCAP2_FSGSBASE indicates that the kernel allows the fsgsbase instructions.
+pub const HWCAP2_FSGSBASE: u64 = 1 << 1;
+
+// The signal frame layout constants used to compute AT_MINSIGSTKSZ.
+pub const RT_SIGFRAME_SIZE: u64 = 440;
+pub const FP_XSTATE_MAGIC2_SIZE: u64 = 4;
+pub const SIGFRAME_ALIGNMENT: u64 = 64;
+pub const SIGFRAME_REDZONE: u64 = 128;
+
 // The constants below are the lower or "standard" cpuid functions, ordered as
 // defined by the hardware.
 #[allow(non_camel_case_types)]
@@ -448,7 +457,7 @@ pub enum XSAVEFeature {
     XSAVEFeaturePKRU = 1 << 9,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Feature(pub i32);
 
 impl Feature {
@@ -464,6 +473,24 @@ impl Feature {
         return format!("<cpuflag {}; block {} bit {}>", self.0, block, bit);
     }
 
+    // CpuidLocation returns the cpuid function, index and register of the
+    // feature and its bit mask, the register is 0 to 3 for eax to edx.
+    pub fn CpuidLocation(&self) -> Option<(u32, u32, usize, u32)> {
+        let mask = 1 << (self.0 % 32);
+        let loc = match self.0 / 32 {
+            0 => (1, 0, 2),
+            1 => (1, 0, 3),
+            2 => (CpuidFunction::extendedFeatureInfo as u32, 0, 1),
+            3 => (CpuidFunction::extendedFeatureInfo as u32, 0, 2),
+            4 => (CpuidFunction::xSaveInfo as u32, 1, 0),
+            5 => (ExtendedFunction::extendedFeatures as u32, 0, 2),
+            6 => (ExtendedFunction::extendedFeatures as u32, 0, 3),
+            _ => return None,
+        };
+
+        return Some((loc.0, loc.1, loc.2, mask));
+    }
+
     fn flagString(&self, cpuinfoOnly: bool) -> &'static str {
         match X86_FEATURE_STRINGS.Get(self.0) {
             Some(s) => return s,
@@ -480,6 +507,29 @@ impl Feature {
     }
 }
 
+// FeaturesByName returns the features with the cpuinfo flag name, a name
+// ending with '*' matches all the flags with the prefix, e.g. "avx512*".
+pub fn FeaturesByName(name: &str) -> Vec<Feature> {
+    let matches = |s: &str| -> bool {
+        match name.strip_suffix('*') {
+            Some(prefix) => return s.starts_with(prefix),
+            None => return s == name,
+        }
+    };
+
+    let mut features = Vec::new();
+    for (f, s) in X86_FEATURE_STRINGS
+        .iter()
+        .chain(X86_FEATURE_PARSE_ONLY_STRINGS.iter())
+    {
+        if matches(s) {
+            features.push(Feature(*f));
+        }
+    }
+
+    return features;
+}
+
 fn featureID(b: Block, bit: i32) -> Feature {
     return Feature(32 * b + bit);
 }
@@ -509,6 +559,9 @@ pub struct FeatureSet {
 
     // SteppingID is part of the processor signature.
     pub SteppingID: u8,
+
+    // ModelName is the brand string returned by eax=0x80000002-0x80000004.
+    pub ModelName: String,
 }
 
 impl FeatureSet {
@@ -549,7 +602,12 @@ impl FeatureSet {
             "model\t\t: {}\n",
             ((fs.ExtendedModel << 4) & 0xff) | fs.Model
         );
-        res += &format!("model name\t: {}\n", "unknown");
+        let modelName = if fs.ModelName.len() > 0 {
+            fs.ModelName.as_str()
+        } else {
+            "unknown"
+        };
+        res += &format!("model name\t: {}\n", modelName);
         res += &format!("stepping\t: {}\n", "unknown");
         res += &format!("cpu MHz\t\t: {}\n", *CPU_FREQ_MHZ.lock());
         res += &format!("fpu\t\t: yes\n");
@@ -693,6 +751,33 @@ impl FeatureSet {
         return (ax, bx, cx, dx);
     }
 
+    // HWCap returns the value for the AT_HWCAP auxv entry, which on x86_64 is
+    // the edx register of the featureInfo cpuid function.
+    pub fn HWCap(&self) -> u64 {
+        return self.blockMask(1) as u64;
+    }
+
+    // HWCap2 returns the value for the AT_HWCAP2 auxv entry. qkernel enables
+    // CR4.FSGSBASE for the guest so the user space can use the fsgsbase instructions.
+    pub fn HWCap2(&self) -> u64 {
+        let mut hwcap2 = 0;
+        if self.HasFeature(Feature(X86Feature::X86FeatureFSGSBase as i32)) {
+            hwcap2 |= HWCAP2_FSGSBASE;
+        }
+
+        return hwcap2;
+    }
+
+    // MinSigStackSize returns the value for the AT_MINSIGSTKSZ auxv entry: the
+    // size of the rt_sigframe plus the extended state saved with it and the
+    // red zone, aligned as linux does in arch/x86/kernel/signal.c.
+    pub fn MinSigStackSize(&self) -> u64 {
+        let (xstateSize, _) = self.ExtendedStateSize();
+        let frameSize = RT_SIGFRAME_SIZE + xstateSize as u64 + FP_XSTATE_MAGIC2_SIZE;
+        let frameSize = (frameSize + SIGFRAME_ALIGNMENT - 1) & !(SIGFRAME_ALIGNMENT - 1);
+        return frameSize + SIGFRAME_REDZONE;
+    }
+
     pub fn UseXsave(&self) -> bool {
         return self.HasFeature(Feature(X86Feature::X86FeatureXSAVE as i32))
             && self.HasFeature(Feature(X86Feature::X86FeatureOSXSAVE as i32));
@@ -862,6 +947,15 @@ pub fn HostFeatureSet() -> FeatureSet {
         featureBlock6 = dx & !(X86Feature::Block6DuplicateMask as u32);
     }
 
+    let mut modelName = String::new();
+    if ax >= BRAND_STRING_FUNCTION + 2 {
+        modelName = brandStringFromRegs(&[
+            HostID(BRAND_STRING_FUNCTION, 0),
+            HostID(BRAND_STRING_FUNCTION + 1, 0),
+            HostID(BRAND_STRING_FUNCTION + 2, 0),
+        ]);
+    }
+
     let set = setFromBlockMasks(&[
         featureBlock0,
         featureBlock1,
@@ -880,9 +974,27 @@ pub fn HostFeatureSet() -> FeatureSet {
         Family: f,
         Model: m,
         SteppingID: sid,
+        ModelName: modelName,
     };
 }
 
+// the first of the 3 cpuid functions returning the 48-byte brand string
+pub const BRAND_STRING_FUNCTION: u32 = 0x80000002;
+
+// Helper to convert the regs of the 3 brand string functions into the brand
+// string, it is NUL padded and may have leading spaces.
+fn brandStringFromRegs(regs: &[(u32, u32, u32, u32); 3]) -> String {
+    let mut bytes: Vec<u8> = Vec::with_capacity(48);
+    for (ax, bx, cx, dx) in regs.iter() {
+        for r in &[*ax, *bx, *cx, *dx] {
+            bytes.extend_from_slice(&r.to_le_bytes());
+        }
+    }
+
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    return String::from_utf8_lossy(&bytes[..len]).trim().to_string();
+}
+
 // Helper to convert 3 regs into 12-byte vendor ID.
 fn vendorIDFromRegs(bx: u32, cx: u32, dx: u32) -> String {
     let mut bytes: Vec<u8> = Vec::with_capacity(12);
@@ -48,6 +48,27 @@ impl HostSpace {
         HyperCall64(HYPERCALL_URING_WAKE, minCompleted, 0, 0);
     }
 
+    // HwRandom reads from the host rng device, it works before the qcall is ready.
+    pub fn HwRandom(buf: u64, len: u64) -> i64 {
+        let ret: i64 = 0;
+        HyperCall64(HYPERCALL_RNG, buf, len, &ret as *const _ as u64);
+        return unsafe { core::ptr::read_volatile(&ret) };
+    }
+
+    // PluginCall calls the qcall plugin id of qvisor with the buffer addr/len,
+    // it returns -ENOSYS if qvisor has no such plugin.
+    pub fn PluginCall(id: u16, addr: u64, len: u64) -> i64 {
+        assert!(id < HYPERCALL_PLUGIN_COUNT);
+        let ret: i64 = -SysErr::ENOSYS as i64;
+        HyperCall64(
+            HYPERCALL_PLUGIN_BASE + id,
+            addr,
+            len,
+            &ret as *const _ as u64,
+        );
+        return unsafe { core::ptr::read_volatile(&ret) };
+    }
+
     pub fn LoadProcessKernel(processAddr: u64, len: usize) -> i64 {
         let mut msg = Msg::LoadProcessKernel(LoadProcessKernel {
             processAddr: processAddr,
@@ -80,6 +101,21 @@ impl HostSpace {
         return HostSpace::Call(&mut msg, false) as i64;
     }
 
+    // NetDevs fills the NetDevInfo array at addr with the interfaces of the
+    // sandbox network namespace, it returns the count filled.
+    pub fn NetDevs(addr: u64, count: usize) -> i64 {
+        let mut msg = Msg::NetDevs(NetDevs { addr, count });
+
+        return HostSpace::Call(&mut msg, false) as i64;
+    }
+
+    // PauseAbort undoes what qvisor has done for a pause the guest failed.
+    pub fn PauseAbort() -> i64 {
+        let mut msg = Msg::PauseAbort(PauseAbort {});
+
+        return HostSpace::Call(&mut msg, false) as i64;
+    }
+
     pub fn EventfdWrite(fd: i32) -> i64 {
         let mut msg = Msg::EventfdWrite(EventfdWrite { fd });
 
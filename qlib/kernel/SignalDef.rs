@@ -152,6 +152,15 @@ pub struct SigPoll {
     pub fd: i32,
 }
 
+/* SIGSYS */
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SigSys {
+    pub callAddr: u64,
+    pub syscall: i32,
+    pub arch: u32,
+}
+
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct SignalInfo {
@@ -240,6 +249,11 @@ impl SignalInfo {
         return unsafe { &mut *(addr as *mut SigPoll) };
     }
 
+    pub fn SigSys(&self) -> &mut SigSys {
+        let addr = &self.fields[0] as *const _ as u64;
+        return unsafe { &mut *(addr as *mut SigSys) };
+    }
+
     // SignalInfoUser (properly SI_USER) indicates that a signal was sent from
     // a kill() or raise() syscall.
     pub const SIGNAL_INFO_USER: i32 = 0;
@@ -0,0 +1,200 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::mutex::*;
+use super::super::super::super::vcpu_mgr::*;
+use super::super::super::asm::*;
+use super::super::super::kernel::waiter::cond::*;
+use super::super::super::task::*;
+use super::super::super::SHARESPACE;
+use super::tsc_timer::*;
+
+// The KVM async page faults, enabled with Config.AsyncPageFault. When a task
+// in the application touches a guest page the host has swapped out, or not
+// faulted in yet, KVM doesn't stall the vcpu till the host page is in but
+// injects a "page not present" page fault with a token. The task waits for
+// the token and the vcpu runs the other tasks. KVM brings the page in
+// asynchronously and sends the token back with the "page ready" interrupt of
+// the local APIC, the task then goes back to the faulting instruction.
+//
+// KVM only sends the async page faults of the application, the kernel runs
+// with the interrupts disabled. The page ready interrupts stay pending in the
+// APIC while the vcpu is in the kernel, the idle vcpus poll the token instead.
+
+pub const ASYNC_PF_VECTOR: u8 = 0xeb;
+
+const MSR_KVM_ASYNC_PF_EN: u32 = 0x4b564d02;
+const MSR_KVM_ASYNC_PF_INT: u32 = 0x4b564d06;
+const MSR_KVM_ASYNC_PF_ACK: u32 = 0x4b564d07;
+
+const KVM_ASYNC_PF_ENABLED: u64 = 1 << 0;
+const KVM_ASYNC_PF_DELIVERY_AS_INT: u64 = 1 << 3;
+
+const KVM_FEATURE_ASYNC_PF: u32 = 1 << 4;
+const KVM_FEATURE_ASYNC_PF_INT: u32 = 1 << 14;
+
+const KVM_PV_REASON_PAGE_NOT_PRESENT: u32 = 1;
+
+// the page ready token which wakes all the waits of the vcpu, sent when KVM
+// drops its async page faults
+const ASYNC_PF_WAKE_ALL: u32 = !0;
+
+static ASYNC_PF: AtomicBool = AtomicBool::new(false);
+
+// KvmApfData is the struct kvm_vcpu_pv_apf_data KVM updates, 64 bytes aligned.
+#[derive(Debug, Default)]
+#[repr(C)]
+#[repr(align(64))]
+pub struct KvmApfData {
+    // the reason of a page fault, KVM_PV_REASON_PAGE_NOT_PRESENT
+    pub flags: u32,
+    // the token of the page ready interrupt
+    pub token: u32,
+    pub pad: [u64; 7],
+    pub enabled: u32,
+}
+
+lazy_static! {
+    // the pending async page faults, by token. The cond of a token is
+    // signaled when its page is ready.
+    pub static ref ASYNC_PF_WAITS: QMutex<BTreeMap<u32, AsyncPfWait>> = QMutex::new(BTreeMap::new());
+}
+
+pub struct AsyncPfWait {
+    pub cond: Cond,
+    // the vcpu the page fault happened on, which gets the page ready
+    pub vcpuId: usize,
+}
+
+// InitAsyncPageFault enables the async page faults of the current vcpu, it is
+// called on each vcpu at its start.
+pub fn InitAsyncPageFault(enable: bool) -> bool {
+    if !enable {
+        return false;
+    }
+
+    let features = KvmFeatures();
+    if features & KVM_FEATURE_ASYNC_PF == 0 || features & KVM_FEATURE_ASYNC_PF_INT == 0 {
+        info!("async page fault: not supported by the vcpu");
+        return false;
+    }
+
+    if !InitX2Apic() {
+        return false;
+    }
+
+    let cpu = CPULocal::Myself();
+    unsafe { core::ptr::write_volatile(&cpu.apf.enabled as *const _ as *mut u32, 1) };
+    WriteMsr(MSR_KVM_ASYNC_PF_INT, ASYNC_PF_VECTOR as u64);
+    // the kernel memory is identity mapped, the address is the guest physical one
+    WriteMsr(
+        MSR_KVM_ASYNC_PF_EN,
+        &cpu.apf as *const _ as u64 | KVM_ASYNC_PF_ENABLED | KVM_ASYNC_PF_DELIVERY_AS_INT,
+    );
+
+    ASYNC_PF.store(true, Ordering::Release);
+    return true;
+}
+
+#[inline]
+pub fn AsyncPageFault() -> bool {
+    return ASYNC_PF.load(Ordering::Relaxed);
+}
+
+// AsyncPageNotPresent returns the token of the page fault being handled if it
+// is an async one. It has to be called before anything else can fault.
+#[inline]
+pub fn AsyncPageNotPresent(cr2: u64) -> Option<u32> {
+    if !AsyncPageFault() {
+        return None;
+    }
+
+    let apf = &CPULocal::Myself().apf;
+    let flags = unsafe { core::ptr::read_volatile(&apf.flags) };
+    if flags & KVM_PV_REASON_PAGE_NOT_PRESENT == 0 {
+        return None;
+    }
+
+    unsafe { core::ptr::write_volatile(&apf.flags as *const _ as *mut u32, 0) };
+    return Some(cr2 as u32);
+}
+
+// AsyncPfWaitPage blocks the task till the page of the token is ready. The
+// wait is uninterruptible as the host page fault it stands for.
+pub fn AsyncPfWaitPage(task: &Task, token: u32) {
+    let vcpuId = CPULocal::CpuId() as usize;
+    let cond = ASYNC_PF_WAITS
+        .lock()
+        .entry(token)
+        .or_insert_with(|| AsyncPfWait {
+            cond: Cond::default(),
+            vcpuId: vcpuId,
+        })
+        .cond
+        .clone();
+
+    let vcpu = &SHARESPACE.scheduler.VcpuArr[vcpuId];
+    vcpu.asyncPfWaits.fetch_add(1, Ordering::Relaxed);
+    cond.WaitUninterruptible(task);
+    vcpu.asyncPfWaits.fetch_sub(1, Ordering::Relaxed);
+    ASYNC_PF_WAITS.lock().remove(&token);
+}
+
+// AsyncPageReady wakes the wait of the page ready token of the current vcpu,
+// if any, and acks it to KVM so that it sends the next one.
+pub fn AsyncPageReady() {
+    let apf = &CPULocal::Myself().apf;
+    let token = unsafe { core::ptr::read_volatile(&apf.token) };
+    if token == 0 {
+        return;
+    }
+
+    unsafe { core::ptr::write_volatile(&apf.token as *const _ as *mut u32, 0) };
+    WriteMsr(MSR_KVM_ASYNC_PF_ACK, 1);
+
+    let mut waits = ASYNC_PF_WAITS.lock();
+    if token == ASYNC_PF_WAKE_ALL {
+        let vcpuId = CPULocal::CpuId() as usize;
+        for (_, wait) in waits.iter() {
+            if wait.vcpuId == vcpuId {
+                wait.cond.Broadcast();
+            }
+        }
+
+        return;
+    }
+
+    // the page can be ready before its task waits
+    waits
+        .entry(token)
+        .or_insert_with(|| AsyncPfWait {
+            cond: Cond::default(),
+            vcpuId: CPULocal::CpuId() as usize,
+        })
+        .cond
+        .Broadcast();
+}
+
+// PollAsyncPageReady takes the page ready of the current vcpu pending in its
+// APIC, for the vcpu in the kernel.
+#[inline]
+pub fn PollAsyncPageReady() {
+    if AsyncPageFault() {
+        AsyncPageReady();
+    }
+}
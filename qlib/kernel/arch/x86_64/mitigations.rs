@@ -0,0 +1,124 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::common::*;
+use super::super::super::super::cpuid::*;
+use super::super::super::asm::*;
+
+// The speculative execution mitigations of the guest kernel, set per sandbox:
+//   IBPB: barrier of the indirect branch predictors when the vcpu switches to
+//     a task of another address space
+//   L1D_FLUSH: flush of the L1 data cache before returning to the application
+//   MDS: clear of the cpu buffers (verw) before returning to the application
+// A mitigation the vcpu doesn't support is skipped.
+pub const MITIGATION_IBPB: u64 = 1 << 0;
+pub const MITIGATION_L1D_FLUSH: u64 = 1 << 1;
+pub const MITIGATION_MDS: u64 = 1 << 2;
+
+const MSR_IA32_PRED_CMD: u32 = 0x49;
+const PRED_CMD_IBPB: u64 = 1 << 0;
+const MSR_IA32_FLUSH_CMD: u32 = 0x10b;
+const L1D_FLUSH: u64 = 1 << 0;
+
+// cpuid 7.0 edx
+const CPUID_7_EDX_MD_CLEAR: u32 = 1 << 10;
+const CPUID_7_EDX_SPEC_CTRL: u32 = 1 << 26;
+const CPUID_7_EDX_FLUSH_L1D: u32 = 1 << 28;
+// cpuid 0x80000008 ebx, the IBPB of AMD
+const CPUID_80000008_EBX_IBPB: u32 = 1 << 12;
+
+static MITIGATIONS: AtomicU64 = AtomicU64::new(0);
+
+// AmdIbpb returns whether the vcpu has the IBPB of AMD, Intel reports it with
+// the spec ctrl bit of cpuid 7.
+fn AmdIbpb() -> bool {
+    let (maxExt, _, _, _) = HostID(0x80000000, 0);
+    if maxExt < 0x80000008 {
+        return false;
+    }
+
+    let (_, ebx, _, _) = HostID(0x80000008, 0);
+    return ebx & CPUID_80000008_EBX_IBPB != 0;
+}
+
+// InitMitigations enables the mitigations supported by the vcpu.
+pub fn InitMitigations(mitigations: u64) {
+    let (_, _, _, edx) = HostID(7, 0);
+    let mut enabled = 0;
+    for (flag, supported, name) in &[
+        (
+            MITIGATION_IBPB,
+            edx & CPUID_7_EDX_SPEC_CTRL != 0 || AmdIbpb(),
+            "ibpb",
+        ),
+        (
+            MITIGATION_L1D_FLUSH,
+            edx & CPUID_7_EDX_FLUSH_L1D != 0,
+            "l1d-flush",
+        ),
+        (MITIGATION_MDS, edx & CPUID_7_EDX_MD_CLEAR != 0, "mds"),
+    ] {
+        if mitigations & *flag == 0 {
+            continue;
+        }
+
+        if !*supported {
+            info!("mitigations: {} is not supported by the cpu, skipped", name);
+            continue;
+        }
+
+        info!("mitigations: {} enabled", name);
+        enabled |= *flag;
+    }
+
+    MITIGATIONS.store(enabled, Ordering::Release);
+}
+
+#[inline]
+pub fn Mitigations() -> u64 {
+    return MITIGATIONS.load(Ordering::Relaxed);
+}
+
+// AddressSpaceSwitch is called when the vcpu switches to a task of another
+// memory manager.
+#[inline]
+pub fn AddressSpaceSwitch() {
+    if Mitigations() & MITIGATION_IBPB != 0 {
+        WriteMsr(MSR_IA32_PRED_CMD, PRED_CMD_IBPB);
+    }
+}
+
+// ReturnToUser is called right before the vcpu returns to the application.
+#[inline]
+pub fn ReturnToUser() {
+    let mitigations = Mitigations();
+    if mitigations == 0 {
+        return;
+    }
+
+    if mitigations & MITIGATION_L1D_FLUSH != 0 {
+        WriteMsr(MSR_IA32_FLUSH_CMD, L1D_FLUSH);
+    }
+
+    // the L1D flush clears the cpu buffers as well
+    if mitigations & (MITIGATION_L1D_FLUSH | MITIGATION_MDS) == MITIGATION_MDS {
+        let ds: u16 = KDATA;
+        unsafe {
+            llvm_asm!("verw $0" : : "m" (ds) : "cc" : "volatile");
+        }
+    }
+}
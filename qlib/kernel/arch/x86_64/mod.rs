@@ -13,5 +13,9 @@
 // limitations under the License.
 
 pub mod arch_x86;
+pub mod async_pf;
 pub mod context;
+pub mod mitigations;
 pub mod signal;
+pub mod steal_time;
+pub mod tsc_timer;
@@ -0,0 +1,181 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::linux::time::*;
+use super::super::super::super::vcpu_mgr::*;
+use super::super::super::asm::*;
+use super::super::super::SHARESPACE;
+use super::super::super::{LoadVcpuFreq, Tsc};
+use super::tsc_timer::*;
+
+// The KVM steal time, enabled with Config.StealTime. KVM accounts the time a
+// vcpu was runnable on the host but didn't run, i.e. the host CPU was busy
+// with other threads, in the kvm_steal_time of the vcpu.
+//
+// Each vcpu keeps the rate of its recent steal time, the scheduler wakes the
+// idle vcpus the host runs the most first and a vcpu the host keeps preempted
+// hands the tasks of the others to an idle vcpu the host runs more.
+
+const MSR_KVM_STEAL_TIME: u32 = 0x4b564d03;
+const KVM_MSR_ENABLED: u64 = 1 << 0;
+const KVM_FEATURE_STEAL_TIME: u32 = 1 << 5;
+
+// KVM_VCPU_PREEMPTED is set in preempted when the host schedules the vcpu out
+const KVM_VCPU_PREEMPTED: u8 = 1 << 0;
+
+// the steal rate is in per mille of the time of the vcpu, it is sampled at
+// most every CLOCK_TICK
+pub const STEAL_RATE_SCALE: u64 = 1000;
+// a vcpu with a steal rate above it doesn't steal the tasks of the others
+pub const STEAL_RATE_HIGH: u64 = 500;
+
+static STEAL_TIME: AtomicBool = AtomicBool::new(false);
+
+// KvmStealTime is the struct kvm_steal_time KVM updates, 64 bytes aligned.
+#[derive(Debug, Default)]
+#[repr(C)]
+#[repr(align(64))]
+pub struct KvmStealTime {
+    // the steal time of the vcpu in ns
+    pub steal: u64,
+    pub version: u32,
+    pub flags: u32,
+    pub preempted: u8,
+    pub u8Pad: [u8; 3],
+    pub pad: [u32; 11],
+}
+
+impl KvmStealTime {
+    // Steal returns the steal time of the vcpu in ns.
+    pub fn Steal(&self) -> u64 {
+        loop {
+            let version = unsafe { core::ptr::read_volatile(&self.version) };
+            core::sync::atomic::fence(Ordering::Acquire);
+            let steal = unsafe { core::ptr::read_volatile(&self.steal) };
+            core::sync::atomic::fence(Ordering::Acquire);
+            if version & 1 != 0 || version != unsafe { core::ptr::read_volatile(&self.version) } {
+                continue;
+            }
+
+            return steal;
+        }
+    }
+
+    // Preempted returns whether the host has scheduled the vcpu out.
+    pub fn Preempted(&self) -> bool {
+        let preempted = unsafe { core::ptr::read_volatile(&self.preempted) };
+        return preempted & KVM_VCPU_PREEMPTED != 0;
+    }
+}
+
+// StealSample is the last sample of the steal rate of a vcpu.
+#[derive(Debug, Default)]
+pub struct StealSample {
+    // the TSC and the steal time of the sample
+    pub tsc: AtomicU64,
+    pub steal: AtomicU64,
+    // the average steal rate, in STEAL_RATE_SCALE
+    pub rate: AtomicU64,
+}
+
+// InitStealTime enables the steal time of the current vcpu, it is called on
+// each vcpu at its start.
+pub fn InitStealTime(enable: bool) -> bool {
+    if !enable {
+        return false;
+    }
+
+    if KvmFeatures() & KVM_FEATURE_STEAL_TIME == 0 {
+        info!("steal time: not supported by the vcpu");
+        return false;
+    }
+
+    let cpu = CPULocal::Myself();
+    cpu.stealSample
+        .tsc
+        .store(Tsc::RawRdtsc() as u64, Ordering::Relaxed);
+    // the kernel memory is identity mapped, the address is the guest physical one
+    WriteMsr(
+        MSR_KVM_STEAL_TIME,
+        &cpu.stealTime as *const _ as u64 | KVM_MSR_ENABLED,
+    );
+    cpu.stealSample
+        .steal
+        .store(cpu.stealTime.Steal(), Ordering::Relaxed);
+
+    STEAL_TIME.store(true, Ordering::Release);
+    return true;
+}
+
+#[inline]
+pub fn StealTime() -> bool {
+    return STEAL_TIME.load(Ordering::Relaxed);
+}
+
+// StealRate returns the recent steal rate of the vcpu, in STEAL_RATE_SCALE.
+#[inline]
+pub fn StealRate(vcpuId: usize) -> u64 {
+    if !StealTime() {
+        return 0;
+    }
+
+    return SHARESPACE.scheduler.VcpuArr[vcpuId]
+        .stealSample
+        .rate
+        .load(Ordering::Relaxed);
+}
+
+// VcpuPreempted returns whether the host has scheduled the vcpu out.
+#[inline]
+pub fn VcpuPreempted(vcpuId: usize) -> bool {
+    if !StealTime() {
+        return false;
+    }
+
+    return SHARESPACE.scheduler.VcpuArr[vcpuId].stealTime.Preempted();
+}
+
+// UpdateStealRate samples the steal rate of the current vcpu, it is called
+// when the vcpu picks its next task.
+pub fn UpdateStealRate() {
+    if !StealTime() {
+        return;
+    }
+
+    let cpu = CPULocal::Myself();
+    let sample = &cpu.stealSample;
+    let now = Tsc::RawRdtsc() as u64;
+    let last = sample.tsc.load(Ordering::Relaxed);
+    let cycles = now.wrapping_sub(last);
+    let freq = LoadVcpuFreq() as u64;
+    if freq == 0 || (cycles as u128 * SECOND as u128) < (freq as u128 * CLOCK_TICK as u128) {
+        return;
+    }
+
+    let ns = (cycles as u128 * SECOND as u128 / freq as u128) as u64;
+    let steal = cpu.stealTime.Steal();
+    let delta = steal.wrapping_sub(sample.steal.load(Ordering::Relaxed));
+    let rate = core::cmp::min(delta * STEAL_RATE_SCALE / ns, STEAL_RATE_SCALE);
+
+    // the average of the last samples, a burst of steal time moves it by a
+    // quarter
+    let prev = sample.rate.load(Ordering::Relaxed);
+    sample.rate.store((prev * 3 + rate) / 4, Ordering::Relaxed);
+    sample.tsc.store(now, Ordering::Relaxed);
+    sample.steal.store(steal, Ordering::Relaxed);
+}
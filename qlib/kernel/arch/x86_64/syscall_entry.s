@@ -21,6 +21,9 @@
 .globl simd_fp_handler
 .globl virtualization_handler
 .globl security_handler
+.globl tsc_deadline_handler
+.globl spurious_handler
+.globl async_pf_handler
 
 .extern syscall_handler, CopyData,
 
@@ -44,6 +47,9 @@
 .extern SIMDFPHandler
 .extern VirtualizationHandler
 .extern SecurityHandler
+.extern TscDeadlineHandler
+.extern SpuriousHandler
+.extern AsyncPfHandler
 
 .intel_syntax noprefix
 
@@ -404,6 +410,15 @@ virtualization_handler:
 security_handler:
     HandlerWithoutErrorCode SecurityHandler
 
+tsc_deadline_handler:
+    HandlerWithoutErrorCode TscDeadlineHandler
+
+spurious_handler:
+    HandlerWithoutErrorCode SpuriousHandler
+
+async_pf_handler:
+    HandlerWithoutErrorCode AsyncPfHandler
+
 initX86FPState:
     // Save MXCSR (callee-save)
     STMXCSR     [rsp - 8]
@@ -0,0 +1,292 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicI64;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::cpuid::*;
+use super::super::super::super::linux::time::*;
+use super::super::super::super::vcpu_mgr::*;
+use super::super::super::asm::*;
+use super::super::super::{LoadVcpuFreq, Tsc};
+
+// The TSC deadline timer of the vcpus, enabled with Config.TscDeadlineTimer.
+// qvisor keeps the local APIC of the vcpus in KVM (the split irqchip) and the
+// vcpu arms the TSC deadline of its x2APIC when it enters the application, so
+// the preemption of a task running too long is an interrupt of the vcpu
+// instead of qvisor polling the vcpus and kicking them with a signal. The
+// LINT0 of the APIC takes the external interrupts qvisor injects as before.
+//
+// The clock samples of the guest read the KVM pvclock of the vcpu when KVM
+// has it instead of asking qvisor for the host time with a hypercall. The TSC
+// of the vcpus is invariant when the host one is, else the pvclock keeps the
+// samples right when the host TSC changes.
+
+pub const TSC_DEADLINE_VECTOR: u8 = 0xec;
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+
+const MSR_IA32_APIC_BASE: u32 = 0x1b;
+const APIC_BASE_EXTD: u64 = 1 << 10;
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+const MSR_IA32_TSC_DEADLINE: u32 = 0x6e0;
+
+// the x2APIC registers
+const X2APIC_EOI: u32 = 0x80b;
+const X2APIC_SIVR: u32 = 0x80f;
+const X2APIC_LVT_TIMER: u32 = 0x832;
+const X2APIC_LVT0: u32 = 0x835;
+
+const APIC_SIVR_ENABLE: u64 = 1 << 8;
+const APIC_LVT_DM_EXTINT: u64 = 0x7 << 8;
+const APIC_LVT_TIMER_TSCDEADLINE: u64 = 0x2 << 17;
+
+// the KVM paravirt cpuid leaves and the pvclock, see
+// Documentation/virt/kvm/x86/cpuid.rst and msr.rst
+const KVM_CPUID_SIGNATURE: u32 = 0x40000000;
+const KVM_CPUID_FEATURES: u32 = 0x40000001;
+const KVM_FEATURE_CLOCKSOURCE2: u32 = 1 << 3;
+const KVM_FEATURE_CLOCKSOURCE_STABLE_BIT: u32 = 1 << 24;
+const MSR_KVM_SYSTEM_TIME_NEW: u32 = 0x4b564d01;
+const PVCLOCK_TSC_STABLE_BIT: u8 = 1 << 0;
+
+// cpuid 0x80000007 edx
+const CPUID_INVARIANT_TSC: u32 = 1 << 8;
+
+// the time a task runs in the application before it is preempted, the one
+// of ShareSpace::CheckVcpuTimeout
+pub const PREEMPT_TIMEOUT: i64 = 2 * CLOCK_TICK;
+
+static TSC_DEADLINE_TIMER: AtomicBool = AtomicBool::new(false);
+static PVCLOCK: AtomicBool = AtomicBool::new(false);
+// the host monotonic time minus the pvclock time
+static PVCLOCK_OFFSET: AtomicI64 = AtomicI64::new(0);
+
+// PvClockTimeInfo is the struct pvclock_vcpu_time_info KVM updates, it can't
+// cross a page.
+#[derive(Debug, Default)]
+#[repr(C)]
+#[repr(align(32))]
+pub struct PvClockTimeInfo {
+    pub version: u32,
+    pub pad0: u32,
+    pub tscTimestamp: u64,
+    pub systemTime: u64,
+    pub tscToSystemMul: u32,
+    pub tscShift: i8,
+    pub flags: u8,
+    pub pad: [u8; 2],
+}
+
+impl PvClockTimeInfo {
+    // Read returns the pvclock time in ns and the flags.
+    pub fn Read(&self) -> (i64, u8) {
+        loop {
+            let version = unsafe { core::ptr::read_volatile(&self.version) };
+            core::sync::atomic::fence(Ordering::Acquire);
+            let tscTimestamp = unsafe { core::ptr::read_volatile(&self.tscTimestamp) };
+            let systemTime = unsafe { core::ptr::read_volatile(&self.systemTime) };
+            let mul = unsafe { core::ptr::read_volatile(&self.tscToSystemMul) };
+            let shift = unsafe { core::ptr::read_volatile(&self.tscShift) };
+            let flags = unsafe { core::ptr::read_volatile(&self.flags) };
+            let tsc = Tsc::RawRdtsc() as u64;
+            core::sync::atomic::fence(Ordering::Acquire);
+            if version & 1 != 0 || version != unsafe { core::ptr::read_volatile(&self.version) } {
+                continue;
+            }
+
+            let mut delta = tsc.wrapping_sub(tscTimestamp);
+            if shift < 0 {
+                delta >>= -shift as u32;
+            } else {
+                delta <<= shift as u32;
+            }
+
+            let ns = systemTime + ((delta as u128 * mul as u128) >> 32) as u64;
+            return (ns as i64, flags);
+        }
+    }
+}
+
+// InvariantTsc returns whether the TSC of the vcpu runs at a constant rate
+// in all the power states.
+pub fn InvariantTsc() -> bool {
+    let (_, _, _, edx) = HostID(0x80000007, 0);
+    return edx & CPUID_INVARIANT_TSC != 0;
+}
+
+// KvmFeatures returns the KVM paravirt features of the vcpu, 0 if the vcpu
+// is not a KVM one.
+pub fn KvmFeatures() -> u32 {
+    let (max, bx, cx, dx) = HostID(KVM_CPUID_SIGNATURE, 0);
+    // "KVMKVMKVM\0\0\0"
+    if bx != 0x4b4d564b || cx != 0x564b4d56 || dx != 0x4d || max < KVM_CPUID_FEATURES {
+        return 0;
+    }
+
+    let (ax, _, _, _) = HostID(KVM_CPUID_FEATURES, 0);
+    return ax;
+}
+
+// InitX2Apic enables the x2APIC of the current vcpu, the local APIC qvisor
+// keeps in KVM for the TSC deadline timer and the async page faults.
+pub fn InitX2Apic() -> bool {
+    let featureSet = HostFeatureSet();
+    if !featureSet.HasFeature(Feature(X86Feature::X86FeatureX2APIC as i32)) {
+        info!("x2apic: not supported by the vcpu");
+        return false;
+    }
+
+    let base = ReadMsr(MSR_IA32_APIC_BASE);
+    WriteMsr(MSR_IA32_APIC_BASE, base | APIC_BASE_ENABLE | APIC_BASE_EXTD);
+    WriteMsr(X2APIC_SIVR, APIC_SIVR_ENABLE | SPURIOUS_VECTOR as u64);
+    // the interrupts qvisor injects come from LINT0, it has to take them even
+    // without the timer
+    WriteMsr(X2APIC_LVT0, APIC_LVT_DM_EXTINT);
+    return true;
+}
+
+// ApicEoi acks the interrupt of the local APIC being handled.
+#[inline]
+pub fn ApicEoi() {
+    WriteMsr(X2APIC_EOI, 0);
+}
+
+// InitTscDeadlineTimer sets up the TSC deadline timer of the current vcpu, it
+// is called on each vcpu at its start.
+pub fn InitTscDeadlineTimer(enable: bool) -> bool {
+    if !enable || !InitX2Apic() {
+        return false;
+    }
+
+    let featureSet = HostFeatureSet();
+    if !featureSet.HasFeature(Feature(X86Feature::X86FeatureTSCD as i32)) {
+        info!("tsc deadline timer: not supported by the vcpu");
+        return false;
+    }
+
+    WriteMsr(
+        X2APIC_LVT_TIMER,
+        APIC_LVT_TIMER_TSCDEADLINE | TSC_DEADLINE_VECTOR as u64,
+    );
+
+    TSC_DEADLINE_TIMER.store(true, Ordering::Release);
+    return true;
+}
+
+#[inline]
+pub fn TscDeadlineTimer() -> bool {
+    return TSC_DEADLINE_TIMER.load(Ordering::Relaxed);
+}
+
+// PreemptCycles returns the TSC cycles of PREEMPT_TIMEOUT.
+pub fn PreemptCycles() -> i64 {
+    return (PREEMPT_TIMEOUT as i128 * LoadVcpuFreq() as i128 / SECOND as i128) as i64;
+}
+
+// ArmTscDeadline arms the deadline of the vcpu entering the application, the
+// deadline armed is kept till it fires.
+#[inline]
+pub fn ArmTscDeadline() {
+    if !TscDeadlineTimer() {
+        return;
+    }
+
+    let cpu = CPULocal::Myself();
+    if cpu.tscDeadline.load(Ordering::Relaxed) != 0 {
+        return;
+    }
+
+    SetTscDeadline(cpu, Tsc::RawRdtsc() + PreemptCycles());
+}
+
+pub fn SetTscDeadline(cpu: &CPULocal, deadline: i64) {
+    cpu.tscDeadline.store(deadline, Ordering::Relaxed);
+    WriteMsr(MSR_IA32_TSC_DEADLINE, deadline as u64);
+}
+
+// TscDeadlineExpired acks the timer interrupt and returns whether the task in
+// the application has run PREEMPT_TIMEOUT, else the deadline is moved to the
+// timeout of the task.
+pub fn TscDeadlineExpired(enterAppTimestamp: i64, now: i64) -> bool {
+    ApicEoi();
+    let cpu = CPULocal::Myself();
+    cpu.tscDeadline.store(0, Ordering::Relaxed);
+    if enterAppTimestamp == 0 {
+        return false;
+    }
+
+    let cycles = PreemptCycles();
+    if now - enterAppTimestamp >= cycles {
+        return true;
+    }
+
+    SetTscDeadline(cpu, Tsc::RawRdtsc() + (enterAppTimestamp + cycles - now));
+    return false;
+}
+
+// InitPvClock registers the pvclock of the current vcpu, it is called on each
+// vcpu at its start. hostMonotonic returns the host monotonic time, it is
+// called on the boot vcpu to get the offset of the pvclock.
+pub fn InitPvClock(boot: bool, hostMonotonic: &dyn Fn() -> i64) -> bool {
+    let features = KvmFeatures();
+    if features & KVM_FEATURE_CLOCKSOURCE2 == 0 {
+        return false;
+    }
+
+    if boot {
+        // the pvclock of the vcpus don't drift from each other only with the
+        // stable bit, else the samples keep asking qvisor
+        if features & KVM_FEATURE_CLOCKSOURCE_STABLE_BIT == 0 {
+            info!("pvclock: not stable, not used");
+            return false;
+        }
+
+        info!("pvclock: invariant tsc {}", InvariantTsc());
+    } else if !PVCLOCK.load(Ordering::Acquire) {
+        return false;
+    }
+
+    let cpu = CPULocal::Myself();
+    // the kernel memory is identity mapped, the address is the guest physical one
+    WriteMsr(MSR_KVM_SYSTEM_TIME_NEW, &cpu.pvclock as *const _ as u64 | 1);
+
+    if boot {
+        let (_, flags) = cpu.pvclock.Read();
+        if flags & PVCLOCK_TSC_STABLE_BIT == 0 {
+            WriteMsr(MSR_KVM_SYSTEM_TIME_NEW, 0);
+            info!("pvclock: tsc is not stable, not used");
+            return false;
+        }
+
+        let host = hostMonotonic();
+        let (pv, _) = cpu.pvclock.Read();
+        PVCLOCK_OFFSET.store(host - pv, Ordering::Release);
+        PVCLOCK.store(true, Ordering::Release);
+    }
+
+    return true;
+}
+
+// PvClockMonotonic returns the host monotonic time of the pvclock, none if
+// the pvclock is not used.
+#[inline]
+pub fn PvClockMonotonic() -> Option<i64> {
+    if !PVCLOCK.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let (ns, _) = CPULocal::Myself().pvclock.Read();
+    return Some(ns + PVCLOCK_OFFSET.load(Ordering::Relaxed));
+}
@@ -14,6 +14,7 @@
 
 //use super::super::perf_tunning::*;
 
+use super::arch::x86_64::mitigations::ReturnToUser;
 use super::{SUPPORT_XSAVE, SUPPORT_XSAVEOPT};
 use core::sync::atomic::Ordering;
 
@@ -78,6 +79,7 @@ pub fn CurrentCr3() -> u64 {
 
 #[inline]
 pub fn EnterUser(entry: u64, userStackAddr: u64, kernelStackAddr: u64) {
+    ReturnToUser();
     //PerfGoto(PerfType::User);
     unsafe {
         llvm_asm!("
@@ -120,6 +122,7 @@ pub fn EnterUser(entry: u64, userStackAddr: u64, kernelStackAddr: u64) {
 
 #[inline]
 pub fn SyscallRet(kernelRsp: u64) -> ! {
+    ReturnToUser();
     unsafe {
         llvm_asm!("
             //we have to store callee save registers for signal handling
@@ -161,6 +164,7 @@ pub fn SyscallRet(kernelRsp: u64) -> ! {
 
 #[inline]
 pub fn IRet(kernelRsp: u64) -> ! {
+    ReturnToUser();
     unsafe {
         llvm_asm!("
             //we have to store callee save registers for signal handling
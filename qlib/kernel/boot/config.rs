@@ -17,4 +17,8 @@ use alloc::string::String;
 pub struct Config {
     pub RootDir: String,
     pub Debug: bool,
+    // the size option of the /dev/shm tmpfs, empty for the default
+    pub ShmSize: String,
+    // the /dev/shm is a volume of the user instead of the tmpfs
+    pub ShmVolume: bool,
 }
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use alloc::string::String;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::ptr;
 
@@ -22,6 +23,10 @@ use super::super::super::super::kernel_def::{
 use super::super::super::common::*;
 use super::super::super::control_msg::*;
 use super::super::super::vcpu_mgr::*;
+use super::super::fs::etc_files::*;
+use super::super::fs::host::diriops::InvalidateReaddirCaches;
+use super::super::memmgr::mem_high::SetMemoryHigh;
+use super::super::socket::hostinet::tls_offload::TlsAccept;
 use super::super::task::*;
 use super::super::taskMgr;
 use super::super::Kernel;
@@ -127,10 +132,25 @@ pub fn ControlMsgHandler(fd: *const u8) {
         Payload::Pause => {
             let kernel = LOADER.Lock(task).unwrap().kernel.clone();
             kernel.Pause();
+            // the host freezes the sandbox after the pause, the uring is
+            // quiesced first. A freeze with ops still moving data would lose
+            // them, the pause fails then.
+            if !IOURING.Quiesce() {
+                IOURING.Unquiesce();
+                kernel.Unpause();
+                Kernel::HostSpace::PauseAbort();
+                WriteControlMsgResp(
+                    fd,
+                    &UCallResp::UCallRespErr("pause: the uring is still busy".to_string()),
+                    true,
+                );
+                return;
+            }
             WriteControlMsgResp(fd, &UCallResp::PauseResp, true);
         }
         Payload::Unpause => {
             let kernel = LOADER.Lock(task).unwrap().kernel.clone();
+            IOURING.Unquiesce();
             kernel.Unpause();
             WriteControlMsgResp(fd, &UCallResp::UnpauseResp, true);
         }
@@ -139,6 +159,16 @@ pub fn ControlMsgHandler(fd: *const u8) {
             let ps = Processes(&kernel, &cid);
             WriteControlMsgResp(fd, &UCallResp::PsResp(ps), true);
         }
+        Payload::Tasks(cid) => {
+            let kernel = LOADER.Lock(task).unwrap().kernel.clone();
+            let tasks = Tasks(&kernel, &cid);
+            WriteControlMsgResp(fd, &UCallResp::TasksResp(tasks), true);
+        }
+        Payload::Sockets(cid) => {
+            let kernel = LOADER.Lock(task).unwrap().kernel.clone();
+            let sockets = Sockets(&kernel, &cid);
+            WriteControlMsgResp(fd, &UCallResp::SocketsResp(sockets), true);
+        }
         Payload::Signal(signalArgs) => {
             HandleSignal(&signalArgs);
             WriteControlMsgResp(fd, &UCallResp::SignalResp, true);
@@ -196,6 +226,53 @@ pub fn ControlMsgHandler(fd: *const u8) {
         Payload::WaitAll => {
             SetWaitContainerfd(fd);
         }
+        Payload::UpdateConfig(update) => {
+            let (old, config) = {
+                let mut config = SHARESPACE.config.write();
+                let old = *config;
+                config.Update(&update);
+                (old, *config)
+            };
+            // the entries cached before the cache is turned off are not
+            // refreshed while it is off
+            if old.ReaddirCache != config.ReaddirCache {
+                InvalidateReaddirCaches();
+            }
+            SetMemoryHigh(config.MemoryHigh);
+            info!("UpdateConfig new config is {:?}", &config);
+            WriteControlMsgResp(fd, &UCallResp::UpdateConfigResp(config), true);
+        }
+        Payload::UpdateEtcFiles(files) => {
+            ETC_FILES.Update(Task::Current(), &files);
+            WriteControlMsgResp(fd, &UCallResp::UpdateEtcFilesResp, true);
+        }
+        Payload::ProcessResources(args) => {
+            match LOADER.Lock(task).unwrap().ProcessResources(&args) {
+                Ok(resources) => {
+                    WriteControlMsgResp(fd, &UCallResp::ProcessResourcesResp(resources), true);
+                }
+                Err(e) => {
+                    WriteControlMsgResp(fd, &UCallResp::UCallRespErr(format!("{:?}", e)), true);
+                }
+            }
+        }
+        Payload::FlushRootfs(cid) => match LOADER.Lock(task).unwrap().FlushRootfs(&cid) {
+            Ok(()) => {
+                WriteControlMsgResp(fd, &UCallResp::FlushRootfsResp, true);
+            }
+            Err(e) => {
+                WriteControlMsgResp(fd, &UCallResp::UCallRespErr(format!("{:?}", e)), true);
+            }
+        },
+        Payload::TlsAccept(args) => match TlsAccept(args.port, args.fd, &args.addr) {
+            Ok(()) => {
+                WriteControlMsgResp(fd, &UCallResp::TlsAcceptResp, true);
+            }
+            Err(e) => {
+                Kernel::HostSpace::Close(args.fd);
+                WriteControlMsgResp(fd, &UCallResp::UCallRespErr(format!("{:?}", e)), true);
+            }
+        },
     }
 
     // free curent task in the waitfn context
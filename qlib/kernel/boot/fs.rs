@@ -22,15 +22,20 @@ use alloc::vec::Vec;
 use super::super::super::auth::*;
 use super::super::super::common::*;
 use super::super::super::linux_def::{FileMode, FilePermissions, SysErr};
+use super::super::super::loader::EtcFiles;
 use super::super::super::path::*;
 use super::super::fs::dirent::*;
+use super::super::fs::ephemeral::*;
+use super::super::fs::etc_files::*;
 use super::super::fs::filesystems::*;
+use super::super::fs::flags::*;
 use super::super::fs::host::fs::*;
 use super::super::fs::host::util::*;
 use super::super::fs::inode::*;
 use super::super::fs::mount::*;
 use super::super::fs::overlay::*;
 use super::super::fs::ramfs::tree::*;
+use super::super::fs::tmpfs::shm::*;
 use super::super::task::*;
 
 use super::*;
@@ -47,6 +52,50 @@ const SYSFS: &str = "sysfs";
 const TMPFS: &str = "tmpfs";
 const NONEFS: &str = "none";
 
+// DEV_SHM is the /dev/shm of the containers. The containers of a sandbox are a
+// pod and share the ipc namespace, so they share one tmpfs at /dev/shm too. The
+// POSIX shared memory and the unix sockets bound there are visible to all of
+// them, which the handover between containers, e.g. envoy hot restart, needs.
+// A /dev/shm volume of the user replaces the tmpfs, it is mounted from the
+// rootfs of the container where the host has mounted it.
+const DEV_SHM: &str = "/dev/shm";
+
+lazy_static! {
+    pub static ref POD_SHM: QMutex<Option<Inode>> = QMutex::new(None);
+}
+
+// PodShm returns the /dev/shm tmpfs of the sandbox, it is created by the first
+// container with the size of its /dev/shm.
+fn PodShm(task: &Task, mf: &MountSourceFlags, opts: &str) -> Result<Inode> {
+    let mut shm = POD_SHM.lock();
+    if let Some(inode) = shm.as_ref() {
+        return Ok(inode.clone());
+    }
+
+    let filesystem = MustFindFilesystem(TMPFS);
+    let inode = filesystem
+        .lock()
+        .Mount(task, &"none".to_string(), mf, opts)?;
+    SHM_USAGE.SetPod(&inode.lock().MountSource.clone());
+    *shm = Some(inode.clone());
+    return Ok(inode);
+}
+
+// ShmVolume returns the /dev/shm volume of the container, the host directory
+// at /dev/shm of its rootfs.
+fn ShmVolume(task: &Task, conf: &config::Config, mf: &MountSourceFlags) -> Result<Inode> {
+    let path = format!("{}{}", conf.RootDir, DEV_SHM);
+    let (fd, writeable, fstat) = TryOpenAt(-100, &path)?;
+    let ms = MountSource::NewHostMountSource(
+        &path,
+        &ROOT_OWNER,
+        &WhitelistFileSystem::New(),
+        mf,
+        false,
+    );
+    return Inode::NewHostInode(task, &Arc::new(QMutex::new(ms)), fd, &fstat, writeable);
+}
+
 fn CreateRootMount(
     task: &Task,
     spec: &oci::Spec,
@@ -61,13 +110,14 @@ fn CreateRootMount(
     let rootStr = &config.RootDir;
     let (fd, writeable, fstat) = TryOpenAt(-100, rootStr)?;
 
-    let ms = MountSource::NewHostMountSource(
+    let mut ms = MountSource::NewHostMountSource(
         &rootStr,
         &ROOT_OWNER,
         &WhitelistFileSystem::New(),
         &mf,
         false,
     );
+    ms.Usage = RootfsUsage();
     let hostRoot = Inode::NewHostInode(task, &Arc::new(QMutex::new(ms)), fd, &fstat, writeable)?;
 
     let submounts = SubTargets(&"/".to_string(), mounts);
@@ -100,30 +150,30 @@ fn SubTargets(root: &str, mnts: &Vec<oci::Mount>) -> Vec<String> {
 }
 
 fn GetMountNameAndOptions(_conf: &config::Config, m: &oci::Mount) -> Result<(String, Vec<String>)> {
-    let fsName;
-    let mut opts = Vec::new();
+    // the type is resolved in the filesystem registry, "none" is sysfs
+    let fsName = match m.typ.as_str() {
+        NONEFS => SYSFS,
+        typ => typ,
+    };
 
-    match m.typ.as_str() {
-        DEVPTS | DEVTMPFS | PROCFS | SYSFS => {
-            fsName = m.typ.to_string();
-        }
-        NONEFS => {
-            fsName = SYSFS.to_string();
-        }
-        TMPFS => {
-            fsName = m.typ.to_string();
-            opts = ParseAndFilterOptions(&m.options, &vec!["mode", "uid", "gid"])?;
-        }
-        _ => {
+    let filesystem = match FindFilesystem(fsName) {
+        None => {
             info!("ignoring unknown filesystem type {}", m.typ);
             return Err(Error::Common(format!(
                 "ignoring unknown filesystem type {}",
                 m.typ
             )));
         }
-    }
+        Some(f) => f,
+    };
 
-    return Ok((fsName, opts));
+    let allowed = filesystem.lock().SpecOptions();
+    let opts = if allowed.len() == 0 {
+        Vec::new()
+    } else {
+        ParseAndFilterOptions(&m.options, &allowed)?
+    };
+    return Ok((fsName.to_string(), opts));
 }
 
 pub fn InitTestSpec() -> oci::Spec {
@@ -145,10 +195,12 @@ pub fn InitTestSpec() -> oci::Spec {
     };
 }
 
-pub fn InitRootFs(task: &mut Task, root: &str) -> Result<MountNs> {
+pub fn InitRootFs(task: &mut Task, root: &str, shmSize: &str, shmVolume: bool) -> Result<MountNs> {
     let config = config::Config {
         RootDir: root.to_string(),
         Debug: true,
+        ShmSize: shmSize.to_string(),
+        ShmVolume: shmVolume,
     };
 
     debug!("init rootfs under {} for container", root);
@@ -162,7 +214,7 @@ pub fn SetupContainerFS(
     spec: &oci::Spec,
     conf: &config::Config,
 ) -> Result<MountNs> {
-    let mounts = CompileMounts(spec);
+    let mounts = CompileMounts(spec, conf);
 
     //error!("SetupRootContainerFS 1.0 mounts[0].destination is {:?}", &mounts[0].destination);
 
@@ -175,7 +227,139 @@ pub fn SetupContainerFS(
     return Ok(mns);
 }
 
-fn CompileMounts(spec: &oci::Spec) -> Vec<oci::Mount> {
+// MountEtcFiles mounts the virtual /etc files of the sandbox over the files of
+// the container rootfs.
+pub fn MountEtcFiles(task: &Task, mns: &MountNs, files: &EtcFiles) -> Result<()> {
+    ETC_FILES.Update(task, files);
+
+    let root = mns.Root();
+    for (path, _) in files.Files().iter() {
+        let inode = match ETC_FILES.Inode(path) {
+            None => continue,
+            Some(i) => i,
+        };
+
+        // e.g. a distroless image without /etc
+        if let Err(e) = MakeFileMountPoint(task, mns, &root, path) {
+            info!("etc files: skip {}, no mount point {:?}", path, e);
+            continue;
+        }
+
+        let mut maxTraversals = 0;
+        let dirent = mns.FindDirent(
+            task,
+            &root,
+            Some(root.clone()),
+            path,
+            &mut maxTraversals,
+            true,
+        )?;
+        mns.Mount(&dirent, &inode)?;
+        info!("Mounted virtual {}", path);
+    }
+
+    return Ok(());
+}
+
+// MountHostDevices mounts the device nodes of the container, the host nodes
+// in its rootfs, over the files of the guest /dev. A node the guest /dev has
+// itself, e.g. /dev/null, is kept.
+pub fn MountHostDevices(
+    task: &Task,
+    mns: &MountNs,
+    rootDir: &str,
+    devices: &[String],
+) -> Result<()> {
+    let root = mns.Root();
+    for path in devices {
+        let mut maxTraversals = 0;
+        if mns
+            .FindDirent(
+                task,
+                &root,
+                Some(root.clone()),
+                path,
+                &mut maxTraversals,
+                true,
+            )
+            .is_ok()
+        {
+            continue;
+        }
+
+        let hostPath = Join(rootDir, path);
+        let (fd, writeable, fstat) = match TryOpenAt(-100, &hostPath) {
+            Err(e) => {
+                info!("devices: skip {}, open fail {:?}", path, e);
+                continue;
+            }
+            Ok(ret) => ret,
+        };
+
+        let ms = MountSource::NewHostMountSource(
+            &hostPath,
+            &ROOT_OWNER,
+            &WhitelistFileSystem::New(),
+            &MountSourceFlags::default(),
+            false,
+        );
+        let inode = Inode::NewHostInode(task, &Arc::new(QMutex::new(ms)), fd, &fstat, writeable)?;
+
+        let (dir, _) = SplitLast(path);
+        MakeDirs(task, mns, &root, dir)?;
+        MakeFileMountPoint(task, mns, &root, path)?;
+
+        let mut maxTraversals = 0;
+        let dirent = mns.FindDirent(
+            task,
+            &root,
+            Some(root.clone()),
+            path,
+            &mut maxTraversals,
+            true,
+        )?;
+        mns.Mount(&dirent, &inode)?;
+        info!("Mounted host device {}", path);
+    }
+
+    return Ok(());
+}
+
+// MakeDirs creates the missing directories of the path, e.g. /dev/dri.
+fn MakeDirs(task: &Task, mns: &MountNs, root: &Dirent, path: &str) -> Result<()> {
+    let mut remainingTraversals = 0;
+    if path == "/"
+        || mns
+            .FindDirent(
+                task,
+                root,
+                Some(root.clone()),
+                path,
+                &mut remainingTraversals,
+                true,
+            )
+            .is_ok()
+    {
+        return Ok(());
+    }
+
+    let (dir, name) = SplitLast(path);
+    MakeDirs(task, mns, root, dir)?;
+
+    let mut remainingTraversals = 0;
+    let parent = mns.FindDirent(
+        task,
+        root,
+        Some(root.clone()),
+        dir,
+        &mut remainingTraversals,
+        true,
+    )?;
+    let perms = FilePermissions::FromMode(FileMode(0o755));
+    return parent.CreateDirectory(task, root, name, &perms);
+}
+
+fn CompileMounts(spec: &oci::Spec, conf: &config::Config) -> Vec<oci::Mount> {
     let mut _procMounted = false;
     let mut _sysMounted = false;
     let mut mounts = Vec::new();
@@ -195,6 +379,18 @@ fn CompileMounts(spec: &oci::Spec) -> Vec<oci::Mount> {
         options: Vec::new(),
     });
 
+    // the tmpfs mode is parsed as decimal
+    let mut shmOptions = vec![format!("mode={}", 0o1777)];
+    if conf.ShmSize.len() > 0 {
+        shmOptions.push(format!("size={}", conf.ShmSize));
+    }
+    mounts.push(oci::Mount {
+        destination: DEV_SHM.to_string(),
+        typ: TMPFS.to_string(),
+        source: "".to_string(),
+        options: shmOptions,
+    });
+
     mounts.push(oci::Mount {
         destination: "/proc".to_string(),
         typ: PROCFS.to_string(),
@@ -306,6 +502,48 @@ fn MakeMountPoint(task: &Task, mns: &MountNs, root: &Dirent, path: &str) -> Resu
     return Ok(());
 }
 
+// MakeFileMountPoint creates the file to mount a file over, its directory has
+// to exist.
+fn MakeFileMountPoint(task: &Task, mns: &MountNs, root: &Dirent, path: &str) -> Result<()> {
+    let mut remainingTraversals = 0;
+    if mns
+        .FindDirent(
+            task,
+            root,
+            Some(root.clone()),
+            path,
+            &mut remainingTraversals,
+            true,
+        )
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    let (dir, name) = SplitLast(path);
+    let mut remainingTraversals = 0;
+    let parent = mns.FindDirent(
+        task,
+        root,
+        Some(root.clone()),
+        dir,
+        &mut remainingTraversals,
+        true,
+    )?;
+    let perms = FilePermissions::FromMode(FileMode(0o644));
+    parent.Create(
+        task,
+        root,
+        name,
+        &FileFlags {
+            Read: true,
+            ..Default::default()
+        },
+        &perms,
+    )?;
+    return Ok(());
+}
+
 fn MountSubmount(
     task: &Task,
     config: &config::Config,
@@ -323,9 +561,17 @@ fn MountSubmount(
     let filesystem = MustFindFilesystem(&fsName);
     let mf = mountFlags(&m.options);
 
-    let mut inode = filesystem
-        .lock()
-        .Mount(task, &"none".to_string(), &mf, &opts.join(","))?;
+    let mut inode = if Clean(&m.destination).as_str() == DEV_SHM {
+        if config.ShmVolume {
+            ShmVolume(task, config, &mf)?
+        } else {
+            PodShm(task, &mf, &opts.join(","))?
+        }
+    } else {
+        filesystem
+            .lock()
+            .Mount(task, &"none".to_string(), &mf, &opts.join(","))?
+    };
     let submounts = SubTargets(&m.destination, mounts);
     if submounts.len() > 0 {
         info!("adding submount overlay over {}", m.destination);
@@ -26,19 +26,28 @@ use super::super::super::auth::cap_set::*;
 use super::super::super::auth::id::*;
 use super::super::super::auth::userns::*;
 use super::super::super::common::*;
+use super::super::super::control_msg::{ProcessResources, ProcessResourcesArgs};
 use super::super::super::cpuid::*;
 use super::super::super::limits::*;
 use super::super::super::linux_def::*;
 use super::super::super::loader::*;
+use super::super::arch::x86_64::mitigations::InitMitigations;
 use super::super::fs::host::tty::*;
 use super::super::fs::mount::*;
+use super::super::fs::tmpfs::shm::*;
+use super::super::kernel::fd_table::*;
+use super::super::kernel::io_max::*;
 use super::super::kernel::ipc_namespace::*;
 use super::super::kernel::kernel::*;
+use super::super::kernel::time_namespace::*;
 use super::super::kernel::uts_namespace::*;
 use super::super::kernel::waiter::qlock::*;
+use super::super::socket::hostinet::socket::NewSocketFileFromHostFd;
 use super::super::task::*;
+use super::super::threadmgr::pid_namespace::INIT_TID;
 use super::super::threadmgr::thread::*;
 use super::super::threadmgr::thread_group::*;
+use super::super::version::SetUname;
 use super::super::SignalDef::*;
 use super::super::SHARESPACE;
 use super::fs::*;
@@ -245,6 +254,16 @@ impl Loader {
                 .expect("Task: create std fds");
         }
 
+        // socket activation, the sockets follow stdio as sd_listen_fds(3) expects
+        for i in 0..procArgs.ListenFds.len() {
+            let file = NewSocketFileFromHostFd(task, procArgs.ListenFds[i])?;
+            task.NewFDAt(3 + i as i32, &file, &FDFlags::default())?;
+        }
+
+        if procArgs.ListenFds.len() > 0 {
+            procArgs.Envv.push(format!("LISTEN_PID={}", tid));
+        }
+
         GetKernel().Start()?;
 
         //task.NewStdFds(&procArgs.Stdiofds[..], procArgs.Terminal).expect("Task: create std fds");
@@ -326,12 +345,29 @@ impl Loader {
             Some(&processSpec.TaskCaps()),
             &userns,
         );
-        let rootMounts = InitRootFs(Task::Current(), &processSpec.Root)
-            .expect("in loader::StartSubContainer, InitRootfs fail");
+        let rootMounts = InitRootFs(
+            Task::Current(),
+            &processSpec.Root,
+            &processSpec.ShmSize,
+            processSpec.ShmVolume,
+        )
+        .expect("in loader::StartSubContainer, InitRootfs fail");
+        if let Some(files) = &processSpec.EtcFiles {
+            MountEtcFiles(Task::Current(), &rootMounts, files)?;
+        }
+        MountHostDevices(
+            Task::Current(),
+            &rootMounts,
+            &processSpec.Root,
+            &processSpec.Devices,
+        )?;
         kernel
             .mounts
             .write()
             .insert(processSpec.ID.clone(), rootMounts);
+        IO_MAX.Set(&processSpec.ID, &processSpec.IoMax);
+        IO_MAX.AddPartitions(&processSpec.IoMaxPartitions);
+        SHM_USAGE.Set(&processSpec.ID, &processSpec.ShmSize);
 
         //todo: investigate PID namespace and whether we need it.
         let mut createProcessArgs = NewProcess(processSpec, &creds, &kernel);
@@ -421,7 +457,7 @@ pub struct LoaderInternal {
 
 impl LoaderInternal {
     //init the root process
-    pub fn Init(&mut self, process: Process) -> CreateProcessArgs {
+    pub fn Init(&mut self, process: Process) -> Result<CreateProcessArgs> {
         let console = process.Terminal;
         let sandboxID = process.ID.to_string();
 
@@ -441,8 +477,12 @@ impl LoaderInternal {
 
         let hostName = process.HostName.to_string();
 
-        let utsns = UTSNamespace::New(hostName.to_string(), hostName.to_string(), userns.clone());
+        // the domainname is not set by the spec, "(none)" as Linux
+        let utsns = UTSNamespace::New(hostName.to_string(), "(none)".to_string(), userns.clone());
+        SetUname(&process.KernelRelease, &process.KernelVersion);
+        InitMitigations(process.Mitigations);
         let ipcns = IPCNamespace::New(&userns);
+        let timens = TimeNamespace::New(&userns, process.MonotonicOffset, process.BoottimeOffset);
 
         let kernalArgs = InitKernalArgs {
             FeatureSet: Arc::new(QMutex::new(HostFeatureSet())),
@@ -451,20 +491,33 @@ impl LoaderInternal {
             ExtraAuxv: Vec::new(),
             RootUTSNamespace: utsns,
             RootIPCNamespace: ipcns,
+            RootTimeNamespace: timens,
         };
 
         let kernel = Kernel::Init(kernalArgs);
         *SHARESPACE.kernel.lock() = Some(kernel.clone());
 
         let rootMounts =
-            InitRootFs(Task::Current(), &process.Root).expect("in loader::New, InitRootfs fail");
+            InitRootFs(Task::Current(), &process.Root, &process.ShmSize, process.ShmVolume)?;
+        if let Some(files) = &process.EtcFiles {
+            MountEtcFiles(Task::Current(), &rootMounts, files)?;
+        }
+        MountHostDevices(
+            Task::Current(),
+            &rootMounts,
+            &process.Root,
+            &process.Devices,
+        )?;
         kernel.mounts.write().insert(sandboxID.clone(), rootMounts);
+        IO_MAX.Set(&sandboxID, &process.IoMax);
+        IO_MAX.AddPartitions(&process.IoMaxPartitions);
+        SHM_USAGE.Set(&sandboxID, &process.ShmSize);
 
         let processArgs = NewProcess(process, &creds, &kernel);
         self.kernel = kernel;
         self.console = console;
         self.sandboxID = sandboxID;
-        return processArgs;
+        return Ok(processArgs);
     }
 
     pub fn ThreadGroupFromID(&self, key: &ExecID) -> Option<(ThreadGroup, Option<TTYFileOps>)> {
@@ -552,10 +605,7 @@ impl LoaderInternal {
         }) {
             None => (),
             Some((execTG, _)) => {
-                return execTG.SendSignal(&SignalInfo {
-                    Signo: signo,
-                    ..Default::default()
-                });
+                return Self::SendProcessSignal(&execTG, signo);
             }
         }
 
@@ -573,10 +623,28 @@ impl LoaderInternal {
             Some(tg) => tg,
         };
 
-        return tg.SendSignal(&SignalInfo {
+        return Self::SendProcessSignal(&tg, signo);
+    }
+
+    // SendProcessSignal sends the signal of the host to the process. With the
+    // built-in init, the signal of an init process is forwarded to its
+    // process group, so the commands of a shell entrypoint, which doesn't
+    // pass on SIGTERM, are stopped with it.
+    fn SendProcessSignal(tg: &ThreadGroup, signo: i32) -> Result<()> {
+        let info = SignalInfo {
             Signo: signo,
             ..Default::default()
-        });
+        };
+
+        if SHARESPACE.config.read().BuiltinInit
+            && tg.PIDNamespace().IDOfThreadGroup(tg) == INIT_TID
+        {
+            if let Some(pg) = tg.ProcessGroup() {
+                return pg.SendSignal(&info);
+            }
+        }
+
+        return tg.SendSignal(&info);
     }
 
     pub fn SignalAll(&self, signo: i32) -> Result<()> {
@@ -596,6 +664,97 @@ impl LoaderInternal {
         }
     }
 
+    // ProcessResources returns the rlimits, the nice value and the
+    // oom_score_adj of a process in the container, the ones given in args are
+    // set first. The host operator is privileged, the limits are set as a root
+    // process does. If the pid is 0, it is the container init process.
+    pub fn ProcessResources(&self, args: &ProcessResourcesArgs) -> Result<ProcessResources> {
+        let mut cid = args.cid.clone();
+        if cid.is_empty() {
+            cid = self.sandboxID.clone();
+        }
+
+        let (initTG, _) = match self.ThreadGroupFromID(&ExecID {
+            cid: cid.clone(),
+            pid: 0,
+        }) {
+            None => return Err(Error::Common(format!("no such container {}", cid))),
+            Some(r) => r,
+        };
+
+        let pidns = initTG.PIDNamespace();
+        let tg = if args.pid == 0 {
+            initTG.clone()
+        } else {
+            match pidns.ThreadGroupWithID(args.pid) {
+                None => {
+                    return Err(Error::Common(format!(
+                        "no such process with PID {}",
+                        args.pid
+                    )))
+                }
+                Some(tg) => tg,
+            }
+        };
+
+        let leader = match tg.Leader() {
+            None => return Err(Error::Common(format!("process {} exited", args.pid))),
+            Some(t) => t,
+        };
+
+        // the request is checked as a whole first, it is applied all or
+        // none of it
+        for (resource, limit) in &args.limits {
+            if !SETABLE_LIMITS.contains(resource) {
+                return Err(Error::Common(format!("rlimit {:?} can't be set", resource)));
+            }
+
+            if limit.Cur > limit.Max {
+                return Err(Error::Common(format!(
+                    "rlimit {:?}: soft limit {} is over the hard limit {}",
+                    resource, limit.Cur, limit.Max
+                )));
+            }
+        }
+
+        if let Some(adj) = args.oomScoreAdj {
+            if adj < OOM_SCORE_ADJ_MIN || adj > OOM_SCORE_ADJ_MAX {
+                return Err(Error::Common(format!(
+                    "oom_score_adj {} is out of [{}, {}]",
+                    adj, OOM_SCORE_ADJ_MIN, OOM_SCORE_ADJ_MAX
+                )));
+            }
+        }
+
+        let limits = tg.Limits();
+        for (resource, limit) in &args.limits {
+            limits.Set(*resource, *limit, true)?;
+            if *resource == LimitType::CPU {
+                leader.NotifyRlimitCPUUpdated();
+            }
+        }
+
+        if let Some(nice) = args.nice {
+            // truncated to [-20, 19] as setpriority(2)
+            let nice = core::cmp::max(-20, core::cmp::min(nice, 19));
+            let threads: Vec<Thread> = tg.lock().tasks.iter().cloned().collect();
+            for t in threads {
+                t.SetNiceness(nice);
+            }
+        }
+
+        if let Some(adj) = args.oomScoreAdj {
+            tg.SetOomScoreAdj(adj)?;
+        }
+
+        return Ok(ProcessResources {
+            pid: pidns.IDOfThreadGroup(&tg),
+            limits: limits.GetInternalCopy().data.into_iter().collect(),
+            nice: leader.Niceness(),
+            oomScoreAdj: tg.OomScoreAdj(),
+        });
+    }
+
     pub fn ThreadGroupFromIDLocked(
         &self,
         key: &ExecID,
@@ -627,6 +786,19 @@ impl LoaderInternal {
         return Ok(());
     }
 
+    // FlushRootfs drops the dirents the container caches of its root
+    // filesystem, so its files are looked up again on the host after its
+    // writable layer is rolled back.
+    pub fn FlushRootfs(&self, cid: &str) -> Result<()> {
+        let mns = match self.kernel.mounts.read().get(cid) {
+            None => return Err(Error::NotExist),
+            Some(mns) => mns.clone(),
+        };
+
+        mns.Root().flush();
+        return Ok(());
+    }
+
     pub fn DestroyContainer(&mut self, cid: String) -> Result<()> {
         let l = self;
         let execId = ExecID {
@@ -648,6 +820,8 @@ impl LoaderInternal {
         }
 
         l.processes.remove(&execId);
+        IO_MAX.Set(&cid, &[]);
+        SHM_USAGE.Remove(&cid);
 
         info!("Container {} destroyed", &cid);
         return Ok(());
@@ -675,6 +849,7 @@ pub fn NewProcess(process: Process, creds: &auth::Credentials, k: &Kernel) -> Cr
         IPCNamespace: ipcns,
         ContainerID: process.ID,
         Stdiofds: stdiofds,
+        ListenFds: process.ListenFds,
         Terminal: process.Terminal,
         ExecId: process.ExecId.clone(),
         ..Default::default()
@@ -12,18 +12,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::string::String;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 
 use super::super::super::control_msg::*;
+use super::super::super::linux_def::*;
 use super::super::kernel::kernel::*;
+use super::super::task::*;
+use super::super::threadmgr::thread::*;
+use super::super::threadmgr::thread_group::*;
 
-pub fn Processes(k: &Kernel, containerID: &str) -> Vec<ProcessInfo> {
+// containerThreadGroups returns the live thread groups and their pids of the container,
+// all the thread groups when containerID is empty.
+fn containerThreadGroups(k: &Kernel, containerID: &str) -> Vec<(i32, ThreadGroup, Thread)> {
     let ts = k.TaskSet();
     let root = ts.Root();
     let tgs = root.ThreadGroups();
 
     let mut ret = Vec::new();
-
     for tg in tgs {
         let pid = root.IDOfThreadGroup(&tg);
         // If tg has already been reaped ignore it.
@@ -31,28 +38,199 @@ pub fn Processes(k: &Kernel, containerID: &str) -> Vec<ProcessInfo> {
             continue;
         }
 
-        let lead = tg.Leader().unwrap();
+        let lead = match tg.Leader() {
+            None => continue,
+            Some(l) => l,
+        };
 
-        if containerID.len() == 0 && containerID != &lead.ContainerID() {
+        if containerID.len() != 0 && containerID != &lead.ContainerID() {
             continue;
         }
 
+        ret.push((pid, tg, lead));
+    }
+
+    return ret;
+}
+
+pub fn Processes(k: &Kernel, containerID: &str) -> Vec<ProcessInfo> {
+    let root = k.TaskSet().Root();
+    let now = Task::Current().Now();
+
+    let mut ret = Vec::new();
+
+    for (pid, tg, lead) in containerThreadGroups(k, containerID) {
         let mut ppid = 0;
         match lead.Parent() {
             None => (),
             Some(p) => ppid = root.IDOfThreadGroup(&p.ThreadGroup()),
         }
 
+        let cpu = tg.CPUStats();
+        let cputime = cpu.UserTime + cpu.SysTime;
+        let elapsed = now.Sub(lead.StartTime());
+        let utilization = if elapsed > 0 {
+            (cputime as i128 * 100 / elapsed as i128) as i32
+        } else {
+            0
+        };
+
+        let (vsz, rss) = {
+            let mm = lead.MemoryManager();
+            let _ml = mm.MappingReadLock();
+            (mm.VirtualMemorySizeLocked(), mm.ResidentSetSizeLocked())
+        };
+
         ret.push(ProcessInfo {
             UID: lead.Credentials().lock().EffectiveKUID,
             PID: pid,
             PPID: ppid,
             STime: lead.StartTime().0,
-            Utilization: 0,
-            Time: 0,
+            Utilization: utilization,
+            Time: cputime,
             Cmd: lead.Name(),
+            RSS: rss,
+            VSZ: vsz,
+            Threads: tg.Count() as i32,
         })
     }
 
     return ret;
 }
+
+// Tasks returns the state of each thread of the container.
+pub fn Tasks(k: &Kernel, containerID: &str) -> Vec<TaskInfo> {
+    let root = k.TaskSet().Root();
+
+    let mut ret = Vec::new();
+    for (pid, tg, _) in containerThreadGroups(k, containerID) {
+        let threads: Vec<Thread> = tg.lock().tasks.iter().cloned().collect();
+        for t in threads {
+            let tid = root.IDOfTask(&t);
+            if tid == 0 {
+                continue;
+            }
+
+            let cpu = t.CPUStats();
+            let state = t.lock().StateStatus().to_string();
+            ret.push(TaskInfo {
+                PID: pid,
+                TID: tid,
+                State: state,
+                UserTime: cpu.UserTime,
+                SysTime: cpu.SysTime,
+                Cmd: t.Name(),
+            })
+        }
+    }
+
+    return ret;
+}
+
+// SockAddrString formats the sockaddr returned by getsockname/getpeername.
+pub fn SockAddrString(addr: &[u8]) -> (String, String) {
+    if addr.len() < 2 {
+        return ("unknown".to_string(), "".to_string());
+    }
+
+    let family = u16::from_ne_bytes([addr[0], addr[1]]) as i32;
+    match family {
+        AFType::AF_INET if addr.len() >= 8 => {
+            let port = u16::from_be_bytes([addr[2], addr[3]]);
+            let str = format!("{}.{}.{}.{}:{}", addr[4], addr[5], addr[6], addr[7], port);
+            return ("inet".to_string(), str);
+        }
+        AFType::AF_INET6 if addr.len() >= 24 => {
+            let port = u16::from_be_bytes([addr[2], addr[3]]);
+            let mut segs = Vec::with_capacity(8);
+            for i in 0..8 {
+                segs.push(format!(
+                    "{:x}",
+                    u16::from_be_bytes([addr[8 + i * 2], addr[9 + i * 2]])
+                ));
+            }
+            return ("inet6".to_string(), format!("[{}]:{}", segs.join(":"), port));
+        }
+        AFType::AF_UNIX => {
+            let path = &addr[2..];
+            if path.len() > 0 && path[0] == 0 {
+                // abstract socket
+                let name = String::from_utf8_lossy(&path[1..]).to_string();
+                return ("unix".to_string(), format!("@{}", name.trim_end_matches('\0')));
+            }
+
+            let end = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+            return (
+                "unix".to_string(),
+                String::from_utf8_lossy(&path[..end]).to_string(),
+            );
+        }
+        AFType::AF_NETLINK => return ("netlink".to_string(), "".to_string()),
+        _ => return (format!("{}", family), "".to_string()),
+    }
+}
+
+fn SocketTypeString(typ: i32) -> String {
+    match typ & SocketType::SOCK_TYPE_MASK {
+        SocketType::SOCK_STREAM => "stream".to_string(),
+        SocketType::SOCK_DGRAM => "dgram".to_string(),
+        SocketType::SOCK_RAW => "raw".to_string(),
+        SocketType::SOCK_SEQPACKET => "seqpacket".to_string(),
+        t => format!("{}", t),
+    }
+}
+
+// Sockets returns the sockets opened by the processes of the container.
+pub fn Sockets(k: &Kernel, containerID: &str) -> Vec<SocketInfo> {
+    let task = Task::Current();
+
+    let mut ret = Vec::new();
+    for (pid, _, lead) in containerThreadGroups(k, containerID) {
+        let fdTbl = lead.lock().fdTbl.clone();
+        for fd in fdTbl.GetFDs() {
+            let file = match fdTbl.Get(fd) {
+                Err(_) => continue,
+                Ok((f, _)) => f,
+            };
+
+            if !file.Dirent.Inode().StableAttr().IsSocket() {
+                continue;
+            }
+
+            let sock = file.FileOp.clone();
+            let mut buf: [u8; 128] = [0; 128];
+
+            let (family, local) = match sock.GetSockName(task, &mut buf) {
+                Err(_) => ("unknown".to_string(), "".to_string()),
+                Ok(n) => SockAddrString(&buf[..n as usize]),
+            };
+
+            let peer = match sock.GetPeerName(task, &mut buf) {
+                Err(_) => "".to_string(),
+                Ok(n) => SockAddrString(&buf[..n as usize]).1,
+            };
+
+            let mut typ = [0u8; 4];
+            let typ = match sock.GetSockOpt(
+                task,
+                LibcConst::SOL_SOCKET as i32,
+                LibcConst::SO_TYPE as i32,
+                &mut typ,
+            ) {
+                Ok(_) => SocketTypeString(i32::from_ne_bytes(typ)),
+                Err(_) => "".to_string(),
+            };
+
+            ret.push(SocketInfo {
+                PID: pid,
+                FD: fd,
+                Family: family,
+                Type: typ,
+                LocalAddr: local,
+                PeerAddr: peer,
+            });
+        }
+    }
+
+    return ret;
+}
@@ -39,6 +39,7 @@ pub fn IsSupportedDevMount(m: &oci::Mount) -> bool {
         "/dev/full",
         "/dev/random",
         "/dev/urandom",
+        "/dev/hwrng",
         "/dev/shm",
         "/dev/pts",
         "/dev/ptmx",
@@ -0,0 +1,234 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use super::super::super::auth::id::*;
+use super::super::super::auth::*;
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+
+pub const XATTR_NAME_POSIX_ACL_ACCESS: &str = "system.posix_acl_access";
+pub const XATTR_NAME_POSIX_ACL_DEFAULT: &str = "system.posix_acl_default";
+
+pub const POSIX_ACL_XATTR_VERSION: u32 = 0x0002;
+
+// posix_acl_xattr_entry.e_tag
+pub struct AclTag {}
+
+impl AclTag {
+    pub const ACL_USER_OBJ: u16 = 0x01;
+    pub const ACL_USER: u16 = 0x02;
+    pub const ACL_GROUP_OBJ: u16 = 0x04;
+    pub const ACL_GROUP: u16 = 0x08;
+    pub const ACL_MASK: u16 = 0x10;
+    pub const ACL_OTHER: u16 = 0x20;
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct AclEntry {
+    pub Tag: u16,
+    pub Perm: u16,
+    pub Id: u32,
+}
+
+impl AclEntry {
+    pub fn PermMask(&self) -> PermMask {
+        return PermMask {
+            read: self.Perm & 0x4 != 0,
+            write: self.Perm & 0x2 != 0,
+            execute: self.Perm & 0x1 != 0,
+        };
+    }
+}
+
+// PosixAcl is the parsed content of the system.posix_acl_access xattr
+#[derive(Debug, Default, Clone)]
+pub struct PosixAcl {
+    pub Entries: Vec<AclEntry>,
+}
+
+impl PosixAcl {
+    const HEADER_SIZE: usize = 4;
+    const ENTRY_SIZE: usize = 8;
+
+    // Parse the posix_acl_xattr_header followed by posix_acl_xattr_entry array,
+    // all fields are little endian.
+    pub fn Parse(buf: &[u8]) -> Result<Self> {
+        if buf.len() < Self::HEADER_SIZE || (buf.len() - Self::HEADER_SIZE) % Self::ENTRY_SIZE != 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let version = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        if version != POSIX_ACL_XATTR_VERSION {
+            return Err(Error::SysError(SysErr::EOPNOTSUPP));
+        }
+
+        let mut entries = Vec::new();
+        let mut pos = Self::HEADER_SIZE;
+        while pos < buf.len() {
+            let e = &buf[pos..pos + Self::ENTRY_SIZE];
+            entries.push(AclEntry {
+                Tag: u16::from_le_bytes([e[0], e[1]]),
+                Perm: u16::from_le_bytes([e[2], e[3]]),
+                Id: u32::from_le_bytes([e[4], e[5], e[6], e[7]]),
+            });
+            pos += Self::ENTRY_SIZE;
+        }
+
+        return Ok(Self { Entries: entries });
+    }
+
+    // A minimal acl only has ACL_USER_OBJ, ACL_GROUP_OBJ and ACL_OTHER entries,
+    // which is exactly equivalent to the file mode bits.
+    pub fn IsMinimal(&self) -> bool {
+        for e in &self.Entries {
+            match e.Tag {
+                AclTag::ACL_USER_OBJ | AclTag::ACL_GROUP_OBJ | AclTag::ACL_OTHER => (),
+                _ => return false,
+            }
+        }
+
+        return true;
+    }
+
+    fn Mask(&self) -> Option<PermMask> {
+        for e in &self.Entries {
+            if e.Tag == AclTag::ACL_MASK {
+                return Some(e.PermMask());
+            }
+        }
+
+        return None;
+    }
+
+    // Permission implements the access check algorithm of acl(5) and returns
+    // whether reqPerms is granted. inGroup reports whether the caller is a member
+    // of the group, including the supplementary groups.
+    pub fn Permission(
+        &self,
+        owner: &FileOwner,
+        euid: KUID,
+        inGroup: &dyn Fn(KGID) -> bool,
+        reqPerms: &PermMask,
+    ) -> bool {
+        let masked = |p: PermMask| -> PermMask {
+            match self.Mask() {
+                None => p,
+                Some(m) => PermMask {
+                    read: p.read && m.read,
+                    write: p.write && m.write,
+                    execute: p.execute && m.execute,
+                },
+            }
+        };
+
+        if owner.UID == euid {
+            for e in &self.Entries {
+                if e.Tag == AclTag::ACL_USER_OBJ {
+                    return e.PermMask().SupersetOf(reqPerms);
+                }
+            }
+        }
+
+        for e in &self.Entries {
+            if e.Tag == AclTag::ACL_USER && KUID(e.Id) == euid {
+                return masked(e.PermMask()).SupersetOf(reqPerms);
+            }
+        }
+
+        let mut found = false;
+        for e in &self.Entries {
+            let matched = match e.Tag {
+                AclTag::ACL_GROUP_OBJ => inGroup(owner.GID),
+                AclTag::ACL_GROUP => inGroup(KGID(e.Id)),
+                _ => false,
+            };
+
+            if matched {
+                if masked(e.PermMask()).SupersetOf(reqPerms) {
+                    return true;
+                }
+                found = true;
+            }
+        }
+
+        if found {
+            return false;
+        }
+
+        for e in &self.Entries {
+            if e.Tag == AclTag::ACL_OTHER {
+                return e.PermMask().SupersetOf(reqPerms);
+            }
+        }
+
+        return false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn Build(entries: &[(u16, u16, u32)]) -> Vec<u8> {
+        let mut buf = POSIX_ACL_XATTR_VERSION.to_le_bytes().to_vec();
+        for (tag, perm, id) in entries {
+            buf.extend_from_slice(&tag.to_le_bytes());
+            buf.extend_from_slice(&perm.to_le_bytes());
+            buf.extend_from_slice(&id.to_le_bytes());
+        }
+        return buf;
+    }
+
+    #[test]
+    fn TestAclPermission() {
+        let buf = Build(&[
+            (AclTag::ACL_USER_OBJ, 6, 0),
+            (AclTag::ACL_USER, 6, 1001),
+            (AclTag::ACL_GROUP_OBJ, 4, 0),
+            (AclTag::ACL_GROUP, 7, 2000),
+            (AclTag::ACL_MASK, 4, 0),
+            (AclTag::ACL_OTHER, 0, 0),
+        ]);
+        let acl = PosixAcl::Parse(&buf).unwrap();
+        assert!(!acl.IsMinimal());
+
+        let owner = FileOwner {
+            UID: KUID(1000),
+            GID: KGID(100),
+        };
+        let write = PermMask {
+            write: true,
+            ..Default::default()
+        };
+        let read = PermMask {
+            read: true,
+            ..Default::default()
+        };
+
+        // owner entry is not masked
+        assert!(acl.Permission(&owner, KUID(1000), &|_| false, &write));
+        // named user entry is limited by the mask
+        assert!(acl.Permission(&owner, KUID(1001), &|_| false, &read));
+        assert!(!acl.Permission(&owner, KUID(1001), &|_| false, &write));
+        // supplementary group matches the named group entry
+        assert!(acl.Permission(&owner, KUID(1002), &|g| g == KGID(2000), &read));
+        assert!(!acl.Permission(&owner, KUID(1002), &|g| g == KGID(2000), &write));
+        // other
+        assert!(!acl.Permission(&owner, KUID(1002), &|_| false, &read));
+
+        assert!(PosixAcl::Parse(&buf[..7]).is_err());
+    }
+}
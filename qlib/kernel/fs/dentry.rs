@@ -97,8 +97,15 @@ impl<'a> DirCtx<'a> {
         self.Serializer.CopyOut(task, name, attr)
     }
 
-    pub fn ReadDir(&mut self, task: &Task, map: &DentMap) -> Result<usize> {
+    // offset is the index of the first entry to emit. It is only used when there is
+    // no DirCursor, e.g. after the directory is lseek'ed, otherwise the cursor name
+    // is used so that concurrent create/unlink won't make the iteration skip entries.
+    pub fn ReadDir(&mut self, task: &Task, map: &DentMap, offset: i32) -> Result<usize> {
+        let mut skip = 0;
         let range = if self.DirCursor == "".to_string() {
+            if offset > 0 {
+                skip = offset as usize;
+            }
             map.GetAll()
         } else {
             let str = self.DirCursor.clone();
@@ -112,6 +119,11 @@ impl<'a> DirCtx<'a> {
                 continue;
             }
 
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+
             match self.DirEmit(task, name, attr) {
                 Err(error) => {
                     if count > 0 {
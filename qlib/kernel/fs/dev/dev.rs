@@ -42,6 +42,9 @@ const FULL_DEV_MINOR: u32 = 7;
 const RANDOM_DEV_MINOR: u32 = 8;
 const URANDOM_DEV_MINOR: u32 = 9;
 
+const MISC_DEV_MAJOR: u16 = 10;
+const HWRNG_DEV_MINOR: u32 = 183;
+
 fn NewTTYDevice(iops: &Arc<TTYDevice>, msrc: &Arc<QMutex<MountSource>>) -> Inode {
     let deviceId = DEV_DEVICE.lock().id.DeviceID();
     let inodeId = DEV_DEVICE.lock().NextIno();
@@ -142,7 +145,12 @@ fn NewFullDevice(iops: &Arc<FullDevice>, msrc: &Arc<QMutex<MountSource>>) -> Ino
     return Inode(Arc::new(QMutex::new(inodeInternal)));
 }
 
-fn NewRandomDevice(iops: &Arc<RandomDevice>, msrc: &Arc<QMutex<MountSource>>, minor: u32) -> Inode {
+fn NewRandomDevice(
+    iops: &Arc<RandomDevice>,
+    msrc: &Arc<QMutex<MountSource>>,
+    major: u16,
+    minor: u32,
+) -> Inode {
     let deviceId = DEV_DEVICE.lock().id.DeviceID();
     let inodeId = DEV_DEVICE.lock().NextIno();
 
@@ -151,7 +159,7 @@ fn NewRandomDevice(iops: &Arc<RandomDevice>, msrc: &Arc<QMutex<MountSource>>, mi
         DeviceId: deviceId,
         InodeId: inodeId,
         BlockSize: MemoryDef::PAGE_SIZE as i64,
-        DeviceFileMajor: MEM_DEV_MAJOR,
+        DeviceFileMajor: major,
         DeviceFileMinor: minor,
     };
 
@@ -278,6 +286,7 @@ pub fn NewDev(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
         NewRandomDevice(
             &Arc::new(RandomDevice::New(task, &ROOT_OWNER, &FileMode(0o0666))),
             msrc,
+            MEM_DEV_MAJOR,
             RANDOM_DEV_MINOR,
         ),
     );
@@ -286,9 +295,20 @@ pub fn NewDev(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
         NewRandomDevice(
             &Arc::new(RandomDevice::New(task, &ROOT_OWNER, &FileMode(0o0666))),
             msrc,
+            MEM_DEV_MAJOR,
             URANDOM_DEV_MINOR,
         ),
     );
+    // The host rng device, like virtio-rng it reads straight from the host.
+    contents.insert(
+        "hwrng".to_string(),
+        NewRandomDevice(
+            &Arc::new(RandomDevice::NewHwRng(task, &ROOT_OWNER, &FileMode(0o0600))),
+            msrc,
+            MISC_DEV_MAJOR,
+            HWRNG_DEV_MINOR,
+        ),
+    );
 
     // A devpts is typically mounted at /dev/pts to provide
     // pseudoterminal support. Place an empty directory there for
@@ -24,6 +24,7 @@ use super::super::super::super::common::*;
 use super::super::super::super::linux_def::*;
 use super::super::super::super::mem::io::*;
 use super::super::super::super::mem::seq::*;
+use super::super::super::kernel::entropy::*;
 use super::super::super::kernel::time::*;
 use super::super::super::kernel::waiter::qlock::*;
 use super::super::super::kernel::waiter::*;
@@ -43,11 +44,13 @@ use super::super::fsutil::inode::*;
 use super::super::inode::*;
 use super::super::mount::*;
 
-pub struct RandomDevice(pub QRwLock<InodeSimpleAttributesInternal>);
+// RandomDevice backs /dev/random, /dev/urandom and /dev/hwrng. The first two
+// read from the guest entropy pool, /dev/hwrng reads from the host rng device.
+pub struct RandomDevice(pub QRwLock<InodeSimpleAttributesInternal>, pub bool);
 
 impl Default for RandomDevice {
     fn default() -> Self {
-        return Self(QRwLock::new(Default::default()));
+        return Self(QRwLock::new(Default::default()), false);
     }
 }
 
@@ -67,7 +70,13 @@ impl RandomDevice {
             &FilePermissions::FromMode(*mode),
             FSMagic::TMPFS_MAGIC,
         );
-        return Self(QRwLock::new(attr));
+        return Self(QRwLock::new(attr), false);
+    }
+
+    pub fn NewHwRng(task: &Task, owner: &FileOwner, mode: &FileMode) -> Self {
+        let mut dev = Self::New(task, owner, mode);
+        dev.1 = true;
+        return dev;
     }
 }
 
@@ -194,7 +203,7 @@ impl InodeOperations for RandomDevice {
         flags.Pread = true;
         flags.PWrite = true;
 
-        let fops = RandomFileOperations {};
+        let fops = RandomFileOperations { hw: self.1 };
 
         let f = FileInternal {
             UniqueId: NewUID(),
@@ -284,7 +293,9 @@ impl InodeOperations for RandomDevice {
     }
 }
 
-pub struct RandomFileOperations {}
+pub struct RandomFileOperations {
+    pub hw: bool,
+}
 
 impl Waitable for RandomFileOperations {
     fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
@@ -328,15 +339,19 @@ impl FileOperations for RandomFileOperations {
     fn ReadAt(
         &self,
         task: &Task,
-        _f: &File,
+        f: &File,
         dsts: &mut [IoVec],
         _offset: i64,
         _blocking: bool,
     ) -> Result<i64> {
+        if !self.hw {
+            WaitReady(task, !f.Blocking())?;
+        }
+
         let len = IoVec::NumBytes(dsts);
         let buf = DataBuff::New(len);
 
-        let mut ioReader = RandomReader {};
+        let mut ioReader = RandomReader { hw: self.hw };
         let mut reader = FromIOReader {
             reader: &mut ioReader,
         };
@@ -403,11 +418,18 @@ impl FileOperations for RandomFileOperations {
 
 impl SockOperations for RandomFileOperations {}
 
-pub struct RandomReader {}
+pub struct RandomReader {
+    pub hw: bool,
+}
 
 impl IOReader for RandomReader {
     fn Read(&mut self, buf: &mut [u8]) -> Result<i64> {
-        let res = Kernel::HostSpace::GetRandom(&buf[0] as *const _ as u64, buf.len() as u64, 0);
+        if !self.hw {
+            GetRandom(buf)?;
+            return Ok(buf.len() as i64);
+        }
+
+        let res = Kernel::HostSpace::HwRandom(&buf[0] as *const _ as u64, buf.len() as u64);
         if res < 0 {
             return Err(Error::SysError(-res as i32));
         }
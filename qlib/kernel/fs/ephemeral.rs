@@ -0,0 +1,51 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::sync::Arc;
+use core::sync::atomic::AtomicU64;
+
+use super::super::super::linux_def::*;
+use super::super::SHARESPACE;
+use super::mount::*;
+
+// The ephemeral storage of the sandbox, as the kubelet counts it: what the
+// sandbox writes to its root filesystem and keeps in the tmpfs mounts. The
+// growth of the root filesystem files and the tmpfs files are charged here,
+// and past the EphemeralStorage limit of the config the writes fail with
+// ENOSPC, so a runaway log file fills the limit instead of the node disk.
+// The charge starts from what the writable layer already holds, so a restart
+// of the container doesn't reset it.
+lazy_static! {
+    pub static ref EPHEMERAL_STORAGE: Arc<FsUsage> = Arc::new(FsUsage {
+        size: EphemeralStorageLimit(),
+        persistent: true,
+        used: AtomicU64::new(SHARESPACE.config.read().EphemeralStorageUsed),
+        ..Default::default()
+    });
+}
+
+// EphemeralStorageLimit returns the limit in bytes, 0 is unlimited.
+pub fn EphemeralStorageLimit() -> u64 {
+    return SHARESPACE.config.read().EphemeralStorage * MemoryDef::ONE_MB;
+}
+
+// RootfsUsage returns the usage of the root filesystem mount. Without a limit
+// the root filesystem is not charged and statfs(2) reports the host filesystem.
+pub fn RootfsUsage() -> Option<Arc<FsUsage>> {
+    if EphemeralStorageLimit() == 0 {
+        return None;
+    }
+
+    return Some(EPHEMERAL_STORAGE.clone());
+}
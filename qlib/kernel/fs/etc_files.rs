@@ -0,0 +1,125 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::auth::*;
+use super::super::super::device::*;
+use super::super::super::linux_def::*;
+use super::super::super::loader::*;
+use super::super::task::*;
+use super::attr::*;
+use super::inode::*;
+use super::mount::*;
+use super::procfs::inode::*;
+
+// The virtual /etc/resolv.conf, /etc/hosts, /etc/hostname and /etc/machine-id
+// of the sandbox. They are served from memory and mounted over the files of the
+// container rootfs, all the containers of the pod see the same ones. An update replaces
+// the content at once: a file opened before it keeps reading the old content
+// and the next open gets the new one, so a resolver never reads half of each.
+lazy_static! {
+    pub static ref ETC_FILES: EtcFileSet = EtcFileSet::default();
+}
+
+pub struct EtcFile {
+    pub iops: StaticFileInodeOps,
+    pub inode: Inode,
+}
+
+#[derive(Default)]
+pub struct EtcFileSet {
+    // path -> file
+    pub files: QMutex<BTreeMap<String, EtcFile>>,
+}
+
+impl EtcFileSet {
+    // Update replaces the content of the files set in files.
+    pub fn Update(&self, task: &Task, files: &EtcFiles) {
+        let mut set = self.files.lock();
+        for (path, content) in files.Files().iter() {
+            let content = match content {
+                None => continue,
+                Some(c) => Arc::new(c.as_bytes().to_vec()),
+            };
+
+            match set.get(*path) {
+                Some(file) => {
+                    let mut iops = file.iops.write();
+                    iops.unstable = WithCurrentTime(task, &iops.unstable);
+                    iops.unstable.Size = content.len() as i64;
+                    iops.content = content;
+                }
+                None => {
+                    set.insert(path.to_string(), NewEtcFile(task, &content));
+                }
+            }
+
+            info!("etc files: {} updated", path);
+        }
+    }
+
+    pub fn Inode(&self, path: &str) -> Option<Inode> {
+        return self.files.lock().get(path).map(|f| f.inode.clone());
+    }
+}
+
+fn NewEtcFile(task: &Task, content: &Arc<Vec<u8>>) -> EtcFile {
+    let unstable = WithCurrentTime(
+        task,
+        &UnstableAttr {
+            Owner: ROOT_OWNER,
+            Perms: FilePermissions::FromMode(FileMode(0o444)),
+            Size: content.len() as i64,
+            Links: 1,
+            ..Default::default()
+        },
+    );
+
+    let iops = StaticFileInodeOps(Arc::new(QRwLock::new(StaticFileInodeOpsInternal {
+        fsType: FSMagic::TMPFS_MAGIC,
+        unstable: unstable,
+        content: content.clone(),
+    })));
+
+    let mut msrc = MountSource::NewPseudoMountSource();
+    msrc.Flags.ReadOnly = true;
+
+    let deviceId = PSEUDO_DEVICE.lock().id.DeviceID();
+    let inodeId = PSEUDO_DEVICE.lock().NextIno();
+    let sattr = StableAttr {
+        Type: InodeType::RegularFile,
+        DeviceId: deviceId,
+        InodeId: inodeId,
+        BlockSize: MemoryDef::PAGE_SIZE as i64,
+        DeviceFileMajor: 0,
+        DeviceFileMinor: 0,
+    };
+
+    let inode = Inode::New(
+        &Arc::new(StaticFileInodeOps(iops.0.clone())),
+        &Arc::new(QMutex::new(msrc)),
+        &sattr,
+    );
+
+    return EtcFile {
+        iops: iops,
+        inode: inode,
+    };
+}
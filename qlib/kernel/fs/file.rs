@@ -171,6 +171,19 @@ pub trait SockOperations: Sync + Send {
         return Err(Error::SysError(SysErr::ENOTSOCK));
     }
 
+    // SendSegments sends the first datagrams of msgs as one segmented by the
+    // host, it returns the count of them sent, 0 if the socket sends them one
+    // by one.
+    fn SendSegments(
+        &self,
+        _task: &Task,
+        _msgs: &mut [MMsgHdr],
+        _flags: i32,
+        _deadline: Option<Time>,
+    ) -> Result<usize> {
+        return Ok(0);
+    }
+
     fn SetRecvTimeout(&self, _nanoseconds: i64) {
         return;
     }
@@ -249,7 +262,9 @@ pub enum FileOpsType {
     ReadonlyFileOperations,
     DynamicDirFileOperations,
     SignalOperation,
-    InotifyFileOperations
+    InotifyFileOperations,
+    PidfdOperation,
+    SeccompListener,
 }
 
 pub trait FileOperations: Sync + Send + Waitable + SockOperations + SpliceOperations {
@@ -699,6 +714,7 @@ impl File {
                 *offsetLock = current + n;
             }
 
+            self.SyncWrite(task, current, n)?;
             return Ok(n);
         } else {
             let blocking = self.Blocking();
@@ -755,6 +771,7 @@ impl File {
             if self.flags.lock().0.Append {
                 let (cnt, len) = fops.Append(task, self, srcs)?;
                 *offsetLock = len;
+                self.SyncWrite(task, len - cnt, cnt)?;
                 return Ok(cnt);
             }
 
@@ -819,9 +836,31 @@ impl File {
             fops.WriteAt(task, self, srcs, offset, blocking)?
         };
 
+        self.SyncWrite(task, offset, n)?;
         return Ok(n);
     }
 
+    // SyncWrite makes the n bytes written at offset durable when the file is
+    // opened with O_SYNC or O_DSYNC, as generic_write_sync of Linux. Only the
+    // regular files are synced, a pipe, a socket or a character device has
+    // nothing to make durable.
+    pub fn SyncWrite(&self, task: &Task, offset: i64, n: i64) -> Result<()> {
+        if n <= 0 || !self.Dirent.Inode().StableAttr().IsRegular() {
+            return Ok(());
+        }
+
+        let flags = self.flags.lock().0;
+        let syncType = if flags.Sync {
+            SyncType::SyncAll
+        } else if flags.DSync {
+            SyncType::SyncData
+        } else {
+            return Ok(());
+        };
+
+        return self.Fsync(task, offset, offset + n - 1, syncType);
+    }
+
     pub fn Fsync(&self, task: &Task, start: i64, end: i64, syncType: SyncType) -> Result<()> {
         let fops = self.FileOp.clone();
         return fops.Fsync(task, self, start, end, syncType);
@@ -267,7 +267,7 @@ impl FileOperations for OverlayFileOperations {
         offset: i32,
     ) -> (i32, Result<i64>) {
         let cache = self.dirCache.lock();
-        let n = match dirCtx.ReadDir(task, &cache) {
+        let n = match dirCtx.ReadDir(task, &cache, offset) {
             Err(e) => return (offset, Err(e)),
             Ok(n) => n,
         };
@@ -19,6 +19,7 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 
 use super::super::super::common::*;
+use super::super::super::linux_def::*;
 use super::super::super::singleton::*;
 use super::super::task::*;
 use super::inode::*;
@@ -43,6 +44,15 @@ pub fn RegisterFilesystem<T: Filesystem + 'static>(f: &Arc<QMutex<T>>) {
     FILESYSTEMS.lock().RegisterFilesystem(f)
 }
 
+// FindFilesystemForMount returns the filesystem of the type of a mount, the
+// mount fails with ENODEV on an unknown type.
+pub fn FindFilesystemForMount(name: &str) -> Result<Arc<QMutex<Filesystem>>> {
+    match FindFilesystem(name) {
+        None => return Err(Error::SysError(SysErr::ENODEV)),
+        Some(f) => return Ok(f),
+    }
+}
+
 pub fn GetFilesystems() -> Vec<Arc<QMutex<Filesystem>>> {
     return FILESYSTEMS.lock().GetFilesystems();
 }
@@ -104,4 +114,10 @@ pub trait Filesystem: Send {
     ) -> Result<Inode>;
     fn AllowUserMount(&self) -> bool;
     fn AllowUserList(&self) -> bool;
+
+    // SpecOptions returns the keys of the options of a mount of the OCI spec
+    // passed to Mount as its data, the other options are dropped.
+    fn SpecOptions(&self) -> Vec<&'static str> {
+        return Vec::new();
+    }
 }
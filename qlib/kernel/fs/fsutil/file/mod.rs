@@ -67,15 +67,17 @@ pub fn SeekWithDirCursor(
                 return Ok(offset);
             }
             InodeType::Directory | InodeType::SpecialDirectory => {
-                if offset != 0 {
+                if offset < 0 {
                     return Err(Error::SysError(SysErr::EINVAL));
                 }
 
+                // the offset is the entry index, reset the cursor so that the next
+                // readdir starts from the entry at the offset.
                 if let Some(cursor) = dirCursor {
                     *cursor = "".to_string();
                 }
 
-                return Ok(0);
+                return Ok(offset);
             }
             _ => return Err(Error::SysError(SysErr::EINVAL)),
         }
@@ -89,15 +91,17 @@ pub fn SeekWithDirCursor(
                 return Ok(current + offset);
             }
             InodeType::Directory | InodeType::SpecialDirectory => {
-                if offset != 0 {
+                if current + offset < 0 {
                     return Err(Error::SysError(SysErr::EINVAL));
                 }
 
-                if let Some(cursor) = dirCursor {
-                    *cursor = "".to_string()
+                if offset != 0 {
+                    if let Some(cursor) = dirCursor {
+                        *cursor = "".to_string()
+                    }
                 }
 
-                return Ok(current);
+                return Ok(current + offset);
             }
             _ => return Err(Error::SysError(SysErr::EINVAL)),
         }
@@ -75,6 +75,19 @@ pub trait ReadonlyFileNode: Send + Sync {
     ) -> Result<i64> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
+
+    // WriteAt is for the few proc files which take a write, e.g.
+    // timens_offsets, the others are read only.
+    fn WriteAt(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _srcs: &[IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
 }
 
 pub struct ReadonlyFileOperations<T: 'static + ReadonlyFileNode> {
@@ -133,13 +146,13 @@ impl<T: 'static + ReadonlyFileNode> FileOperations for ReadonlyFileOperations<T>
 
     fn WriteAt(
         &self,
-        _task: &Task,
-        _f: &File,
-        _srcs: &[IoVec],
-        _offset: i64,
-        _blocking: bool,
+        task: &Task,
+        f: &File,
+        srcs: &[IoVec],
+        offset: i64,
+        blocking: bool,
     ) -> Result<i64> {
-        return Err(Error::SysError(SysErr::EINVAL));
+        return self.node.WriteAt(task, f, srcs, offset, blocking);
     }
 
     fn Append(&self, task: &Task, f: &File, srcs: &[IoVec]) -> Result<(i64, i64)> {
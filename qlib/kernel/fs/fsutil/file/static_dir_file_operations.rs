@@ -122,7 +122,7 @@ impl FileOperations for StaticDirFileOperations {
         dirCtx: &mut DirCtx,
         offset: i32,
     ) -> (i32, Result<i64>) {
-        let n = match dirCtx.ReadDir(task, &self.dentryMap) {
+        let n = match dirCtx.ReadDir(task, &self.dentryMap, offset) {
             Err(e) => return (offset, Err(e)),
             Ok(n) => n,
         };
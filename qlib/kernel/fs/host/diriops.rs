@@ -20,6 +20,8 @@ use alloc::sync::Weak;
 use alloc::vec::Vec;
 use core::any::Any;
 use core::ops::Deref;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
 
 use crate::qlib::kernel::fs::host::dirent::Dirent64;
 use super::super::super::super::auth::*;
@@ -46,6 +48,15 @@ use super::*;
 
 pub const MAX_FILENAME_LEN : usize = (1 << 16) - 1;
 
+// the generation of the readdir caches, the caches of an older generation are
+// stale, e.g. filled before the cache was turned off and on again
+pub static READDIR_CACHE_GEN: AtomicU64 = AtomicU64::new(0);
+
+// InvalidateReaddirCaches drops the readdir caches of all the directories.
+pub fn InvalidateReaddirCaches() {
+    READDIR_CACHE_GEN.fetch_add(1, Ordering::SeqCst);
+}
+
 pub struct HostDirOpIntern {
     pub mops: Arc<QMutex<MountSourceOperations>>,
     //this should be SuperOperations
@@ -55,6 +66,8 @@ pub struct HostDirOpIntern {
 
     pub overrides: BTreeMap<String, Inode>,
     pub readdirCache: Option<DentMap>,
+    // the READDIR_CACHE_GEN of readdirCache
+    pub readdirCacheGen: u64,
 }
 
 impl Default for HostDirOpIntern {
@@ -66,6 +79,7 @@ impl Default for HostDirOpIntern {
             errorcode: 0,
             overrides: BTreeMap::new(),
             readdirCache: None,
+            readdirCacheGen: 0,
         };
     }
 }
@@ -93,6 +107,7 @@ impl HostDirOpIntern {
             sattr: fstat.StableAttr(),
             errorcode: 0,
             readdirCache: None,
+            readdirCacheGen: 0,
             overrides: BTreeMap::new(),
         };
     }
@@ -132,11 +147,28 @@ impl HostDirOpIntern {
                     pos += (*d).reclen as u64;
                 }
 
+                let pathname = CString::FromAddr(&name[0] as *const _ as u64);
+                let pathname = pathname.Str().unwrap().to_string();
+
+                // Some host filesystems don't fill d_type, we have to stat the entry
+                // to report the right d_type in getdents.
+                let inodeType = if dType == DType::DT_UNKNOWN {
+                    let mut fstat = LibcStat::default();
+                    let ret = Fstatat(fd, &pathname, &mut fstat, ATType::AT_SYMLINK_NOFOLLOW);
+                    if ret < 0 {
+                        // the entry might be removed in the middle
+                        continue;
+                    }
+                    InodeType(fstat.st_mode)
+                } else {
+                    InodeType(DType::ModeType(dType) as u32)
+                };
+
                 // Construct the key to find the virtual inode.
                 // Directory entries reside on the same Device
                 // and SecondaryDevice as their parent.
                 let dentry = DentAttr {
-                    Type: InodeType(DType::ModeType(dType) as u32),
+                    Type: inodeType,
                     InodeId: HOSTFILE_DEVICE.lock().Map(MultiDeviceKey {
                         Device: deviceId, //ft.deviceId,
                         Inode: inode,
@@ -147,8 +179,7 @@ impl HostDirOpIntern {
                     }),
                 };
 
-                let pathname = CString::FromAddr(&name[0] as *const _ as u64);
-                entries.insert(pathname.Str().unwrap().to_string(), dentry);
+                entries.insert(pathname, dentry);
             }
         }
 
@@ -162,26 +193,29 @@ impl HostDirOpIntern {
         offset: i32,
     ) -> (i32, Result<i64>) {
         if SHARESPACE.config.read().ReaddirCache {
-            if self.readdirCache.is_none() {
+            let gen = READDIR_CACHE_GEN.load(Ordering::Acquire);
+            if self.readdirCache.is_none() || self.readdirCacheGen != gen {
                 let dentryMap = match self.ReadDirAll(task) {
                     Err(e) => return (offset, Err(e)),
                     Ok(entires) => entires,
                 };
 
                 self.readdirCache = Some(dentryMap);
+                self.readdirCacheGen = gen;
             }
 
-            return match dirCtx.ReadDir(task, self.readdirCache.as_ref().unwrap()) {
+            return match dirCtx.ReadDir(task, self.readdirCache.as_ref().unwrap(), offset) {
                 Err(e) => (offset, Err(e)),
                 Ok(count) => (offset + count as i32, Ok(0)),
             };
         } else {
+            self.readdirCache = None;
             let dentryMap = match self.ReadDirAll(task) {
                 Err(e) => return (offset, Err(e)),
                 Ok(entires) => entires,
             };
 
-            return match dirCtx.ReadDir(task, &dentryMap) {
+            return match dirCtx.ReadDir(task, &dentryMap, offset) {
                 Err(e) => (offset, Err(e)),
                 Ok(count) => (offset + count as i32, Ok(0)),
             };
@@ -386,7 +420,7 @@ impl InodeOperations for HostDirOp {
     ) -> Result<File> {
         //let fd = openAt(self.HostFd(), name, (LibcConst::O_RDWR | LibcConst::O_CREAT | LibcConst::O_EXCL) as i32, perm.LinuxMode());
 
-        let owner = task.FileOwner();
+        let (owner, perm) = NewChildOwner(task, dir, perm, false);
 
         let mut newFlags = *flags;
 
@@ -417,11 +451,11 @@ impl InodeOperations for HostDirOp {
     fn CreateDirectory(
         &self,
         task: &Task,
-        _dir: &mut Inode,
+        dir: &mut Inode,
         name: &str,
         perm: &FilePermissions,
     ) -> Result<()> {
-        let owner = task.FileOwner();
+        let (owner, perm) = NewChildOwner(task, dir, perm, true);
 
         let ret = Mkdirat(
             self.HostFd(),
@@ -486,11 +520,11 @@ impl InodeOperations for HostDirOp {
     fn CreateFifo(
         &self,
         task: &Task,
-        _dir: &mut Inode,
+        dir: &mut Inode,
         name: &str,
         perm: &FilePermissions,
     ) -> Result<()> {
-        let owner = task.FileOwner();
+        let (owner, perm) = NewChildOwner(task, dir, perm, false);
 
         let ret = Mkfifoat(
             self.HostFd(),
@@ -508,12 +542,23 @@ impl InodeOperations for HostDirOp {
         return Ok(());
     }
 
-    fn Remove(&self, _task: &Task, _dir: &mut Inode, name: &str) -> Result<()> {
+    fn Remove(&self, _task: &Task, dir: &mut Inode, name: &str) -> Result<()> {
         match self.lock().overrides.remove(name) {
             None => (),
             Some(_) => return Ok(())
         }
 
+        // the last link of a file charged to the ephemeral storage frees its space
+        let usage = dir.lock().MountSource.lock().Usage.clone();
+        let mut freed = 0;
+        if let Some(_) = &usage {
+            let mut fstat = LibcStat::default();
+            let ret = Fstatat(self.HostFd(), name, &mut fstat, ATType::AT_SYMLINK_NOFOLLOW);
+            if ret == 0 && fstat.IsRegularFile() && fstat.st_nlink == 1 {
+                freed = fstat.st_size;
+            }
+        }
+
         let flags = 0; //ATType::AT_REMOVEDIR
 
         let ret = UnLinkAt(self.HostFd(), name, flags);
@@ -522,6 +567,10 @@ impl InodeOperations for HostDirOp {
             return Err(Error::SysError(-ret as i32));
         }
 
+        if let Some(usage) = usage {
+            usage.Charge(-freed);
+        }
+
         self.lock().readdirCache = None;
         return Ok(());
     }
@@ -616,7 +665,8 @@ impl InodeOperations for HostDirOp {
     }
 
     fn Check(&self, task: &Task, inode: &Inode, reqPerms: &PermMask) -> Result<bool> {
-        return ContextCanAccessFile(task, inode, reqPerms);
+        let acl = AccessAcl(self.HostFd());
+        return ContextCanAccessFileWithAcl(task, inode, reqPerms, acl.as_ref());
     }
 
     fn SetPermissions(&self, _task: &Task, _dir: &mut Inode, f: FilePermissions) -> bool {
@@ -106,8 +106,13 @@ impl FileOperations for HostDirFops {
         return Err(Error::SysError(SysErr::EISDIR));
     }
 
-    fn Fsync(&self, _task: &Task, _f: &File, _start: i64, _end: i64, _syncType: SyncType) -> Result<()> {
-        return Ok(());
+    // the directory is synced for the entries created, renamed or removed in it
+    fn Fsync(&self, task: &Task, f: &File, start: i64, end: i64, syncType: SyncType) -> Result<()> {
+        match self.DirOp.Fsync(task, f, start, end, syncType) {
+            // the directory opened with O_PATH can't be synced
+            Err(Error::SysError(SysErr::EBADF)) => return Ok(()),
+            res => return res,
+        }
     }
 
     fn Flush(&self, _task: &Task, _f: &File) -> Result<()> {
@@ -35,6 +35,7 @@ use super::super::dirent::*;
 use super::super::file::*;
 use super::super::fsutil::file::*;
 use super::super::host::hostinodeop::*;
+use super::super::host::ioctl::*;
 use super::super::inode::*;
 
 pub enum HostFileBuf {
@@ -119,6 +120,16 @@ impl FileOperations for HostFileOp {
     }
 
     fn Seek(&self, task: &Task, f: &File, whence: i32, current: i64, offset: i64) -> Result<i64> {
+        // the size of a block device is not in its stat
+        if whence == SeekWhence::SEEK_END && self.InodeOp.InodeType() == InodeType::BlockDevice {
+            let size = BlockDeviceSize(self.InodeOp.HostFd())?;
+            if offset > 0 || size + offset < 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            return Ok(size + offset);
+        }
+
         let mut dirCursor = self.DirCursor.lock();
         let mut cursor = "".to_string();
         let newOffset = SeekWithDirCursor(task, f, whence, current, offset, Some(&mut cursor))?;
@@ -197,7 +208,11 @@ impl FileOperations for HostFileOp {
         return inode.UnstableAttr(task);
     }
 
-    fn Ioctl(&self, _task: &Task, _f: &File, _fd: i32, _request: u64, _val: u64) -> Result<()> {
+    fn Ioctl(&self, task: &Task, f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
+        if self.InodeOp.InodeType() == InodeType::BlockDevice {
+            return ioctlBlockDevice(task, f, self.InodeOp.HostFd(), request, val);
+        }
+
         return Err(Error::SysError(SysErr::ENOTTY));
     }
 
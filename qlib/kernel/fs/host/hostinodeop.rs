@@ -24,12 +24,14 @@ use core::ops::Deref;
 use super::super::super::super::addr::*;
 use super::super::super::super::auth::*;
 use super::super::super::super::common::*;
+use super::super::super::super::device::DecodeDeviceId;
 use super::super::super::super::linux_def::*;
 use super::super::super::super::mem::areaset::*;
 use super::super::super::super::range::*;
 use super::super::super::fd::*;
 use super::super::super::task::*;
 use super::super::super::guestfdnotifier::*;
+use super::super::super::kernel::io_max::*;
 use super::super::super::kernel::time::*;
 pub use super::super::super::memmgr::vma::MMappable;
 use super::super::super::kernel::waiter::qlock::*;
@@ -49,7 +51,9 @@ use super::super::flags::*;
 use super::super::inode::*;
 use super::fs::*;
 use super::hostfileop::*;
+use super::shared_mappable::*;
 use super::util::*;
+use super::writeback::*;
 use super::*;
 
 pub struct MappableInternal {
@@ -157,7 +161,7 @@ impl Default for MappableInternal {
 }
 
 #[derive(Default, Clone)]
-pub struct Mappable(Arc<QMutex<MappableInternal>>);
+pub struct Mappable(pub Arc<QMutex<MappableInternal>>);
 
 impl Deref for Mappable {
     type Target = Arc<QMutex<MappableInternal>>;
@@ -185,6 +189,15 @@ pub struct HostInodeOpIntern {
     pub mappable: Option<Mappable>,
     pub bufWriteLock: QAsyncLock,
     pub hasMappable: bool,
+
+    // the usage of the in memory filesystem the file is charged to, e.g. tmpfs
+    pub usage: Option<Arc<FsUsage>>,
+
+    // the st_dev of the host file, the io.max of a container is keyed by it
+    pub hostDev: u64,
+
+    // the key of the mappable in SHARED_MAPPABLES if it is shared
+    pub sharedKey: Option<SharedMappableKey>,
 }
 
 impl Default for HostInodeOpIntern {
@@ -201,6 +214,9 @@ impl Default for HostInodeOpIntern {
             size: 0,
             bufWriteLock: QAsyncLock::default(),
             hasMappable: false,
+            usage: None,
+            hostDev: 0,
+            sharedKey: None,
         };
     }
 }
@@ -224,11 +240,27 @@ impl Drop for HostInodeOpIntern {
             match self.mappable.take() {
                 None => (),
                 Some(mapable) => {
-                    mapable.lock().Clear();
+                    // the mappable of a read only file might be shared with other inodes
+                    // of the same host file, only the last user can unmap the chunks
+                    if let Some(key) = &self.sharedKey {
+                        if SHARED_MAPPABLES.Release(key, &mapable) {
+                            mapable.lock().Clear();
+                        }
+                    } else if Arc::strong_count(&mapable.0) == 1 {
+                        mapable.lock().Clear();
+                    }
                 }
             }
         }
 
+        if let Some(usage) = self.usage.take() {
+            if !usage.persistent {
+                usage.Charge(-self.size);
+                usage.RemoveFile();
+            }
+        }
+
+        WRITEBACK.Close(self.HostFd);
         HostSpace::Close(self.HostFd);
     }
 }
@@ -253,10 +285,19 @@ impl HostInodeOpIntern {
             size: fstat.st_size,
             bufWriteLock: QAsyncLock::default(),
             hasMappable: false,
+            usage: None,
+            hostDev: fstat.st_dev,
+            sharedKey: None,
         };
 
         if ret.CanMap() {
-            ret.mappable = Some(Mappable::default());
+            if !writeable && ret.sattr.Type == InodeType::RegularFile {
+                let key = SharedMappableKey::New(fstat);
+                ret.mappable = Some(SHARED_MAPPABLES.GetOrCreate(&key));
+                ret.sharedKey = Some(key);
+            } else {
+                ret.mappable = Some(Mappable::default());
+            }
         }
 
         return ret;
@@ -415,8 +456,37 @@ impl HostInodeOpIntern {
         return self.HostFd;
     }
 
+    // SetSize updates the size and charges the change to the usage.
+    pub fn SetSize(&mut self, size: i64) {
+        if let Some(usage) = &self.usage {
+            usage.Charge(size - self.size);
+        }
+
+        self.size = size;
+    }
+
+    // IoMaxDevice returns the host device the io.max of the container limits
+    // the IO of the file on, None if the IO isn't throttled.
+    pub fn IoMaxDevice(&self) -> Option<(u32, u32)> {
+        match self.sattr.Type {
+            InodeType::BlockDevice => {
+                return Some((
+                    self.sattr.DeviceFileMajor as u32,
+                    self.sattr.DeviceFileMinor,
+                ))
+            }
+            InodeType::RegularFile => {
+                let (major, minor) = DecodeDeviceId(self.hostDev as u32);
+                return Some((major as u32, minor));
+            }
+            _ => return None,
+        }
+    }
+
     pub fn BufWriteEnable(&self) -> bool {
-        return SHARESPACE.config.read().FileBufWrite && !self.hasMappable;
+        // the writer must see the error of its write with the strict durability
+        let config = SHARESPACE.config.read();
+        return config.FileBufWrite && !config.StrictDurability && !self.hasMappable;
     }
 
     pub fn BufWriteLock(&self) -> QAsyncLock {
@@ -562,7 +632,34 @@ impl HostInodeOp {
     pub fn UpdateMaxLen(&self, size: i64) {
         let mut h = self.lock();
         if h.size < size {
-            h.size = size;
+            h.SetSize(size);
+        }
+    }
+
+    // SetUsage charges the file to the usage of its filesystem, the charge
+    // moves from the previous usage of the file.
+    pub fn SetUsage(&self, usage: &Arc<FsUsage>) {
+        let mut h = self.lock();
+        if let Some(old) = h.usage.take() {
+            if !old.persistent {
+                old.RemoveFile();
+                old.Charge(-h.size);
+            }
+        }
+
+        if !usage.persistent {
+            usage.AddFile();
+            usage.Charge(h.size);
+        }
+        h.usage = Some(usage.clone());
+    }
+
+    // CheckGrow returns ENOSPC if the file can't grow to size.
+    pub fn CheckGrow(&self, size: i64) -> Result<()> {
+        let h = self.lock();
+        match &h.usage {
+            None => return Ok(()),
+            Some(usage) => return usage.Check(size - h.size),
         }
     }
 
@@ -644,7 +741,17 @@ impl HostInodeOp {
         let iovs = buf.Iovs(size);
         let inodeType = self.InodeType();
 
-        if inodeType != InodeType::RegularFile && inodeType != InodeType::CharacterDevice {
+        let ioMaxDev = self.lock().IoMaxDevice();
+        if let Some(dev) = ioMaxDev {
+            IO_MAX.Throttle(task, dev, false, size as u64)?;
+        }
+
+        // a block device, e.g. a raw block volume, is read at the offset as a
+        // file without the file map
+        if inodeType != InodeType::RegularFile
+            && inodeType != InodeType::CharacterDevice
+            && inodeType != InodeType::BlockDevice
+        {
             let ret = IORead(hostIops.HostFd(), &iovs)?;
 
             // todo: handle partial write
@@ -741,8 +848,19 @@ impl HostInodeOp {
         let iovs = buf.Iovs(len);
 
         let inodeType = self.InodeType();
+        if inodeType == InodeType::RegularFile {
+            hostIops.CheckGrow(offset + len as i64)?;
+        }
 
-        if inodeType != InodeType::RegularFile && inodeType != InodeType::CharacterDevice {
+        let ioMaxDev = self.lock().IoMaxDevice();
+        if let Some(dev) = ioMaxDev {
+            IO_MAX.Throttle(task, dev, true, len as u64)?;
+        }
+
+        if inodeType != InodeType::RegularFile
+            && inodeType != InodeType::CharacterDevice
+            && inodeType != InodeType::BlockDevice
+        {
             let ret = IOWrite(hostIops.HostFd(), &iovs)?;
             return Ok(ret as i64);
         } else {
@@ -753,7 +871,9 @@ impl HostInodeOp {
             };
 
             if SHARESPACE.config.read().UringIO {
-                let ret = if self.BufWriteEnable() {
+                // the writes of a raw block volume are not buffered, the
+                // database sees their errors
+                let ret = if self.BufWriteEnable() && inodeType != InodeType::BlockDevice {
                     let lock = self.BufWriteLock().Lock(task);
                     let count = IOURING.BufFileWrite(hostIops.HostFd(), buf, offset, lock);
                     count
@@ -772,8 +892,9 @@ impl HostInodeOp {
                         return Err(Error::SysError(-ret as i32));
                     }
                 } else if ret >= 0 {
-                    if inodeType != InodeType::CharacterDevice {
+                    if inodeType == InodeType::RegularFile {
                         hostIops.UpdateMaxLen(offset + ret);
+                        WRITEBACK.Dirty(hostIops.HostFd(), ret as u64);
                     }
 
                     return Ok(ret as i64);
@@ -787,7 +908,10 @@ impl HostInodeOp {
             match IOWriteAt(hostIops.HostFd(), &iovs, offset as u64) {
                 Err(e) => return Err(e),
                 Ok(ret) => {
-                    hostIops.UpdateMaxLen(offset + ret);
+                    if inodeType == InodeType::RegularFile {
+                        hostIops.UpdateMaxLen(offset + ret);
+                        WRITEBACK.Dirty(hostIops.HostFd(), ret as u64);
+                    }
                     return Ok(ret);
                 }
             }
@@ -810,6 +934,16 @@ impl HostInodeOp {
             let len = task.CopyDataInFromIovs(&mut buf.buf, srcs, true)?;
             let iovs = buf.Iovs(len);
 
+            if inodeType == InodeType::RegularFile {
+                let size = hostIops.lock().size;
+                hostIops.CheckGrow(size + len as i64)?;
+            }
+
+            let ioMaxDev = self.lock().IoMaxDevice();
+            if let Some(dev) = ioMaxDev {
+                IO_MAX.Throttle(task, dev, true, len as u64)?;
+            }
+
             let iovsAddr = &iovs[0] as *const _ as u64;
             let iovcnt = 1;
 
@@ -820,6 +954,7 @@ impl HostInodeOp {
 
             if inodeType == InodeType::RegularFile {
                 hostIops.UpdateMaxLen(len);
+                WRITEBACK.Dirty(hostIops.HostFd(), count as u64);
             }
 
             return Ok((count, len));
@@ -869,6 +1004,8 @@ impl HostInodeOp {
             return Err(Error::SysError(-ret as i32));
         }
 
+        WRITEBACK.CheckError(fd)?;
+        WRITEBACK.Clean(fd);
         return Ok(());
     }
 
@@ -1195,7 +1332,8 @@ impl InodeOperations for HostInodeOp {
     }
 
     fn Check(&self, task: &Task, inode: &Inode, reqPerms: &PermMask) -> Result<bool> {
-        return ContextCanAccessFile(task, inode, reqPerms);
+        let acl = AccessAcl(self.HostFd());
+        return ContextCanAccessFileWithAcl(task, inode, reqPerms, acl.as_ref());
     }
 
     fn SetPermissions(&self, _task: &Task, _dir: &mut Inode, f: FilePermissions) -> bool {
@@ -1244,12 +1382,13 @@ impl InodeOperations for HostInodeOp {
             return Err(Error::SysError(-ret as i32));
         }
 
-        self.lock().size = size;
+        self.lock().SetSize(size);
 
         return Ok(());
     }
 
     fn Allocate(&self, task: &Task, _dir: &mut Inode, offset: i64, length: i64) -> Result<()> {
+        self.CheckGrow(offset + length)?;
         let ret = Fallocate(self.HostFd(), 0, offset, length);
 
         if ret < 0 {
@@ -1257,7 +1396,7 @@ impl InodeOperations for HostInodeOp {
         }
 
         let uattr = self.UnstableAttr(task)?;
-        self.lock().size = uattr.Size;
+        self.lock().SetSize(uattr.Size);
 
         return Ok(());
     }
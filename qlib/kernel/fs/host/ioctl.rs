@@ -14,6 +14,8 @@
 
 use super::super::super::super::common::*;
 use super::super::super::super::linux_def::*;
+use super::super::super::task::*;
+use super::super::file::*;
 
 use super::tty::*;
 use super::util::*;
@@ -57,3 +59,69 @@ pub fn ioctlSetWinsize(fd: i32, w: &Winsize) -> Result<()> {
 
     return Ok(());
 }
+
+// ioctlBlockDevice serves the ioctls of a host block device, e.g. a raw block
+// volume, from the host device.
+pub fn ioctlBlockDevice(task: &Task, f: &File, fd: i32, request: u64, val: u64) -> Result<()> {
+    match request {
+        IoCtlCmd::BLKGETSIZE64 | IoCtlCmd::BLKGETSIZE => {
+            let mut v: u64 = 0;
+            ioctlHost(fd, request, &mut v as *mut u64 as u64)?;
+            task.CopyOutObj(&v, val)?;
+        }
+        IoCtlCmd::BLKSSZGET
+        | IoCtlCmd::BLKPBSZGET
+        | IoCtlCmd::BLKBSZGET
+        | IoCtlCmd::BLKIOMIN
+        | IoCtlCmd::BLKIOOPT
+        | IoCtlCmd::BLKROGET
+        | IoCtlCmd::BLKDISCARDZEROES => {
+            let mut v: u32 = 0;
+            ioctlHost(fd, request, &mut v as *mut u32 as u64)?;
+            task.CopyOutObj(&v, val)?;
+        }
+        IoCtlCmd::BLKROTATIONAL => {
+            let mut v: u16 = 0;
+            ioctlHost(fd, request, &mut v as *mut u16 as u64)?;
+            task.CopyOutObj(&v, val)?;
+        }
+        IoCtlCmd::BLKDISCARD | IoCtlCmd::BLKSECDISCARD | IoCtlCmd::BLKZEROOUT => {
+            // they destroy the data of the range as a write
+            if !f.Flags().Write {
+                return Err(Error::SysError(SysErr::EBADF));
+            }
+
+            // the start and the length of the range in bytes
+            let range: [u64; 2] = task.CopyInObj(val)?;
+            ioctlHost(fd, request, &range as *const [u64; 2] as u64)?;
+        }
+        IoCtlCmd::BLKFLSBUF => {
+            // the buffers of the device are shared by all its users
+            if !task.Creds().HasCapability(Capability::CAP_SYS_ADMIN) {
+                return Err(Error::SysError(SysErr::EACCES));
+            }
+
+            ioctlHost(fd, request, 0)?;
+        }
+        _ => return Err(Error::SysError(SysErr::ENOTTY)),
+    }
+
+    return Ok(());
+}
+
+// BlockDeviceSize returns the bytes of the host block device.
+pub fn BlockDeviceSize(fd: i32) -> Result<i64> {
+    let mut size: u64 = 0;
+    ioctlHost(fd, IoCtlCmd::BLKGETSIZE64, &mut size as *mut u64 as u64)?;
+    return Ok(size as i64);
+}
+
+fn ioctlHost(fd: i32, request: u64, argp: u64) -> Result<()> {
+    let ret = Ioctl(fd, request, argp);
+
+    if ret < 0 {
+        return Err(Error::SysError(-ret));
+    }
+
+    return Ok(());
+}
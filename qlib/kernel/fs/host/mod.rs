@@ -17,9 +17,11 @@ pub mod fs;
 pub mod hostfileop;
 pub mod hostinodeop;
 pub mod ioctl;
+pub mod shared_mappable;
 pub mod socket_iovec;
 pub mod tty;
 pub mod util;
+pub mod writeback;
 pub mod diriops;
 pub mod hostdirfops;
 pub mod fifoiops;
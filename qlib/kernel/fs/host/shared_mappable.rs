@@ -0,0 +1,120 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::sync::Arc;
+use alloc::sync::Weak;
+
+use super::super::super::super::linux_def::*;
+use super::hostinodeop::*;
+
+lazy_static! {
+    pub static ref SHARED_MAPPABLES: SharedMappables = SharedMappables::default();
+}
+
+// SharedMappableKey identifies one version of a host file. The mtime and size
+// are part of the key so that a file replaced or modified on the host won't
+// share the pages of the old content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SharedMappableKey {
+    pub dev: u64,
+    pub ino: u64,
+    pub size: i64,
+    pub mtime: i64,
+    pub mtimeNsec: i64,
+}
+
+impl SharedMappableKey {
+    pub fn New(fstat: &LibcStat) -> Self {
+        return Self {
+            dev: fstat.st_dev,
+            ino: fstat.st_ino,
+            size: fstat.st_size,
+            mtime: fstat.st_mtime,
+            mtimeNsec: fstat.st_mtime_nsec,
+        };
+    }
+}
+
+// SharedMappables is the sandbox wide cache of the file mappables of read only
+// host files. Processes loading the same binary or shared library through
+// different inodes (e.g. different containers of the pod, or after the dirent
+// cache eviction) then map the same physical pages for the .text segments
+// instead of private copies of the host file chunks.
+#[derive(Default)]
+pub struct SharedMappables {
+    pub map: QMutex<SharedMappablesInternal>,
+}
+
+#[derive(Default)]
+pub struct SharedMappablesInternal {
+    pub map: BTreeMap<SharedMappableKey, Weak<QMutex<MappableInternal>>>,
+
+    // the map is swept of the stale entries when it grows to sweepAt
+    pub sweepAt: usize,
+}
+
+// the map size from which the stale entries are swept
+const SHARED_MAPPABLES_SWEEP_MIN: usize = 1024;
+
+impl SharedMappables {
+    // GetOrCreate returns the live mappable for the file version or registers a new one.
+    pub fn GetOrCreate(&self, key: &SharedMappableKey) -> Mappable {
+        let mut intern = self.map.lock();
+        match intern.map.get(key).and_then(|w| w.upgrade()) {
+            Some(m) => return Mappable(m),
+            None => (),
+        }
+
+        // the entries of the mappables which outlive their inodes, e.g. in
+        // the mappings of a process, are left when they are dropped. They are
+        // swept when the map doubles so that the sweep is amortized.
+        if intern.map.len() >= intern.sweepAt {
+            intern.map.retain(|_, w| w.strong_count() > 0);
+            intern.sweepAt = core::cmp::max(SHARED_MAPPABLES_SWEEP_MIN, intern.map.len() * 2);
+        }
+
+        let mappable = Mappable::default();
+        intern.map.insert(*key, Arc::downgrade(&mappable.0));
+        return mappable;
+    }
+
+    // Release returns whether the inode dropping the mappable is its last
+    // user and then removes it from the map. The check and the removal are
+    // under the map lock so that GetOrCreate can't hand the mappable out
+    // while the last user clears it.
+    pub fn Release(&self, key: &SharedMappableKey, mappable: &Mappable) -> bool {
+        let mut intern = self.map.lock();
+        if Arc::strong_count(&mappable.0) != 1 {
+            return false;
+        }
+
+        // the entry of the key might be a newer mappable of the file version
+        let current = match intern.map.get(key) {
+            None => false,
+            Some(w) => w.as_ptr() == Arc::as_ptr(&mappable.0),
+        };
+
+        if current {
+            intern.map.remove(key);
+        }
+
+        return true;
+    }
+
+    pub fn Count(&self) -> usize {
+        return self.map.lock().map.len();
+    }
+}
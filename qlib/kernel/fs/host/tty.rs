@@ -25,7 +25,7 @@ use super::super::super::kernel::waiter::*;
 use super::super::super::quring::QUring;
 use super::super::super::task::*;
 use super::super::super::threadmgr::processgroup::*;
-use super::super::super::threadmgr::session::*;
+use super::super::super::threadmgr::tty::*;
 use super::super::super::SHARESPACE;
 
 use super::super::attr::*;
@@ -372,52 +372,73 @@ impl KernelTermios {
 
     pub fn IsEOF(&self, c: u8) -> bool {
         return c == self.ControlCharacters[Self::VEOF as usize]
-            && self.ControlCharacters[Self::VEOF as usize] == DISABLED_CHAR;
+            && self.ControlCharacters[Self::VEOF as usize] != DISABLED_CHAR;
     }
-}
 
-pub struct TTYFileOpsInternal {
-    pub fileOps: Arc<HostFileOp>,
-    pub termios: KernelTermios,
-    pub session: Option<Session>,
-    pub fgProcessgroup: Option<ProcessGroup>,
-    pub fd: i32,
-    pub buf: Arc<SocketBuff>,
-    pub queue: Queue,
-}
+    // IsSpecial returns whether c is the enabled control character of index.
+    pub fn IsSpecial(&self, c: u8, index: u8) -> bool {
+        let cc = self.ControlCharacters[index as usize];
+        return cc != DISABLED_CHAR && c == cc;
+    }
 
-impl TTYFileOpsInternal {
-    fn checkChange(&self, _task: &Task, _sig: Signal) -> Result<()> {
-        return Ok(());
-        /*let thread = match &task.thread {
-            // No task? Linux does not have an analog for this case, but
-            // tty_check_change is more of a blacklist of cases than a
-            // whitelist, and is surprisingly permissive. Allowing the
-            // change seems most appropriate.
-            None => return Ok(()),
-            Some(ref t) => t.clone(),
-        };
+    // IsCtl returns whether c is echoed as ^X with ECHOCTL. Compare
+    // drivers/tty/n_tty.c:echo_char.
+    pub fn IsCtl(&self, c: u8) -> bool {
+        if c == 0x7f {
+            return true;
+        }
 
-        let tg = thread.lock().tg.clone();
-        let pg = tg.ProcessGroup();
+        return c < 0x20
+            && c != '\t' as u8
+            && c != '\n' as u8
+            && !self.IsSpecial(c, Self::VSTART)
+            && !self.IsSpecial(c, Self::VSTOP);
+    }
 
-        // If the session for the task is different than the session for the
-        // controlling TTY, then the change is allowed. Seems like a bad idea,
-        // but that's exactly what linux does.
-        if tg.Session() != self.fgProcessgroup.Session() {
-            return Ok(())
+    // MapInput maps an input character by ISTRIP, IGNCR, ICRNL, INLCR and
+    // IUCLC, it returns None if the character is ignored.
+    pub fn MapInput(&self, c: u8) -> Option<u8> {
+        let mut c = c;
+        if self.IEnabled(InputFlags::ISTRIP) {
+            c &= 0x7f;
         }
 
-        // If we are the foreground process group, then the change is allowed.
-        if pg == self.fgProcessgroup {
-            return Ok(())
+        if c == '\r' as u8 {
+            if self.IEnabled(InputFlags::IGNCR) {
+                return None;
+            }
+
+            if self.IEnabled(InputFlags::ICRNL) {
+                c = '\n' as u8;
+            }
+        } else if c == '\n' as u8 && self.IEnabled(InputFlags::INLCR) {
+            c = '\r' as u8;
         }
 
-        // We are not the foreground process group.
+        if self.IEnabled(InputFlags::IUCLC) && self.LEnabled(LocalFlags::IEXTEN) {
+            c = c.to_ascii_lowercase();
+        }
 
-        // Is the provided signal blocked or ignored?
-        if (thread.SignalMask() & SignalSet::SignalSetOf(&sig).0 != 0 || tg.SignalHandlers())*/
+        return Some(c);
     }
+
+    // ReadParams returns VMIN and VTIME of the noncanonical reads.
+    pub fn ReadParams(&self) -> (usize, usize) {
+        return (
+            self.ControlCharacters[Self::VMIN as usize] as usize,
+            self.ControlCharacters[Self::VTIME as usize] as usize,
+        );
+    }
+}
+
+pub struct TTYFileOpsInternal {
+    pub fileOps: Arc<HostFileOp>,
+    pub termios: KernelTermios,
+    // tty is the job control state of the terminal
+    pub tty: TTY,
+    pub fd: i32,
+    pub buf: Arc<SocketBuff>,
+    pub queue: Queue,
 }
 
 #[derive(Clone)]
@@ -440,8 +461,7 @@ impl TTYFileOps {
         let internal = TTYFileOpsInternal {
             fileOps: fops,
             termios: DEFAULT_SLAVE_TERMIOS,
-            session: None,
-            fgProcessgroup: None,
+            tty: TTY::default(),
             fd: fd,
             buf: Arc::new(SocketBuff::Init(MemoryDef::DEFAULT_BUF_PAGE_COUNT)),
             queue: queue,
@@ -461,17 +481,18 @@ impl TTYFileOps {
     }
 
     pub fn InitForegroundProcessGroup(&self, pg: &ProcessGroup) {
-        let mut t = self.lock();
-        if t.fgProcessgroup.is_some() {
-            panic!("foreground process group is already set");
-        }
-
-        t.fgProcessgroup = Some(pg.clone());
-        t.session = Some(pg.Session());
+        let tty = self.lock().tty.clone();
+        tty.Init(pg);
     }
 
     pub fn ForegroundProcessGroup(&self) -> Option<ProcessGroup> {
-        return self.lock().fgProcessgroup.clone();
+        let tty = self.lock().tty.clone();
+        return tty.ForegroundProcessGroup();
+    }
+
+    pub fn CheckChange(&self, task: &Task, sig: Signal) -> Result<()> {
+        let tty = self.lock().tty.clone();
+        return tty.CheckChange(task, sig);
     }
 }
 
@@ -545,7 +566,7 @@ impl FileOperations for TTYFileOps {
         offset: i64,
         blocking: bool,
     ) -> Result<i64> {
-        self.lock().checkChange(task, Signal(Signal::SIGTTIN))?;
+        self.CheckChange(task, Signal(Signal::SIGTTIN))?;
 
         if SHARESPACE.config.read().UringIO && ENABLE_RINGBUF {
             let fd = self.lock().fd;
@@ -569,11 +590,9 @@ impl FileOperations for TTYFileOps {
         offset: i64,
         blocking: bool,
     ) -> Result<i64> {
-        {
-            let t = self.lock();
-            if t.termios.LEnabled(LocalFlags::TOSTOP) {
-                t.checkChange(task, Signal(Signal::SIGTTOU))?;
-            }
+        let tostop = self.lock().termios.LEnabled(LocalFlags::TOSTOP);
+        if tostop {
+            self.CheckChange(task, Signal(Signal::SIGTTOU))?;
         }
 
         let size = IoVec::NumBytes(srcs);
@@ -602,11 +621,9 @@ impl FileOperations for TTYFileOps {
     }
 
     fn Append(&self, task: &Task, f: &File, srcs: &[IoVec]) -> Result<(i64, i64)> {
-        {
-            let t = self.lock();
-            if t.termios.LEnabled(LocalFlags::TOSTOP) {
-                t.checkChange(task, Signal(Signal::SIGTTOU))?;
-            }
+        let tostop = self.lock().termios.LEnabled(LocalFlags::TOSTOP);
+        if tostop {
+            self.CheckChange(task, Signal(Signal::SIGTTOU))?;
         }
 
         let fops = self.lock().fileOps.clone();
@@ -632,7 +649,7 @@ impl FileOperations for TTYFileOps {
         return res;
     }
 
-    fn Ioctl(&self, task: &Task, _f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
+    fn Ioctl(&self, task: &Task, f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
         let fops = self.lock().fileOps.clone();
         let fd = fops
             .as_any()
@@ -654,68 +671,20 @@ impl FileOperations for TTYFileOps {
             }
 
             IoCtlCmd::TCSETS | IoCtlCmd::TCSETSW | IoCtlCmd::TCSETSF => {
-                self.lock().checkChange(task, Signal(Signal::SIGTTOU))?;
+                self.CheckChange(task, Signal(Signal::SIGTTOU))?;
 
                 let t: Termios = task.CopyInObj(val)?;
                 ioctlSetTermios(fd, ioctl, &t)?;
                 self.lock().termios.FromTermios(&t);
                 return Ok(());
             }
-            IoCtlCmd::TIOCGPGRP => {
-                let thread = task.Thread();
-                let tg = thread.ThreadGroup();
-                let pidns = tg.PIDNamespace();
-
-                let internal = self.lock();
-
-                let pgid = pidns.IDOfProcessGroup(internal.fgProcessgroup.as_ref().unwrap());
-                info!("TIOCGPGRP pgid is {}, val is {:x}", pgid, val);
-
-                task.CopyOutObj(&pgid, val)?;
-
-                return Ok(());
-            }
-            IoCtlCmd::TIOCSPGRP => {
-                let thread = match &task.thread {
-                    None => return Err(Error::SysError(SysErr::ENOTTY)),
-                    Some(ref t) => t.clone(),
-                };
-
-                let mut t = self.lock();
-                match t.checkChange(task, Signal(Signal::SIGTTOU)) {
-                    // drivers/tty/tty_io.c:tiocspgrp() converts -EIO from
-                    // tty_check_change() to -ENOTTY.
-                    Err(Error::SysError(SysErr::EIO)) => {
-                        return Err(Error::SysError(SysErr::ENOTTY))
-                    }
-                    Err(e) => return Err(e),
-                    Ok(()) => (),
-                }
-
-                let tg = thread.ThreadGroup();
-                let session = tg.Session();
-                if session != t.session {
-                    return Err(Error::SysError(SysErr::ENOTTY));
-                }
-
-                let pgid: i32 = task.CopyInObj(val)?;
-                if pgid < 0 {
-                    return Err(Error::SysError(SysErr::EINVAL));
-                }
-
-                let pidns = tg.PIDNamespace();
-                let pg = match pidns.ProcessGroupWithID(pgid) {
-                    None => return Err(Error::SysError(SysErr::ESRCH)),
-                    Some(pg) => pg,
-                };
-
-                // Check that new process group is in the TTY session.
-                if pg.Session() != t.session.clone().unwrap() {
-                    return Err(Error::SysError(SysErr::EPERM));
-                }
-
-                t.fgProcessgroup = Some(pg);
-                return Ok(());
+            IoCtlCmd::TIOCGPGRP
+            | IoCtlCmd::TIOCSPGRP
+            | IoCtlCmd::TIOCSCTTY
+            | IoCtlCmd::TIOCNOTTY
+            | IoCtlCmd::TIOCGSID => {
+                let tty = self.lock().tty.clone();
+                return tty.Ioctl(task, f, request, val);
             }
             IoCtlCmd::TIOCGWINSZ => {
                 let mut win = Winsize::default();
@@ -738,9 +707,6 @@ impl FileOperations for TTYFileOps {
             | IoCtlCmd::TIOCEXCL
             | IoCtlCmd::TIOCNXCL
             | IoCtlCmd::TIOCGEXCL
-            | IoCtlCmd::TIOCNOTTY
-            | IoCtlCmd::TIOCSCTTY
-            | IoCtlCmd::TIOCGSID
             | IoCtlCmd::TIOCGETD
             | IoCtlCmd::TIOCVHANGUP
             | IoCtlCmd::TIOCGDEV
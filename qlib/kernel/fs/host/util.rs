@@ -31,6 +31,7 @@ use super::super::super::task::*;
 
 use super::super::super::super::path;
 use super::super::super::Kernel::HostSpace;
+use super::super::acl::*;
 use super::super::attr::*;
 use super::super::super::SHARESPACE;
 use super::super::super::IOURING;
@@ -549,6 +550,23 @@ pub fn Getxattr(fd: i32, name: &str) -> Result<Vec<u8>> {
     return Ok(val[0..ret as usize].to_vec())
 }
 
+// AccessAcl returns the access acl of the host file when it is more than the mode bits.
+pub fn AccessAcl(fd: i32) -> Option<PosixAcl> {
+    if !SHARESPACE.config.read().PosixAcl {
+        return None;
+    }
+
+    let buf = match Getxattr(fd, XATTR_NAME_POSIX_ACL_ACCESS) {
+        Err(_) => return None,
+        Ok(buf) => buf,
+    };
+
+    match PosixAcl::Parse(&buf) {
+        Ok(acl) if !acl.IsMinimal() => return Some(acl),
+        _ => return None,
+    }
+}
+
 pub fn Setxattr(fd: i32, name: &str, value: &[u8], flags: u32) -> Result<()> {
     let name = CString::New(name);
     let addr = if value.len() == 0 {
@@ -616,8 +634,10 @@ pub fn StatFS(fd: i32) -> Result<FsInfo> {
     fsInfo.Type = statfs.Type;
     fsInfo.TotalBlocks = statfs.Blocks;
     fsInfo.FreeBlocks = statfs.BlocksFree;
+    fsInfo.AvailableBlocks = statfs.BlocksAvailable;
     fsInfo.TotalFiles = statfs.Files;
     fsInfo.FreeFiles = statfs.FilesFree;
+    fsInfo.BlockSize = statfs.BlockSize;
 
     return Ok(fsInfo);
 }
\ No newline at end of file
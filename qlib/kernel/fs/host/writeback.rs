@@ -0,0 +1,226 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::memmgr::mem_high::*;
+use super::super::super::quring::uring_async::*;
+use super::super::super::Kernel::HostSpace;
+use super::super::super::IOURING;
+use super::super::super::SHARESPACE;
+
+// Writeback of the host files. The data written by the guest sits dirty in the
+// page cache of the host until the host flushes it at its own pace, or all of
+// it at once when the application calls fsync(2) or closes a big file, so a log
+// heavy application sees long write stalls. The guest counts the bytes written
+// to every host file and, like vm.dirty_background_ratio and vm.dirty_ratio:
+//   over the background threshold, or WRITEBACK_CHUNK dirty bytes of a file,
+//   the writeback of the dirty files is started with async sync_file_range(2)
+//   over the dirty threshold the writer writes back its file and waits for it
+// The ratios are percent of the sandbox memory, i.e. MemoryHigh if it is set
+// or KernelMemSize. Both of them 0 disables the writeback.
+//
+// The errors of the writes and the writeback done in the background are kept
+// per file and returned by the next fsync(2), as the errseq of Linux does. With
+// StrictDurability the error sticks and every later fsync fails too, so a
+// database never takes a lost write as durable. A host fd is reused once its
+// file is closed, so the state of a file is of the generation of its fd and
+// the completions of a closed file, which carry an older one, are dropped.
+
+// a file is written back when it has this many dirty bytes
+pub const WRITEBACK_CHUNK: u64 = 16 * MemoryDef::ONE_MB;
+
+lazy_static! {
+    pub static ref WRITEBACK: Writeback = Writeback::default();
+}
+
+#[derive(Default)]
+pub struct Writeback {
+    // host fd -> dirty bytes
+    pub files: QMutex<BTreeMap<i32, u64>>,
+    pub dirty: AtomicU64,
+    pub writeback: AtomicU64,
+    // host fd -> the errno of the failed background write
+    pub errors: QMutex<BTreeMap<i32, i32>>,
+    // host fd -> the generation of the file of the fd
+    pub gens: QMutex<BTreeMap<i32, u64>>,
+    pub nextGen: AtomicU64,
+
+    pub started: AtomicU64,
+    pub throttled: AtomicU64,
+}
+
+// DirtyThresholds returns the background and the dirty thresholds in bytes.
+pub fn DirtyThresholds() -> (u64, u64) {
+    let mem = SandboxMemory();
+    let config = SHARESPACE.config.read();
+    let background = mem / 100 * config.DirtyBackgroundRatio;
+    let dirty = mem / 100 * config.DirtyRatio;
+    return (background, dirty);
+}
+
+impl Writeback {
+    // Dirty accounts len bytes written to the host file fd.
+    pub fn Dirty(&self, fd: i32, len: u64) {
+        let (background, limit) = DirtyThresholds();
+        if (background == 0 && limit == 0) || len == 0 {
+            return;
+        }
+
+        // the counters are updated under the lock so Clean never sees the
+        // bytes of a file before they are added to the total
+        let (fileDirty, dirty) = {
+            let mut files = self.files.lock();
+            let fileDirty = files.entry(fd).or_insert(0);
+            *fileDirty += len;
+            (*fileDirty, self.dirty.fetch_add(len, Ordering::SeqCst) + len)
+        };
+
+        let total = dirty + self.writeback.load(Ordering::SeqCst);
+
+        if limit > 0 && total >= limit {
+            self.Throttle(fd);
+            return;
+        }
+
+        if background > 0 && dirty >= background {
+            self.StartAll();
+        } else if fileDirty >= WRITEBACK_CHUNK {
+            self.Start(fd);
+        }
+    }
+
+    // Start starts the writeback of the file.
+    pub fn Start(&self, fd: i32) {
+        let bytes = {
+            let mut files = self.files.lock();
+            let bytes = match files.remove(&fd) {
+                None => return,
+                Some(b) => b,
+            };
+
+            self.writeback.fetch_add(bytes, Ordering::SeqCst);
+            self.dirty.fetch_sub(bytes, Ordering::SeqCst);
+            bytes
+        };
+
+        self.started.fetch_add(1, Ordering::Relaxed);
+        let op = AsyncSyncFileRange::New(fd, self.Gen(fd), bytes);
+        IOURING.AUCall(AsyncOps::AsyncSyncFileRange(op));
+    }
+
+    // StartAll starts the writeback of all the dirty files.
+    pub fn StartAll(&self) {
+        let fds: Vec<i32> = self.files.lock().keys().cloned().collect();
+        for fd in fds {
+            self.Start(fd);
+        }
+    }
+
+    // Done is called when the writeback of bytes completes.
+    pub fn Done(&self, bytes: u64) {
+        self.writeback.fetch_sub(bytes, Ordering::SeqCst);
+    }
+
+    // Throttle makes the writer write back its file synchronously so the dirty
+    // data doesn't grow faster than the disk takes it.
+    pub fn Throttle(&self, fd: i32) {
+        let cnt = self.throttled.fetch_add(1, Ordering::Relaxed) + 1;
+        if cnt % 1000 == 1 {
+            info!(
+                "writeback: dirty {} writeback {} over the dirty threshold, throttled {} times",
+                self.dirty.load(Ordering::Relaxed),
+                self.writeback.load(Ordering::Relaxed),
+                cnt
+            );
+        }
+
+        // the other files keep going in the background
+        self.Clean(fd);
+        self.StartAll();
+
+        let ret = HostSpace::SyncFileRange(
+            fd,
+            0,
+            0,
+            SyncFileRangeFlags::SYNC_FILE_RANGE_WAIT_BEFORE
+                | SyncFileRangeFlags::SYNC_FILE_RANGE_WRITE
+                | SyncFileRangeFlags::SYNC_FILE_RANGE_WAIT_AFTER,
+        );
+        if ret < 0 {
+            info!("writeback: sync fd {} fail {}", fd, ret);
+        }
+    }
+
+    // Clean drops the dirty bytes of the file, it is called when the file is
+    // synced.
+    pub fn Clean(&self, fd: i32) {
+        let mut files = self.files.lock();
+        if let Some(bytes) = files.remove(&fd) {
+            self.dirty.fetch_sub(bytes, Ordering::SeqCst);
+        }
+    }
+
+    // Close forgets the file.
+    pub fn Close(&self, fd: i32) {
+        self.Clean(fd);
+        let mut gens = self.gens.lock();
+        gens.remove(&fd);
+        self.errors.lock().remove(&fd);
+    }
+
+    // Gen returns the generation of the file of the fd, the async ops of the
+    // file carry it to their completion.
+    pub fn Gen(&self, fd: i32) -> u64 {
+        let mut gens = self.gens.lock();
+        return *gens
+            .entry(fd)
+            .or_insert_with(|| self.nextGen.fetch_add(1, Ordering::Relaxed) + 1);
+    }
+
+    // SetError keeps the error of a background write of the file of the
+    // generation, the one of a closed file is dropped.
+    pub fn SetError(&self, fd: i32, gen: u64, errno: i32) {
+        let gens = self.gens.lock();
+        if gens.get(&fd) != Some(&gen) {
+            info!("writeback: drop the error {} of the closed fd {}", errno, fd);
+            return;
+        }
+
+        // keep the first error
+        self.errors.lock().entry(fd).or_insert(errno);
+    }
+
+    // CheckError returns the error of the background writes of the file since
+    // the last check.
+    pub fn CheckError(&self, fd: i32) -> Result<()> {
+        let mut errors = self.errors.lock();
+        let errno = if SHARESPACE.config.read().StrictDurability {
+            errors.get(&fd).cloned()
+        } else {
+            errors.remove(&fd)
+        };
+
+        match errno {
+            None => return Ok(()),
+            Some(errno) => return Err(Error::SysError(errno)),
+        }
+    }
+}
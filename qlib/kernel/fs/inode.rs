@@ -32,6 +32,7 @@ use super::super::socket::unix::transport::unix::*;
 use super::super::task::*;
 use super::super::uid::*;
 
+use super::acl::*;
 use super::attr::*;
 use super::dentry::*;
 use super::dirent::*;
@@ -46,19 +47,41 @@ use super::mount::*;
 use super::overlay::*;
 
 pub fn ContextCanAccessFile(task: &Task, inode: &Inode, reqPerms: &PermMask) -> Result<bool> {
+    return ContextCanAccessFileWithAcl(task, inode, reqPerms, None);
+}
+
+// ContextCanAccessFileWithAcl is ContextCanAccessFile with the file's access acl, the
+// acl replaces the mode bits check when it is present.
+pub fn ContextCanAccessFileWithAcl(
+    task: &Task,
+    inode: &Inode,
+    reqPerms: &PermMask,
+    acl: Option<&PosixAcl>,
+) -> Result<bool> {
     let creds = task.creds.clone();
     let uattr = inode.UnstableAttr(task)?;
 
     //info!("ContextCanAccessFile 1, perms is {:?}", &uattr.Perms);
-    let mut p = &uattr.Perms.Other;
-    {
+    let granted = {
         let creds = creds.lock();
-        if uattr.Owner.UID == creds.EffectiveKUID {
-            p = &uattr.Perms.User
-        } else if creds.InGroup(uattr.Owner.GID) {
-            p = &uattr.Perms.Group
+        match acl {
+            Some(acl) => acl.Permission(
+                &uattr.Owner,
+                creds.EffectiveKUID,
+                &|kgid| creds.InGroup(kgid),
+                reqPerms,
+            ),
+            None => {
+                let mut p = &uattr.Perms.Other;
+                if uattr.Owner.UID == creds.EffectiveKUID {
+                    p = &uattr.Perms.User
+                } else if creds.InGroup(uattr.Owner.GID) {
+                    p = &uattr.Perms.Group
+                }
+                p.SupersetOf(reqPerms)
+            }
         }
-    }
+    };
 
     //info!("ContextCanAccessFile 2");
     if inode.StableAttr().IsFile()
@@ -68,8 +91,8 @@ pub fn ContextCanAccessFile(task: &Task, inode: &Inode, reqPerms: &PermMask) ->
         return Ok(false);
     }
 
-    //info!("ContextCanAccessFile 3, p is {:?}, reqPerms is {:?}", &p, reqPerms);
-    if p.SupersetOf(reqPerms) {
+    //info!("ContextCanAccessFile 3, reqPerms is {:?}", reqPerms);
+    if granted {
         return Ok(true);
     }
 
@@ -89,6 +112,42 @@ pub fn ContextCanAccessFile(task: &Task, inode: &Inode, reqPerms: &PermMask) ->
     return Ok(false);
 }
 
+// NewChildOwner returns the owner and permissions of a new inode created in dir.
+// Same as inode_init_owner in Linux: when dir has the setgid bit set, the new inode
+// inherits the group of dir and a new directory inherits the setgid bit as well.
+pub fn NewChildOwner(
+    task: &Task,
+    dir: &Inode,
+    perms: &FilePermissions,
+    isDir: bool,
+) -> (FileOwner, FilePermissions) {
+    let mut owner = task.FileOwner();
+    let mut perms = *perms;
+
+    let dirAttr = match dir.UnstableAttr(task) {
+        Err(_) => return (owner, perms),
+        Ok(a) => a,
+    };
+
+    if dirAttr.Perms.SetGid {
+        owner.GID = dirAttr.Owner.GID;
+        if isDir {
+            perms.SetGid = true;
+        }
+    }
+
+    // a regular file can't be setgid for a group the creator is not a member of
+    if !isDir && perms.SetGid && perms.Group.execute {
+        let creds = task.creds.clone();
+        let inGroup = creds.lock().InGroup(owner.GID);
+        if !inGroup && !creds.HasCapability(Capability::CAP_FSETID) {
+            perms.SetGid = false;
+        }
+    }
+
+    return (owner, perms);
+}
+
 #[derive(Debug)]
 pub enum IopsType {
     MockInodeOperations,
@@ -270,6 +329,22 @@ impl Inode {
         return Self(Arc::new(QMutex::new(inodeInternal)));
     }
 
+    // WithMountSource returns an inode of the same file in the mount source
+    // msrc, e.g. the root of a bind mount with options of its own.
+    pub fn WithMountSource(&self, msrc: &Arc<QMutex<MountSource>>) -> Self {
+        let me = self.lock();
+        let inodeInternal = InodeIntern {
+            UniqueId: NewUID(),
+            InodeOp: me.InodeOp.clone(),
+            StableAttr: me.StableAttr.clone(),
+            LockCtx: LockCtx::default(),
+            MountSource: msrc.clone(),
+            Overlay: me.Overlay.clone(),
+        };
+
+        return Self(Arc::new(QMutex::new(inodeInternal)));
+    }
+
     pub fn WouldBlock(&self) -> bool {
         return self.lock().InodeOp.WouldBlock();
     }
@@ -338,6 +413,13 @@ impl Inode {
                     writeable,
                 );
 
+                if inodeType == InodeType::RegularFile {
+                    let usage = msrc.lock().Usage.clone();
+                    if let Some(usage) = usage {
+                        iops.SetUsage(&usage);
+                    }
+                }
+
                 return Ok(Self(Arc::new(QMutex::new(InodeIntern {
                     UniqueId: NewUID(),
                     InodeOp: Arc::new(iops),
@@ -871,7 +953,13 @@ impl Inode {
         }
 
         let inodeOp = self.lock().InodeOp.clone();
-        return inodeOp.StatFS(task);
+        let info = inodeOp.StatFS(task)?;
+
+        let usage = self.lock().MountSource.lock().Usage.clone();
+        match usage {
+            None => return Ok(info),
+            Some(usage) => return Ok(usage.FsInfo(info.Type)),
+        }
     }
 }
 
@@ -16,11 +16,14 @@ pub mod dirent;
 pub mod file;
 pub mod inode;
 //pub mod inodeOperations;
+pub mod acl;
 pub mod anon;
 pub mod attr;
 pub mod copy_up;
 pub mod dentry;
 pub mod dev;
+pub mod etc_files;
+pub mod ephemeral;
 pub mod file_overlay;
 pub mod filesystems;
 pub mod flags;
@@ -39,10 +42,21 @@ pub mod tmpfs;
 pub mod tty;
 pub mod inotify;
 
+// the filesystem types registered at boot. The mounts find their type in the
+// registry, an optional filesystem only adds its Init here, behind its
+// feature, e.g.
+//   #[cfg(feature = "erofs")]
+//   self::erofs::Init,
+const FILESYSTEM_MODULES: &[fn()] = &[
+    self::tty::Init,
+    self::dev::Init,
+    self::procfs::Init,
+    self::sys::Init,
+    self::tmpfs::Init,
+];
+
 pub fn Init() {
-    self::tty::Init();
-    self::dev::Init();
-    self::procfs::Init();
-    self::sys::Init();
-    self::tmpfs::Init();
+    for init in FILESYSTEM_MODULES {
+        init();
+    }
 }
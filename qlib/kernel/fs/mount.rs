@@ -30,6 +30,8 @@ use super::super::super::common::*;
 use super::super::super::linux_def::*;
 use super::super::super::lrc_cache::*;
 use super::super::super::path::*;
+use super::super::super::rcu::*;
+use super::super::kernel::fs_context::*;
 use super::super::task::*;
 use super::dirent::*;
 use super::filesystems::*;
@@ -109,8 +111,11 @@ impl Mount {
 
 pub struct MountNsInternal {
     pub userns: UserNameSpace,
-    pub root: Dirent,
-    pub mounts: QMutex<BTreeMap<u64, Arc<QMutex<Mount>>>>,
+    // the root is replaced by pivot_root(2). The root and the mounts are
+    // read by the path lookups and changed by the mount syscalls only, the
+    // lookups read them without a lock.
+    pub root: QRcu<Dirent>,
+    pub mounts: QRcu<BTreeMap<u64, Arc<QMutex<Mount>>>>,
     pub mountId: AtomicU64,
 }
 
@@ -118,8 +123,8 @@ impl Default for MountNsInternal {
     fn default() -> Self {
         return Self {
             userns: UserNameSpace::default(),
-            root: Dirent::default(),
-            mounts: QMutex::new(BTreeMap::new()),
+            root: QRcu::New(Dirent::default()),
+            mounts: QRcu::New(BTreeMap::new()),
             mountId: AtomicU64::new(0),
         };
     }
@@ -144,8 +149,8 @@ impl MountNs {
         mounts.insert(d.ID(), rootMount);
         let internal = MountNsInternal {
             userns: task.creds.lock().UserNamespace.clone(),
-            root: d,
-            mounts: QMutex::new(mounts),
+            root: QRcu::New(d),
+            mounts: QRcu::New(mounts),
             mountId: AtomicU64::new(2),
         };
 
@@ -157,7 +162,11 @@ impl MountNs {
     }
 
     pub fn Root(&self) -> Dirent {
-        return self.root.clone();
+        return self.root.Read().as_ref().clone();
+    }
+
+    pub fn SetRoot(&self, root: &Dirent) {
+        self.root.Write(root.clone());
     }
 
     pub fn Mount(&self, mountPoint: &Dirent, inode: &Inode) -> Result<()> {
@@ -173,28 +182,160 @@ impl MountNs {
         mountPoint.clone().DropExtendedReference();
 
         let mntId = mountPoint.ID();
-        let mut mounts = self.mounts.lock();
-        let prev = mounts.get(&mntId);
+        self.mounts.Update(|mounts| {
+            let prev = mounts.get(&mntId);
 
-        let havePre = match prev {
-            Some(_) => true,
-            _ => false,
-        };
+            let havePre = match prev {
+                Some(_) => true,
+                _ => false,
+            };
 
-        if havePre {
-            childMnt.prev = Some(prev.unwrap().clone());
-            mounts.remove(&mntId);
+            if havePre {
+                childMnt.prev = Some(prev.unwrap().clone());
+                mounts.remove(&mntId);
+                mounts.insert(replacement.ID(), Arc::new(QMutex::new(childMnt)));
+                return;
+            }
+
+            childMnt.prev = Some(Arc::new(QMutex::new(Mount::NewUndoMount(mountPoint))));
             mounts.insert(replacement.ID(), Arc::new(QMutex::new(childMnt)));
-            return Ok(());
-        }
+        });
 
-        childMnt.prev = Some(Arc::new(QMutex::new(Mount::NewUndoMount(mountPoint))));
-        mounts.insert(replacement.ID(), Arc::new(QMutex::new(childMnt)));
         return Ok(());
     }
 
+    // Remount replaces the root of the mount at node with inode, e.g. the root
+    // of a bind mount with a mount source of its own. The mount is detached
+    // and mounted again at its mount point.
+    pub fn Remount(&self, node: &Dirent, inode: &Inode) -> Result<()> {
+        let mountPoint = {
+            let mounts = self.mounts.Read();
+            let m = match mounts.get(&node.ID()) {
+                None => return Err(Error::SysError(SysErr::EINVAL)),
+                Some(m) => m.clone(),
+            };
+            let prev = match &m.lock().prev {
+                // the root mount has no mount point
+                None => return Err(Error::SysError(SysErr::EINVAL)),
+                Some(prev) => prev.clone(),
+            };
+            let root = prev.lock().root.clone();
+            root
+        };
+
+        self.Unmount(node, true)?;
+        return self.Mount(&mountPoint, inode);
+    }
+
+    // Top returns the topmost dirent of the dirent tree of the namespace, the
+    // root of the namespace is under it after pivot_root(2).
+    fn Top(&self) -> Dirent {
+        let mut top = self.Root();
+        loop {
+            match top.Parent() {
+                None => return top,
+                Some(p) => top = p,
+            }
+        }
+    }
+
+    // Copy returns a copy of the namespace for clone(2) and unshare(2) with
+    // CLONE_NEWNS. The mounts change the dirent tree, so the copy has a tree
+    // of its own: a new top dirent of the same root inode with the mounts of
+    // the namespace mounted again in the order they were mounted, the stacked
+    // ones and the ones shadowed by a later mount included. The copied mounts
+    // share the mount sources, as the superblocks of Linux, the mounts and the
+    // unmounts after the copy are private to each namespace.
+    pub fn Copy(&self, task: &Task) -> Result<Self> {
+        let top = self.Top();
+
+        let mut mounts: Vec<(u64, Dirent)> = Vec::new();
+        for m in self.mounts.Read().values() {
+            let mut m = m.clone();
+            loop {
+                let prev = {
+                    let mnt = m.lock();
+                    if !mnt.IsUndo() && !mnt.IsRoot() {
+                        mounts.push((mnt.Id, mnt.root.clone()));
+                    }
+                    mnt.prev.clone()
+                };
+
+                match prev {
+                    None => break,
+                    Some(p) => m = p,
+                }
+            }
+        }
+        mounts.sort_by_key(|(id, _)| *id);
+
+        let copy = Self::New(task, &top.Inode());
+        let copyTop = copy.Root();
+        for (_, root) in &mounts {
+            let (path, reachable) = root.FullName(&top);
+            if !reachable {
+                continue;
+            }
+
+            let mut traversals = MAX_SYMLINK_TRAVERSALS;
+            let mountPoint =
+                match copy.FindDirent(task, &copyTop, None, &path, &mut traversals, false) {
+                    Err(e) => {
+                        // the mount point is gone, e.g. its directory is removed
+                        info!("mount ns copy: skip the mount at {} {:?}", path, e);
+                        continue;
+                    }
+                    Ok(d) => d,
+                };
+
+            if mountPoint.Parent().is_none() {
+                continue;
+            }
+
+            copy.Mount(&mountPoint, &root.Inode())?;
+        }
+
+        let root = copy.Translate(task, self, &self.Root());
+        copy.SetRoot(&root);
+        return Ok(copy);
+    }
+
+    // Translate returns the dirent of this copy of the namespace from at the
+    // path of the dirent d of from, the root of the copy if there is none.
+    fn Translate(&self, task: &Task, from: &MountNs, d: &Dirent) -> Dirent {
+        let top = self.Top();
+        let (path, reachable) = d.FullName(&from.Top());
+        if !reachable {
+            return self.Root();
+        }
+
+        let mut traversals = MAX_SYMLINK_TRAVERSALS;
+        match self.FindDirent(task, &top, None, &path, &mut traversals, false) {
+            Ok(d) => return d,
+            Err(_) => return self.Root(),
+        }
+    }
+
+    // MoveFSContext moves the root and the working directory of fsc from the
+    // namespace from to the same paths in this copy of it.
+    pub fn MoveFSContext(&self, task: &Task, from: &MountNs, fsc: &FSContext) {
+        let root = self.Translate(task, from, &fsc.RootDirectory());
+        let cwd = self.Translate(task, from, &fsc.WorkDirectory());
+        fsc.SetRootDirectory(&root);
+        fsc.SetWorkDirectory(&cwd);
+    }
+
     pub fn Unmount(&self, node: &Dirent, detachOnly: bool) -> Result<()> {
-        let mut mounts = self.mounts.lock();
+        return self
+            .mounts
+            .Update(|mounts| Self::UnmountLocked(mounts, node, detachOnly));
+    }
+
+    fn UnmountLocked(
+        mounts: &mut BTreeMap<u64, Arc<QMutex<Mount>>>,
+        node: &Dirent,
+        detachOnly: bool,
+    ) -> Result<()> {
         let orig = mounts.get(&node.ID());
         let orig = match orig {
             None => return Err(Error::SysError(SysErr::EINVAL)),
@@ -232,9 +373,39 @@ impl MountNs {
         return Ok(());
     }
 
+    // DetachOutside detaches the mounts out of reach from root, e.g. the ones
+    // of the old root once pivot_root(2) moved the root of the namespace.
+    pub fn DetachOutside(&self, root: &Dirent) {
+        self.mounts.Update(|mounts| loop {
+            let outside: Vec<Dirent> = mounts
+                .values()
+                .filter_map(|m| {
+                    let m = m.lock();
+                    if m.IsUndo() || m.IsRoot() || m.root.DescendantOf(root) {
+                        return None;
+                    }
+                    Some(m.root.clone())
+                })
+                .collect();
+
+            // the unmount of a stacked mount puts back the one under it, which
+            // is detached in the next round
+            let mut detached = false;
+            for d in outside {
+                if Self::UnmountLocked(mounts, &d, true).is_ok() {
+                    detached = true;
+                }
+            }
+
+            if !detached {
+                return;
+            }
+        });
+    }
+
     pub fn FindMount(&self, d: &Dirent) -> Option<Arc<QMutex<Mount>>> {
         let mut d = d.clone();
-        let mounts = self.mounts.lock();
+        let mounts = self.mounts.Read();
         loop {
             let id = d.ID();
 
@@ -256,7 +427,7 @@ impl MountNs {
     pub fn AllMountsUnder(&self, parent: &Arc<QMutex<Mount>>) -> Vec<Arc<QMutex<Mount>>> {
         let mut ret: Vec<Arc<QMutex<Mount>>> = Vec::new();
 
-        for (_, mp) in self.mounts.lock().iter() {
+        for (_, mp) in self.mounts.Read().iter() {
             if mp.lock().IsUndo() {
                 continue;
             }
@@ -574,6 +745,8 @@ pub struct MountSource {
     pub MountSourceOperations: Arc<QMutex<MountSourceOperations>>,
     pub fscache: LruCache<Dirent>,
     frozen: Vec<Dirent>,
+    // the space used by the filesystem kept in memory
+    pub Usage: Option<Arc<FsUsage>>,
 }
 
 impl Default for MountSource {
@@ -584,6 +757,7 @@ impl Default for MountSource {
             MountSourceOperations: Arc::new(QMutex::new(SimpleMountSourceOperations::default())),
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            Usage: None,
         };
     }
 }
@@ -623,6 +797,20 @@ impl MountSource {
             MountSourceOperations: mops.clone(),
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            Usage: None,
+        };
+    }
+
+    // Fork returns a mount source of the same filesystem with the options
+    // flags, for a bind mount with options of its own.
+    pub fn Fork(&self, flags: &MountSourceFlags) -> Self {
+        return Self {
+            Flags: flags.clone(),
+            FileSystemType: self.FileSystemType.clone(),
+            MountSourceOperations: self.MountSourceOperations.clone(),
+            fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
+            frozen: Vec::new(),
+            Usage: self.Usage.clone(),
         };
     }
 
@@ -638,6 +826,7 @@ impl MountSource {
             MountSourceOperations: mops.clone(),
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            Usage: None,
         };
     }
 
@@ -653,6 +842,7 @@ impl MountSource {
             MountSourceOperations: mops.clone(),
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            Usage: None,
         };
     }
 
@@ -679,6 +869,7 @@ impl MountSource {
             MountSourceOperations: mops,
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            Usage: None,
         };
     }
 
@@ -697,6 +888,7 @@ impl MountSource {
             MountSourceOperations: mops,
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            Usage: None,
         };
     }
 
@@ -715,6 +907,7 @@ impl MountSource {
             MountSourceOperations: mops,
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            Usage: None,
         };
     }
 
@@ -733,6 +926,7 @@ impl MountSource {
             MountSourceOperations: mops,
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            Usage: None,
         };
     }
 
@@ -749,6 +943,7 @@ impl MountSource {
             MountSourceOperations: mops,
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            Usage: None,
         };
     }
 
@@ -835,11 +1030,134 @@ pub struct FsInfo {
     // FreeBlocks is the number of free blocks available.
     pub FreeBlocks: u64,
 
+    // AvailableBlocks is the number of free blocks available to the
+    // unprivileged users.
+    pub AvailableBlocks: u64,
+
     // TotalFiles is the total file nodes in the filesystem.
     pub TotalFiles: u64,
 
     // FreeFiles is the number of free file nodes.
     pub FreeFiles: u64,
+
+    // BlockSize is the size of the blocks above, 0 is the block size of the inode.
+    pub BlockSize: i64,
+}
+
+// FsUsage is the space used by a filesystem which keeps its files in the
+// memory of the sandbox, e.g. tmpfs. The filesystem has no device to ask for
+// the totals, statfs(2) reports them from here. It is also the space the
+// sandbox writes to a host filesystem, see fs::ephemeral.
+#[derive(Debug, Default)]
+pub struct FsUsage {
+    // the size of the filesystem in bytes, 0 is unlimited
+    pub size: u64,
+    // the max count of the files
+    pub inodes: u64,
+    // the files stay after they are closed, only the size changes are charged
+    pub persistent: bool,
+    // the usage charged together with this one
+    pub parent: Option<Arc<FsUsage>>,
+
+    pub used: AtomicU64,
+    pub files: AtomicU64,
+}
+
+impl FsUsage {
+    pub fn New(size: u64, inodes: u64) -> Self {
+        return Self {
+            size: size,
+            inodes: inodes,
+            ..Default::default()
+        };
+    }
+
+    // Charge adds delta bytes to the used space, a negative delta frees it.
+    pub fn Charge(&self, delta: i64) {
+        if delta >= 0 {
+            self.used.fetch_add(delta as u64, Ordering::SeqCst);
+        } else if self.persistent {
+            // the files of a persistent filesystem might be in the read only
+            // layers of the image, their space was never charged
+            let _ = self
+                .used
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                    Some(used.saturating_sub((-delta) as u64))
+                });
+        } else {
+            let used = self.used.fetch_sub((-delta) as u64, Ordering::SeqCst);
+            debug_assert!(
+                used >= (-delta) as u64,
+                "FsUsage: uncharge {} over the used {}",
+                -delta,
+                used
+            );
+        }
+
+        if let Some(parent) = &self.parent {
+            parent.Charge(delta);
+        }
+    }
+
+    // Check returns ENOSPC if delta more bytes go over the size.
+    pub fn Check(&self, delta: i64) -> Result<()> {
+        if delta <= 0 {
+            return Ok(());
+        }
+
+        if self.size > 0 && self.Used() + delta as u64 > self.size {
+            return Err(Error::SysError(SysErr::ENOSPC));
+        }
+
+        match &self.parent {
+            None => return Ok(()),
+            Some(parent) => return parent.Check(delta),
+        }
+    }
+
+    // AddFile counts a file in the usage, the parent counts it as well as it
+    // does for the bytes.
+    pub fn AddFile(&self) {
+        self.files.fetch_add(1, Ordering::SeqCst);
+        if let Some(parent) = &self.parent {
+            parent.AddFile();
+        }
+    }
+
+    pub fn RemoveFile(&self) {
+        self.files.fetch_sub(1, Ordering::SeqCst);
+        if let Some(parent) = &self.parent {
+            parent.RemoveFile();
+        }
+    }
+
+    pub fn Used(&self) -> u64 {
+        return self.used.load(Ordering::SeqCst);
+    }
+
+    pub fn FsInfo(&self, fsType: u64) -> FsInfo {
+        let blockSize = MemoryDef::PAGE_SIZE;
+        let total = self.size / blockSize;
+        let used = (self.Used() + blockSize - 1) / blockSize;
+        let free = if total > used { total - used } else { 0 };
+
+        let files = self.files.load(Ordering::SeqCst);
+        let freeFiles = if self.inodes > files {
+            self.inodes - files
+        } else {
+            0
+        };
+
+        return FsInfo {
+            Type: fsType,
+            TotalBlocks: total,
+            FreeBlocks: free,
+            AvailableBlocks: free,
+            TotalFiles: self.inodes,
+            FreeFiles: freeFiles,
+            BlockSize: blockSize as i64,
+        };
+    }
 }
 
 #[cfg(test1)]
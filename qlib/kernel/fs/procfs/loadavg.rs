@@ -19,6 +19,7 @@ use alloc::vec::Vec;
 use super::super::super::super::auth::*;
 use super::super::super::super::common::*;
 use super::super::super::super::linux_def::*;
+use super::super::super::kernel::loadavg::*;
 use super::super::super::task::*;
 use super::super::attr::*;
 use super::super::dirent::*;
@@ -53,8 +54,23 @@ pub fn NewLoadAvgSimpleFileInode(
 pub struct LoadAvgData {}
 
 impl LoadAvgData {
-    pub fn GenSnapshot(&self, _task: &Task) -> Vec<u8> {
-        let ret = format!("{:.2} {:.2} {:.2} {}/{} {}\n", 0.00, 0.00, 0.00, 0, 0, 0);
+    pub fn GenSnapshot(&self, task: &Task) -> Vec<u8> {
+        let loads = LOAD_AVG.Loads();
+        let pidns = task.Thread().PIDNamespace();
+        let threads = pidns.Tasks().len();
+        let last = pidns.lock().last;
+        let ret = format!(
+            "{}.{:02} {}.{:02} {}.{:02} {}/{} {}\n",
+            LoadInt(loads[0]),
+            LoadFrac(loads[0]),
+            LoadInt(loads[1]),
+            LoadFrac(loads[1]),
+            LoadInt(loads[2]),
+            LoadFrac(loads[2]),
+            RunnableTasks(),
+            threads,
+            last
+        );
         return ret.as_bytes().to_vec();
     }
 }
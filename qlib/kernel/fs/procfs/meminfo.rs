@@ -22,6 +22,7 @@ use super::super::file::*;
 use super::super::flags::*;
 use super::super::inode::*;
 use super::super::mount::*;
+use super::super::tmpfs::shm::*;
 //use super::super::super::super::linux::time::*;
 use super::super::super::super::auth::*;
 use super::super::super::super::common::*;
@@ -78,7 +79,10 @@ impl ReadonlyFileNode for MeminfoFileNode {
         s += &format!("Writeback:             0 kB\n");
         s += &format!("AnonPages:      {:>8} kB\n", info.totalram / 100 / 1024);
         s += &format!("Mapped:         {:>8} kB\n", info.totalram / 100 / 1024);
-        s += &format!("Shmem:                 0 kB\n");
+        s += &format!(
+            "Shmem:          {:>8} kB\n",
+            SHM_USAGE.Used(&task.Thread().ContainerID()) / 1024
+        );
 
         // it always change 0 to 2, that's weird
         //s += &format!("Shmem:          {} kB\n", 0);
@@ -115,7 +115,9 @@ impl StatData {
         let mut buf = "".to_string();
 
         // We currently export only zero CPU stats. We could
-        // at least provide some aggregate stats.
+        // at least provide some aggregate stats. The steal time of the vcpus
+        // is left out too: without the user, system and idle times it would
+        // show as all of the time of the cpus.
         let cpu = CpuStats::default();
         buf += &format!("cpu {}\n", cpu.ToString());
 
@@ -0,0 +1,95 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+
+use super::super::super::super::super::super::auth::*;
+use super::super::super::super::super::super::common::*;
+use super::super::super::super::super::super::linux_def::*;
+use super::super::super::super::super::task::*;
+use super::super::super::super::super::version::*;
+use super::super::super::super::attr::*;
+use super::super::super::super::dirent::*;
+use super::super::super::super::file::*;
+use super::super::super::super::flags::*;
+use super::super::super::super::inode::*;
+use super::super::super::super::mount::*;
+use super::super::super::super::ramfs::dir::*;
+use super::super::super::dir_proc::*;
+use super::super::super::inode::*;
+use super::random::*;
+
+// ProcSysKernelDirNode represents the /proc/sys/kernel directory.
+pub struct ProcSysKernelDirNode {}
+
+impl DirDataNode for ProcSysKernelDirNode {
+    fn Lookup(&self, d: &Dir, task: &Task, dir: &Inode, name: &str) -> Result<Dirent> {
+        return d.Lookup(task, dir, name);
+    }
+
+    fn GetFile(
+        &self,
+        d: &Dir,
+        task: &Task,
+        dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        return d.GetFile(task, dir, dirent, flags);
+    }
+}
+
+fn NewStaticString(task: &Task, msrc: &Arc<QMutex<MountSource>>, s: &str) -> Inode {
+    let content = format!("{}\n", s);
+    return NewStaticProcInode(task, msrc, &Arc::new(content.as_bytes().to_vec()));
+}
+
+// NewKernel returns /proc/sys/kernel, ostype, osrelease and version agree with
+// uname(2).
+pub fn NewKernel(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mut contents = BTreeMap::new();
+    contents.insert(
+        "ostype".to_string(),
+        NewStaticString(task, msrc, VERSION.Sysname),
+    );
+    contents.insert(
+        "osrelease".to_string(),
+        NewStaticString(task, msrc, &KernelRelease()),
+    );
+    contents.insert(
+        "version".to_string(),
+        NewStaticString(task, msrc, &KernelVersion()),
+    );
+    contents.insert("random".to_string(), NewRandom(task, msrc));
+
+    let kernelDir = DirNode {
+        dir: Dir::New(
+            task,
+            contents,
+            &ROOT_OWNER,
+            &FilePermissions::FromMode(FileMode(0o0555)),
+        ),
+        data: ProcSysKernelDirNode {},
+    };
+
+    return NewProcInode(
+        &Arc::new(kernelDir),
+        msrc,
+        InodeType::SpecialDirectory,
+        None,
+    );
+}
@@ -0,0 +1,189 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+
+use super::super::super::super::super::super::auth::*;
+use super::super::super::super::super::super::common::*;
+use super::super::super::super::super::super::linux_def::*;
+use super::super::super::super::super::kernel::entropy::*;
+use super::super::super::super::super::task::*;
+use super::super::super::super::attr::*;
+use super::super::super::super::dirent::*;
+use super::super::super::super::file::*;
+use super::super::super::super::flags::*;
+use super::super::super::super::fsutil::file::readonly_file::*;
+use super::super::super::super::fsutil::inode::simple_file_inode::*;
+use super::super::super::super::inode::*;
+use super::super::super::super::mount::*;
+use super::super::super::super::ramfs::dir::*;
+use super::super::super::dir_proc::*;
+use super::super::super::inode::*;
+
+// /proc/sys/kernel/random. boot_id is a random uuid made once per boot of the
+// sandbox, all the containers of the sandbox see the same one and it is kept
+// across a pause. The guest memory restored from an image, e.g. a hibernation
+// one, may be restored many times, so the boot_id is made again after a
+// restore with the restore epoch of the entropy pool. uuid is a new random
+// uuid for each open. The boot_id is made on its first read, the entropy pool
+// may have no seed yet when procfs is built.
+lazy_static! {
+    // the boot_id and the restore epoch it is made in
+    pub static ref BOOT_ID: QMutex<Option<(u64, String)>> = QMutex::new(None);
+}
+
+pub fn BootId(task: &Task, nonblock: bool) -> Result<String> {
+    let epoch = Epoch();
+    if let Some((e, id)) = BOOT_ID.lock().clone() {
+        if e == epoch {
+            return Ok(id);
+        }
+    }
+
+    WaitReady(task, nonblock)?;
+    let id = NewUuid()?;
+    let mut bootId = BOOT_ID.lock();
+    match bootId.as_ref() {
+        Some((e, _)) if *e == epoch => (),
+        _ => *bootId = Some((epoch, id)),
+    }
+
+    return Ok(bootId.clone().unwrap().1);
+}
+
+// NewUuid returns a random version 4 uuid.
+pub fn NewUuid() -> Result<String> {
+    let mut b = [0u8; 16];
+    GetRandom(&mut b)?;
+    b[6] = (b[6] & 0x0f) | 0x40;
+    b[8] = (b[8] & 0x3f) | 0x80;
+
+    let mut s = String::with_capacity(36);
+    for i in 0..b.len() {
+        if i == 4 || i == 6 || i == 8 || i == 10 {
+            s.push('-');
+        }
+        s += &format!("{:02x}", b[i]);
+    }
+
+    return Ok(s);
+}
+
+pub struct BootIdInode {}
+
+impl SimpleFileTrait for BootIdInode {
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let bootId = format!("{}\n", BootId(task, flags.NonBlocking)?);
+        let fops = NewSnapshotReadonlyFileOperations(bootId.as_bytes().to_vec());
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
+
+pub struct UuidInode {}
+
+impl SimpleFileTrait for UuidInode {
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        WaitReady(task, flags.NonBlocking)?;
+        let uuid = format!("{}\n", NewUuid()?);
+        let fops = NewSnapshotReadonlyFileOperations(uuid.as_bytes().to_vec());
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
+
+pub fn NewBootIdFile(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let node = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        BootIdInode {},
+    );
+
+    return NewProcInode(&Arc::new(node), msrc, InodeType::SpecialFile, None);
+}
+
+pub fn NewUuidFile(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let node = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        UuidInode {},
+    );
+
+    return NewProcInode(&Arc::new(node), msrc, InodeType::SpecialFile, None);
+}
+
+// ProcSysKernelRandomDirNode represents the /proc/sys/kernel/random directory.
+pub struct ProcSysKernelRandomDirNode {}
+
+impl DirDataNode for ProcSysKernelRandomDirNode {
+    fn Lookup(&self, d: &Dir, task: &Task, dir: &Inode, name: &str) -> Result<Dirent> {
+        return d.Lookup(task, dir, name);
+    }
+
+    fn GetFile(
+        &self,
+        d: &Dir,
+        task: &Task,
+        dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        return d.GetFile(task, dir, dirent, flags);
+    }
+}
+
+pub fn NewRandom(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mut contents = BTreeMap::new();
+    contents.insert("boot_id".to_string(), NewBootIdFile(task, msrc));
+    contents.insert("uuid".to_string(), NewUuidFile(task, msrc));
+
+    let randomDir = DirNode {
+        dir: Dir::New(
+            task,
+            contents,
+            &ROOT_OWNER,
+            &FilePermissions::FromMode(FileMode(0o0555)),
+        ),
+        data: ProcSysKernelRandomDirNode {},
+    };
+
+    return NewProcInode(
+        &Arc::new(randomDir),
+        msrc,
+        InodeType::SpecialDirectory,
+        None,
+    );
+}
@@ -0,0 +1,35 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::sync::Arc;
+
+use super::super::super::super::super::task::*;
+use super::super::super::super::super::SHARESPACE;
+use super::super::super::super::inode::*;
+use super::super::super::super::mount::*;
+use super::super::super::inode::*;
+
+// the writeback thresholds of the host files, see fs::host::writeback
+pub fn NewDirtyBackgroundRatio(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let ratio = format!("{}\n", SHARESPACE.config.read().DirtyBackgroundRatio);
+
+    return NewStaticProcInode(task, msrc, &Arc::new(ratio.as_bytes().to_vec()));
+}
+
+pub fn NewDirtyRatio(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let ratio = format!("{}\n", SHARESPACE.config.read().DirtyRatio);
+
+    return NewStaticProcInode(task, msrc, &Arc::new(ratio.as_bytes().to_vec()));
+}
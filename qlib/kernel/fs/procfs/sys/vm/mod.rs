@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod dirty;
 pub mod mmap_min_addr;
 pub mod overcommit;
 pub mod vm;
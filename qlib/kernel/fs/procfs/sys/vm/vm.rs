@@ -30,6 +30,7 @@ use super::super::super::super::mount::*;
 use super::super::super::super::ramfs::dir::*;
 use super::super::super::dir_proc::*;
 use super::super::super::inode::*;
+use super::dirty::*;
 use super::mmap_min_addr::*;
 use super::overcommit::*;
 
@@ -55,6 +56,11 @@ impl DirDataNode for ProcSysDirNode {
 
 pub fn NewVm(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
     let mut contents = BTreeMap::new();
+    contents.insert(
+        "dirty_background_ratio".to_string(),
+        NewDirtyBackgroundRatio(task, msrc),
+    );
+    contents.insert("dirty_ratio".to_string(), NewDirtyRatio(task, msrc));
     contents.insert("mmap_min_addr".to_string(), NewMinAddrData(task, msrc));
     contents.insert("overcommit_memory".to_string(), NewOvercommit(task, msrc));
 
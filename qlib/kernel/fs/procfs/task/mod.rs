@@ -20,10 +20,12 @@ pub mod fds;
 pub mod io;
 pub mod maps;
 pub mod mounts;
+pub mod oom_score_adj;
 pub mod stat;
 pub mod statm;
 pub mod status;
 pub mod subtasks;
 pub mod task;
+pub mod timens_offsets;
 pub mod uid_pid_map;
 //pub mod namespace_symlink;
@@ -0,0 +1,143 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::super::super::auth::*;
+use super::super::super::super::super::common::*;
+use super::super::super::super::super::linux_def::*;
+use super::super::super::super::task::*;
+use super::super::super::super::threadmgr::thread::*;
+use super::super::super::attr::*;
+use super::super::super::dirent::*;
+use super::super::super::file::*;
+use super::super::super::flags::*;
+use super::super::super::fsutil::file::readonly_file::*;
+use super::super::super::fsutil::inode::simple_file_inode::*;
+use super::super::super::inode::*;
+use super::super::super::mount::*;
+use super::super::inode::*;
+
+// /proc/[pid]/oom_score_adj. The sandbox has no oom killer, the value is kept
+// for the applications and the tools reading it.
+pub fn NewOomScoreAdj(task: &Task, thread: &Thread, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let v = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o644)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        OomScoreAdjSimpleFileTrait {
+            thread: thread.clone(),
+        },
+    );
+    return NewProcInode(
+        &Arc::new(v),
+        msrc,
+        InodeType::SpecialFile,
+        Some(thread.clone()),
+    );
+}
+
+pub struct OomScoreAdjSimpleFileTrait {
+    pub thread: Thread,
+}
+
+impl SimpleFileTrait for OomScoreAdjSimpleFileTrait {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = ReadonlyFileOperations {
+            node: OomScoreAdjFileNode {
+                thread: self.thread.clone(),
+            },
+        };
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
+
+pub struct OomScoreAdjFileNode {
+    pub thread: Thread,
+}
+
+impl ReadonlyFileNode for OomScoreAdjFileNode {
+    fn ReadAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        dsts: &mut [IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        if offset < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let buf = format!("{}\n", self.thread.ThreadGroup().OomScoreAdj());
+        if offset as usize >= buf.len() {
+            return Ok(0);
+        }
+
+        let n = task.CopyDataOutToIovs(&buf.as_bytes()[offset as usize..], dsts, true)?;
+
+        return Ok(n as i64);
+    }
+
+    fn WriteAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        srcs: &[IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        let size = IoVec::NumBytes(srcs);
+        if size > MemoryDef::PAGE_SIZE as usize {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let mut buf = Vec::with_capacity(size);
+        buf.resize(size, 0);
+        let n = task.CopyDataInFromIovs(&mut buf, srcs, true)?;
+        let s =
+            String::from_utf8(buf[..n].to_vec()).map_err(|_| Error::SysError(SysErr::EINVAL))?;
+        let adj = s
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| Error::SysError(SysErr::EINVAL))?;
+
+        let tg = self.thread.ThreadGroup();
+        // lowering the value needs CAP_SYS_RESOURCE as linux does
+        if adj < tg.OomScoreAdj() {
+            let root = task.Thread().Kernel().RootUserNamespace();
+            if !task
+                .Creds()
+                .HasCapabilityIn(Capability::CAP_SYS_RESOURCE, &root)
+            {
+                return Err(Error::SysError(SysErr::EACCES));
+            }
+        }
+
+        tg.SetOomScoreAdj(adj)?;
+        return Ok(n as i64);
+    }
+}
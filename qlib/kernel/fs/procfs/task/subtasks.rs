@@ -145,7 +145,7 @@ impl DynamicDirFileNode for SubTasksFileNode {
         };
 
         if offset == 0 {
-            let root = task.mountNS.root.clone();
+            let root = task.mountNS.Root();
             let (dot, dotdot) = root.GetDotAttrs(&root);
 
             dirCtx.DirEmit(task, &".".to_string(), &dot)?;
@@ -41,9 +41,11 @@ use super::fds::*;
 use super::io::*;
 use super::maps::*;
 use super::mounts::*;
+use super::oom_score_adj::*;
 use super::stat::*;
 use super::statm::*;
 use super::status::*;
+use super::timens_offsets::*;
 use super::uid_pid_map::*;
 
 // taskDir represents a task-level directory.
@@ -99,12 +101,20 @@ impl ProcNode {
             NewMountInfoFile(task, thread, msrc),
         );
         contents.insert("mounts".to_string(), NewMountsFile(task, thread, msrc));
+        contents.insert(
+            "oom_score_adj".to_string(),
+            NewOomScoreAdj(task, thread, msrc),
+        );
         contents.insert(
             "stat".to_string(),
             NewStat(task, thread, showSubtasks, self.lock().pidns.clone(), msrc),
         );
         contents.insert("statm".to_string(), NewStatm(task, thread, msrc));
         contents.insert("status".to_string(), NewStatus(task, thread, msrc));
+        contents.insert(
+            "timens_offsets".to_string(),
+            NewTimensOffsets(task, thread, msrc),
+        );
         contents.insert("uid_map".to_string(), NewIdMap(task, thread, msrc, false));
 
         if showSubtasks {
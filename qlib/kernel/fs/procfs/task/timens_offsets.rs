@@ -0,0 +1,194 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::super::super::auth::*;
+use super::super::super::super::super::common::*;
+use super::super::super::super::super::linux::time::*;
+use super::super::super::super::super::linux_def::*;
+use super::super::super::super::kernel::timer::MonotonicNow;
+use super::super::super::super::task::*;
+use super::super::super::super::threadmgr::thread::*;
+use super::super::super::attr::*;
+use super::super::super::dirent::*;
+use super::super::super::file::*;
+use super::super::super::flags::*;
+use super::super::super::fsutil::file::readonly_file::*;
+use super::super::super::fsutil::inode::simple_file_inode::*;
+use super::super::super::inode::*;
+use super::super::super::mount::*;
+use super::super::inode::*;
+
+// /proc/[pid]/timens_offsets shows the clock offsets of the time namespace the
+// children of the task are created in. A write, e.g. "monotonic 86400 0", sets
+// them before the first child enters the namespace.
+pub fn NewTimensOffsets(task: &Task, thread: &Thread, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let v = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o644)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        TimensOffsetsSimpleFileTrait {
+            thread: thread.clone(),
+        },
+    );
+    return NewProcInode(
+        &Arc::new(v),
+        msrc,
+        InodeType::SpecialFile,
+        Some(thread.clone()),
+    );
+}
+
+pub struct TimensOffsetsSimpleFileTrait {
+    pub thread: Thread,
+}
+
+impl SimpleFileTrait for TimensOffsetsSimpleFileTrait {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = ReadonlyFileOperations {
+            node: TimensOffsetsFileNode {
+                thread: self.thread.clone(),
+            },
+        };
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
+
+pub struct TimensOffsetsFileNode {
+    pub thread: Thread,
+}
+
+fn ParseClock(s: &str) -> Result<i32> {
+    match s {
+        "monotonic" => return Ok(CLOCK_MONOTONIC),
+        "boottime" => return Ok(CLOCK_BOOTTIME),
+        _ => match s.parse::<i32>() {
+            Ok(c) if c == CLOCK_MONOTONIC || c == CLOCK_BOOTTIME => return Ok(c),
+            _ => return Err(Error::SysError(SysErr::EINVAL)),
+        },
+    }
+}
+
+// ParseOffsets parses the "<clock> <secs> <nsecs>" lines.
+fn ParseOffsets(s: &str) -> Result<Vec<(i32, i64)>> {
+    let mut offsets = Vec::new();
+    for line in s.lines().filter(|l| l.trim().len() > 0) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 3 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let clock = ParseClock(fields[0])?;
+        let secs = fields[1]
+            .parse::<i64>()
+            .map_err(|_| Error::SysError(SysErr::EINVAL))?;
+        let nsecs = fields[2]
+            .parse::<i64>()
+            .map_err(|_| Error::SysError(SysErr::EINVAL))?;
+        if nsecs < 0 || nsecs >= SECOND {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let offset = secs
+            .checked_mul(SECOND)
+            .and_then(|ns| ns.checked_add(nsecs))
+            .ok_or(Error::SysError(SysErr::ERANGE))?;
+        offsets.push((clock, offset));
+    }
+
+    return Ok(offsets);
+}
+
+impl ReadonlyFileNode for TimensOffsetsFileNode {
+    fn ReadAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        dsts: &mut [IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        if offset < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let timens = self.thread.TimeNamespaceForChildren();
+        let mut buf = String::new();
+        for (name, ns) in &[
+            ("monotonic", timens.MonotonicOffset()),
+            ("boottime", timens.BoottimeOffset()),
+        ] {
+            let ts = Timespec::FromNs(*ns);
+            buf += &format!("{:<10} {:>10} {:>9}\n", name, ts.tv_sec, ts.tv_nsec);
+        }
+
+        if offset as usize >= buf.len() {
+            return Ok(0);
+        }
+
+        let n = task.CopyDataOutToIovs(&buf.as_bytes()[offset as usize..], dsts, true)?;
+
+        return Ok(n as i64);
+    }
+
+    fn WriteAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        srcs: &[IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        let size = IoVec::NumBytes(srcs);
+        if size > MemoryDef::PAGE_SIZE as usize {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let mut buf = Vec::with_capacity(size);
+        buf.resize(size, 0);
+        let n = task.CopyDataInFromIovs(&mut buf, srcs, true)?;
+        let s =
+            String::from_utf8(buf[..n].to_vec()).map_err(|_| Error::SysError(SysErr::EINVAL))?;
+        let offsets = ParseOffsets(&s)?;
+
+        let timens = self.thread.TimeNamespaceForChildren();
+        if !task
+            .Creds()
+            .HasCapabilityIn(Capability::CAP_SYS_TIME, &timens.UserNamespace())
+        {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        // CLOCK_BOOTTIME is CLOCK_MONOTONIC in the sandbox
+        let now = MonotonicNow();
+        for (clock, offset) in offsets {
+            timens.SetOffset(clock, offset, now)?;
+        }
+
+        return Ok(n as i64);
+    }
+}
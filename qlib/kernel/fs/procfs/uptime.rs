@@ -45,11 +45,9 @@ impl ReadonlyFileNode for UptimeFileNode {
             return Err(Error::SysError(SysErr::EINVAL));
         }
 
-        let kernel = GetKernel();
-        let startTime = kernel.startTime;
-        let now = task.Now();
-
-        let val = now.Sub(startTime) / 1000_000;
+        // the uptime is the boottime clock of the time namespace
+        let boottime = GetKernel().Uptime() + task.Thread().TimeNamespace().BoottimeOffset();
+        let val = boottime / 1000_000;
         let second = val / 1000;
         let ms = val % 1000 / 10;
         let s = format!("{}.{} 0.00", second, ms);
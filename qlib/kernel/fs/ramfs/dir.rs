@@ -673,7 +673,7 @@ impl FileOperations for DirFileOperation {
     ) -> (i32, Result<i64>) {
         let d = self.dir.read();
 
-        let n = match dirCtx.ReadDir(task, &d.dentryMap) {
+        let n = match dirCtx.ReadDir(task, &d.dentryMap, offset) {
             Err(e) => return (offset, Err(e)),
             Ok(n) => n,
         };
@@ -0,0 +1,84 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::super::auth::*;
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::kernel::io_max::*;
+use super::super::super::task::*;
+use super::super::dirent::*;
+use super::super::file::*;
+use super::super::flags::*;
+use super::super::fsutil::file::readonly_file::*;
+use super::super::fsutil::inode::simple_file_inode::*;
+use super::super::inode::*;
+use super::super::mount::*;
+use super::sys::*;
+
+// /sys/fs/cgroup shows the io.max the guest enforces for the container of the
+// reader, see qlib/kernel/kernel/io_max.rs. It is read only, the limits come
+// from the spec.
+
+pub fn NewIoMax(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let v = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::SYSFS_MAGIC,
+        false,
+        IoMaxData {},
+    );
+    return NewFile(&Arc::new(v), msrc);
+}
+
+pub struct IoMaxData {}
+
+impl IoMaxData {
+    pub fn GenSnapshot(&self, task: &Task) -> Vec<u8> {
+        let mut ret = "".to_string();
+        for limit in IO_MAX.Limits(&task.Thread().ContainerID()) {
+            ret += &limit.Line();
+            ret += "\n";
+        }
+
+        return ret.as_bytes().to_vec();
+    }
+}
+
+impl SimpleFileTrait for IoMaxData {
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = NewSnapshotReadonlyFileOperations(self.GenSnapshot(task));
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
+
+pub fn NewCgroupDir(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mut m = BTreeMap::new();
+
+    m.insert("io.max".to_string(), NewIoMax(task, msrc));
+    return NewDir(task, msrc, m);
+}
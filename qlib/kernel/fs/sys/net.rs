@@ -0,0 +1,210 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::super::auth::*;
+use super::super::super::super::common::*;
+use super::super::super::super::linux::netdevice::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::task::*;
+use super::super::super::Kernel::HostSpace;
+use super::super::dirent::*;
+use super::super::file::*;
+use super::super::flags::*;
+use super::super::fsutil::file::readonly_file::*;
+use super::super::fsutil::inode::simple_file_inode::*;
+use super::super::inode::*;
+use super::super::mount::*;
+use super::sys::*;
+
+// /sys/class/net of the guest. The guest sockets are host sockets, so the
+// interfaces are the ones of the sandbox network namespace, the host reports
+// them with their counters. The directory of an interface is made when sysfs
+// is mounted, the content of its files is read from the host when they are
+// opened.
+
+#[derive(Clone, Copy, Debug)]
+pub enum NetDevAttr {
+    Address,
+    AddrLen,
+    Carrier,
+    Flags,
+    IfIndex,
+    Mtu,
+    OperState,
+    TxQueueLen,
+    Type,
+    RxPackets,
+    TxPackets,
+    RxBytes,
+    TxBytes,
+    RxErrors,
+    TxErrors,
+    RxDropped,
+    TxDropped,
+    Multicast,
+    Collisions,
+}
+
+const NETDEV_ATTRS: [(&str, NetDevAttr); 9] = [
+    ("address", NetDevAttr::Address),
+    ("addr_len", NetDevAttr::AddrLen),
+    ("carrier", NetDevAttr::Carrier),
+    ("flags", NetDevAttr::Flags),
+    ("ifindex", NetDevAttr::IfIndex),
+    ("mtu", NetDevAttr::Mtu),
+    ("operstate", NetDevAttr::OperState),
+    ("tx_queue_len", NetDevAttr::TxQueueLen),
+    ("type", NetDevAttr::Type),
+];
+
+const NETDEV_STATS: [(&str, NetDevAttr); 10] = [
+    ("rx_packets", NetDevAttr::RxPackets),
+    ("tx_packets", NetDevAttr::TxPackets),
+    ("rx_bytes", NetDevAttr::RxBytes),
+    ("tx_bytes", NetDevAttr::TxBytes),
+    ("rx_errors", NetDevAttr::RxErrors),
+    ("tx_errors", NetDevAttr::TxErrors),
+    ("rx_dropped", NetDevAttr::RxDropped),
+    ("tx_dropped", NetDevAttr::TxDropped),
+    ("multicast", NetDevAttr::Multicast),
+    ("collisions", NetDevAttr::Collisions),
+];
+
+// NetDevs returns the interfaces of the sandbox network namespace.
+pub fn NetDevs() -> Vec<NetDevInfo> {
+    let mut devs = Vec::with_capacity(MAX_NETDEVS);
+    devs.resize(MAX_NETDEVS, NetDevInfo::default());
+    let ret = HostSpace::NetDevs(&mut devs[0] as *mut _ as u64, MAX_NETDEVS);
+    if ret < 0 {
+        info!("/sys/class/net: get the interfaces fail {}", ret);
+        return Vec::new();
+    }
+
+    devs.truncate(ret as usize);
+    return devs;
+}
+
+impl NetDevAttr {
+    pub fn Value(&self, dev: &NetDevInfo) -> String {
+        let stats = &dev.Stats;
+        match self {
+            Self::Address => return dev.AddrString(),
+            Self::AddrLen => return format!("{}", dev.AddrLen),
+            Self::Carrier => return format!("{}", dev.Carrier),
+            Self::Flags => return format!("0x{:x}", dev.Flags),
+            Self::IfIndex => return format!("{}", dev.Index),
+            Self::Mtu => return format!("{}", dev.Mtu),
+            Self::OperState => return dev.OperStateString().to_string(),
+            Self::TxQueueLen => return format!("{}", dev.TxQueueLen),
+            Self::Type => return format!("{}", dev.Type),
+            Self::RxPackets => return format!("{}", stats.RxPackets),
+            Self::TxPackets => return format!("{}", stats.TxPackets),
+            Self::RxBytes => return format!("{}", stats.RxBytes),
+            Self::TxBytes => return format!("{}", stats.TxBytes),
+            Self::RxErrors => return format!("{}", stats.RxErrors),
+            Self::TxErrors => return format!("{}", stats.TxErrors),
+            Self::RxDropped => return format!("{}", stats.RxDropped),
+            Self::TxDropped => return format!("{}", stats.TxDropped),
+            Self::Multicast => return format!("{}", stats.Multicast),
+            Self::Collisions => return format!("{}", stats.Collisions),
+        }
+    }
+}
+
+pub struct NetDevData {
+    pub name: String,
+    pub attr: NetDevAttr,
+}
+
+impl NetDevData {
+    pub fn GenSnapshot(&self, _task: &Task) -> Result<Vec<u8>> {
+        for dev in NetDevs() {
+            if dev.Name() == self.name {
+                let ret = format!("{}\n", self.attr.Value(&dev));
+                return Ok(ret.as_bytes().to_vec());
+            }
+        }
+
+        // the interface is gone from the namespace
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+impl SimpleFileTrait for NetDevData {
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = NewSnapshotReadonlyFileOperations(self.GenSnapshot(task)?);
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
+
+pub fn NewNetDevFile(
+    task: &Task,
+    msrc: &Arc<QMutex<MountSource>>,
+    name: &str,
+    attr: NetDevAttr,
+) -> Inode {
+    let data = NetDevData {
+        name: name.to_string(),
+        attr: attr,
+    };
+
+    let v = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::SYSFS_MAGIC,
+        false,
+        data,
+    );
+    return NewFile(&Arc::new(v), msrc);
+}
+
+pub fn NewNetDevDir(task: &Task, msrc: &Arc<QMutex<MountSource>>, name: &str) -> Inode {
+    let mut m = BTreeMap::new();
+    for (file, attr) in NETDEV_ATTRS.iter() {
+        m.insert(file.to_string(), NewNetDevFile(task, msrc, name, *attr));
+    }
+
+    let mut stats = BTreeMap::new();
+    for (file, attr) in NETDEV_STATS.iter() {
+        stats.insert(file.to_string(), NewNetDevFile(task, msrc, name, *attr));
+    }
+    m.insert("statistics".to_string(), NewDir(task, msrc, stats));
+
+    return NewDir(task, msrc, m);
+}
+
+pub fn NewClassNetDir(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mut m = BTreeMap::new();
+    for dev in NetDevs() {
+        let name = dev.Name();
+        m.insert(name.clone(), NewNetDevDir(task, msrc, &name));
+    }
+
+    return NewDir(task, msrc, m);
+}
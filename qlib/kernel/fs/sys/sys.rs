@@ -26,7 +26,9 @@ use super::super::attr::*;
 use super::super::inode::*;
 use super::super::mount::*;
 use super::super::ramfs::dir::*;
+use super::cgroup::*;
 use super::devices::*;
+use super::net::*;
 
 pub fn NewFile<T: InodeOperations + 'static>(
     iops: &Arc<T>,
@@ -80,6 +82,7 @@ pub fn NewSys(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
     content.insert("bus".to_string(), NewDir(task, msrc, BTreeMap::new()));
 
     let mut classContent = BTreeMap::new();
+    classContent.insert("net".to_string(), NewClassNetDir(task, msrc));
     classContent.insert(
         "power_supply".to_string(),
         NewDir(task, msrc, BTreeMap::new()),
@@ -89,7 +92,9 @@ pub fn NewSys(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
     content.insert("dev".to_string(), NewDir(task, msrc, BTreeMap::new()));
     content.insert("devices".to_string(), NewDevicesDir(task, msrc));
     content.insert("firmware".to_string(), NewDir(task, msrc, BTreeMap::new()));
-    content.insert("fs".to_string(), NewDir(task, msrc, BTreeMap::new()));
+    let mut fsContent = BTreeMap::new();
+    fsContent.insert("cgroup".to_string(), NewCgroupDir(task, msrc));
+    content.insert("fs".to_string(), NewDir(task, msrc, fsContent));
     content.insert("kernel".to_string(), NewDir(task, msrc, BTreeMap::new()));
     content.insert("module".to_string(), NewDir(task, msrc, BTreeMap::new()));
     content.insert("power".to_string(), NewDir(task, msrc, BTreeMap::new()));
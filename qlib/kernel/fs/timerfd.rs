@@ -92,6 +92,7 @@ pub fn NewTimerfd(task: &Task, clockId: i32) -> Result<File> {
     let tops = TimerOperations {
         ops: internal,
         timer: timer,
+        clockId: clockId,
     };
 
     // Timerfds reject writes, but the Write flag must be set in order to
@@ -111,6 +112,7 @@ pub fn NewTimerfd(task: &Task, clockId: i32) -> Result<File> {
 pub struct TimerOperations {
     pub ops: Arc<TimerOperationsInternal>,
     pub timer: Timer,
+    pub clockId: i32,
 }
 
 impl TimerOperations {
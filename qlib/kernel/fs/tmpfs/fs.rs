@@ -17,11 +17,14 @@ use alloc::collections::btree_map::BTreeMap;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 use super::super::super::super::auth::id::*;
 use super::super::super::super::common::*;
 use super::super::super::super::linux_def::*;
+use super::super::super::memmgr::mem_high::*;
 use super::super::super::task::*;
+use super::super::ephemeral::*;
 use super::super::filesystems::*;
 use super::super::host::fs::*;
 use super::super::inode::*;
@@ -37,6 +40,13 @@ pub const ROOT_UIDKEY: &str = "uid";
 // GID for the root directory.
 pub const ROOT_GIDKEY: &str = "gid";
 
+// The size of the filesystem in bytes, with a k, m or g suffix, or a percent of
+// the sandbox memory with a % suffix.
+pub const SIZE_KEY: &str = "size";
+
+// The max count of the files.
+pub const NR_INODES_KEY: &str = "nr_inodes";
+
 // Permissions that exceed modeMask will be rejected.
 pub const MODE_MASK: u16 = 0o1777;
 
@@ -45,11 +55,36 @@ pub const DEFAULT_MODE: u16 = 0o777;
 
 pub struct TmpfsFileSystem {}
 
+// ParseSize parses the size and nr_inodes options as memparse of Linux.
+pub fn ParseSize(s: &str, percentOf: u64) -> Option<u64> {
+    let s = s.trim();
+    if s.len() == 0 {
+        return None;
+    }
+
+    let (num, mul) = match s.as_bytes()[s.len() - 1] {
+        b'%' => {
+            let pct = s[..s.len() - 1].parse::<u64>().ok()?;
+            return Some(percentOf / 100 * pct);
+        }
+        b'k' | b'K' => (&s[..s.len() - 1], 1 << 10),
+        b'm' | b'M' => (&s[..s.len() - 1], 1 << 20),
+        b'g' | b'G' => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+
+    return num.parse::<u64>().ok()?.checked_mul(mul);
+}
+
 impl Filesystem for TmpfsFileSystem {
     fn Name(&self) -> String {
         return "tmpfs".to_string();
     }
 
+    fn SpecOptions(&self) -> Vec<&'static str> {
+        return vec!["mode", "uid", "gid", "size", "nr_inodes"];
+    }
+
     fn Flags(&self) -> FilesystemFlags {
         return 0;
     }
@@ -123,6 +158,32 @@ impl Filesystem for TmpfsFileSystem {
             }
         }
 
+        // as Linux, the default is half of the memory
+        let mem = SandboxMemory();
+        let mut size = mem / 2;
+        match options.remove(SIZE_KEY) {
+            None => (),
+            Some(s) => match ParseSize(&s, mem) {
+                None => {
+                    info!("size value not parsable 'size={}'", s);
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+                Some(s) => size = s,
+            },
+        }
+
+        let mut inodes = mem / MemoryDef::PAGE_SIZE / 2;
+        match options.remove(NR_INODES_KEY) {
+            None => (),
+            Some(s) => match ParseSize(&s, inodes * 2) {
+                None => {
+                    info!("nr_inodes value not parsable 'nr_inodes={}'", s);
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+                Some(n) => inodes = n,
+            },
+        }
+
         // Fail if the caller passed us more options than we can parse. They may be
         // expecting us to set something we can't set.
         if options.len() > 0 {
@@ -130,7 +191,10 @@ impl Filesystem for TmpfsFileSystem {
             return Err(Error::SysError(SysErr::EINVAL));
         }
 
-        let msrc = MountSource::NewCachingMountSource(self, flags);
+        let mut msrc = MountSource::NewCachingMountSource(self, flags);
+        let mut usage = FsUsage::New(size, inodes);
+        usage.parent = Some(EPHEMERAL_STORAGE.clone());
+        msrc.Usage = Some(Arc::new(usage));
 
         let inode = NewTmpfsDir(
             task,
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 pub mod fs;
+pub mod shm;
 pub mod tmpfs_dir;
 pub mod tmpfs_fifo;
 pub mod tmpfs_file;
@@ -0,0 +1,102 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+
+use super::super::super::memmgr::mem_high::*;
+use super::super::mount::*;
+use super::fs::*;
+
+// The accounting of the /dev/shm of the containers. The containers of the
+// sandbox share one tmpfs at /dev/shm, sized by the /dev/shm of the first
+// container, e.g. the --shm-size of docker or the sandbox shm of a pod. The
+// files of the POSIX shared memory, shm_open(3) and sem_open(3), are charged
+// to the container of the task creating them and then to the tmpfs, so a
+// container can't go over the size of its own /dev/shm either and its usage
+// shows up on its own.
+
+lazy_static! {
+    pub static ref SHM_USAGE: ShmUsage = ShmUsage::default();
+}
+
+#[derive(Default)]
+pub struct ShmUsageInternal {
+    // the mount source of the /dev/shm tmpfs of the sandbox
+    pub msrc: Option<Arc<QMutex<MountSource>>>,
+    pub containers: BTreeMap<String, Arc<FsUsage>>,
+}
+
+#[derive(Default)]
+pub struct ShmUsage(QMutex<ShmUsageInternal>);
+
+impl ShmUsage {
+    // SetPod records the /dev/shm tmpfs of the sandbox.
+    pub fn SetPod(&self, msrc: &Arc<QMutex<MountSource>>) {
+        self.0.lock().msrc = Some(msrc.clone());
+    }
+
+    // Set sets the size of the /dev/shm of the container, e.g. "64m", an empty
+    // size is the one of the tmpfs.
+    pub fn Set(&self, cid: &str, size: &str) {
+        let mut intern = self.0.lock();
+        let pod = match &intern.msrc {
+            None => return,
+            Some(msrc) => msrc.lock().Usage.clone(),
+        };
+
+        let limit = if size.len() == 0 {
+            0
+        } else {
+            match ParseSize(size, SandboxMemory()) {
+                None => {
+                    info!("container {}: /dev/shm size not parsable {}", cid, size);
+                    0
+                }
+                Some(s) => s,
+            }
+        };
+
+        let mut usage = FsUsage::New(limit, 0);
+        usage.parent = pod;
+        intern.containers.insert(cid.to_string(), Arc::new(usage));
+    }
+
+    pub fn Remove(&self, cid: &str) {
+        self.0.lock().containers.remove(cid);
+    }
+
+    // Usage returns the usage of the container to charge a new file of msrc
+    // to, None if msrc is not the /dev/shm tmpfs.
+    pub fn Usage(&self, msrc: &Arc<QMutex<MountSource>>, cid: &str) -> Option<Arc<FsUsage>> {
+        let intern = self.0.lock();
+        match &intern.msrc {
+            Some(m) if Arc::ptr_eq(m, msrc) => (),
+            _ => return None,
+        }
+
+        return intern.containers.get(cid).cloned();
+    }
+
+    // Used returns the bytes of /dev/shm used by the container.
+    pub fn Used(&self, cid: &str) -> u64 {
+        match self.0.lock().containers.get(cid) {
+            None => return 0,
+            Some(usage) => return usage.Used(),
+        }
+    }
+}
@@ -43,8 +43,10 @@ pub const TMPFS_FSINFO: FsInfo = FsInfo {
     Type: FSMagic::TMPFS_MAGIC,
     TotalBlocks: 0,
     FreeBlocks: 0,
+    AvailableBlocks: 0,
     TotalFiles: 0,
     FreeFiles: 0,
+    BlockSize: 0,
 };
 
 pub fn TmpfsRename(
@@ -108,11 +110,12 @@ pub struct TmpfsDir(pub Dir);
 
 fn NewDirFn(task: &Task, dir: &Inode, perms: &FilePermissions) -> Result<Inode> {
     let msrc = dir.lock().MountSource.clone();
+    let (owner, perms) = NewChildOwner(task, dir, perms, true);
     return Ok(NewTmpfsDir(
         task,
         BTreeMap::new(),
-        &task.FileOwner(),
-        perms,
+        &owner,
+        &perms,
         msrc,
     ));
 }
@@ -140,9 +143,10 @@ fn NewSocketFn(
 
 fn NewFileFn(task: &Task, dir: &Inode, perms: &FilePermissions) -> Result<Inode> {
     let msrc = dir.lock().MountSource.clone();
+    let (owner, perms) = NewChildOwner(task, dir, perms, false);
     let uattr = UnstableAttr {
-        Owner: task.FileOwner(),
-        Perms: *perms,
+        Owner: owner,
+        Perms: perms,
         ..Default::default()
     };
 
@@ -34,6 +34,7 @@ use super::super::flags::*;
 use super::super::host::hostinodeop::*;
 use super::super::inode::*;
 use super::super::mount::*;
+use super::shm::*;
 use super::tmpfs_dir::*;
 
 pub fn NewTmpfsFileInode(
@@ -55,6 +56,11 @@ pub fn NewTmpfsFileInode(
         Some(iops) => iops.clone(),
     };
 
+    // a file of /dev/shm is charged to the container
+    if let Some(usage) = SHM_USAGE.Usage(msrc, &task.Thread().ContainerID()) {
+        hostiops.SetUsage(&usage);
+    }
+
     let ops = TmpfsFileInodeOp {
         inodeops: hostiops,
         uattr: Arc::new(QMutex::new(uattr)),
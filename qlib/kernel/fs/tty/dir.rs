@@ -498,7 +498,7 @@ impl FileOperations for DirFileOperations {
             .expect("DirInodeOperations convert fail")
             .lock();
 
-        return match dirCtx.ReadDir(task, &ops.dentryMap) {
+        return match dirCtx.ReadDir(task, &ops.dentryMap, offset) {
             Err(e) => (offset, Err(e)),
             Ok(count) => (offset + count as i32, Ok(0)),
         };
@@ -14,10 +14,12 @@
 
 use crate::qlib::mutex::*;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 use super::super::super::super::common::*;
 use super::super::super::super::linux_def::*;
 use super::super::super::task::*;
+use super::super::super::SignalDef::*;
 //use super::super::super::mem::seq::*;
 use super::super::host::tty::*;
 use super::queue::*;
@@ -40,6 +42,13 @@ pub struct LineDiscipline {
     pub outQueue: Arc<QMutex<Queue>>,
     pub termios: KernelTermios,
     pub column: i32,
+    // line is the line being edited in canonical mode, it is moved to
+    // inQueue when it is terminated
+    pub line: Vec<u8>,
+    // lnext is set after VLNEXT, the next character is taken literally
+    pub lnext: bool,
+    // signal is the ISIG signal to send to the foreground process group
+    pub signal: Option<Signal>,
 }
 
 impl LineDiscipline {
@@ -50,6 +59,9 @@ impl LineDiscipline {
             outQueue: Arc::new(QMutex::new(Queue::NewOutputQueue())),
             termios: termios,
             column: 0,
+            line: Vec::new(),
+            lnext: false,
+            signal: None,
         };
 
         return ld;
@@ -68,24 +80,15 @@ impl LineDiscipline {
 
         self.termios.FromTermios(&t);
 
+        // If canonical mode is turned off, the line being edited is moved to
+        // the read buffer. Anything in the read buffer is now readable.
         if oldCanonEnabled && !self.termios.LEnabled(LocalFlags::ICANON) {
-            //l.inQueue.pushWaitBuf(l)
-            self.inQueue.lock().readable = true;
-            //l.slaveWaiter.Notify(waiter.EventIn)
+            let line = core::mem::replace(&mut self.line, Vec::new());
+            let mut inQueue = self.inQueue.lock();
+            inQueue.buf.write(&line)?;
+            inQueue.readable = inQueue.buf.AvailableDataSize() > 0;
         }
 
-        //todo: don't understand
-        /*
-            // If canonical mode is turned off, move bytes from inQueue's wait
-        // buffer to its read buffer. Anything already in the read buffer is
-        // now readable.
-        if oldCanonEnabled && !l.termios.LEnabled(linux.ICANON) {
-            l.inQueue.pushWaitBuf(l)
-            l.inQueue.readable = true
-            l.slaveWaiter.Notify(waiter.EventIn)
-        }
-            */
-
         return Ok(());
     }
 
@@ -103,14 +106,58 @@ impl LineDiscipline {
         return self.inQueue.lock().ReableSize(task, dstAddr);
     }
 
+    // InputQueueRead reads the input, 0 is the end of file of VEOF in
+    // canonical mode.
     pub fn InputQueueRead(&self, _task: &Task, dst: &mut [u8]) -> Result<i64> {
-        let n = self.inQueue.lock().Read(dst)?;
+        return self.inQueue.lock().Read(dst);
+    }
 
-        if n > 0 {
-            return Ok(n);
+    // InputQueueReadMin reads the input if there are min bytes at least, it
+    // is the noncanonical read of VMIN.
+    pub fn InputQueueReadMin(&self, task: &Task, dst: &mut [u8], min: usize) -> Result<i64> {
+        if self.inQueue.lock().buf.AvailableDataSize() < min {
+            return Err(Error::SysError(SysErr::EAGAIN));
         }
 
-        return Err(Error::SysError(SysErr::EAGAIN));
+        return self.InputQueueRead(task, dst);
+    }
+
+    // InputReadable returns whether a read of the slave returns without
+    // waiting, the noncanonical reads wait for VMIN bytes.
+    pub fn InputReadable(&self) -> bool {
+        let inQueue = self.inQueue.lock();
+        if !inQueue.readable {
+            return false;
+        }
+
+        if self.termios.LEnabled(LocalFlags::ICANON) {
+            return true;
+        }
+
+        let (vmin, _) = self.termios.ReadParams();
+        return inQueue.buf.AvailableDataSize() >= core::cmp::max(vmin, 1);
+    }
+
+    pub fn InputWritable(&self) -> bool {
+        let inQueue = self.inQueue.lock();
+        if self.termios.LEnabled(LocalFlags::ICANON) && inQueue.readable {
+            return false;
+        }
+
+        return inQueue.buf.AvailableSpace() > 0;
+    }
+
+    pub fn OutputReadable(&self) -> bool {
+        return self.outQueue.lock().readable;
+    }
+
+    pub fn OutputWritable(&self) -> bool {
+        return self.outQueue.lock().buf.AvailableSpace() > 0;
+    }
+
+    // TakeSignal returns the ISIG signal of the input written.
+    pub fn TakeSignal(&mut self) -> Option<Signal> {
+        return self.signal.take();
     }
 
     pub fn InputQueueWrite(&mut self, _task: &Task, src: &mut [u8]) -> Result<i64> {
@@ -149,12 +196,189 @@ impl LineDiscipline {
         return Err(Error::SysError(SysErr::EAGAIN));
     }
 
-    pub fn ShouldDiscard(&self, q: &Queue, cBytes: &[u8]) -> bool {
+    pub fn ShouldDiscard(&self, cBytes: &[u8]) -> bool {
         return self.termios.LEnabled(LocalFlags::ICANON)
-            && q.buf.AvailableDataSize() + cBytes.len() > CANON_MAX_BYTES
+            && self.line.len() + cBytes.len() > CANON_MAX_BYTES - 1
             && !self.termios.IsTerminating(cBytes);
     }
 
+    // Echo writes the echo of the input to the output queue, it is dropped if
+    // the output queue is full.
+    pub fn Echo(&mut self, bytes: &[u8]) {
+        let mut bytes = bytes.to_vec();
+        let outQueue = self.outQueue.clone();
+        outQueue.lock().Write(&mut bytes, self).ok();
+    }
+
+    // EchoChar echoes an input character, as ^X for a control character with
+    // ECHOCTL.
+    pub fn EchoChar(&mut self, cBytes: &[u8]) {
+        if !self.termios.LEnabled(LocalFlags::ECHO) {
+            // ECHONL echoes the newline even without ECHO
+            if self.termios.LEnabled(LocalFlags::ECHONL)
+                && self.termios.LEnabled(LocalFlags::ICANON)
+                && cBytes == &['\n' as u8]
+            {
+                self.Echo(cBytes);
+            }
+            return;
+        }
+
+        if cBytes.len() == 1
+            && self.termios.LEnabled(LocalFlags::ECHOCTL)
+            && self.termios.IsCtl(cBytes[0])
+        {
+            self.Echo(&['^' as u8, cBytes[0] ^ 0x40]);
+            return;
+        }
+
+        self.Echo(cBytes);
+    }
+
+    // EraseChar erases the last character of the line, it returns false if
+    // the line is empty.
+    fn EraseChar(&mut self) -> bool {
+        let len = self.line.len();
+        if len == 0 {
+            return false;
+        }
+
+        // a utf8 character is erased with its continuation bytes
+        let mut n = 1;
+        if self.termios.IEnabled(InputFlags::IUTF8) {
+            while n < len && self.line[len - n] & 0xc0 == 0x80 {
+                n += 1;
+            }
+        }
+
+        let c = self.line.split_off(len - n);
+        if !self.termios.LEnabled(LocalFlags::ECHO) {
+            return true;
+        }
+
+        if !self.termios.LEnabled(LocalFlags::ECHOE) {
+            let erase = self.termios.ControlCharacters[KernelTermios::VERASE as usize];
+            self.EchoChar(&[erase]);
+            return true;
+        }
+
+        // a control character was echoed in two columns
+        let mut cols = 1;
+        if c.len() == 1 && self.termios.LEnabled(LocalFlags::ECHOCTL) && self.termios.IsCtl(c[0]) {
+            cols = 2;
+        }
+
+        for _i in 0..cols {
+            self.Echo(&['\x08' as u8, ' ' as u8, '\x08' as u8]);
+        }
+
+        return true;
+    }
+
+    // EraseWord erases the last word of the line with the spaces after it,
+    // for VWERASE.
+    fn EraseWord(&mut self) {
+        let mut inWord = false;
+        while let Some(&c) = self.line.last() {
+            let space = c == ' ' as u8 || c == '\t' as u8;
+            if space && inWord {
+                break;
+            }
+
+            if !space {
+                inWord = true;
+            }
+
+            self.EraseChar();
+        }
+    }
+
+    // KillLine erases the line for VKILL.
+    fn KillLine(&mut self) {
+        if self.termios.LEnabled(LocalFlags::ECHO)
+            && self.termios.LEnabled(LocalFlags::ECHOKE)
+            && self.termios.LEnabled(LocalFlags::ECHOE)
+        {
+            while self.EraseChar() {}
+            return;
+        }
+
+        self.line.clear();
+        if self.termios.LEnabled(LocalFlags::ECHO) {
+            let kill = self.termios.ControlCharacters[KernelTermios::VKILL as usize];
+            self.EchoChar(&[kill]);
+            if self.termios.LEnabled(LocalFlags::ECHOK) {
+                self.Echo(&['\n' as u8]);
+            }
+        }
+    }
+
+    // SpecialChar processes the special character c of the input, it returns
+    // false if c isn't one and is taken as input. Compare
+    // drivers/tty/n_tty.c:n_tty_receive_char_special.
+    pub fn SpecialChar(&mut self, q: &mut Queue, c: u8) -> bool {
+        let t = &self.termios;
+        if t.LEnabled(LocalFlags::ISIG) {
+            let sig = if t.IsSpecial(c, KernelTermios::VINTR) {
+                Signal::SIGINT
+            } else if t.IsSpecial(c, KernelTermios::VQUIT) {
+                Signal::SIGQUIT
+            } else if t.IsSpecial(c, KernelTermios::VSUSP) {
+                Signal::SIGTSTP
+            } else {
+                0
+            };
+
+            if sig != 0 {
+                if !t.LEnabled(LocalFlags::NOFLSH) {
+                    self.line.clear();
+                    let n = q.buf.AvailableDataSize();
+                    q.buf.Consume(n);
+                    q.readable = false;
+                }
+
+                self.EchoChar(&[c]);
+                self.signal = Some(Signal(sig));
+                return true;
+            }
+        }
+
+        if !t.LEnabled(LocalFlags::ICANON) {
+            return false;
+        }
+
+        let iexten = t.LEnabled(LocalFlags::IEXTEN);
+        if t.IsSpecial(c, KernelTermios::VEOF) {
+            // the line is readable without a terminator, an empty one is the
+            // end of file
+            let line = core::mem::replace(&mut self.line, Vec::new());
+            q.buf.write(&line).ok();
+            q.readable = true;
+        } else if t.IsSpecial(c, KernelTermios::VERASE) {
+            self.EraseChar();
+        } else if iexten && t.IsSpecial(c, KernelTermios::VWERASE) {
+            self.EraseWord();
+        } else if t.IsSpecial(c, KernelTermios::VKILL) {
+            self.KillLine();
+        } else if iexten && t.IsSpecial(c, KernelTermios::VLNEXT) {
+            self.lnext = true;
+            if t.LEnabled(LocalFlags::ECHO) && t.LEnabled(LocalFlags::ECHOCTL) {
+                self.Echo(&['^' as u8, '\x08' as u8]);
+            }
+        } else if iexten && t.IsSpecial(c, KernelTermios::VREPRINT) {
+            if t.LEnabled(LocalFlags::ECHO) {
+                self.EchoChar(&[c]);
+                self.Echo(&['\n' as u8]);
+                let line = self.line.clone();
+                self.Echo(&line);
+            }
+        } else {
+            return false;
+        }
+
+        return true;
+    }
+
     pub fn Peek(&self, b: &[u8]) -> usize {
         let mut size = 1;
 
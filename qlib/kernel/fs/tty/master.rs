@@ -314,16 +314,16 @@ pub struct MasterFileOperations {
 }
 
 impl Waitable for MasterFileOperations {
-    fn Readiness(&self, _task: &Task, _mask: EventMask) -> EventMask {
-        panic!("MasterFileOperations doesn't support Waitable::Readiness");
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        return self.t.MasterReadiness() & mask;
     }
 
-    fn EventRegister(&self, _task: &Task, _e: &WaitEntry, _mask: EventMask) {
-        panic!("MasterFileOperations doesn't support Waitable::EventRegister");
+    fn EventRegister(&self, task: &Task, e: &WaitEntry, mask: EventMask) {
+        self.t.masterWaiter.EventRegister(task, e, mask);
     }
 
-    fn EventUnregister(&self, _task: &Task, _e: &WaitEntry) {
-        panic!("MasterFileOperations doesn't support Waitable::EventUnregister");
+    fn EventUnregister(&self, task: &Task, e: &WaitEntry) {
+        self.t.masterWaiter.EventUnregister(task, e);
     }
 }
 
@@ -383,6 +383,7 @@ impl FileOperations for MasterFileOperations {
             .ld
             .lock()
             .OutputQueueRead(task, &mut buf[..size as usize])? as usize;
+        self.t.Notify();
         let res = task.CopyDataOutToIovs(&buf[0..cnt], dsts, false)?;
 
         assert!(res == cnt as usize, "MasterFileOperations:ReadAt fail");
@@ -408,8 +409,10 @@ impl FileOperations for MasterFileOperations {
             .t
             .ld
             .lock()
-            .InputQueueWrite(task, &mut buf.buf[0..len as usize])?;
-        return Ok(res);
+            .InputQueueWrite(task, &mut buf.buf[0..len as usize]);
+        self.t.SendSignal();
+        self.t.Notify();
+        return res;
     }
 
     fn Append(&self, task: &Task, f: &File, srcs: &[IoVec]) -> Result<(i64, i64)> {
@@ -437,16 +440,29 @@ impl FileOperations for MasterFileOperations {
         return inode.UnstableAttr(task);
     }
 
-    fn Ioctl(&self, task: &Task, _f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
+    fn Ioctl(&self, task: &Task, f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
         let cmd = request;
         match cmd {
             IoCtlCmd::FIONREAD => return self.t.ld.lock().OutputQueueReadSize(task, val),
             IoCtlCmd::TCGETS => return self.t.ld.lock().GetTermios(task, val),
-            IoCtlCmd::TCSETS => return self.t.ld.lock().SetTermios(task, val),
+            IoCtlCmd::TCSETS => {
+                self.t.ld.lock().SetTermios(task, val)?;
+                self.t.Notify();
+                return Ok(());
+            }
             IoCtlCmd::TCSETSW => {
                 //This should drain the output queue first.
-                return self.t.ld.lock().SetTermios(task, val);
+                self.t.ld.lock().SetTermios(task, val)?;
+                self.t.Notify();
+                return Ok(());
             }
+            // the job control of the pty is on the slave side, the master
+            // operates on it as the slave does
+            IoCtlCmd::TIOCGPGRP
+            | IoCtlCmd::TIOCSPGRP
+            | IoCtlCmd::TIOCSCTTY
+            | IoCtlCmd::TIOCNOTTY
+            | IoCtlCmd::TIOCGSID => return self.t.tty.Ioctl(task, f, request, val),
             IoCtlCmd::TIOCGPTN => {
                 let n = self.t.n;
                 task.CopyOutObj(&n, val)?;
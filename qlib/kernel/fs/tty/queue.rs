@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::vec::Vec;
+
 use super::super::super::super::bytestream::*;
 use super::super::super::super::common::*;
 use super::super::super::super::linux_def::*;
@@ -66,6 +68,7 @@ impl Queue {
             dst = &mut dst[0..CANON_MAX_BYTES]
         }
 
+        // a readable empty buffer is the end of file of VEOF
         let (_, n) = self.buf.read(dst)?;
 
         if self.buf.AvailableDataSize() == 0 {
@@ -167,18 +170,14 @@ pub fn outputQTransform(l: &mut LineDiscipline, q: &mut Queue, buf: &mut [u8]) -
 }
 
 pub fn inputQTransform(l: &mut LineDiscipline, q: &mut Queue, buf: &mut [u8]) -> usize {
-    //todo: don't unerstand
+    let canon = l.termios.LEnabled(LocalFlags::ICANON);
+
     // If there's a line waiting to be read in canonical mode, don't write
     // anything else to the read buffer.
-    if l.termios.LEnabled(LocalFlags::ICANON) && q.readable {
+    if canon && q.readable {
         return 0;
     }
 
-    let mut maxBytes = NON_CANON_MAX_BYTES;
-    if l.termios.LEnabled(LocalFlags::ICANON) {
-        maxBytes = CANON_MAX_BYTES;
-    }
-
     let mut ret = 0;
 
     let mut buf = buf;
@@ -186,58 +185,66 @@ pub fn inputQTransform(l: &mut LineDiscipline, q: &mut Queue, buf: &mut [u8]) ->
         let size = l.Peek(buf);
         let mut cBytes = buf[..size].to_vec();
 
-        match cBytes[0] as char {
-            '\r' => {
-                if l.termios.IEnabled(InputFlags::IGNCR) {
+        // the character after VLNEXT is neither mapped nor special
+        let literal = l.lnext;
+        l.lnext = false;
+        if size == 1 && !literal {
+            match l.termios.MapInput(cBytes[0]) {
+                None => {
                     buf = &mut buf[size..];
                     ret += size;
                     continue;
                 }
-
-                if l.termios.IEnabled(InputFlags::ICRNL) {
-                    cBytes[0] = '\n' as u8;
-                }
+                Some(c) => cBytes[0] = c,
             }
-            '\n' => {
-                if l.termios.IEnabled(InputFlags::INLCR) {
-                    cBytes[0] = '\r' as u8;
+
+            if l.SpecialChar(q, cBytes[0]) {
+                buf = &mut buf[size..];
+                ret += size;
+
+                // VEOF terminated the line
+                if canon && q.readable {
+                    break;
                 }
+                continue;
             }
-            _ => (),
         }
 
-        if l.ShouldDiscard(q, &cBytes) {
+        if canon {
+            if l.ShouldDiscard(&cBytes) {
+                buf = &mut buf[size..];
+                ret += size;
+                continue;
+            }
+
             buf = &mut buf[size..];
             ret += size;
+
+            l.line.extend_from_slice(&cBytes);
+            l.EchoChar(&cBytes);
+
+            if !literal && l.termios.IsTerminating(&cBytes) {
+                let line = core::mem::replace(&mut l.line, Vec::new());
+                q.buf.write(&line).unwrap();
+                q.readable = true;
+                break;
+            }
+
             continue;
         }
 
-        if q.buf.AvailableDataSize() + size > maxBytes {
+        if q.buf.AvailableDataSize() + size > NON_CANON_MAX_BYTES {
             break;
         }
 
         buf = &mut buf[size..];
         ret += size;
 
-        if l.termios.LEnabled(LocalFlags::ICANON) && l.termios.IsEOF(cBytes[0]) {
-            q.readable = true;
-            break;
-        }
-
         q.buf.write(&cBytes).unwrap();
-
-        if l.termios.LEnabled(LocalFlags::ECHO) {
-            let outQueue = l.outQueue.clone();
-            outQueue.lock().Write(&mut cBytes, l).unwrap();
-        }
-
-        if l.termios.LEnabled(LocalFlags::ICANON) && l.termios.IsTerminating(&cBytes) {
-            q.readable = true;
-            break;
-        }
+        l.EchoChar(&cBytes);
     }
 
-    if !l.termios.LEnabled(LocalFlags::ICANON) && q.buf.AvailableDataSize() > 0 {
+    if !canon && q.buf.AvailableDataSize() > 0 {
         q.readable = true;
     }
 
@@ -22,6 +22,7 @@ use core::ops::Deref;
 use super::super::super::super::auth::*;
 use super::super::super::super::common::*;
 use super::super::super::super::device::*;
+use super::super::super::super::linux::time::MILLISECOND;
 use super::super::super::super::linux_def::*;
 use super::super::super::kernel::time::*;
 use super::super::super::kernel::waiter::qlock::*;
@@ -29,12 +30,14 @@ use super::super::super::kernel::waiter::*;
 use super::super::super::socket::unix::transport::unix::*;
 use super::super::super::task::*;
 use super::super::super::uid::NewUID;
+use super::super::super::SignalDef::*;
 use super::super::attr::*;
 use super::super::dentry::*;
 use super::super::dirent::*;
 use super::super::file::*;
 use super::super::flags::*;
 use super::super::host::hostinodeop::*;
+use super::super::host::tty::*;
 use super::super::inode::*;
 use super::super::mount::*;
 use super::dir::*;
@@ -327,16 +330,88 @@ pub struct SlaveFileOperations {
 }
 
 impl Waitable for SlaveFileOperations {
-    fn Readiness(&self, _task: &Task, _mask: EventMask) -> EventMask {
-        panic!("SlaveFileOperations doesn't support Waitable::Readiness");
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        let t = self.d.read().t.clone();
+        return t.SlaveReadiness() & mask;
     }
 
-    fn EventRegister(&self, _task: &Task, _e: &WaitEntry, _mask: EventMask) {
-        panic!("SlaveFileOperations doesn't support Waitable::EventRegister");
+    fn EventRegister(&self, task: &Task, e: &WaitEntry, mask: EventMask) {
+        let t = self.d.read().t.clone();
+        t.slaveWaiter.EventRegister(task, e, mask);
     }
 
-    fn EventUnregister(&self, _task: &Task, _e: &WaitEntry) {
-        panic!("SlaveFileOperations doesn't support Waitable::EventUnregister");
+    fn EventUnregister(&self, task: &Task, e: &WaitEntry) {
+        let t = self.d.read().t.clone();
+        t.slaveWaiter.EventUnregister(task, e);
+    }
+}
+
+impl SlaveFileOperations {
+    // ReadNonCanon reads the input in noncanonical mode by VMIN and VTIME, see
+    // termios(3). VTIME is in tenths of a second, it is the time to wait for
+    // the input with VMIN 0 and the time to wait between the bytes with VMIN
+    // above 0.
+    fn ReadNonCanon(
+        &self,
+        task: &Task,
+        t: &Arc<Terminal>,
+        buf: &mut [u8],
+        blocking: bool,
+    ) -> Result<usize> {
+        let (vmin, vtime) = t.ld.lock().termios.ReadParams();
+        let min = core::cmp::min(vmin, buf.len());
+
+        if vtime == 0 || !blocking {
+            let ret = if min == 0 || !blocking {
+                t.ld.lock().InputQueueRead(task, buf)
+            } else {
+                t.ld.lock().InputQueueReadMin(task, buf, min)
+            };
+
+            match ret {
+                // with VMIN and VTIME 0 the read doesn't wait
+                Err(Error::SysError(SysErr::EAGAIN)) if vmin == 0 && vtime == 0 => return Ok(0),
+                Err(e) => return Err(e),
+                Ok(n) => return Ok(n as usize),
+            }
+        }
+
+        let timeout = vtime as i64 * 100 * MILLISECOND;
+        let general = task.blocker.generalEntry.clone();
+        t.slaveWaiter.EventRegister(task, &general, EVENT_READ);
+        defer!(t.slaveWaiter.EventUnregister(task, &general));
+
+        let mut cnt = 0;
+        loop {
+            match t.ld.lock().InputQueueRead(task, &mut buf[cnt..]) {
+                Err(Error::SysError(SysErr::EAGAIN)) => (),
+                Err(e) => return Err(e),
+                Ok(n) => cnt += n as usize,
+            }
+
+            if cnt >= core::cmp::max(min, 1) {
+                return Ok(cnt);
+            }
+
+            // with VMIN above 0 the timer starts at the first byte
+            let timeout = if cnt == 0 && vmin > 0 {
+                None
+            } else {
+                Some(timeout)
+            };
+
+            match task.blocker.BlockWithMonoTimeout(true, timeout).1 {
+                Err(Error::SysError(SysErr::ETIMEDOUT)) => return Ok(cnt),
+                Err(Error::ErrInterrupted) => {
+                    if cnt > 0 {
+                        return Ok(cnt);
+                    }
+                    return Err(Error::SysError(SysErr::ERESTARTSYS));
+                }
+                Err(e) => return Err(e),
+                Ok(()) => (),
+            }
+        }
     }
 }
 
@@ -382,8 +457,11 @@ impl FileOperations for SlaveFileOperations {
         _f: &File,
         dsts: &mut [IoVec],
         _offset: i64,
-        _blocking: bool,
+        blocking: bool,
     ) -> Result<i64> {
+        let t = self.d.read().t.clone();
+        t.tty.CheckChange(task, Signal(Signal::SIGTTIN))?;
+
         let mut buf: [u8; 4096] = [0; 4096];
 
         let mut size = IoVec::NumBytes(dsts);
@@ -391,13 +469,14 @@ impl FileOperations for SlaveFileOperations {
             size = buf.len();
         }
 
-        let cnt = self
-            .d
-            .read()
-            .t
-            .ld
-            .lock()
-            .InputQueueRead(task, &mut buf[..size as usize])? as usize;
+        let canon = t.ld.lock().termios.LEnabled(LocalFlags::ICANON);
+        let cnt = if canon {
+            t.ld.lock()
+                .InputQueueRead(task, &mut buf[..size as usize])? as usize
+        } else {
+            self.ReadNonCanon(task, &t, &mut buf[..size as usize], blocking)?
+        };
+        t.Notify();
 
         let res = task.CopyDataOutToIovs(&buf[0..cnt], dsts, false)?;
         assert!(res == cnt, "MasterFileOperations:ReadAt fail");
@@ -412,6 +491,12 @@ impl FileOperations for SlaveFileOperations {
         _offset: i64,
         _blocking: bool,
     ) -> Result<i64> {
+        let t = self.d.read().t.clone();
+        let tostop = t.ld.lock().termios.LEnabled(LocalFlags::TOSTOP);
+        if tostop {
+            t.tty.CheckChange(task, Signal(Signal::SIGTTOU))?;
+        }
+
         let size = IoVec::NumBytes(srcs);
         if size == 0 {
             return Ok(0)
@@ -419,13 +504,10 @@ impl FileOperations for SlaveFileOperations {
         let mut buf = DataBuff::New(size);
         let len = task.CopyDataInFromIovs(&mut buf.buf, srcs, true)?;
 
-        let res = self
-            .d
-            .read()
-            .t
-            .ld
-            .lock()
-            .OutputQueueWrite(task, &mut buf.buf[0..len as usize])?;
+        let res =
+            t.ld.lock()
+                .OutputQueueWrite(task, &mut buf.buf[0..len as usize])?;
+        t.Notify();
         return Ok(res);
     }
 
@@ -454,15 +536,33 @@ impl FileOperations for SlaveFileOperations {
         return inode.UnstableAttr(task);
     }
 
-    fn Ioctl(&self, task: &Task, _f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
+    fn Ioctl(&self, task: &Task, f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
         let cmd = request;
         match cmd {
             IoCtlCmd::FIONREAD => return self.d.read().t.ld.lock().InputQueueReadSize(task, val),
             IoCtlCmd::TCGETS => return self.d.read().t.ld.lock().GetTermios(task, val),
-            IoCtlCmd::TCSETS => return self.d.read().t.ld.lock().SetTermios(task, val),
+            IoCtlCmd::TCSETS => {
+                let t = self.d.read().t.clone();
+                t.tty.CheckChange(task, Signal(Signal::SIGTTOU))?;
+                t.ld.lock().SetTermios(task, val)?;
+                t.Notify();
+                return Ok(());
+            }
             IoCtlCmd::TCSETSW => {
                 //This should drain the output queue first.
-                return self.d.read().t.ld.lock().SetTermios(task, val);
+                let t = self.d.read().t.clone();
+                t.tty.CheckChange(task, Signal(Signal::SIGTTOU))?;
+                t.ld.lock().SetTermios(task, val)?;
+                t.Notify();
+                return Ok(());
+            }
+            IoCtlCmd::TIOCGPGRP
+            | IoCtlCmd::TIOCSPGRP
+            | IoCtlCmd::TIOCSCTTY
+            | IoCtlCmd::TIOCNOTTY
+            | IoCtlCmd::TIOCGSID => {
+                let tty = self.d.read().t.tty.clone();
+                return tty.Ioctl(task, f, request, val);
             }
             IoCtlCmd::TIOCGPTN => {
                 let n = self.d.read().t.n;
@@ -15,6 +15,10 @@
 use crate::qlib::mutex::*;
 use alloc::sync::Arc;
 
+use super::super::super::super::linux_def::*;
+use super::super::super::kernel::waiter::*;
+use super::super::super::threadmgr::tty::*;
+use super::super::super::SignalDef::*;
 use super::super::host::tty::*;
 use super::dir::*;
 use super::line_discipline::*;
@@ -23,6 +27,11 @@ pub struct Terminal {
     pub n: u32,
     pub d: DirInodeOperations,
     pub ld: Arc<QMutex<LineDiscipline>>,
+    // tty is the job control state of the terminal
+    pub tty: TTY,
+    // masterWaiter and slaveWaiter are the waiters of the two ends
+    pub masterWaiter: Queue,
+    pub slaveWaiter: Queue,
 }
 
 impl Terminal {
@@ -31,6 +40,64 @@ impl Terminal {
             d: d.clone(),
             n: n,
             ld: Arc::new(QMutex::new(LineDiscipline::New(DEFAULT_SLAVE_TERMIOS))),
+            tty: TTY::default(),
+            masterWaiter: Queue::default(),
+            slaveWaiter: Queue::default(),
         };
     }
+
+    pub fn MasterReadiness(&self) -> EventMask {
+        let ld = self.ld.lock();
+        let mut mask = 0;
+        if ld.OutputReadable() {
+            mask |= READABLE_EVENT;
+        }
+
+        if ld.InputWritable() {
+            mask |= WRITEABLE_EVENT;
+        }
+
+        return mask;
+    }
+
+    pub fn SlaveReadiness(&self) -> EventMask {
+        let ld = self.ld.lock();
+        let mut mask = 0;
+        if ld.InputReadable() {
+            mask |= READABLE_EVENT;
+        }
+
+        if ld.OutputWritable() {
+            mask |= WRITEABLE_EVENT;
+        }
+
+        return mask;
+    }
+
+    // Notify wakes up the waiters of the two ends after a read or a write
+    // changed the queues.
+    pub fn Notify(&self) {
+        let master = self.MasterReadiness();
+        if master != 0 {
+            self.masterWaiter.Notify(master);
+        }
+
+        let slave = self.SlaveReadiness();
+        if slave != 0 {
+            self.slaveWaiter.Notify(slave);
+        }
+    }
+
+    // SendSignal sends the ISIG signal of the input written to the master to
+    // the foreground process group.
+    pub fn SendSignal(&self) {
+        let sig = match self.ld.lock().TakeSignal() {
+            None => return,
+            Some(sig) => sig,
+        };
+
+        if let Some(pg) = self.tty.ForegroundProcessGroup() {
+            pg.SendSignal(&SignalInfo::SignalInfoPriv(sig)).ok();
+        }
+    }
 }
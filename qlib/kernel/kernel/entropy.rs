@@ -0,0 +1,309 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use core::sync::atomic::Ordering;
+
+use super::super::super::common::*;
+use super::super::super::linux::time::*;
+use super::super::super::negotiation::*;
+use super::super::task::*;
+use super::super::Kernel::rdtsc;
+use super::super::Kernel::HostSpace;
+use super::super::SHARESPACE;
+use super::timer::*;
+
+// The guest entropy pool. qkernel gets its seed from the host rng device
+// (HYPERCALL_RNG) at boot, before any task runs, so getrandom(2) and
+// /dev/urandom never block and don't need a qcall per read. The pool is a
+// ChaCha20 generator with fast key erasure, it is reseeded from the host
+// after RESEED_BYTES of output or RESEED_INTERVAL, and after qvisor restores
+// the guest memory, e.g. from a hibernation image which holds the pool.
+// Until the host seed arrives, and after a restore until the pool is
+// reseeded, the pool gives no output: getrandom(2) blocks or fails with
+// EAGAIN as the one of Linux before its crng is ready.
+
+lazy_static! {
+    pub static ref ENTROPY_POOL: QMutex<EntropyPool> = QMutex::new(EntropyPool::default());
+}
+
+const CHACHA_CONSTANTS: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+const CHACHA_BLOCK_SIZE: usize = 64;
+
+fn QuarterRound(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+// ChaCha20Block computes one keystream block with a 64 bits counter and a
+// 64 bits nonce.
+pub fn ChaCha20Block(key: &[u32; 8], counter: u64, nonce: u64, out: &mut [u8; CHACHA_BLOCK_SIZE]) {
+    let mut init = [0u32; 16];
+    init[0..4].copy_from_slice(&CHACHA_CONSTANTS);
+    init[4..12].copy_from_slice(key);
+    init[12] = counter as u32;
+    init[13] = (counter >> 32) as u32;
+    init[14] = nonce as u32;
+    init[15] = (nonce >> 32) as u32;
+
+    let mut s = init;
+    for _ in 0..10 {
+        QuarterRound(&mut s, 0, 4, 8, 12);
+        QuarterRound(&mut s, 1, 5, 9, 13);
+        QuarterRound(&mut s, 2, 6, 10, 14);
+        QuarterRound(&mut s, 3, 7, 11, 15);
+        QuarterRound(&mut s, 0, 5, 10, 15);
+        QuarterRound(&mut s, 1, 6, 11, 12);
+        QuarterRound(&mut s, 2, 7, 8, 13);
+        QuarterRound(&mut s, 3, 4, 9, 14);
+    }
+
+    for i in 0..16 {
+        let w = s[i].wrapping_add(init[i]);
+        out[i * 4..i * 4 + 4].copy_from_slice(&w.to_le_bytes());
+    }
+}
+
+#[derive(Default)]
+pub struct EntropyPool {
+    key: [u32; 8],
+    nonce: u64,
+    seeded: bool,
+    outputSinceReseed: u64,
+    lastReseed: i64,
+    // the restore epoch of the guest memory at the last seed
+    epoch: u64,
+}
+
+impl EntropyPool {
+    pub const SEED_SIZE: usize = 32;
+    pub const RESEED_BYTES: u64 = 1 << 20;
+    pub const RESEED_INTERVAL: i64 = 300 * SECOND;
+
+    // Mix folds the seed into the key, the previous key stays part of the state
+    // so a weak seed can't make the pool worse.
+    pub fn Mix(&mut self, seed: &[u8], now: i64, epoch: u64) {
+        for (i, chunk) in seed.chunks(4).enumerate() {
+            let mut w = [0u8; 4];
+            w[..chunk.len()].copy_from_slice(chunk);
+            self.key[i % 8] ^= u32::from_le_bytes(w);
+        }
+
+        self.Rekey();
+        self.seeded = true;
+        self.outputSinceReseed = 0;
+        self.lastReseed = now;
+        self.epoch = epoch;
+    }
+
+    pub fn Seeded(&self) -> bool {
+        return self.seeded;
+    }
+
+    // Ready returns whether the pool may give output. A pool restored from an
+    // image is shared with all the other restores of the image until the
+    // host reseeds it.
+    pub fn Ready(&self, epoch: u64) -> bool {
+        return self.seeded && self.epoch == epoch;
+    }
+
+    pub fn NeedReseed(&self, now: i64, epoch: u64) -> bool {
+        return !self.seeded
+            || self.epoch != epoch
+            || self.outputSinceReseed >= Self::RESEED_BYTES
+            || now - self.lastReseed >= Self::RESEED_INTERVAL;
+    }
+
+    // Rekey replaces the key with fresh keystream so the earlier output can't be
+    // recovered from the pool state.
+    fn Rekey(&mut self) {
+        let mut block = [0u8; CHACHA_BLOCK_SIZE];
+        ChaCha20Block(&self.key, 0, self.nonce, &mut block);
+        self.nonce = self.nonce.wrapping_add(1);
+        for i in 0..8 {
+            self.key[i] = u32::from_le_bytes([
+                block[i * 4],
+                block[i * 4 + 1],
+                block[i * 4 + 2],
+                block[i * 4 + 3],
+            ]);
+        }
+    }
+
+    pub fn Fill(&mut self, buf: &mut [u8]) {
+        let mut block = [0u8; CHACHA_BLOCK_SIZE];
+        // counter 0 is used by Rekey
+        let mut counter = 1;
+        for chunk in buf.chunks_mut(CHACHA_BLOCK_SIZE) {
+            ChaCha20Block(&self.key, counter, self.nonce, &mut block);
+            chunk.copy_from_slice(&block[..chunk.len()]);
+            counter += 1;
+        }
+
+        self.outputSinceReseed += buf.len() as u64;
+        self.Rekey();
+    }
+}
+
+// HostSeed gets seed material from the host rng device. When qvisor doesn't
+// support HYPERCALL_RNG, fall back to the GetRandom qcall.
+pub fn HostSeed(seed: &mut [u8]) -> Result<()> {
+    let ret = if FeatureEnabled(Feature::HOST_RNG) {
+        HostSpace::HwRandom(&seed[0] as *const _ as u64, seed.len() as u64)
+    } else {
+        HostSpace::GetRandom(&seed[0] as *const _ as u64, seed.len() as u64, 0)
+    };
+
+    if ret < 0 {
+        return Err(Error::SysError(-ret as i32));
+    }
+
+    return Ok(());
+}
+
+// Epoch returns the restore epoch of the guest memory, bumped by qvisor on
+// each restore.
+pub fn Epoch() -> u64 {
+    return SHARESPACE.entropyEpoch.load(Ordering::Acquire);
+}
+
+// InitEntropy seeds the pool at boot, the clock is not ready yet.
+pub fn InitEntropy() {
+    let mut seed = [0u8; EntropyPool::SEED_SIZE + 8];
+    let res = HostSeed(&mut seed[..EntropyPool::SEED_SIZE]);
+
+    // the tsc at boot adds a little jitter in case the host seed is missing
+    let tsc = unsafe { rdtsc() };
+    seed[EntropyPool::SEED_SIZE..].copy_from_slice(&tsc.to_le_bytes());
+
+    let mut pool = ENTROPY_POOL.lock();
+    pool.Mix(&seed, 0, Epoch());
+    if let Err(e) = res {
+        error!("InitEntropy: get host seed fail {:?}", e);
+        // the next read retries the host
+        pool.seeded = false;
+    }
+}
+
+pub const SEED_RETRY: Duration = 10 * MILLISECOND;
+
+fn Reseed(now: i64, epoch: u64) {
+    let mut seed = [0u8; EntropyPool::SEED_SIZE];
+    match HostSeed(&mut seed) {
+        Err(e) => error!("entropy pool reseed fail {:?}", e),
+        Ok(()) => ENTROPY_POOL.lock().Mix(&seed, now, epoch),
+    }
+}
+
+// GetRandom fills buf from the guest entropy pool. It fails with EAGAIN
+// when the pool has no seed from the host yet.
+pub fn GetRandom(buf: &mut [u8]) -> Result<()> {
+    let now = MonotonicNow();
+    let epoch = Epoch();
+    let needReseed = ENTROPY_POOL.lock().NeedReseed(now, epoch);
+    if needReseed {
+        // don't hold the pool lock across the host call
+        Reseed(now, epoch);
+    }
+
+    let mut pool = ENTROPY_POOL.lock();
+    if !pool.Ready(epoch) {
+        return Err(Error::SysError(SysErr::EAGAIN));
+    }
+
+    pool.Fill(buf);
+    return Ok(());
+}
+
+// WaitReady waits for the pool to get its seed from the host, retrying the
+// host every SEED_RETRY.
+pub fn WaitReady(task: &Task, nonblock: bool) -> Result<()> {
+    loop {
+        let epoch = Epoch();
+        if ENTROPY_POOL.lock().Ready(epoch) {
+            return Ok(());
+        }
+
+        Reseed(MonotonicNow(), epoch);
+        if ENTROPY_POOL.lock().Ready(epoch) {
+            return Ok(());
+        }
+
+        if nonblock {
+            return Err(Error::SysError(SysErr::EAGAIN));
+        }
+
+        let (_remain, res) = task.blocker.BlockWithMonoTimeout(true, Some(SEED_RETRY));
+        match res {
+            Err(Error::SysError(SysErr::ETIMEDOUT)) => (),
+            Err(Error::ErrInterrupted) => return Err(Error::SysError(SysErr::ERESTARTSYS)),
+            Err(e) => return Err(e),
+            Ok(()) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn TestChaCha20Block() {
+        // RFC 7539 2.3.2, the 32 bits block count and the first nonce word
+        // form the 64 bits counter.
+        let mut key = [0u32; 8];
+        for i in 0..8 {
+            let b = (i * 4) as u8;
+            key[i] = u32::from_le_bytes([b, b + 1, b + 2, b + 3]);
+        }
+
+        let mut out = [0u8; CHACHA_BLOCK_SIZE];
+        ChaCha20Block(&key, 0x09000000_00000001, 0x4a000000, &mut out);
+        assert_eq!(
+            out[..16],
+            [
+                0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15, 0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20,
+                0x71, 0xc4
+            ]
+        );
+    }
+
+    #[test]
+    fn TestEntropyPoolReseed() {
+        let mut pool = EntropyPool::default();
+        assert!(pool.NeedReseed(0, 0));
+
+        assert!(!pool.Ready(0));
+        pool.Mix(&[1; EntropyPool::SEED_SIZE], 0, 0);
+        assert!(pool.Ready(0));
+        assert!(!pool.NeedReseed(1, 0));
+        // the guest memory is restored
+        assert!(pool.NeedReseed(1, 1));
+        assert!(!pool.Ready(1));
+
+        let mut a = [0u8; 100];
+        let mut b = [0u8; 100];
+        pool.Fill(&mut a);
+        pool.Fill(&mut b);
+        assert!(a != b);
+
+        assert!(pool.NeedReseed(EntropyPool::RESEED_INTERVAL, 0));
+    }
+}
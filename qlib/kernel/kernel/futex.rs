@@ -55,7 +55,9 @@ pub enum KeyKind {
     KindSharedPrivate,
 
     // KindSharedMappable indicates a shared futex on a memory mapping other
-    // than a private anonymous memory mapping.
+    // than a private anonymous memory mapping. It is keyed by the mapped file
+    // and the offset in it, so it works across the processes mapping the file,
+    // e.g. shm, memfd or a MAP_SHARED host file, at any address.
     KindSharedMappable,
 }
 
@@ -71,9 +73,15 @@ pub struct Key {
     // Kind is the type of the Key.
     pub Kind: KeyKind,
 
-    // If Kind is KindPrivate or KindSharedPrivate, addr is the represented
-    // physical memory address. Otherwise, is the user address
+    // If Kind is KindPrivate or KindSharedPrivate, addr is the user address.
+    // Otherwise, it is the offset in the file of Dev and Ino, or the physical
+    // address of a mapping without a file.
     pub Addr: u64,
+
+    // Dev and Ino are the device and the inode number of the file of a
+    // KindSharedMappable futex.
+    pub Dev: u64,
+    pub Ino: u64,
 }
 
 pub const BUCKET_COUNT: usize = 1 << BUCKET_COUNT_BITS;
@@ -81,7 +89,7 @@ pub const BUCKET_COUNT_BITS: usize = 10;
 
 impl Key {
     pub fn HashCode(&self) -> usize {
-        let addr = self.Addr as usize;
+        let addr = (self.Addr ^ self.Ino.wrapping_mul(0x9e37_79b9_7f4a_7c15)) as usize;
         let h1 = (addr >> 2) + (addr >> 12) + (addr >> 22);
         let h2 = (addr >> 32) + (addr >> 42);
         return (h1 + h2) % BUCKET_COUNT;
@@ -100,7 +108,11 @@ pub trait Target {
     // If GetSharedKey returns a Key with a non-nil MappingIdentity, a
     // reference is held on the MappingIdentity, which must be dropped by the
     // caller when the Key is no longer in use.
-    fn GetSharedKey(&self, addr: u64) -> Result<Key>;
+    //
+    // write is set by the operations writing the futex word, the others look
+    // the mapping up read only as kernel/futex.c:get_futex_key with
+    // FUTEX_READ.
+    fn GetSharedKey(&self, addr: u64, write: bool) -> Result<Key>;
 }
 
 impl Target for Task {
@@ -119,33 +131,8 @@ impl Target for Task {
         return Ok(val);
     }
 
-    fn GetSharedKey(&self, addr: u64) -> Result<Key> {
-        /*let vmas = self.mm.vmas.clone();
-        let vma = match vmas.lock().Get(addr) {
-            None => return Err(Error::SysError(SysErr::EFAULT)),
-            Some(v) => v.clone(),
-        };*/
-        let _ml = self.mm.MappingReadLock();
-
-        let (vma, _) = match self.mm.GetVmaAndRangeLocked(addr) {
-            None => return Err(Error::SysError(SysErr::EFAULT)),
-            Some(v) => v.clone(),
-        };
-
-        let private = vma.private;
-        if private {
-            return Ok(Key {
-                Kind: KeyKind::KindSharedPrivate,
-                Addr: addr,
-            });
-        }
-
-        let (phyAdr, _) = self.mm.VirtualToPhyLocked(addr)?;
-
-        return Ok(Key {
-            Kind: KeyKind::KindSharedMappable,
-            Addr: phyAdr,
-        });
+    fn GetSharedKey(&self, addr: u64, write: bool) -> Result<Key> {
+        return self.mm.GetSharedFutexKey(self, addr, write);
     }
 }
 
@@ -208,7 +195,7 @@ fn AtomicOp(t: &Target, addr: u64, opIn: u32) -> Result<bool> {
 }
 
 // getKey returns a Key representing address addr in c.
-fn Getkey(t: &Target, addr: u64, private: bool) -> Result<Key> {
+fn Getkey(t: &Target, addr: u64, private: bool, write: bool) -> Result<Key> {
     // Ensure the address is aligned.
     // It must be a DWORD boundary.
     if addr & 0x3 != 0 {
@@ -219,10 +206,11 @@ fn Getkey(t: &Target, addr: u64, private: bool) -> Result<Key> {
         return Ok(Key {
             Kind: KeyKind::KindPrivate,
             Addr: addr,
+            ..Default::default()
         });
     }
 
-    return t.GetSharedKey(addr);
+    return t.GetSharedKey(addr, write);
 }
 
 pub struct SpinLock {
@@ -442,7 +430,7 @@ impl FutexMgr {
     }
 
     pub fn Wake(&self, t: &Target, addr: u64, private: bool, bitmask: u32, n: i32) -> Result<i32> {
-        let k = Getkey(t, addr, private)?;
+        let k = Getkey(t, addr, private, false)?;
 
         let temp = self.lockQueue(&k);
 
@@ -473,8 +461,8 @@ impl FutexMgr {
         nwake: i32,
         nreq: i32,
     ) -> Result<i32> {
-        let k1 = Getkey(t, addr, private)?;
-        let k2 = Getkey(t, naddr, private)?;
+        let k1 = Getkey(t, addr, private, false)?;
+        let k2 = Getkey(t, naddr, private, false)?;
 
         let (q1, q2) = self.lockQueues(&k1, &k2);
 
@@ -552,8 +540,8 @@ impl FutexMgr {
         nwake2: i32,
         op: u32,
     ) -> Result<i32> {
-        let k1 = Getkey(t, addr1, private)?;
-        let k2 = Getkey(t, addr2, private)?;
+        let k1 = Getkey(t, addr1, private, false)?;
+        let k2 = Getkey(t, addr2, private, true)?;
 
         let (q1, q2) = self.lockQueues(&k1, &k2);
 
@@ -594,7 +582,7 @@ impl FutexMgr {
         val: u32,
         bitmask: u32,
     ) -> Result<()> {
-        let k = Getkey(t, addr, private)?;
+        let k = Getkey(t, addr, private, false)?;
 
         w.Clear();
         w.SetMask(bitmask as u64);
@@ -663,7 +651,7 @@ impl FutexMgr {
         private: bool,
         retry: bool,
     ) -> Result<bool> {
-        let k = Getkey(t, addr, private)?;
+        let k = Getkey(t, addr, private, true)?;
 
         w.Clear();
         w.SetKey(&k);
@@ -746,7 +734,7 @@ impl FutexMgr {
     // waiters, TID of the next waiter (FIFO) is set to the given address, and the
     // waiter woken up. If there are no waiters, 0 is set to the address.
     pub fn UnlockPI(&self, t: &Target, addr: u64, tid: u32, private: bool) -> Result<()> {
-        let k = Getkey(t, addr, private)?;
+        let k = Getkey(t, addr, private, true)?;
 
         let q = self.lockQueueWithCreate(&k);
 
@@ -0,0 +1,236 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+use crate::qlib::mutex::*;
+
+use super::super::super::common::*;
+use super::super::super::linux::time::*;
+use super::super::super::linux_def::*;
+use super::super::super::loader::{BlockPartition, IoMax};
+use super::super::task::*;
+use super::timer::MonotonicNow;
+
+// The io.max of the containers of the sandbox, the IO throttling of cgroup v2
+// done in the guest so the containers of a sandbox don't starve each other on
+// the same host disk. The limits of a container come from the blkio throttle
+// devices of its spec and apply to the reads and the writes of its tasks on
+// the host files and the raw block volumes, keyed by the host device: the
+// device of the host filesystem for a file, the device itself for a block
+// device, a partition being charged to its disk. Every limit is a token bucket refilled at its rate. An IO waits
+// until the buckets of its container on the device are out of debt and is
+// charged with its size before it is issued, as blk-throttle does with a bio,
+// so a short read is charged in full. The limits of the container of the
+// reader are shown in /sys/fs/cgroup/io.max.
+
+// the budget a bucket saves up while its container is idle, in the time of
+// the rate
+const IO_MAX_BURST: i64 = 100 * MILLISECOND;
+
+#[derive(Default, Debug)]
+pub struct TokenBucket {
+    // the tokens per second, 0 is no limit
+    pub rate: u64,
+    pub tokens: i64,
+    pub last: i64,
+}
+
+impl TokenBucket {
+    pub fn New(rate: u64, now: i64) -> Self {
+        let mut ret = Self {
+            rate: rate,
+            tokens: 0,
+            last: now,
+        };
+
+        ret.tokens = ret.Burst();
+        return ret;
+    }
+
+    pub fn Burst(&self) -> i64 {
+        return (self.rate as i128 * IO_MAX_BURST as i128 / SECOND as i128) as i64;
+    }
+
+    pub fn Refill(&mut self, now: i64) {
+        if now <= self.last {
+            return;
+        }
+
+        let tokens =
+            self.tokens as i128 + (now - self.last) as i128 * self.rate as i128 / SECOND as i128;
+        let burst = self.Burst() as i128;
+        self.tokens = if tokens > burst { burst } else { tokens } as i64;
+        self.last = now;
+    }
+
+    // Delay returns the ns till the bucket is out of debt.
+    pub fn Delay(&mut self, now: i64) -> i64 {
+        if self.rate == 0 {
+            return 0;
+        }
+
+        self.Refill(now);
+        if self.tokens >= 0 {
+            return 0;
+        }
+
+        return (-self.tokens as i128 * SECOND as i128 / self.rate as i128) as i64 + 1;
+    }
+
+    pub fn Charge(&mut self, tokens: u64) {
+        if self.rate == 0 {
+            return;
+        }
+
+        self.tokens -= tokens as i64;
+    }
+}
+
+pub struct DeviceIoMax {
+    pub limit: IoMax,
+    pub rbps: TokenBucket,
+    pub wbps: TokenBucket,
+    pub riops: TokenBucket,
+    pub wiops: TokenBucket,
+}
+
+impl DeviceIoMax {
+    pub fn New(limit: &IoMax, now: i64) -> Self {
+        return Self {
+            limit: *limit,
+            rbps: TokenBucket::New(limit.Rbps, now),
+            wbps: TokenBucket::New(limit.Wbps, now),
+            riops: TokenBucket::New(limit.Riops, now),
+            wiops: TokenBucket::New(limit.Wiops, now),
+        };
+    }
+
+    pub fn Buckets(&mut self, write: bool) -> (&mut TokenBucket, &mut TokenBucket) {
+        if write {
+            return (&mut self.wbps, &mut self.wiops);
+        }
+
+        return (&mut self.rbps, &mut self.riops);
+    }
+}
+
+lazy_static! {
+    pub static ref IO_MAX: IoMaxTable = IoMaxTable::default();
+}
+
+#[derive(Default)]
+pub struct IoMaxTable {
+    // there is a container with limits, the IO of the others skips the table
+    pub enabled: AtomicBool,
+    pub containers: QMutex<BTreeMap<String, Vec<DeviceIoMax>>>,
+    // the disks of the partitions of the limited disks
+    pub partitions: QMutex<BTreeMap<(u32, u32), (u32, u32)>>,
+}
+
+impl IoMaxTable {
+    // Set replaces the limits of the container, no limit removes it.
+    pub fn Set(&self, cid: &str, limits: &[IoMax]) {
+        let mut containers = self.containers.lock();
+        if limits.len() == 0 {
+            containers.remove(cid);
+        } else {
+            let now = MonotonicNow();
+            let devs = limits.iter().map(|l| DeviceIoMax::New(l, now)).collect();
+            containers.insert(cid.to_string(), devs);
+        }
+
+        self.enabled.store(containers.len() > 0, Ordering::Release);
+    }
+
+    // AddPartitions adds the partitions of the disks of a container limits.
+    pub fn AddPartitions(&self, partitions: &[BlockPartition]) {
+        let mut map = self.partitions.lock();
+        for p in partitions {
+            map.insert((p.Major, p.Minor), (p.DiskMajor, p.DiskMinor));
+        }
+    }
+
+    // Disk returns the disk the IO on the device is limited on, the device
+    // itself if it isn't a partition.
+    pub fn Disk(&self, dev: (u32, u32)) -> (u32, u32) {
+        match self.partitions.lock().get(&dev) {
+            None => return dev,
+            Some(disk) => return *disk,
+        }
+    }
+
+    pub fn Limits(&self, cid: &str) -> Vec<IoMax> {
+        match self.containers.lock().get(cid) {
+            None => return Vec::new(),
+            Some(devs) => return devs.iter().map(|d| d.limit).collect(),
+        }
+    }
+
+    // Delay returns how long the next IO of the container on the device waits,
+    // the IO is charged once it doesn't.
+    pub fn Delay(&self, cid: &str, dev: (u32, u32), write: bool, bytes: u64) -> i64 {
+        let mut containers = self.containers.lock();
+        let devs = match containers.get_mut(cid) {
+            None => return 0,
+            Some(devs) => devs,
+        };
+
+        let d = match devs
+            .iter_mut()
+            .find(|d| d.limit.Major == dev.0 && d.limit.Minor == dev.1)
+        {
+            None => return 0,
+            Some(d) => d,
+        };
+
+        let now = MonotonicNow();
+        let (bps, iops) = d.Buckets(write);
+        let delay = core::cmp::max(bps.Delay(now), iops.Delay(now));
+        if delay == 0 {
+            bps.Charge(bytes);
+            iops.Charge(1);
+        }
+
+        return delay;
+    }
+
+    // Throttle blocks the task till its container can do an IO of bytes on the
+    // host device.
+    pub fn Throttle(&self, task: &Task, dev: (u32, u32), write: bool, bytes: u64) -> Result<()> {
+        if !self.enabled.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        let dev = self.Disk(dev);
+        let cid = task.Thread().ContainerID();
+        loop {
+            let delay = self.Delay(&cid, dev, write, bytes);
+            if delay == 0 {
+                return Ok(());
+            }
+
+            match task.blocker.BlockWithMonoTimeout(false, Some(delay)).1 {
+                Err(Error::SysError(SysErr::ETIMEDOUT)) | Ok(()) => (),
+                Err(Error::ErrInterrupted) => return Err(Error::SysError(SysErr::ERESTARTSYS)),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
@@ -52,6 +52,7 @@ use super::ipc_namespace::*;
 use super::platform::*;
 use super::signal_handler::*;
 use super::time::*;
+use super::time_namespace::*;
 use super::timer::timekeeper::*;
 use super::timer::timer::*;
 use super::timer::*;
@@ -105,6 +106,7 @@ pub struct KernelInternal {
     pub rootUserNamespace: UserNameSpace,
     pub rootUTSNamespace: UTSNamespace,
     pub rootIPCNamespace: IPCNamespace,
+    pub rootTimeNamespace: TimeNamespace,
     pub applicationCores: usize,
     //pub useHostCores: bool,
 
@@ -140,6 +142,9 @@ pub struct KernelInternal {
     pub cpuClockTicker: Arc<KernelCPUClockTicker>,
 
     pub startTime: Time,
+    // monoStartTime is the monotonic time at which the kernel started, the
+    // uptime is measured from it so that a settimeofday doesn't move it.
+    pub monoStartTime: Time,
     pub started: AtomicBool,
 
     pub platform: DefaultPlatform,
@@ -207,6 +212,7 @@ impl Kernel {
             rootUserNamespace: args.RootUserNamespace,
             rootUTSNamespace: args.RootUTSNamespace,
             rootIPCNamespace: args.RootIPCNamespace,
+            rootTimeNamespace: args.RootTimeNamespace,
             applicationCores: args.ApplicationCores as usize - 1,
             mounts: QRwLock::new(BTreeMap::new()),
             sockets: SocketStore::default(),
@@ -220,6 +226,7 @@ impl Kernel {
             //cpuClockTicker: Timer::New(&MONOTONIC_CLOCK, &cpuTicker),
             cpuClockTicker: cpuTicker,
             startTime: Task::RealTimeNow(),
+            monoStartTime: Task::MonoTimeNow(),
             started: AtomicBool::new(false),
             platform: DefaultPlatform::default(),
             lastProcessTime: QMutex::new(0),
@@ -240,6 +247,11 @@ impl Kernel {
         return self.syslog.clone();
     }
 
+    // Uptime returns the time since the kernel started on the monotonic clock.
+    pub fn Uptime(&self) -> Duration {
+        return Task::MonoTimeNow().Sub(self.monoStartTime);
+    }
+
     pub fn Atomically(&self, mut f: impl FnMut()) {
         let _t = self.lastProcessTime.lock();
         f();
@@ -291,6 +303,10 @@ impl Kernel {
         return self.rootUTSNamespace.clone();
     }
 
+    pub fn RootTimeNamespace(&self) -> TimeNamespace {
+        return self.rootTimeNamespace.clone();
+    }
+
     pub fn RootIPCNamespace(&self) -> IPCNamespace {
         return self.rootIPCNamespace.clone();
     }
@@ -365,6 +381,7 @@ impl Kernel {
             AllowedCPUMask: CPUSet::NewFullCPUSet(self.applicationCores),
             UTSNamespace: args.UTSNamespace.clone(),
             IPCNamespace: args.IPCNamespace.clone(),
+            TimeNamespace: self.RootTimeNamespace(),
             Blocker: task.blocker.clone(),
             ContainerID: args.ContainerID.to_string(),
         };
@@ -531,7 +548,7 @@ impl<'a> Context for CreateProcessContext<'a> {
 
         let containerID = &self.args.ContainerID;
 
-        let root = self.k.mounts.read().get(containerID).unwrap().root.clone();
+        let root = self.k.mounts.read().get(containerID).unwrap().Root();
 
         return root;
     }
@@ -560,6 +577,9 @@ pub struct InitKernalArgs {
 
     // RootIPCNamespace is the root IPC namespace.
     pub RootIPCNamespace: IPCNamespace,
+
+    // RootTimeNamespace is the root time namespace.
+    pub RootTimeNamespace: TimeNamespace,
 }
 
 #[derive(Default)]
@@ -615,6 +635,8 @@ pub struct CreateProcessArgs {
     pub ContainerID: String,
 
     pub Stdiofds: [i32; 3],
+    // ListenFds are the host sockets installed from fd 3, for socket activation.
+    pub ListenFds: Vec<i32>,
     pub Terminal: bool,
     pub ExecId: Option<String>,
 }
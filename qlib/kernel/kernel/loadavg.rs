@@ -0,0 +1,220 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::AtomicI64;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::super::linux::time::*;
+use super::super::super::vcpu_mgr::*;
+use super::super::SHARESPACE;
+
+// The load average of the sandbox, the exponential moving average of the
+// active tasks over 1, 5 and 15 minutes, sampled every 5 seconds. The active
+// tasks are the runnable tasks, i.e. the ready ones and the ones running on a
+// vcpu but the sampler, plus the tasks in an uninterruptible wait. The fixed
+// point arithmetic is the one of kernel/sched/loadavg.c.
+
+// the bits of the fractional part of a load
+pub const FSHIFT: u64 = 11;
+pub const FIXED_1: u64 = 1 << FSHIFT;
+
+// 1/exp(5sec/1min), 1/exp(5sec/5min) and 1/exp(5sec/15min) as fixed point
+pub const EXP_1: u64 = 1884;
+pub const EXP_5: u64 = 2014;
+pub const EXP_15: u64 = 2037;
+
+// the sample period
+pub const LOAD_FREQ: i64 = 5 * SECOND;
+
+// sysinfo(2) reports the loads with 16 bits of fractional part
+pub const SI_LOAD_SHIFT: u64 = 16;
+
+pub static LOAD_AVG: LoadAvg = LoadAvg::New();
+
+// CalcLoad returns the load decayed by exp with active tasks, both fixed point.
+pub fn CalcLoad(load: u64, exp: u64, active: u64) -> u64 {
+    let mut newload = load * exp + active * (FIXED_1 - exp);
+    if active >= load {
+        // round up so a constant load converges to it
+        newload += FIXED_1 - 1;
+    }
+
+    return newload / FIXED_1;
+}
+
+// FixedPower returns x^n of the fixed point x.
+pub fn FixedPower(x: u64, n: u64) -> u64 {
+    let mut x = x;
+    let mut n = n;
+    let mut result = FIXED_1;
+    while n > 0 {
+        if n & 1 != 0 {
+            result = (result * x + FIXED_1 / 2) >> FSHIFT;
+        }
+
+        n >>= 1;
+        if n > 0 {
+            x = (x * x + FIXED_1 / 2) >> FSHIFT;
+        }
+    }
+
+    return result;
+}
+
+// CalcLoadN returns the load after n samples of active tasks.
+pub fn CalcLoadN(load: u64, exp: u64, active: u64, n: u64) -> u64 {
+    return CalcLoad(load, FixedPower(exp, n), active);
+}
+
+// RunnableTasks returns the tasks ready to run or running on a vcpu.
+pub fn RunnableTasks() -> u64 {
+    return RunnableTasksExcept(None);
+}
+
+// RunnableTasksExcept returns the tasks in the ready queues plus the non idle
+// tasks running on a vcpu other than the sampler's one.
+pub fn RunnableTasksExcept(sampler: Option<usize>) -> u64 {
+    let scheduler = &SHARESPACE.scheduler;
+    let mut ready = 0;
+    for vcpuId in 0..scheduler.queue.len() {
+        ready += scheduler.ReadyTaskCnt(vcpuId);
+    }
+
+    let mut running = 0;
+    for (vcpuId, vcpu) in scheduler.VcpuArr.iter().enumerate() {
+        if Some(vcpuId) == sampler {
+            continue;
+        }
+
+        // a vcpu without a task or back on its wait task is idle
+        let current = vcpu.currentTask.load(Ordering::Acquire);
+        if current != 0 && current != vcpu.waitTask.load(Ordering::Acquire) {
+            running += 1;
+        }
+    }
+
+    return ready + running;
+}
+
+// ActiveTasks returns the runnable tasks, but the sampler, plus the tasks in
+// an uninterruptible wait.
+pub fn ActiveTasks(sampler: Option<usize>) -> u64 {
+    let uninterruptible = SHARESPACE.stats.IoWaitTasks();
+    return RunnableTasksExcept(sampler) + uninterruptible;
+}
+
+pub struct LoadAvg {
+    pub loads: [AtomicU64; 3],
+    // the time of the next sample
+    pub next: AtomicI64,
+}
+
+impl LoadAvg {
+    pub const fn New() -> Self {
+        return Self {
+            loads: [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)],
+            next: AtomicI64::new(0),
+        };
+    }
+
+    // Update samples the active tasks if the sample period is over. A late
+    // update folds the missed samples with the current active tasks as
+    // kernel/sched/loadavg.c:calc_global_nohz does.
+    pub fn Update(&self, now: i64) {
+        let next = self.next.load(Ordering::Acquire);
+        if next == 0 {
+            self.next
+                .compare_exchange(0, now + LOAD_FREQ, Ordering::AcqRel, Ordering::Acquire)
+                .ok();
+            return;
+        }
+
+        if now < next {
+            return;
+        }
+
+        let n = ((now - next) / LOAD_FREQ + 1) as u64;
+        // only one updater takes the samples
+        if self
+            .next
+            .compare_exchange(
+                next,
+                next + n as i64 * LOAD_FREQ,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            return;
+        }
+
+        let active = ActiveTasks(Some(CPULocal::CpuId())) * FIXED_1;
+        let exps = [EXP_1, EXP_5, EXP_15];
+        for i in 0..3 {
+            let load = self.loads[i].load(Ordering::Relaxed);
+            self.loads[i].store(CalcLoadN(load, exps[i], active, n), Ordering::Relaxed);
+        }
+    }
+
+    // Loads returns the 1, 5 and 15 minutes loads with FSHIFT bits of
+    // fractional part.
+    pub fn Loads(&self) -> [u64; 3] {
+        return [
+            self.loads[0].load(Ordering::Relaxed),
+            self.loads[1].load(Ordering::Relaxed),
+            self.loads[2].load(Ordering::Relaxed),
+        ];
+    }
+}
+
+// LoadInt and LoadFrac return the integer part and the 2 decimal digits of a
+// load, as the LOAD_INT and LOAD_FRAC macros.
+pub fn LoadInt(load: u64) -> u64 {
+    return load >> FSHIFT;
+}
+
+pub fn LoadFrac(load: u64) -> u64 {
+    return LoadInt((load & (FIXED_1 - 1)) * 100);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn TestCalcLoad() {
+        // a constant load of 1 converges to 1.00
+        let mut load = 0;
+        for _ in 0..1000 {
+            load = CalcLoad(load, EXP_1, FIXED_1);
+        }
+        assert_eq!(LoadInt(load), 1);
+        assert_eq!(LoadFrac(load), 0);
+
+        // after one minute, i.e. 12 samples, the 1 minute load is 1-1/e
+        let load = CalcLoadN(0, EXP_1, FIXED_1, 12);
+        assert_eq!(LoadInt(load), 0);
+        assert_eq!(LoadFrac(load), 63);
+
+        // folded samples decay as the samples one by one
+        let mut load = 4 * FIXED_1;
+        for _ in 0..6 {
+            load = CalcLoad(load, EXP_5, 0);
+        }
+        let folded = CalcLoadN(4 * FIXED_1, EXP_5, 0, 6);
+        assert_eq!(LoadInt(folded), LoadInt(load));
+        assert_eq!(LoadFrac(folded), LoadFrac(load));
+    }
+}
@@ -20,6 +20,7 @@ pub mod vdso;
 pub mod waiter;
 //pub mod ktime;
 pub mod semaphore;
+pub mod time_namespace;
 pub mod uts_namespace;
 pub mod shm;
 pub mod abstract_socket_namespace;
@@ -27,6 +28,9 @@ pub mod aio;
 pub mod async_process;
 pub mod async_wait;
 pub mod cpuset;
+pub mod io_max;
+pub mod loadavg;
+pub mod entropy;
 pub mod epoll;
 pub mod eventfd;
 pub mod fasync;
@@ -38,6 +42,8 @@ pub mod pipe;
 pub mod platform;
 pub mod signal_handler;
 pub mod signalfd;
+pub mod pidfd;
+pub mod seccomp;
 pub mod msgqueue;
 pub mod syslog;
 pub mod socket_store;
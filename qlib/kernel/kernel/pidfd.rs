@@ -0,0 +1,203 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::sync::Arc;
+use core::any::Any;
+use core::ops::Deref;
+
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::fs::anon::*;
+use super::super::fs::attr::*;
+use super::super::fs::dentry::*;
+use super::super::fs::dirent::*;
+use super::super::fs::file::*;
+use super::super::fs::flags::*;
+use super::super::fs::host::hostinodeop::*;
+use super::super::task::*;
+use super::super::threadmgr::thread_group::*;
+use super::waiter::*;
+
+// PIDFD_NONBLOCK is a pidfd_open(2) flag.
+pub const PIDFD_NONBLOCK: i32 = Flags::O_NONBLOCK;
+
+pub struct PidfdOperationInternal {
+    // tg is the thread group the pidfd refers to. The pidfd keeps the thread
+    // group alive but not its thread ID, which is released when it is reaped.
+    pub tg: ThreadGroup,
+}
+
+pub struct PidfdOperation(Arc<PidfdOperationInternal>);
+
+impl Deref for PidfdOperation {
+    type Target = Arc<PidfdOperationInternal>;
+
+    fn deref(&self) -> &Arc<PidfdOperationInternal> {
+        &self.0
+    }
+}
+
+impl PidfdOperation {
+    pub fn NewPidfdFile(task: &Task, tg: &ThreadGroup) -> File {
+        // name matches kernel/pid.c:pidfd_create.
+        let inode = NewAnonInode(task);
+        let dirent = Dirent::New(&inode, "anon_inode:[pidfd]");
+
+        let intern = PidfdOperationInternal { tg: tg.clone() };
+
+        let fops = Self(Arc::new(intern));
+        return File::New(
+            &dirent,
+            &FileFlags {
+                Read: true,
+                Write: true,
+                ..Default::default()
+            },
+            fops,
+        );
+    }
+
+    pub fn ThreadGroup(&self) -> ThreadGroup {
+        return self.tg.clone();
+    }
+
+    // Exited returns whether all the tasks of the thread group have exited.
+    pub fn Exited(&self) -> bool {
+        return self.tg.lock().liveTasks == 0;
+    }
+}
+
+impl SpliceOperations for PidfdOperation {}
+
+impl FileOperations for PidfdOperation {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn FopsType(&self) -> FileOpsType {
+        return FileOpsType::PidfdOperation;
+    }
+
+    fn Seekable(&self) -> bool {
+        return false;
+    }
+
+    fn Seek(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _whence: i32,
+        _current: i64,
+        _offset: i64,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ESPIPE));
+    }
+
+    fn ReadDir(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _offset: i64,
+        _serializer: &mut DentrySerializer,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn ReadAt(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _dsts: &mut [IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn WriteAt(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _srcs: &[IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn Append(&self, _task: &Task, _f: &File, _srcs: &[IoVec]) -> Result<(i64, i64)> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn Fsync(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _start: i64,
+        _end: i64,
+        _syncType: SyncType,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn Flush(&self, _task: &Task, _f: &File) -> Result<()> {
+        return Ok(());
+    }
+
+    fn UnstableAttr(&self, task: &Task, f: &File) -> Result<UnstableAttr> {
+        let inode = f.Dirent.Inode();
+        return inode.UnstableAttr(task);
+    }
+
+    fn Ioctl(&self, _task: &Task, _f: &File, _fd: i32, _request: u64, _val: u64) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTTY));
+    }
+
+    fn IterateDir(
+        &self,
+        _task: &Task,
+        _d: &Dirent,
+        _dirCtx: &mut DirCtx,
+        _offset: i32,
+    ) -> (i32, Result<i64>) {
+        return (0, Err(Error::SysError(SysErr::ENOTDIR)));
+    }
+
+    fn Mappable(&self) -> Result<MMappable> {
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+// The pidfd is readable once the process exits, see pidfd_open(2).
+impl Waitable for PidfdOperation {
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        if mask & READABLE_EVENT != 0 && self.Exited() {
+            return READABLE_EVENT;
+        }
+
+        return 0;
+    }
+
+    fn EventRegister(&self, task: &Task, e: &WaitEntry, mask: EventMask) {
+        let queue = self.tg.lock().exitQueue.clone();
+        queue.EventRegister(task, e, mask);
+    }
+
+    fn EventUnregister(&self, task: &Task, e: &WaitEntry) {
+        let queue = self.tg.lock().exitQueue.clone();
+        queue.EventUnregister(task, e);
+    }
+}
+
+impl SockOperations for PidfdOperation {}
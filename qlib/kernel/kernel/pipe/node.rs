@@ -309,8 +309,10 @@ impl InodeOperations for PipeIops {
             Type: FSMagic::PIPEFS_MAGIC,
             TotalBlocks: 0,
             FreeBlocks: 0,
+            AvailableBlocks: 0,
             TotalFiles: 0,
             FreeFiles: 0,
+            BlockSize: 0,
         });
     }
 
@@ -22,6 +22,7 @@ use super::super::super::linux::time::*;
 use super::super::super::linux_def::*;
 use super::super::threadmgr::thread::*;
 use super::super::SignalDef::*;
+use super::time_namespace::OffsetsInUse;
 use super::timer::timer;
 use super::timer::timer::*;
 
@@ -37,6 +38,7 @@ pub struct IntervalTimerInternal {
     pub id: TimerID,
     pub sigval: u64,
     pub group: bool,
+    pub clockId: i32,
 
     // If sigpending is true, a signal to target is already queued, and timer
     // expirations should increment overrunCur instead of sending another
@@ -224,7 +226,12 @@ fn saturateI32FromU64(x: u64) -> i32 {
 }
 
 impl Thread {
-    pub fn IntervalTimerCreate(&self, c: &Clock, sigev: &mut Sigevent) -> Result<TimerID> {
+    pub fn IntervalTimerCreate(
+        &self,
+        c: &Clock,
+        clockId: i32,
+        sigev: &mut Sigevent,
+    ) -> Result<TimerID> {
         let tg = self.lock().tg.clone();
         let timerMu = tg.TimerMu();
         let _tm = timerMu.lock();
@@ -262,6 +269,7 @@ impl Thread {
         //if sigev is none
 
         let it = IntervalTimer::New(id, sigev.Value);
+        it.lock().clockId = clockId;
 
         match sigev.Notify {
             SIGEV_NONE => (),
@@ -341,7 +349,13 @@ impl Thread {
 
         let timer = it.lock().timer.clone().unwrap();
         let clock = timer.Clock();
-        let newS = timer::Setting::FromItimerspec(its, abs, &clock)?;
+        let newS = if abs && OffsetsInUse() {
+            let clockId = it.lock().clockId;
+            let its = self.TimeNamespace().KernelItimerspec(clockId, its)?;
+            timer::Setting::FromItimerspec(&its, abs, &clock)?
+        } else {
+            timer::Setting::FromItimerspec(its, abs, &clock)?
+        };
 
         let (tm, oldS) = timer.SwapAnd(&newS, || {
             it.lock().timerSettingChanged();
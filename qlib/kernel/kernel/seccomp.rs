@@ -0,0 +1,695 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::vec_deque::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+use super::super::super::bpf::*;
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::super::mutex::*;
+use super::super::super::SysCallID;
+use super::super::fs::anon::*;
+use super::super::fs::attr::*;
+use super::super::fs::dentry::*;
+use super::super::fs::dirent::*;
+use super::super::fs::file::*;
+use super::super::fs::flags::*;
+use super::super::fs::host::hostinodeop::*;
+use super::super::task::*;
+use super::super::threadmgr::thread::*;
+use super::fd_table::*;
+use super::waiter::*;
+
+// The seccomp filters of the tasks, see seccomp(2) and seccomp_unotify(2).
+// A filter is a cBPF program over the seccomp_data of a syscall, run at the
+// entry of each syscall of the tasks which installed it and of their
+// children. A filter installed with SECCOMP_FILTER_FLAG_NEW_LISTENER comes
+// with a listener fd: a SECCOMP_RET_USER_NOTIF of the filter blocks the
+// syscall until the supervisor holding the fd reads the notification and
+// sends the result of the syscall, or lets it run with
+// SECCOMP_USER_NOTIF_FLAG_CONTINUE.
+
+pub const SECCOMP_MODE_NONE: i32 = 0;
+pub const SECCOMP_MODE_STRICT: i32 = 1;
+pub const SECCOMP_MODE_FILTER: i32 = 2;
+
+pub const SECCOMP_SET_MODE_STRICT: u64 = 0;
+pub const SECCOMP_SET_MODE_FILTER: u64 = 1;
+pub const SECCOMP_GET_ACTION_AVAIL: u64 = 2;
+pub const SECCOMP_GET_NOTIF_SIZES: u64 = 3;
+
+pub const SECCOMP_FILTER_FLAG_TSYNC: u64 = 1 << 0;
+pub const SECCOMP_FILTER_FLAG_LOG: u64 = 1 << 1;
+pub const SECCOMP_FILTER_FLAG_SPEC_ALLOW: u64 = 1 << 2;
+pub const SECCOMP_FILTER_FLAG_NEW_LISTENER: u64 = 1 << 3;
+pub const SECCOMP_FILTER_FLAG_TSYNC_ESRCH: u64 = 1 << 4;
+
+pub const SECCOMP_RET_KILL_PROCESS: u32 = 0x80000000;
+pub const SECCOMP_RET_KILL_THREAD: u32 = 0x00000000;
+pub const SECCOMP_RET_TRAP: u32 = 0x00030000;
+pub const SECCOMP_RET_ERRNO: u32 = 0x00050000;
+pub const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc00000;
+pub const SECCOMP_RET_TRACE: u32 = 0x7ff00000;
+pub const SECCOMP_RET_LOG: u32 = 0x7ffc0000;
+pub const SECCOMP_RET_ALLOW: u32 = 0x7fff0000;
+
+pub const SECCOMP_RET_ACTION_FULL: u32 = 0xffff0000;
+pub const SECCOMP_RET_DATA: u32 = 0x0000ffff;
+
+// the errno of SECCOMP_RET_ERRNO is clamped to MAX_ERRNO as on Linux
+pub const MAX_ERRNO: u32 = 4095;
+
+pub const SECCOMP_USER_NOTIF_FLAG_CONTINUE: u32 = 1 << 0;
+
+pub const SECCOMP_ADDFD_FLAG_SETFD: u32 = 1 << 0;
+pub const SECCOMP_ADDFD_FLAG_SEND: u32 = 1 << 1;
+
+pub const SECCOMP_IOCTL_NOTIF_RECV: u64 = 0xc0502100;
+pub const SECCOMP_IOCTL_NOTIF_SEND: u64 = 0xc0182101;
+pub const SECCOMP_IOCTL_NOTIF_ID_VALID: u64 = 0x40082102;
+pub const SECCOMP_IOCTL_NOTIF_ADDFD: u64 = 0x40182103;
+
+pub const AUDIT_ARCH_X86_64: u32 = 0xc000003e;
+
+// the instructions of all the filters of a task, MAX_INSNS_PER_PATH in Linux
+pub const SECCOMP_MAX_INSNS: usize = 32768;
+
+static SECCOMP_IN_USE: AtomicBool = AtomicBool::new(false);
+
+// SeccompInUse returns whether a task of the sandbox has installed a filter,
+// the syscalls skip the check until then.
+pub fn SeccompInUse() -> bool {
+    return SECCOMP_IN_USE.load(Ordering::Relaxed);
+}
+
+// SeccompData is struct seccomp_data.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SeccompData {
+    pub Nr: i32,
+    pub Arch: u32,
+    pub InstructionPointer: u64,
+    pub Args: [u64; 6],
+}
+
+impl SeccompData {
+    pub fn AsBytes(&self) -> &[u8] {
+        let addr = self as *const _ as *const u8;
+        return unsafe { core::slice::from_raw_parts(addr, core::mem::size_of::<Self>()) };
+    }
+}
+
+// SeccompNotif is struct seccomp_notif.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SeccompNotif {
+    pub Id: u64,
+    pub Pid: u32,
+    pub Flags: u32,
+    pub Data: SeccompData,
+}
+
+impl SeccompNotif {
+    pub fn AsBytes(&self) -> &[u8] {
+        let addr = self as *const _ as *const u8;
+        return unsafe { core::slice::from_raw_parts(addr, core::mem::size_of::<Self>()) };
+    }
+}
+
+// SeccompNotifResp is struct seccomp_notif_resp.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct SeccompNotifResp {
+    pub Id: u64,
+    pub Val: i64,
+    pub Error: i32,
+    pub Flags: u32,
+}
+
+// SeccompNotifAddfd is struct seccomp_notif_addfd.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SeccompNotifAddfd {
+    pub Id: u64,
+    pub Flags: u32,
+    pub Srcfd: u32,
+    pub Newfd: u32,
+    pub NewfdFlags: u32,
+}
+
+// SeccompNotifSizes is struct seccomp_notif_sizes.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SeccompNotifSizes {
+    pub Notif: u16,
+    pub Resp: u16,
+    pub Data: u16,
+}
+
+// ActionPrecedence orders the actions as Linux does, the lower the stronger.
+pub fn ActionPrecedence(ret: u32) -> i32 {
+    return (ret & SECCOMP_RET_ACTION_FULL) as i32;
+}
+
+pub fn ActionAvailable(action: u32) -> bool {
+    match action {
+        SECCOMP_RET_KILL_PROCESS
+        | SECCOMP_RET_KILL_THREAD
+        | SECCOMP_RET_TRAP
+        | SECCOMP_RET_ERRNO
+        | SECCOMP_RET_USER_NOTIF
+        | SECCOMP_RET_TRACE
+        | SECCOMP_RET_LOG
+        | SECCOMP_RET_ALLOW => return true,
+        _ => return false,
+    }
+}
+
+pub struct SeccompFilter {
+    pub prog: Vec<BPFInstruction>,
+    // the filter installed before this one, the chain is shared with the
+    // children of the task
+    pub prev: Option<Arc<SeccompFilter>>,
+    // the instructions of the chain up to this filter
+    pub insns: usize,
+    // the filter of the strict mode
+    pub strict: bool,
+    pub notifier: Option<Arc<SeccompNotifier>>,
+}
+
+impl SeccompFilter {
+    pub fn New(
+        prog: Vec<BPFInstruction>,
+        prev: Option<Arc<SeccompFilter>>,
+        strict: bool,
+        notifier: Option<Arc<SeccompNotifier>>,
+    ) -> Result<Arc<Self>> {
+        let insns = prog.len() + prev.as_ref().map(|p| p.insns + 4).unwrap_or(0);
+        if insns > SECCOMP_MAX_INSNS {
+            return Err(Error::SysError(SysErr::ENOMEM));
+        }
+
+        SECCOMP_IN_USE.store(true, Ordering::SeqCst);
+        return Ok(Arc::new(Self {
+            prog: prog,
+            prev: prev,
+            insns: insns,
+            strict: strict,
+            notifier: notifier,
+        }));
+    }
+
+    // StrictProgram allows read, write, exit and rt_sigreturn only.
+    pub fn StrictProgram() -> Vec<BPFInstruction> {
+        let allowed = [
+            SysCallID::sys_read,
+            SysCallID::sys_write,
+            SysCallID::sys_exit,
+            SysCallID::sys_rt_sigreturn,
+        ];
+
+        let mut prog = vec![BPFInstruction::Stmt(LD | W | ABS, 0)];
+        for (i, nr) in allowed.iter().enumerate() {
+            let skip = (allowed.len() - i) as u8;
+            prog.push(BPFInstruction::Jump(JMP | JEQ | K, *nr as u32, skip, 0));
+        }
+        prog.push(BPFInstruction::Stmt(RET | K, SECCOMP_RET_KILL_THREAD));
+        prog.push(BPFInstruction::Stmt(RET | K, SECCOMP_RET_ALLOW));
+        return prog;
+    }
+
+    // Evaluate runs the filters of the chain from the last installed one and
+    // returns the strongest action, with the filter returning it.
+    pub fn Evaluate(this: &Arc<Self>, data: &SeccompData) -> (u32, Arc<SeccompFilter>) {
+        let bytes = data.AsBytes();
+        let mut ret = SECCOMP_RET_ALLOW;
+        let mut matched = this.clone();
+        let mut filter = Some(this.clone());
+        while let Some(f) = filter {
+            let cur = ExecNative(&f.prog, bytes);
+            if ActionPrecedence(cur) < ActionPrecedence(ret) {
+                ret = cur;
+                matched = f.clone();
+            }
+            filter = f.prev.clone();
+        }
+
+        return (ret, matched);
+    }
+
+    // IsAncestor returns whether the chain of this filter contains other, the
+    // empty chain is an ancestor of any chain.
+    pub fn IsAncestor(this: &Option<Arc<Self>>, other: &Option<Arc<Self>>) -> bool {
+        let other = match other {
+            None => return true,
+            Some(o) => o,
+        };
+
+        let mut filter = this.clone();
+        while let Some(f) = filter {
+            if Arc::ptr_eq(&f, other) {
+                return true;
+            }
+            filter = f.prev.clone();
+        }
+
+        return false;
+    }
+
+    pub fn HasListener(this: &Option<Arc<Self>>) -> bool {
+        let mut filter = this.clone();
+        while let Some(f) = filter {
+            if f.notifier.is_some() {
+                return true;
+            }
+            filter = f.prev.clone();
+        }
+
+        return false;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifState {
+    // the notification is not read by the supervisor yet
+    Init,
+    Sent,
+    Replied(SeccompNotifResp),
+}
+
+pub struct SeccompNotification {
+    pub id: u64,
+    pub thread: Thread,
+    // the fd table of the task, for SECCOMP_IOCTL_NOTIF_ADDFD
+    pub fdTbl: FDTable,
+    pub data: SeccompData,
+    pub state: QMutex<NotifState>,
+    pub queue: Queue,
+}
+
+#[derive(Default)]
+pub struct SeccompNotifierInternal {
+    pub nextId: u64,
+    pub notifs: VecDeque<Arc<SeccompNotification>>,
+    // the listener fd is closed, the notifications fail with ENOSYS
+    pub closed: bool,
+}
+
+#[derive(Default)]
+pub struct SeccompNotifier {
+    pub intern: QMutex<SeccompNotifierInternal>,
+    pub queue: Queue,
+}
+
+impl SeccompNotifier {
+    fn Find(&self, id: u64) -> Option<Arc<SeccompNotification>> {
+        return self
+            .intern
+            .lock()
+            .notifs
+            .iter()
+            .find(|n| n.id == id)
+            .cloned();
+    }
+
+    fn Remove(&self, id: u64) {
+        self.intern.lock().notifs.retain(|n| n.id != id);
+        self.queue.Notify(WRITEABLE_EVENT);
+    }
+
+    // Notify queues the syscall of the task to the supervisor and waits for
+    // the response.
+    pub fn Notify(&self, task: &Task, data: &SeccompData) -> Result<SeccompNotifResp> {
+        let notif = {
+            let mut intern = self.intern.lock();
+            if intern.closed {
+                return Err(Error::SysError(SysErr::ENOSYS));
+            }
+
+            let notif = Arc::new(SeccompNotification {
+                id: intern.nextId,
+                thread: task.Thread(),
+                fdTbl: task.fdTbl.clone(),
+                data: *data,
+                state: QMutex::new(NotifState::Init),
+                queue: Queue::default(),
+            });
+            intern.nextId += 1;
+            intern.notifs.push_back(notif.clone());
+            notif
+        };
+        self.queue.Notify(READABLE_EVENT);
+
+        let general = task.blocker.generalEntry.clone();
+        notif.queue.EventRegister(task, &general, EVENT_IN);
+        defer!(notif.queue.EventUnregister(task, &general));
+
+        loop {
+            let state = *notif.state.lock();
+            if let NotifState::Replied(resp) = state {
+                self.Remove(notif.id);
+                return Ok(resp);
+            }
+
+            if self.intern.lock().closed {
+                return Err(Error::SysError(SysErr::ENOSYS));
+            }
+
+            match task.blocker.BlockWithMonoTimer(true, None) {
+                Err(Error::ErrInterrupted) => {
+                    // the syscall is notified again once restarted
+                    self.Remove(notif.id);
+                    return Err(Error::SysError(SysErr::ERESTARTSYS));
+                }
+                Err(e) => {
+                    self.Remove(notif.id);
+                    return Err(e);
+                }
+                Ok(()) => (),
+            }
+        }
+    }
+
+    // Recv returns the first notification not read yet, it blocks until there
+    // is one as in Linux, whatever the O_NONBLOCK of the listener.
+    pub fn Recv(&self, task: &Task) -> Result<SeccompNotif> {
+        let general = task.blocker.generalEntry.clone();
+        self.queue.EventRegister(task, &general, EVENT_IN);
+        defer!(self.queue.EventUnregister(task, &general));
+
+        loop {
+            let notif = self
+                .intern
+                .lock()
+                .notifs
+                .iter()
+                .find(|n| *n.state.lock() == NotifState::Init)
+                .cloned();
+            if let Some(notif) = notif {
+                *notif.state.lock() = NotifState::Sent;
+                let pid = task.Thread().PIDNamespace().IDOfTask(&notif.thread);
+                return Ok(SeccompNotif {
+                    Id: notif.id,
+                    Pid: pid as u32,
+                    Flags: 0,
+                    Data: notif.data,
+                });
+            }
+
+            match task.blocker.BlockWithMonoTimer(true, None) {
+                Err(Error::ErrInterrupted) => return Err(Error::SysError(SysErr::EINTR)),
+                Err(e) => return Err(e),
+                Ok(()) => (),
+            }
+        }
+    }
+
+    // Sent returns the notification of id read by the supervisor.
+    fn Sent(&self, id: u64) -> Result<Arc<SeccompNotification>> {
+        let notif = match self.Find(id) {
+            None => return Err(Error::SysError(SysErr::ENOENT)),
+            Some(n) => n,
+        };
+
+        match *notif.state.lock() {
+            NotifState::Init => return Err(Error::SysError(SysErr::EINPROGRESS)),
+            NotifState::Sent => return Ok(notif.clone()),
+            NotifState::Replied(_) => return Err(Error::SysError(SysErr::ENOENT)),
+        }
+    }
+
+    pub fn Send(&self, resp: &SeccompNotifResp) -> Result<()> {
+        if resp.Flags & !SECCOMP_USER_NOTIF_FLAG_CONTINUE != 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        if resp.Flags & SECCOMP_USER_NOTIF_FLAG_CONTINUE != 0 && (resp.Error != 0 || resp.Val != 0)
+        {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let notif = self.Sent(resp.Id)?;
+        *notif.state.lock() = NotifState::Replied(*resp);
+        notif.queue.Notify(EVENT_IN);
+        return Ok(());
+    }
+
+    pub fn IdValid(&self, id: u64) -> Result<()> {
+        match self.Find(id) {
+            Some(n) if *n.state.lock() == NotifState::Sent => return Ok(()),
+            _ => return Err(Error::SysError(SysErr::ENOENT)),
+        }
+    }
+
+    // AddFd installs the file srcfd of the supervisor in the fd table of the
+    // task of the notification and returns its fd there.
+    pub fn AddFd(&self, task: &Task, addfd: &SeccompNotifAddfd) -> Result<i32> {
+        if addfd.Flags & !(SECCOMP_ADDFD_FLAG_SETFD | SECCOMP_ADDFD_FLAG_SEND) != 0
+            || addfd.NewfdFlags & !(Flags::O_CLOEXEC as u32) != 0
+            || (addfd.Flags & SECCOMP_ADDFD_FLAG_SETFD == 0 && addfd.Newfd != 0)
+        {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let file = task.GetFile(addfd.Srcfd as i32)?;
+        let notif = self.Sent(addfd.Id)?;
+        let flags = FDFlags {
+            CloseOnExec: addfd.NewfdFlags & Flags::O_CLOEXEC as u32 != 0,
+        };
+
+        let fd = if addfd.Flags & SECCOMP_ADDFD_FLAG_SETFD != 0 {
+            notif
+                .fdTbl
+                .NewFDAt(task, addfd.Newfd as i32, &file, &flags)?;
+            addfd.Newfd as i32
+        } else {
+            notif.fdTbl.NewFDFrom(task, 0, &file, &flags)?
+        };
+
+        if addfd.Flags & SECCOMP_ADDFD_FLAG_SEND != 0 {
+            *notif.state.lock() = NotifState::Replied(SeccompNotifResp {
+                Id: addfd.Id,
+                Val: fd as i64,
+                ..Default::default()
+            });
+            notif.queue.Notify(EVENT_IN);
+        }
+
+        return Ok(fd);
+    }
+
+    pub fn Close(&self) {
+        let notifs: Vec<Arc<SeccompNotification>> = {
+            let mut intern = self.intern.lock();
+            intern.closed = true;
+            intern.notifs.iter().cloned().collect()
+        };
+
+        for n in notifs {
+            n.queue.Notify(EVENT_IN);
+        }
+    }
+
+    pub fn Events(&self) -> EventMask {
+        let intern = self.intern.lock();
+        let mut events = 0;
+        for n in &intern.notifs {
+            match *n.state.lock() {
+                NotifState::Init => events |= READABLE_EVENT,
+                NotifState::Sent => events |= WRITEABLE_EVENT,
+                _ => (),
+            }
+        }
+
+        return events;
+    }
+}
+
+// SeccompListener is the file of the listener fd, the notifications fail
+// once it is closed.
+pub struct SeccompListener {
+    pub notifier: Arc<SeccompNotifier>,
+}
+
+impl Drop for SeccompListener {
+    fn drop(&mut self) {
+        self.notifier.Close();
+    }
+}
+
+impl SeccompListener {
+    pub fn NewFile(task: &Task, notifier: &Arc<SeccompNotifier>) -> File {
+        // name matches kernel/seccomp.c:init_listener.
+        let inode = NewAnonInode(task);
+        let dirent = Dirent::New(&inode, "anon_inode:seccomp notify");
+
+        let fops = Self {
+            notifier: notifier.clone(),
+        };
+        return File::New(
+            &dirent,
+            &FileFlags {
+                Read: true,
+                Write: true,
+                ..Default::default()
+            },
+            fops,
+        );
+    }
+}
+
+impl SpliceOperations for SeccompListener {}
+
+impl FileOperations for SeccompListener {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn FopsType(&self) -> FileOpsType {
+        return FileOpsType::SeccompListener;
+    }
+
+    fn Seekable(&self) -> bool {
+        return false;
+    }
+
+    fn Seek(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _whence: i32,
+        _current: i64,
+        _offset: i64,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ESPIPE));
+    }
+
+    fn ReadDir(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _offset: i64,
+        _serializer: &mut DentrySerializer,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn ReadAt(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _dsts: &mut [IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn WriteAt(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _srcs: &[IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn Append(&self, _task: &Task, _f: &File, _srcs: &[IoVec]) -> Result<(i64, i64)> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn Fsync(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _start: i64,
+        _end: i64,
+        _syncType: SyncType,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn Flush(&self, _task: &Task, _f: &File) -> Result<()> {
+        return Ok(());
+    }
+
+    fn UnstableAttr(&self, task: &Task, f: &File) -> Result<UnstableAttr> {
+        let inode = f.Dirent.Inode();
+        return inode.UnstableAttr(task);
+    }
+
+    // SECCOMP_IOCTL_NOTIF_ADDFD returns an fd, it is served by SysIoctl.
+    fn Ioctl(&self, task: &Task, _f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
+        match request {
+            SECCOMP_IOCTL_NOTIF_RECV => {
+                let notif: SeccompNotif = task.CopyInObj(val)?;
+                if notif.AsBytes().iter().any(|b| *b != 0) {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+
+                let notif = self.notifier.Recv(task)?;
+                task.CopyOutObj(&notif, val)?;
+                return Ok(());
+            }
+            SECCOMP_IOCTL_NOTIF_SEND => {
+                let resp: SeccompNotifResp = task.CopyInObj(val)?;
+                return self.notifier.Send(&resp);
+            }
+            SECCOMP_IOCTL_NOTIF_ID_VALID => {
+                let id: u64 = task.CopyInObj(val)?;
+                return self.notifier.IdValid(id);
+            }
+            _ => return Err(Error::SysError(SysErr::ENOTTY)),
+        }
+    }
+
+    fn IterateDir(
+        &self,
+        _task: &Task,
+        _d: &Dirent,
+        _dirCtx: &mut DirCtx,
+        _offset: i32,
+    ) -> (i32, Result<i64>) {
+        return (0, Err(Error::SysError(SysErr::ENOTDIR)));
+    }
+
+    fn Mappable(&self) -> Result<MMappable> {
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+// The listener is readable with a notification to read and writable with one
+// to respond to.
+impl Waitable for SeccompListener {
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        return self.notifier.Events() & mask;
+    }
+
+    fn EventRegister(&self, task: &Task, e: &WaitEntry, mask: EventMask) {
+        self.notifier.queue.EventRegister(task, e, mask);
+    }
+
+    fn EventUnregister(&self, task: &Task, e: &WaitEntry) {
+        self.notifier.queue.EventUnregister(task, e);
+    }
+}
+
+impl SockOperations for SeccompListener {}
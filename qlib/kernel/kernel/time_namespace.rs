@@ -0,0 +1,186 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::sync::Arc;
+use core::ops::Deref;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+use super::super::super::auth::userns::*;
+use super::super::super::common::*;
+use super::super::super::linux::time::*;
+use super::super::super::linux_def::*;
+use super::timer::*;
+
+// Time namespace. It gives the monotonic and the boottime clocks of its tasks
+// an offset, the offsets can only be set before the first task enters the
+// namespace. The vdso reads the clocks from the param page shared by all the
+// processes, so once a namespace has an offset the monotonic clock of the vdso
+// is turned off and clock_gettime goes through the syscall, which adds the
+// offset of the caller.
+static OFFSETS_IN_USE: AtomicBool = AtomicBool::new(false);
+
+// OffsetsInUse returns whether a time namespace of the sandbox has an offset.
+pub fn OffsetsInUse() -> bool {
+    return OFFSETS_IN_USE.load(Ordering::Relaxed);
+}
+
+fn SetOffsetsInUse() {
+    if !OFFSETS_IN_USE.swap(true, Ordering::SeqCst) {
+        info!("time namespace: offsets in use, the vdso monotonic clock is off");
+        // the boot sets the offsets of the root namespace before the time
+        // keeper is up, its first update sees the flag
+        let mut tk = TIME_KEEPER.write();
+        if tk.inited {
+            tk.Update();
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TimeNamespaceInternal {
+    pub monotonicOffset: i64,
+    pub boottimeOffset: i64,
+
+    // a task has entered the namespace, the offsets are fixed
+    pub entered: bool,
+
+    pub userns: UserNameSpace,
+}
+
+#[derive(Clone, Default)]
+pub struct TimeNamespace(Arc<QMutex<TimeNamespaceInternal>>);
+
+impl Deref for TimeNamespace {
+    type Target = Arc<QMutex<TimeNamespaceInternal>>;
+
+    fn deref(&self) -> &Arc<QMutex<TimeNamespaceInternal>> {
+        &self.0
+    }
+}
+
+impl PartialEq for TimeNamespace {
+    fn eq(&self, other: &Self) -> bool {
+        return Arc::ptr_eq(&self.0, &other.0);
+    }
+}
+
+impl TimeNamespace {
+    pub fn New(userns: &UserNameSpace, monotonicOffset: i64, boottimeOffset: i64) -> Self {
+        let internal = TimeNamespaceInternal {
+            monotonicOffset: monotonicOffset,
+            boottimeOffset: boottimeOffset,
+            entered: false,
+            userns: userns.clone(),
+        };
+
+        if monotonicOffset != 0 || boottimeOffset != 0 {
+            SetOffsetsInUse();
+        }
+
+        return Self(Arc::new(QMutex::new(internal)));
+    }
+
+    // Fork returns a child namespace, it starts with the offsets of the parent.
+    pub fn Fork(&self, userns: &UserNameSpace) -> Self {
+        let me = self.lock();
+        let internal = TimeNamespaceInternal {
+            monotonicOffset: me.monotonicOffset,
+            boottimeOffset: me.boottimeOffset,
+            entered: false,
+            userns: userns.clone(),
+        };
+
+        return Self(Arc::new(QMutex::new(internal)));
+    }
+
+    pub fn UserNamespace(&self) -> UserNameSpace {
+        return self.lock().userns.clone();
+    }
+
+    // Enter is called when a task joins the namespace.
+    pub fn Enter(&self) {
+        self.lock().entered = true;
+    }
+
+    pub fn MonotonicOffset(&self) -> i64 {
+        return self.lock().monotonicOffset;
+    }
+
+    pub fn BoottimeOffset(&self) -> i64 {
+        return self.lock().boottimeOffset;
+    }
+
+    // Offset returns the offset of the clock in the namespace.
+    pub fn Offset(&self, clockId: i32) -> i64 {
+        match clockId {
+            CLOCK_MONOTONIC | CLOCK_MONOTONIC_COARSE | CLOCK_MONOTONIC_RAW => {
+                return self.MonotonicOffset()
+            }
+            CLOCK_BOOTTIME | CLOCK_BOOTTIME_ALARM => return self.BoottimeOffset(),
+            _ => return 0,
+        }
+    }
+
+    // KernelTime converts an absolute time of the clock in the namespace to
+    // the time of the kernel clock, a time before the kernel clock started is
+    // the start of it.
+    pub fn KernelTime(&self, clockId: i32, t: i64) -> i64 {
+        let t = t.saturating_sub(self.Offset(clockId));
+        if t < 0 {
+            return 0;
+        }
+
+        return t;
+    }
+
+    // KernelItimerspec converts the absolute expiration of its to the kernel
+    // clock, a zero expiration disarms the timer and is kept.
+    pub fn KernelItimerspec(&self, clockId: i32, its: &Itimerspec) -> Result<Itimerspec> {
+        let mut its = *its;
+        let value = its.Value.ToNs()?;
+        if value != 0 {
+            let t = self.KernelTime(clockId, value);
+            its.Value = Timespec::FromNs(if t == 0 { 1 } else { t });
+        }
+
+        return Ok(its);
+    }
+
+    // SetOffset sets the offset of CLOCK_MONOTONIC or CLOCK_BOOTTIME. now is
+    // the current value of the clock without offset, it can't go negative.
+    pub fn SetOffset(&self, clockId: i32, offset: i64, now: i64) -> Result<()> {
+        let mut me = self.lock();
+        if me.entered {
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        if now.checked_add(offset).map_or(true, |t| t < 0) {
+            return Err(Error::SysError(SysErr::ERANGE));
+        }
+
+        match clockId {
+            CLOCK_MONOTONIC => me.monotonicOffset = offset,
+            CLOCK_BOOTTIME => me.boottimeOffset = offset,
+            _ => return Err(Error::SysError(SysErr::EINVAL)),
+        }
+
+        if offset != 0 {
+            SetOffsetsInUse();
+        }
+
+        return Ok(());
+    }
+}
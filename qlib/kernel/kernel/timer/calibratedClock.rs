@@ -21,6 +21,7 @@ use super::super::super::super::common::*;
 use super::super::super::super::linux::time::*;
 use super::super::super::super::linux_def::*;
 use super::super::super::super::metric::*;
+use super::super::super::super::seqlock::*;
 use super::super::super::super::singleton::*;
 use super::super::super::asm::muldiv64;
 use super::super::super::TSC;
@@ -100,14 +101,25 @@ impl CalibratedClockInternal {
     }
 }
 
+// ClockSnapshot is the part of the internal GetTime needs, published by the
+// writers of the internal so GetTime doesn't take the lock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ClockSnapshot {
+    pub ready: bool,
+    pub params: Parameters,
+}
+
 #[derive(Clone)]
-pub struct CalibratedClock(Arc<QRwLock<CalibratedClockInternal>>);
+pub struct CalibratedClock {
+    pub internal: Arc<QRwLock<CalibratedClockInternal>>,
+    pub snapshot: Arc<QSeqLock<ClockSnapshot>>,
+}
 
 impl Deref for CalibratedClock {
     type Target = Arc<QRwLock<CalibratedClockInternal>>;
 
     fn deref(&self) -> &Arc<QRwLock<CalibratedClockInternal>> {
-        &self.0
+        &self.internal
     }
 }
 
@@ -119,13 +131,34 @@ impl CalibratedClock {
             params: Parameters::default(),
             errorNS: 0,
         };
-        return Self(Arc::new(QRwLock::new(internal)));
+        return Self {
+            internal: Arc::new(QRwLock::new(internal)),
+            snapshot: Arc::new(QSeqLock::default()),
+        };
+    }
+
+    // Publish makes the changes of the internal visible to GetTime, with the
+    // write lock held.
+    fn Publish(&self, c: &CalibratedClockInternal) {
+        self.snapshot.Write(ClockSnapshot {
+            ready: c.ready,
+            params: c.params,
+        });
     }
 
     // reset forces the clock to restart the calibration process, logging the
     // passed message.
     fn reset(&self, str: &str) {
-        self.write().resetLocked(str);
+        let mut c = self.write();
+        c.resetLocked(str);
+        self.Publish(&c);
+    }
+
+    pub fn UpdateParams(&self, actual: &Parameters) -> bool {
+        let mut c = self.write();
+        let ok = c.updateParams(actual);
+        self.Publish(&c);
+        return ok;
     }
 
     // Update runs the update step of the clock, updating its synchronization with
@@ -139,7 +172,12 @@ impl CalibratedClock {
     // Update.
     pub fn Update(&self) -> (Parameters, bool) {
         let mut c = self.write();
+        let ret = Self::UpdateLocked(&mut c);
+        self.Publish(&c);
+        return ret;
+    }
 
+    fn UpdateLocked(c: &mut CalibratedClockInternal) -> (Parameters, bool) {
         let sample = c.sampler.Sample();
         match sample {
             Err(err) => {
@@ -196,21 +234,16 @@ impl CalibratedClock {
 
     // GetTime returns the current time based on the clock calibration.
     pub fn GetTime(&self) -> Result<i64> {
-        let now = {
-            let c = self.read();
-
-            if !c.ready {
-                let ret = c.sampler.Syscall();
-                return ret;
-            }
+        let snapshot = self.snapshot.Read();
+        if !snapshot.ready {
+            return self.read().sampler.Syscall();
+        }
 
-            let now = c.sampler.Cycles();
-            let (v, ok) = c.params.ComputeTime(now);
-            if ok {
-                return Ok(v);
-            }
-            now
-        };
+        let now = Cycles();
+        let (v, ok) = snapshot.params.ComputeTime(now);
+        if ok {
+            return Ok(v);
+        }
 
         let mut c = self.write();
         // Something is seriously wrong with the clock. Try
@@ -223,6 +256,7 @@ impl CalibratedClock {
             )
             .as_str(),
         );
+        self.Publish(&c);
         return c.sampler.Syscall();
     }
 }
@@ -275,8 +309,8 @@ impl CalibratedClocks {
             BaseCycles: tsc,
         };
 
-        let monotonicOk = self.monotonic.write().updateParams(&monotonicParams);
-        let realtimeOk = self.realtime.write().updateParams(&realtimeParams);
+        let monotonicOk = self.monotonic.UpdateParams(&monotonicParams);
+        let realtimeOk = self.realtime.UpdateParams(&realtimeParams);
 
         let monotonicParams = self.monotonic.read().params;
         let realtimeParams = self.realtime.read().params;
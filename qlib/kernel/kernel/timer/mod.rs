@@ -17,7 +17,9 @@ pub mod parameters;
 pub mod sampler;
 pub mod timekeeper;
 pub mod timer;
+pub mod timer_dispatch;
 pub mod timer_store;
+pub mod timer_wheel;
 
 //pub use self::raw_timer::*;
 
@@ -27,6 +29,7 @@ use self::timer_store::*;
 use super::super::super::object_ref::*;
 use super::super::super::singleton::*;
 use super::super::SHARESPACE;
+use super::loadavg::LOAD_AVG;
 
 pub static TIME_KEEPER: TimerKeeperRef = TimerKeeperRef::New();
 
@@ -47,6 +50,7 @@ pub struct TimerUpdater {}
 impl TimerListenerTrait for TimerUpdater {
     fn Notify(&self, _exp: u64) {
         TIME_KEEPER.write().Update();
+        LOAD_AVG.Update(MonotonicNow());
     }
 
     fn Destroy(&self) {}
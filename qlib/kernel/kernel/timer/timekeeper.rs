@@ -18,22 +18,50 @@ use core::ops::Deref;
 
 use super::super::super::super::common::*;
 use super::super::super::super::linux::time::*;
+use super::super::super::super::seqlock::*;
 use super::super::super::kernel::time::*;
 //use super::super::super::super::perf_tunning::*;
+use super::super::super::vcpu::TscAuxSupported;
+use super::super::time_namespace::OffsetsInUse;
 use super::super::vdso::*;
 use super::calibratedClock::*;
 use super::timer::Clock;
 use super::timer::*;
 use super::*;
 
-#[derive(Clone, Default)]
-pub struct TimeKeeper(Arc<QRwLock<TimeKeeperInternal>>);
+// TimeKeeperSnapshot is the part of the internal GetTime needs, it is set
+// once by Init.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimeKeeperSnapshot {
+    pub inited: bool,
+    pub monotonicOffset: i64,
+}
+
+#[derive(Clone)]
+pub struct TimeKeeper {
+    pub internal: Arc<QRwLock<TimeKeeperInternal>>,
+    // the clocks of the internal, GetTime reads them with the snapshot
+    // without the lock
+    pub clocks: CalibratedClocks,
+    pub snapshot: Arc<QSeqLock<TimeKeeperSnapshot>>,
+}
+
+impl Default for TimeKeeper {
+    fn default() -> Self {
+        let internal = TimeKeeperInternal::default();
+        return Self {
+            clocks: internal.clocks.clone(),
+            internal: Arc::new(QRwLock::new(internal)),
+            snapshot: Arc::new(QSeqLock::default()),
+        };
+    }
+}
 
 impl Deref for TimeKeeper {
     type Target = Arc<QRwLock<TimeKeeperInternal>>;
 
     fn deref(&self) -> &Arc<QRwLock<TimeKeeperInternal>> {
-        &self.0
+        &self.internal
     }
 }
 
@@ -42,6 +70,10 @@ impl TimeKeeper {
         {
             let mut internal = self.write();
             internal.Init(vdsoParamPageAddr);
+            self.snapshot.Write(TimeKeeperSnapshot {
+                inited: internal.inited,
+                monotonicOffset: internal.monotonicOffset,
+            });
         }
 
         let timer = Timer::Period(
@@ -74,7 +106,14 @@ impl TimeKeeper {
     }
 
     pub fn GetTime(&self, c: ClockID) -> Result<i64> {
-        return self.read().GetTime(c);
+        let snapshot = self.snapshot.Read();
+        assert!(snapshot.inited, "TimeKeeper not inited");
+        let mut now = self.clocks.GetTime(c)?;
+        if c == MONOTONIC {
+            now += snapshot.monotonicOffset;
+        }
+
+        return Ok(now);
     }
 
     pub fn BootTime(&self) -> Time {
@@ -158,7 +197,9 @@ impl TimeKeeperInternal {
 
         let mut p = VdsoParams::default();
         if monotonicOk {
-            p.monotonicReady = 1;
+            // with time namespace offsets the vdso can't return the monotonic
+            // clock of the caller, leave it to the syscall
+            p.monotonicReady = if OffsetsInUse() { 0 } else { 1 };
             p.monotonicBaseCycles = monotonicParams.BaseCycles;
             p.monotonicBaseRef = monotonicParams.BaseRef + self.monotonicOffset;
             p.monotonicFrequency = monotonicParams.Frequency;
@@ -173,6 +214,8 @@ impl TimeKeeperInternal {
             p.realtimeFrequency = realtimeParams.Frequency;
         }
 
+        p.getcpuReady = if TscAuxSupported() { 1 } else { 0 };
+
         match self.params.Write(&p) {
             Err(err) => info!("Unable to update VDSO parameter page: {:?}", err),
             _ => (),
@@ -324,6 +324,11 @@ pub struct TimerInternal {
     pub Id: u64,
     pub Expire: i64,
     pub State: TimerState,
+    // the generation of its entry in the timer wheel, 0 if it is not there
+    pub Wheel: u64,
+    // bumped when the timer is reset or stopped, the dispatch stamps it on
+    // the queued timer to tell a stale one
+    pub Seq: u64,
 }
 
 impl Default for TimerInternal {
@@ -338,6 +343,8 @@ impl Default for TimerInternal {
             Id: id,
             State: TimerState::default(),
             Expire: 0,
+            Wheel: 0,
+            Seq: 0,
         };
     }
 }
@@ -354,6 +361,8 @@ impl TimerInternal {
             Id: id,
             State: TimerState::default(),
             Expire: 0,
+            Wheel: 0,
+            Seq: 0,
         };
 
         return ret;
@@ -411,6 +420,26 @@ impl Timer {
         return ret;
     }
 
+    // Test returns a stopped timer out of the timer store with the id and the
+    // expire, it doesn't need the share space.
+    #[cfg(test)]
+    pub fn Test(id: u64, expire: i64) -> Self {
+        let internal = TimerInternal {
+            clock: Clock::Dummy,
+            listener: TimerListener::DummyTimerListener(DummyTimerListener {}),
+            setting: Setting::default(),
+            paused: true,
+
+            Id: id,
+            State: TimerState::Stopped,
+            Expire: expire,
+            Wheel: 0,
+            Seq: 0,
+        };
+
+        return Self(Arc::new(QMutex::new(internal)));
+    }
+
     fn Timeout(&self) -> i64 {
         let mut t = self.lock();
 
@@ -440,6 +469,8 @@ impl Timer {
             Id: id,
             State: TimerState::default(),
             Expire: 0,
+            Wheel: 0,
+            Seq: 0,
         };
 
         let mut res = Self(Arc::new(QMutex::new(internal)));
@@ -458,6 +489,8 @@ impl Timer {
             Id: id,
             State: TimerState::default(),
             Expire: 0,
+            Wheel: 0,
+            Seq: 0,
         };
 
         let mut res = Self(Arc::new(QMutex::new(internal)));
@@ -484,6 +517,8 @@ impl Timer {
             Id: id,
             State: TimerState::default(),
             Expire: 0,
+            Wheel: 0,
+            Seq: 0,
         };
 
         let mut res = Self(Arc::new(QMutex::new(internal)));
@@ -642,12 +677,16 @@ impl Timer {
     // It returns true if the call stops the timer, false if the timer has already
     // expired or been stopped.
     pub fn Stop(&self) -> bool {
-        let state = self.lock().State;
-        if state != TimerState::Running {
-            return false;
+        {
+            let mut t = self.lock();
+            if t.State != TimerState::Running {
+                return false;
+            }
+
+            t.State = TimerState::Stopped;
+            t.Seq += 1;
         }
 
-        self.lock().State = TimerState::Stopped;
         TIMER_STORE.CancelTimer(self);
         return true;
     }
@@ -662,18 +701,51 @@ impl Timer {
 
         assert!(timeout > 0, "Timer::Reset get negtive delta");
 
+        self.Rearm();
         TIMER_STORE.ResetTimer(self, timeout);
-        self.lock().State = TimerState::Running;
         return false;
     }
 
+    // Rearm marks the timer running before it is queued to the store, a
+    // dispatch of the queued timer stamps the new Seq.
+    fn Rearm(&self) {
+        let mut t = self.lock();
+        t.State = TimerState::Running;
+        t.Seq += 1;
+    }
+
     pub fn Fire(&self, ts: &mut TimerStoreIntern) {
         self.lock().State = TimerState::Expired;
 
         let delta = self.Timeout();
         if delta > 0 {
+            self.Rearm();
             ts.ResetTimer(self, delta);
-            self.lock().State = TimerState::Running;
+        }
+    }
+
+    // DispatchSeq returns the stamp of the timer queued by the dispatch.
+    pub fn DispatchSeq(&self) -> u64 {
+        return self.lock().Seq;
+    }
+
+    // FireDispatched fires the timer queued to a vcpu by the dispatch with
+    // the stamp seq, out of the lock of the store. A timer reset or stopped
+    // since it was queued isn't fired.
+    pub fn FireDispatched(&self, seq: u64) {
+        {
+            let mut t = self.lock();
+            if t.State != TimerState::Running || t.Seq != seq {
+                return;
+            }
+
+            t.State = TimerState::Expired;
+        }
+
+        let delta = self.Timeout();
+        if delta > 0 {
+            self.Rearm();
+            TIMER_STORE.ResetTimer(self, delta);
         }
     }
 
@@ -0,0 +1,193 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+use cache_padded::CachePadded;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::ringbuf::*;
+use super::super::super::super::task_mgr::*;
+use super::super::super::super::vcpu_mgr::*;
+use super::timer::*;
+
+// The dispatch of the timer expirations. The due timers are fired by the
+// thread calling TimerStore::Trigger, mostly the io thread, under the lock
+// of the store, and a storm of expirations, e.g. the timeouts of thousands of
+// connections, holds up the thread and the vcpus resetting their timers
+// behind the lock. With the dispatch, Trigger pops the due timers in the
+// order of their deadlines and queues each to the least loaded vcpu not
+// running a task, by its ready tasks and queued timers, which fires them in
+// the same order when it is idle. The vcpus and their loads are taken once
+// per Trigger, and each vcpu which got timers is woken up once at its end.
+// A timer is fired inline when all the vcpus are busy or their queues are
+// full, and the timers left to a vcpu which has got a task since are fired by
+// the io thread. Vcpu 0 is the one of the global queue and isn't dispatched
+// to.
+pub const TIMER_DISPATCH_QUEUE_SIZE: usize = 1024;
+
+// DispatchBatch is the dispatch of the due timers of a TimerStore::Trigger,
+// the vcpus are chosen once for the batch and woken up once at its end.
+#[derive(Default)]
+pub struct DispatchBatch {
+    // the vcpus not running a task at the first due timer, None before it
+    pub vcpus: Option<Vec<DispatchVcpu>>,
+}
+
+pub struct DispatchVcpu {
+    pub vcpuId: usize,
+    // the load of the vcpu with the timers of the batch, u64::MAX if its
+    // queue is full
+    pub load: u64,
+    pub queued: bool,
+}
+
+#[derive(Default)]
+pub struct TimerDispatch {
+    // the expiration queue of each vcpu, empty when the dispatch is off, of
+    // the timers with their stamps at the dispatch
+    pub queues: Vec<CachePadded<QRingQueue<(Timer, u64)>>>,
+    pub dispatched: AtomicU64,
+    pub inline: AtomicU64,
+}
+
+impl TimerDispatch {
+    pub fn New(vcpuCount: usize) -> Self {
+        let mut queues = Vec::with_capacity(vcpuCount);
+        for _i in 0..vcpuCount {
+            queues.push(CachePadded::new(QRingQueue::New(TIMER_DISPATCH_QUEUE_SIZE)));
+        }
+
+        return Self {
+            queues: queues,
+            ..Default::default()
+        };
+    }
+
+    pub fn Enabled(&self) -> bool {
+        return self.queues.len() > 2;
+    }
+
+    // Batch returns the vcpus not running a task with their loads, by their
+    // ready tasks and queued timers.
+    fn Batch(&self, scheduler: &Scheduler) -> Vec<DispatchVcpu> {
+        let mut vcpus = Vec::new();
+        for vcpuId in 1..self.queues.len() {
+            if scheduler.VcpuArr[vcpuId].State() == VcpuState::Running {
+                continue;
+            }
+
+            vcpus.push(DispatchVcpu {
+                vcpuId: vcpuId,
+                load: scheduler.ReadyTaskCnt(vcpuId) + self.queues[vcpuId].CountLockless() as u64,
+                queued: false,
+            });
+        }
+
+        return vcpus;
+    }
+
+    // Dispatch queues the due timer to the least loaded vcpu of the batch, it
+    // returns false if the caller has to fire it. The vcpus are woken up by
+    // Wake at the end of the batch.
+    pub fn Dispatch(
+        &self,
+        scheduler: &Scheduler,
+        batch: &mut DispatchBatch,
+        timer: &Timer,
+    ) -> bool {
+        if !self.Enabled() {
+            return false;
+        }
+
+        if batch.vcpus.is_none() {
+            batch.vcpus = Some(self.Batch(scheduler));
+        }
+
+        let vcpus = batch.vcpus.as_mut().unwrap();
+        loop {
+            let vcpu = match vcpus
+                .iter_mut()
+                .filter(|v| v.load != u64::MAX)
+                .min_by_key(|v| v.load)
+            {
+                None => {
+                    self.inline.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+                Some(v) => v,
+            };
+
+            if self.queues[vcpu.vcpuId]
+                .Push(&(timer.clone(), timer.DispatchSeq()))
+                .is_err()
+            {
+                // the queue is full, the vcpu is left out of the batch
+                vcpu.load = u64::MAX;
+                continue;
+            }
+
+            vcpu.load += 1;
+            vcpu.queued = true;
+            self.dispatched.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+    }
+
+    // Wake wakes up once each vcpu the batch has queued timers to.
+    pub fn Wake(&self, scheduler: &Scheduler, batch: &DispatchBatch) {
+        let vcpus = match &batch.vcpus {
+            None => return,
+            Some(vcpus) => vcpus,
+        };
+
+        for v in vcpus {
+            if v.queued {
+                scheduler.WakeIdleCPU(v.vcpuId);
+            }
+        }
+    }
+
+    // Process fires the timers dispatched to the vcpu.
+    pub fn Process(&self, vcpuId: usize) -> usize {
+        if !self.Enabled() {
+            return 0;
+        }
+
+        let mut count = 0;
+        while let Some((timer, seq)) = self.queues[vcpuId].TryPop() {
+            timer.FireDispatched(seq);
+            count += 1;
+        }
+
+        return count;
+    }
+
+    // ProcessBusy fires the timers left to the vcpus which have got a task
+    // since they were dispatched.
+    pub fn ProcessBusy(&self, scheduler: &Scheduler) -> usize {
+        if !self.Enabled() {
+            return 0;
+        }
+
+        let mut count = 0;
+        for vcpuId in 1..self.queues.len() {
+            if scheduler.VcpuArr[vcpuId].State() == VcpuState::Running {
+                count += self.Process(vcpuId);
+            }
+        }
+
+        return count;
+    }
+}
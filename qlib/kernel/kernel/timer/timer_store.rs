@@ -20,7 +20,10 @@ use core::cmp::Ordering;
 use core::ops::Deref;
 
 use super::super::super::IOURING;
+use super::super::super::SHARESPACE;
 use super::timer::*;
+use super::timer_dispatch::*;
+use super::timer_wheel::*;
 use super::*;
 
 #[derive(Debug, Copy, Clone)]
@@ -85,33 +88,60 @@ impl TimerStore {
         ts.RemoveTimer(timer);
         ts.Trigger();
     }
+
+    // Hold holds the firing of the due timers, or releases it. The host holds
+    // them after an external freeze of the sandbox till the io of the freeze
+    // is processed.
+    pub fn Hold(&self, held: bool) {
+        self.lock().held = held;
+    }
 }
 
 #[derive(Default)]
 pub struct TimerStoreIntern {
     // expire time -> Timer
     pub timerSeq: BTreeMap<TimerUnit, Timer>, // order by expire time
+    // the timers not due in the current tick
+    pub wheel: TimerWheel,
     pub nextExpire: i64,
     pub uringExpire: i64,
     pub uringId: u64,
+    // the due timers are not fired, see TimerStore::Hold
+    pub held: bool,
 }
 
 impl TimerStoreIntern {
     // the timeout need to process a timer, <PROCESS_TIME means the timer will be triggered immediatelyfa
     pub const PROCESS_TIME: i64 = 30_000;
+    // the time after which the held timers are checked again
+    pub const HOLD_RECHECK: i64 = 1_000_000;
 
     pub fn Print(&self) -> String {
         let keys: Vec<TimerUnit> = self.timerSeq.keys().cloned().collect();
-        return format!("TimerStoreIntern seq is {:#?}", keys);
+        return format!(
+            "TimerStoreIntern seq is {:#?}, wheel has {} timers",
+            keys,
+            self.wheel.Live()
+        );
     }
 
     pub fn Trigger(&mut self) -> i64 {
+        if self.held {
+            return Self::HOLD_RECHECK;
+        }
+
         let mut now = MONOTONIC_CLOCK.Now().0;
+        let mut batch = DispatchBatch::default();
         while now + Self::PROCESS_TIME >= self.nextExpire  {
             let timer = self.GetFirst(now + Self::PROCESS_TIME);
             match timer {
                 Some(timer) => {
-                    timer.Fire(self);
+                    if !SHARESPACE
+                        .timerDispatch
+                        .Dispatch(&SHARESPACE.scheduler, &mut batch, &timer)
+                    {
+                        timer.Fire(self);
+                    }
                 }
                 None => break,
             }
@@ -119,6 +149,8 @@ impl TimerStoreIntern {
             now = MONOTONIC_CLOCK.Now().0;
         }
 
+        SHARESPACE.timerDispatch.Wake(&SHARESPACE.scheduler, &batch);
+
         if self.nextExpire == 0 {
             return -1;
         }
@@ -136,9 +168,10 @@ impl TimerStoreIntern {
 
     // return: existing or not
     pub fn RemoveTimer(&mut self, timer: &Timer) -> bool {
-        let timer = timer.lock();
+        let mut timer = timer.lock();
 
         if timer.Expire > 0 {
+            self.wheel.Cancel(&mut timer);
             self.timerSeq.remove(&timer.TimerUnit());
             return true;
         }
@@ -149,6 +182,7 @@ impl TimerStoreIntern {
     pub fn ResetTimer(&mut self, timer: &Timer, timeout: i64) {
         let mut tl = timer.lock();
         if tl.Expire > 0 {
+            self.wheel.Cancel(&mut tl);
             self.timerSeq.remove(&tl.TimerUnit());
         }
 
@@ -159,11 +193,18 @@ impl TimerStoreIntern {
         let current = MONOTONIC_CLOCK.Now().0;
         tl.Expire = current + timeout;
 
-        if self.nextExpire == 0 || self.nextExpire > tl.Expire {
-            self.nextExpire = tl.Expire;
-        }
+        // the wheel keeps it till the tick before it expires
+        let expire = match self.wheel.Add(timer, &mut tl, current) {
+            Some(deadline) => deadline,
+            None => {
+                self.timerSeq.insert(tl.TimerUnit(), timer.clone());
+                tl.Expire
+            }
+        };
 
-        self.timerSeq.insert(tl.TimerUnit(), timer.clone());
+        if self.nextExpire == 0 || self.nextExpire > expire {
+            self.nextExpire = expire;
+        }
     }
 
     pub fn RemoveUringTimer(&mut self) {
@@ -198,20 +239,30 @@ impl TimerStoreIntern {
             return None;
         }
 
-        let timer = match self.timerSeq.pop_first() {
-            None => return None,
-            Some((_, timer)) => timer,
+        self.wheel.Advance(now, &mut self.timerSeq);
+
+        let due = match self.timerSeq.first_key_value() {
+            None => false,
+            Some((tu, _)) => tu.expire <= now,
         };
 
-        match self.timerSeq.first_key_value() {
-            None => {
-                self.nextExpire = 0;
-            }
-            Some((tu, _)) => {
-                self.nextExpire = tu.expire;
+        let timer = if due {
+            self.timerSeq.pop_first().map(|(_, timer)| timer)
+        } else {
+            None
+        };
+
+        self.nextExpire = match self.timerSeq.first_key_value() {
+            None => 0,
+            Some((tu, _)) => tu.expire,
+        };
+
+        if let Some(deadline) = self.wheel.NextDeadline() {
+            if self.nextExpire == 0 || self.nextExpire > deadline {
+                self.nextExpire = deadline;
             }
         }
 
-        return Some(timer);
+        return timer;
     }
 }
@@ -0,0 +1,387 @@
+// Copyright (c) 2021 Quark Container Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::vec::Vec;
+
+use super::timer::*;
+use super::timer_store::*;
+
+// TimerWheel is the hierarchical timing wheel of the timers which don't
+// expire soon, e.g. the receive timeouts and the keepalive timers of a lot of
+// idle connections, which are reset or canceled far more often than they
+// fire. Adding and canceling such a timer doesn't touch the sorted timers of
+// the timer store, the wheel only moves it there in the tick before it
+// expires, through the lower levels on the way.
+//
+// A tick is 2^TICK_SHIFT ns. Level k has SLOTS slots of SLOTS^k ticks, the
+// timer goes to the lowest level whose slots reach its tick. A timer past the
+// last level waits in its last slot and is placed again from there. A canceled
+// timer is left in its slot and dropped when the slot is processed, it is
+// known by the generation of its wheel entry, the stale entries are compacted
+// when they outnumber the live ones.
+pub const TICK_SHIFT: u64 = 20;
+pub const SLOT_SHIFT: u64 = 6;
+pub const SLOTS: usize = 1 << SLOT_SHIFT;
+pub const LEVELS: usize = 5;
+
+// the stale entries over the live ones which trigger a compaction
+pub const COMPACT_THRESHOLD: usize = 4096;
+
+pub struct WheelEntry {
+    pub gen: u64,
+    pub timer: Timer,
+}
+
+pub struct TimerWheel {
+    pub slots: Vec<Vec<WheelEntry>>,
+    // the non empty slots of each level
+    pub occupied: [u64; LEVELS],
+    // the tick the wheel has been processed till
+    pub curTick: u64,
+    pub nextGen: u64,
+    pub entries: usize,
+    pub stale: usize,
+}
+
+impl Default for TimerWheel {
+    fn default() -> Self {
+        let mut slots = Vec::with_capacity(LEVELS * SLOTS);
+        for _i in 0..LEVELS * SLOTS {
+            slots.push(Vec::new());
+        }
+
+        return Self {
+            slots: slots,
+            occupied: [0; LEVELS],
+            curTick: 0,
+            nextGen: 1,
+            entries: 0,
+            stale: 0,
+        };
+    }
+}
+
+impl TimerWheel {
+    pub fn Tick(time: i64) -> u64 {
+        if time < 0 {
+            return 0;
+        }
+
+        return time as u64 >> TICK_SHIFT;
+    }
+
+    // Deadline returns the time the wheel has to be advanced at to process
+    // the tick.
+    pub fn Deadline(tick: u64) -> i64 {
+        let deadline = ((tick - 1) << TICK_SHIFT) as i64;
+        if deadline <= 0 {
+            return 1;
+        }
+
+        return deadline;
+    }
+
+    pub fn Live(&self) -> usize {
+        return self.entries - self.stale;
+    }
+
+    // Add adds the timer to the slot of its expire, it returns the deadline of
+    // the slot or None if the timer is due in the current tick and has to be
+    // sorted now.
+    pub fn Add(&mut self, timer: &Timer, tl: &mut TimerInternal, now: i64) -> Option<i64> {
+        if self.entries == 0 && self.curTick < Self::Tick(now) {
+            self.curTick = Self::Tick(now);
+        }
+
+        let tick = Self::Tick(tl.Expire);
+        if tick <= self.curTick {
+            return None;
+        }
+
+        // past the last level, the last slot from the current one
+        let top = SLOT_SHIFT * (LEVELS - 1) as u64;
+        let mut level = LEVELS - 1;
+        let mut slot = ((self.curTick >> top) as usize + SLOTS - 1) % SLOTS;
+        for k in 0..LEVELS {
+            let shift = SLOT_SHIFT * k as u64;
+            let diff = (tick >> shift) - (self.curTick >> shift);
+            if diff < SLOTS as u64 {
+                level = k;
+                slot = (tick >> shift) as usize % SLOTS;
+                break;
+            }
+        }
+
+        let gen = self.nextGen;
+        self.nextGen += 1;
+        tl.Wheel = gen;
+        self.slots[level * SLOTS + slot].push(WheelEntry {
+            gen: gen,
+            timer: timer.clone(),
+        });
+        self.occupied[level] |= 1 << slot;
+        self.entries += 1;
+
+        return Some(Self::Deadline(self.SlotTick(level, slot)));
+    }
+
+    // Cancel drops the wheel entry of the timer.
+    pub fn Cancel(&mut self, tl: &mut TimerInternal) {
+        if tl.Wheel != 0 {
+            tl.Wheel = 0;
+            self.stale += 1;
+        }
+    }
+
+    // SlotTick returns the tick the slot of the level is processed at.
+    pub fn SlotTick(&self, level: usize, slot: usize) -> u64 {
+        let shift = SLOT_SHIFT * level as u64;
+        let base = self.curTick >> shift;
+        let mut diff = (slot + SLOTS - (base as usize % SLOTS)) % SLOTS;
+        if diff == 0 {
+            diff = SLOTS;
+        }
+
+        return (base + diff as u64) << shift;
+    }
+
+    // NextTick returns the first tick with a slot to process.
+    pub fn NextTick(&self) -> Option<u64> {
+        let mut next: Option<u64> = None;
+        for level in 0..LEVELS {
+            let occupied = self.occupied[level];
+            if occupied == 0 {
+                continue;
+            }
+
+            let shift = SLOT_SHIFT * level as u64;
+            let base = (self.curTick >> shift) as u32 % SLOTS as u32;
+            // the slots after the current one first
+            let rotated = occupied.rotate_right((base + 1) % SLOTS as u32);
+            let slot = (base + 1 + rotated.trailing_zeros()) as usize % SLOTS;
+            let tick = self.SlotTick(level, slot);
+            if next.map_or(true, |n| tick < n) {
+                next = Some(tick);
+            }
+        }
+
+        return next;
+    }
+
+    // NextDeadline returns the time the wheel has to be advanced at.
+    pub fn NextDeadline(&self) -> Option<i64> {
+        return self.NextTick().map(|tick| Self::Deadline(tick));
+    }
+
+    // Advance processes the slots till the tick after now, the timers due in
+    // the processed ticks are sorted in timerSeq.
+    pub fn Advance(&mut self, now: i64, timerSeq: &mut BTreeMap<TimerUnit, Timer>) {
+        let target = Self::Tick(now) + 1;
+        if self.entries == 0 {
+            if self.curTick < target {
+                self.curTick = target;
+            }
+            return;
+        }
+
+        loop {
+            let tick = match self.NextTick() {
+                Some(tick) if tick <= target => tick,
+                _ => break,
+            };
+
+            self.curTick = tick;
+            // the higher levels first, their timers may go to the lower ones
+            for level in (0..LEVELS).rev() {
+                let shift = SLOT_SHIFT * level as u64;
+                if tick & ((1 << shift) - 1) != 0 {
+                    continue;
+                }
+
+                let slot = (tick >> shift) as usize % SLOTS;
+                if self.occupied[level] & (1 << slot) != 0 {
+                    self.ProcessSlot(level, slot, timerSeq);
+                }
+            }
+        }
+
+        if self.curTick < target {
+            self.curTick = target;
+        }
+
+        if self.stale > self.Live() + COMPACT_THRESHOLD {
+            self.Compact();
+        }
+    }
+
+    fn ProcessSlot(
+        &mut self,
+        level: usize,
+        slot: usize,
+        timerSeq: &mut BTreeMap<TimerUnit, Timer>,
+    ) {
+        let entries = core::mem::replace(&mut self.slots[level * SLOTS + slot], Vec::new());
+        self.occupied[level] &= !(1 << slot);
+        self.entries -= entries.len();
+
+        let now = (self.curTick << TICK_SHIFT) as i64;
+        for e in entries {
+            let mut tl = e.timer.lock();
+            if tl.Wheel != e.gen {
+                self.stale -= 1;
+                continue;
+            }
+
+            tl.Wheel = 0;
+            if self.Add(&e.timer, &mut tl, now).is_none() {
+                timerSeq.insert(tl.TimerUnit(), e.timer.clone());
+            }
+        }
+    }
+
+    // Compact drops the stale entries of all the slots.
+    pub fn Compact(&mut self) {
+        for level in 0..LEVELS {
+            for slot in 0..SLOTS {
+                let entries = &mut self.slots[level * SLOTS + slot];
+                if entries.len() == 0 {
+                    continue;
+                }
+
+                entries.retain(|e| e.timer.lock().Wheel == e.gen);
+                if entries.len() == 0 {
+                    self.occupied[level] &= !(1 << slot);
+                }
+            }
+        }
+
+        self.entries = self.entries - self.stale;
+        self.stale = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn Time(tick: u64) -> i64 {
+        return (tick << TICK_SHIFT) as i64;
+    }
+
+    fn Add(wheel: &mut TimerWheel, timer: &Timer, now: i64) -> Option<i64> {
+        let mut tl = timer.lock();
+        return wheel.Add(timer, &mut tl, now);
+    }
+
+    // Level returns the level of the live entry of the timer.
+    fn Level(wheel: &TimerWheel, timer: &Timer) -> Option<usize> {
+        let (gen, id) = {
+            let tl = timer.lock();
+            (tl.Wheel, tl.Id)
+        };
+        for level in 0..LEVELS {
+            for slot in 0..SLOTS {
+                for e in &wheel.slots[level * SLOTS + slot] {
+                    if e.gen == gen && e.timer.lock().Id == id {
+                        return Some(level);
+                    }
+                }
+            }
+        }
+
+        return None;
+    }
+
+    #[test]
+    fn TestWheelCascade() {
+        let mut wheel = TimerWheel::default();
+        let mut timerSeq = BTreeMap::new();
+
+        // 5000 ticks away, the timer goes to the level 2
+        let timer = Timer::Test(1, Time(5000));
+        assert_eq!(Add(&mut wheel, &timer, 0), Some(TimerWheel::Deadline(4096)));
+        assert_eq!(Level(&wheel, &timer), Some(2));
+
+        // its level 2 slot is processed at the tick 4096, it moves down
+        wheel.Advance(Time(4095), &mut timerSeq);
+        assert_eq!(timerSeq.len(), 0);
+        assert_eq!(Level(&wheel, &timer), Some(1));
+
+        wheel.Advance(Time(4998), &mut timerSeq);
+        assert_eq!(timerSeq.len(), 0);
+        assert_eq!(Level(&wheel, &timer), Some(0));
+
+        // sorted in the tick before it expires
+        wheel.Advance(Time(4999), &mut timerSeq);
+        assert_eq!(timerSeq.len(), 1);
+        assert_eq!(timer.lock().Wheel, 0);
+        assert_eq!(wheel.entries, 0);
+        assert_eq!(wheel.NextTick(), None);
+    }
+
+    #[test]
+    fn TestWheelCancel() {
+        let mut wheel = TimerWheel::default();
+        let mut timerSeq = BTreeMap::new();
+
+        let timer = Timer::Test(1, Time(100));
+        assert!(Add(&mut wheel, &timer, 0).is_some());
+        wheel.Cancel(&mut timer.lock());
+        assert_eq!(wheel.Live(), 0);
+
+        // the stale entry is dropped when its slot is processed
+        wheel.Advance(Time(200), &mut timerSeq);
+        assert_eq!(timerSeq.len(), 0);
+        assert_eq!(wheel.entries, 0);
+        assert_eq!(wheel.stale, 0);
+
+        // a timer canceled and added again fires once, at the new expire
+        let timer = Timer::Test(2, Time(300));
+        Add(&mut wheel, &timer, Time(200));
+        wheel.Cancel(&mut timer.lock());
+        timer.lock().Expire = Time(400);
+        Add(&mut wheel, &timer, Time(200));
+        assert_eq!(wheel.entries, 2);
+        assert_eq!(wheel.Live(), 1);
+
+        wheel.Advance(Time(350), &mut timerSeq);
+        assert_eq!(timerSeq.len(), 0);
+        wheel.Advance(Time(399), &mut timerSeq);
+        assert_eq!(timerSeq.len(), 1);
+        assert_eq!(timerSeq.keys().next().unwrap().expire, Time(400));
+        assert_eq!(wheel.entries, 0);
+        assert_eq!(wheel.stale, 0);
+    }
+
+    #[test]
+    fn TestWheelCompact() {
+        let mut wheel = TimerWheel::default();
+        let mut timers = Vec::new();
+        for i in 0..8 {
+            let timer = Timer::Test(i, Time(1000 + i));
+            Add(&mut wheel, &timer, 0);
+            timers.push(timer);
+        }
+
+        for timer in &timers[..6] {
+            wheel.Cancel(&mut timer.lock());
+        }
+
+        wheel.Compact();
+        assert_eq!(wheel.entries, 2);
+        assert_eq!(wheel.stale, 0);
+        assert_eq!(wheel.Live(), 2);
+    }
+}
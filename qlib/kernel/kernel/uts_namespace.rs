@@ -14,16 +14,18 @@
 
 use crate::qlib::mutex::*;
 use alloc::string::String;
-use alloc::string::ToString;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::ops::Deref;
 
 use super::super::super::auth::userns::*;
 
+// the names are kept as the raw bytes set by sethostname(2)/setdomainname(2),
+// they don't have to be valid utf8
 #[derive(Default)]
 pub struct UTSNamespaceInternal {
-    pub hostName: String,
-    pub domainName: String,
+    pub hostName: Vec<u8>,
+    pub domainName: Vec<u8>,
     pub userns: UserNameSpace,
 }
 
@@ -41,27 +43,27 @@ impl Deref for UTSNamespace {
 impl UTSNamespace {
     pub fn New(hostName: String, domainName: String, userns: UserNameSpace) -> Self {
         let internal = UTSNamespaceInternal {
-            hostName: hostName,
-            domainName: domainName,
+            hostName: hostName.into_bytes(),
+            domainName: domainName.into_bytes(),
             userns: userns,
         };
 
         return Self(Arc::new(QMutex::new(internal)));
     }
 
-    pub fn HostName(&self) -> String {
-        return self.lock().hostName.to_string();
+    pub fn HostName(&self) -> Vec<u8> {
+        return self.lock().hostName.clone();
     }
 
-    pub fn SetHostName(&self, host: String) {
+    pub fn SetHostName(&self, host: Vec<u8>) {
         self.lock().hostName = host;
     }
 
-    pub fn DomainName(&self) -> String {
-        return self.lock().domainName.to_string();
+    pub fn DomainName(&self) -> Vec<u8> {
+        return self.lock().domainName.clone();
     }
 
-    pub fn SetDomainName(&self, domain: String) {
+    pub fn SetDomainName(&self, domain: Vec<u8>) {
         self.lock().domainName = domain;
     }
 
@@ -72,8 +74,8 @@ impl UTSNamespace {
     pub fn Fork(&self, userns: &UserNameSpace) -> Self {
         let me = self.lock();
         let internal = UTSNamespaceInternal {
-            hostName: me.hostName.to_string(),
-            domainName: me.domainName.to_string(),
+            hostName: me.hostName.clone(),
+            domainName: me.domainName.clone(),
             userns: userns.clone(),
         };
 
@@ -34,6 +34,9 @@ pub struct VdsoParams {
     pub realtimeBaseCycles: i64,
     pub realtimeBaseRef: i64,
     pub realtimeFrequency: u64,
+
+    // the TSC_AUX of the vcpus is set, the vdso getcpu uses rdtscp
+    pub getcpuReady: u64,
 }
 
 impl VdsoParams {
@@ -65,6 +65,29 @@ impl Cond {
         return res;
     }
 
+    // WaitUninterruptible waits for the notification, the signals don't
+    // interrupt it.
+    pub fn WaitUninterruptible(&self, task: &Task) {
+        let e;
+        {
+            let c = self.lock();
+            if c.signaled {
+                return;
+            }
+
+            e = task.blocker.generalEntry.clone();
+            e.Clear();
+            c.queue.EventRegister(task, &e, !0);
+        }
+
+        let signal = self.lock().signaled;
+        if !signal {
+            task.blocker.BlockGeneralOnly();
+        }
+
+        self.lock().queue.EventUnregister(task, &e);
+    }
+
     pub fn Broadcast(&self) {
         let mut c = self.lock();
         c.signaled = true;
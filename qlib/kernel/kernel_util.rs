@@ -13,18 +13,14 @@
 // limitations under the License.
 
 use super::super::common::*;
-use super::Kernel::*;
+use super::kernel::entropy::*;
 
 pub const GRND_NONBLOCK: u32 = 0x01;
 pub const GRND_RANDOM: u32 = 0x02;
 
-pub fn Random(buf: u64, len: u64, flags: u32) -> Result<()> {
-    let res = HostSpace::GetRandom(buf, len, flags);
-    if res >= 0 {
-        return Ok(());
-    }
-
-    return Err(Error::SysError(-res as i32));
+pub fn Random(buf: u64, len: u64, _flags: u32) -> Result<()> {
+    let slice = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len as usize) };
+    return GetRandom(slice);
 }
 
 pub fn RandU64() -> Result<u64> {
@@ -35,6 +31,6 @@ pub fn RandU64() -> Result<u64> {
 
 pub fn RandU128() -> Result<(u64, u64)> {
     let res: [u64; 2] = [0; 2];
-    Random(&res[0] as *const _ as u64, 18, GRND_RANDOM)?;
+    Random(&res[0] as *const _ as u64, 16, GRND_RANDOM)?;
     return Ok((res[0], res[1]));
 }
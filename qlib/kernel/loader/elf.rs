@@ -31,7 +31,7 @@ use super::super::arch::x86_64::context::*;
 use super::super::fs::file::*;
 use super::super::memmgr::*;
 use super::super::task::*;
-use super::super::util::cstring::*;
+use super::loader::OpenPath;
 
 pub const ELF_MAGIC: &str = "\x7fELF";
 pub const INTERPRETER_SCRIPT_MAGIC: &str = "#!";
@@ -42,6 +42,21 @@ pub const LINUX_OS: OS = 0;
 pub type Arch = i32;
 pub const AMD64: Arch = 0;
 
+// PT_GNU_PROPERTY is the program header which holds the .note.gnu.property section.
+pub const PT_GNU_PROPERTY: u32 = 0x6474e553;
+
+pub const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+pub const GNU_PROPERTY_X86_FEATURE_1_AND: u32 = 0xc0000002;
+pub const GNU_PROPERTY_X86_FEATURE_1_IBT: u32 = 1 << 0;
+pub const GNU_PROPERTY_X86_FEATURE_1_SHSTK: u32 = 1 << 1;
+
+// MAX_PHDR_TABLE_SIZE is the max size of the program header table, linux
+// rejects binaries with a bigger table (fs/binfmt_elf.c:load_elf_phdrs).
+pub const MAX_PHDR_TABLE_SIZE: usize = 64 * 1024;
+
+// MAX_GNU_PROPERTY_SIZE is the max size of PT_GNU_PROPERTY segment we will read.
+pub const MAX_GNU_PROPERTY_SIZE: u64 = 4096;
+
 // elfInfo contains the metadata needed to load an ELF binary.
 pub struct ElfHeadersInfo {
     // os is the target OS of the ELF.
@@ -86,7 +101,7 @@ pub fn ParseHeader(task: &mut Task, file: &File) -> Result<ElfHeadersInfo> {
     let slice = unsafe { slice::from_raw_parts(addr as *const u8, 2 * 4096) };*/
 
     let mut buf = DataBuff::New(2 * 0x1000);
-    let n = match ReadAll(task, &file, &mut buf.buf, 0) {
+    let mut n = match ReadAll(task, &file, &mut buf.buf, 0) {
         Err(e) => {
             print!("Error ParseHeader {:?}", e);
             return Err(Error::SysError(SysErr::ENOEXEC));
@@ -94,6 +109,44 @@ pub fn ParseHeader(task: &mut Task, file: &File) -> Result<ElfHeadersInfo> {
         Ok(n) => n,
     };
 
+    // Large binaries (e.g. statically linked binaries with a lot of segments)
+    // might have the program header table beyond the first 2 pages.
+    let phdrEnd = {
+        let elfFile = ElfFile::new(&buf.buf[0..n])
+            .map_err(|e| Error::IOError(format!("io::error is {:?}", e)))?;
+        match &elfFile.header.pt2 {
+            HeaderPt2::Header64(pt2) => {
+                let tableSize = pt2.ph_entry_size as usize * pt2.ph_count as usize;
+                if tableSize > MAX_PHDR_TABLE_SIZE {
+                    info!("ParseHeader: program header table too big {:x}", tableSize);
+                    return Err(Error::SysError(SysErr::ENOEXEC));
+                }
+                pt2.ph_offset as usize + tableSize
+            }
+            _ => return Err(Error::WrongELFFormat),
+        }
+    };
+
+    if phdrEnd > n {
+        if n < buf.buf.len() {
+            info!("ParseHeader: program header table {:x} beyond file end {:x}", phdrEnd, n);
+            return Err(Error::SysError(SysErr::ENOEXEC));
+        }
+
+        buf = DataBuff::New(phdrEnd);
+        n = match ReadAll(task, &file, &mut buf.buf, 0) {
+            Err(e) => {
+                print!("Error ParseHeader {:?}", e);
+                return Err(Error::SysError(SysErr::ENOEXEC));
+            }
+            Ok(n) => n,
+        };
+
+        if phdrEnd > n {
+            return Err(Error::SysError(SysErr::ENOEXEC));
+        }
+    }
+
     let elfFile = ElfFile::new(&buf.buf[0..n])
         .map_err(|e| Error::IOError(format!("io::error is {:?}", e)))?;
 
@@ -125,8 +178,10 @@ pub fn ParseHeader(task: &mut Task, file: &File) -> Result<ElfHeadersInfo> {
     for p in elfFile.program_iter() {
         if let Ph64(header) = p {
             let headerType = header.get_type().map_err(Error::ELFLoadError)?;
-            if headerType == Type::Interp || headerType == Type::Load {
-                phdrs.push(*header);
+            match headerType {
+                Type::Interp | Type::Load | Type::Phdr => phdrs.push(*header),
+                Type::OsSpecific(PT_GNU_PROPERTY) => phdrs.push(*header),
+                _ => (),
             }
         }
     }
@@ -297,6 +352,60 @@ pub fn ReadAll(task: &mut Task, file: &File, data: &mut [u8], offset: u64) -> Re
     return Ok(cnt);
 }
 
+// ParseGnuProperty parses the NT_GNU_PROPERTY_TYPE_0 note in the PT_GNU_PROPERTY
+// segment and returns the GNU_PROPERTY_X86_FEATURE_1_AND value.
+pub fn ParseGnuProperty(task: &mut Task, file: &File, header: &ProgramHeader64) -> Result<u32> {
+    if header.file_size > MAX_GNU_PROPERTY_SIZE || header.file_size < 16 {
+        info!("Error: PT_GNU_PROPERTY size {:x} is invalid", header.file_size);
+        return Err(Error::SysError(SysErr::ENOEXEC));
+    }
+
+    let mut data: Vec<u8> = vec![0; header.file_size as usize];
+    let n = ReadAll(task, file, &mut data, header.offset)?;
+    if n != data.len() {
+        return Err(Error::SysError(SysErr::EIO));
+    }
+
+    let readU32 = |off: usize| -> u32 {
+        return u32::from_le_bytes([data[off], data[off + 1], data[off + 2], data[off + 3]]);
+    };
+
+    // Elf64_Nhdr: namesz, descsz, type followed by "GNU\0"
+    let nameSize = readU32(0) as usize;
+    let descSize = readU32(4) as usize;
+    let noteType = readU32(8);
+    if noteType != NT_GNU_PROPERTY_TYPE_0 || nameSize != 4 || &data[12..16] != b"GNU\0" {
+        return Ok(0);
+    }
+
+    let mut off = 16;
+    let end = off + descSize;
+    if end > data.len() {
+        return Err(Error::SysError(SysErr::ENOEXEC));
+    }
+
+    while off + 8 <= end {
+        let prType = readU32(off);
+        let prSize = readU32(off + 4) as usize;
+        off += 8;
+        if off + prSize > end {
+            return Err(Error::SysError(SysErr::ENOEXEC));
+        }
+
+        if prType == GNU_PROPERTY_X86_FEATURE_1_AND {
+            if prSize != 4 {
+                return Err(Error::SysError(SysErr::ENOEXEC));
+            }
+            return Ok(readU32(off));
+        }
+
+        // the property data is 8 bytes aligned on 64 bit
+        off += (prSize + 7) & !7;
+    }
+
+    return Ok(0);
+}
+
 pub fn LoadParseElf(
     task: &mut Task,
     file: &File,
@@ -307,6 +416,7 @@ pub fn LoadParseElf(
     let mut start = 0;
     let mut end = 0;
     let mut interpreter = "".to_string();
+    let mut phdrVaddr = None;
 
     let filesize = file.UnstableAttr(task)?.Size;
     for header in &info.phdrs {
@@ -382,8 +492,21 @@ pub fn LoadParseElf(
                     Ok(a) => a.0,
                 };
             }
+            Type::Phdr => {
+                // PT_PHDR gives the address of the program header table in
+                // memory, which doesn't need to be start + e_phoff.
+                phdrVaddr = Some(header.virtual_addr);
+            }
+            Type::OsSpecific(PT_GNU_PROPERTY) => {
+                // the CET features are not enabled in the sandbox, the note
+                // is only checked
+                let x86Features = ParseGnuProperty(task, file, header)?;
+                if x86Features != 0 {
+                    info!("the elf x86 feature_1_and is {:x}", x86Features);
+                }
+            }
             t => {
-                panic!("find unexpect type {:?}", t)
+                info!("LoadParseElf: ignore program header type {:?}", t);
             }
         }
     }
@@ -434,15 +557,18 @@ pub fn LoadParseElf(
         }
     }
 
-    let phdrAddr = match Addr(start).AddLen(info.phdrAddr) {
-        Err(_) => {
-            info!(
-                "ELF start address {:x} + phdr offset {:x} overflows",
-                start, info.phdrAddr
-            );
-            0
-        }
-        Ok(a) => a.0,
+    let phdrAddr = match phdrVaddr {
+        Some(vaddr) => vaddr + offset,
+        None => match Addr(start).AddLen(info.phdrAddr) {
+            Err(_) => {
+                info!(
+                    "ELF start address {:x} + phdr offset {:x} overflows",
+                    start, info.phdrAddr
+                );
+                0
+            }
+            Ok(a) => a.0,
+        },
     };
 
     return Ok(LoadedElf {
@@ -526,16 +652,19 @@ pub fn LoadElf(task: &mut Task, file: &File) -> Result<LoadedElf> {
 
     let mut interp = LoadedElf::default();
     if bin.interpreter.as_str() != "" {
-        let fileName = CString::New(&bin.interpreter);
-        let fd = task.Open(fileName.Ptr(), OpenFlags::O_RDONLY as u64, 0); //kernel address is same as phy address
-        if (fd as i64) < 0 {
-            info!(
-                "LoadElf Error opening interpreter {} with error {}",
-                &bin.interpreter, -fd as i64
-            );
-        }
+        // open the interpreter without installing it in the fd table so that it
+        // won't leak into the new process image
+        let interpFile = match OpenPath(task, &bin.interpreter.clone(), 40) {
+            Err(e) => {
+                info!(
+                    "LoadElf Error opening interpreter {} with error {:?}",
+                    &bin.interpreter, e
+                );
+                return Err(e);
+            }
+            Ok((f, _)) => f,
+        };
 
-        let interpFile = task.GetFile(fd as i32)?;
         interp = loadInterpreterELF(task, &interpFile, &bin)?;
 
         if interp.interpreter.as_str() != "" {
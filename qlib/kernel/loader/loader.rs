@@ -26,6 +26,7 @@ use super::super::fs::dirent::*;
 use super::super::fs::inotify::*;
 use super::super::fs::file::*;
 use super::super::fs::flags::*;
+use super::super::kernel::kernel::GetKernel;
 use super::super::kernel::timer::*;
 use super::super::kernel_util::*;
 use super::super::memmgr::*;
@@ -301,6 +302,28 @@ pub fn SetupUserStack(
 
     let execfn = stack.PushStr(task, argv[0].as_str())?;
 
+    let (hwcap, hwcap2, minSigStackSize) = {
+        let kernel = GetKernel();
+        let featureSet = kernel.featureSet.lock();
+        (
+            featureSet.HWCap(),
+            featureSet.HWCap2(),
+            featureSet.MinSigStackSize(),
+        )
+    };
+
+    let (uid, euid, gid, egid) = {
+        let creds = task.Creds();
+        let c = creds.lock();
+        let userns = c.UserNamespace.clone();
+        (
+            c.RealKUID.In(&userns).OrOverflow().0,
+            c.EffectiveKUID.In(&userns).OrOverflow().0,
+            c.RealKGID.In(&userns).OrOverflow().0,
+            c.EffectiveKGID.In(&userns).OrOverflow().0,
+        )
+    };
+
     /*auxv vector*/
     let mut auxv = Vec::new();
     auxv.push(AuxEntry {
@@ -317,7 +340,11 @@ pub fn SetupUserStack(
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_HWCAP2,
-        Val: 0,
+        Val: hwcap2,
+    });
+    auxv.push(AuxEntry {
+        Key: AuxVec::AT_MINSIGSTKSZ,
+        Val: minSigStackSize,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_RANDOM,
@@ -329,19 +356,19 @@ pub fn SetupUserStack(
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_EGID,
-        Val: 0,
+        Val: egid as u64,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_GID,
-        Val: 0,
+        Val: gid as u64,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_EUID,
-        Val: 0,
+        Val: euid as u64,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_UID,
-        Val: 0,
+        Val: uid as u64,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_FLAGS,
@@ -357,7 +384,7 @@ pub fn SetupUserStack(
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_HWCAP,
-        Val: 0xbfebfbff,
+        Val: hwcap,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_SYSINFO_EHDR,
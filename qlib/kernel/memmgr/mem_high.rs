@@ -0,0 +1,126 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::super::common::*;
+use super::super::super::linux::time::*;
+use super::super::super::linux_def::*;
+use super::super::super::vcpu_mgr::CPULocal;
+use super::super::task::*;
+use super::super::PAGE_MGR;
+use super::super::SHARESPACE;
+use super::zswap::*;
+
+// memory.high of the sandbox. When the guest memory goes over the high
+// watermark the task is throttled on its way back to the application, like
+// the memory cgroup does on the return to user mode: the cached free pages are
+// given back first, then the task sleeps in proportion to the overage so the
+// bursty allocators slow down and get the chance to free memory before the
+// hard limit of the host cgroup kills the sandbox. With the compressed tier on,
+// the cold pages of the task are compressed before it is throttled.
+
+// the max sleep of one throttling
+pub const MEMORY_HIGH_MAX_DELAY: i64 = 2 * SECOND;
+
+// the sleep for every 1/1000 over the high watermark
+const MEMORY_HIGH_DELAY_PER_MILLE: i64 = 100 * MICROSECOND;
+
+pub static MEMORY_HIGH_THROTTLED: AtomicU64 = AtomicU64::new(0);
+
+// the memory.high in bytes, a copy of the config which is checked on every
+// return to user without the config lock
+static MEMORY_HIGH: AtomicU64 = AtomicU64::new(0);
+
+// MemoryUsage returns the bytes of the guest memory in use, the compressed
+// tier included.
+pub fn MemoryUsage() -> u64 {
+    return PAGE_MGR.AllocatedPages() * MemoryDef::PAGE_SIZE + SHARESPACE.stats.ZswapBytes();
+}
+
+pub fn MemoryHigh() -> u64 {
+    return MEMORY_HIGH.load(Ordering::Relaxed);
+}
+
+// SetMemoryHigh is called with the MemoryHigh of the config at the boot and
+// when the config is updated.
+pub fn SetMemoryHigh(mb: u64) {
+    MEMORY_HIGH.store(mb * MemoryDef::ONE_MB, Ordering::Relaxed);
+}
+
+// SandboxMemory returns the memory the sandbox is sized for, the memory.high
+// if it is set or the guest memory.
+pub fn SandboxMemory() -> u64 {
+    let high = MemoryHigh();
+    if high > 0 {
+        return high;
+    }
+
+    return SHARESPACE.config.read().KernelMemSize * MemoryDef::ONE_GB;
+}
+
+// OverHighDelay returns how long a task is throttled with the usage.
+pub fn OverHighDelay(usage: u64, high: u64) -> i64 {
+    if high == 0 || usage <= high {
+        return 0;
+    }
+
+    let perMille = ((usage - high) as u128 * 1000 / high as u128) as i64;
+    let delay = (perMille + 1) * MEMORY_HIGH_DELAY_PER_MILLE;
+    if delay > MEMORY_HIGH_MAX_DELAY {
+        return MEMORY_HIGH_MAX_DELAY;
+    }
+
+    return delay;
+}
+
+// HandleOverHigh reclaims and throttles the task when the guest is over
+// memory.high.
+pub fn HandleOverHigh(task: &Task) {
+    let high = MemoryHigh();
+    if high == 0 || MemoryUsage() <= high {
+        return;
+    }
+
+    // the free pages cached by the cpu
+    CPULocal::Myself().pageAllocator.lock().Clean();
+
+    // then the cold pages of the task go to the compressed tier
+    if Zswap::Enabled() && MemoryUsage() > high {
+        task.mm.SwapOutColdPages(ZSWAP_SCAN_SPAN);
+    }
+
+    let delay = OverHighDelay(MemoryUsage(), high);
+    if delay == 0 {
+        return;
+    }
+
+    let cnt = MEMORY_HIGH_THROTTLED.fetch_add(1, Ordering::Relaxed) + 1;
+    if cnt % 1000 == 1 {
+        info!(
+            "memory.high: usage {} over high {}, throttled {} times",
+            MemoryUsage(),
+            high,
+            cnt
+        );
+    }
+
+    // a pending signal ends the sleep, the task is throttled again next time
+    let (_, res) = task.blocker.BlockWithMonoTimeout(false, Some(delay));
+    match res {
+        Err(Error::SysError(SysErr::ETIMEDOUT)) | Err(Error::ErrInterrupted) | Ok(()) => (),
+        Err(e) => error!("memory.high: throttle fail {:?}", e),
+    }
+}
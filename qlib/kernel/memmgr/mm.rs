@@ -170,6 +170,9 @@ pub struct MemoryManagerInternal {
     pub layout: QMutex<MmapLayout>,
     pub aioManager: AIOManager,
     pub membarrierPrivateEnabled: AtomicBool,
+
+    // where the next scan for the cold pages starts, see zswap.rs
+    pub zswapCursor: AtomicU64,
 }
 
 #[derive(Clone)]
@@ -295,6 +298,7 @@ impl MemoryManager {
             layout: QMutex::new(layout),
             aioManager: AIOManager::default(),
             membarrierPrivateEnabled: AtomicBool::new(false),
+            zswapCursor: AtomicU64::new(0),
         };
 
         return Self(Arc::new(internal));
@@ -1018,6 +1022,11 @@ impl MemoryManager {
             return Err(Error::SysError(SysErr::EFAULT));
         }
 
+        // the page is in the compressed tier, the copy of a private file page too
+        if self.SwapInLocked(vma, pageAddr)? {
+            return Ok(());
+        }
+
         let exec = vma.effectivePerms.Exec();
         match &vma.mappable.HostIops() {
             Some(iops) => {
@@ -1032,7 +1041,13 @@ impl MemoryManager {
 
                     let writeable = vma.effectivePerms.Write();
                     if writeable {
-                        let page = { super::super::PAGE_MGR.AllocPage(true).unwrap() };
+                        let page = if SHARESPACE.config.read().Ksm {
+                            let page = super::super::PAGE_MGR.AllocKsmPage().unwrap();
+                            SHARESPACE.ksm.Hint(page);
+                            page
+                        } else {
+                            super::super::PAGE_MGR.AllocPage(true).unwrap()
+                        };
                         CopyPage(page, phyAddr);
                         self.MapPageWriteLocked(pageAddr, page, exec);
                         super::super::PAGE_MGR.DerefPage(page);
@@ -1054,7 +1069,13 @@ impl MemoryManager {
                 //let vmaOffset = pageAddr - range.Start();
                 //let phyAddr = vmaOffset + vma.offset; // offset in the phyAddr
 
-                let phyAddr = super::super::PAGE_MGR.AllocPage(true).unwrap();
+                let phyAddr = if SHARESPACE.config.read().KsmAnon {
+                    let page = super::super::PAGE_MGR.AllocKsmPage().unwrap();
+                    SHARESPACE.ksm.Hint(page);
+                    page
+                } else {
+                    super::super::PAGE_MGR.AllocPage(true).unwrap()
+                };
                 let writeable = vma.effectivePerms.Write();
                 if writeable {
                     self.MapPageWriteLocked(pageAddr, phyAddr, exec);
@@ -1168,18 +1189,35 @@ impl MemoryManager {
             // Kernel phy address
             let end = start + len;
             assert!(MemoryDef::PHY_LOWER_ADDR <= end && end <= MemoryDef::PHY_UPPER_ADDR);
-            output.push(IoVec {
+            let iov = IoVec {
                 start: start,
                 len: len as usize,
-            });
+            };
+            // pinned as well for UnpinIovs, a noop unless from the page pool
+            PinIov(&iov);
+            output.push(iov);
             return Ok(());
         }
 
         let len = self.FixPermissionLocked(task, rlock, start, len, writable, allowPartial)?;
+        return self.PinPagesLocked(start, start + len, output, true);
+    }
 
+    // PinPagesLocked appends the physical iovs of the user range to output and
+    // takes a reference of their pages, so that they are neither swapped out
+    // nor freed while the host does IO on them. The mapping lock is held since
+    // the translation, the caller releases the pages with UnpinIovs once the
+    // IO is done. An iov covers the pieces of a page once at most, so that
+    // each page is released as many times as it is pinned.
+    fn PinPagesLocked(
+        &self,
+        start: u64,
+        end: u64,
+        output: &mut Vec<IoVec>,
+        merge: bool,
+    ) -> Result<()> {
+        let mut iovs: Vec<IoVec> = Vec::new();
         let mut start = start;
-        let end = start + len;
-
         while start < end {
             let next = if Addr(start).IsPageAligned() {
                 start + MemoryDef::PAGE_SIZE
@@ -1190,6 +1228,7 @@ impl MemoryManager {
             match self.VirtualToPhyLocked(start) {
                 Err(e) => {
                     info!("convert to phyaddress fail, addr = {:x} e={:?}", start, e);
+                    UnpinIovs(&iovs);
                     return Err(Error::SysError(SysErr::EFAULT));
                 }
                 Ok((pAddr, _)) => {
@@ -1201,20 +1240,31 @@ impl MemoryManager {
                             (next - start) as usize
                         },
                     };
-
-                    let cnt = output.len();
-                    if cnt > 0 && output[cnt - 1].End() == iov.start {
-                        // use the last entry
-                        output[cnt - 1].len += iov.len;
-                    } else {
-                        output.push(iov);
-                    }
+                    PinIov(&iov);
+                    iovs.push(iov);
                 }
             }
 
             start = next;
         }
 
+        // the iovs of the former calls are left as they are, so that the
+        // caller can release the ones of a call
+        let first = output.len();
+        for iov in iovs {
+            let cnt = output.len();
+            if merge
+                && cnt > first
+                && output[cnt - 1].End() == iov.start
+                && Addr(iov.start).IsPageAligned()
+            {
+                // use the last entry
+                output[cnt - 1].len += iov.len;
+            } else {
+                output.push(iov);
+            }
+        }
+
         return Ok(());
     }
 
@@ -1648,38 +1698,26 @@ impl MemoryManager {
         writable: bool,
     ) -> Result<()> {
         self.FixPermissionLocked(task, rlock, start, len, writable, false)?;
+        return self.PinPagesLocked(start, start + len, output, false);
+    }
+}
 
-        let mut start = start;
-        let end = start + len;
-
-        while start < end {
-            let next = if Addr(start).IsPageAligned() {
-                start + MemoryDef::PAGE_SIZE
-            } else {
-                Addr(start).RoundUp().unwrap().0
-            };
-
-            match self.VirtualToPhyLocked(start) {
-                Err(e) => {
-                    info!("convert to phyaddress fail, addr = {:x} e={:?}", start, e);
-                    return Err(Error::SysError(SysErr::EFAULT));
-                }
-                Ok((pAddr, _)) => {
-                    output.push(IoVec {
-                        start: pAddr,
-                        len: if end < next {
-                            (end - start) as usize
-                        } else {
-                            (next - start) as usize
-                        }, //iov.len,
-                    });
-                }
-            }
+fn PinIov(iov: &IoVec) {
+    let mut page = iov.start & !(MemoryDef::PAGE_SIZE - 1);
+    while page < iov.End() {
+        PAGE_MGR.Ref(page).unwrap();
+        page += MemoryDef::PAGE_SIZE;
+    }
+}
 
-            start = next;
+// UnpinIovs releases the pages pinned for the host IO by V2P and V2PIov.
+pub fn UnpinIovs(iovs: &[IoVec]) {
+    for iov in iovs {
+        let mut page = iov.start & !(MemoryDef::PAGE_SIZE - 1);
+        while page < iov.End() {
+            PAGE_MGR.Deref(page).unwrap();
+            page += MemoryDef::PAGE_SIZE;
         }
-
-        return Ok(());
     }
 }
 
@@ -15,6 +15,7 @@
 pub mod arch;
 mod mapping;
 pub mod mapping_set;
+pub mod mem_high;
 pub mod memmap;
 pub mod metadata;
 pub mod mm;
@@ -22,6 +23,7 @@ pub mod pma;
 pub mod pmamgr;
 pub mod syscalls;
 pub mod vma;
+pub mod zswap;
 //pub mod buf_allocator;
 pub mod linked_list;
 
@@ -32,6 +32,7 @@ use super::super::asm::*;
 use super::super::task::*;
 use super::super::PAGE_MGR;
 use super::pmamgr::*;
+use super::zswap::*;
 
 pub type PageMgrRef = ObjectRef<PageMgr>;
 
@@ -41,16 +42,30 @@ pub struct PageMgr {
     pub vsyscallPages: Mutex<Arc<Vec<u64>>>,
 }
 
+// the swap entries of the page tables reference the handles of the
+// compressed tier as the pages
 impl RefMgr for PageMgr {
     fn Ref(&self, addr: u64) -> Result<u64> {
+        if IsSwapHandle(addr) {
+            return ZSWAP.Ref(addr);
+        }
+
         return self.pagepool.Ref(addr);
     }
 
     fn Deref(&self, addr: u64) -> Result<u64> {
+        if IsSwapHandle(addr) {
+            return ZSWAP.Deref(addr);
+        }
+
         return self.pagepool.Deref(addr);
     }
 
     fn GetRef(&self, addr: u64) -> Result<u64> {
+        if IsSwapHandle(addr) {
+            return ZSWAP.GetRef(addr);
+        }
+
         return self.pagepool.GetRef(addr);
     }
 }
@@ -78,6 +93,10 @@ impl Default for PageMgr {
 }
 
 impl PageMgr {
+    pub fn AllocKsmPage(&self) -> Result<u64> {
+        return self.pagepool.AllocKsmPage();
+    }
+
     pub fn New() -> Self {
         return Self {
             pagepool: PagePool::New(),
@@ -94,6 +113,14 @@ impl PageMgr {
         self.pagepool.PrintRefs();
     }
 
+    pub fn AllocatedPages(&self) -> u64 {
+        return self.pagepool.AllocatedPages();
+    }
+
+    pub fn AllocatedAddrs(&self) -> Vec<u64> {
+        return self.pagepool.AllocatedAddrs();
+    }
+
     pub fn DerefPage(&self, addr: u64) {
         self.pagepool.Deref(addr).unwrap();
     }
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use alloc::collections::btree_map::BTreeMap;
+use alloc::vec::Vec;
 use core::sync::atomic::AtomicU64;
 use core::sync::atomic::Ordering;
 use spin::Mutex;
@@ -47,11 +48,103 @@ pub fn CheckZeroPage(pageStart: u64) {
 }
 
 pub const REF_MAP_PARTITION_CNT : usize = 16;
+// The pages the guest hints to the KSM of the host, see qlib/mem/ksm.rs. The
+// kernel heap and the pages of the page pool share the 2MB blocks of the heap
+// and a block is marked mergeable as a whole, so the pages to hint are taken
+// from whole 2MB blocks of the heap which hold nothing else. A block stays in
+// the pool when its last page is freed, it is still mergeable on the host and
+// the kernel heap objects must never be in a merged page.
+#[derive(Default)]
+pub struct KsmBlocks {
+    // block address -> (bitmap of the free pages, count of the used pages)
+    pub blocks: BTreeMap<u64, ([u64; 8], u32)>,
+    // the blocks with a free page
+    pub avail: Vec<u64>,
+}
+
+#[derive(Default)]
+pub struct KsmPagePool {
+    pub blocks: Mutex<KsmBlocks>,
+    pub blockCnt: AtomicU64,
+}
+
+impl KsmPagePool {
+    pub const PAGE_CNT: u32 = (MemoryDef::PAGE_SIZE_2M / MemoryDef::PAGE_SIZE) as u32;
+
+    fn BlockAllocator() -> AlignedAllocator {
+        return AlignedAllocator::New(
+            MemoryDef::PAGE_SIZE_2M as usize,
+            MemoryDef::PAGE_SIZE_2M as usize,
+        );
+    }
+
+    pub fn Allocate(&self) -> Result<u64> {
+        let mut guard = self.blocks.lock();
+        let blocks = &mut *guard;
+        let block = match blocks.avail.last() {
+            Some(block) => *block,
+            None => {
+                let block = Self::BlockAllocator().Allocate()?;
+                if block == 0 {
+                    return Err(Error::NoEnoughMemory);
+                }
+                blocks.blocks.insert(block, ([u64::MAX; 8], 0));
+                blocks.avail.push(block);
+                self.blockCnt.fetch_add(1, Ordering::Relaxed);
+                block
+            }
+        };
+
+        let (free, used) = blocks.blocks.get_mut(&block).unwrap();
+        for i in 0..free.len() {
+            if free[i] != 0 {
+                let bit = free[i].trailing_zeros() as u64;
+                free[i] &= !(1 << bit);
+                *used += 1;
+                if *used == Self::PAGE_CNT {
+                    blocks.avail.pop();
+                }
+                return Ok(block + (i as u64 * 64 + bit) * MemoryDef::PAGE_SIZE);
+            }
+        }
+
+        panic!("KsmPagePool: block {:x} has no free page", block);
+    }
+
+    // Free returns false when the page is not of the pool.
+    pub fn Free(&self, addr: u64) -> bool {
+        if self.blockCnt.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+
+        let block = addr & !(MemoryDef::PAGE_SIZE_2M - 1);
+        let mut blocks = self.blocks.lock();
+        let full = match blocks.blocks.get_mut(&block) {
+            None => return false,
+            Some((free, used)) => {
+                let page = (addr - block) / MemoryDef::PAGE_SIZE;
+                free[(page / 64) as usize] |= 1 << (page % 64);
+                *used -= 1;
+                *used == Self::PAGE_CNT - 1
+            }
+        };
+
+        if full {
+            blocks.avail.push(block);
+        }
+
+        return true;
+    }
+}
+
 pub struct PagePool {
     //refCount for whole pma
     pub refCount: AtomicU64,
     pub refs: [Mutex<BTreeMap<u64, u32>>; 16],
     pub allocator: AlignedAllocator,
+    // the pages handed out to the guest, for the memory usage accounting
+    pub allocated: AtomicU64,
+    pub ksmPages: KsmPagePool,
 }
 
 impl PagePool {
@@ -64,9 +157,25 @@ impl PagePool {
                 MemoryDef::PAGE_SIZE as usize,
                 MemoryDef::PAGE_SIZE as usize,
             ),
+            allocated: AtomicU64::new(0),
+            ksmPages: KsmPagePool::default(),
         };
     }
 
+    pub fn AllocatedPages(&self) -> u64 {
+        return self.allocated.load(Ordering::Relaxed);
+    }
+
+    // AllocatedAddrs returns the addresses of the pages handed out.
+    pub fn AllocatedAddrs(&self) -> Vec<u64> {
+        let mut addrs = Vec::new();
+        for refs in &self.refs {
+            addrs.extend(refs.lock().keys().cloned());
+        }
+
+        return addrs;
+    }
+
     pub fn PrintRefs(&self) {
         //error!("PagePool left is {:#x?}", self.refs);
     }
@@ -153,6 +262,18 @@ impl PagePool {
         return Ok(addr);
     }
 
+    // AllocKsmPage allocates a page with a reference from a block holding only
+    // the pages to hint to KSM.
+    pub fn AllocKsmPage(&self) -> Result<u64> {
+        let addr = self.ksmPages.Allocate()?;
+        ZeroPage(addr);
+        self.allocated.fetch_add(1, Ordering::Relaxed);
+        let idx = Self::PartitionId(addr);
+        self.refs[idx].lock().insert(addr, 1);
+        self.refCount.fetch_add(1, Ordering::Release);
+        return Ok(addr);
+    }
+
     pub fn FreePage(&self, addr: u64) -> Result<()> {
         return self.Free(addr);
     }
@@ -161,6 +282,7 @@ impl PagePool {
         match CPULocal::Myself().pageAllocator.lock().AllocPage() {
             Some(page) => {
                 ZeroPage(page);
+                self.allocated.fetch_add(1, Ordering::Relaxed);
                 return Ok(page);
             }
             None => (),
@@ -168,12 +290,18 @@ impl PagePool {
 
         let addr = self.allocator.Allocate()?;
         ZeroPage(addr as u64);
+        self.allocated.fetch_add(1, Ordering::Relaxed);
         //error!("AllocPage {:x}", addr);
 
         return Ok(addr as u64);
     }
 
     pub fn Free(&self, addr: u64) -> Result<()> {
+        self.allocated.fetch_sub(1, Ordering::Relaxed);
+        if self.ksmPages.Free(addr) {
+            return Ok(());
+        }
+
         CPULocal::Myself().pageAllocator.lock().FreePage(addr);
         return Ok(());
         //return self.allocator.Free(addr);
@@ -675,7 +675,12 @@ impl MemoryManager {
         return Ok(addr);
     }
 
-    pub fn GetSharedFutexKey(&self, task: &Task, addr: u64) -> Result<Key> {
+    // GetSharedFutexKey returns the key of the shared futex at addr. The futex
+    // of a file mapping is keyed by the file and the offset as
+    // kernel/futex.c:get_futex_key does, the page doesn't have to be faulted in
+    // and the key is the same in all the processes mapping the file. Only the
+    // write operations fault the page in writable.
+    pub fn GetSharedFutexKey(&self, task: &Task, addr: u64, write: bool) -> Result<Key> {
         let rl = self.MappingReadLock();
 
         let ar = match Addr(addr).ToRange(4) {
@@ -683,7 +688,12 @@ impl MemoryManager {
             Err(_) => return Err(Error::SysError(SysErr::EFAULT)),
         };
 
-        let (vseg, _, err) = self.GetVMAsLocked(&ar, &AccessType::ReadOnly(), false);
+        let at = if write {
+            AccessType::ReadWrite()
+        } else {
+            AccessType::ReadOnly()
+        };
+        let (vseg, _, err) = self.GetVMAsLocked(&ar, &at, false);
         match err {
             Ok(()) => (),
             Err(e) => return Err(e),
@@ -694,16 +704,28 @@ impl MemoryManager {
             return Ok(Key {
                 Kind: KeyKind::KindSharedPrivate,
                 Addr: addr,
+                ..Default::default()
+            });
+        }
+
+        if let Some(iops) = vma.mappable.HostIops() {
+            let sattr = iops.StableAttr();
+            return Ok(Key {
+                Kind: KeyKind::KindSharedMappable,
+                Addr: vseg.MappableOffsetAt(addr),
+                Dev: sattr.DeviceId,
+                Ino: sattr.InodeId,
             });
         }
 
-        self.V2PLocked(task, &rl, addr, 4, &mut task.GetMut().iovs, true, false)?;
+        self.V2PLocked(task, &rl, addr, 4, &mut task.GetMut().iovs, write, false)?;
         defer!(task.GetMut().iovs.clear());
         assert!(task.GetMut().iovs.len() == 1);
 
         return Ok(Key {
             Kind: KeyKind::KindSharedMappable,
             Addr: task.GetMut().iovs[0].start,
+            ..Default::default()
         });
     }
 
@@ -720,7 +742,9 @@ impl MemoryManager {
             let mut vseg = mapping.vmas.LowerBoundSeg(ar.Start());
             while vseg.Ok() && vseg.Range().Start() < ar.End() {
                 let vma = vseg.Value();
-                if vma.mlockMode != MLockMode::MlockNone && advise == MAdviseOp::MADV_DONTNEED {
+                if vma.mlockMode != MLockMode::MlockNone
+                    && (advise == MAdviseOp::MADV_DONTNEED || advise == MAdviseOp::MADV_FREE)
+                {
                     return Err(Error::SysError(SysErr::EINVAL));
                 }
 
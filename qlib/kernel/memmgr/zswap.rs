@@ -0,0 +1,335 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::vec::Vec;
+use core::slice;
+use core::sync::atomic::Ordering;
+use x86_64::structures::paging::PageTableFlags;
+use x86_64::PhysAddr;
+
+use crate::qlib::mutex::*;
+
+use super::super::super::addr::*;
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::super::pagetable::*;
+use super::super::super::range::*;
+use super::super::asm::*;
+use super::super::PAGE_MGR;
+use super::super::SHARESPACE;
+use super::mem_high::*;
+use super::mm::*;
+use super::*;
+
+// The compressed tier of the guest memory, as the zswap of Linux. When the
+// guest is over memory.high, the task throttled scans the next span of the
+// private memory of its mm: a page accessed since the last scan is aged by
+// clearing its accessed bit, a page which is not and is mapped only there,
+// with no reference taken by V2P for a host IO in flight (see UnpinIovs), is
+// lz4 compressed into the pool and released, its pte becomes a swap entry
+// with the handle of the page in the pool (see qlib/pagetable.rs). The next
+// touch of the page faults it back in from the pool. The pool is in the guest
+// kernel heap and is capped at ZswapMaxPoolPercent of the sandbox memory, the
+// pages lz4 can't shrink stay in memory. There is no host swap file behind the
+// pool yet, a page stays in the pool until it is touched or unmapped.
+
+// the address span scanned by the reclaim of a throttled task
+pub const ZSWAP_SCAN_SPAN: u64 = 512 * MemoryDef::PAGE_SIZE;
+
+lazy_static! {
+    pub static ref ZSWAP: Zswap = Zswap::default();
+}
+
+pub struct ZswapSlot {
+    // the lz4 block of the page, empty for a zero page
+    pub data: Vec<u8>,
+    // the swap entries of the page, the forked mms share the slot
+    pub refs: u64,
+}
+
+#[derive(Default)]
+pub struct ZswapInternal {
+    pub slots: BTreeMap<u64, ZswapSlot>,
+    pub nextHandle: u64,
+}
+
+#[derive(Default)]
+pub struct Zswap(QMutex<ZswapInternal>);
+
+impl Zswap {
+    pub fn Enabled() -> bool {
+        return SHARESPACE.config.read().ZswapMaxPoolPercent > 0;
+    }
+
+    pub fn MaxPoolBytes() -> u64 {
+        let percent = SHARESPACE.config.read().ZswapMaxPoolPercent;
+        return SandboxMemory() / 100 * percent;
+    }
+
+    // Store compresses the page into the pool and returns its handle, None if
+    // the page doesn't shrink or the pool is full.
+    pub fn Store(&self, page: u64) -> Option<u64> {
+        let pageSize = MemoryDef::PAGE_SIZE as usize;
+        let src = unsafe { slice::from_raw_parts(page as *const u8, pageSize) };
+        let words = unsafe { slice::from_raw_parts(page as *const u64, pageSize / 8) };
+
+        let data = if words.iter().all(|w| *w == 0) {
+            Vec::new()
+        } else {
+            let mut buf = vec![0u8; lz4_flex::block::get_maximum_output_size(pageSize)];
+            match lz4_flex::block::compress_into(src, &mut buf) {
+                Ok(n) if n < pageSize => {
+                    buf.truncate(n);
+                    buf.shrink_to_fit();
+                    buf
+                }
+                _ => return None,
+            }
+        };
+
+        let len = data.len() as u64;
+        if SHARESPACE.stats.ZswapBytes() + len > Self::MaxPoolBytes() {
+            return None;
+        }
+
+        let mut intern = self.0.lock();
+        if intern.nextHandle == 0 {
+            intern.nextHandle = SWAP_HANDLE_BASE;
+        }
+
+        let handle = intern.nextHandle;
+        intern.nextHandle += MemoryDef::PAGE_SIZE;
+        intern.slots.insert(
+            handle,
+            ZswapSlot {
+                data: data,
+                refs: 1,
+            },
+        );
+        SHARESPACE.stats.ZswapStore(len);
+        return Some(handle);
+    }
+
+    // Load decompresses the page of the handle into the zeroed page.
+    pub fn Load(&self, handle: u64, page: u64) -> Result<()> {
+        let intern = self.0.lock();
+        let slot = match intern.slots.get(&handle) {
+            None => return Err(Error::AddressNotMap(handle)),
+            Some(slot) => slot,
+        };
+
+        if slot.data.len() == 0 {
+            return Ok(());
+        }
+
+        let pageSize = MemoryDef::PAGE_SIZE as usize;
+        let dst = unsafe { slice::from_raw_parts_mut(page as *mut u8, pageSize) };
+        match lz4_flex::block::decompress_into(&slot.data, dst) {
+            Ok(n) if n == pageSize => return Ok(()),
+            _ => {
+                return Err(Error::Common(format!(
+                    "zswap: corrupted page of handle {:x}",
+                    handle
+                )))
+            }
+        }
+    }
+}
+
+impl RefMgr for Zswap {
+    fn Ref(&self, handle: u64) -> Result<u64> {
+        let mut intern = self.0.lock();
+        match intern.slots.get_mut(&handle) {
+            None => return Err(Error::AddressNotMap(handle)),
+            Some(slot) => {
+                slot.refs += 1;
+                return Ok(slot.refs);
+            }
+        }
+    }
+
+    fn Deref(&self, handle: u64) -> Result<u64> {
+        let mut intern = self.0.lock();
+        let refs = match intern.slots.get_mut(&handle) {
+            None => return Err(Error::AddressNotMap(handle)),
+            Some(slot) => {
+                slot.refs -= 1;
+                slot.refs
+            }
+        };
+
+        if refs == 0 {
+            let slot = intern.slots.remove(&handle).unwrap();
+            SHARESPACE.stats.ZswapFree(slot.data.len() as u64);
+        }
+
+        return Ok(refs);
+    }
+
+    fn GetRef(&self, handle: u64) -> Result<u64> {
+        let intern = self.0.lock();
+        match intern.slots.get(&handle) {
+            None => return Ok(0),
+            Some(slot) => return Ok(slot.refs),
+        }
+    }
+}
+
+impl MemoryManager {
+    // SwappableRangesLocked returns the ranges of the vmas whose pages can go
+    // to the compressed tier.
+    pub fn SwappableRangesLocked(&self) -> Vec<Range> {
+        let mut ranges = Vec::new();
+        let mapping = self.mapping.lock();
+        let mut seg = mapping.vmas.FirstSeg();
+        while seg.Ok() {
+            let vma = seg.Value();
+            if !vma.kernel && vma.private && vma.mlockMode == MLockMode::MlockNone {
+                ranges.push(seg.Range());
+            }
+            seg = seg.NextSeg();
+        }
+
+        return ranges;
+    }
+
+    // SwapOutColdPages scans the span from the cursor of the mm and moves its
+    // cold pages to the compressed tier, returns the pages swapped out.
+    pub fn SwapOutColdPages(&self, span: u64) -> u64 {
+        let _ml = self.MappingWriteLock();
+
+        let ranges = self.SwappableRangesLocked();
+        if ranges.len() == 0 {
+            return 0;
+        }
+
+        // the scan goes on from the cursor and wraps at the last range
+        let cursor = self.zswapCursor.load(Ordering::Relaxed);
+        let range = match ranges.iter().find(|r| r.End() > cursor) {
+            None => ranges[0],
+            Some(r) => *r,
+        };
+        let start = if range.Start() > cursor {
+            range.Start()
+        } else {
+            cursor
+        };
+        let end = if range.End() - start > span {
+            start + span
+        } else {
+            range.End()
+        };
+        self.zswapCursor.store(end, Ordering::Relaxed);
+
+        let mut cold = Vec::new();
+        {
+            let pt = self.pagetable.write();
+            let _ = pt.pt.Traverse(
+                Addr(start),
+                Addr(end),
+                |entry, vaddr| {
+                    let flags = entry.flags();
+                    if !flags.contains(PageTableFlags::PRESENT) {
+                        return;
+                    }
+
+                    if flags.contains(PageTableFlags::ACCESSED) {
+                        entry.set_flags(flags - PageTableFlags::ACCESSED);
+                        Invlpg(vaddr);
+                        return;
+                    }
+
+                    // the page shared with another mm or a file, or pinned
+                    // for a host IO, stays
+                    let phyAddr = entry.addr().as_u64();
+                    match PAGE_MGR.GetRef(phyAddr) {
+                        Ok(1) => (),
+                        _ => return,
+                    }
+
+                    entry.set_flags(flags - PageTableFlags::PRESENT);
+                    Invlpg(vaddr);
+                    cold.push((vaddr, phyAddr, flags));
+                },
+                false,
+            );
+
+            if cold.len() > 0 {
+                pt.pt.EnableTlbShootdown();
+            }
+        }
+
+        if cold.len() == 0 {
+            return 0;
+        }
+
+        // no vcpu writes the cold pages once they are compressed
+        self.TlbShootdown();
+
+        let mut swapped = 0;
+        let pt = self.pagetable.write();
+        for (vaddr, phyAddr, flags) in cold {
+            let handle = ZSWAP.Store(phyAddr);
+            let _ = pt.pt.Traverse(
+                Addr(vaddr),
+                Addr(vaddr + MemoryDef::PAGE_SIZE),
+                |entry, _| match handle {
+                    None => entry.set_flags(flags),
+                    Some(handle) => entry.set_addr(
+                        PhysAddr::new(handle),
+                        (flags - PageTableFlags::PRESENT - PageTableFlags::DIRTY) | SWAP_ENTRY_FLAG,
+                    ),
+                },
+                true,
+            );
+
+            if handle.is_some() {
+                PAGE_MGR.DerefPage(phyAddr);
+                swapped += 1;
+            }
+        }
+
+        return swapped;
+    }
+
+    // SwapInLocked faults in the page at pageAddr from the compressed tier,
+    // returns false if it is not swapped out.
+    pub fn SwapInLocked(&self, vma: &VMA, pageAddr: u64) -> Result<bool> {
+        let handle = match self.pagetable.read().pt.SwapHandle(pageAddr) {
+            None => return Ok(false),
+            Some(handle) => handle,
+        };
+
+        let page = PAGE_MGR.AllocPage(true)?;
+        match ZSWAP.Load(handle, page) {
+            Err(e) => {
+                PAGE_MGR.DerefPage(page);
+                return Err(e);
+            }
+            Ok(()) => (),
+        }
+
+        // the swap entry is replaced, which releases its handle
+        let exec = vma.effectivePerms.Exec();
+        if vma.effectivePerms.Write() {
+            self.MapPageWriteLocked(pageAddr, page, exec);
+        } else {
+            self.MapPageReadLocked(pageAddr, page, exec);
+        }
+
+        PAGE_MGR.DerefPage(page);
+        return Ok(true);
+    }
+}
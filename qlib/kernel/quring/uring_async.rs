@@ -28,6 +28,7 @@ use super::super::super::uring::opcode;
 use super::super::super::uring::opcode::*;
 use super::super::super::uring::squeue;
 use super::super::fs::file::*;
+use super::super::fs::host::writeback::*;
 use super::super::kernel::aio::aio_context::*;
 use super::super::kernel::async_wait::*;
 use super::super::kernel::eventfd::*;
@@ -64,6 +65,7 @@ pub enum AsyncOps {
     AsyncEpollCtl(AsyncEpollCtl),
     AsyncSend(AsyncSend),
     PollHostEpollWait(PollHostEpollWait),
+    AsyncSyncFileRange(AsyncSyncFileRange),
     None,
 }
 
@@ -92,6 +94,7 @@ impl AsyncOps {
             AsyncOps::AsyncEpollCtl(ref msg) => return msg.SEntry(),
             AsyncOps::AsyncSend(ref msg) => return msg.SEntry(),
             AsyncOps::PollHostEpollWait(ref msg) => return msg.SEntry(),
+            AsyncOps::AsyncSyncFileRange(ref msg) => return msg.SEntry(),
             AsyncOps::None => (),
         };
 
@@ -122,6 +125,7 @@ impl AsyncOps {
             AsyncOps::AsyncEpollCtl(ref mut msg) => msg.Process(result),
             AsyncOps::AsyncSend(ref mut msg) => msg.Process(result),
             AsyncOps::PollHostEpollWait(ref mut msg) => msg.Process(result),
+            AsyncOps::AsyncSyncFileRange(ref mut msg) => msg.Process(result),
             AsyncOps::None => {
                 //panic!("AsyncOps::None SEntry fail")
                 panic!("AsyncOps::None SEntry fail result {} id {}", result, id);
@@ -160,6 +164,7 @@ impl AsyncOps {
             AsyncOps::AsyncEpollCtl(_) => return 20,
             AsyncOps::AsyncSend(_) => return 21,
             AsyncOps::PollHostEpollWait(_) => return 22,
+            AsyncOps::AsyncSyncFileRange(_) => return 23,
             AsyncOps::None => (),
         };
 
@@ -464,8 +469,12 @@ impl AsyncWritev {
 
 pub struct AsyncBufWrite {
     pub fd: i32,
+    // the writeback generation of the file of the fd
+    pub gen: u64,
     pub buf: DataBuff,
     pub offset: i64,
+    // the bytes written so far
+    pub done: usize,
     pub lockGuard: Option<QAsyncLockGuard>,
 }
 
@@ -474,17 +483,35 @@ impl AsyncBufWrite {
         //let op = Write::new(types::Fd(self.fd), self.addr as * const u8, self.len as u32);
         let op = opcode::Write::new(
             types::Fd(self.fd),
-            self.buf.Ptr() as *const u8,
-            self.buf.Len() as u32,
+            (self.buf.Ptr() + self.done as u64) as *const u8,
+            (self.buf.Len() - self.done) as u32,
         )
-        .offset(self.offset);
+        .offset(self.offset + self.done as i64);
 
         return op.build().flags(squeue::Flags::FIXED_FILE);
     }
 
     pub fn Process(&mut self, result: i32) -> bool {
-        assert!(result as usize == self.buf.Len(), "result is {}, self.buf.len() is {}, fd is {}",
-            result, self.buf.Len(), self.fd);
+        // the writer has gone, the error is reported by the next fsync
+        if result <= 0 {
+            let errno = if result == 0 { SysErr::EIO } else { -result };
+            error!(
+                "AsyncBufWrite fd {} offset {} fail {}",
+                self.fd,
+                self.offset + self.done as i64,
+                errno
+            );
+            WRITEBACK.SetError(self.fd, self.gen, errno);
+            self.lockGuard = None;
+            return false;
+        }
+
+        self.done += result as usize;
+        if self.done < self.buf.Len() {
+            // short write, submit the rest
+            return true;
+        }
+
         self.lockGuard = None;
         return false;
     }
@@ -492,8 +519,10 @@ impl AsyncBufWrite {
     pub fn New(fd: i32, buf: DataBuff, offset: i64, lockGuard: QAsyncLockGuard) -> Self {
         return Self {
             fd,
+            gen: WRITEBACK.Gen(fd),
             buf,
             offset,
+            done: 0,
             lockGuard: Some(lockGuard),
         };
     }
@@ -713,12 +742,16 @@ impl AsyncAccept {
             return false;
         }
 
-        NewSocket(result);
-        let sockBuf = Arc::new(SocketBuff::default());
+        // dropped by the packet filter, accept the next one
+        if NewSocket(result) < 0 {
+            self.len = 16;
+            return true;
+        }
+
         let (trigger, hasSpace) = self
             .acceptQueue
             .lock()
-            .EnqSocket(result, self.addr, self.len, sockBuf);
+            .EnqSocket(result, self.addr, self.len, None);
         if trigger {
             self.queue.Notify(EventMaskFromLinux(READABLE_EVENT as u32));
         }
@@ -1088,11 +1121,11 @@ impl AIORead {
         cbAddr: u64,
         eventfops: Option<EventOperations>,
     ) -> Result<Self> {
-        let iov = IoVec::NewFromAddr(cb.buf, cb.bytes as usize);
+        let iov = task.UserIoVec(cb.buf, cb.bytes as usize)?;
 
         let iovs = vec![iov];
         task.FixPermissionForIovs(&iovs, true)?;
-        let buf = DataBuff::New(cb.bytes as usize);
+        let buf = DataBuff::New(iovs[0].len);
 
         return Ok(Self {
             fd: cb.fd as i32,
@@ -1342,6 +1375,40 @@ impl PollHostEpollWait {
     }
 }
 
+// AsyncSyncFileRange starts the writeback of the dirty data of a host file
+// without waiting for it.
+pub struct AsyncSyncFileRange {
+    pub fd: i32,
+    // the writeback generation of the file of the fd
+    pub gen: u64,
+    // the dirty bytes under writeback
+    pub bytes: u64,
+}
+
+impl AsyncSyncFileRange {
+    pub fn SEntry(&self) -> squeue::Entry {
+        let op = opcode::SyncFileRange::new(types::Fd(self.fd), 0)
+            .offset(0)
+            .flags(SyncFileRangeFlags::SYNC_FILE_RANGE_WRITE);
+
+        return op.build().flags(squeue::Flags::FIXED_FILE);
+    }
+
+    pub fn Process(&mut self, result: i32) -> bool {
+        if result < 0 {
+            info!("AsyncSyncFileRange::Process fd {} result {}", self.fd, result);
+            WRITEBACK.SetError(self.fd, self.gen, -result);
+        }
+
+        WRITEBACK.Done(self.bytes);
+        return false;
+    }
+
+    pub fn New(fd: i32, gen: u64, bytes: u64) -> Self {
+        return Self { fd, gen, bytes };
+    }
+}
+
 #[repr(C)]
 #[repr(packed)]
 #[derive(Debug, Default, Copy, Clone)]
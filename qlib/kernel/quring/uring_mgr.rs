@@ -12,8 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::qlib::mutex::*;
+use alloc::collections::vec_deque::VecDeque;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::sync::atomic;
+use core::sync::atomic::AtomicBool;
 use core::sync::atomic::AtomicU64;
 use core::sync::atomic::Ordering;
 
@@ -27,11 +31,13 @@ use super::super::fs::file::*;
 use super::super::task::*;
 use super::super::taskMgr::*;
 
+use super::super::super::linux::time::MILLISECOND;
 use super::super::super::linux_def::*;
 use super::super::super::socket_buf::*;
 use super::super::super::uring::util::*;
 use super::super::super::vcpu_mgr::*;
 use super::super::kernel::async_wait::*;
+use super::super::kernel::timer::MonotonicNow;
 use super::super::kernel::waiter::qlock::*;
 use super::super::kernel::waiter::*;
 use super::super::socket::hostinet::socket::*;
@@ -126,6 +132,9 @@ pub type IOUringRef = ObjectRef<QUring>;
 pub struct QUring {
     pub uringsAddr: AtomicU64,
     pub asyncMgr: UringAsyncMgr,
+    // the submissions are held while the sandbox is paused, see Quiesce
+    pub quiesced: AtomicBool,
+    pub held: QMutex<VecDeque<squeue::Entry>>,
 }
 
 impl QUring {
@@ -135,6 +144,8 @@ impl QUring {
         let ret = QUring {
             asyncMgr: UringAsyncMgr::New(size),
             uringsAddr: AtomicU64::new(0),
+            quiesced: AtomicBool::new(false),
+            held: QMutex::new(VecDeque::new()),
         };
 
         return ret;
@@ -537,6 +548,33 @@ impl QUring {
         }
     }
 
+    // NextCompleteEntries pops at most batch.len() completions with one lock
+    // of the completion queue.
+    pub fn NextCompleteEntries(&self, batch: &mut [cqueue::Entry]) -> usize {
+        let mut count = 0;
+        if super::super::SHARESPACE.config.read().UringBuf {
+            let mut cq = self.IOUring().completeq.lock();
+            while count < batch.len() {
+                match cq.pop_front() {
+                    None => break,
+                    Some(cqe) => batch[count] = cqe,
+                }
+                count += 1;
+            }
+        } else {
+            let mut cq = self.IOUring().cq.lock();
+            while count < batch.len() {
+                match cq.next() {
+                    None => break,
+                    Some(cqe) => batch[count] = cqe,
+                }
+                count += 1;
+            }
+        }
+
+        return count;
+    }
+
     pub fn ProcessOne(&self) -> bool {
         if super::super::Shutdown() {
             return false;
@@ -553,21 +591,29 @@ impl QUring {
         }
     }
 
+    // the completions are reaped URING_REAP_BATCH a time, the completion
+    // queue lock is not held when they are processed. One drain reaps at most
+    // URING_REAP_MAX so the vcpu gets back to its tasks, the callers drain
+    // again while the count is not 0.
+    pub const URING_REAP_BATCH: usize = 32;
+    pub const URING_REAP_MAX: usize = 512;
+
     pub fn DrainCompletionQueue(&self) -> usize {
         let mut count = 0;
-        loop {
+        let mut batch: [cqueue::Entry; Self::URING_REAP_BATCH] = Default::default();
+        while count < Self::URING_REAP_MAX {
             if super::super::Shutdown() {
                 return 0;
             }
 
-            let cqe = self.NextCompleteEntry();
+            let n = self.NextCompleteEntries(&mut batch);
+            for cqe in &batch[..n] {
+                self.Process(cqe);
+            }
 
-            match cqe {
-                None => break,
-                Some(cqe) => {
-                    count += 1;
-                    self.Process(&cqe);
-                }
+            count += n;
+            if n < batch.len() {
+                break;
             }
         }
 
@@ -590,34 +636,7 @@ impl QUring {
     }
 
     pub fn UringPush(&self, entry: squeue::Entry) {
-        if super::super::SHARESPACE.config.read().UringBuf {
-            let mut s = self.IOUring().submitq.lock();
-            s.push_back(entry);
-        } else {
-            loop {
-                let mut s = self.IOUring().sq.lock();
-                if s.freeSlot() < Self::SUBMISSION_QUEUE_FREE_COUNT {
-                    drop(s);
-                    super::super::super::ShareSpace::Yield();
-                    error!("AUringCall1: submission full... ");
-                    continue;
-                }
-
-                unsafe {
-                    match s.push(entry) {
-                        Ok(_) => (),
-                        Err(_) => panic!("AUringCall submission queue is full"),
-                    }
-                }
-
-                break;
-            }
-        }
-
-        self.IOUring()
-            .Submit()
-            .expect("QUringIntern::submit fail");
-        return;
+        self.PushEntries(&[entry]);
     }
 
     pub fn AUringCall(&self, entry: squeue::Entry) {
@@ -625,32 +644,37 @@ impl QUring {
     }
 
     pub fn AUringCallLinked(&self, entry1: squeue::Entry, entry2: squeue::Entry) {
+        self.PushEntries(&[entry1.flags(squeue::Flags::IO_LINK), entry2]);
+    }
+
+    // PushEntries submits the entries together, the linked ones stay adjacent.
+    pub fn PushEntries(&self, entries: &[squeue::Entry]) {
+        {
+            let mut held = self.held.lock();
+            if self.quiesced.load(Ordering::Acquire) {
+                held.extend(entries.iter().cloned());
+                return;
+            }
+        }
+
         if super::super::SHARESPACE.config.read().UringBuf {
             let mut s = self.IOUring().submitq.lock();
-            s.push_back(entry1.flags(squeue::Flags::IO_LINK));
-            s.push_back(entry2);
+            s.extend(entries.iter().cloned());
         } else {
             loop {
                 let mut s = self.IOUring().sq.lock();
-                if s.freeSlot() < Self::SUBMISSION_QUEUE_FREE_COUNT + 1 {
+                if s.freeSlot() < Self::SUBMISSION_QUEUE_FREE_COUNT + entries.len() - 1 {
                     drop(s);
                     super::super::super::ShareSpace::Yield();
-                    error!("AUringCallLinked: submission full... idx");
+                    error!("AUringCall: submission full... ");
                     continue;
                 }
 
-                unsafe {
-                    match s.push(entry1.flags(squeue::Flags::IO_LINK)) {
-                        Ok(_) => (),
-                        Err(_e) => {
-                            panic!("AUringCallLinked push fail 1 ...");
-                        }
-                    }
-
-                    match s.push(entry2) {
-                        Ok(_) => (),
-                        Err(_e) => {
-                            panic!("AUringCallLinked push fail 2 ...");
+                for entry in entries {
+                    unsafe {
+                        match s.push(entry.clone()) {
+                            Ok(_) => (),
+                            Err(_) => panic!("AUringCall submission queue is full"),
                         }
                     }
                 }
@@ -659,9 +683,58 @@ impl QUring {
             }
         }
 
-        self.IOUring()
-            .Submit()
-            .expect("QUringIntern::submit fail");
+        self.IOUring().Submit().expect("QUringIntern::submit fail");
         return;
     }
+
+    // the time Quiesce waits for the submitted ops, and the time without
+    // completion after which they are taken as quiesced
+    pub const QUIESCE_TIMEOUT: i64 = 200 * MILLISECOND;
+    pub const QUIESCE_IDLE: i64 = 2 * MILLISECOND;
+
+    // Quiesce holds the new submissions and waits till the host has taken the
+    // submitted ones and their completions are processed, so that the
+    // SocketBuffs are not left half updated when the sandbox is frozen. The
+    // ops still in flight after that, e.g. the polls and the timeouts, move no
+    // data. It returns false if the uring is still busy after QUIESCE_TIMEOUT.
+    pub fn Quiesce(&self) -> bool {
+        {
+            let _held = self.held.lock();
+            self.quiesced.store(true, Ordering::Release);
+        }
+
+        let start = MonotonicNow();
+        let mut lastActive = start;
+        loop {
+            let now = MonotonicNow();
+            let uring = self.IOUring();
+            let submitted = uring.pendingCnt.load(Ordering::Acquire) == 0
+                && uring.SqLen() == 0
+                && uring.submitq.lock().len() == 0;
+            if self.DrainCompletionQueue() > 0 || !submitted {
+                lastActive = now;
+            } else if now - lastActive >= Self::QUIESCE_IDLE {
+                return true;
+            }
+
+            if now - start >= Self::QUIESCE_TIMEOUT {
+                return false;
+            }
+
+            super::super::super::ShareSpace::Yield();
+        }
+    }
+
+    // Unquiesce submits the entries held since Quiesce.
+    pub fn Unquiesce(&self) {
+        let entries: Vec<squeue::Entry> = {
+            let mut held = self.held.lock();
+            self.quiesced.store(false, Ordering::Release);
+            held.drain(..).collect()
+        };
+
+        if entries.len() > 0 {
+            self.PushEntries(&entries);
+        }
+    }
 }
@@ -13,7 +13,8 @@ pub fn Ioctl(task: &Task, ep: &BoundEndpoint, _fd: i32, request: u64, val: u64)
     let flags = request as i32;
 
     match flags as u64 {
-        /*LibcConst::SIOCGIFFLAGS
+        LibcConst::SIOCGIFFLAGS
+        | LibcConst::SIOCGIFADDR
         | LibcConst::SIOCGIFBRDADDR
         | LibcConst::SIOCGIFDSTADDR
         | LibcConst::SIOCGIFHWADDR
@@ -24,18 +25,25 @@ pub fn Ioctl(task: &Task, ep: &BoundEndpoint, _fd: i32, request: u64, val: u64)
         | LibcConst::SIOCGIFNAME
         | LibcConst::SIOCGIFNETMASK
         | LibcConst::SIOCGIFTXQLEN => {
+            // a unix socket has no host fd, the interface queries go to the
+            // shared host socket
             let addr = val;
-            let bep = ep.BaseEndpoint();
-            bep.HostIoctlIFReq(task, request, addr)?;
+            DUMMY_HOST_SOCKET.HostIoctlIFReq(task, request, addr)?;
 
             return Ok(());
-        }*/
+        }
         LibcConst::SIOCGIFCONF => {
             let addr = val;
             DUMMY_HOST_SOCKET.HostIoctlIFConf(task, request, addr)?;
 
             return Ok(());
         }
+        LibcConst::SIOCETHTOOL => {
+            let addr = val;
+            DUMMY_HOST_SOCKET.HostIoctlEthtool(task, request, addr)?;
+
+            return Ok(());
+        }
         LibcConst::TIOCINQ => {
             let mut v = SockOpt::ReceiveQueueSizeOption(0);
             ep.GetSockOpt(&mut v)?;
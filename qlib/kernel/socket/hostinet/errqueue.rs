@@ -0,0 +1,210 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+use core::ptr;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::common::*;
+use super::super::super::super::linux::socket::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::super::mutex::*;
+use super::super::super::task::*;
+use super::super::super::tcpip::tcpip::*;
+use super::super::super::Kernel::HostSpace;
+use super::super::control::*;
+use super::socket::*;
+
+// The error queue of the sockets, read with MSG_ERRQUEUE. The ICMP errors
+// of IP_RECVERR/IPV6_RECVERR and the TX timestamps of SO_TIMESTAMPING are
+// queued by the host socket, the guest reads them from it with their
+// extended errors as they are, the cmsg ABI is the one of the host.
+// A MSG_ZEROCOPY send is copied to the host as any other send, the host
+// never sees the flag and so never queues its completion. The completions
+// are queued here instead, with SO_EE_CODE_ZEROCOPY_COPIED as Linux does
+// when it falls back to a copy, the application can reuse its buffer at the
+// return of the send anyway.
+
+pub const SO_EE_ORIGIN_NONE: u8 = 0;
+pub const SO_EE_ORIGIN_LOCAL: u8 = 1;
+pub const SO_EE_ORIGIN_ICMP: u8 = 2;
+pub const SO_EE_ORIGIN_ICMP6: u8 = 3;
+pub const SO_EE_ORIGIN_TXSTATUS: u8 = 4;
+pub const SO_EE_ORIGIN_ZEROCOPY: u8 = 5;
+pub const SO_EE_ORIGIN_TXTIME: u8 = 6;
+pub const SO_EE_ORIGIN_TIMESTAMPING: u8 = SO_EE_ORIGIN_TXSTATUS;
+
+pub const SO_EE_CODE_ZEROCOPY_COPIED: u8 = 1;
+
+pub const IP_RECVERR: i32 = 11;
+pub const IPV6_RECVERR: i32 = 25;
+
+// SockExtendedErr is struct sock_extended_err from linux/errqueue.h.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SockExtendedErr {
+    pub Errno: u32,
+    pub Origin: u8,
+    pub Type: u8,
+    pub Code: u8,
+    pub Pad: u8,
+    pub Info: u32,
+    pub Data: u32,
+}
+
+#[derive(Default)]
+pub struct ErrQueue {
+    // the application set SO_ZEROCOPY
+    pub zerocopy: AtomicBool,
+    // the id of the next MSG_ZEROCOPY send and the range of the completions
+    // not read yet, the consecutive ones are reported at once as Linux does
+    pub zerocopyIds: QMutex<(u32, Option<(u32, u32)>)>,
+}
+
+impl ErrQueue {
+    pub fn SetZeroCopy(&self, enable: bool) {
+        self.zerocopy.store(enable, Ordering::Relaxed);
+    }
+
+    // ZeroCopyDone queues the completion of count MSG_ZEROCOPY sends, it
+    // returns false if SO_ZEROCOPY is not set and the flag is ignored.
+    pub fn ZeroCopyDone(&self, count: u32) -> bool {
+        if !self.zerocopy.load(Ordering::Relaxed) || count == 0 {
+            return false;
+        }
+
+        let mut ids = self.zerocopyIds.lock();
+        let lo = ids.0;
+        let hi = lo.wrapping_add(count - 1);
+        ids.0 = hi.wrapping_add(1);
+        ids.1 = match ids.1 {
+            None => Some((lo, hi)),
+            Some((first, _)) => Some((first, hi)),
+        };
+
+        return true;
+    }
+
+    pub fn HasPending(&self) -> bool {
+        return self.zerocopyIds.lock().1.is_some();
+    }
+
+    pub fn TakeZeroCopy(&self) -> Option<(u32, u32)> {
+        return self.zerocopyIds.lock().1.take();
+    }
+}
+
+// EncodeExtendedErr returns the cmsg of err, truncated to controlDataLen.
+pub fn EncodeExtendedErr(
+    family: i32,
+    err: &SockExtendedErr,
+    controlDataLen: usize,
+) -> (i32, Vec<u8>) {
+    let (level, typ) = if family == AFType::AF_INET6 {
+        (SOL_IPV6, IPV6_RECVERR)
+    } else {
+        (SOL_IP, IP_RECVERR)
+    };
+
+    let size = core::mem::size_of::<SockExtendedErr>();
+    let len = SIZE_OF_CONTROL_MESSAGE_HEADER + size;
+    if controlDataLen < len {
+        return (MsgType::MSG_CTRUNC, Vec::new());
+    }
+
+    let mut control: Vec<u8> = vec![0; CMsgSpace(size).min(controlDataLen)];
+    let h = ControlMessageHeader {
+        Length: len as u64,
+        Level: level,
+        Type: typ,
+    };
+    unsafe {
+        ptr::write_unaligned(&mut control[0] as *mut _ as *mut ControlMessageHeader, h);
+        ptr::write_unaligned(
+            &mut control[SIZE_OF_CONTROL_MESSAGE_HEADER] as *mut _ as *mut SockExtendedErr,
+            *err,
+        );
+    }
+
+    return (0, control);
+}
+
+impl SocketOperations {
+    // RecvErrQueue reads the first error of the error queue of the socket,
+    // it never blocks as on Linux.
+    pub fn RecvErrQueue(
+        &self,
+        task: &Task,
+        dsts: &mut [IoVec],
+        senderRequested: bool,
+        controlDataLen: usize,
+    ) -> Result<(i64, i32, Option<(SockAddr, usize)>, Vec<u8>)> {
+        if let Some((lo, hi)) = self.errQueue.TakeZeroCopy() {
+            let err = SockExtendedErr {
+                Origin: SO_EE_ORIGIN_ZEROCOPY,
+                Code: SO_EE_CODE_ZEROCOPY_COPIED,
+                Info: lo,
+                Data: hi,
+                ..Default::default()
+            };
+            let (flags, control) = EncodeExtendedErr(self.family, &err, controlDataLen);
+            return Ok((0, flags | MsgType::MSG_ERRQUEUE, None, control));
+        }
+
+        // the payload of an error is the head of the packet in error
+        let size = IoVec::NumBytes(dsts);
+        let buf = DataBuff::New(size);
+        let iovs = buf.Iovs(size);
+
+        let mut msgHdr = MsgHdr::default();
+        msgHdr.iov = &iovs[0] as *const _ as u64;
+        msgHdr.iovLen = iovs.len();
+
+        let mut addr: [u8; SIZEOF_SOCKADDR] = [0; SIZEOF_SOCKADDR];
+        if senderRequested {
+            msgHdr.msgName = &mut addr[0] as *mut _ as u64;
+            msgHdr.nameLen = SIZEOF_SOCKADDR as u32;
+        }
+
+        let mut controlVec: Vec<u8> = vec![0; controlDataLen];
+        msgHdr.msgControlLen = controlDataLen;
+        if controlDataLen != 0 {
+            msgHdr.msgControl = &mut controlVec[0] as *mut _ as u64;
+        }
+
+        let res = HostSpace::IORecvMsg(
+            self.fd,
+            &mut msgHdr as *mut _ as u64,
+            MsgType::MSG_ERRQUEUE | MsgType::MSG_DONTWAIT,
+            false,
+        ) as i32;
+        if res < 0 {
+            return Err(Error::SysError(-res as i32));
+        }
+
+        let senderAddr = if senderRequested && msgHdr.nameLen >= 4 {
+            let addr = GetAddr(addr[0] as i16, &addr[0..msgHdr.nameLen as usize])?;
+            let l = addr.Len();
+            Some((addr, l))
+        } else {
+            None
+        };
+
+        controlVec.resize(msgHdr.msgControlLen, 0);
+        let count = (res as usize).min(buf.buf.len());
+        task.CopyDataOutToIovs(&buf.buf[0..count], dsts, false)?;
+        return Ok((res as i64, msgHdr.msgFlags, senderAddr, controlVec));
+    }
+}
@@ -12,9 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod errqueue;
+pub mod offload;
 pub mod rdma_socket;
 pub mod socket;
 pub mod socket_buf;
+pub mod tls_offload;
+pub mod tproxy;
+pub mod txtime;
 
 pub fn Init() {
     self::socket::Init();
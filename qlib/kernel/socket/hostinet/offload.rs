@@ -0,0 +1,429 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::vec_deque::VecDeque;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::super::mutex::*;
+use super::super::super::kernel::time::*;
+use super::super::super::kernel::waiter::*;
+use super::super::super::task::*;
+use super::super::super::tcpip::tcpip::*;
+use super::super::super::Kernel;
+use super::super::super::Kernel::HostSpace;
+use super::super::super::SHARESPACE;
+use super::super::control::*;
+use super::socket::*;
+
+// The GRO and the TSO of the udp sockets, enabled with Config.UdpOffload.
+// The sockets of the guest are host ones, the host aggregates and segments
+// their packets at its devices already, what costs is the qcall and the copy
+// of each datagram between the guest and the host.
+//   TSO: the datagrams of a sendmmsg(2) batch with the same size and address
+//   are sent as one datagram, segmented by the host with UDP_SEGMENT.
+//   GRO: the host socket coalesces the datagrams of a peer with UDP_GRO, they
+//   are split back here and queued for the next receives of the socket.
+// An application setting UDP_GRO itself gets the coalesced datagrams as on
+// the host.
+
+pub const SOL_UDP: i32 = 17;
+pub const UDP_SEGMENT: i32 = 103;
+pub const UDP_GRO: i32 = 104;
+
+// the limits of a segmented send, UDP_MAX_SEGMENTS is 64 on the older hosts
+pub const UDP_MAX_SEGMENTS: usize = 64;
+pub const UDP_GSO_MAX_SIZE: usize = 65000;
+// the largest coalesced datagram received
+pub const UDP_GRO_MAX_SIZE: usize = 65536;
+
+#[derive(Debug, Clone, Default)]
+pub struct GroSegment {
+    pub addr: Vec<u8>,
+    pub data: Vec<u8>,
+    pub control: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct UdpOffload {
+    // the socket is a udp one, its batches are segmented by the host
+    pub gso: AtomicBool,
+    // the host socket coalesces the datagrams received
+    pub gro: AtomicBool,
+    // the application set UDP_GRO
+    pub userGro: AtomicBool,
+    pub pending: QMutex<VecDeque<GroSegment>>,
+}
+
+impl UdpOffload {
+    pub fn New(fd: i32, family: i32, stype: i32) -> Self {
+        let ret = Self::default();
+        if !SHARESPACE.config.read().UdpOffload
+            || stype != SockType::SOCK_DGRAM
+            || (family != AFType::AF_INET && family != AFType::AF_INET6)
+        {
+            return ret;
+        }
+
+        match Kernel::GetSockOptI32(fd, LibcConst::SOL_SOCKET as _, LibcConst::SO_PROTOCOL as _) {
+            Ok(proto) if proto == LibcConst::IPPROTO_UDP as i32 => (),
+            _ => return ret,
+        }
+
+        ret.gso.store(true, Ordering::Relaxed);
+        let val: i32 = 1;
+        let res = HostSpace::SetSockOpt(fd, SOL_UDP, UDP_GRO, &val as *const _ as u64, 4);
+        ret.gro.store(res >= 0, Ordering::Relaxed);
+        return ret;
+    }
+
+    // Gro returns whether the receives of the socket are split here.
+    pub fn Gro(&self) -> bool {
+        return self.gro.load(Ordering::Relaxed) && !self.userGro.load(Ordering::Relaxed);
+    }
+
+    pub fn Gso(&self) -> bool {
+        return self.gso.load(Ordering::Relaxed);
+    }
+
+    // SetUserGro takes the UDP_GRO of the application, it returns false if the
+    // host socket has to set it.
+    pub fn SetUserGro(&self, enable: bool) -> bool {
+        if !self.gro.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        self.userGro.store(enable, Ordering::Relaxed);
+        return true;
+    }
+
+    pub fn UserGro(&self) -> Option<bool> {
+        if !self.gro.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        return Some(self.userGro.load(Ordering::Relaxed));
+    }
+
+    pub fn HasPending(&self) -> bool {
+        return self.pending.lock().len() > 0;
+    }
+
+    // PendingSize returns the size of the next datagram queued.
+    pub fn PendingSize(&self) -> Option<usize> {
+        return self.pending.lock().front().map(|s| s.data.len());
+    }
+}
+
+// StripGro returns the segment size of the UDP_GRO control message, 0 if
+// there is none, and the other control messages.
+pub fn StripGro(control: &[u8]) -> (usize, Vec<u8>) {
+    let mut segSize = 0;
+    let mut ret = Vec::with_capacity(control.len());
+    let mut i = 0;
+    while i + SIZE_OF_CONTROL_MESSAGE_HEADER <= control.len() {
+        let h = unsafe {
+            &*(control[i..i + SIZE_OF_CONTROL_MESSAGE_HEADER].as_ptr()
+                as *const ControlMessageHeader)
+        };
+
+        let len = h.Length as usize;
+        if len < SIZE_OF_CONTROL_MESSAGE_HEADER || i + len > control.len() {
+            break;
+        }
+
+        let next = core::cmp::min(i + CMsgAlign(len), control.len());
+        if h.Level == SOL_UDP && h.Type == UDP_GRO && len >= SIZE_OF_CONTROL_MESSAGE_HEADER + 4 {
+            let v = &control[i + SIZE_OF_CONTROL_MESSAGE_HEADER..];
+            segSize = i32::from_ne_bytes([v[0], v[1], v[2], v[3]]) as usize;
+        } else {
+            ret.extend_from_slice(&control[i..next]);
+        }
+
+        i = next;
+    }
+
+    return (segSize, ret);
+}
+
+impl SocketOperations {
+    // RecvGro receives the next datagram of a socket with GRO, from the ones
+    // queued or else from the host.
+    pub fn RecvGro(
+        &self,
+        task: &Task,
+        dsts: &mut [IoVec],
+        flags: i32,
+        deadline: Option<Time>,
+        senderRequested: bool,
+        controlDataLen: usize,
+    ) -> Result<(i64, i32, Option<(SockAddr, usize)>, Vec<u8>)> {
+        loop {
+            // the segment is copied out of the lock, the copy can fault
+            let seg = {
+                let mut pending = self.offload.pending.lock();
+                if flags & MsgType::MSG_PEEK != 0 {
+                    pending.front().cloned()
+                } else {
+                    pending.pop_front()
+                }
+            };
+
+            if let Some(seg) = seg {
+                let size = IoVec::NumBytes(dsts);
+                let n = core::cmp::min(size, seg.data.len());
+                task.CopyDataOutToIovs(&seg.data[0..n], dsts, false)?;
+
+                let mut msgFlags = 0;
+                if n < seg.data.len() {
+                    msgFlags |= MsgType::MSG_TRUNC;
+                }
+
+                let ret = if flags & MsgType::MSG_TRUNC != 0 {
+                    seg.data.len()
+                } else {
+                    n
+                };
+
+                let senderAddr = if senderRequested && seg.addr.len() >= 4 {
+                    let addr = GetAddr(seg.addr[0] as i16, &seg.addr[..])?;
+                    let l = addr.Len();
+                    Some((addr, l))
+                } else {
+                    None
+                };
+
+                let mut control = seg.control;
+                if control.len() > controlDataLen {
+                    control.resize(controlDataLen, 0);
+                    msgFlags |= MsgType::MSG_CTRUNC;
+                }
+
+                return Ok((ret as i64, msgFlags, senderAddr, control));
+            }
+
+            self.RecvGroHost(task, flags, deadline)?;
+        }
+    }
+
+    // RecvGroHost receives a datagram from the host and queues its segments.
+    fn RecvGroHost(&self, task: &Task, flags: i32, deadline: Option<Time>) -> Result<()> {
+        let buf = DataBuff::New(UDP_GRO_MAX_SIZE);
+        let iovs = buf.Iovs(UDP_GRO_MAX_SIZE);
+        let mut addr: [u8; SIZEOF_SOCKADDR] = [0; SIZEOF_SOCKADDR];
+        // the control messages the application asked for with the sockopts, and
+        // the one of UDP_GRO
+        let mut controlVec: Vec<u8> = vec![0; 256];
+
+        let mut msgHdr = MsgHdr::default();
+        msgHdr.iov = &iovs[0] as *const _ as u64;
+        msgHdr.iovLen = iovs.len();
+        msgHdr.msgName = &mut addr[0] as *mut _ as u64;
+        msgHdr.nameLen = SIZEOF_SOCKADDR as u32;
+        msgHdr.msgControl = &mut controlVec[0] as *mut _ as u64;
+        msgHdr.msgControlLen = controlVec.len();
+
+        let general = task.blocker.generalEntry.clone();
+        self.EventRegister(task, &general, EVENT_READ);
+        defer!(self.EventUnregister(task, &general));
+
+        let mut res = HostSpace::IORecvMsg(
+            self.fd,
+            &mut msgHdr as *mut _ as u64,
+            MsgType::MSG_DONTWAIT,
+            false,
+        ) as i32;
+
+        while res == -SysErr::EWOULDBLOCK && flags & MsgType::MSG_DONTWAIT == 0 {
+            match task.blocker.BlockWithMonoTimer(true, deadline) {
+                Err(Error::ErrInterrupted) => {
+                    return Err(Error::SysError(SysErr::ERESTARTSYS));
+                }
+                Err(Error::SysError(SysErr::ETIMEDOUT)) => {
+                    return Err(Error::SysError(SysErr::EAGAIN));
+                }
+                Err(e) => {
+                    return Err(e);
+                }
+                _ => (),
+            }
+
+            msgHdr.nameLen = SIZEOF_SOCKADDR as u32;
+            msgHdr.msgControlLen = controlVec.len();
+            res = HostSpace::IORecvMsg(
+                self.fd,
+                &mut msgHdr as *mut _ as u64,
+                MsgType::MSG_DONTWAIT,
+                false,
+            ) as i32;
+        }
+
+        if res < 0 {
+            return Err(Error::SysError(-res as i32));
+        }
+
+        let len = res as usize;
+        let addr = addr[0..msgHdr.nameLen as usize].to_vec();
+        let (segSize, control) = StripGro(&controlVec[0..msgHdr.msgControlLen]);
+        let segSize = if segSize == 0 { len } else { segSize };
+
+        let mut pending = self.offload.pending.lock();
+        let mut offset = 0;
+        loop {
+            let end = core::cmp::min(offset + segSize, len);
+            pending.push_back(GroSegment {
+                addr: addr.clone(),
+                data: buf.buf[offset..end].to_vec(),
+                control: control.clone(),
+            });
+
+            offset = end;
+            if offset >= len {
+                break;
+            }
+        }
+
+        return Ok(());
+    }
+
+    // SendGso sends the first datagrams of msgs with the same size and address
+    // as one segmented by the host, it returns the count of them sent, 0 if
+    // there are not 2 of them.
+    pub fn SendGso(
+        &self,
+        task: &Task,
+        msgs: &mut [MMsgHdr],
+        flags: i32,
+        deadline: Option<Time>,
+    ) -> Result<usize> {
+        if !self.offload.Gso() || msgs.len() < 2 {
+            return Ok(0);
+        }
+
+        let mut name: Vec<u8> = Vec::new();
+        let mut segSize = 0;
+        let mut total = 0;
+        let mut srcs = Vec::new();
+        for i in 0..msgs.len() {
+            let msg = &msgs[i].msgHdr;
+            if msg.msgControlLen != 0 || msg.iovLen > UIO_MAXIOV || srcs.len() == UDP_MAX_SEGMENTS {
+                break;
+            }
+
+            let n: Vec<u8> = task.CopyInVec(msg.msgName, msg.nameLen as usize)?;
+            if i > 0 && n != name {
+                break;
+            }
+
+            let iovs = task.IovsFromAddr(msg.iov, msg.iovLen)?;
+            let size = IoVec::NumBytes(&iovs);
+            if i == 0 {
+                if size == 0 {
+                    return Ok(0);
+                }
+
+                name = n;
+                segSize = size;
+            } else if size > segSize || size == 0 {
+                break;
+            }
+
+            if total + size > UDP_GSO_MAX_SIZE {
+                break;
+            }
+
+            total += size;
+            srcs.push((iovs, size));
+
+            // only the last segment is shorter
+            if size < segSize {
+                break;
+            }
+        }
+
+        if srcs.len() < 2 {
+            return Ok(0);
+        }
+
+        let mut buf = DataBuff::New(total);
+        let mut offset = 0;
+        for (iovs, size) in &srcs {
+            task.CopyDataInFromIovs(&mut buf.buf[offset..offset + size], iovs, true)?;
+            offset += size;
+        }
+
+        let mut control: Vec<u8> = vec![0; CMsgSpace(2)];
+        let h = ControlMessageHeader {
+            Length: (SIZE_OF_CONTROL_MESSAGE_HEADER + 2) as u64,
+            Level: SOL_UDP,
+            Type: UDP_SEGMENT,
+        };
+        unsafe {
+            core::ptr::write_unaligned(&mut control[0] as *mut _ as *mut ControlMessageHeader, h)
+        };
+        control[SIZE_OF_CONTROL_MESSAGE_HEADER..SIZE_OF_CONTROL_MESSAGE_HEADER + 2]
+            .copy_from_slice(&(segSize as u16).to_ne_bytes());
+
+        let iovs = buf.Iovs(total);
+        let mut msgHdr = MsgHdr::default();
+        msgHdr.iov = &iovs[0] as *const _ as u64;
+        msgHdr.iovLen = iovs.len();
+        if name.len() > 0 {
+            msgHdr.msgName = &name[0] as *const _ as u64;
+            msgHdr.nameLen = name.len() as u32;
+        }
+        msgHdr.msgControl = &control[0] as *const _ as u64;
+        msgHdr.msgControlLen = control.len();
+
+        let mut res = HostSpace::IOSendMsg(
+            self.fd,
+            &msgHdr as *const _ as u64,
+            flags | MsgType::MSG_DONTWAIT,
+            false,
+        ) as i32;
+        while res == -SysErr::EWOULDBLOCK && flags & MsgType::MSG_DONTWAIT == 0 {
+            let general = task.blocker.generalEntry.clone();
+
+            self.EventRegister(task, &general, EVENT_WRITE);
+            defer!(self.EventUnregister(task, &general));
+            task.blocker.BlockWithMonoTimer(true, deadline)?;
+
+            res = HostSpace::IOSendMsg(
+                self.fd,
+                &msgHdr as *const _ as u64,
+                flags | MsgType::MSG_DONTWAIT,
+                false,
+            ) as i32;
+        }
+
+        // the host or its device can't segment, the datagrams are sent one by one
+        if res == -SysErr::EIO || res == -SysErr::EINVAL || res == -SysErr::ENOPROTOOPT {
+            self.offload.gso.store(false, Ordering::Relaxed);
+            return Ok(0);
+        }
+
+        if res < 0 {
+            return Err(Error::SysError(-res as i32));
+        }
+
+        for i in 0..srcs.len() {
+            msgs[i].msgLen = srcs[i].1 as u32;
+        }
+
+        return Ok(srcs.len());
+    }
+}
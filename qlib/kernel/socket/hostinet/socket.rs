@@ -20,6 +20,7 @@ use core::any::Any;
 use core::fmt;
 use core::ops::Deref;
 use core::ptr;
+use core::slice;
 use core::sync::atomic::AtomicBool;
 use core::sync::atomic::AtomicI64;
 use core::sync::atomic::Ordering;
@@ -27,6 +28,8 @@ use core::sync::atomic::Ordering;
 //use super::super::*;
 use super::super::super::super::common::*;
 use super::super::super::super::linux::netdevice::*;
+use super::super::super::super::linux::socket::SO_TXTIME;
+use super::super::super::super::linux::socket::SO_ZEROCOPY;
 use super::super::super::super::linux::time::Timeval;
 use super::super::super::super::linux_def::*;
 use super::super::super::super::mem::block::*;
@@ -47,6 +50,7 @@ use super::super::super::kernel::waiter::*;
 use super::super::super::quring::QUring;
 use super::super::super::task::*;
 use super::super::super::tcpip::tcpip::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::super::super::Kernel;
 use super::super::super::Kernel::HostSpace;
 use super::super::super::IOURING;
@@ -55,7 +59,11 @@ use super::super::control::ControlMessageTCPInq;
 use super::super::control::*;
 use super::super::socket::*;
 use super::super::unix::transport::unix::*;
+use super::errqueue::*;
+use super::offload::*;
 use super::rdma_socket::*;
+use super::tproxy::*;
+use super::txtime::*;
 
 lazy_static! {
     pub static ref DUMMY_HOST_SOCKET : DummyHostSocket = DummyHostSocket::New();
@@ -99,6 +107,37 @@ fn newSocketFile(
     return Ok(file)
 }
 
+// the backlog of an inherited listening socket, same as the limit of listen(2)
+const INHERITED_LISTEN_BACKLOG: i32 = 1024 + 1;
+
+// NewSocketFileFromHostFd creates the guest file of a host socket which is
+// created outside of the sandbox, e.g. passed by socket activation. The socket
+// type is read from the host socket and a listening socket gets its accept
+// queue as if listen(2) was called in the guest.
+pub fn NewSocketFileFromHostFd(task: &Task, fd: i32) -> Result<File> {
+    let family = Kernel::GetSockOptI32(fd, LibcConst::SOL_SOCKET as _, LibcConst::SO_DOMAIN as _)?;
+    let stype = Kernel::GetSockOptI32(fd, LibcConst::SOL_SOCKET as _, LibcConst::SO_TYPE as _)?;
+    let listening =
+        Kernel::GetSockOptI32(fd, LibcConst::SOL_SOCKET as _, LibcConst::SO_ACCEPTCONN as _)? != 0;
+
+    if family != AFType::AF_INET && family != AFType::AF_INET6 {
+        return Err(Error::SysError(SysErr::EAFNOSUPPORT));
+    }
+
+    let socketType = if stype == SockType::SOCK_STREAM {
+        SocketBufType::TCPInit
+    } else {
+        SocketBufType::NoTCP
+    };
+
+    let file = newSocketFile(task, family, fd, stype, false, socketType, None)?;
+    if listening && stype == SockType::SOCK_STREAM {
+        file.FileOp.Listen(task, INHERITED_LISTEN_BACKLOG)?;
+    }
+
+    return Ok(file);
+}
+
 #[repr(u64)]
 #[derive(Clone)]
 pub enum SocketBufType {
@@ -177,6 +216,12 @@ pub struct SocketOperationsIntern {
     pub enableAsyncAccept: AtomicBool,
     pub hostops: HostInodeOp,
     passInq: AtomicBool,
+    pub offload: UdpOffload,
+    pub errQueue: ErrQueue,
+    pub txtime: TxTime,
+    // the address of the TLS client of a connection handed over by the TLS
+    // termination of qvisor, see tls_offload.rs
+    pub tlsPeer: QMutex<Option<Vec<u8>>>,
 }
 
 #[derive(Clone)]
@@ -222,6 +267,10 @@ impl SocketOperations {
             enableAsyncAccept: AtomicBool::new(false),
             hostops: hostops,
             passInq: AtomicBool::new(false),
+            offload: UdpOffload::New(fd, family, stype),
+            errQueue: ErrQueue::default(),
+            txtime: TxTime::default(),
+            tlsPeer: QMutex::new(None),
         };
 
         let ret = Self(Arc::new(ret));
@@ -434,6 +483,13 @@ impl Waitable for SocketOperations {
             return future;
         };
 
+        // the host doesn't know the datagrams queued after a GRO receive
+        if mask & EVENT_IN != 0 && self.offload.HasPending() {
+            let future = Future::New(0 as EventMask);
+            future.Set(Ok(EVENT_IN));
+            return future;
+        }
+
         let fd = self.fd;
         let future = IOURING.UnblockPollAdd(fd, mask as u32, wait);
         return future;
@@ -450,7 +506,16 @@ impl Waitable for SocketOperations {
         }
 
         let fd = self.fd;
-        return NonBlockingPoll(fd, mask);
+        let mut events = 0;
+        if mask & EVENT_IN != 0 && self.offload.HasPending() {
+            events |= EVENT_IN;
+        }
+
+        if self.errQueue.HasPending() {
+            events |= EVENT_ERR;
+        }
+
+        return events | NonBlockingPoll(fd, mask);
 
         /*let mv = MultiWait::New(task.GetTaskIdQ());
         error!("Readiness 1");
@@ -515,9 +580,17 @@ impl DummyHostSocket {
         return *s;
     }
 
+    pub fn HostIoctlIFReq(&self, task: &Task, request: u64, addr: u64) -> Result<()> {
+        return HostIoctlIFReq(task, self.Socket(), request, addr)
+    }
+
     pub fn HostIoctlIFConf(&self, task: &Task, request: u64, addr: u64) -> Result<()> {
         return HostIoctlIFConf(task, self.Socket(), request, addr)
     }
+
+    pub fn HostIoctlEthtool(&self, task: &Task, request: u64, addr: u64) -> Result<()> {
+        return HostIoctlEthtool(task, self.Socket(), request, addr)
+    }
 }
 
 
@@ -535,6 +608,9 @@ pub fn HostIoctlIFReq(task: &Task, hostfd: i32, request: u64, addr: u64) -> Resu
 
 pub fn HostIoctlIFConf(task: &Task, hostfd: i32, request: u64, addr: u64) -> Result<()> {
     let mut ifc: IFConf = task.CopyInObj(addr)?;
+    if ifc.Len < 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
 
     const MAX_LEN: usize = 64 * 0x1000; // 256 KB
 
@@ -572,6 +648,92 @@ pub fn HostIoctlIFConf(task: &Task, hostfd: i32, request: u64, addr: u64) -> Res
     return Ok(());
 }
 
+// HostQueueSize returns the result of the FIONREAD/SIOCOUTQ family ioctl on the
+// host fd.
+pub fn HostQueueSize(hostfd: i32, request: u64) -> Result<i32> {
+    let v: i32 = 0;
+    let res = HostSpace::IoCtl(hostfd, request, &v as *const _ as u64);
+    if res < 0 {
+        return Err(Error::SysError(-res as i32));
+    }
+
+    return Ok(v);
+}
+
+// ifr_data of SIOCETHTOOL points to a command specific structure in the user
+// memory, copy it to a kernel buffer and pass that one to the host.
+pub fn HostIoctlEthtool(task: &Task, hostfd: i32, request: u64, addr: u64) -> Result<()> {
+    let mut ifr: IFReq = task.CopyInObj(addr)?;
+    let dataAddr = ifr.DataPtr();
+    let cmd: u32 = task.CopyInObj(dataAddr)?;
+    let mut size = match EthtoolArgSize(cmd) {
+        None => return Err(Error::SysError(SysErr::EOPNOTSUPP)),
+        Some(size) => size,
+    };
+
+    // the ring sizes of the interfaces of the sandbox network namespace
+    if EthtoolIsSet(cmd) && !task.Creds().HasCapability(Capability::CAP_NET_ADMIN) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    // the data array of the statistics and their names, the host fills one
+    // entry for each of the driver, whatever the caller asks
+    let fixed: Vec<u8> = task.CopyInVec(dataAddr, size)?;
+    let entries = match EthtoolStringSet(cmd, &fixed) {
+        Some(sset) => EthtoolSsetCount(hostfd, &ifr, sset)?,
+        None if cmd == ETHTOOL_GSSET_INFO => EthtoolSsetInfoEntries(&fixed),
+        None => 0,
+    };
+
+    let dataSize = entries * EthtoolEntrySize(cmd);
+    if dataSize > MAX_ETHTOOL_DATA_SIZE {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+    size += dataSize;
+
+    CheckUserRange(dataAddr, size as u64)?;
+    let buf = DataBuff::New(size);
+    task.mm.CopyDataIn(task, dataAddr, buf.Ptr(), size, false)?;
+
+    ifr.SetDataPtr(buf.Ptr());
+    let res = HostSpace::IoCtl(hostfd, request, &mut ifr as *const _ as u64);
+    if res < 0 {
+        return Err(Error::SysError(-res as i32));
+    }
+
+    task.mm.CopyDataOut(task, buf.Ptr(), dataAddr, size, false)?;
+    return Ok(());
+}
+
+// EthtoolSsetCount returns the number of strings of the string set of the
+// interface of ifr.
+fn EthtoolSsetCount(hostfd: i32, ifr: &IFReq, sset: u32) -> Result<usize> {
+    if sset >= 64 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    // struct ethtool_sset_info with the data of the one set
+    let mut info = [0u32; 5];
+    info[0] = ETHTOOL_GSSET_INFO;
+    let mask = 1u64 << sset;
+    info[2] = mask as u32;
+    info[3] = (mask >> 32) as u32;
+
+    let mut ifr = *ifr;
+    ifr.SetDataPtr(&mut info[0] as *mut _ as u64);
+    let res = HostSpace::IoCtl(hostfd, LibcConst::SIOCETHTOOL, &mut ifr as *const _ as u64);
+    if res < 0 {
+        return Err(Error::SysError(-res as i32));
+    }
+
+    // the driver clears the bit of a set it doesn't have
+    if info[2] == 0 && info[3] == 0 {
+        return Ok(0);
+    }
+
+    return Ok(info[4] as usize);
+}
+
 impl SpliceOperations for SocketOperations {}
 
 impl FileOperations for SocketOperations {
@@ -631,6 +793,12 @@ impl FileOperations for SocketOperations {
                 return ret;
             }
             _ => {
+                if self.offload.Gro() {
+                    let (ret, _, _, _) =
+                        self.RecvGro(task, dsts, MsgType::MSG_DONTWAIT, None, false, 0)?;
+                    return Ok(ret);
+                }
+
                 let size = IoVec::NumBytes(dsts);
                 let buf = DataBuff::New(size);
                 let iovs = buf.Iovs(size);
@@ -710,6 +878,7 @@ impl FileOperations for SocketOperations {
         let hostfd = self.fd;
         match flags as u64 {
             LibcConst::SIOCGIFFLAGS
+            | LibcConst::SIOCGIFADDR
             | LibcConst::SIOCGIFBRDADDR
             | LibcConst::SIOCGIFDSTADDR
             | LibcConst::SIOCGIFHWADDR
@@ -731,20 +900,38 @@ impl FileOperations for SocketOperations {
 
                 return Ok(());
             }
+            LibcConst::SIOCETHTOOL => {
+                let addr = val;
+                HostIoctlEthtool(task, hostfd, request, addr)?;
+
+                return Ok(());
+            }
             LibcConst::TIOCINQ => {
-                if self.SocketBufEnabled() {
-                    let v = self.SocketBuf().readBuf.lock().AvailableDataSize() as i32;
-                    task.CopyOutObj(&v, val)?;
-                    return Ok(());
+                // the bytes already copied to the guest read buffer plus the ones
+                // still queued in the host socket
+                let v = if self.SocketBufEnabled() {
+                    let queued = HostQueueSize(self.fd, request).unwrap_or(0);
+                    self.SocketBuf().ReadBufAvailableDataSize() as i32 + queued
+                } else if let Some(size) = self.offload.PendingSize() {
+                    // the next datagram, as the host
+                    size as i32
                 } else {
-                    let tmp: i32 = 0;
-                    let res = Kernel::HostSpace::IoCtl(self.fd, request, &tmp as *const _ as u64);
-                    if res < 0 {
-                        return Err(Error::SysError(-res as i32));
-                    }
-                    task.CopyOutObj(&tmp, val)?;
-                    return Ok(());
-                }
+                    HostQueueSize(self.fd, request)?
+                };
+                task.CopyOutObj(&v, val)?;
+                return Ok(());
+            }
+            LibcConst::TIOCOUTQ | LibcConst::SIOCOUTQNSD => {
+                // the bytes in the guest write buffer are not sent yet, the host
+                // only counts what is left in its own send queue
+                let v = if self.SocketBufEnabled() {
+                    let queued = HostQueueSize(self.fd, request).unwrap_or(0);
+                    self.SocketBuf().WriteBufAvailableDataSize() as i32 + queued
+                } else {
+                    HostQueueSize(self.fd, request)?
+                };
+                task.CopyOutObj(&v, val)?;
+                return Ok(());
             }
             _ => {
                 let tmp: i32 = 0;
@@ -932,7 +1119,11 @@ impl SockOperations for SocketOperations {
 
         let remoteAddr = &acceptItem.addr.data[0..len];
         //let sockBuf = self.ConfigSocketBufType();
-        let sockBuf = self.SocketBufType().Accept(acceptItem.sockBuf.clone());
+        let buf = match &acceptItem.sockBuf {
+            Some(buf) => buf.clone(),
+            None => Arc::new(SocketBuff::default()),
+        };
+        let sockBuf = self.SocketBufType().Accept(buf);
 
         let file = newSocketFile(
             task,
@@ -944,6 +1135,12 @@ impl SockOperations for SocketOperations {
             Some(remoteAddr.to_vec()),
         )?;
 
+        if acceptItem.tlsOffload {
+            if let Some(sock) = file.FileOp.as_any().downcast_ref::<SocketOperations>() {
+                *sock.tlsPeer.lock() = Some(remoteAddr.to_vec());
+            }
+        }
+
         let fdFlags = FDFlags {
             CloseOnExec: flags & SocketFlags::SOCK_CLOEXEC != 0,
         };
@@ -991,6 +1188,14 @@ impl SockOperations for SocketOperations {
             && (self.family == AFType::AF_INET || self.family == AFType::AF_INET6)
             && self.stype == SockType::SOCK_STREAM;
 
+        // the backlog is capped by the somaxconn of the sandbox as the host
+        // does, a huge backlog doesn't let a flood queue without bound
+        let somaxconn = SHARESPACE.config.read().SoMaxConn;
+        let backlog = if somaxconn > 0 && backlog > somaxconn {
+            somaxconn
+        } else {
+            backlog
+        };
         let len = if backlog <= 0 { 5 } else { backlog };
 
         let socketBuf = self.socketBuf.lock().clone();
@@ -1146,6 +1351,18 @@ impl SockOperations for SocketOperations {
         return Ok(optlen as i64)
         */
 
+        if IsOriginalDst(level, name) {
+            return OriginalDst(self.fd, level, name, opt);
+        }
+
+        if level == SOL_UDP && name == UDP_GRO && opt.len() >= 4 {
+            if let Some(gro) = self.offload.UserGro() {
+                let val = gro as i32;
+                opt[0..4].copy_from_slice(&val.to_ne_bytes());
+                return Ok(4);
+            }
+        }
+
         let mut optLen = opt.len();
         let res = if optLen == 0 {
             Kernel::HostSpace::GetSockOpt(
@@ -1209,9 +1426,15 @@ impl SockOperations for SocketOperations {
 
         let opt = &opt[..optlen];*/
 
+        // the TCP options of a connection of the TLS termination are the ones
+        // of the TLS socket of qvisor, the host socket is a unix one
+        if (level as u64) == LibcConst::SOL_TCP && self.tlsPeer.lock().is_some() {
+            return Ok(0);
+        }
+
         if (level as u64) == LibcConst::SOL_SOCKET && (name as u64) == LibcConst::SO_SNDTIMEO {
             if opt.len() >= SocketSize::SIZEOF_TIMEVAL {
-                let timeVal = task.CopyInObj::<Timeval>(&opt[0] as *const _ as u64)?;
+                let timeVal = unsafe { core::ptr::read_unaligned(opt.as_ptr() as *const Timeval) };
                 self.SetSendTimeout(timeVal.ToDuration() as i64);
             } else {
                 //TODO: to be aligned with Linux, Linux allows shorter length for this flag.
@@ -1221,7 +1444,7 @@ impl SockOperations for SocketOperations {
 
         if (level as u64) == LibcConst::SOL_SOCKET && (name as u64) == LibcConst::SO_RCVTIMEO {
             if opt.len() >= SocketSize::SIZEOF_TIMEVAL {
-                let timeVal = task.CopyInObj::<Timeval>(&opt[0] as *const _ as u64)?;
+                let timeVal = unsafe { core::ptr::read_unaligned(opt.as_ptr() as *const Timeval) };
                 self.SetRecvTimeout(timeVal.ToDuration() as i64);
             } else {
                 //TODO: to be aligned with Linux, Linux allows shorter length for this flag.
@@ -1229,9 +1452,62 @@ impl SockOperations for SocketOperations {
             }
         }
 
+        // the device of a socket bound to one is only changed with CAP_NET_RAW
+        if (level as u64) == LibcConst::SOL_SOCKET
+            && ((name as u64) == LibcConst::SO_BINDTODEVICE
+                || (name as u64) == LibcConst::SO_BINDTOIFINDEX)
+            && !task.Creds().HasCapability(Capability::CAP_NET_RAW)
+        {
+            let mut ifindex: i32 = 0;
+            let mut len = core::mem::size_of::<i32>();
+            let res = Kernel::HostSpace::GetSockOpt(
+                self.fd,
+                level,
+                LibcConst::SO_BINDTOIFINDEX as i32,
+                &mut ifindex as *mut _ as u64,
+                &mut len as *mut _ as u64,
+            );
+            if res >= 0 && ifindex != 0 {
+                return Err(Error::SysError(SysErr::EPERM));
+            }
+        }
+
+        CheckPrivilegedOption(task, level, name)?;
+
+        // the host socket keeps its UDP_GRO for the GRO of the guest
+        if level == SOL_UDP && name == UDP_GRO && opt.len() >= 4 {
+            let val = i32::from_ne_bytes([opt[0], opt[1], opt[2], opt[3]]);
+            if self.offload.SetUserGro(val != 0) {
+                return Ok(0);
+            }
+        }
+
+        // the departure times of SO_TXTIME are moved to the host clock at the
+        // sends
+        if (level as u64) == LibcConst::SOL_SOCKET && name == SO_TXTIME {
+            let txtime = self.txtime.SockOpt(task, opt)?;
+            let res = Kernel::HostSpace::SetSockOpt(
+                self.fd,
+                level,
+                name,
+                &txtime as *const _ as u64,
+                core::mem::size_of::<SockTxTime>() as u32,
+            );
+            if res < 0 {
+                return Err(Error::SysError(-res as i32));
+            }
+
+            let clockId = i32::from_ne_bytes([opt[0], opt[1], opt[2], opt[3]]);
+            self.txtime.Set(clockId);
+            return Ok(res);
+        }
+
         // TCP_INQ is bound to buffer implementation
         if (level as u64) == LibcConst::SOL_TCP && (name as u64) == LibcConst::TCP_INQ {
-            let val = unsafe { *(&opt[0] as *const _ as u64 as *const i32) };
+            if opt.len() < 4 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+            let val = unsafe { core::ptr::read_unaligned(opt.as_ptr() as *const i32) };
             if val == 1 {
                 self.passInq.store(true, Ordering::Relaxed);
             } else {
@@ -1262,6 +1538,12 @@ impl SockOperations for SocketOperations {
             return Err(Error::SysError(-res as i32));
         }
 
+        // the host checks the socket supports SO_ZEROCOPY
+        if (level as u64) == LibcConst::SOL_SOCKET && name == SO_ZEROCOPY && optLen >= 4 {
+            let val = i32::from_ne_bytes([opt[0], opt[1], opt[2], opt[3]]);
+            self.errQueue.SetZeroCopy(val != 0);
+        }
+
         return Ok(res);
     }
 
@@ -1281,6 +1563,12 @@ impl SockOperations for SocketOperations {
     }
 
     fn GetPeerName(&self, _task: &Task, socketaddr: &mut [u8]) -> Result<i64> {
+        if let Some(peer) = self.tlsPeer.lock().as_ref() {
+            let len = core::cmp::min(peer.len(), socketaddr.len());
+            socketaddr[..len].copy_from_slice(&peer[..len]);
+            return Ok(peer.len() as i64);
+        }
+
         let len = socketaddr.len() as i32;
         let res = Kernel::HostSpace::GetPeerName(
             self.fd,
@@ -1303,19 +1591,22 @@ impl SockOperations for SocketOperations {
         senderRequested: bool,
         controlDataLen: usize,
     ) -> Result<(i64, i32, Option<(SockAddr, usize)>, Vec<u8>)> {
-
-        //todo: we don't support MSG_ERRQUEUE
         if flags
             & !(MsgType::MSG_DONTWAIT
             | MsgType::MSG_PEEK
             | MsgType::MSG_TRUNC
             | MsgType::MSG_CTRUNC
-            | MsgType::MSG_WAITALL)
+            | MsgType::MSG_WAITALL
+            | MsgType::MSG_ERRQUEUE)
             != 0
             {
                 return Err(Error::SysError(SysErr::EINVAL));
             }
 
+        if flags & MsgType::MSG_ERRQUEUE != 0 {
+            return self.RecvErrQueue(task, dsts, senderRequested, controlDataLen);
+        }
+
         let waitall = (flags & MsgType::MSG_WAITALL) != 0;
         let dontwait = (flags & MsgType::MSG_DONTWAIT) != 0;
 
@@ -1421,6 +1712,10 @@ impl SockOperations for SocketOperations {
             return Ok((count as i64, retFlags, senderAddr, controlData));
         }
 
+        if self.offload.Gro() {
+            return self.RecvGro(task, dsts, flags, deadline, senderRequested, controlDataLen);
+        }
+
         /*
         if IoVec::NumBytes(dsts) == 0 {
             return Ok((0, 0, None, SCMControlMessages::default()))
@@ -1523,6 +1818,17 @@ impl SockOperations for SocketOperations {
         msgHdr: &mut MsgHdr,
         deadline: Option<Time>,
     ) -> Result<i64> {
+        // the data is copied to the host, a MSG_ZEROCOPY send is completed
+        // at its return
+        if flags & MsgType::MSG_ZEROCOPY != 0 {
+            let res = self.SendMsg(task, srcs, flags & !MsgType::MSG_ZEROCOPY, msgHdr, deadline);
+            if res.is_ok() && self.errQueue.ZeroCopyDone(1) {
+                self.queue.Notify(EVENT_ERR);
+            }
+
+            return res;
+        }
+
         if self.SocketBufEnabled() {
             if self.SocketBuf().WClosed() {
                 return Err(Error::SysError(SysErr::ESPIPE))
@@ -1618,6 +1924,16 @@ impl SockOperations for SocketOperations {
         msgHdr.iovLen = iovs.len();
         msgHdr.msgFlags = 0;
 
+        let mut control: Vec<u8>;
+        if self.txtime.Enabled() && msgHdr.msgControlLen != 0 {
+            control = unsafe {
+                slice::from_raw_parts(msgHdr.msgControl as *const u8, msgHdr.msgControlLen)
+            }
+            .to_vec();
+            self.txtime.ToHost(task, &mut control);
+            msgHdr.msgControl = &control[0] as *const _ as u64;
+        }
+
         let mut res = Kernel::HostSpace::IOSendMsg(
             self.fd,
             msgHdr as *const _ as u64,
@@ -1651,6 +1967,21 @@ impl SockOperations for SocketOperations {
         return Ok(res as i64);
     }
 
+    fn SendSegments(
+        &self,
+        task: &Task,
+        msgs: &mut [MMsgHdr],
+        flags: i32,
+        deadline: Option<Time>,
+    ) -> Result<usize> {
+        let n = self.SendGso(task, msgs, flags & !MsgType::MSG_ZEROCOPY, deadline)?;
+        if flags & MsgType::MSG_ZEROCOPY != 0 && self.errQueue.ZeroCopyDone(n as u32) {
+            self.queue.Notify(EVENT_ERR);
+        }
+
+        return Ok(n);
+    }
+
     fn SetRecvTimeout(&self, ns: i64) {
         self.recv.store(ns, Ordering::Relaxed)
     }
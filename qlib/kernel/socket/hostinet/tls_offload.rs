@@ -0,0 +1,84 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::super::socket_buf::*;
+use super::super::super::kernel::kernel::GetKernel;
+use super::super::super::task::*;
+use super::socket::*;
+
+// The connections of the TLS termination of qvisor. qvisor terminates the TLS
+// of a port and hands the plaintext of a connection to the guest listener of
+// the plain port: the connection is one end of a unix stream pair, queued to
+// the accept queue of the listener with the address of the TLS client. The
+// guest reads and writes it over the uring socket buffers as the other
+// accepted sockets. The TCP options are the ones of the TLS socket in qvisor,
+// the SOL_TCP setsockopt of the guest on the connection is ignored and
+// getpeername returns the address of the TLS client.
+
+// TlsAccept queues the connection fd of the TLS client addr to the uring
+// listener of the port.
+pub fn TlsAccept(port: u16, fd: i32, addr: &[u8]) -> Result<()> {
+    let task = Task::Current();
+    let mut buf = [0u8; 128];
+    for (_, file) in GetKernel().sockets.ListSockets() {
+        let sock = match file.FileOp.as_any().downcast_ref::<SocketOperations>() {
+            None => continue,
+            Some(s) => s,
+        };
+
+        // only the listeners accepting with the uring see the queued items
+        let queue = match sock.SocketBufType() {
+            SocketBufType::TCPUringlServer(q) => q,
+            _ => continue,
+        };
+
+        let n = match file.FileOp.GetSockName(task, &mut buf) {
+            Err(_) => continue,
+            Ok(n) => n as usize,
+        };
+
+        // the port of sockaddr_in and sockaddr_in6
+        if n < 4 || u16::from_be_bytes([buf[2], buf[3]]) != port {
+            continue;
+        }
+
+        let mut item = AcceptItem::default();
+        item.fd = fd;
+        let len = core::cmp::min(addr.len(), item.addr.data.len());
+        item.addr.data[..len].copy_from_slice(&addr[..len]);
+        item.len = len as u32;
+        item.tlsOffload = true;
+
+        let trigger = {
+            let mut q = queue.lock();
+            // a full queue stops the uring accept, which is restarted when it
+            // drains, don't fill it up behind its back
+            if q.queue.len() + 1 >= q.queueLen {
+                return Err(Error::SysError(SysErr::EAGAIN));
+            }
+
+            q.EnqItem(item).0
+        };
+
+        if trigger {
+            sock.Notify(READABLE_EVENT);
+        }
+
+        return Ok(());
+    }
+
+    return Err(Error::SysError(SysErr::ECONNREFUSED));
+}
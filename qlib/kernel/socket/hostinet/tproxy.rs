@@ -0,0 +1,99 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::super::super::common::*;
+use super::super::super::super::linux::socket::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::task::*;
+use super::super::super::Kernel::HostSpace;
+
+// The options of the transparent proxies and the service meshes. The
+// redirect rules, iptables REDIRECT or TPROXY, are the ones of the network
+// namespace of the sandbox on the host, e.g. set by the CNI plugin of the
+// mesh, so the conntrack entries of the redirected connections are the ones
+// of the host sockets. SO_ORIGINAL_DST looks the original destination up in
+// the host conntrack, IP_TRANSPARENT has the host socket bind to and accept
+// the foreign addresses and IP_RECVORIGDSTADDR gets the destination of the
+// datagrams in the cmsgs of the host. The host allows them to qvisor, the
+// guest checks the capabilities of the task as Linux does.
+
+// SO_ORIGINAL_DST of linux/netfilter_ipv4.h and IP6T_SO_ORIGINAL_DST of
+// linux/netfilter_ipv6/ip6_tables.h
+pub const SO_ORIGINAL_DST: i32 = 80;
+pub const IP6T_SO_ORIGINAL_DST: i32 = 80;
+
+const SIZEOF_SOCKADDR_IN: usize = 16;
+const SIZEOF_SOCKADDR_IN6: usize = 28;
+
+// PrivilegedOption returns whether the option needs CAP_NET_ADMIN or
+// CAP_NET_RAW to be set.
+pub fn PrivilegedOption(level: i32, name: i32) -> bool {
+    return (level == SOL_IP && name as u64 == LibcConst::IP_TRANSPARENT)
+        || (level == SOL_IPV6 && name as u64 == LibcConst::IPV6_TRANSPARENT)
+        || (level == SOL_SOCKET && name == SO_MARK);
+}
+
+// CheckPrivilegedOption checks the task can set the option, the host doesn't
+// as qvisor has the capabilities the task may lack.
+pub fn CheckPrivilegedOption(task: &Task, level: i32, name: i32) -> Result<()> {
+    if !PrivilegedOption(level, name) {
+        return Ok(());
+    }
+
+    let creds = task.Creds();
+    if !creds.HasCapability(Capability::CAP_NET_ADMIN)
+        && !creds.HasCapability(Capability::CAP_NET_RAW)
+    {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    return Ok(());
+}
+
+pub fn IsOriginalDst(level: i32, name: i32) -> bool {
+    return (level == SOL_IP && name == SO_ORIGINAL_DST)
+        || (level == SOL_IPV6 && name == IP6T_SO_ORIGINAL_DST);
+}
+
+// OriginalDst gets the destination of the connection of the host socket fd
+// before its redirect, as a sockaddr_in or sockaddr_in6 by the level. It
+// fails with ENOENT if the connection was not redirected.
+pub fn OriginalDst(fd: i32, level: i32, name: i32, opt: &mut [u8]) -> Result<i64> {
+    let size = if level == SOL_IP {
+        SIZEOF_SOCKADDR_IN
+    } else {
+        SIZEOF_SOCKADDR_IN6
+    };
+
+    if opt.len() < size {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let mut addr = [0u8; SIZEOF_SOCKADDR_IN6];
+    let mut len = size as u32;
+    let res = HostSpace::GetSockOpt(
+        fd,
+        level,
+        name,
+        &mut addr[0] as *mut _ as u64,
+        &mut len as *mut _ as u64,
+    );
+    if res < 0 {
+        return Err(Error::SysError(-res as i32));
+    }
+
+    let len = len as usize;
+    opt[..len].copy_from_slice(&addr[..len]);
+    return Ok(len as i64);
+}
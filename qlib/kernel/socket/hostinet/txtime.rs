@@ -0,0 +1,141 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::ptr;
+use core::sync::atomic::AtomicI32;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::common::*;
+use super::super::super::super::linux::socket::*;
+use super::super::super::super::linux::time::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::kernel::timer::*;
+use super::super::super::task::*;
+use super::super::control::*;
+
+// The SO_TXTIME pacing of the sockets. The host socket takes the earliest
+// departure time of each packet in its SCM_TXTIME cmsg and its qdisc, fq or
+// etf, holds the packet until then, the send returns at once. The cmsgs go
+// to the host as they are, but the monotonic clock of the guest starts at
+// the boot of the sandbox and is shifted by the time namespace of the task,
+// the departure times of it are moved to the host monotonic clock here. The
+// guest boottime clock is its monotonic clock, the host socket uses the host
+// monotonic one for it. The realtime clock is the one of the host, and so
+// is the TAI clock the application derives from it.
+
+pub const SCM_TXTIME: i32 = SO_TXTIME;
+pub const CLOCK_TAI: i32 = 11;
+
+pub const SOF_TXTIME_DEADLINE_MODE: u32 = 1 << 0;
+pub const SOF_TXTIME_REPORT_ERRORS: u32 = 1 << 1;
+pub const SOF_TXTIME_FLAGS_MASK: u32 = SOF_TXTIME_REPORT_ERRORS | SOF_TXTIME_DEADLINE_MODE;
+
+// SockTxTime is struct sock_txtime from linux/net_tstamp.h.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SockTxTime {
+    pub ClockId: i32,
+    pub Flags: u32,
+}
+
+pub struct TxTime {
+    // the clock of SO_TXTIME of the application, -1 without it
+    pub clockId: AtomicI32,
+}
+
+impl Default for TxTime {
+    fn default() -> Self {
+        return Self {
+            clockId: AtomicI32::new(-1),
+        };
+    }
+}
+
+impl TxTime {
+    // SockOpt checks the SO_TXTIME of the application, it returns the one of
+    // the host socket.
+    pub fn SockOpt(&self, task: &Task, opt: &[u8]) -> Result<SockTxTime> {
+        if opt.len() < core::mem::size_of::<SockTxTime>() {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let mut txtime = unsafe { ptr::read_unaligned(&opt[0] as *const _ as *const SockTxTime) };
+        if txtime.Flags & !SOF_TXTIME_FLAGS_MASK != 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        match txtime.ClockId {
+            CLOCK_MONOTONIC => (),
+            CLOCK_REALTIME | CLOCK_TAI | CLOCK_BOOTTIME => {
+                // as on Linux, the clocks other than the monotonic one are for
+                // the etf qdisc of the administrator
+                if !task.Creds().HasCapability(Capability::CAP_NET_ADMIN) {
+                    return Err(Error::SysError(SysErr::EPERM));
+                }
+            }
+            _ => return Err(Error::SysError(SysErr::EINVAL)),
+        }
+
+        // the host boottime clock runs on while the host is suspended
+        if txtime.ClockId == CLOCK_BOOTTIME {
+            txtime.ClockId = CLOCK_MONOTONIC;
+        }
+
+        return Ok(txtime);
+    }
+
+    pub fn Set(&self, clockId: i32) {
+        self.clockId.store(clockId, Ordering::Relaxed);
+    }
+
+    pub fn Enabled(&self) -> bool {
+        return self.clockId.load(Ordering::Relaxed) >= 0;
+    }
+
+    // ToHost moves the departure times of the SCM_TXTIME cmsgs of control to
+    // the host clock.
+    pub fn ToHost(&self, task: &Task, control: &mut [u8]) {
+        let clockId = self.clockId.load(Ordering::Relaxed);
+        if clockId != CLOCK_MONOTONIC && clockId != CLOCK_BOOTTIME {
+            return;
+        }
+
+        let offset = TIME_KEEPER.read().monotonicOffset;
+        let ns = task.Thread().TimeNamespace();
+
+        let mut off = 0;
+        while off + SIZE_OF_CONTROL_MESSAGE_HEADER <= control.len() {
+            let h = unsafe {
+                ptr::read_unaligned(&control[off] as *const _ as *const ControlMessageHeader)
+            };
+            let len = h.Length as usize;
+            if len < SIZE_OF_CONTROL_MESSAGE_HEADER || off + len > control.len() {
+                return;
+            }
+
+            let data = off + SIZE_OF_CONTROL_MESSAGE_HEADER;
+            if h.Level == SOL_SOCKET
+                && h.Type == SCM_TXTIME
+                && len >= SIZE_OF_CONTROL_MESSAGE_HEADER + 8
+            {
+                let p = &mut control[data] as *mut _ as *mut u64;
+                let t = unsafe { ptr::read_unaligned(p) } as i64;
+                let t = ns.KernelTime(clockId, t).saturating_sub(offset);
+                unsafe { ptr::write_unaligned(p, t as u64) };
+            }
+
+            off += CMsgAlign(len);
+        }
+    }
+}
@@ -20,6 +20,7 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::any::Any;
 use core::ops::*;
+use core::slice;
 use core::sync::atomic::AtomicI64;
 use core::sync::atomic::Ordering;
 
@@ -995,14 +996,24 @@ impl SockOperations for UnixSocketOperations {
 
                 return Err(Error::SysError(SysErr::EOPNOTSUPP));
             } else {
-                task.CopyInVec(msgHdr.msgName, msgHdr.nameLen as usize)?
+                // the name and the control are copied in to the kernel by the syscall
+                unsafe {
+                    slice::from_raw_parts(msgHdr.msgName as *const u8, msgHdr.nameLen as usize)
+                }
+                .to_vec()
             }
         } else {
             Vec::new()
         };
 
         let controlVec: Vec<u8> = if msgHdr.msgControl != 0 {
-            task.CopyInVec(msgHdr.msgControl, msgHdr.msgControlLen as usize)?
+            unsafe {
+                slice::from_raw_parts(
+                    msgHdr.msgControl as *const u8,
+                    msgHdr.msgControlLen as usize,
+                )
+            }
+            .to_vec()
         } else {
             Vec::new()
         };
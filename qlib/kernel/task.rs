@@ -192,6 +192,9 @@ pub struct Task {
     pub ioUsage: IO,
     pub sched: TaskSchedInfo,
     pub iovs: Vec<IoVec>,
+    // the task copies the user memory in CopyDataIn/CopyDataOut, holding no
+    // lock but the read lock of its mappings, see Oops
+    pub userCopy: bool,
 
     pub perfcounters: Option<Arc<Counters>>,
 
@@ -307,6 +310,7 @@ impl Task {
             ioUsage: IO::default(),
             sched: TaskSchedInfo::default(),
             iovs: Vec::new(),
+            userCopy: false,
             perfcounters: None,
             guard: Guard::default(),
         };
@@ -407,9 +411,10 @@ impl Task {
         return self.syscallRestartBlock.take();
     }
 
-    // TODO(Cong): double check the semantics of this
+    // IsChrooted returns whether the root of the task differs from the root of
+    // its mount namespace, which pivot_root(2) moves together with the task root.
     pub fn IsChrooted(&self) -> bool {
-        let realRoot = self.mountNS.root.clone();
+        let realRoot = self.mountNS.Root();
         let root = self.fsContext.RootDirectory();
         return root != realRoot;
     }
@@ -667,6 +672,7 @@ impl Task {
                     ioUsage: ioUsage,
                     sched: TaskSchedInfo::default(),
                     iovs: Vec::with_capacity(4),
+                    userCopy: false,
                     perfcounters: perfcounters,
                     guard: Guard::default(),
                 },
@@ -770,6 +776,7 @@ impl Task {
                     ioUsage: dummyTask.ioUsage.clone(),
                     sched: TaskSchedInfo::default(),
                     iovs: Vec::new(),
+                    userCopy: false,
                     perfcounters: None,
                     guard: Guard::default(),
                 },
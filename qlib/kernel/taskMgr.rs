@@ -19,6 +19,8 @@ use super::super::super::kernel_def::*;
 use super::super::linux_def::*;
 use super::super::task_mgr::*;
 use super::super::vcpu_mgr::*;
+use super::arch::x86_64::async_pf::PollAsyncPageReady;
+use super::arch::x86_64::steal_time::*;
 use super::quring::uring_mgr::*;
 use super::task::*;
 use super::threadmgr::task_sched::*;
@@ -108,6 +110,7 @@ pub fn WaitFn() {
     let mut task = TaskId::default();
     loop {
         let next = if task.data == 0 {
+            UpdateStealRate();
             SHARESPACE.scheduler.GetNext()
         } else {
             let tmp = task;
@@ -124,6 +127,7 @@ pub fn WaitFn() {
                 //debug!("vcpu wakeup {:x}", addr);
                 assert!(addr >= 0);
                 task = TaskId::New(addr as u64);
+                PollAsyncPageReady();
 
                 SHARESPACE.scheduler.DecreaseHaltVcpuCnt();
             }
@@ -217,7 +221,10 @@ pub fn Wait() {
                 Some(t) => next = Some(t),
             }
         } else {
-            if PollAsyncMsg() == 0 {
+            PollAsyncPageReady();
+            let steered = SHARESPACE.recvSteering.Process(vcpuId);
+            let fired = SHARESPACE.timerDispatch.Process(vcpuId);
+            if PollAsyncMsg() + steered + fired == 0 {
                 unsafe {
                     llvm_asm!("pause" :::: "volatile");
                 }
@@ -280,6 +287,17 @@ impl Scheduler {
             },
         }
 
+        // a vcpu the host keeps from running leaves the tasks of the other
+        // vcpus to an idle one with less steal time, it runs them itself if
+        // there is none
+        let rate = StealRate(vcpuId);
+        if rate >= STEAL_RATE_HIGH
+            && self.GlobalReadyTaskCnt() > 0
+            && self.WakeLessStolen(rate)
+        {
+            return None;
+        }
+
         match self.Steal(vcpuId) {
             None => return None,
             Some(t) => {
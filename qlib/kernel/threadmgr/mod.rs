@@ -35,3 +35,4 @@ pub mod task_usermem;
 pub mod thread;
 pub mod thread_group;
 pub mod threads;
+pub mod tty;
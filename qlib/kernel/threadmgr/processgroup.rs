@@ -323,13 +323,19 @@ impl ProcessGroup {
     }
 
     pub fn SendSignal(&self, info: &SignalInfo) -> Result<()> {
+        let ts = self.lock().originator.TaskSet();
+        let _r = ts.ReadLock();
+        return self.SendSignalLocked(info);
+    }
+
+    // Preconditions: The TaskSet mutex must be locked.
+    pub fn SendSignalLocked(&self, info: &SignalInfo) -> Result<()> {
         let ts = self.lock().originator.TaskSet();
         let mut lastError: Result<()> = Ok(());
         let rootns = ts.Root();
 
-        let _r = ts.ReadLock();
         for (tg, _) in &rootns.lock().tgids {
-            if tg.ProcessGroup() == Some(self.clone()) {
+            if tg.lock().processGroup == Some(self.clone()) {
                 let lock = tg.lock().signalLock.clone();
                 let _s = lock.lock();
                 let leader = tg.lock().leader.Upgrade().unwrap();
@@ -22,6 +22,7 @@ use super::super::uid::NewUID;
 use super::processgroup::*;
 use super::thread::*;
 use super::thread_group::*;
+use super::tty::*;
 use crate::qlib::kernel::threadmgr::refcounter::AtomicRefCount;
 
 #[derive(Default)]
@@ -30,6 +31,9 @@ pub struct SessionInternal {
     pub leader: ThreadGroup,
     pub refs: AtomicRefCount,
     pub processGroups: BTreeSet<ProcessGroup>,
+
+    // tty is the controlling terminal of the session.
+    pub tty: Option<TTY>,
 }
 
 #[derive(Clone, Default)]
@@ -75,6 +79,7 @@ impl Session {
             leader: leader,
             refs: Default::default(),
             processGroups: BTreeSet::new(),
+            tty: None,
         };
         return Self {
             uid: NewUID(),
@@ -24,6 +24,7 @@ use super::super::kernel::timer::*;
 use super::super::kernel::waiter::*;
 use super::super::task::*;
 use super::super::threadmgr::thread::*;
+use super::super::SHARESPACE;
 
 impl Thread {
     pub fn Interrupted(&self, clear: bool) -> bool {
@@ -357,7 +358,12 @@ impl Blocker {
 
     // block on general entry
     pub fn BlockGeneralOnly(&self) {
+        // the uninterruptible wait counts in the load average and in the io
+        // wait of the sandbox
+        let start = MonotonicNow();
+        SHARESPACE.stats.IoWaitStart();
         self.waiter.Wait(0b001);
+        SHARESPACE.stats.IoWaitEnd(MonotonicNow() - start);
         self.waiter.lock().bitmap &= !(1 << Waiter::GENERAL_WAITID);
 
         return;
@@ -92,6 +92,15 @@ pub struct SharingOptions {
     // If NewIPCNamespace is true, the task should have an independent IPC
     // namespace.
     pub NewIPCNamespace: bool,
+
+    // If NewMountNamespace is true, the task should have an independent mount
+    // namespace, a copy of the one of its parent.
+    pub NewMountNamespace: bool,
+
+    // If NewTimeNamespace is true, the children of the task created later
+    // are in a new time namespace. It is only set by unshare(2), CLONE_NEWTIME
+    // is the exit signal bits of clone(2).
+    pub NewTimeNamespace: bool,
 }
 
 #[derive(Debug, Copy, Clone, Default)]
@@ -173,6 +182,7 @@ impl CloneOptions {
                 NewFSContext: flags & CloneOp::CLONE_FS == 0,
                 NewUTSNamespace: flags & CloneOp::CLONE_NEWUTS != 0,
                 NewIPCNamespace: flags & CloneOp::CLONE_NEWIPC != 0,
+                NewMountNamespace: flags & CloneOp::CLONE_NEWNS != 0,
             },
 
             Stack: cStack,
@@ -225,6 +235,12 @@ impl CloneOptions {
             return Err(Error::SysError(SysErr::EINVAL));
         }
 
+        // the root and the working directory of the task move to the new
+        // mount namespace, they can't be shared with the parent
+        if opts.sharingOption.NewMountNamespace && !opts.sharingOption.NewFSContext {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
         return Ok(opts);
     }
 }
@@ -266,6 +282,15 @@ impl Thread {
             ipcns = IPCNamespace::New(&userns);
         }
 
+        // a task in another time namespace can't share the address space or
+        // the thread group
+        let timens = t.timensForChildren.clone();
+        if (!opts.sharingOption.NewAddressSpace || !opts.sharingOption.NewThreadGroup)
+            && timens != t.timens
+        {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
         let mut memoryMgr = t.memoryMgr.clone();
         if opts.sharingOption.NewAddressSpace {
             let newMM = memoryMgr.Fork()?;
@@ -306,6 +331,7 @@ impl Thread {
             let kernel = t.k.clone();
             let limit = tg.lock().limits.clone();
             let cid = tg.lock().containerID.clone();
+            let oomScoreAdj = tg.lock().oomScoreAdj;
             tg = kernel.newThreadGroup(
                 &pidns,
                 &sh,
@@ -314,6 +340,7 @@ impl Thread {
                 &cid,
                 &None,
             );
+            tg.lock().oomScoreAdj = oomScoreAdj;
         }
 
         let mut cfg = TaskConfig {
@@ -332,6 +359,7 @@ impl Thread {
             AllowedCPUMask: t.allowedCPUMask.Copy(),
             UTSNamespace: utsns,
             IPCNamespace: ipcns,
+            TimeNamespace: timens,
             Blocker: Blocker::New(stackAddr),
             ContainerID: t.containerID.to_string(),
         };
@@ -355,6 +383,13 @@ impl Thread {
         let nt = ts.NewTask(&cfg, false, &kernel)?;
 
         nt.lock().name = name;
+        {
+            // the filter is copied under the lock of the thread group, a
+            // TSYNC of another thread can't miss the new thread
+            let _tglock = tg.lock();
+            let seccomp = self.lock().seccomp.clone();
+            nt.lock().seccomp = seccomp;
+        }
 
         if userns != creds.lock().UserNamespace.clone() {
             nt.SetUserNamespace(&userns)
@@ -470,6 +505,16 @@ impl Task {
         let task = Task::Current();
         let thread = task.Thread();
 
+        let mountNS = if opts.sharingOption.NewMountNamespace {
+            if !thread.HasCapability(Capability::CAP_SYS_ADMIN) {
+                return Err(Error::SysError(SysErr::EPERM));
+            }
+
+            task.mountNS.Copy(task)?
+        } else {
+            task.mountNS.clone()
+        };
+
         let nt = thread.Clone(&opts, s_ptr as u64)?;
 
         unsafe {
@@ -478,6 +523,9 @@ impl Task {
             let utsns = nt.lock().utsns.clone();
             let ipcns = nt.lock().ipcns.clone();
             let fsContext = nt.lock().fsc.clone();
+            if opts.sharingOption.NewMountNamespace {
+                mountNS.MoveFSContext(task, &task.mountNS, &fsContext);
+            }
             let fdTbl = nt.lock().fdTbl.clone();
             let blocker = nt.lock().blocker.clone();
             let sched = nt.lock().sched.clone();
@@ -512,7 +560,7 @@ impl Task {
                     tidInfo: Default::default(),
                     isWaitThread: false,
                     signalStack: signalStack,
-                    mountNS: task.mountNS.clone(),
+                    mountNS: mountNS,
                     // Arc::new(QMutex::new(Default::default())),
                     creds: creds,
                     utsns: utsns,
@@ -631,6 +679,15 @@ impl Task {
             tlock.ipcns = self.ipcns.clone();
         }
 
+        if opts.NewTimeNamespace {
+            if !haveCapSysAdmin {
+                return Err(Error::SysError(SysErr::EPERM));
+            }
+
+            let userns = creds.lock().UserNamespace.clone();
+            tlock.timensForChildren = tlock.timensForChildren.Fork(&userns);
+        }
+
         if opts.NewFiles {
             let fdtbl = self.fdTbl.clone();
             self.fdTbl = fdtbl.Fork(i32::MAX);
@@ -643,6 +700,21 @@ impl Task {
             tlock.fsc = self.fsContext.clone();
         }
 
+        // the lookups of the copy may lock the thread
+        core::mem::drop(tlock);
+
+        // NewMountNamespace implies NewFSContext, the root and the working
+        // directory of the task move to the copy
+        if opts.NewMountNamespace {
+            if !haveCapSysAdmin {
+                return Err(Error::SysError(SysErr::EPERM));
+            }
+
+            let mns = self.mountNS.Copy(self)?;
+            mns.MoveFSContext(self, &self.mountNS, &self.fsContext);
+            self.mountNS = mns;
+        }
+
         return Ok(());
     }
 }
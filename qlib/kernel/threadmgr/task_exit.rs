@@ -29,6 +29,7 @@ use super::super::LOADER;
 use super::super::super::perf_tunning::*;
 use super::super::task::*;
 use super::super::SignalDef::*;
+use super::super::SHARESPACE;
 use super::task_stop::*;
 
 // An ExitStatus is a value communicated from an exiting task or thread group
@@ -223,6 +224,30 @@ impl Thread {
         };
 
         let inittg = init.lock().tg.clone();
+
+        // "... the orphaned process will be reparented to the nearest still
+        // living ancestor subreaper." - prctl(2). The walk stops at the init
+        // process, which reaps the orphans of its namespace anyway.
+        // Compare kernel/exit.c:find_new_reaper().
+        if tg.lock().hasChildSubreaper {
+            let mut ancestor = self.lock().parent.clone();
+            while let Some(a) = ancestor {
+                let atg = a.lock().tg.clone();
+                if atg == inittg || atg.PIDNamespace() != pidns {
+                    break;
+                }
+
+                if atg.lock().isChildSubreaper {
+                    match atg.anyNonExitingTaskLocked() {
+                        Some(t2) => return Some(t2),
+                        None => (),
+                    }
+                }
+
+                ancestor = a.lock().parent.clone();
+            }
+        }
+
         return inittg.anyNonExitingTaskLocked();
     }
 
@@ -433,6 +458,18 @@ impl Thread {
         }
 
         tg.lock().terminationSignal = Signal(Signal::SIGCHLD);
+
+        // The built-in init reaps the orphans of the sandbox. An entrypoint
+        // running as the init process only waits for the children it forks.
+        if let Some(p) = parent {
+            if SHARESPACE.config.read().BuiltinInit {
+                let ptg = p.lock().tg.clone();
+                let pidns = tg.PIDNamespace();
+                let isInit = pidns.lock().tgids.get(&ptg) == Some(&INIT_TID);
+                tg.lock().initReaped = isInit;
+            }
+        }
+
         let exitParentNotified = self.lock().exitParentNotified;
         let exitParentAcked = self.lock().exitParentAcked;
         if exitParentNotified && !exitParentAcked {
@@ -774,6 +811,13 @@ impl Thread {
                     let parentTg = parent.unwrap().lock().tg.clone();
                     let lock = parentTg.lock().signalLock.clone();
 
+                    // The orphan is reaped by the built-in init, the init
+                    // process doesn't know about it.
+                    if tg.lock().initReaped {
+                        t.lock().exitParentAcked = true;
+                        signalParent = false;
+                    }
+
                     {
                         let _s = lock.lock();
                         let sh = parentTg.lock().signalHandlers.clone();
@@ -1001,6 +1045,12 @@ impl Thread {
         }
 
         self.exitNotifyLocked();
+        if tg.lock().liveTasks == 0 {
+            let queue = tg.lock().exitQueue.clone();
+            queue.Notify(READABLE_EVENT);
+            tg.ReleaseSessionTTYLocked();
+        }
+
         if isRootProcess && tg.lock().liveTasks == 0 {
             let execId = execId.unwrap_or_default();
             info!(
@@ -1174,11 +1224,18 @@ impl WaitOptions {
 
         let tg = t.lock().tg.clone();
         let pg = tg.lock().processGroup.clone();
-        if self.SpecificPGID != 0
-            && pg.is_some()
-            && self.SpecificPGID != *pidns.lock().pgids.get(&pg.unwrap()).unwrap()
-        {
-            return false;
+        if self.SpecificPGID != 0 {
+            let pgid = match pg {
+                None => 0,
+                Some(pg) => match pidns.lock().pgids.get(&pg) {
+                    None => 0,
+                    Some(id) => *id,
+                },
+            };
+
+            if self.SpecificPGID != pgid {
+                return false;
+            }
         }
 
         let leader = tg.lock().leader.Upgrade();
@@ -1252,8 +1309,9 @@ impl Task {
                 }
                 Some(addr) => {
                     let val: u32 = 0;
-                    self.CopyOutObj(&val, addr).unwrap();
-                    self.futexMgr.Wake(self, addr, false, !0, 1).unwrap();
+                    // the address is from the user, ignore the faults as linux
+                    self.CopyOutObj(&val, addr).ok();
+                    self.futexMgr.Wake(self, addr, false, !0, 1).ok();
                 }
             }
         }
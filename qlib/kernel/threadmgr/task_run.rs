@@ -14,6 +14,7 @@
 
 use super::super::super::common::*;
 use super::super::super::linux_def::*;
+use super::super::memmgr::mem_high::*;
 use super::super::task::*;
 use super::super::threadmgr::task_syscall::*;
 use super::super::threadmgr::thread::*;
@@ -30,6 +31,9 @@ impl Task {
     pub fn RunApp(&mut self) -> TaskRunState {
         let t = self.Thread();
 
+        // the memory.high throttling is done before going back to the application
+        HandleOverHigh(self);
+
         //if the task has been interrupted
         if t.Interrupted(true) {
             // Checkpointing instructs tasks to stop by sending an interrupt, so we
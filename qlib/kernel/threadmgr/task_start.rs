@@ -21,6 +21,7 @@ use super::super::kernel::fd_table::*;
 use super::super::kernel::fs_context::*;
 use super::super::kernel::ipc_namespace::*;
 use super::super::kernel::kernel::*;
+use super::super::kernel::time_namespace::*;
 use super::super::kernel::uts_namespace::*;
 use super::super::memmgr::mm::*;
 use super::super::SignalDef::*;
@@ -77,6 +78,9 @@ pub struct TaskConfig {
     // IPCNamespace is the IPCNamespace of the new task.
     pub IPCNamespace: IPCNamespace,
 
+    // TimeNamespace is the TimeNamespace of the new task.
+    pub TimeNamespace: TimeNamespace,
+
     pub Blocker: Blocker,
 
     pub ContainerID: String,
@@ -28,17 +28,106 @@ use super::super::memmgr::mm::*;
 use super::super::task::*;
 use super::super::util::cstring::*;
 
+// MAX_RW_COUNT is the most bytes of one read or write, as MAX_RW_COUNT of
+// include/linux/fs.h.
+pub const MAX_RW_COUNT: usize = (core::i32::MAX as usize) & !(MemoryDef::PAGE_SIZE as usize - 1);
+
+// MAX_USER_STRING_LEN caps the strings copied in from the user, the longest one
+// is an execve argument of MAX_ARG_STRLEN.
+pub const MAX_USER_STRING_LEN: usize = 32 * MemoryDef::PAGE_SIZE as usize;
+
+// IsUserRange returns whether [addr, addr+len) is in the user address space.
+// The kernel memory from KVM_IOEVENTFD_BASEADDR to PHY_UPPER_ADDR is below
+// LOWER_TOP and is mapped in every address space, the permission check of the
+// memory manager lets the kernel buffers through so the user pointers have to
+// be checked before.
+pub fn IsUserRange(addr: u64, len: u64) -> bool {
+    let end = match addr.checked_add(len) {
+        None => return false,
+        Some(end) => end,
+    };
+
+    if end > MemoryDef::LOWER_TOP {
+        return false;
+    }
+
+    if len == 0 {
+        return true;
+    }
+
+    return end <= MemoryDef::KVM_IOEVENTFD_BASEADDR || addr >= MemoryDef::PHY_UPPER_ADDR;
+}
+
+pub fn CheckUserRange(addr: u64, len: u64) -> Result<()> {
+    if !IsUserRange(addr, len) {
+        return Err(Error::SysError(SysErr::EFAULT));
+    }
+
+    return Ok(());
+}
+
+// UserRangeLen returns the length of the user memory from addr, up to maxlen.
+pub fn UserRangeLen(addr: u64, maxlen: usize) -> Result<usize> {
+    CheckUserRange(addr, 1)?;
+    let limit = if addr < MemoryDef::KVM_IOEVENTFD_BASEADDR {
+        MemoryDef::KVM_IOEVENTFD_BASEADDR
+    } else {
+        MemoryDef::LOWER_TOP
+    };
+
+    if ((limit - addr) as usize) < maxlen {
+        return Ok((limit - addr) as usize);
+    }
+
+    return Ok(maxlen);
+}
+
+// CheckAligned returns EINVAL if addr is not aligned for T, e.g. a futex word
+// has to be on a u32 boundary.
+pub fn CheckAligned<T: Sized>(addr: u64) -> Result<()> {
+    if addr % align_of::<T>() as u64 != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    return Ok(());
+}
+
+// ElemAddr returns the address of the element idx of the array of T at addr.
+pub fn ElemAddr<T: Sized>(addr: u64, idx: usize) -> Result<u64> {
+    let offset = match idx.checked_mul(size_of::<T>()) {
+        None => return Err(Error::SysError(SysErr::EFAULT)),
+        Some(o) => o as u64,
+    };
+
+    match addr.checked_add(offset) {
+        None => return Err(Error::SysError(SysErr::EFAULT)),
+        Some(a) => return Ok(a),
+    }
+}
+
 impl MemoryManager {
     // copy raw data from user to kernel
     pub fn CopyDataIn(&self, task: &Task, vaddr: u64, to: u64, len: usize, allowPartial: bool) -> Result<()> {
         let rl = self.MappingReadLock();
 
-        return self.CopyDataInLocked(task, &rl, vaddr, to, len, allowPartial);
+        self.V2PLocked(task, &rl, vaddr, len as u64, &mut task.GetMut().iovs, false, allowPartial)?;
+        defer!(task.GetMut().iovs.clear());
+
+        // the copy holds only the read lock, a fault in it can be confined to the task
+        task.GetMut().userCopy = true;
+        self.CopyIovsIn(task, to, len);
+        task.GetMut().userCopy = false;
+        return Ok(());
     }
 
     pub fn CopyDataInLocked(&self, task: &Task, rl: &QUpgradableLockGuard, vaddr: u64, to: u64, len: usize, allowPartial: bool) -> Result<()> {
         self.V2PLocked(task, rl, vaddr, len as u64, &mut task.GetMut().iovs, false, allowPartial)?;
         defer!(task.GetMut().iovs.clear());
+        self.CopyIovsIn(task, to, len);
+        return Ok(());
+    }
+
+    fn CopyIovsIn(&self, task: &Task, to: u64, len: usize) {
         let target: *mut u8 = to as *mut u8;
         let dst = unsafe { slice::from_raw_parts_mut(target, len) };
 
@@ -50,13 +139,16 @@ impl MemoryManager {
 
             offset += iov.len;
         }
-
-        return Ok(());
     }
 
     pub fn CopyDataOutLocked(&self, task: &Task, rl: &QUpgradableLockGuard, from: u64, vaddr: u64, len: usize, allowPartial: bool) -> Result<()> {
         self.V2PLocked(task, rl, vaddr, len as u64, &mut task.GetMut().iovs, true, allowPartial)?;
         defer!(task.GetMut().iovs.clear());
+        self.CopyIovsOut(task, from, len);
+        return Ok(());
+    }
+
+    fn CopyIovsOut(&self, task: &Task, from: u64, len: usize) {
         let from: *const u8 = from as *const u8;
         let src = unsafe { slice::from_raw_parts(from, len) };
 
@@ -68,14 +160,19 @@ impl MemoryManager {
             dst[0..iov.len].clone_from_slice(&src[offset..offset + iov.len]);
             offset += iov.len;
         }
-
-        return Ok(());
     }
 
     pub fn CopyDataOut(&self, task: &Task, from: u64, vaddr: u64, len: usize, allowPartial: bool) -> Result<()> {
         let rl = self.MappingReadLock();
 
-        return self.CopyDataOutLocked(task, &rl, from, vaddr, len, allowPartial);
+        self.V2PLocked(task, &rl, vaddr, len as u64, &mut task.GetMut().iovs, true, allowPartial)?;
+        defer!(task.GetMut().iovs.clear());
+
+        // the copy holds only the read lock, a fault in it can be confined to the task
+        task.GetMut().userCopy = true;
+        self.CopyIovsOut(task, from, len);
+        task.GetMut().userCopy = false;
+        return Ok(());
     }
 
     pub fn CopyDataOutToIovsLocked(
@@ -223,17 +320,17 @@ impl MemoryManager {
     }
 
     pub fn CopyInObjLocked<T: Sized + Copy>(&self, task: &Task, rl: &QUpgradableLockGuard, src: u64) -> Result<T> {
-        let data: T = unsafe { MaybeUninit::uninit().assume_init() };
+        let mut data = MaybeUninit::<T>::uninit();
         let size = size_of::<T>();
-        self.CopyDataInLocked(task, rl, src, &data as *const _ as u64, size, false)?;
-        return Ok(data);
+        self.CopyDataInLocked(task, rl, src, data.as_mut_ptr() as u64, size, false)?;
+        return Ok(unsafe { data.assume_init() });
     }
 
     pub fn CopyInObj<T: Sized + Copy>(&self, task: &Task, src: u64) -> Result<T> {
-        let data: T = unsafe { MaybeUninit::uninit().assume_init() };
+        let mut data = MaybeUninit::<T>::uninit();
         let size = size_of::<T>();
-        self.CopyDataIn(task, src, &data as *const _ as u64, size, false)?;
-        return Ok(data);
+        self.CopyDataIn(task, src, data.as_mut_ptr() as u64, size, false)?;
+        return Ok(unsafe { data.assume_init() });
     }
 
     pub fn CopyOutObjLocked<T: Sized + Copy>(&self, task: &Task, rl: &QUpgradableLockGuard, data: &T, dst: u64) -> Result<()> {
@@ -310,28 +407,29 @@ impl MemoryManager {
         return self.CopyDataOut(task, src.as_ptr() as u64, dst, size, false);
     }
 
+    // SwapU32 returns the old value of the u32 at vaddr. The u32 is aligned so
+    // it doesn't cross a page and the write breaks the cow of the page.
     pub fn SwapU32(&self, task: &Task, vaddr: u64, new: u32) -> Result<u32> {
-        let rl = self.MappingReadLock();
-
-        assert!(vaddr % 4 == 0);
+        CheckAligned::<u32>(vaddr)?;
+        CheckUserRange(vaddr, 4)?;
 
-        self.V2PLocked(task, &rl, vaddr, 4, &mut task.GetMut().iovs, false, false)?;
+        let rl = self.MappingReadLock();
+        self.V2PLocked(task, &rl, vaddr, 4, &mut task.GetMut().iovs, true, false)?;
         defer!(task.GetMut().iovs.clear());
 
         assert!(task.GetMut().iovs.len() == 1);
         let addr = task.GetMut().iovs[0].start;
         let val = unsafe { &*(addr as *const AtomicU32) };
 
-        val.swap(new, Ordering::SeqCst);
-        return Ok(new);
+        return Ok(val.swap(new, Ordering::SeqCst));
     }
 
     pub fn CompareAndSwapU32(&self, task: &Task, vaddr: u64, old: u32, new: u32) -> Result<u32> {
-        let rl = self.MappingReadLock();
-
-        assert!(vaddr % 4 == 0);
+        CheckAligned::<u32>(vaddr)?;
+        CheckUserRange(vaddr, 4)?;
 
-        self.V2PLocked(task, &rl, vaddr, 4, &mut task.GetMut().iovs, false, false)?;
+        let rl = self.MappingReadLock();
+        self.V2PLocked(task, &rl, vaddr, 4, &mut task.GetMut().iovs, true, false)?;
         defer!(task.GetMut().iovs.clear());
 
         assert!(task.GetMut().iovs.len() == 1);
@@ -393,6 +491,7 @@ impl MemoryManager {
 
             //let maxlen = self.FixPermission(task, ptr, thisMax as u64, false, true)? as usize;
             //error!("CopyInVector 2 ptr is {:x?}, thisMax is {}", &ptr, maxlen);
+            let thisMax = UserRangeLen(ptr, thisMax)?;
             let (str, err) = self.CopyInString(task, ptr, thisMax);
             match err {
                 Err(e) => return Err(e),
@@ -481,6 +580,13 @@ impl MemoryManager {
 impl Task {
     //Copy a vec from user memory
     pub fn CopyInVec<T: Sized + Copy>(&self, addr: u64, size: usize) -> Result<Vec<T>> {
+        let len = match size.checked_mul(size_of::<T>()) {
+            None => return Err(Error::SysError(SysErr::EFAULT)),
+            Some(len) => len as u64,
+        };
+        CheckUserRange(addr, len)?;
+        // the vec is allocated with the size, don't allocate for an unmapped range
+        self.CheckPermission(addr, len, false, false)?;
         return self.mm.CopyInVec(self, addr, size);
     }
 
@@ -490,6 +596,7 @@ impl Task {
 
     //Copy a slice to user memory
     pub fn  CopyOutSlice<T: Sized + Copy>(&self, src: &[T], dst: u64, len: usize) -> Result<()> {
+        CheckUserRange(dst, (size_of::<T>() * src.len()) as u64)?;
         return self.mm.CopyOutSlice(self, src, dst, len);
     }
 
@@ -507,14 +614,65 @@ impl Task {
 
     //Copy an Object from user memory
     pub fn CopyInObj<T: Sized + Copy>(&self, src: u64) -> Result<T> {
+        CheckUserRange(src, size_of::<T>() as u64)?;
         return self.mm.CopyInObj(self, src);
     }
 
     //Copy an Object to user memory
     pub fn CopyOutObj<T: Sized + Copy>(&self, src: &T, dst: u64) -> Result<()> {
+        CheckUserRange(dst, size_of::<T>() as u64)?;
         return self.mm.CopyOutObj(self, src, dst);
     }
 
+    //Copy the element idx of an array from user memory
+    pub fn CopyInObjAt<T: Sized + Copy>(&self, addr: u64, idx: usize) -> Result<T> {
+        return self.CopyInObj(ElemAddr::<T>(addr, idx)?);
+    }
+
+    //Copy an Object to the element idx of an array in user memory
+    pub fn CopyOutObjAt<T: Sized + Copy>(&self, src: &T, addr: u64, idx: usize) -> Result<()> {
+        return self.CopyOutObj(src, ElemAddr::<T>(addr, idx)?);
+    }
+
+    // CopyOutObjs copies the objects to the array at dst and returns the count
+    // copied. The copy stops at the first fault, which is returned only if no
+    // object is copied, as the syscalls returning partial results report it,
+    // e.g. epoll_wait(2).
+    pub fn CopyOutObjs<T: Sized + Copy>(&self, src: &[T], dst: u64) -> Result<usize> {
+        if src.len() == 0 {
+            return Ok(0);
+        }
+
+        let size = ElemAddr::<T>(0, src.len())?;
+        CheckUserRange(dst, size)?;
+        let len = self.CheckPermission(dst, size, true, true)?;
+        let count = len as usize / size_of::<T>();
+        if count == 0 {
+            return Err(Error::SysError(SysErr::EFAULT));
+        }
+
+        self.mm.CopyOutSlice(self, &src[..count], dst, count)?;
+        return Ok(count);
+    }
+
+    // CopyInObjs copies in the array of count objects at src up to the first
+    // fault, the fault is returned if no object is copied.
+    pub fn CopyInObjs<T: Sized + Copy>(&self, src: u64, count: usize) -> Result<Vec<T>> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let size = ElemAddr::<T>(0, count)?;
+        CheckUserRange(src, size)?;
+        let len = self.CheckPermission(src, size, false, true)?;
+        let count = len as usize / size_of::<T>();
+        if count == 0 {
+            return Err(Error::SysError(SysErr::EFAULT));
+        }
+
+        return self.mm.CopyInVec(self, src, count);
+    }
+
     pub fn CopyDataOut(&self, from: u64, vaddr: u64, len: usize, allowPartial: bool) -> Result<()> {
         return self.mm.CopyDataOut(self, from, vaddr, len, allowPartial);
     }
@@ -531,6 +689,16 @@ impl Task {
     // would exceed maxlen, CopyStringIn returns the string truncated to maxlen and
     // ENAMETOOLONG.
     pub fn CopyInString(&self, addr: u64, maxlen: usize) -> (String, Result<()>) {
+        let mut maxlen = maxlen;
+        if maxlen > MAX_USER_STRING_LEN {
+            maxlen = MAX_USER_STRING_LEN;
+        }
+
+        let maxlen = match UserRangeLen(addr, maxlen) {
+            Err(e) => return ("".to_string(), Err(e)),
+            Ok(l) => l,
+        };
+
         return self.mm.CopyInString(self, addr, maxlen);
     }
 
@@ -540,6 +708,7 @@ impl Task {
         maxElemSize: usize,
         maxTotalSize: i32,
     ) -> Result<Vec<String>> {
+        CheckUserRange(addr, 8)?;
         return self.mm.CopyInVector(self, addr, maxElemSize, maxTotalSize);
     }
 
@@ -615,9 +784,48 @@ impl Task {
         if iovsnum > UIO_MAXIOV {
             return Err(Error::SysError(SysErr::EINVAL))
         }
-        return self.mm.CopyInVec(self, iovs, iovsnum);
+        let mut iovs = self.CopyInVec(iovs, iovsnum)?;
+        Self::CheckUserIovs(&mut iovs)?;
+        return Ok(iovs);
+    }
+
+    // UserIoVec returns the iovec of a user buffer, the length is clamped to
+    // MAX_RW_COUNT as the one of read(2) and write(2).
+    pub fn UserIoVec(&self, addr: u64, len: usize) -> Result<IoVec> {
+        let mut len = len;
+        if len > MAX_RW_COUNT {
+            len = MAX_RW_COUNT;
+        }
+
+        CheckUserRange(addr, len as u64)?;
+        return Ok(IoVec::NewFromAddr(addr, len));
     }
 
+    // CheckUserIovs checks the iovecs from the user as
+    // lib/iov_iter.c:import_iovec does. The total length is clamped to
+    // MAX_RW_COUNT by truncating the iovecs.
+    pub fn CheckUserIovs(iovs: &mut [IoVec]) -> Result<()> {
+        let mut total = 0;
+        for iov in iovs.iter_mut() {
+            if iov.len as i64 <= 0 {
+                if iov.len != 0 {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+                continue;
+            }
+
+            CheckUserRange(iov.start, iov.len as u64)?;
+            if iov.len > MAX_RW_COUNT - total {
+                iov.len = MAX_RW_COUNT - total;
+            }
+            total += iov.len;
+        }
+
+        return Ok(());
+    }
+
+    // V2P translates the user range for the host IO, the pages are pinned
+    // till UnpinIovs.
     pub fn V2P(&self, start: u64, len: u64, output: &mut Vec<IoVec>, writable: bool, allowPartial: bool) -> Result<()> {
         return self.mm.V2P(self, start, len, output, writable, allowPartial);
     }
@@ -627,10 +835,63 @@ impl Task {
     }
 
     pub fn V2PIovs(&self, iovs: &[IoVec], writable: bool, output: &mut Vec<IoVec>, allowPartial: bool) -> Result<()> {
+        let cnt = output.len();
         for iov in iovs {
-            self.V2PIov(iov, output, writable, allowPartial)?;
+            if let Err(e) = self.V2PIov(iov, output, writable, allowPartial) {
+                UnpinIovs(&output[cnt..]);
+                output.truncate(cnt);
+                return Err(e);
+            }
         }
 
         return Ok(());
     }
+
+    pub fn UnpinIovs(&self, iovs: &[IoVec]) {
+        UnpinIovs(iovs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn TestIsUserRange() {
+        assert!(IsUserRange(0x1000, 0x1000));
+        assert!(IsUserRange(MemoryDef::LOWER_TOP - 0x1000, 0x1000));
+        assert!(IsUserRange(MemoryDef::PHY_UPPER_ADDR, 0x1000));
+        assert!(!IsUserRange(MemoryDef::LOWER_TOP - 0x1000, 0x1001));
+        assert!(!IsUserRange(MemoryDef::PHY_LOWER_ADDR, 8));
+        assert!(!IsUserRange(MemoryDef::KVM_IOEVENTFD_BASEADDR - 8, 16));
+        assert!(!IsUserRange(MemoryDef::PHY_UPPER_ADDR - 8, 16));
+        assert!(!IsUserRange(!0 - 8, 16));
+    }
+
+    #[test]
+    fn TestElemAddr() {
+        assert_eq!(ElemAddr::<u64>(0x1000, 3).unwrap(), 0x1018);
+        assert!(ElemAddr::<u64>(!0 - 8, 2).is_err());
+        assert!(ElemAddr::<u64>(0x1000, usize::MAX / 4).is_err());
+        assert!(CheckAligned::<u32>(0x1004).is_ok());
+        assert!(CheckAligned::<u32>(0x1002).is_err());
+    }
+
+    #[test]
+    fn TestCheckUserIovs() {
+        let mut iovs = [
+            IoVec::NewFromAddr(0x1000, MAX_RW_COUNT - 0x1000),
+            IoVec::NewFromAddr(0x1000_0000, 0x2000),
+            IoVec::NewFromAddr(0x2000_0000, 0x1000),
+        ];
+        Task::CheckUserIovs(&mut iovs).unwrap();
+        assert_eq!(iovs[1].len, 0x1000);
+        assert_eq!(iovs[2].len, 0);
+
+        let mut iovs = [IoVec::NewFromAddr(0x1000, !0)];
+        assert!(Task::CheckUserIovs(&mut iovs).is_err());
+
+        let mut iovs = [IoVec::NewFromAddr(MemoryDef::PHY_LOWER_ADDR, 0x1000)];
+        assert!(Task::CheckUserIovs(&mut iovs).is_err());
+    }
 }
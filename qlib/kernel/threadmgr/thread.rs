@@ -29,7 +29,9 @@ use super::super::kernel::fd_table::*;
 use super::super::kernel::fs_context::*;
 use super::super::kernel::ipc_namespace::*;
 use super::super::kernel::kernel::*;
+use super::super::kernel::seccomp::SeccompFilter;
 use super::super::kernel::time::*;
+use super::super::kernel::time_namespace::*;
 use super::super::kernel::uts_namespace::*;
 use super::super::kernel::waiter::queue::*;
 use super::super::kernel::waiter::waitgroup::*;
@@ -81,6 +83,11 @@ pub struct ThreadInternal {
     pub utsns: UTSNamespace,
     pub ipcns: IPCNamespace,
 
+    // timens is the time namespace of the task, the children it creates join
+    // timensForChildren, which unshare(CLONE_NEWTIME) replaces.
+    pub timens: TimeNamespace,
+    pub timensForChildren: TimeNamespace,
+
     pub SignalQueue: Queue,
 
     // tg is the thread group that this task belongs to. The tg pointer is
@@ -322,18 +329,15 @@ pub struct ThreadInternal {
     pub ioUsage: IO,
 
     pub robust_list_head: u64,
+
+    // seccomp is the last seccomp filter installed by the task, the filters
+    // are inherited by the children and kept across execve.
+    pub seccomp: Option<Arc<SeccompFilter>>,
 }
 
 impl ThreadInternal {
     pub fn IsChrooted(&self) -> bool {
-        let realRoot = self
-            .k
-            .mounts
-            .read()
-            .get(&self.containerID)
-            .unwrap()
-            .root
-            .clone();
+        let realRoot = self.k.mounts.read().get(&self.containerID).unwrap().Root();
         let root = self.fsc.RootDirectory();
         return realRoot == root;
     }
@@ -426,6 +430,14 @@ impl Thread {
         return self.lock().utsns.clone();
     }
 
+    pub fn TimeNamespace(&self) -> TimeNamespace {
+        return self.lock().timens.clone();
+    }
+
+    pub fn TimeNamespaceForChildren(&self) -> TimeNamespace {
+        return self.lock().timensForChildren.clone();
+    }
+
     pub fn MemoryManager(&self) -> MemoryManager {
         return self.lock().memoryMgr.clone();
     }
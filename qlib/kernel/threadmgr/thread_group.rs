@@ -43,6 +43,10 @@ use super::session::*;
 use super::thread::*;
 use super::threads::*;
 
+// the range of oom_score_adj, see include/uapi/linux/oom.h
+pub const OOM_SCORE_ADJ_MIN: i32 = -1000;
+pub const OOM_SCORE_ADJ_MAX: i32 = 1000;
+
 #[derive(Default)]
 pub struct ThreadGroupInternal {
     // pidns is the PID namespace containing the thread group and all of its
@@ -51,6 +55,10 @@ pub struct ThreadGroupInternal {
 
     pub eventQueue: Queue,
 
+    // exitQueue is notified when the last task of the thread group exits, the
+    // pidfds of the thread group wait on it.
+    pub exitQueue: Queue,
+
     // leader is the thread group's leader, which is the oldest task in the
     // thread group; usually the last task in the thread group to call
     // execve(), or if no such task exists then the first task in the thread
@@ -266,6 +274,31 @@ pub struct ThreadGroupInternal {
     // Resource limits for this ThreadGroup. The limits pointer is immutable.
     pub limits: LimitSet,
 
+    // oomScoreAdj is the /proc/[pid]/oom_score_adj of the thread group, it is
+    // inherited by the children.
+    pub oomScoreAdj: i32,
+
+    // isChildSubreaper is the "child subreaper" attribute set with
+    // prctl(PR_SET_CHILD_SUBREAPER). The orphaned descendants of a subreaper
+    // are reparented to it rather than to the init process.
+    //
+    // isChildSubreaper is protected by the TaskSet mutex.
+    pub isChildSubreaper: bool,
+
+    // hasChildSubreaper is true if any ancestor of the thread group is a
+    // subreaper, it saves the walk up the process tree when there is none.
+    // It is analogous to Linux's signal_struct::has_child_subreaper.
+    //
+    // hasChildSubreaper is protected by the TaskSet mutex.
+    pub hasChildSubreaper: bool,
+
+    // If initReaped is true, the thread group is an orphan reparented to the
+    // init process with Config.BuiltinInit, and the kernel reaps it when it
+    // exits rather than the init process.
+    //
+    // initReaped is protected by the TaskSet mutex.
+    pub initReaped: bool,
+
     // execed indicates an exec has occurred since creation. This will be
     // set by finishExec, and new TheadGroups will have this field cleared.
     // When execed is set, the processGroup may no longer be changed.
@@ -279,8 +312,6 @@ pub struct ThreadGroupInternal {
     // root track whether this threadgroup is directly started by container provisioning
     pub root: bool,
     pub timerMu: Arc<QMutex<()>>,
-    // todo: handle tty
-    //pub tty: Option<TTY>
 }
 
 #[derive(Default)]
@@ -533,7 +564,7 @@ impl ThreadGroup {
         checkExec: bool,
     ) -> Result<()> {
         let owner = pidns.lock().owner.clone();
-        let _r = owner.ReadLock();
+        let _l = owner.WriteLock();
 
         let pg = match pidns.lock().processGroups.get(&pgid) {
             None => return Err(Error::SysError(SysErr::EPERM)),
@@ -554,7 +585,6 @@ impl ThreadGroup {
         pg.incRefWithParent(parentPG.clone());
 
         let pgCurr = self.lock().processGroup.clone().unwrap();
-        pg.incRefWithParent(Some(pgCurr.clone()));
         self.forEachChildThreadGroupLocked(|childTG: ThreadGroup| {
             let pgTmp = childTG.lock().processGroup.clone().unwrap();
             pgTmp.incRefWithParent(Some(pg.clone()));
@@ -590,6 +620,57 @@ impl ThreadGroup {
         return self.lock().limits.clone();
     }
 
+    pub fn OomScoreAdj(&self) -> i32 {
+        return self.lock().oomScoreAdj;
+    }
+
+    pub fn SetOomScoreAdj(&self, adj: i32) -> Result<()> {
+        if adj < OOM_SCORE_ADJ_MIN || adj > OOM_SCORE_ADJ_MAX {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        self.lock().oomScoreAdj = adj;
+        return Ok(());
+    }
+
+    pub fn IsChildSubreaper(&self) -> bool {
+        let ts = self.TaskSet();
+        let _r = ts.ReadLock();
+        return self.lock().isChildSubreaper;
+    }
+
+    // SetChildSubreaper sets or unsets the "child subreaper" attribute of the
+    // thread group. Compare kernel/sys.c:prctl PR_SET_CHILD_SUBREAPER.
+    pub fn SetChildSubreaper(&self, isSubreaper: bool) {
+        let ts = self.TaskSet();
+        let _l = ts.WriteLock();
+
+        self.lock().isChildSubreaper = isSubreaper;
+        if isSubreaper {
+            // The descendants forked before keep hasChildSubreaper set when
+            // the attribute is unset, as Linux does.
+            self.forEachChildThreadGroupLocked(|childTG: ThreadGroup| {
+                childTG.setHasChildSubreaperLocked();
+            });
+        }
+    }
+
+    // setHasChildSubreaperLocked marks the thread group and its descendants
+    // as having a subreaper ancestor.
+    //
+    // Preconditions: The TaskSet mutex must be locked for writing.
+    fn setHasChildSubreaperLocked(&self) {
+        if self.lock().hasChildSubreaper {
+            // the descendants are already marked
+            return;
+        }
+
+        self.lock().hasChildSubreaper = true;
+        self.forEachChildThreadGroupLocked(|childTG: ThreadGroup| {
+            childTG.setHasChildSubreaperLocked();
+        });
+    }
+
     pub fn release(&self) {
         // Timers must be destroyed without holding the TaskSet or signal mutexes
         // since timers send signals with Timer.mu locked.
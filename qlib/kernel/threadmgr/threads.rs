@@ -169,6 +169,8 @@ impl TaskSet {
 
     pub fn NewTask(&self, cfg: &TaskConfig, fromContext: bool, kernel: &Kernel) -> Result<Thread> {
         let tg = cfg.ThreadGroup.clone();
+        // the offsets of the time namespace are fixed from now on
+        cfg.TimeNamespace.Enter();
 
         let internal = ThreadInternal {
             id: 0,
@@ -183,6 +185,8 @@ impl TaskSet {
             creds: cfg.Credentials.clone(),
             utsns: cfg.UTSNamespace.clone(),
             ipcns: cfg.IPCNamespace.clone(),
+            timens: cfg.TimeNamespace.clone(),
+            timensForChildren: cfg.TimeNamespace.clone(),
             SignalQueue: Queue::default(),
             tg: tg.clone(),
             parent: cfg.Parent.clone(),
@@ -221,6 +225,7 @@ impl TaskSet {
             containerID: cfg.ContainerID.to_string(),
             ioUsage: IO::default(),
             robust_list_head: 0,
+            seccomp: None,
         };
 
         let t = Thread {
@@ -272,6 +277,15 @@ impl TaskSet {
         let leader = tg.lock().leader.Upgrade();
         if leader.is_none() {
             tg.lock().leader = t.Downgrade();
+            if let Some(p) = &parent {
+                let ptg = p.lock().tg.clone();
+                let hasChildSubreaper = {
+                    let ptg = ptg.lock();
+                    ptg.hasChildSubreaper || ptg.isChildSubreaper
+                };
+                tg.lock().hasChildSubreaper = hasChildSubreaper;
+            }
+
             let parentPG = tg.parentPG();
             if parentPG.is_none() {
                 tg.createSession().unwrap();
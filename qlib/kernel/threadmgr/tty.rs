@@ -0,0 +1,414 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::sync::Arc;
+use core::ops::Deref;
+
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::fs::file::*;
+use super::super::task::*;
+use super::super::SignalDef::*;
+use super::processgroup::*;
+use super::session::*;
+use super::thread::*;
+use super::thread_group::*;
+
+// TTYInternal is the job control state of a terminal, see
+// drivers/tty/tty_jobctrl.c.
+#[derive(Default)]
+pub struct TTYInternal {
+    // session is the session of which the terminal is the controlling
+    // terminal.
+    pub session: Option<Session>,
+
+    // fgProcessGroup is the foreground process group of the session.
+    pub fgProcessGroup: Option<ProcessGroup>,
+}
+
+#[derive(Clone, Default)]
+pub struct TTY(Arc<QMutex<TTYInternal>>);
+
+impl Deref for TTY {
+    type Target = Arc<QMutex<TTYInternal>>;
+
+    fn deref(&self) -> &Arc<QMutex<TTYInternal>> {
+        &self.0
+    }
+}
+
+impl PartialEq for TTY {
+    fn eq(&self, other: &Self) -> bool {
+        return Arc::ptr_eq(&self.0, &other.0);
+    }
+}
+
+impl Eq for TTY {}
+
+impl TTY {
+    // Init makes the terminal the controlling terminal of the session of pg,
+    // with pg in the foreground. It is used for the terminal of a process
+    // started by the container runtime.
+    pub fn Init(&self, pg: &ProcessGroup) {
+        let session = pg.Session();
+        {
+            let mut t = self.lock();
+            if t.fgProcessGroup.is_some() {
+                panic!("foreground process group is already set");
+            }
+
+            t.session = Some(session.clone());
+            t.fgProcessGroup = Some(pg.clone());
+        }
+
+        session.lock().tty = Some(self.clone());
+    }
+
+    pub fn ForegroundProcessGroup(&self) -> Option<ProcessGroup> {
+        return self.lock().fgProcessGroup.clone();
+    }
+
+    // CheckChange checks whether the task may read from (SIGTTIN) or write to
+    // or change (SIGTTOU) the terminal. A task of a background process group
+    // stops its process group with sig. Compare
+    // drivers/tty/tty_jobctrl.c:__tty_check_change.
+    pub fn CheckChange(&self, task: &Task, sig: Signal) -> Result<()> {
+        let thread = match &task.thread {
+            // No task? Linux does not have an analog for this case, but
+            // tty_check_change is more of a blacklist of cases than a
+            // whitelist, and is surprisingly permissive. Allowing the
+            // change seems most appropriate.
+            None => return Ok(()),
+            Some(ref t) => t.clone(),
+        };
+
+        let tg = thread.ThreadGroup();
+        let pg = match tg.ProcessGroup() {
+            None => return Ok(()),
+            Some(pg) => pg,
+        };
+
+        let (session, fg) = {
+            let t = self.lock();
+            (t.session.clone(), t.fgProcessGroup.clone())
+        };
+
+        // If the session for the task is different than the session for the
+        // controlling TTY, then the change is allowed. Seems like a bad idea,
+        // but that's exactly what linux does.
+        if session != Some(pg.Session()) {
+            return Ok(());
+        }
+
+        // If we are the foreground process group, then the change is allowed.
+        if fg == Some(pg.clone()) {
+            return Ok(());
+        }
+
+        // We are not the foreground process group.
+
+        // Is the provided signal blocked or ignored?
+        if thread.SignalMask().0 & SignalSet::MakeSignalSet(&[sig]).0 != 0
+            || tg.SignalHandlers().IsIgored(sig)
+        {
+            // If the signal is SIGTTIN, then we are attempting to read from
+            // the TTY. Don't send the signal and return EIO.
+            if sig.0 == Signal::SIGTTIN {
+                return Err(Error::SysError(SysErr::EIO));
+            }
+
+            // Otherwise, we are writing or changing terminal state. This is
+            // allowed.
+            return Ok(());
+        }
+
+        // If the process group is an orphan, return EIO.
+        if pg.IsOrphan() {
+            return Err(Error::SysError(SysErr::EIO));
+        }
+
+        // Otherwise, send the signal to the process group and return
+        // ERESTARTSYS.
+        pg.SendSignal(&SignalInfo::SignalInfoPriv(sig)).ok();
+        return Err(Error::SysError(SysErr::ERESTARTSYS));
+    }
+
+    // Ioctl handles the job control ioctls of the terminal.
+    pub fn Ioctl(&self, task: &Task, f: &File, request: u64, val: u64) -> Result<()> {
+        let tg = match &task.thread {
+            None => return Err(Error::SysError(SysErr::ENOTTY)),
+            Some(ref t) => t.ThreadGroup(),
+        };
+
+        match request {
+            IoCtlCmd::TIOCGPGRP => {
+                let pgid = tg.ForegroundProcessGroupID(self)?;
+                task.CopyOutObj(&pgid, val)?;
+                return Ok(());
+            }
+            IoCtlCmd::TIOCSPGRP => {
+                match self.CheckChange(task, Signal(Signal::SIGTTOU)) {
+                    // drivers/tty/tty_io.c:tiocspgrp() converts -EIO from
+                    // tty_check_change() to -ENOTTY.
+                    Err(Error::SysError(SysErr::EIO)) => {
+                        return Err(Error::SysError(SysErr::ENOTTY))
+                    }
+                    Err(e) => return Err(e),
+                    Ok(()) => (),
+                }
+
+                let pgid: i32 = task.CopyInObj(val)?;
+                return tg.SetForegroundProcessGroup(self, pgid);
+            }
+            IoCtlCmd::TIOCSCTTY => {
+                // steal the terminal from another session with val 1
+                let isAdmin = task.Creds().HasCapability(Capability::CAP_SYS_ADMIN);
+                return tg.SetControllingTTY(self, val == 1, isAdmin, f.Flags().Read);
+            }
+            IoCtlCmd::TIOCNOTTY => {
+                return tg.ReleaseControllingTTY(self);
+            }
+            IoCtlCmd::TIOCGSID => {
+                let sid = tg.SessionIDOfTTY(self)?;
+                task.CopyOutObj(&sid, val)?;
+                return Ok(());
+            }
+            _ => return Err(Error::SysError(SysErr::ENOTTY)),
+        }
+    }
+}
+
+impl ThreadGroup {
+    fn sessionLocked(&self) -> Option<Session> {
+        return match &self.lock().processGroup {
+            None => None,
+            Some(pg) => Some(pg.Session()),
+        };
+    }
+
+    // SetControllingTTY makes tty the controlling terminal of the session of
+    // the thread group, which has to be the session leader. With steal, the
+    // terminal is taken from the session it controls.
+    pub fn SetControllingTTY(
+        &self,
+        tty: &TTY,
+        steal: bool,
+        isAdmin: bool,
+        isReadable: bool,
+    ) -> Result<()> {
+        let ts = self.TaskSet();
+        let _w = ts.WriteLock();
+
+        let session = match self.sessionLocked() {
+            None => return Err(Error::SysError(SysErr::EPERM)),
+            Some(s) => s,
+        };
+
+        // "The calling process must be a session leader and not have a
+        // controlling terminal already." - tty_ioctl(4)
+        if session.lock().leader != self.clone() {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        let ctty = session.lock().tty.clone();
+        if let Some(ctty) = ctty {
+            if ctty == *tty {
+                return Ok(());
+            }
+
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        // Linux requires the terminal open for reading, or CAP_SYS_ADMIN.
+        if !isReadable && !isAdmin {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        let other = tty.lock().session.clone();
+        if let Some(other) = other {
+            // "If this terminal is already the controlling terminal of a
+            // different session group, then the ioctl fails with EPERM,
+            // unless the caller has the CAP_SYS_ADMIN capability and arg
+            // equals 1, in which case the terminal is stolen, and all
+            // processes that had it as controlling terminal lose it."
+            if !steal || !isAdmin {
+                return Err(Error::SysError(SysErr::EPERM));
+            }
+
+            other.lock().tty = None;
+        }
+
+        {
+            let mut t = tty.lock();
+            t.session = Some(session.clone());
+            t.fgProcessGroup = self.lock().processGroup.clone();
+        }
+
+        session.lock().tty = Some(tty.clone());
+        return Ok(());
+    }
+
+    // ReleaseControllingTTY gives up tty as the controlling terminal. When
+    // the caller is the session leader, the whole session loses the terminal.
+    pub fn ReleaseControllingTTY(&self, tty: &TTY) -> Result<()> {
+        let ts = self.TaskSet();
+        let _w = ts.WriteLock();
+
+        let session = match self.sessionLocked() {
+            None => return Err(Error::SysError(SysErr::ENOTTY)),
+            Some(s) => s,
+        };
+
+        if session.lock().tty != Some(tty.clone()) {
+            return Err(Error::SysError(SysErr::ENOTTY));
+        }
+
+        // The controlling terminal is tracked per session, a process other
+        // than the session leader keeps it.
+        if session.lock().leader != self.clone() {
+            return Ok(());
+        }
+
+        self.disassociateTTYLocked(&session, false);
+        return Ok(());
+    }
+
+    // ReleaseSessionTTYLocked is called when the thread group exits, the
+    // session of a session leader loses its controlling terminal.
+    //
+    // Preconditions: The TaskSet mutex must be locked for writing.
+    pub fn ReleaseSessionTTYLocked(&self) {
+        let session = match self.sessionLocked() {
+            None => return,
+            Some(s) => s,
+        };
+
+        if session.lock().leader != self.clone() || session.lock().tty.is_none() {
+            return;
+        }
+
+        self.disassociateTTYLocked(&session, true);
+    }
+
+    // disassociateTTYLocked ends the association of the session with its
+    // controlling terminal and hangs up the foreground process group. Compare
+    // drivers/tty/tty_jobctrl.c:disassociate_ctty.
+    //
+    // Preconditions: The TaskSet mutex must be locked for writing.
+    fn disassociateTTYLocked(&self, session: &Session, onExit: bool) {
+        let tty = match session.lock().tty.take() {
+            None => return,
+            Some(tty) => tty,
+        };
+
+        let fg = {
+            let mut t = tty.lock();
+            t.session = None;
+            t.fgProcessGroup.take()
+        };
+
+        if let Some(fg) = fg {
+            fg.SendSignalLocked(&SignalInfo::SignalInfoPriv(Signal(Signal::SIGHUP)))
+                .ok();
+            if !onExit {
+                fg.SendSignalLocked(&SignalInfo::SignalInfoPriv(Signal(Signal::SIGCONT)))
+                    .ok();
+            }
+        }
+    }
+
+    // ForegroundProcessGroupID returns the ID of the foreground process group
+    // of tty in the PID namespace of the thread group.
+    pub fn ForegroundProcessGroupID(&self, tty: &TTY) -> Result<ProcessGroupID> {
+        let ts = self.TaskSet();
+        let _r = ts.ReadLock();
+
+        let session = self.sessionLocked();
+        if session.is_none() || session.unwrap().lock().tty != Some(tty.clone()) {
+            return Err(Error::SysError(SysErr::ENOTTY));
+        }
+
+        let fg = match tty.lock().fgProcessGroup.clone() {
+            None => return Ok(0),
+            Some(pg) => pg,
+        };
+
+        let pidns = self.PIDNamespace();
+        let id = match pidns.lock().pgids.get(&fg) {
+            None => 0,
+            Some(id) => *id,
+        };
+
+        return Ok(id);
+    }
+
+    // SetForegroundProcessGroup moves the process group pgid of the session
+    // to the foreground of tty.
+    pub fn SetForegroundProcessGroup(&self, tty: &TTY, pgid: ProcessGroupID) -> Result<()> {
+        if pgid < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let ts = self.TaskSet();
+        let _w = ts.WriteLock();
+
+        let session = match self.sessionLocked() {
+            None => return Err(Error::SysError(SysErr::ENOTTY)),
+            Some(s) => s,
+        };
+
+        if session.lock().tty != Some(tty.clone()) {
+            return Err(Error::SysError(SysErr::ENOTTY));
+        }
+
+        let pidns = self.PIDNamespace();
+        let pg = match pidns.lock().processGroups.get(&pgid) {
+            None => return Err(Error::SysError(SysErr::ESRCH)),
+            Some(pg) => pg.clone(),
+        };
+
+        // Check that new process group is in the TTY session.
+        if pg.Session() != session {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        tty.lock().fgProcessGroup = Some(pg);
+        return Ok(());
+    }
+
+    // SessionIDOfTTY returns the ID of the session tty controls, which has
+    // to be the session of the thread group.
+    pub fn SessionIDOfTTY(&self, tty: &TTY) -> Result<SessionID> {
+        let ts = self.TaskSet();
+        let _r = ts.ReadLock();
+
+        let session = match self.sessionLocked() {
+            None => return Err(Error::SysError(SysErr::ENOTTY)),
+            Some(s) => s,
+        };
+
+        if session.lock().tty != Some(tty.clone()) {
+            return Err(Error::SysError(SysErr::ENOTTY));
+        }
+
+        let pidns = self.PIDNamespace();
+        let id = match pidns.lock().sids.get(&session) {
+            None => 0,
+            Some(id) => *id,
+        };
+
+        return Ok(id);
+    }
+}
@@ -17,6 +17,7 @@ use core::sync::atomic::Ordering;
 
 use super::asm::*;
 //use super::IOURING;
+use super::super::cpuid::HostID;
 use super::super::singleton::*;
 use super::super::vcpu_mgr::*;
 use super::SHARESPACE;
@@ -61,6 +62,26 @@ pub enum PrCtlEnum {
     ARCH_GET_GS = 0x1004,
 }
 
+// cpuid 0x80000001 edx
+const CPUID_EXT_EDX_RDTSCP: u32 = 1 << 27;
+
+// TscAuxSupported returns whether the vcpu has rdtscp and TSC_AUX.
+pub fn TscAuxSupported() -> bool {
+    let (_, _, _, edx) = HostID(0x80000001, 0);
+    return edx & CPUID_EXT_EDX_RDTSCP != 0;
+}
+
+// SetTscAux sets the TSC_AUX of the vcpu to (node << 12) | cpu as Linux does,
+// so the vdso getcpu and GetCpu get the vcpu the task runs on with rdtscp.
+pub fn SetTscAux(cpuId: u64) {
+    if !TscAuxSupported() {
+        return;
+    }
+
+    // there is only one numa node
+    WriteMsr(MSR::MSR_TSC_AUX as u32, cpuId & 0xfff);
+}
+
 pub fn RegisterSysCall(addr: u64) {
     //WriteMsr(MSR::MSR_STAR as u32, 0x00200008<<32);
     WriteMsr(MSR::MSR_STAR as u32, 0x00100008 << 32);
@@ -12,6 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::qlib::mutex::*;
+use alloc::string::String;
+use alloc::string::ToString;
+
 pub enum Arch {
     // AMD64 is the x86-64 architecture.
     AMD64,
@@ -57,3 +61,42 @@ pub const VERSION: Version = Version {
     Release: LINUX_RELEASE,
     Version: LINUX_VERSION,
 };
+
+// UTS_LEN is the maximum length of the strings of uname(2).
+pub const UTS_LEN: usize = 64;
+
+// The release and the version reported by uname(2) and /proc/sys/kernel. Some
+// applications gate features on the kernel version, so they can be set per
+// sandbox to the kernel the application is tested with.
+lazy_static! {
+    pub static ref KERNEL_RELEASE: QRwLock<String> = QRwLock::new(LINUX_RELEASE.to_string());
+    pub static ref KERNEL_VERSION: QRwLock<String> = QRwLock::new(LINUX_VERSION.to_string());
+}
+
+// SetUname overrides the release and the version, an empty one is kept.
+pub fn SetUname(release: &str, version: &str) {
+    if release.len() > 0 {
+        *KERNEL_RELEASE.write() = Truncate(release);
+    }
+
+    if version.len() > 0 {
+        *KERNEL_VERSION.write() = Truncate(version);
+    }
+}
+
+fn Truncate(s: &str) -> String {
+    let mut end = if s.len() > UTS_LEN { UTS_LEN } else { s.len() };
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    return s[..end].to_string();
+}
+
+pub fn KernelRelease() -> String {
+    return KERNEL_RELEASE.read().clone();
+}
+
+pub fn KernelVersion() -> String {
+    return KERNEL_VERSION.read().clone();
+}
@@ -71,6 +71,21 @@ pub unsafe fn InitSingleton() {
     FROM_LINUX_RESOURCE.Init(SortArr::New(&arr));
 }
 
+// SETABLE_LIMITS are the limits a process can set.
+pub const SETABLE_LIMITS: [LimitType; 9] = [
+    LimitType::NumberOfFiles,
+    LimitType::AS,
+    LimitType::CPU,
+    LimitType::Data,
+    LimitType::FileSize,
+    LimitType::MemoryLocked,
+    LimitType::Stack,
+    // These are not enforced, but we include them here to avoid returning
+    // EPERM, since some apps expect them to succeed.
+    LimitType::Core,
+    LimitType::ProcessCount,
+];
+
 pub const INFINITY: u64 = u64::MAX;
 
 pub fn FromLinux(rl: u64) -> u64 {
@@ -13,6 +13,8 @@
 // limitations under the License.
 
 use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
 
 pub const IFNAMSIZ: usize = 16;
 
@@ -48,12 +50,24 @@ impl IFReq {
         for i in 0..len {
             if self.IFName[i] == 0 {
                 idx = i;
+                break;
             }
         }
 
         return String::from_utf8(self.IFName[0..idx].to_vec()).expect("IFReq Name() fail");
     }
 
+    // DataPtr returns ifr_data, the user address of the SIOCETHTOOL argument.
+    pub fn DataPtr(&self) -> u64 {
+        let mut ptr = [0u8; 8];
+        ptr.copy_from_slice(&self.Data[0..8]);
+        return u64::from_ne_bytes(ptr);
+    }
+
+    pub fn SetDataPtr(&mut self, ptr: u64) {
+        self.Data[0..8].copy_from_slice(&ptr.to_ne_bytes());
+    }
+
     pub fn SetName(&mut self, name: &str) {
         assert!(name.len() <= IFNAMSIZ, "IFReq setname is too large");
         for i in 0..name.len() {
@@ -91,3 +105,160 @@ pub struct IFConf {
     pub _pad: [u8; 4],
     pub Ptr: u64,
 }
+
+// The SIOCETHTOOL commands, see include/uapi/linux/ethtool.h. The queries
+// used to probe an interface, its statistics (ethtool -S) and its ring sizes
+// (ethtool -g/-G) are supported.
+pub const ETHTOOL_GSET: u32 = 0x1;
+pub const ETHTOOL_GDRVINFO: u32 = 0x3;
+pub const ETHTOOL_GLINK: u32 = 0xa;
+pub const ETHTOOL_GRINGPARAM: u32 = 0x10;
+pub const ETHTOOL_SRINGPARAM: u32 = 0x11;
+pub const ETHTOOL_GSTRINGS: u32 = 0x1b;
+pub const ETHTOOL_GSTATS: u32 = 0x1d;
+pub const ETHTOOL_GSSET_INFO: u32 = 0x37;
+
+pub const ETH_GSTRING_LEN: usize = 32;
+
+// sizeof(struct ethtool_cmd), sizeof(struct ethtool_drvinfo),
+// sizeof(struct ethtool_value) and sizeof(struct ethtool_ringparam)
+pub const SIZE_OF_ETHTOOL_CMD: usize = 44;
+pub const SIZE_OF_ETHTOOL_DRVINFO: usize = 196;
+pub const SIZE_OF_ETHTOOL_VALUE: usize = 8;
+pub const SIZE_OF_ETHTOOL_RINGPARAM: usize = 36;
+
+// the fixed part of struct ethtool_gstrings, ethtool_stats and
+// ethtool_sset_info, the data array follows it
+pub const SIZE_OF_ETHTOOL_GSTRINGS: usize = 12;
+pub const SIZE_OF_ETHTOOL_STATS: usize = 8;
+pub const SIZE_OF_ETHTOOL_SSET_INFO: usize = 16;
+
+// the max size of the data array of an ethtool command
+pub const MAX_ETHTOOL_DATA_SIZE: usize = 1 << 20;
+
+// EthtoolArgSize returns the size of the structure ifr_data points to for the
+// ethtool command, without the data array of the variable length ones, or
+// None if the command is not supported.
+pub fn EthtoolArgSize(cmd: u32) -> Option<usize> {
+    match cmd {
+        ETHTOOL_GSET => return Some(SIZE_OF_ETHTOOL_CMD),
+        ETHTOOL_GDRVINFO => return Some(SIZE_OF_ETHTOOL_DRVINFO),
+        ETHTOOL_GLINK => return Some(SIZE_OF_ETHTOOL_VALUE),
+        ETHTOOL_GRINGPARAM | ETHTOOL_SRINGPARAM => return Some(SIZE_OF_ETHTOOL_RINGPARAM),
+        ETHTOOL_GSTRINGS => return Some(SIZE_OF_ETHTOOL_GSTRINGS),
+        ETHTOOL_GSTATS => return Some(SIZE_OF_ETHTOOL_STATS),
+        ETHTOOL_GSSET_INFO => return Some(SIZE_OF_ETHTOOL_SSET_INFO),
+        _ => return None,
+    }
+}
+
+pub const ETH_SS_STATS: u32 = 1;
+
+fn EthtoolWord(arg: &[u8], i: usize) -> u32 {
+    let mut v = [0u8; 4];
+    v.copy_from_slice(&arg[i * 4..i * 4 + 4]);
+    return u32::from_ne_bytes(v);
+}
+
+// EthtoolStringSet returns the string set of the ethtool command whose data
+// array has an entry per string of the set. The size of the array is the one
+// of the set in the driver, not the one the caller gives.
+pub fn EthtoolStringSet(cmd: u32, arg: &[u8]) -> Option<u32> {
+    match cmd {
+        // ethtool_gstrings.string_set
+        ETHTOOL_GSTRINGS => return Some(EthtoolWord(arg, 1)),
+        ETHTOOL_GSTATS => return Some(ETH_SS_STATS),
+        _ => return None,
+    }
+}
+
+// EthtoolEntrySize returns the size of an entry of the data array of the
+// ethtool command.
+pub fn EthtoolEntrySize(cmd: u32) -> usize {
+    match cmd {
+        ETHTOOL_GSTRINGS => return ETH_GSTRING_LEN,
+        ETHTOOL_GSTATS => return 8,
+        ETHTOOL_GSSET_INFO => return 4,
+        _ => return 0,
+    }
+}
+
+// EthtoolSsetInfoEntries returns the max entries of the data array of
+// ETHTOOL_GSSET_INFO, one for each set of ethtool_sset_info.sset_mask.
+pub fn EthtoolSsetInfoEntries(arg: &[u8]) -> usize {
+    return (EthtoolWord(arg, 2).count_ones() + EthtoolWord(arg, 3).count_ones()) as usize;
+}
+
+// EthtoolIsSet returns whether the ethtool command changes the interface.
+pub fn EthtoolIsSet(cmd: u32) -> bool {
+    return cmd == ETHTOOL_SRINGPARAM;
+}
+
+// The max interfaces of the sandbox network namespace the host reports.
+pub const MAX_NETDEVS: usize = 64;
+pub const MAX_ADDR_LEN: usize = 32;
+
+// NetDevInfo is an interface of the sandbox network namespace with its
+// statistics, the host gets them from the rtnetlink link dump (IFLA_*).
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NetDevInfo {
+    pub Name: [u8; IFNAMSIZ],
+    pub Index: i32,
+    // ARPHRD_*
+    pub Type: u16,
+    // IF_OPER_*
+    pub OperState: u8,
+    pub Carrier: u8,
+    pub Flags: u32,
+    pub Mtu: u32,
+    pub TxQueueLen: u32,
+    pub AddrLen: u32,
+    pub Addr: [u8; MAX_ADDR_LEN],
+    pub Stats: NetDevStats,
+}
+
+// NetDevStats is the head of struct rtnl_link_stats64.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NetDevStats {
+    pub RxPackets: u64,
+    pub TxPackets: u64,
+    pub RxBytes: u64,
+    pub TxBytes: u64,
+    pub RxErrors: u64,
+    pub TxErrors: u64,
+    pub RxDropped: u64,
+    pub TxDropped: u64,
+    pub Multicast: u64,
+    pub Collisions: u64,
+}
+
+impl NetDevInfo {
+    pub fn Name(&self) -> String {
+        let end = self.Name.iter().position(|&c| c == 0).unwrap_or(IFNAMSIZ);
+        return String::from_utf8_lossy(&self.Name[..end]).to_string();
+    }
+
+    // OperStateString returns the operstate of the interface as sysfs shows it.
+    pub fn OperStateString(&self) -> &'static str {
+        match self.OperState {
+            1 => "notpresent",
+            2 => "down",
+            3 => "lowerlayerdown",
+            4 => "testing",
+            5 => "dormant",
+            6 => "up",
+            _ => "unknown",
+        }
+    }
+
+    pub fn AddrString(&self) -> String {
+        let len = core::cmp::min(self.AddrLen as usize, MAX_ADDR_LEN);
+        let bytes: Vec<String> = self.Addr[..len]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        return bytes.join(":");
+    }
+}
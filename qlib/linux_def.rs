@@ -547,6 +547,17 @@ pub struct LibcStatfs {
     pub Spare: [u64; 4],
 }
 
+// The mount flags of statfs(2).
+pub struct StatfsFlags {}
+
+impl StatfsFlags {
+    pub const ST_RDONLY: u64 = 0x0001;
+    pub const ST_NOSUID: u64 = 0x0002;
+    pub const ST_NODEV: u64 = 0x0004;
+    pub const ST_NOEXEC: u64 = 0x0008;
+    pub const ST_NOATIME: u64 = 0x0400;
+}
+
 // Filesystem types used in statfs(2).
 // See linux/magic.h.
 pub struct FSMagic {}
@@ -869,7 +880,7 @@ impl FilePermissions {
             m |= ModeType::S_ISUID;
         }
 
-        if self.SetUid {
+        if self.SetGid {
             m |= ModeType::S_ISGID;
         }
 
@@ -1334,6 +1345,7 @@ impl LibcConst {
     pub const IPV6_RECVERR: u64 = 0x19;
     pub const IPV6_RECVHOPLIMIT: u64 = 0x33;
     pub const IPV6_RECVHOPOPTS: u64 = 0x35;
+    pub const IPV6_RECVORIGDSTADDR: u64 = 0x4a;
     pub const IPV6_RECVPKTINFO: u64 = 0x31;
     pub const IPV6_RECVRTHDR: u64 = 0x38;
     pub const IPV6_RECVTCLASS: u64 = 0x42;
@@ -1346,6 +1358,7 @@ impl LibcConst {
     pub const IPV6_RXDSTOPTS: u64 = 0x3b;
     pub const IPV6_RXHOPOPTS: u64 = 0x36;
     pub const IPV6_TCLASS: u64 = 0x43;
+    pub const IPV6_TRANSPARENT: u64 = 0x4b;
     pub const IPV6_UNICAST_HOPS: u64 = 0x10;
     pub const IPV6_V6ONLY: u64 = 0x1a;
     pub const IPV6_XFRM_POLICY: u64 = 0x23;
@@ -1446,6 +1459,7 @@ impl LibcConst {
     pub const MNT_DETACH: u64 = 0x2;
     pub const MNT_EXPIRE: u64 = 0x4;
     pub const MNT_FORCE: u64 = 0x1;
+    pub const UMOUNT_NOFOLLOW: u64 = 0x8;
     pub const MSG_CMSG_CLOEXEC: u64 = 0x40000000;
     pub const MSG_CONFIRM: u64 = 0x800;
     pub const MSG_CTRUNC: u64 = 0x8;
@@ -1856,6 +1870,7 @@ impl LibcConst {
     pub const SIOCDEVPRIVATE: u64 = 0x89f0;
     pub const SIOCDIFADDR: u64 = 0x8936;
     pub const SIOCDRARP: u64 = 0x8960;
+    pub const SIOCETHTOOL: u64 = 0x8946;
     pub const SIOCGARP: u64 = 0x8954;
     pub const SIOCGIFADDR: u64 = 0x8915;
     pub const SIOCGIFBR: u64 = 0x8940;
@@ -1880,6 +1895,7 @@ impl LibcConst {
     pub const SIOCGRARP: u64 = 0x8961;
     pub const SIOCGSTAMP: u64 = 0x8906;
     pub const SIOCGSTAMPNS: u64 = 0x8907;
+    pub const SIOCOUTQNSD: u64 = 0x894b;
     pub const SIOCPROTOPRIVATE: u64 = 0x89e0;
     pub const SIOCRTMSG: u64 = 0x890d;
     pub const SIOCSARP: u64 = 0x8955;
@@ -1931,6 +1947,7 @@ impl LibcConst {
     pub const SO_ACCEPTCONN: u64 = 0x1e;
     pub const SO_ATTACH_FILTER: u64 = 0x1a;
     pub const SO_BINDTODEVICE: u64 = 0x19;
+    pub const SO_BINDTOIFINDEX: u64 = 0x3e;
     pub const SO_BROADCAST: u64 = 0x6;
     pub const SO_BSDCOMPAT: u64 = 0xe;
     pub const SO_REUSEPORT: u64 = 0xf;
@@ -2683,6 +2700,22 @@ impl IoCtlCmd {
     pub const SIOCSPGRP: u64 = 0x00008902;
     pub const FIOGETOWN: u64 = 0x00008903;
     pub const SIOCGPGRP: u64 = 0x00008904;
+
+    // the block device ioctls
+    pub const BLKROGET: u64 = 0x0000125e;
+    pub const BLKGETSIZE: u64 = 0x00001260;
+    pub const BLKFLSBUF: u64 = 0x00001261;
+    pub const BLKSSZGET: u64 = 0x00001268;
+    pub const BLKDISCARD: u64 = 0x00001277;
+    pub const BLKIOMIN: u64 = 0x00001278;
+    pub const BLKIOOPT: u64 = 0x00001279;
+    pub const BLKPBSZGET: u64 = 0x0000127b;
+    pub const BLKDISCARDZEROES: u64 = 0x0000127c;
+    pub const BLKSECDISCARD: u64 = 0x0000127d;
+    pub const BLKROTATIONAL: u64 = 0x0000127e;
+    pub const BLKZEROOUT: u64 = 0x0000127f;
+    pub const BLKBSZGET: u64 = 0x80081270;
+    pub const BLKGETSIZE64: u64 = 0x80081272;
 }
 
 #[derive(Clone, PartialEq, Copy, Debug)]
@@ -2811,6 +2844,15 @@ impl IDType {
     pub const P_ALL: i32 = 0x0;
     pub const P_PID: i32 = 0x1;
     pub const P_PGID: i32 = 0x2;
+    pub const P_PIDFD: i32 = 0x3;
+}
+
+pub struct SyncFileRangeFlags {}
+
+impl SyncFileRangeFlags {
+    pub const SYNC_FILE_RANGE_WAIT_BEFORE: u32 = 1;
+    pub const SYNC_FILE_RANGE_WRITE: u32 = 2;
+    pub const SYNC_FILE_RANGE_WAIT_AFTER: u32 = 4;
 }
 
 pub struct MAdviseOp {}
@@ -2821,6 +2863,7 @@ impl MAdviseOp {
     pub const MADV_SEQUENTIAL: i32 = 2;
     pub const MADV_WILLNEED: i32 = 3;
     pub const MADV_DONTNEED: i32 = 4;
+    pub const MADV_FREE: i32 = 8;
     pub const MADV_REMOVE: i32 = 9;
     pub const MADV_DONTFORK: i32 = 10;
     pub const MADV_DOFORK: i32 = 11;
@@ -2851,6 +2894,7 @@ impl CloneOp {
     pub const CLONE_NEWPID: i32 = 0x20000000;
     pub const CLONE_NEWUSER: i32 = 0x10000000;
     pub const CLONE_NEWUTS: i32 = 0x4000000;
+    pub const CLONE_NEWTIME: i32 = 0x80;
     pub const CLONE_PARENT: i32 = 0x8000;
     pub const CLONE_PARENT_SETTID: i32 = 0x100000;
     pub const CLONE_PTRACE: i32 = 0x2000;
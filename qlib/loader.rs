@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use alloc::string::String;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 
 use super::auth::cap_set::*;
@@ -38,6 +39,16 @@ pub struct Process {
     //host
     pub NumCpu: u32,
     pub HostName: String,
+    // the release and the version of uname(2), empty for the default
+    pub KernelRelease: String,
+    pub KernelVersion: String,
+    // the offsets in ns of the monotonic and the boottime clocks of the root
+    // time namespace
+    pub MonotonicOffset: i64,
+    pub BoottimeOffset: i64,
+    // the speculative execution mitigations of the guest kernel, MITIGATION_*
+    // of qlib::kernel::arch::x86_64::mitigations
+    pub Mitigations: u64,
 
     //Container
     pub limitSet: LimitSetInternal,
@@ -45,5 +56,94 @@ pub struct Process {
 
     pub Root: String,
     pub Stdiofds: [i32; 3],
+    // the host sockets passed by socket activation, installed from fd 3
+    pub ListenFds: Vec<i32>,
     pub ExecId: Option<String>,
+    // the virtual /etc files of the sandbox, None keeps the rootfs ones
+    pub EtcFiles: Option<EtcFiles>,
+    // the device nodes of the container the guest /dev shows as host backed
+    // files, e.g. the ones injected by CDI
+    pub Devices: Vec<String>,
+    // the io.max of the container, from the blkio throttle devices of its spec
+    pub IoMax: Vec<IoMax>,
+    // the partitions of the disks io.max limits, the IO of a file is keyed by
+    // the device of its filesystem, which may be a partition of the disk
+    pub IoMaxPartitions: Vec<BlockPartition>,
+    // the paths of the quark.prefetch annotation, read ahead by qvisor
+    pub Prefetch: Vec<String>,
+    // the size of the /dev/shm of the container, e.g. "64m", from its spec
+    pub ShmSize: String,
+    // the /dev/shm of the container is a volume of the user
+    pub ShmVolume: bool,
+}
+
+// IoMax is a line of the io.max of cgroup v2, the limits of the IO of a
+// container on a host device, 0 is no limit.
+#[derive(Serialize, Deserialize, Default, Debug, Eq, PartialEq, Clone, Copy)]
+pub struct IoMax {
+    pub Major: u32,
+    pub Minor: u32,
+    pub Rbps: u64,
+    pub Wbps: u64,
+    pub Riops: u64,
+    pub Wiops: u64,
+}
+
+impl IoMax {
+    // Line returns the line of the limits in io.max.
+    pub fn Line(&self) -> String {
+        let limit = |v: u64| {
+            if v == 0 {
+                "max".to_string()
+            } else {
+                format!("{}", v)
+            }
+        };
+
+        return format!(
+            "{}:{} rbps={} wbps={} riops={} wiops={}",
+            self.Major,
+            self.Minor,
+            limit(self.Rbps),
+            limit(self.Wbps),
+            limit(self.Riops),
+            limit(self.Wiops)
+        );
+    }
+}
+
+// BlockPartition is a partition of a host disk.
+#[derive(Serialize, Deserialize, Default, Debug, Eq, PartialEq, Clone, Copy)]
+pub struct BlockPartition {
+    pub Major: u32,
+    pub Minor: u32,
+    pub DiskMajor: u32,
+    pub DiskMinor: u32,
+}
+
+pub const ETC_RESOLV_CONF: &str = "/etc/resolv.conf";
+pub const ETC_HOSTS: &str = "/etc/hosts";
+pub const ETC_HOSTNAME: &str = "/etc/hostname";
+pub const ETC_MACHINE_ID: &str = "/etc/machine-id";
+
+// EtcFiles is the content of the /etc files the guest serves from memory, a
+// None file is left as it is.
+#[derive(Serialize, Deserialize, Default, Debug, Eq, PartialEq, Clone)]
+pub struct EtcFiles {
+    pub ResolvConf: Option<String>,
+    pub Hosts: Option<String>,
+    pub Hostname: Option<String>,
+    // the machine-id injected for the sandbox, it doesn't change at runtime
+    pub MachineId: Option<String>,
+}
+
+impl EtcFiles {
+    pub fn Files(&self) -> [(&'static str, &Option<String>); 4] {
+        return [
+            (ETC_RESOLV_CONF, &self.ResolvConf),
+            (ETC_HOSTS, &self.Hosts),
+            (ETC_HOSTNAME, &self.Hostname),
+            (ETC_MACHINE_ID, &self.MachineId),
+        ];
+    }
 }
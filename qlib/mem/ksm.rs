@@ -0,0 +1,78 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::linux_def::*;
+
+// The KSM hints of the guest heap. The pages of a host file are mapped from
+// the host page cache and are shared with the other sandboxes already, but a
+// private writable file mapping, e.g. the data segment of a binary or of a
+// library of the image, gets a copy of the file page in the heap at the
+// fault. The nodes packing many sandboxes of the same image hold many such
+// identical copies. With Ksm in the config qkernel hints the 2MB block of the
+// heap such a copy is in, with KsmAnon the blocks of the anonymous pages too,
+// and qvisor marks the hinted blocks MADV_MERGEABLE for the ksmd of the host.
+// A block is hinted once, the kernel merges the adjacent mergeable ranges
+// into one vma. The hinted pages are allocated from the blocks of KsmPagePool
+// which hold no kernel heap object, the kernel heap is never merged. Ksm and
+// KsmAnon are set by the operator only.
+
+pub const KSM_BLOCK_SIZE: u64 = MemoryDef::PAGE_SIZE_2M;
+pub const KSM_BLOCK_COUNT: usize = (MemoryDef::HEAP_SIZE / KSM_BLOCK_SIZE) as usize;
+pub const KSM_WORD_COUNT: usize = KSM_BLOCK_COUNT / 64;
+
+pub struct KsmHints {
+    // a bit per 2MB block of the heap, set by the guest, cleared by the host
+    // when ksm is turned off
+    pub blocks: [AtomicU64; KSM_WORD_COUNT],
+}
+
+impl Default for KsmHints {
+    fn default() -> Self {
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        return Self {
+            blocks: [ZERO; KSM_WORD_COUNT],
+        };
+    }
+}
+
+impl KsmHints {
+    pub fn Hint(&self, addr: u64) {
+        if addr < MemoryDef::HEAP_OFFSET || addr >= MemoryDef::HEAP_OFFSET + MemoryDef::HEAP_SIZE {
+            return;
+        }
+
+        let block = ((addr - MemoryDef::HEAP_OFFSET) / KSM_BLOCK_SIZE) as usize;
+        let mask = 1 << (block % 64);
+        let word = &self.blocks[block / 64];
+        // most faults are in a hinted block, the cache line stays shared
+        if word.load(Ordering::Relaxed) & mask == 0 {
+            word.fetch_or(mask, Ordering::Relaxed);
+        }
+    }
+
+    pub fn Word(&self, idx: usize) -> u64 {
+        return self.blocks[idx].load(Ordering::Relaxed);
+    }
+
+    pub fn TakeWord(&self, idx: usize) -> u64 {
+        return self.blocks[idx].swap(0, Ordering::Relaxed);
+    }
+
+    pub fn BlockAddr(idx: usize, bit: usize) -> u64 {
+        return MemoryDef::HEAP_OFFSET + (idx * 64 + bit) as u64 * KSM_BLOCK_SIZE;
+    }
+}
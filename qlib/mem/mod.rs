@@ -16,6 +16,7 @@ pub mod areaset;
 pub mod block;
 pub mod buddy_allocator;
 pub mod io;
+pub mod ksm;
 pub mod list_allocator;
 pub mod pool;
 pub mod seq;
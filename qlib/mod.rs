@@ -18,7 +18,9 @@ extern crate spin;
 //#[macro_use]
 //pub mod macros;
 pub mod addr;
+pub mod audit;
 pub mod auxv;
+pub mod bpf;
 pub mod buddyallocator;
 pub mod common;
 pub mod linux_def;
@@ -42,11 +44,14 @@ pub mod lrc_cache;
 pub mod mem;
 pub mod metric;
 pub mod mutex;
+pub mod negotiation;
 pub mod object_ref;
 pub mod path;
 pub mod perf_tunning;
 pub mod platform;
 pub mod qmsg;
+pub mod rcu;
+pub mod seqlock;
 pub mod singleton;
 pub mod socket_buf;
 pub mod sort_arr;
@@ -58,6 +63,7 @@ pub mod backtracer;
 pub mod kernel;
 pub mod rdma_share;
 pub mod ringbuf;
+pub mod rps;
 pub mod vcpu_mgr;
 
 pub mod rdma_svc_cli;
@@ -79,6 +85,7 @@ use self::fileinfo::*;
 use self::kernel::kernel::futex::*;
 use self::kernel::kernel::kernel::Kernel;
 use self::kernel::kernel::timer::timekeeper::*;
+use self::kernel::kernel::timer::timer_dispatch::*;
 use self::kernel::kernel::timer::timer_store::*;
 use self::kernel::memmgr::pma::*;
 use self::kernel::quring::uring_mgr::QUring;
@@ -87,7 +94,9 @@ use self::object_ref::ObjectRef;
 use self::qmsg::*;
 use self::rdma_svc_cli::*;
 use self::ringbuf::*;
+use self::rps::*;
 use self::task_mgr::*;
+use self::usage::sandbox::*;
 use super::asm::*;
 
 pub fn InitSingleton() {
@@ -124,6 +133,13 @@ pub const HYPERCALL_VCPU_DEBUG: u16 = 21;
 pub const HYPERCALL_VCPU_PRINT: u16 = 22;
 pub const HYPERCALL_VCPU_WAIT: u16 = 23;
 pub const HYPERCALL_RELEASE_VCPU: u16 = 24;
+pub const HYPERCALL_CONSOLE: u16 = 25;
+pub const HYPERCALL_NEGOTIATE: u16 = 26;
+pub const HYPERCALL_RNG: u16 = 27;
+
+// the hypercalls reserved for the qcall plugins of qvisor
+pub const HYPERCALL_PLUGIN_BASE: u16 = 0x100;
+pub const HYPERCALL_PLUGIN_COUNT: u16 = 0x100;
 
 pub const DUMMY_TASKID: TaskId = TaskId::New(0xffff_ffff);
 
@@ -676,6 +692,8 @@ pub struct ShareSpace {
     pub futexMgr: CachePadded<FutexMgr>,
     pub pageMgr: CachePadded<PageMgr>,
     pub ioMgr: CachePadded<IOMgr>,
+    pub recvSteering: CachePadded<RecvSteering>,
+    pub timerDispatch: CachePadded<TimerDispatch>,
     pub config: CachePadded<QRwLock<Config>>,
     pub rdmaSvcCli: CachePadded<RDMASvcClient>,
 
@@ -690,6 +708,12 @@ pub struct ShareSpace {
     pub uid: CachePadded<AtomicU64>,
     pub inotifyCookie: CachePadded<AtomicU32>,
     pub waitMask: CachePadded<AtomicU64>,
+    pub stats: CachePadded<SandboxStats>,
+    pub audit: CachePadded<audit::AuditQueue>,
+    pub ksm: CachePadded<mem::ksm::KsmHints>,
+    // bumped by qvisor when the guest memory is restored, e.g. from a
+    // hibernation image, the guest entropy pool reseeds on it
+    pub entropyEpoch: CachePadded<AtomicU64>,
 
     pub supportMemoryBarrier: bool,
     pub controlSock: i32,
@@ -0,0 +1,172 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::config::Config;
+
+// Boot time handshake between qkernel and qvisor. qkernel sends FeatureNegotiation
+// through HYPERCALL_NEGOTIATE before it touches any shared structure, qvisor checks
+// the versions and fills the features both sides support.
+
+pub const NEGOTIATION_MAGIC: u64 = 0x5155_4152_4b4e_4547; // "QUARKNEG"
+
+// bump it when the layout of a structure shared between qkernel and qvisor changes,
+// e.g. ShareSpace, QUring, Config.
+//
+// 2: the receive steering, the net devices, the stats, the audit ring, the ksm
+//    config, the steal time of CPULocal, the timer dispatch and the timer hold
+pub const SHARESPACE_VERSION: u32 = 2;
+
+// bump it when the qcall/hcall message set changes, e.g. Msg or HostOutputMsg.
+//
+// 2: the NetDevs and PauseAbort qcalls, the console, negotiate and rng
+//    hypercalls and the hypercall range of the plugins
+pub const QCALL_VERSION: u32 = 2;
+
+// Optional subsystems. A feature is only enabled when both sides support it.
+pub struct Feature {}
+
+impl Feature {
+    pub const RDMA: u64 = 1 << 0;
+    pub const URING_IO: u64 = 1 << 1;
+    pub const INOTIFY: u64 = 1 << 2;
+    pub const BOOT_CONSOLE: u64 = 1 << 3;
+    pub const HOST_RNG: u64 = 1 << 4;
+
+    pub const ALL: u64 =
+        Self::RDMA | Self::URING_IO | Self::INOTIFY | Self::BOOT_CONSOLE | Self::HOST_RNG;
+
+    // Enabled returns the features the config turns on, the hypercalls of
+    // qvisor are always there.
+    pub fn Enabled(config: &Config) -> u64 {
+        let mut features = Self::BOOT_CONSOLE | Self::HOST_RNG;
+        if config.EnableRDMA {
+            features |= Self::RDMA;
+        }
+
+        if config.UringIO {
+            features |= Self::URING_IO;
+        }
+
+        if config.EnableInotify {
+            features |= Self::INOTIFY;
+        }
+
+        return features;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum NegotiationStatus {
+    Pending = 0,
+    Ok = 1,
+    BadMagic = 2,
+    ShareSpaceMismatch = 3,
+    QCallMismatch = 4,
+}
+
+impl Default for NegotiationStatus {
+    fn default() -> Self {
+        return Self::Pending;
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct FeatureNegotiation {
+    pub magic: u64,
+    pub shareSpaceVersion: u32,
+    pub qcallVersion: u32,
+    pub shareSpaceSize: u64,
+    pub guestFeatures: u64,
+
+    // filled by qvisor
+    pub hostFeatures: u64,
+    pub agreedFeatures: u64,
+    pub status: NegotiationStatus,
+}
+
+impl FeatureNegotiation {
+    pub fn New(shareSpaceSize: u64, guestFeatures: u64) -> Self {
+        return Self {
+            magic: NEGOTIATION_MAGIC,
+            shareSpaceVersion: SHARESPACE_VERSION,
+            qcallVersion: QCALL_VERSION,
+            shareSpaceSize: shareSpaceSize,
+            guestFeatures: guestFeatures,
+            ..Default::default()
+        };
+    }
+
+    // Negotiate is called by qvisor with its own view of the shared structures.
+    pub fn Negotiate(&mut self, shareSpaceSize: u64, hostFeatures: u64) -> NegotiationStatus {
+        self.hostFeatures = hostFeatures;
+        self.agreedFeatures = 0;
+
+        self.status = if self.magic != NEGOTIATION_MAGIC {
+            NegotiationStatus::BadMagic
+        } else if self.shareSpaceVersion != SHARESPACE_VERSION || self.shareSpaceSize != shareSpaceSize {
+            NegotiationStatus::ShareSpaceMismatch
+        } else if self.qcallVersion != QCALL_VERSION {
+            NegotiationStatus::QCallMismatch
+        } else {
+            self.agreedFeatures = self.guestFeatures & hostFeatures;
+            NegotiationStatus::Ok
+        };
+
+        return self.status;
+    }
+}
+
+// The features agreed at boot time.
+pub static NEGOTIATED_FEATURES: AtomicU64 = AtomicU64::new(0);
+
+pub fn SetNegotiatedFeatures(features: u64) {
+    NEGOTIATED_FEATURES.store(features, Ordering::SeqCst);
+}
+
+pub fn FeatureEnabled(feature: u64) -> bool {
+    return NEGOTIATED_FEATURES.load(Ordering::Relaxed) & feature == feature;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn TestNegotiate() {
+        let mut n = FeatureNegotiation::New(4096, Feature::RDMA | Feature::INOTIFY);
+        assert_eq!(n.Negotiate(4096, Feature::INOTIFY | Feature::URING_IO), NegotiationStatus::Ok);
+        assert_eq!(n.agreedFeatures, Feature::INOTIFY);
+
+        let mut n = FeatureNegotiation::New(4096, Feature::ALL);
+        assert_eq!(n.Negotiate(8192, Feature::ALL), NegotiationStatus::ShareSpaceMismatch);
+        assert_eq!(n.agreedFeatures, 0);
+
+        let mut n = FeatureNegotiation::New(4096, Feature::ALL);
+        n.qcallVersion += 1;
+        assert_eq!(n.Negotiate(4096, Feature::ALL), NegotiationStatus::QCallMismatch);
+
+        let mut config = Config::default();
+        config.UringIO = false;
+        config.EnableInotify = true;
+        let mut n = FeatureNegotiation::New(4096, Feature::ALL);
+        assert_eq!(n.Negotiate(4096, Feature::Enabled(&config)), NegotiationStatus::Ok);
+        assert_eq!(n.agreedFeatures & (Feature::RDMA | Feature::URING_IO), 0);
+        assert!(n.agreedFeatures & Feature::INOTIFY != 0);
+    }
+}
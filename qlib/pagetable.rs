@@ -31,6 +31,24 @@ use super::common::{Allocator, Error, Result};
 use super::linux_def::*;
 use super::mem::stackvec::*;
 
+// A swap entry is a pte of a page moved out of the guest memory, e.g. to the
+// compressed tier. It is not present and has SWAP_ENTRY_FLAG, its address is
+// the handle of the page in the tier and its flags are the ones of the page
+// before. The handles are above the guest physical memory, the page pool
+// refcounts them as the pages so a forked, remapped or unmapped swap entry
+// keeps the handle referenced as it does a page.
+pub const SWAP_ENTRY_FLAG: PageTableFlags = PageTableFlags::BIT_10;
+pub const SWAP_HANDLE_BASE: u64 = 1 << 48;
+
+pub fn IsSwapHandle(addr: u64) -> bool {
+    return addr >= SWAP_HANDLE_BASE;
+}
+
+pub fn IsSwapEntry(entry: &PageTableEntry) -> bool {
+    let flags = entry.flags();
+    return flags.contains(SWAP_ENTRY_FLAG) && !flags.contains(PageTableFlags::PRESENT);
+}
+
 #[derive(Default)]
 pub struct PageTables {
     //Root page guest physical address
@@ -154,12 +172,13 @@ impl PageTables {
             match self.VirtualToEntry(vAddr) {
                 Ok(entry) => {
                     let phyAddr = entry.addr().as_u64();
-                    to.MapPage(
-                        Addr(vAddr),
-                        Addr(phyAddr),
-                        PageOpts::UserReadOnly().Val(),
-                        pagePool,
-                    )?;
+                    // a swapped out page is shared by the handle
+                    let flags = if IsSwapEntry(entry) {
+                        entry.flags()
+                    } else {
+                        PageOpts::UserReadOnly().Val()
+                    };
+                    to.MapPage(Addr(vAddr), Addr(phyAddr), flags, pagePool)?;
                 }
                 Err(_) => (),
             }
@@ -250,7 +269,7 @@ impl PageTables {
 
     pub fn VirtualToPhy(&self, vaddr: u64) -> Result<(u64, AccessType)> {
         let pteEntry = self.VirtualToEntry(vaddr)?;
-        if pteEntry.is_unused() {
+        if pteEntry.is_unused() || IsSwapEntry(pteEntry) {
             return Err(Error::AddressNotMap(vaddr));
         }
 
@@ -262,6 +281,14 @@ impl PageTables {
         return Ok((phyAddr, permission));
     }
 
+    // SwapHandle returns the handle of the page at vaddr if it is swapped out.
+    pub fn SwapHandle(&self, vaddr: u64) -> Option<u64> {
+        match self.VirtualToEntry(vaddr) {
+            Ok(entry) if IsSwapEntry(entry) => return Some(entry.addr().as_u64()),
+            _ => return None,
+        }
+    }
+
     pub fn PrintPageFlags(&self, vaddr: u64) -> Result<()> {
         let pteEntry = self.VirtualToEntry(vaddr)?;
         if pteEntry.is_unused() {
@@ -396,7 +423,12 @@ impl PageTables {
             match entry {
                 Ok(oldentry) => {
                     let phyAddr = oldentry.addr().as_u64();
-                    addrs.push(Some(phyAddr));
+                    let swapFlags = if IsSwapEntry(oldentry) {
+                        Some(oldentry.flags())
+                    } else {
+                        None
+                    };
+                    addrs.push(Some((phyAddr, swapFlags)));
                     pagePool.Ref(phyAddr).unwrap();
                     self.Unmap(
                         oldStart.0 + offset,
@@ -415,8 +447,13 @@ impl PageTables {
         let mut idx = 0;
         while start.0 + offset < end.0 {
             match addrs[idx] {
-                Some(phyAddr) => {
-                    self.MapPage(Addr(start.0 + offset), Addr(phyAddr), flags, pagePool)?;
+                Some((phyAddr, swapFlags)) => {
+                    self.MapPage(
+                        Addr(start.0 + offset),
+                        Addr(phyAddr),
+                        swapFlags.unwrap_or(flags),
+                        pagePool,
+                    )?;
                     pagePool.Deref(phyAddr).unwrap();
                 }
                 None => (),
@@ -452,7 +489,12 @@ impl PageTables {
             match entry {
                 Ok(oldentry) => {
                     let phyAddr = oldentry.addr().as_u64();
-                    addrs.push(Some(phyAddr));
+                    let swapFlags = if IsSwapEntry(oldentry) {
+                        Some(oldentry.flags())
+                    } else {
+                        None
+                    };
+                    addrs.push(Some((phyAddr, swapFlags)));
                     pagePool.Ref(phyAddr).unwrap();
                     self.Unmap(oldStart.0 + offset, oldStart.0 + offset + MemoryDef::PAGE_SIZE, pagePool)?;
                 }
@@ -471,6 +513,11 @@ impl PageTables {
                 match entry {
                     Ok(oldentry) => {
                         let phyAddr = oldentry.addr().as_u64();
+                        let flags = if IsSwapEntry(oldentry) {
+                            oldentry.flags()
+                        } else {
+                            flags
+                        };
                         self.MapPage(Addr(start.0 + offset), Addr(phyAddr), flags, pagePool)?;
                         self.Unmap(
                             oldStart.0 + offset,
@@ -919,6 +966,11 @@ impl PageTables {
             start,
             end,
             |entry, virtualAddr| {
+                // the page swapped in gets the protection of its vma
+                if IsSwapEntry(entry) {
+                    return;
+                }
+
                 entry.set_flags(flags);
                 Invlpg(virtualAddr);
             },
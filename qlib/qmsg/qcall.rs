@@ -117,6 +117,8 @@ pub enum Msg {
     FListXattr(FListXattr),
     HostMemoryBarrier(HostMemoryBarrier),
     Mkfifoat(Mkfifoat),
+    NetDevs(NetDevs),
+    PauseAbort(PauseAbort),
 }
 
 #[derive(Clone, Default, Debug)]
@@ -162,6 +164,15 @@ pub struct Sysinfo {
     pub addr: u64,
 }
 
+#[derive(Clone, Default, Debug)]
+pub struct PauseAbort {}
+
+#[derive(Clone, Default, Debug)]
+pub struct NetDevs {
+    pub addr: u64,
+    pub count: usize,
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct Rdtsc {}
 
@@ -0,0 +1,200 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::hint::spin_loop;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::mutex::*;
+
+// QRcu publishes a read-mostly value, e.g. a table looked up by every
+// syscall of a kind and changed by a few. A reader takes an Arc of the
+// current value without a lock and keeps using it after the value is
+// replaced. A writer publishes a new value, copied from the current one for
+// Update, and drops its reference to the old value once the readers which
+// may have loaded the old pointer are done taking theirs. The readers are
+// counted in two slots flipped by each write, so a writer only waits for the
+// readers started before it, and only for the few instructions they take.
+pub struct QRcu<T> {
+    ptr: AtomicPtr<T>,
+    epoch: AtomicU64,
+    readers: [AtomicU64; 2],
+    writer: QMutex<()>,
+}
+
+unsafe impl<T: Send + Sync> Sync for QRcu<T> {}
+unsafe impl<T: Send + Sync> Send for QRcu<T> {}
+
+impl<T> QRcu<T> {
+    pub fn New(data: T) -> Self {
+        return Self {
+            ptr: AtomicPtr::new(Arc::into_raw(Arc::new(data)) as *mut T),
+            epoch: AtomicU64::new(0),
+            readers: [AtomicU64::new(0), AtomicU64::new(0)],
+            writer: QMutex::new(()),
+        };
+    }
+
+    pub fn Read(&self) -> Arc<T> {
+        // the reader is counted in the slot of the epoch it saw, and only
+        // goes on if no writer flipped the epoch in between, so the writer
+        // which replaces the pointer the reader loads waits for it
+        let slot = loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            let slot = (epoch & 1) as usize;
+            self.readers[slot].fetch_add(1, Ordering::SeqCst);
+            if self.epoch.load(Ordering::SeqCst) == epoch {
+                break slot;
+            }
+
+            self.readers[slot].fetch_sub(1, Ordering::SeqCst);
+            spin_loop();
+        };
+
+        let ptr = self.ptr.load(Ordering::SeqCst);
+        let data = unsafe {
+            Arc::increment_strong_count(ptr);
+            Arc::from_raw(ptr)
+        };
+        self.readers[slot].fetch_sub(1, Ordering::SeqCst);
+        return data;
+    }
+
+    pub fn Write(&self, data: T) {
+        let _writer = self.writer.lock();
+        self.Publish(Arc::new(data));
+    }
+
+    // Update publishes a copy of the current value changed by f and returns
+    // the result of f. The updates are serialized.
+    pub fn Update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R
+    where
+        T: Clone,
+    {
+        let _writer = self.writer.lock();
+        // the writer lock keeps the current value
+        let mut data = unsafe { (*self.ptr.load(Ordering::SeqCst)).clone() };
+        let ret = f(&mut data);
+        self.Publish(Arc::new(data));
+        return ret;
+    }
+
+    // Publish replaces the value, with the writer lock held. A reader which
+    // may have loaded the old pointer saw the epoch before the flip and is
+    // counted in the old slot, the one which saw the flipped epoch loads the
+    // new pointer.
+    fn Publish(&self, data: Arc<T>) {
+        let old = self
+            .ptr
+            .swap(Arc::into_raw(data) as *mut T, Ordering::SeqCst);
+        let slot = (self.epoch.fetch_add(1, Ordering::SeqCst) & 1) as usize;
+        while self.readers[slot].load(Ordering::SeqCst) != 0 {
+            spin_loop();
+        }
+
+        unsafe {
+            drop(Arc::from_raw(old));
+        }
+    }
+}
+
+impl<T> Drop for QRcu<T> {
+    fn drop(&mut self) {
+        unsafe {
+            drop(Arc::from_raw(*self.ptr.get_mut()));
+        }
+    }
+}
+
+impl<T: Default> Default for QRcu<T> {
+    fn default() -> Self {
+        return Self::New(T::default());
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for QRcu<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "QRcu {{ data: {:?} }}", self.Read())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn TestRcuReaderKeepsOldValue() {
+        let rcu = QRcu::New(1);
+        let old = rcu.Read();
+        rcu.Write(2);
+
+        assert_eq!(*old, 1);
+        assert_eq!(*rcu.Read(), 2);
+        assert_eq!(Arc::strong_count(&old), 1);
+    }
+
+    #[test]
+    fn TestRcuUpdate() {
+        let rcu = QRcu::New(alloc::vec![1, 2]);
+        let len = rcu.Update(|v| {
+            v.push(3);
+            v.len()
+        });
+
+        assert_eq!(len, 3);
+        assert_eq!(*rcu.Read(), alloc::vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn TestRcuConcurrentWriters() {
+        use std::thread;
+
+        const WRITERS: u64 = 4;
+        const READERS: usize = 4;
+        const WRITES: u64 = 2000;
+
+        let rcu = Arc::new(QRcu::New(alloc::vec![0u64; 16]));
+        let mut threads = alloc::vec::Vec::new();
+        for w in 0..WRITERS {
+            let rcu = rcu.clone();
+            threads.push(thread::spawn(move || {
+                for i in 0..WRITES {
+                    rcu.Write(alloc::vec![w * WRITES + i; 16]);
+                }
+            }));
+        }
+
+        for _ in 0..READERS {
+            let rcu = rcu.clone();
+            threads.push(thread::spawn(move || {
+                for _ in 0..WRITES * WRITERS {
+                    // a freed value would show up as torn or garbage content
+                    let v = rcu.Read();
+                    assert!(v.iter().all(|x| *x == v[0]));
+                    assert!(v[0] < WRITERS * WRITES);
+                }
+            }));
+        }
+
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        let v = rcu.Read();
+        assert_eq!(Arc::strong_count(&v), 2);
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+use cache_padded::CachePadded;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::kernel::GlobalIOMgr;
+use super::linux_def::*;
+use super::ringbuf::*;
+use super::task_mgr::*;
+use super::vcpu_mgr::*;
+
+// The receive steering of the host socket events. The events of the host
+// epoll are processed by the thread which polls it, mostly the io thread, and
+// their processing, the wakeup of the waiters and the epoll callbacks of the
+// guest, saturates it with many connections. With the steering, the event of
+// a socket is queued to the vcpu of its flow, the hash of its host fd, and the
+// vcpu processes it when it is idle, the io thread wakes it up for that. The
+// events of a vcpu running a task are still processed by the poller, the vcpu
+// wouldn't get to them before it is idle. Vcpu 0 is the one of the global
+// queue and isn't steered to.
+pub const RPS_QUEUE_SIZE: usize = 1024;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SteerEvent {
+    pub fd: i32,
+    pub mask: EventMask,
+}
+
+#[derive(Default)]
+pub struct RecvSteering {
+    // the steering queue of each vcpu, empty when the steering is off
+    pub queues: Vec<CachePadded<QRingQueue<SteerEvent>>>,
+    pub steered: AtomicU64,
+    pub inline: AtomicU64,
+}
+
+impl RecvSteering {
+    pub fn New(vcpuCount: usize) -> Self {
+        let mut queues = Vec::with_capacity(vcpuCount);
+        for _i in 0..vcpuCount {
+            queues.push(CachePadded::new(QRingQueue::New(RPS_QUEUE_SIZE)));
+        }
+
+        return Self {
+            queues: queues,
+            ..Default::default()
+        };
+    }
+
+    pub fn Enabled(&self) -> bool {
+        return self.queues.len() > 2;
+    }
+
+    // FlowVcpu returns the vcpu of the flow of the host fd.
+    pub fn FlowVcpu(&self, fd: i32) -> usize {
+        let hash = (fd as u32).wrapping_mul(0x9e3779b1);
+        return 1 + (hash >> 16) as usize % (self.queues.len() - 1);
+    }
+
+    // Steer queues the event to the vcpu of its flow if the vcpu is idle, it
+    // returns false if the caller has to process the event.
+    pub fn Steer(&self, scheduler: &Scheduler, fd: i32, mask: EventMask) -> bool {
+        if !self.Enabled() {
+            return false;
+        }
+
+        let vcpuId = self.FlowVcpu(fd);
+        if scheduler.VcpuArr[vcpuId].State() == VcpuState::Running {
+            self.inline.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        let event = SteerEvent { fd: fd, mask: mask };
+        if self.queues[vcpuId].Push(&event).is_err() {
+            self.inline.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        self.steered.fetch_add(1, Ordering::Relaxed);
+        scheduler.WakeIdleCPU(vcpuId);
+        return true;
+    }
+
+    // Process processes the events steered to the vcpu.
+    pub fn Process(&self, vcpuId: usize) -> usize {
+        if !self.Enabled() {
+            return 0;
+        }
+
+        let mut count = 0;
+        while let Some(event) = self.queues[vcpuId].TryPop() {
+            GlobalIOMgr().Notify(event.fd, event.mask);
+            count += 1;
+        }
+
+        return count;
+    }
+
+    // ProcessBusy processes the events left to the vcpus which have got a
+    // task since they were steered.
+    pub fn ProcessBusy(&self, scheduler: &Scheduler) -> usize {
+        if !self.Enabled() {
+            return 0;
+        }
+
+        let mut count = 0;
+        for vcpuId in 1..self.queues.len() {
+            if scheduler.VcpuArr[vcpuId].State() == VcpuState::Running {
+                count += self.Process(vcpuId);
+            }
+        }
+
+        return count;
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::hint::spin_loop;
+use core::sync::atomic::fence;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::mutex::*;
+
+// QSeqLock protects a small Copy value read on the hot paths and written
+// rarely, e.g. the parameters of a clock. A reader copies the value without
+// writing to the shared memory and retries when a writer ran meanwhile, the
+// sequence is odd while a writer runs. The writers are serialized by a spin
+// lock and are not to block, a reader spins until the writer is done.
+pub struct QSeqLock<T: Copy> {
+    seq: AtomicU64,
+    writer: QMutex<()>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Copy + Send> Sync for QSeqLock<T> {}
+unsafe impl<T: Copy + Send> Send for QSeqLock<T> {}
+
+impl<T: Copy> QSeqLock<T> {
+    pub const fn New(data: T) -> Self {
+        return Self {
+            seq: AtomicU64::new(0),
+            writer: QMutex::new(()),
+            data: UnsafeCell::new(data),
+        };
+    }
+
+    pub fn Read(&self) -> T {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq & 1 != 0 {
+                spin_loop();
+                continue;
+            }
+
+            // the copy may be torn by a writer, it is dropped then
+            let data = unsafe { core::ptr::read_volatile(self.data.get()) };
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return data;
+            }
+        }
+    }
+
+    pub fn Write(&self, data: T) {
+        self.Update(|d| *d = data);
+    }
+
+    // Update changes the value in place and returns the result of f.
+    pub fn Update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let _writer = self.writer.lock();
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.seq.store(seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        let ret = f(unsafe { &mut *self.data.get() });
+
+        self.seq.store(seq + 2, Ordering::Release);
+        return ret;
+    }
+}
+
+impl<T: Copy + Default> Default for QSeqLock<T> {
+    fn default() -> Self {
+        return Self::New(T::default());
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for QSeqLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "QSeqLock {{ data: {:?} }}", self.Read())
+    }
+}
@@ -128,6 +128,10 @@ impl SocketBuff {
         return self.readBuf.lock().AvailableDataSize() > 0;
     }
 
+    pub fn ReadBufAvailableDataSize(&self) -> usize {
+        return self.readBuf.lock().AvailableDataSize();
+    }
+
     pub fn WriteBufAvailableDataSize(&self) -> usize {
         return self.writeBuf.lock().AvailableDataSize();
     }
@@ -223,7 +227,12 @@ pub struct AcceptItem {
     pub fd: i32,
     pub addr: TcpSockAddr,
     pub len: u32,
-    pub sockBuf: Arc<SocketBuff>,
+    // the buffer of the connection is allocated when it is accepted by the
+    // application if it is none, the connections pending in the queue of a
+    // flooded listener don't hold buffers
+    pub sockBuf: Option<Arc<SocketBuff>>,
+    // the plaintext of a connection of the TLS termination of qvisor
+    pub tlsOffload: bool,
 }
 
 #[derive(Default, Clone, Debug)]
@@ -268,15 +277,21 @@ impl AcceptQueueIntern {
         fd: i32,
         addr: TcpSockAddr,
         len: u32,
-        sockBuf: Arc<SocketBuff>,
+        sockBuf: Option<Arc<SocketBuff>>,
     ) -> (bool, bool) {
         let item = AcceptItem {
             fd: fd,
             addr: addr,
             len: len,
             sockBuf: sockBuf,
+            tlsOffload: false,
         };
 
+        return self.EnqItem(item);
+    }
+
+    //return: (trigger, hasSpace)
+    pub fn EnqItem(&mut self, item: AcceptItem) -> (bool, bool) {
         self.queue.push_back(item);
         self.total += 1;
         let trigger = self.queue.len() == 1;
@@ -209,7 +209,7 @@ impl Scheduler {
         loop {
             let mask = self.vcpuWaitMask.load(Ordering::Acquire);
 
-            let vcpuId = mask.trailing_zeros() as usize;
+            let vcpuId = self.IdleVcpu(mask);
             if vcpuId >= 64 {
                 return -1;
             }
@@ -220,6 +220,55 @@ impl Scheduler {
         }
     }
 
+    // WakeLessStolen wakes the idle vcpu with the lowest steal rate if the
+    // rate is lower than rate, it returns whether one is woken.
+    pub fn WakeLessStolen(&self, rate: u64) -> bool {
+        loop {
+            let mask = self.vcpuWaitMask.load(Ordering::Acquire);
+
+            let vcpuId = self.IdleVcpu(mask);
+            if vcpuId >= 64
+                || self.VcpuArr[vcpuId]
+                    .stealSample
+                    .rate
+                    .load(Ordering::Relaxed)
+                    >= rate
+            {
+                return false;
+            }
+
+            if self.WakeIdleCPU(vcpuId) {
+                return true;
+            }
+        }
+    }
+
+    // IdleVcpu returns the vcpu of mask with the lowest steal rate, i.e. the
+    // one the host runs the most, 64 if mask is empty.
+    pub fn IdleVcpu(&self, mask: u64) -> usize {
+        let mut idle = 64;
+        let mut idleRate = u64::MAX;
+        let mut mask = mask;
+        while mask != 0 {
+            let vcpuId = mask.trailing_zeros() as usize;
+            mask &= mask - 1;
+
+            let rate = self.VcpuArr[vcpuId]
+                .stealSample
+                .rate
+                .load(Ordering::Relaxed);
+            if rate < idleRate {
+                idle = vcpuId;
+                idleRate = rate;
+                if rate == 0 {
+                    break;
+                }
+            }
+        }
+
+        return idle;
+    }
+
     pub fn WakeAll(&self) {
         for i in 1..self.vcpuCnt {
             self.WakeIdleCPU(i);
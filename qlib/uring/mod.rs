@@ -252,6 +252,12 @@ impl Parameters {
         self.0.features & sys::IORING_FEAT_POLL_32BITS != 0
     }
 
+    /// If this flag is set, `io_uring_enter(2)` takes a `io_uring_getevents_arg` with a timeout
+    /// for the completion wait.
+    pub fn is_feature_ext_arg(&self) -> bool {
+        self.0.features & sys::IORING_FEAT_EXT_ARG != 0
+    }
+
     pub fn sq_entries(&self) -> u32 {
         self.0.sq_entries
     }
@@ -53,6 +53,7 @@ pub const IORING_CQ_EVENTFD_DISABLED: u32 = 1;
 pub const IORING_ENTER_GETEVENTS: u32 = 1;
 pub const IORING_ENTER_SQ_WAKEUP: u32 = 2;
 pub const IORING_ENTER_SQ_WAIT: u32 = 4;
+pub const IORING_ENTER_EXT_ARG: u32 = 8;
 pub const IORING_FEAT_SINGLE_MMAP: u32 = 1;
 pub const IORING_FEAT_NODROP: u32 = 2;
 pub const IORING_FEAT_SUBMIT_STABLE: u32 = 4;
@@ -60,6 +61,7 @@ pub const IORING_FEAT_RW_CUR_POS: u32 = 8;
 pub const IORING_FEAT_CUR_PERSONALITY: u32 = 16;
 pub const IORING_FEAT_FAST_POLL: u32 = 32;
 pub const IORING_FEAT_POLL_32BITS: u32 = 64;
+pub const IORING_FEAT_EXT_ARG: u32 = 256;
 pub const IO_URING_OP_SUPPORTED: u32 = 1;
 pub type __u8 = u8;
 pub type __u16 = u16;
@@ -292,6 +294,15 @@ pub const IORING_REGISTER_RESTRICTIONS: u32 = 11;
 pub const IORING_REGISTER_ENABLE_RINGS: u32 = 12;
 pub const IORING_REGISTER_LAST: u32 = 13;
 pub type _bindgen_ty_7 = u32;
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct io_uring_getevents_arg {
+    pub sigmask: __u64,
+    pub sigmask_sz: __u32,
+    pub pad: __u32,
+    pub ts: __u64,
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct io_uring_files_update {
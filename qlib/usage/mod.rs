@@ -15,3 +15,4 @@
 pub mod cpu;
 pub mod io;
 pub mod memory;
+pub mod sandbox;
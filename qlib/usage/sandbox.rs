@@ -0,0 +1,187 @@
+// Copyright (c) 2021 Quark Container Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::fence;
+use core::sync::atomic::AtomicI64;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+// The resource usage of the sandbox for the vertical autoscaling and the
+// userspace OOM agents, which poll hundreds of sandboxes every second. qvisor
+// publishes it in a page of a host file the agents map, a read is a copy out
+// of their own mapping without a syscall to the sandbox:
+//
+//   offset 0: u32 magic USAGE_PAGE_MAGIC
+//   offset 4: u32 version USAGE_PAGE_VERSION
+//   offset 8: u64 sequence, odd while the usage is being updated
+//   offset 16: SandboxUsage
+//
+// A reader reads the sequence, copies the usage and reads the sequence again,
+// it retries when the sequence is odd or has changed.
+pub const USAGE_PAGE_MAGIC: u32 = 0x51555347;
+pub const USAGE_PAGE_VERSION: u32 = 1;
+pub const USAGE_PAGE_SIZE: usize = 4096;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct SandboxUsage {
+    // the host CLOCK_MONOTONIC of the update in ns
+    pub Timestamp: u64,
+    // the guest memory in use and the memory the sandbox is sized for, bytes
+    pub WorkingSet: u64,
+    pub MemoryLimit: u64,
+    // the host cpu time of the sandbox in ns
+    pub CpuTime: u64,
+    // the ns the sandbox had work and no cpu: the guest tasks ready without a
+    // vcpu and the vcpus waiting for a host cpu
+    pub RunnableTime: u64,
+    // the ns the guest tasks waited for the host IO
+    pub IoWaitTime: u64,
+    // the tasks ready or running and the tasks waiting for the host IO now
+    pub RunnableTasks: u64,
+    pub IoWaitTasks: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct UsagePage {
+    pub Magic: u32,
+    pub Version: u32,
+    pub Seq: AtomicU64,
+    pub Usage: SandboxUsage,
+}
+
+impl UsagePage {
+    pub fn Init(&mut self) {
+        self.Magic = USAGE_PAGE_MAGIC;
+        self.Version = USAGE_PAGE_VERSION;
+        self.Seq.store(0, Ordering::Release);
+    }
+
+    // Publish updates the usage, there is a single writer. The page is the
+    // seqlock of QSeqLock with the layout fixed for the agents, the orderings
+    // are the ones of QSeqLock.
+    pub fn Publish(&mut self, usage: &SandboxUsage) {
+        let seq = self.Seq.load(Ordering::Relaxed);
+        self.Seq.store(seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        unsafe {
+            core::ptr::write_volatile(&mut self.Usage as *mut SandboxUsage, *usage);
+        }
+
+        self.Seq.store(seq + 2, Ordering::Release);
+    }
+
+    // Read returns a consistent copy of the usage.
+    pub fn Read(&self) -> SandboxUsage {
+        loop {
+            let seq = self.Seq.load(Ordering::Acquire);
+            if seq & 1 != 0 {
+                core::hint::spin_loop();
+                continue;
+            }
+
+            // the copy may be torn by the writer, it is dropped then
+            let usage = unsafe { core::ptr::read_volatile(&self.Usage as *const SandboxUsage) };
+            fence(Ordering::Acquire);
+            if self.Seq.load(Ordering::Relaxed) == seq {
+                return usage;
+            }
+        }
+    }
+}
+
+// SandboxStats is the usage the guest accounts for in the share space.
+#[derive(Debug, Default)]
+pub struct SandboxStats {
+    // the tasks waiting for the host IO, e.g. a qcall or an io_uring request
+    pub ioWaitTasks: AtomicI64,
+    // the ns the tasks have waited for the host IO
+    pub ioWaitTime: AtomicU64,
+    // the pages in the compressed tier of the guest and the bytes they take
+    pub zswapPages: AtomicU64,
+    pub zswapBytes: AtomicU64,
+}
+
+impl SandboxStats {
+    pub fn IoWaitStart(&self) {
+        self.ioWaitTasks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn IoWaitEnd(&self, waited: i64) {
+        self.ioWaitTasks.fetch_sub(1, Ordering::Relaxed);
+        if waited > 0 {
+            self.ioWaitTime.fetch_add(waited as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn IoWaitTasks(&self) -> u64 {
+        let tasks = self.ioWaitTasks.load(Ordering::Relaxed);
+        if tasks > 0 {
+            return tasks as u64;
+        }
+
+        return 0;
+    }
+
+    pub fn IoWaitTime(&self) -> u64 {
+        return self.ioWaitTime.load(Ordering::Relaxed);
+    }
+
+    pub fn ZswapStore(&self, bytes: u64) {
+        self.zswapPages.fetch_add(1, Ordering::Relaxed);
+        self.zswapBytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn ZswapFree(&self, bytes: u64) {
+        self.zswapPages.fetch_sub(1, Ordering::Relaxed);
+        self.zswapBytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    pub fn ZswapPages(&self) -> u64 {
+        return self.zswapPages.load(Ordering::Relaxed);
+    }
+
+    pub fn ZswapBytes(&self) -> u64 {
+        return self.zswapBytes.load(Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn TestUsagePage() {
+        // the layout is read by the agents
+        let page = UsagePage::default();
+        let base = &page as *const _ as usize;
+        assert_eq!(&page.Seq as *const _ as usize - base, 8);
+        assert_eq!(&page.Usage as *const _ as usize - base, 16);
+        assert!(core::mem::size_of::<UsagePage>() <= USAGE_PAGE_SIZE);
+
+        let mut page = UsagePage::default();
+        page.Init();
+        let usage = SandboxUsage {
+            WorkingSet: 1 << 20,
+            IoWaitTasks: 2,
+            ..Default::default()
+        };
+        page.Publish(&usage);
+        assert_eq!(page.Seq.load(Ordering::Relaxed), 2);
+        assert_eq!(page.Read().WorkingSet, 1 << 20);
+        assert_eq!(page.Read().IoWaitTasks, 2);
+    }
+}
@@ -17,6 +17,9 @@ use core::sync::atomic::Ordering;
 use core::sync::atomic::{AtomicI64, AtomicU8};
 use spin::Mutex;
 
+use super::kernel::arch::x86_64::async_pf::KvmApfData;
+use super::kernel::arch::x86_64::steal_time::{KvmStealTime, StealSample};
+use super::kernel::arch::x86_64::tsc_timer::PvClockTimeInfo;
 use super::mem::list_allocator::*;
 use super::ShareSpace;
 
@@ -59,6 +62,17 @@ pub struct CPULocal {
     pub enterAppTimestamp: AtomicI64,
     pub interruptMask: AtomicU64,
     pub mode: AtomicU8,
+    // the time in ns the vcpu spins for a task before it blocks
+    pub haltPollNs: AtomicU64,
+    // the TSC deadline armed in the x2APIC of the vcpu, 0 if none
+    pub tscDeadline: AtomicI64,
+    pub pvclock: PvClockTimeInfo,
+    pub apf: KvmApfData,
+    // the tasks waiting for the async page faults of the vcpu, qvisor doesn't
+    // block the idle vcpu with them so that it gets their page ready
+    pub asyncPfWaits: AtomicU64,
+    pub stealTime: KvmStealTime,
+    pub stealSample: StealSample,
 }
 
 impl CPULocal {
@@ -7,6 +7,7 @@ use std::fmt;
 use super::qlib::kernel::quring::uring_async::UringAsyncMgr;
 use super::qlib::common::*;
 use super::qlib::control_msg::*;
+use super::qlib::kernel::kernel::timer::timer_dispatch::*;
 use super::qlib::kernel::memmgr::pma::*;
 use super::qlib::kernel::task::*;
 use super::qlib::kernel::Kernel::*;
@@ -19,6 +20,7 @@ use super::qlib::mutex::*;
 use super::qlib::perf_tunning::*;
 use super::qlib::qmsg::*;
 use super::qlib::rdma_svc_cli::*;
+use super::qlib::rps::*;
 use super::qlib::task_mgr::*;
 use super::qlib::vcpu_mgr::*;
 use super::qlib::*;
@@ -119,6 +121,12 @@ impl ShareSpace {
         }
 
         self.scheduler = Scheduler::New(vcpuCount);
+        if self.config.read().RecvSteering {
+            self.recvSteering = CachePadded::new(RecvSteering::New(vcpuCount));
+        }
+        if self.config.read().TimerDispatch {
+            self.timerDispatch = CachePadded::new(TimerDispatch::New(vcpuCount));
+        }
         self.values = values;
 
         self.scheduler.Init();
@@ -158,6 +166,12 @@ impl ShareSpace {
                 continue;
             }
 
+            // the vcpu preempts the task itself with its TSC deadline timer
+            let vcpu = &self.scheduler.VcpuArr[i];
+            if vcpu.tscDeadline.load(Ordering::Relaxed) != 0 {
+                continue;
+            }
+
             //error!("CheckVcpuTimeout {}/{}/{}/{}", i, enterAppTimestamp, now, Tsc::Scale(now - enterAppTimestamp));
             if Tsc::Scale(now - enterAppTimestamp) * 1000 > 2 * CLOCK_TICK {
                 //self.scheduler.VcpuArr[i].ResetEnterAppTimestamp();
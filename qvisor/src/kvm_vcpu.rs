@@ -40,12 +40,19 @@ use super::qlib::GetTimeCall;
 //use super::qlib::kernel::stack::*;
 use super::qlib::linux::time::Timespec;
 use super::qlib::linux_def::*;
+use super::qlib::negotiation::*;
 use super::qlib::pagetable::*;
 use super::qlib::perf_tunning::*;
 use super::qlib::task_mgr::*;
 use super::qlib::vcpu_mgr::*;
+use super::runc::runtime::async_pf::ASYNC_PF_WAIT_MS;
 use super::runc::runtime::vm::*;
 use super::syncmgr::*;
+use super::vmspace::dirty_ring::*;
+use super::vmspace::exit_stats::{CyclesToNs, ExitReason, ExitStats, KvmExitStats};
+use super::vmspace::exit_stats::{KVM_EXIT_X86_RDMSR, KVM_EXIT_X86_WRMSR};
+use super::vmspace::qcall_pool::QCALL_POOL;
+use super::vmspace::teardown::Exit;
 use super::URING_MGR;
 
 #[repr(C)]
@@ -150,6 +157,8 @@ pub struct KVMVcpu {
 
     pub autoStart: bool,
     pub interrupting: AtomicBool,
+    // the profile of the exits to qvisor
+    pub exitStats: ExitStats,
     //the pipe id to notify io_mgr
 }
 
@@ -225,6 +234,7 @@ impl KVMVcpu {
             shareSpaceAddr: shareSpaceAddr,
             autoStart: autoStart,
             interrupting: AtomicBool::new(false),
+            exitStats: ExitStats::default(),
         });
     }
 
@@ -456,7 +466,13 @@ impl KVMVcpu {
             "start enter guest[{}]: entry is {:x}, stack is {:x}",
             self.id, self.entry, self.topStackAddr
         );
+        // the exit in handling and the tsc at its start
+        let mut exit: Option<(ExitReason, u64)> = None;
         loop {
+            if let Some((reason, start)) = exit.take() {
+                self.exitStats.Record(reason, start);
+            }
+
             if !super::runc::runtime::vm::IsRunning() {
                 return Ok(());
             }
@@ -487,6 +503,7 @@ impl KVMVcpu {
             };
             self.state
                 .store(KVMVcpuState::HOST as u64, Ordering::Release);
+            exit = Some((Self::ExitReasonOf(&kvmRet), ExitStats::Start()));
 
             match kvmRet {
                 VcpuExit::IoIn(addr, data) => {
@@ -575,6 +592,7 @@ impl KVMVcpu {
                             let exitCode = regs.rbx as i32;
 
                             super::print::LOG.Clear();
+                            super::print::BOOT_CONSOLE.Flush();
                             PerfPrint();
 
                             SetExitStatus(exitCode);
@@ -591,8 +609,9 @@ impl KVMVcpu {
                             let addr = vcpu_regs.rbx;
                             let msg = unsafe { &*(addr as *const Print) };
 
+                            super::print::BOOT_CONSOLE.Flush();
                             eprintln!("Application error: {}", msg.str);
-                            ::std::process::exit(1);
+                            Exit(1);
                         }
 
                         qlib::HYPERCALL_WAKEUP_VCPU => {
@@ -606,6 +625,46 @@ impl KVMVcpu {
                             SyncMgr::WakeVcpu(vcpuId);
                         }
 
+                        qlib::HYPERCALL_NEGOTIATE => {
+                            let vcpu_regs = self.vcpu.get_regs().unwrap();
+                            let addr = vcpu_regs.rbx;
+                            let negotiation = unsafe { &mut *(addr as *mut FeatureNegotiation) };
+
+                            let hostFeatures = Feature::Enabled(&QUARK_CONFIG.lock());
+                            let status = negotiation.Negotiate(size_of::<ShareSpace>() as u64, hostFeatures);
+                            if status != NegotiationStatus::Ok {
+                                error!(
+                                    "qkernel/qvisor feature negotiation fail {:?}: qkernel {:x?}, qvisor sharespace version {} qcall version {}",
+                                    status, negotiation, SHARESPACE_VERSION, QCALL_VERSION
+                                );
+                            } else {
+                                info!("qkernel/qvisor agreed features {:x}", negotiation.agreedFeatures);
+                                SetNegotiatedFeatures(negotiation.agreedFeatures);
+                            }
+                        }
+
+                        qlib::HYPERCALL_RNG => {
+                            let vcpu_regs = self.vcpu.get_regs().unwrap();
+                            let addr = vcpu_regs.rbx;
+                            let len = vcpu_regs.rcx as usize;
+                            let retAddr = vcpu_regs.rdi;
+                            let data = unsafe { slice::from_raw_parts_mut(addr as *mut u8, len) };
+
+                            let ret = super::vmspace::random::HostEntropy(data);
+                            unsafe {
+                                *(retAddr as *mut i64) = ret;
+                            }
+                        }
+
+                        qlib::HYPERCALL_CONSOLE => {
+                            let vcpu_regs = self.vcpu.get_regs().unwrap();
+                            let addr = vcpu_regs.rbx;
+                            let len = vcpu_regs.rcx as usize;
+                            let data = unsafe { slice::from_raw_parts(addr as *const u8, len) };
+
+                            super::print::BOOT_CONSOLE.Write(self.id, data);
+                        }
+
                         qlib::HYPERCALL_PRINT => {
                             let vcpu_regs = self.vcpu.get_regs().unwrap();
                             let addr = vcpu_regs.rbx;
@@ -637,12 +696,12 @@ impl KVMVcpu {
                                 "OOM!!! cpu [{}], size is {:x}, alignment is {:x}",
                                 self.id, data1, data2
                             );
-                            ::std::process::exit(1);
+                            Exit(1);
                         }
 
                         qlib::HYPERCALL_EXIT => {
                             info!("call in HYPERCALL_EXIT");
-                            unsafe { libc::_exit(0) }
+                            Exit(0);
                         }
 
                         qlib::HYPERCALL_U64 => unsafe {
@@ -784,6 +843,26 @@ impl KVMVcpu {
                             }
                         }
 
+                        port if super::qcall_plugin::IsPluginHypercall(port) => {
+                            let vcpu_regs = self.vcpu.get_regs().unwrap();
+                            let retAddr = vcpu_regs.rdi;
+                            // the result is written in the guest, the address
+                            // is to be an aligned i64 of the guest kernel
+                            if retAddr % 8 != 0 || !super::qcall_plugin::InGuestKernel(retAddr, 8) {
+                                error!("qcall plugin {:x} invalid result address {:x}", port, retAddr);
+                            } else {
+                                let ret = super::qcall_plugin::Dispatch(
+                                    self.id,
+                                    port,
+                                    vcpu_regs.rbx,
+                                    vcpu_regs.rcx,
+                                );
+                                unsafe {
+                                    *(retAddr as *mut i64) = ret;
+                                }
+                            }
+                        }
+
                         _ => info!("Unknow hyper call!!!!! address is {}", addr),
                     }
                 }
@@ -802,6 +881,9 @@ impl KVMVcpu {
                 VcpuExit::Hlt => {
                     error!("in hlt....");
                 }
+                VcpuExit::Unsupported(KVM_EXIT_DIRTY_RING_FULL) => {
+                    DIRTY_RING.Collect();
+                }
                 VcpuExit::FailEntry => {
                     info!("get fail entry***********************************");
                     break;
@@ -890,9 +972,7 @@ impl KVMVcpu {
 
                     error!("Panic: CPU[{}] Unexpected exit reason: {:?}, regs is {:#x?}, sregs is {:#x?}",
                         self.id, r, regs, vcpu_sregs);
-                    unsafe {
-                        libc::exit(0);
-                    }
+                    Exit(0);
                 }
             }
         }
@@ -901,6 +981,25 @@ impl KVMVcpu {
         Ok(())
     }
 
+    fn ExitReasonOf(exit: &VcpuExit) -> ExitReason {
+        match exit {
+            VcpuExit::IoOut(addr, _) => match *addr {
+                qlib::HYPERCALL_HLT | qlib::HYPERCALL_IOWAIT | qlib::HYPERCALL_VCPU_WAIT => {
+                    return ExitReason::Hlt
+                }
+                _ => return ExitReason::IoOut,
+            },
+            VcpuExit::Hlt => return ExitReason::Hlt,
+            VcpuExit::IoIn(..) => return ExitReason::IoIn,
+            VcpuExit::MmioRead(..) | VcpuExit::MmioWrite(..) => return ExitReason::Mmio,
+            VcpuExit::Unsupported(KVM_EXIT_X86_RDMSR)
+            | VcpuExit::Unsupported(KVM_EXIT_X86_WRMSR) => return ExitReason::Msr,
+            VcpuExit::Unsupported(KVM_EXIT_DIRTY_RING_FULL) => return ExitReason::DirtyRingFull,
+            VcpuExit::IrqWindowOpen | VcpuExit::Intr => return ExitReason::Interrupt,
+            _ => return ExitReason::Other,
+        }
+    }
+
     pub fn VcpuWait(&self) -> i64 {
         let sharespace = &SHARE_SPACE;
         loop {
@@ -938,6 +1037,10 @@ impl KVMVcpu {
                 None => break,
                 Some(HostOutputMsg::QCall(addr)) => {
                     count += 1;
+                    if QCALL_POOL.Dispatch(addr) {
+                        continue;
+                    }
+
                     let eventAddr = addr as *mut QMsg; // as &mut qlib::Event;
                     let qmsg = unsafe { &mut (*eventAddr) };
                     let currTaskId = qmsg.taskId;
@@ -993,6 +1096,10 @@ impl KVMVcpu {
             return Ok(());
         }
         defer!(ClearDump(self.id));
+        error!("vcpu {} exits:\n{}", self.id, self.exitStats.Dump());
+        if let Some(stats) = KvmExitStats(self.vcpu.as_raw_fd()) {
+            error!("vcpu {} kvm exit stats: {:?}", self.id, stats);
+        }
         let regs = self
             .vcpu
             .get_regs()
@@ -1053,6 +1160,9 @@ impl Scheduler {
 
 pub const VCPU_WAIT_CYCLES: i64 = 1_000_000; // 1ms
 
+// the first halt polling of a vcpu after a short sleep, as halt_poll_ns_grow_start of KVM
+pub const HALT_POLL_GROW_START_NS: u64 = 10_000;
+
 impl CPULocal {
     pub fn Init(&mut self, vcpuId: usize) {
         let epfd = unsafe { epoll_create1(0) };
@@ -1166,10 +1276,77 @@ impl CPULocal {
         return None;
     }
 
+    // HaltPoll spins for a task for the halt polling time of the vcpu, which
+    // saves the sleep and the wakeup of the vcpu thread when a task comes soon.
+    pub fn HaltPoll(&self, sharespace: &ShareSpace) -> Option<u64> {
+        let pollNs = self.haltPollNs.load(Ordering::Relaxed);
+        if pollNs == 0 || sharespace.config.read().HaltPollNs == 0 {
+            return None;
+        }
+
+        let start = ExitStats::Start();
+        while !sharespace.Shutdown() {
+            match sharespace.scheduler.GetNext() {
+                None => (),
+                Some(newTask) => return Some(newTask.data),
+            }
+
+            if CyclesToNs(ExitStats::Start().wrapping_sub(start)) >= pollNs {
+                break;
+            }
+
+            core::hint::spin_loop();
+        }
+
+        return None;
+    }
+
+    // AdjustHaltPoll grows the halt polling time of the vcpu after a sleep
+    // shorter than the longest polling, the polling would have got the task,
+    // and shrinks it after a longer sleep, as kvm_vcpu_halt of KVM.
+    pub fn AdjustHaltPoll(&self, sharespace: &ShareSpace, blockNs: u64) {
+        let config = *sharespace.config.read();
+        let maxNs = config.HaltPollNs;
+
+        let pollNs = self.haltPollNs.load(Ordering::Relaxed);
+        let newNs = if maxNs == 0 {
+            0
+        } else if blockNs <= pollNs {
+            pollNs
+        } else if pollNs > 0 && blockNs > maxNs {
+            if config.HaltPollShrink == 0 {
+                0
+            } else {
+                pollNs / config.HaltPollShrink
+            }
+        } else if pollNs < maxNs && blockNs < maxNs {
+            if pollNs == 0 {
+                HALT_POLL_GROW_START_NS.min(maxNs)
+            } else {
+                (pollNs * config.HaltPollGrow.max(1)).min(maxNs)
+            }
+        } else {
+            pollNs
+        };
+
+        if newNs != pollNs {
+            self.haltPollNs.store(newNs, Ordering::Relaxed);
+        }
+    }
+
     pub fn VcpuWait(&self, sharespace: &ShareSpace, block: bool) -> Result<u64> {
         let mut events = [epoll_event { events: 0, u64: 0 }; 2];
 
-        let time = if block { -1 } else { 0 };
+        // with the async page faults the vcpu has to go back to KVM_RUN to get
+        // their page ready
+        let asyncPfWait = block && self.asyncPfWaits.load(Ordering::Relaxed) > 0;
+        let time = if asyncPfWait {
+            ASYNC_PF_WAIT_MS
+        } else if block {
+            -1
+        } else {
+            0
+        };
 
         sharespace.scheduler.VcpWaitMaskSet(self.vcpuId);
         defer!(sharespace.scheduler.VcpWaitMaskClear(self.vcpuId););
@@ -1179,9 +1356,21 @@ impl CPULocal {
             Some(newTask) => return Ok(newTask),
         }
 
+        if block {
+            match self.HaltPoll(sharespace) {
+                None => (),
+                Some(newTask) => return Ok(newTask),
+            }
+        }
+
         super::ALLOCATOR.Clear();
         self.ToWaiting(sharespace);
         defer!(self.ToSearch(sharespace););
+        let blockStart = ExitStats::Start();
+        defer!(if block {
+            let blockNs = CyclesToNs(ExitStats::Start().wrapping_sub(blockStart));
+            self.AdjustHaltPoll(sharespace, blockNs);
+        });
 
         while !sharespace.Shutdown() {
             match self.Process(sharespace) {
@@ -1191,6 +1380,11 @@ impl CPULocal {
                 }
             }
 
+            // the socket events steered and the timers dispatched to the vcpu,
+            // it is woken up for them
+            sharespace.recvSteering.Process(self.vcpuId);
+            sharespace.timerDispatch.Process(self.vcpuId);
+
             if sharespace.scheduler.VcpWaitMaskSet(self.vcpuId) {
                 match sharespace.scheduler.GetNext() {
                     None => (),
@@ -1202,7 +1396,10 @@ impl CPULocal {
 
             super::ALLOCATOR.Clear();
 
-            let _nfds = unsafe { epoll_wait(self.epollfd, &mut events[0], 2, time) };
+            let nfds = unsafe { epoll_wait(self.epollfd, &mut events[0], 2, time) };
+            if nfds == 0 && asyncPfWait {
+                return Ok(0);
+            }
 
             {
                 let mut data: u64 = 0;
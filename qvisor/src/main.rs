@@ -72,6 +72,7 @@ mod kvm_vcpu;
 mod memmgr;
 pub mod namespace;
 mod qcall;
+pub mod qcall_plugin;
 pub mod qlib;
 pub mod rdma_def;
 pub mod runc;
@@ -95,6 +96,7 @@ use self::qlib::qmsg::*;
 use self::qlib::ShareSpace;
 use self::qlib::ShareSpaceRef;
 use self::runc::cmd::command::*;
+use self::runc::runtime::config_file::HostConfig;
 use self::runc::shim::service::*;
 use self::vmspace::host_pma_keeper::*;
 use self::vmspace::hostfdnotifier::*;
@@ -146,6 +148,7 @@ lazy_static! {
         config.Load();
         Mutex::new(config)
     };
+    pub static ref HOST_CONFIG: HostConfig = HostConfig::Load();
     pub static ref URING_MGR: Arc<Mutex<UringMgr>> = {
         let uringQueueSize = if QUARK_CONFIG.lock().UringBuf {
             1024
@@ -163,6 +166,7 @@ pub const LOG_FILE: &'static str = "/var/log/quark/quark.log";
 
 pub fn InitSingleton() {
     self::qlib::InitSingleton();
+    self::qcall_plugin::RegisterPlugins();
 }
 
 #[global_allocator]
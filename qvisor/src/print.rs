@@ -12,10 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::collections::btree_map::BTreeMap;
+use alloc::collections::vec_deque::VecDeque;
 use alloc::string::String;
 use chrono::prelude::*;
 use lazy_static::lazy_static;
+use std::fs::File;
 use std::fs::OpenOptions;
+use std::io::Write;
 use std::os::unix::io::IntoRawFd;
 use core::sync::atomic::AtomicBool;
 use core::sync::atomic::AtomicI32;
@@ -24,10 +28,12 @@ use core::sync::atomic::Ordering;
 use super::qlib::kernel::Timestamp;
 use super::qlib::kernel::IOURING;
 use super::qlib::kernel::SHARESPACE;
+use super::qlib::mutex::*;
 use super::ThreadId;
 
 lazy_static! {
     pub static ref LOG: Log = Log::New();
+    pub static ref BOOT_CONSOLE: BootConsole = BootConsole::New();
 }
 
 pub struct Log {
@@ -40,6 +46,7 @@ pub fn SetSyncPrint(syncPrint: bool) {
 }
 
 pub const LOG_FILE_DEFAULT: &str = "/var/log/quark/quark.log";
+pub const BOOT_LOG_FILE_DEFAULT: &str = "/var/log/quark/boot.log";
 pub const LOG_FILE_FORMAT: &str = "/var/log/quark/{}.log";
 pub const TIME_FORMAT: &str = "%H:%M:%S%.3f";
 
@@ -154,6 +161,107 @@ impl Log {
     }
 }
 
+// BootConsole persists the output of the qkernel early console (HYPERCALL_CONSOLE).
+// The output is line buffered per vcpu and each line is timestamped by qvisor, as
+// the guest might not have a working clock when it prints.
+pub struct BootConsole {
+    pub file: QMutex<File>,
+    pub lines: QMutex<BTreeMap<usize, ConsoleLine>>,
+}
+
+// the bytes kept of a line, a longer line keeps its tail
+pub const BOOT_CONSOLE_LINE_MAX: usize = 4096;
+
+// ConsoleLine is the ring of the last BOOT_CONSOLE_LINE_MAX bytes of a line of
+// the early console, the guest can't grow it with a line never ended.
+#[derive(Default)]
+pub struct ConsoleLine {
+    pub buf: VecDeque<u8>,
+    // the count of the bytes of the line dropped from the ring
+    pub dropped: usize,
+}
+
+impl ConsoleLine {
+    pub fn Push(&mut self, b: u8) {
+        if self.buf.len() == BOOT_CONSOLE_LINE_MAX {
+            self.buf.pop_front();
+            self.dropped += 1;
+        }
+
+        self.buf.push_back(b);
+    }
+
+    pub fn Take(&mut self) -> String {
+        let (head, tail) = self.buf.as_slices();
+        let mut line = String::from_utf8_lossy(head).into_owned();
+        line.push_str(&String::from_utf8_lossy(tail));
+        if self.dropped > 0 {
+            line = format!("[{} bytes dropped] {}", self.dropped, line);
+        }
+
+        self.buf.clear();
+        self.dropped = 0;
+        return line;
+    }
+
+    pub fn IsEmpty(&self) -> bool {
+        return self.buf.len() == 0 && self.dropped == 0;
+    }
+}
+
+impl BootConsole {
+    pub fn New() -> Self {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(BOOT_LOG_FILE_DEFAULT)
+            .expect("BootConsole Open fail");
+        return Self {
+            file: QMutex::new(file),
+            lines: QMutex::new(BTreeMap::new()),
+        };
+    }
+
+    pub fn Reset(&self, name: &str) {
+        let filename = format!("/var/log/quark/{}.boot.log", name);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(filename)
+            .expect("BootConsole Open fail");
+        *self.file.lock() = file;
+    }
+
+    pub fn Write(&self, vcpuId: usize, data: &[u8]) {
+        let mut lines = self.lines.lock();
+        let line = lines.entry(vcpuId).or_insert_with(ConsoleLine::default);
+        for &b in data {
+            if b == b'\n' {
+                self.WriteLine(vcpuId, &line.Take());
+            } else {
+                line.Push(b);
+            }
+        }
+    }
+
+    // Flush writes out the partial lines, e.g. when the VM exits.
+    pub fn Flush(&self) {
+        let mut lines = self.lines.lock();
+        for (vcpuId, line) in lines.iter_mut() {
+            if !line.IsEmpty() {
+                self.WriteLine(*vcpuId, &line.Take());
+            }
+        }
+    }
+
+    fn WriteLine(&self, vcpuId: usize, line: &str) {
+        let line = format!("{} [vcpu{}] {}\n", Log::Now(), vcpuId, line);
+        // the log file is best effort, the line is also copied to the main log
+        let _ = self.file.lock().write_all(line.as_bytes());
+        LOG.RawWrite(&format!("[CONSOLE] {}", line));
+    }
+}
+
 #[macro_export]
 macro_rules! raw {
     // macth like arm for macro
@@ -332,6 +332,12 @@ impl KVMVcpu {
             Msg::Sysinfo(msg) => {
                 ret = super::VMSpace::Sysinfo(msg.addr) as u64;
             }
+            Msg::NetDevs(msg) => {
+                ret = super::vmspace::netdev::NetDevs(msg.addr, msg.count) as u64;
+            }
+            Msg::PauseAbort(_) => {
+                super::ucall::ucall_server::PauseAbort();
+            }
             Msg::ReadDir(msg) => {
                 ret = super::VMSpace::ReadDir(msg.dirfd, msg.addr, msg.len, msg.reset) as u64;
             }
@@ -0,0 +1,159 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::sync::Arc;
+use core::slice;
+use spin::RwLock;
+
+use super::qlib::common::*;
+use super::qlib::linux_def::*;
+use super::qlib::{HYPERCALL_PLUGIN_BASE, HYPERCALL_PLUGIN_COUNT};
+
+// The qcall plugins serve the host services which are not part of qvisor,
+// e.g. the client of a storage system or a secrets fetcher. A plugin is
+// added with its id and constructor to QCALL_PLUGIN_TABLE, RegisterPlugins
+// registers the table at the start of qvisor. The guest calls it with the
+// hypercall HYPERCALL_PLUGIN_BASE + id through HostSpace::PluginCall, the
+// dispatch code of the vcpu doesn't change with the plugins. The call runs
+// on the vcpu thread which takes the exit, out of GLOCK: a plugin is to be
+// thread safe and not to block for long, the vcpu doesn't run the guest
+// meanwhile.
+
+// PluginCall is a hypercall of a plugin. The guest memory is mapped at the
+// same address in qvisor, addr and len are a buffer of the guest kernel
+// whose layout is up to the plugin.
+#[derive(Debug, Clone, Copy)]
+pub struct PluginCall {
+    pub vcpuId: usize,
+    pub id: u16,
+    pub addr: u64,
+    pub len: u64,
+}
+
+pub trait QcallPlugin: Send + Sync {
+    fn Name(&self) -> &'static str;
+
+    // Call serves the hypercall, it returns the result to the guest, a
+    // negative errno on failure.
+    fn Call(&self, call: &PluginCall) -> i64;
+}
+
+lazy_static! {
+    pub static ref QCALL_PLUGINS: RwLock<BTreeMap<u16, Arc<dyn QcallPlugin>>> =
+        RwLock::new(BTreeMap::new());
+}
+
+pub fn RegisterPlugin(id: u16, plugin: Arc<dyn QcallPlugin>) -> Result<()> {
+    if id >= HYPERCALL_PLUGIN_COUNT {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let mut plugins = QCALL_PLUGINS.write();
+    if let Some(p) = plugins.get(&id) {
+        error!(
+            "qcall plugin {} conflicts with {} on id {}",
+            plugin.Name(),
+            p.Name(),
+            id
+        );
+        return Err(Error::SysError(SysErr::EEXIST));
+    }
+
+    info!("register qcall plugin {} with id {}", plugin.Name(), id);
+    plugins.insert(id, plugin);
+    return Ok(());
+}
+
+// the plugins built in qvisor, the downstream plugins are added here
+pub const QCALL_PLUGIN_TABLE: &[(u16, fn() -> Arc<dyn QcallPlugin>)] =
+    &[(QCALL_PLUGIN_VERSION, VersionPlugin::New)];
+
+// RegisterPlugins registers the plugins of QCALL_PLUGIN_TABLE, a plugin which
+// fails to register is left out.
+pub fn RegisterPlugins() {
+    for (id, new) in QCALL_PLUGIN_TABLE {
+        let plugin = new();
+        if let Err(e) = RegisterPlugin(*id, plugin) {
+            error!("qcall plugin id {} registration fail {:?}", id, e);
+        }
+    }
+}
+
+pub const QCALL_PLUGIN_VERSION: u16 = 0;
+
+// VersionPlugin copies the version of qvisor in the buffer, it returns the
+// length of the version.
+pub struct VersionPlugin {}
+
+impl VersionPlugin {
+    pub fn New() -> Arc<dyn QcallPlugin> {
+        return Arc::new(Self {});
+    }
+}
+
+impl QcallPlugin for VersionPlugin {
+    fn Name(&self) -> &'static str {
+        return "version";
+    }
+
+    fn Call(&self, call: &PluginCall) -> i64 {
+        let version = env!("CARGO_PKG_VERSION").as_bytes();
+        if (call.len as usize) < version.len() {
+            return -SysErr::ERANGE as i64;
+        }
+
+        let buf = unsafe { slice::from_raw_parts_mut(call.addr as *mut u8, version.len()) };
+        buf.copy_from_slice(version);
+        return version.len() as i64;
+    }
+}
+
+// InGuestKernel returns whether addr..addr+len is in the guest kernel memory,
+// which qvisor maps at the same address.
+pub fn InGuestKernel(addr: u64, len: u64) -> bool {
+    let end = match addr.checked_add(len) {
+        None => return false,
+        Some(e) => e,
+    };
+
+    return addr >= MemoryDef::PHY_LOWER_ADDR && end <= MemoryDef::PHY_UPPER_ADDR;
+}
+
+pub fn IsPluginHypercall(port: u16) -> bool {
+    return port >= HYPERCALL_PLUGIN_BASE && port < HYPERCALL_PLUGIN_BASE + HYPERCALL_PLUGIN_COUNT;
+}
+
+// Dispatch serves the hypercall of port, it returns ENOSYS for the ids
+// without plugin and EFAULT for a buffer out of the guest kernel.
+pub fn Dispatch(vcpuId: usize, port: u16, addr: u64, len: u64) -> i64 {
+    if !InGuestKernel(addr, len) {
+        return -SysErr::EFAULT as i64;
+    }
+
+    let id = port - HYPERCALL_PLUGIN_BASE;
+    let plugin = match QCALL_PLUGINS.read().get(&id) {
+        None => return -SysErr::ENOSYS as i64,
+        Some(p) => p.clone(),
+    };
+
+    let call = PluginCall {
+        vcpuId: vcpuId,
+        id: id,
+        addr: addr,
+        len: len,
+    };
+
+    return plugin.Call(&call);
+}
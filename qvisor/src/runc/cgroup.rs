@@ -25,6 +25,7 @@ use std::{thread, time};
 
 use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
+use super::super::qlib::loader::{BlockPartition, IoMax};
 use super::super::qlib::path::*;
 use super::oci::*;
 use super::specutils::specutils::MkdirAll;
@@ -422,6 +423,30 @@ impl Cgroup {
         )));
     }
 
+    // Freeze freezes or thaws the tasks of the cgroup with the freezer
+    // controller and waits till the freezer reaches the state.
+    pub fn Freeze(&self, frozen: bool) -> Result<()> {
+        let path = self.MakePath("freezer");
+        let state = if frozen { "FROZEN" } else { "THAWED" };
+        SetValue(&path, "freezer.state", state)?;
+
+        // the freezer stays in FREEZING till all the tasks are frozen
+        for i in 0..7 {
+            if GetValue(&path, "freezer.state")?.trim() == state {
+                return Ok(());
+            }
+
+            //sleep 2^i * 10 ms
+            let millies = time::Duration::from_millis(10 << i);
+            thread::sleep(millies);
+        }
+
+        return Err(Error::Common(format!(
+            "cgroup {} freezer doesn't reach {}",
+            &path, state
+        )));
+    }
+
     pub fn MakePath(&self, controllerName: &str) -> String {
         let mut path = self.Name.to_string();
         match self.Parents.get(controllerName) {
@@ -548,6 +573,79 @@ pub fn SetThrottle(path: &str, name: &str, devs: &[LinuxThrottleDevice]) -> Resu
     return Ok(());
 }
 
+// IoMaxFromSpec returns the io.max the guest enforces for the container, the
+// blkio throttle devices of the spec merged by device.
+pub fn IoMaxFromSpec(spec: &Spec) -> Vec<IoMax> {
+    let b = match spec
+        .linux
+        .as_ref()
+        .and_then(|l| l.resources.as_ref())
+        .and_then(|r| r.block_io.as_ref())
+    {
+        None => return Vec::new(),
+        Some(b) => b,
+    };
+
+    let mut limits: BTreeMap<(u32, u32), IoMax> = BTreeMap::new();
+    let mut set = |devs: &[LinuxThrottleDevice], f: fn(&mut IoMax, u64)| {
+        for dev in devs {
+            let (major, minor) = (dev.major as u32, dev.minor as u32);
+            let limit = limits.entry((major, minor)).or_insert(IoMax {
+                Major: major,
+                Minor: minor,
+                ..Default::default()
+            });
+            f(limit, dev.rate);
+        }
+    };
+
+    set(&b.throttle_read_bps_device, |l, v| l.Rbps = v);
+    set(&b.throttle_write_bps_device, |l, v| l.Wbps = v);
+    set(&b.throttle_read_iops_device, |l, v| l.Riops = v);
+    set(&b.throttle_write_iops_device, |l, v| l.Wiops = v);
+
+    return limits.into_iter().map(|(_, l)| l).collect();
+}
+
+// IoMaxPartitions returns the partitions of the disks of the io.max, from the
+// sysfs directories of the disks.
+pub fn IoMaxPartitions(limits: &[IoMax]) -> Vec<BlockPartition> {
+    let mut partitions = Vec::new();
+    for limit in limits {
+        let path = format!("/sys/dev/block/{}:{}", limit.Major, limit.Minor);
+        let entries = match fs::read_dir(&path) {
+            Err(_) => continue,
+            Ok(entries) => entries,
+        };
+
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            if !dir.join("partition").exists() {
+                continue;
+            }
+
+            let dev = match fs::read_to_string(dir.join("dev")) {
+                Err(_) => continue,
+                Ok(dev) => dev,
+            };
+
+            let mut parts = dev.trim().split(':');
+            let major = parts.next().and_then(|m| m.parse::<u32>().ok());
+            let minor = parts.next().and_then(|m| m.parse::<u32>().ok());
+            if let (Some(major), Some(minor)) = (major, minor) {
+                partitions.push(BlockPartition {
+                    Major: major,
+                    Minor: minor,
+                    DiskMajor: limit.Major,
+                    DiskMinor: limit.Minor,
+                });
+            }
+        }
+    }
+
+    return partitions;
+}
+
 fn NetworkClass(spec: &LinuxResources, path: &str) -> Result<()> {
     match spec.network {
         None => return Ok(()),
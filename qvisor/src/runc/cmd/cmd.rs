@@ -21,6 +21,7 @@ use std::fs;
 use super::super::super::qlib::common::*;
 use super::super::super::qlib::config::*;
 use super::super::cmd::config::*;
+use super::super::runtime::config_file::*;
 use super::super::runtime::loader::*;
 use super::super::runtime::vm::*;
 use super::command::*;
@@ -77,15 +78,20 @@ impl CmdCmd {
 impl Config {
     pub const CONFIG_FILE: &'static str = "/etc/quark/config.json";
 
-    // if the config file exist, load file and return true; otherwise return false
+    // if the config file exist, load file and return true; otherwise return false.
+    // config.toml is used if it exists, else the old config.json.
     pub fn Load(&mut self) -> bool {
+        if let Ok(contents) = fs::read_to_string(CONFIG_TOML_FILE) {
+            *self = ConfigFromToml(&contents).expect("configuration wrong format");
+            return true;
+        }
+
         let contents = match fs::read_to_string(Self::CONFIG_FILE) {
             Ok(c) => c,
             _ => return false,
         };
 
-        let config = serde_json::from_str(&contents).expect("configuration wrong format");
-        *self = config;
+        *self = ConfigFromJson(&contents).expect("configuration wrong format");
         return true;
     }
 
@@ -22,15 +22,23 @@ use super::config;
 use super::config::*;
 use super::create::*;
 use super::delete::*;
+use super::do_cmd::*;
+use super::etc_files::*;
 use super::exec::*;
 use super::kill::*;
+use super::layer::*;
 use super::list::*;
+use super::netstat::*;
 use super::pause::*;
 use super::ps::*;
+use super::reconfig::*;
+use super::resources::*;
 use super::resume::*;
 use super::run::*;
+use super::selftest::*;
 use super::start::*;
 use super::state::*;
+use super::top::*;
 use super::wait::*;
 
 fn id_validator(val: String) -> core::result::Result<(), String> {
@@ -175,6 +183,14 @@ pub fn Parse() -> Result<Arguments> {
         .subcommand(KillCmd::SubCommand(&common))
         .subcommand(DeleteCmd::SubCommand(&common))
         .subcommand(StateCmd::SubCommand(&common))
+        .subcommand(ReconfigCmd::SubCommand(&common))
+        .subcommand(EtcFilesCmd::SubCommand(&common))
+        .subcommand(ResourcesCmd::SubCommand(&common))
+        .subcommand(LayerCmd::SubCommand(&common))
+        .subcommand(TopCmd::SubCommand(&common))
+        .subcommand(NetstatCmd::SubCommand(&common))
+        .subcommand(DoCmd::SubCommand(&common))
+        .subcommand(SelftestCmd::SubCommand(&common))
         .get_matches_from(get_args());
 
     let level = match matches.occurrences_of("v") {
@@ -259,6 +275,38 @@ pub fn Parse() -> Result<Arguments> {
             config: gConfig,
             cmd: Command::StateCmd(StateCmd::Init(&cmd_matches)?),
         },
+        ("reconfig", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::ReconfigCmd(ReconfigCmd::Init(&cmd_matches)?),
+        },
+        ("etc-files", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::EtcFilesCmd(EtcFilesCmd::Init(&cmd_matches)?),
+        },
+        ("resources", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::ResourcesCmd(ResourcesCmd::Init(&cmd_matches)?),
+        },
+        ("layer", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::LayerCmd(LayerCmd::Init(&cmd_matches)?),
+        },
+        ("top", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::TopCmd(TopCmd::Init(&cmd_matches)?),
+        },
+        ("netstat", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::NetstatCmd(NetstatCmd::Init(&cmd_matches)?),
+        },
+        ("do", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::DoCmd(DoCmd::Init(&cmd_matches)?),
+        },
+        ("selftest", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::SelftestCmd(SelftestCmd::Init(&cmd_matches)?),
+        },
         // We should never reach here because clap already enforces this
         _ => panic!("command not recognized"),
     };
@@ -288,6 +336,14 @@ pub enum Command {
     KillCmd(KillCmd),
     DeleteCmd(DeleteCmd),
     StateCmd(StateCmd),
+    ReconfigCmd(ReconfigCmd),
+    EtcFilesCmd(EtcFilesCmd),
+    ResourcesCmd(ResourcesCmd),
+    LayerCmd(LayerCmd),
+    TopCmd(TopCmd),
+    NetstatCmd(NetstatCmd),
+    DoCmd(DoCmd),
+    SelftestCmd(SelftestCmd),
 }
 
 pub fn Run(args: &mut Arguments) -> Result<()> {
@@ -306,5 +362,13 @@ pub fn Run(args: &mut Arguments) -> Result<()> {
         Command::KillCmd(cmd) => return cmd.Run(&mut args.config),
         Command::DeleteCmd(cmd) => return cmd.Run(&mut args.config),
         Command::StateCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::ReconfigCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::EtcFilesCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::ResourcesCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::LayerCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::TopCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::NetstatCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::DoCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::SelftestCmd(cmd) => return cmd.Run(&mut args.config),
     }
 }
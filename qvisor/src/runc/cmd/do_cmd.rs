@@ -0,0 +1,377 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::linux_def::*;
+use super::super::super::qlib::path::*;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::super::oci::*;
+use super::super::runtime::prefetch::{ElfDeps, OpenInRoot, OpenRoot, ResolveLib};
+use super::super::specutils::specutils::{MkdirAll, ValidateSpec};
+use super::command::*;
+
+const DEFAULT_PATH_ENV: &str = "PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+// DoCmd runs a host binary in a sandbox without an OCI bundle. A temporary bundle
+// is built around the binary: the rootfs only contains the binary, its ELF
+// interpreter and the shared libraries it needs.
+#[derive(Debug)]
+pub struct DoCmd {
+    pub id: String,
+    pub cwd: String,
+    pub env: Vec<String>,
+    pub volumes: Vec<String>,
+    pub keep: bool,
+    pub cmd: Vec<String>,
+}
+
+impl DoCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        let id = match cmd_matches.value_of("id") {
+            None => format!("do-{}", process::id()),
+            Some(id) => id.to_string(),
+        };
+
+        return Ok(Self {
+            id: id,
+            cwd: cmd_matches.value_of("cwd").unwrap().to_string(),
+            env: match cmd_matches.values_of("env") {
+                None => Vec::new(),
+                Some(iter) => iter.map(|s| s.to_string()).collect(),
+            },
+            volumes: match cmd_matches.values_of("volume") {
+                None => Vec::new(),
+                Some(iter) => iter.map(|s| s.to_string()).collect(),
+            },
+            keep: cmd_matches.is_present("keep"),
+            cmd: cmd_matches
+                .values_of("cmd")
+                .unwrap()
+                .map(|s| s.to_string())
+                .collect(),
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(_common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("do")
+            .setting(AppSettings::ColoredHelp)
+            .setting(AppSettings::TrailingVarArg)
+            .arg(
+                Arg::with_name("id")
+                    .help("container id, defaults to do-<pid>")
+                    .takes_value(true)
+                    .long("id"),
+            )
+            .arg(
+                Arg::with_name("cwd")
+                    .help("working directory inside the sandbox")
+                    .default_value("/")
+                    .takes_value(true)
+                    .long("cwd"),
+            )
+            .arg(
+                Arg::with_name("env")
+                    .help("environment variable in KEY=VALUE format")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .long("env")
+                    .short("e"),
+            )
+            .arg(
+                Arg::with_name("volume")
+                    .help("bind mount a host path into the sandbox: src[:dst[:ro]]")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .long("volume")
+                    .short("v"),
+            )
+            .arg(
+                Arg::with_name("keep")
+                    .help("keep the generated bundle after the command exits")
+                    .long("keep"),
+            )
+            .arg(
+                Arg::with_name("cmd")
+                    .help("host binary and its arguments")
+                    .required(true)
+                    .multiple(true),
+            )
+            .about("Run a host binary in a sandbox without a container image");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let status = self.Exec(gCfg)?;
+        if status != 0 {
+            process::exit(status);
+        }
+
+        return Ok(());
+    }
+
+    // Exec runs the command in a temporary sandbox and returns its exit status.
+    pub fn Exec(&self, gCfg: &GlobalConfig) -> Result<i32> {
+        let bundleDir = Join(&env::temp_dir().to_string_lossy(), &format!("quark-{}", self.id));
+        let rootfs = Join(&bundleDir, "rootfs");
+
+        // the cleanup below destroys the container of the id and removes its
+        // bundle, so only run with an id which is not in use
+        if Path::new(&Join(&gCfg.RootDir, &self.id)).exists() {
+            return Err(Error::Common(format!(
+                "container with id {} already exists",
+                &self.id
+            )));
+        }
+
+        if Path::new(&bundleDir).exists() {
+            return Err(Error::Common(format!(
+                "bundle {} of id {} already exists",
+                &bundleDir, &self.id
+            )));
+        }
+
+        let res = self.Prepare(&bundleDir, &rootfs);
+        let res = match res {
+            Err(e) => Err(e),
+            Ok(spec) => Container::Run(
+                &self.id, spec, gCfg, &bundleDir, "", "", "", false, true,
+            ),
+        };
+
+        if let Ok(mut container) = Container::Load(&gCfg.RootDir, &self.id) {
+            if let Err(e) = container.Destroy() {
+                error!("DoCmd: destroy container {} fail {:?}", &self.id, e);
+            }
+        }
+
+        if !self.keep {
+            fs::remove_dir_all(&bundleDir).ok();
+        }
+
+        let status = res?;
+        return Ok(WaitStatus(status as u32).ExitStatus());
+    }
+
+    // Prepare builds the rootfs and writes config.json into bundleDir.
+    fn Prepare(&self, bundleDir: &str, rootfs: &str) -> Result<Spec> {
+        let binary = LookPath(&self.cmd[0])?;
+
+        for dir in &["proc", "dev", "tmp", "etc", "sys"] {
+            MkdirAll(&Join(rootfs, dir))?;
+        }
+        fs::set_permissions(&Join(rootfs, "tmp"), fs::Permissions::from_mode(0o1777))
+            .map_err(|e| Error::IOError(format!("chmod /tmp fail {:?}", e)))?;
+
+        CopyIn(rootfs, &binary)?;
+        for lib in SharedLibs(&binary)? {
+            CopyIn(rootfs, &lib)?;
+        }
+
+        for file in &["/etc/passwd", "/etc/group", "/etc/hosts", "/etc/resolv.conf"] {
+            if fs::metadata(file).is_ok() {
+                CopyIn(rootfs, file)?;
+            }
+        }
+
+        let mut args = self.cmd.clone();
+        args[0] = binary;
+
+        let spec = self.Spec(args)?;
+        spec.save(&Join(bundleDir, "config.json"))
+            .map_err(|e| Error::IOError(format!("save config.json fail {:?}", e)))?;
+        return Ok(spec);
+    }
+
+    fn Spec(&self, args: Vec<String>) -> Result<Spec> {
+        let mut spec = Spec::default();
+        spec.version = "1.0.0".to_string();
+        spec.hostname = self.id.to_string();
+        spec.root.path = "rootfs".to_string();
+
+        spec.process.args = args;
+        spec.process.cwd = self.cwd.to_string();
+        spec.process.env.push(DEFAULT_PATH_ENV.to_string());
+        for e in &self.env {
+            if !e.contains('=') {
+                return Err(Error::Common(format!("invalid env {}, expect KEY=VALUE", e)));
+            }
+            spec.process.env.push(e.to_string());
+        }
+
+        spec.mounts.push(Mount {
+            destination: "/proc".to_string(),
+            typ: "proc".to_string(),
+            source: "proc".to_string(),
+            options: Vec::new(),
+        });
+        spec.mounts.push(Mount {
+            destination: "/dev".to_string(),
+            typ: "tmpfs".to_string(),
+            source: "tmpfs".to_string(),
+            options: vec!["nosuid".to_string(), "mode=755".to_string()],
+        });
+        spec.mounts.push(Mount {
+            destination: "/sys".to_string(),
+            typ: "sysfs".to_string(),
+            source: "sysfs".to_string(),
+            options: vec!["nosuid".to_string(), "ro".to_string()],
+        });
+
+        for v in &self.volumes {
+            let parts: Vec<&str> = v.split(':').collect();
+            let (src, dst, ro) = match parts.len() {
+                1 => (parts[0], parts[0], false),
+                2 => (parts[0], parts[1], false),
+                3 if parts[2] == "ro" => (parts[0], parts[1], true),
+                _ => return Err(Error::Common(format!("invalid volume {}", v))),
+            };
+
+            let src = fs::canonicalize(src)
+                .map_err(|e| Error::IOError(format!("volume {} fail {:?}", src, e)))?;
+            let mut options = vec!["rbind".to_string()];
+            if ro {
+                options.push("ro".to_string());
+            }
+
+            spec.mounts.push(Mount {
+                destination: dst.to_string(),
+                typ: "bind".to_string(),
+                source: src.to_string_lossy().to_string(),
+                options: options,
+            });
+        }
+
+        let mut linux = Linux::default();
+        for typ in &[
+            LinuxNamespaceType::pid,
+            LinuxNamespaceType::ipc,
+            LinuxNamespaceType::uts,
+            LinuxNamespaceType::mount,
+            LinuxNamespaceType::network,
+        ] {
+            linux.namespaces.push(LinuxNamespace {
+                typ: *typ,
+                path: "".to_string(),
+            });
+        }
+        spec.linux = Some(linux);
+
+        ValidateSpec(&spec)?;
+        return Ok(spec);
+    }
+}
+
+// LookPath resolves the binary the same way as the shell, a name without '/'
+// is searched in $PATH.
+fn LookPath(name: &str) -> Result<String> {
+    let executable = |path: &str| -> bool {
+        match fs::metadata(path) {
+            Ok(m) => m.is_file() && m.permissions().mode() & 0o111 != 0,
+            Err(_) => false,
+        }
+    };
+
+    if name.contains('/') {
+        let path = fs::canonicalize(name)
+            .map_err(|e| Error::IOError(format!("binary {} fail {:?}", name, e)))?;
+        let path = path.to_string_lossy().to_string();
+        if !executable(&path) {
+            return Err(Error::Common(format!("{} is not executable", name)));
+        }
+        return Ok(path);
+    }
+
+    let paths = env::var("PATH").unwrap_or_default();
+    for dir in paths.split(':') {
+        if dir.len() == 0 {
+            continue;
+        }
+
+        let path = Join(dir, name);
+        if executable(&path) {
+            return Ok(path);
+        }
+    }
+
+    return Err(Error::Common(format!("{} not found in $PATH", name)));
+}
+
+// SharedLibs returns the ELF interpreter and the shared libraries of the binary,
+// read from the PT_INTERP and the DT_NEEDED of the elf files, recursively. The
+// binary isn't run. A static binary or a script returns an empty list.
+fn SharedLibs(binary: &str) -> Result<Vec<String>> {
+    let root = OpenRoot("/")?;
+    let mut libs: Vec<String> = Vec::new();
+    let mut elfs = VecDeque::new();
+    elfs.push_back(binary.to_string());
+    while let Some(path) = elfs.pop_front() {
+        let file = match OpenInRoot(&root, &path) {
+            None => return Err(Error::Common(format!("open {} fail", path))),
+            Some(f) => f,
+        };
+
+        let (interp, needed, runpath) = match ElfDeps(&file) {
+            Err(_) => continue,
+            Ok(deps) => deps,
+        };
+
+        let mut deps = Vec::new();
+        if let Some(interp) = interp {
+            deps.push(interp);
+        }
+
+        for lib in &needed {
+            match ResolveLib(&root, &path, lib, &runpath) {
+                None => {
+                    return Err(Error::Common(format!(
+                        "{}: {} not found",
+                        path, lib
+                    )))
+                }
+                Some(lib) => deps.push(lib),
+            }
+        }
+
+        for dep in deps {
+            if !libs.contains(&dep) {
+                libs.push(dep.clone());
+                elfs.push_back(dep);
+            }
+        }
+    }
+
+    return Ok(libs);
+}
+
+// CopyIn copies the host file into the same path under rootfs. Symlinks are
+// followed so the rootfs only has regular files.
+fn CopyIn(rootfs: &str, path: &str) -> Result<()> {
+    let dst = Join(rootfs, path);
+    MkdirAll(&Dir(&dst))?;
+    fs::copy(path, &dst)
+        .map_err(|e| Error::IOError(format!("copy {} to {} fail {:?}", path, dst, e)))?;
+    return Ok(());
+}
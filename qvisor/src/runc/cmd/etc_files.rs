@@ -0,0 +1,137 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::fs;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::loader::EtcFiles;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::super::runtime::etc_files::*;
+use super::command::*;
+
+#[derive(Debug)]
+pub struct EtcFilesCmd {
+    pub id: String,
+    pub files: EtcFiles,
+}
+
+fn ReadFile(path: Option<&str>) -> Result<Option<String>> {
+    match path {
+        None => return Ok(None),
+        Some(p) => {
+            let content = fs::read_to_string(p)
+                .map_err(|e| Error::IOError(format!("read {} fail {:?}", p, e)))?;
+            return Ok(Some(content));
+        }
+    }
+}
+
+fn List<'a>(cmd_matches: &'a ArgMatches, name: &str) -> Vec<&'a str> {
+    match cmd_matches.value_of(name) {
+        None => return Vec::new(),
+        Some(v) => {
+            return v
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| s.len() > 0)
+                .collect();
+        }
+    }
+}
+
+impl EtcFilesCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        let nameservers = List(cmd_matches, "nameservers");
+        let resolvConf = if nameservers.len() > 0 {
+            if cmd_matches.value_of("resolv-conf").is_some() {
+                return Err(Error::Common(
+                    "resolv-conf and nameservers can't be set together".to_string(),
+                ));
+            }
+
+            Some(ResolvConf(
+                &nameservers,
+                &List(cmd_matches, "searches"),
+                &List(cmd_matches, "options"),
+            ))
+        } else {
+            ReadFile(cmd_matches.value_of("resolv-conf"))?
+        };
+
+        let files = EtcFiles {
+            ResolvConf: resolvConf,
+            Hosts: ReadFile(cmd_matches.value_of("hosts"))?,
+            Hostname: cmd_matches.value_of("hostname").map(|h| format!("{}\n", h)),
+            MachineId: None,
+        };
+
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            files: files,
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("etc-files")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("resolv-conf")
+                    .help("host file with the new /etc/resolv.conf")
+                    .takes_value(true)
+                    .long("resolv-conf"),
+            )
+            .arg(
+                Arg::with_name("nameservers")
+                    .help("comma separated nameservers of the new /etc/resolv.conf")
+                    .takes_value(true)
+                    .long("nameservers"),
+            )
+            .arg(
+                Arg::with_name("searches")
+                    .help("comma separated search domains of the new /etc/resolv.conf")
+                    .takes_value(true)
+                    .long("searches"),
+            )
+            .arg(
+                Arg::with_name("options")
+                    .help("comma separated resolver options of the new /etc/resolv.conf")
+                    .takes_value(true)
+                    .long("options"),
+            )
+            .arg(
+                Arg::with_name("hosts")
+                    .help("host file with the new /etc/hosts")
+                    .takes_value(true)
+                    .long("hosts"),
+            )
+            .arg(
+                Arg::with_name("hostname")
+                    .help("the new /etc/hostname")
+                    .takes_value(true)
+                    .long("hostname"),
+            )
+            .about("etc-files updates the virtual /etc files of a running sandbox");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let container = Container::Load(&gCfg.RootDir, &self.id)?;
+        container.UpdateEtcFiles(&self.files)?;
+        return Ok(());
+    }
+}
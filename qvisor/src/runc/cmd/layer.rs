@@ -0,0 +1,76 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use super::super::super::qlib::common::*;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+#[derive(Debug)]
+pub struct LayerCmd {
+    pub id: String,
+    pub action: String,
+    pub name: String,
+}
+
+impl LayerCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        let action = cmd_matches.value_of("action").unwrap().to_string();
+        let name = cmd_matches.value_of("name").unwrap_or("").to_string();
+        if action != "list" && name.len() == 0 {
+            return Err(Error::Common(format!(
+                "layer {} needs a snapshot name",
+                action
+            )));
+        }
+
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            action: action,
+            name: name,
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("layer")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("action")
+                    .required(true)
+                    .possible_values(&["snapshot", "rollback", "list"])
+                    .help("snapshot or roll back the writable layer, or list its snapshots"),
+            )
+            .arg(Arg::with_name("name").help("the name of the snapshot"))
+            .about("layer snapshots the writable layer of the root filesystem of a container and rolls it back");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let mut container = Container::Load(&gCfg.RootDir, &self.id)?;
+        match self.action.as_str() {
+            "snapshot" => container.SnapshotLayer(&self.name)?,
+            "rollback" => container.RollbackLayer(&self.name)?,
+            _ => {
+                for name in container.LayerSnapshots()? {
+                    println!("{}", name);
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
@@ -18,13 +18,21 @@ pub mod command;
 pub mod config;
 pub mod create;
 pub mod delete;
+pub mod do_cmd;
+pub mod etc_files;
 pub mod exec;
 pub mod kill;
+pub mod layer;
 pub mod list;
+pub mod netstat;
 pub mod pause;
 pub mod ps;
+pub mod reconfig;
+pub mod resources;
 pub mod resume;
 pub mod run;
+pub mod selftest;
 pub mod start;
 pub mod state;
+pub mod top;
 pub mod wait;
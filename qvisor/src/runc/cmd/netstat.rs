@@ -0,0 +1,84 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use clap::{App, AppSettings, ArgMatches, SubCommand};
+use std::io::Write;
+use tabwriter::TabWriter;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::control_msg::*;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+#[derive(Debug)]
+pub struct NetstatCmd {
+    pub id: String,
+    pub json: bool,
+}
+
+impl NetstatCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            json: match cmd_matches.value_of("format").unwrap() {
+                "table" => false,
+                "json" => true,
+                _ => return Err(Error::Common("invalid format option".to_string())),
+            },
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("netstat")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(&common.format_arg)
+            .about("netstat displays the sockets opened by the processes inside a container");
+    }
+
+    pub fn Run(&mut self, gCfg: &GlobalConfig) -> Result<()> {
+        let container = Container::Load(&gCfg.RootDir, &self.id)?;
+        let sockets = container.Sockets()?;
+
+        if self.json {
+            let str = serde_json::to_string(&sockets)
+                .map_err(|e| Error::Common(format!("netstat ser error is {:?}", e)))?;
+            println!("{}", str);
+        } else {
+            PrintSocketsToTable(&sockets);
+        }
+
+        return Ok(());
+    }
+}
+
+pub fn PrintSocketsToTable(sockets: &[SocketInfo]) {
+    let mut tw = TabWriter::new(vec![]).minwidth(10).padding(3);
+
+    write!(&mut tw, "PID\tFD\tFAMILY\tTYPE\tLOCAL ADDRESS\tPEER ADDRESS\n").unwrap();
+    for s in sockets {
+        write!(
+            &mut tw,
+            "{}\t{}\t{}\t{}\t{}\t{}\n",
+            s.PID, s.FD, s.Family, s.Type, s.LocalAddr, s.PeerAddr
+        )
+        .unwrap();
+    }
+    tw.flush().unwrap();
+
+    let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+    println!("{}", written);
+}
@@ -83,12 +83,21 @@ impl PsCmd {
 pub fn PrintProcessListToTable(pl: &[ProcessInfo]) {
     let mut tw = TabWriter::new(vec![]).minwidth(10).padding(3);
 
-    write!(&mut tw, "UID\tPID\tPPID\tC\tSTIME\tTIME\tCMD\n").unwrap();
+    write!(&mut tw, "UID\tPID\tPPID\tC\tSTIME\tTIME\tNLWP\tVSZ\tRSS\tCMD\n").unwrap();
     for d in pl {
         write!(
             &mut tw,
-            "\n{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
-            d.UID.0, d.PID, d.PPID, d.Utilization, d.STime, d.Time, d.Cmd
+            "\n{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            d.UID.0,
+            d.PID,
+            d.PPID,
+            d.Utilization,
+            d.STime,
+            d.Time,
+            d.Threads,
+            d.VSZ / 1024,
+            d.RSS / 1024,
+            d.Cmd
         )
         .unwrap();
     }
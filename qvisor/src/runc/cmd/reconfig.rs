@@ -0,0 +1,219 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::config;
+use super::super::super::qlib::config::ConfigUpdate;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+#[derive(Debug)]
+pub struct ReconfigCmd {
+    pub id: String,
+    pub update: ConfigUpdate,
+}
+
+fn ParseBool(name: &str, val: Option<&str>) -> Result<Option<bool>> {
+    match val {
+        None => return Ok(None),
+        Some("true") => return Ok(Some(true)),
+        Some("false") => return Ok(Some(false)),
+        Some(v) => return Err(Error::Common(format!("invalid {} value {}", name, v))),
+    }
+}
+
+fn ParseU64(name: &str, val: Option<&str>) -> Result<Option<u64>> {
+    match val {
+        None => return Ok(None),
+        Some(v) => match v.parse::<u64>() {
+            Ok(n) => return Ok(Some(n)),
+            Err(_) => return Err(Error::Common(format!("invalid {} value {}", name, v))),
+        },
+    }
+}
+
+fn ParsePercent(name: &str, val: Option<&str>) -> Result<Option<u64>> {
+    match ParseU64(name, val)? {
+        Some(v) if v > 100 => {
+            return Err(Error::Common(format!("invalid {} value {}", name, v)))
+        }
+        v => return Ok(v),
+    }
+}
+
+fn ParseDebugLevel(val: Option<&str>) -> Result<Option<config::DebugLevel>> {
+    let level = match val {
+        None => return Ok(None),
+        Some("off") => config::DebugLevel::Off,
+        Some("error") => config::DebugLevel::Error,
+        Some("warn") => config::DebugLevel::Warn,
+        Some("info") => config::DebugLevel::Info,
+        Some("debug") => config::DebugLevel::Debug,
+        Some("trace") => config::DebugLevel::Trace,
+        Some(v) => return Err(Error::Common(format!("invalid debug-level value {}", v))),
+    };
+
+    return Ok(Some(level));
+}
+
+fn ParseLogLevel(val: Option<&str>) -> Result<Option<config::LogLevel>> {
+    let level = match val {
+        None => return Ok(None),
+        Some("none") => config::LogLevel::None,
+        Some("simple") => config::LogLevel::Simple,
+        Some("complex") => config::LogLevel::Complex,
+        Some(v) => return Err(Error::Common(format!("invalid log-level value {}", v))),
+    };
+
+    return Ok(Some(level));
+}
+
+impl ReconfigCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        let update = ConfigUpdate {
+            DebugLevel: ParseDebugLevel(cmd_matches.value_of("debug-level"))?,
+            LogLevel: ParseLogLevel(cmd_matches.value_of("log-level"))?,
+            PerfDebug: ParseBool("perf-debug", cmd_matches.value_of("perf-debug"))?,
+            PrintException: ParseBool("print-exception", cmd_matches.value_of("print-exception"))?,
+            ReaddirCache: ParseBool("readdir-cache", cmd_matches.value_of("readdir-cache"))?,
+            PosixAcl: ParseBool("posix-acl", cmd_matches.value_of("posix-acl"))?,
+            MemoryHigh: ParseU64("memory-high", cmd_matches.value_of("memory-high"))?,
+            HaltPollNs: ParseU64("halt-poll-ns", cmd_matches.value_of("halt-poll-ns"))?,
+            HaltPollGrow: ParseU64("halt-poll-grow", cmd_matches.value_of("halt-poll-grow"))?,
+            HaltPollShrink: ParseU64("halt-poll-shrink", cmd_matches.value_of("halt-poll-shrink"))?,
+            UringWaitUs: ParseU64("uring-wait-us", cmd_matches.value_of("uring-wait-us"))?,
+            DirtyBackgroundRatio: ParsePercent(
+                "dirty-background-ratio",
+                cmd_matches.value_of("dirty-background-ratio"),
+            )?,
+            DirtyRatio: ParsePercent("dirty-ratio", cmd_matches.value_of("dirty-ratio"))?,
+            Ksm: ParseBool("ksm", cmd_matches.value_of("ksm"))?,
+            KsmAnon: ParseBool("ksm-anon", cmd_matches.value_of("ksm-anon"))?,
+        };
+
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            update: update,
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("reconfig")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("debug-level")
+                    .help("off, error, warn, info, debug or trace")
+                    .takes_value(true)
+                    .long("debug-level"),
+            )
+            .arg(
+                Arg::with_name("log-level")
+                    .help("none, simple or complex")
+                    .takes_value(true)
+                    .long("log-level"),
+            )
+            .arg(
+                Arg::with_name("perf-debug")
+                    .help("enable the perf metrics: true or false")
+                    .takes_value(true)
+                    .long("perf-debug"),
+            )
+            .arg(
+                Arg::with_name("print-exception")
+                    .help("true or false")
+                    .takes_value(true)
+                    .long("print-exception"),
+            )
+            .arg(
+                Arg::with_name("readdir-cache")
+                    .help("true or false")
+                    .takes_value(true)
+                    .long("readdir-cache"),
+            )
+            .arg(
+                Arg::with_name("posix-acl")
+                    .help("true or false")
+                    .takes_value(true)
+                    .long("posix-acl"),
+            )
+            .arg(
+                Arg::with_name("memory-high")
+                    .help("memory.high of the sandbox in MB, 0 to disable")
+                    .takes_value(true)
+                    .long("memory-high"),
+            )
+            .arg(
+                Arg::with_name("halt-poll-ns")
+                    .help("the longest halt polling of an idle vcpu in ns, 0 to disable")
+                    .takes_value(true)
+                    .long("halt-poll-ns"),
+            )
+            .arg(
+                Arg::with_name("halt-poll-grow")
+                    .help("the growth factor of the halt polling after a short sleep")
+                    .takes_value(true)
+                    .long("halt-poll-grow"),
+            )
+            .arg(
+                Arg::with_name("halt-poll-shrink")
+                    .help("the shrink divisor of the halt polling after a long sleep, 0 to reset it")
+                    .takes_value(true)
+                    .long("halt-poll-shrink"),
+            )
+            .arg(
+                Arg::with_name("uring-wait-us")
+                    .help("the wait of the idle io thread for a completion in us, 0 to poll")
+                    .takes_value(true)
+                    .long("uring-wait-us"),
+            )
+            .arg(
+                Arg::with_name("dirty-background-ratio")
+                    .help("the percent of the memory dirty at which the writeback starts")
+                    .takes_value(true)
+                    .long("dirty-background-ratio"),
+            )
+            .arg(
+                Arg::with_name("dirty-ratio")
+                    .help("the percent of the memory dirty at which the writers are throttled")
+                    .takes_value(true)
+                    .long("dirty-ratio"),
+            )
+            .arg(
+                Arg::with_name("ksm")
+                    .help("merge the guest copies of the image file pages: true or false")
+                    .takes_value(true)
+                    .long("ksm"),
+            )
+            .arg(
+                Arg::with_name("ksm-anon")
+                    .help("merge the guest anonymous pages too: true or false")
+                    .takes_value(true)
+                    .long("ksm-anon"),
+            )
+            .about("reconfig updates the runtime configuration of a running sandbox");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let container = Container::Load(&gCfg.RootDir, &self.id)?;
+        let config = container.UpdateConfig(&self.update)?;
+
+        println!("{:#?}", config);
+        return Ok(());
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::control_msg::*;
+use super::super::super::qlib::limits::*;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+// the resource names of prlimit(1)
+const LIMIT_NAMES: &[(&str, LimitType)] = &[
+    ("as", LimitType::AS),
+    ("core", LimitType::Core),
+    ("cpu", LimitType::CPU),
+    ("data", LimitType::Data),
+    ("fsize", LimitType::FileSize),
+    ("locks", LimitType::Locks),
+    ("memlock", LimitType::MemoryLocked),
+    ("msgqueue", LimitType::MessageQueueBytes),
+    ("nice", LimitType::Nice),
+    ("nofile", LimitType::NumberOfFiles),
+    ("nproc", LimitType::ProcessCount),
+    ("rss", LimitType::Rss),
+    ("rtprio", LimitType::RealTimePriority),
+    ("rttime", LimitType::Rttime),
+    ("sigpending", LimitType::SignalsPending),
+    ("stack", LimitType::Stack),
+];
+
+#[derive(Debug)]
+pub struct ResourcesCmd {
+    pub id: String,
+    pub args: ProcessResourcesArgs,
+}
+
+fn ParseLimitValue(v: &str) -> Result<u64> {
+    if v == "unlimited" || v == "infinity" {
+        return Ok(INFINITY);
+    }
+
+    return v
+        .parse::<u64>()
+        .map_err(|_| Error::Common(format!("invalid limit value {}", v)));
+}
+
+// ParseLimit parses "<resource>=<soft>:<hard>", a single value sets both.
+fn ParseLimit(val: &str) -> Result<(LimitType, Limit)> {
+    let (name, value) = match val.find('=') {
+        None => return Err(Error::Common(format!("invalid limit {}", val))),
+        Some(i) => (&val[..i], &val[i + 1..]),
+    };
+
+    let resource = match LIMIT_NAMES.iter().find(|(n, _)| *n == name) {
+        None => return Err(Error::Common(format!("unknown resource {}", name))),
+        Some((_, r)) => *r,
+    };
+
+    let limit = match value.find(':') {
+        None => {
+            let v = ParseLimitValue(value)?;
+            Limit { Cur: v, Max: v }
+        }
+        Some(i) => Limit {
+            Cur: ParseLimitValue(&value[..i])?,
+            Max: ParseLimitValue(&value[i + 1..])?,
+        },
+    };
+
+    return Ok((resource, limit));
+}
+
+fn ParseI32(name: &str, val: Option<&str>) -> Result<Option<i32>> {
+    match val {
+        None => return Ok(None),
+        Some(v) => match v.parse::<i32>() {
+            Ok(n) => return Ok(Some(n)),
+            Err(_) => return Err(Error::Common(format!("invalid {} value {}", name, v))),
+        },
+    }
+}
+
+fn LimitString(v: u64) -> String {
+    if v == INFINITY {
+        return "unlimited".to_string();
+    }
+
+    return format!("{}", v);
+}
+
+impl ResourcesCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        let mut limits = Vec::new();
+        if let Some(vals) = cmd_matches.values_of("limit") {
+            for v in vals {
+                limits.push(ParseLimit(v)?);
+            }
+        }
+
+        let args = ProcessResourcesArgs {
+            cid: String::new(),
+            pid: ParseI32("pid", cmd_matches.value_of("pid"))?.unwrap_or(0),
+            limits: limits,
+            nice: ParseI32("nice", cmd_matches.value_of("nice"))?,
+            oomScoreAdj: ParseI32("oom-score-adj", cmd_matches.value_of("oom-score-adj"))?,
+        };
+
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            args: args,
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("resources")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("pid")
+                    .help("the process ID in the container, defaults to the container init process")
+                    .takes_value(true)
+                    .long("pid"),
+            )
+            .arg(
+                Arg::with_name("limit")
+                    .help("set a rlimit as prlimit(1), e.g. nofile=1024:4096 or cpu=unlimited, only the ones setrlimit(2) can set")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .long("limit"),
+            )
+            .arg(
+                Arg::with_name("nice")
+                    .help("set the nice value of the process")
+                    .takes_value(true)
+                    .allow_hyphen_values(true)
+                    .long("nice"),
+            )
+            .arg(
+                Arg::with_name("oom-score-adj")
+                    .help("set the oom_score_adj of the process, -1000 to 1000")
+                    .takes_value(true)
+                    .allow_hyphen_values(true)
+                    .long("oom-score-adj"),
+            )
+            .about("resources gets or sets the rlimits, nice and oom_score_adj of a process in a running container");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let container = Container::Load(&gCfg.RootDir, &self.id)?;
+        let resources = container.ProcessResources(&self.args)?;
+
+        println!("PID: {}", resources.pid);
+        println!("NICE: {}", resources.nice);
+        println!("OOM_SCORE_ADJ: {}", resources.oomScoreAdj);
+        println!("{:<12} {:>20} {:>20}", "RESOURCE", "SOFT", "HARD");
+        for (name, resource) in LIMIT_NAMES {
+            let limit = match resources.limits.iter().find(|(r, _)| r == resource) {
+                None => Limit::default(),
+                Some((_, l)) => *l,
+            };
+            println!(
+                "{:<12} {:>20} {:>20}",
+                name,
+                LimitString(limit.Cur),
+                LimitString(limit.Max)
+            );
+        }
+
+        return Ok(());
+    }
+}
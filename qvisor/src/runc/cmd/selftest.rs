@@ -0,0 +1,171 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process;
+use tabwriter::TabWriter;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::path::*;
+use super::super::cmd::config::*;
+use super::command::*;
+use super::do_cmd::*;
+
+// The suite binary installed next to quark, see src/selftest/main.rs.
+pub const SELFTEST_SUITE: &str = "quark-selftest";
+
+const RESULT_DIR: &str = "/selftest";
+
+// SelftestCmd validates the node before workloads are scheduled onto it: it
+// boots a sandbox with the installed quark and runs the conformance suite in
+// it as DoCmd does, then reports the checks which failed and the features the
+// sandbox doesn't support. The command fails when a check fails, or when a
+// feature is unsupported with --strict.
+#[derive(Debug)]
+pub struct SelftestCmd {
+    pub suite: String,
+    pub strict: bool,
+    pub keep: bool,
+}
+
+impl SelftestCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        let suite = match cmd_matches.value_of("suite") {
+            Some(suite) => suite.to_string(),
+            None => {
+                let exe = env::current_exe()
+                    .map_err(|e| Error::IOError(format!("current exe fail {:?}", e)))?;
+                Join(&Dir(&exe.to_string_lossy()), SELFTEST_SUITE)
+            }
+        };
+
+        return Ok(Self {
+            suite: suite,
+            strict: cmd_matches.is_present("strict"),
+            keep: cmd_matches.is_present("keep"),
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(_common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("selftest")
+            .setting(AppSettings::ColoredHelp)
+            .arg(
+                Arg::with_name("suite")
+                    .help("the conformance suite binary, defaults to quark-selftest next to quark")
+                    .takes_value(true)
+                    .long("suite"),
+            )
+            .arg(
+                Arg::with_name("strict")
+                    .help("fail when a feature is unsupported as well")
+                    .long("strict"),
+            )
+            .arg(
+                Arg::with_name("keep")
+                    .help("keep the generated bundle and the results")
+                    .long("keep"),
+            )
+            .about("Run the syscall and netstack conformance suite in a sandbox");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let id = format!("selftest-{}", process::id());
+        let resultDir = Join(
+            &env::temp_dir().to_string_lossy(),
+            &format!("quark-{}-results", id),
+        );
+        fs::create_dir_all(&resultDir)
+            .map_err(|e| Error::IOError(format!("create {} fail {:?}", resultDir, e)))?;
+
+        let doCmd = DoCmd {
+            id: id,
+            cwd: "/".to_string(),
+            env: Vec::new(),
+            volumes: vec![format!("{}:{}", resultDir, RESULT_DIR)],
+            keep: self.keep,
+            cmd: vec![self.suite.to_string(), Join(RESULT_DIR, "results")],
+        };
+
+        let status = doCmd.Exec(gCfg);
+        let results = fs::read_to_string(&Join(&resultDir, "results"));
+        if !self.keep {
+            fs::remove_dir_all(&resultDir).ok();
+        }
+
+        let status = status?;
+        let results = match results {
+            Ok(r) => r,
+            Err(e) => {
+                return Err(Error::Common(format!(
+                    "selftest suite exited with {} without results {:?}",
+                    status, e
+                )))
+            }
+        };
+
+        let (failed, unsupported) = self.Report(&results);
+        // the suite exits with 1 when a check fails, other exits are crashes
+        if status != 0 && status != 1 {
+            return Err(Error::Common(format!(
+                "selftest suite exited with {} after {} checks",
+                status,
+                results.lines().count()
+            )));
+        }
+
+        if failed > 0 || (self.strict && unsupported > 0) {
+            process::exit(1);
+        }
+
+        return Ok(());
+    }
+
+    // Report prints the results and returns the number of the failed and the
+    // unsupported checks.
+    fn Report(&self, results: &str) -> (usize, usize) {
+        let mut tw = TabWriter::new(vec![]).minwidth(12).padding(3);
+        write!(&mut tw, "CHECK\tSTATUS\tDETAIL\n").unwrap();
+
+        let (mut passed, mut failed, mut unsupported) = (0, 0, 0);
+        for line in results.lines() {
+            let fields: Vec<&str> = line.splitn(3, '\t').collect();
+            if fields.len() != 3 {
+                continue;
+            }
+
+            match fields[0] {
+                "pass" => passed += 1,
+                "fail" => failed += 1,
+                _ => unsupported += 1,
+            }
+
+            write!(tw, "{}\t{}\t{}\n", fields[1], fields[0], fields[2]).unwrap();
+        }
+
+        tw.flush().unwrap();
+
+        let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+        println!("{}", written);
+        println!(
+            "{} passed, {} failed, {} unsupported",
+            passed, failed, unsupported
+        );
+        return (failed, unsupported);
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::io::Write;
+use std::{thread, time};
+use tabwriter::TabWriter;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::control_msg::*;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+#[derive(Debug)]
+pub struct TopCmd {
+    pub id: String,
+    pub interval: u64,
+    pub iterations: u64,
+}
+
+impl TopCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        let interval = cmd_matches
+            .value_of("interval")
+            .unwrap()
+            .parse::<u64>()
+            .map_err(|e| Error::Common(format!("invalid interval {:?}", e)))?;
+        let iterations = cmd_matches
+            .value_of("iterations")
+            .unwrap()
+            .parse::<u64>()
+            .map_err(|e| Error::Common(format!("invalid iterations {:?}", e)))?;
+
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            interval: interval,
+            iterations: iterations,
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("top")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("interval")
+                    .help("sample interval in ms")
+                    .default_value("1000")
+                    .takes_value(true)
+                    .long("interval")
+                    .short("i"),
+            )
+            .arg(
+                Arg::with_name("iterations")
+                    .help("number of samples, 0 means forever")
+                    .default_value("1")
+                    .takes_value(true)
+                    .long("iterations")
+                    .short("n"),
+            )
+            .about("top displays the state and cpu usage of the tasks running inside a container");
+    }
+
+    pub fn Run(&mut self, gCfg: &GlobalConfig) -> Result<()> {
+        let container = Container::Load(&gCfg.RootDir, &self.id)?;
+
+        let mut prev = CpuTimes(&container.Tasks()?);
+        let mut count = 0;
+        loop {
+            thread::sleep(time::Duration::from_millis(self.interval));
+            let tasks = container.Tasks()?;
+            PrintTasksToTable(&tasks, &prev, self.interval);
+            prev = CpuTimes(&tasks);
+
+            count += 1;
+            if self.iterations != 0 && count >= self.iterations {
+                break;
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+fn CpuTimes(tasks: &[TaskInfo]) -> BTreeMap<i32, i64> {
+    let mut ret = BTreeMap::new();
+    for t in tasks {
+        ret.insert(t.TID, t.UserTime + t.SysTime);
+    }
+
+    return ret;
+}
+
+pub fn PrintTasksToTable(tasks: &[TaskInfo], prev: &BTreeMap<i32, i64>, intervalMs: u64) {
+    let mut tw = TabWriter::new(vec![]).minwidth(10).padding(3);
+
+    write!(&mut tw, "PID\tTID\tSTATE\t%CPU\tUSER(ms)\tSYS(ms)\tCMD\n").unwrap();
+    for t in tasks {
+        let now = t.UserTime + t.SysTime;
+        let before = prev.get(&t.TID).copied().unwrap_or(now);
+        let cpu = (now - before) as f64 * 100.0 / (intervalMs as f64 * 1_000_000.0);
+        write!(
+            &mut tw,
+            "{}\t{}\t{}\t{:.1}\t{}\t{}\t{}\n",
+            t.PID,
+            t.TID,
+            t.State,
+            cpu,
+            t.UserTime / 1_000_000,
+            t.SysTime / 1_000_000,
+            t.Cmd
+        )
+        .unwrap();
+    }
+    tw.flush().unwrap();
+
+    let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+    println!("{}", written);
+}
@@ -27,19 +27,25 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use super::super::super::qlib::auth::cap_set::*;
 use super::super::super::qlib::auth::id::*;
 use super::super::super::qlib::common::*;
+use super::super::super::qlib::config::{Config, ConfigUpdate};
 use super::super::super::qlib::control_msg::*;
 use super::super::super::qlib::linux_def::*;
+use super::super::super::qlib::loader::EtcFiles;
 use super::super::super::qlib::path::*;
 use super::super::super::ucall::ucall::*;
+use super::super::super::QUARK_CONFIG;
 //use super::super::super::qlib::util::*;
 use super::super::cgroup::*;
 use super::super::cmd::config::*;
 use super::super::cmd::exec::*;
 use super::super::oci::serialize::*;
 use super::super::oci::*;
+use super::super::runtime::block_volume::*;
+use super::super::runtime::cdi::*;
 use super::super::sandbox::sandbox::*;
 use super::super::specutils::specutils::*;
 use super::hook::*;
+use super::layer::*;
 use super::status::*;
 
 use super::super::shim::container_io::*;
@@ -101,6 +107,11 @@ pub struct Container {
     // root container, this is the same as Root.
     #[serde(default)]
     pub RootContainerDir: String,
+
+    // LoopDevices are the loop devices the disk images of the container are
+    // attached to, with their images.
+    #[serde(default)]
+    pub LoopDevices: Vec<(String, String)>,
 }
 
 // List returns all container ids in the given root directory.
@@ -425,7 +436,7 @@ impl Container {
     pub fn Create(
         id: &str,
         action: RunAction,
-        spec: Spec,
+        mut spec: Spec,
         conf: &GlobalConfig,
         bundleDir: &str,
         consoleSocket: &str,
@@ -439,6 +450,7 @@ impl Container {
         debug!("spec for creating container: {:?}", &spec);
         //debug!("container spec is {:?}", &spec);
         ValidateID(id)?;
+        ApplyCdiDevices(&mut spec)?;
 
         Self::CheckTerminal(action, spec.process.terminal, consoleSocket, detach)?;
 
@@ -479,8 +491,11 @@ impl Container {
                 Owner: user,
                 Sandbox: None,
                 RootContainerDir: conf.RootDir.to_string(),
+                LoopDevices: Vec::new(),
             };
 
+            c.LoopDevices = ApplyBlockVolumes(&mut c.Spec)?;
+
             // If the metadata annotations indicate that this container should be
             // started in an existing sandbox, we must do so. The metadata will
             // indicate the ID of the sandbox, which is the same as the ID of the
@@ -622,7 +637,7 @@ impl Container {
     pub fn Create1(
         id: &str,
         action: RunAction,
-        spec: Spec,
+        mut spec: Spec,
         conf: &GlobalConfig,
         bundleDir: &str,
         userlog: &str,
@@ -632,6 +647,7 @@ impl Container {
         info!("Create container {} in root dir: {}", id, &conf.RootDir);
         //debug!("container spec is {:?}", &spec);
         ValidateID(id)?;
+        ApplyCdiDevices(&mut spec)?;
 
         let _unlockRoot = maybeLockRootContainer(&spec, &conf.RootDir)?;
 
@@ -670,8 +686,11 @@ impl Container {
                 Owner: user,
                 Sandbox: None,
                 RootContainerDir: conf.RootDir.to_string(),
+                LoopDevices: Vec::new(),
             };
 
+            c.LoopDevices = ApplyBlockVolumes(&mut c.Spec)?;
+
             // If the metadata annotations indicate that this container should be
             // started in an existing sandbox, we must do so. The metadata will
             // indicate the ID of the sandbox, which is the same as the ID of the
@@ -712,7 +731,9 @@ impl Container {
                     None => None,
                 };
 
-                let ret = Sandbox::New1(id, action, conf, bundleDir, io, userlog, cg, pivot);
+                let ret = Sandbox::New1(
+                    id, action, &c.Spec, conf, bundleDir, io, userlog, cg, pivot,
+                );
 
                 c.Sandbox = match ret {
                     Err(e) => {
@@ -848,7 +869,25 @@ impl Container {
 
         self.RequireStatus("Pause", &[Status::Running])?;
 
-        self.Sandbox.as_ref().unwrap().Pause(&self.ID)?;
+        // the rdma_srv keeps writing the socket buffers of the sandbox, its io
+        // can't be quiesced
+        if QUARK_CONFIG.lock().EnableRDMA {
+            return Err(Error::Common(format!(
+                "container {} can't be paused with rdma enabled",
+                self.ID
+            )));
+        }
+
+        // the sandbox quiesces its io before its cgroup is frozen, so that no
+        // host io is left half done till the thaw
+        let sandbox = self.Sandbox.as_ref().unwrap();
+        sandbox.Pause(&self.ID)?;
+        if let Some(cgroup) = &sandbox.Cgroup {
+            if let Err(e) = cgroup.Freeze(true) {
+                sandbox.Unpause(&self.ID)?;
+                return Err(e);
+            }
+        }
         self.changeStatus(Status::Paused);
         return self.Save();
     }
@@ -860,16 +899,138 @@ impl Container {
 
         self.RequireStatus("Resume", &[Status::Paused])?;
 
-        self.Sandbox.as_ref().unwrap().Unpause(&self.ID)?;
+        let sandbox = self.Sandbox.as_ref().unwrap();
+        if let Some(cgroup) = &sandbox.Cgroup {
+            cgroup.Freeze(false)?;
+        }
+        sandbox.Unpause(&self.ID)?;
         self.changeStatus(Status::Running);
         return self.Save();
     }
 
+    // UpdateConfig reloads the runtime reloadable config of the sandbox.
+    pub fn UpdateConfig(&self, update: &ConfigUpdate) -> Result<Config> {
+        self.RequireStatus("update config of", &[Status::Running, Status::Paused])?;
+        return self.Sandbox.as_ref().unwrap().UpdateConfig(update);
+    }
+
+    // UpdateEtcFiles replaces the content of the virtual /etc files of the sandbox.
+    pub fn UpdateEtcFiles(&self, files: &EtcFiles) -> Result<()> {
+        self.RequireStatus("update /etc files of", &[Status::Running, Status::Paused])?;
+        return self.Sandbox.as_ref().unwrap().UpdateEtcFiles(files);
+    }
+
+    // ProcessResources gets and sets the rlimits, the nice value and the
+    // oom_score_adj of a process in the container.
+    pub fn ProcessResources(&self, args: &ProcessResourcesArgs) -> Result<ProcessResources> {
+        self.RequireStatus("get resources of", &[Status::Running, Status::Paused])?;
+        let mut args = args.clone();
+        args.cid = self.ID.clone();
+        return self.Sandbox.as_ref().unwrap().ProcessResources(&args);
+    }
+
     pub fn Processes(&self) -> Result<Vec<ProcessInfo>> {
         self.RequireStatus("get processes of", &[Status::Running, Status::Paused])?;
         return self.Sandbox.as_ref().unwrap().Processes(&self.ID);
     }
 
+    pub fn Tasks(&self) -> Result<Vec<TaskInfo>> {
+        self.RequireStatus("get tasks of", &[Status::Running, Status::Paused])?;
+        return self.Sandbox.as_ref().unwrap().Tasks(&self.ID);
+    }
+
+    pub fn Sockets(&self) -> Result<Vec<SocketInfo>> {
+        self.RequireStatus("get sockets of", &[Status::Running, Status::Paused])?;
+        return self.Sandbox.as_ref().unwrap().Sockets(&self.ID);
+    }
+
+    // RootfsPath returns the host path of the root filesystem of the container.
+    fn RootfsPath(&self) -> String {
+        if IsAbs(&self.Spec.root.path) {
+            return self.Spec.root.path.to_string();
+        }
+
+        return Join(&self.BundleDir, &self.Spec.root.path);
+    }
+
+    fn LayerSnapshotDir(&self, name: &str) -> Result<String> {
+        ValidSnapshotName(name)?;
+        return Ok(Join(&Join(&self.Root, LAYER_SNAPSHOTS_DIR), name));
+    }
+
+    // WhilePaused runs f with the container paused if it is running.
+    fn WhilePaused<F: FnOnce(&Self) -> Result<()>>(&mut self, f: F) -> Result<()> {
+        let running = self.Status == Status::Running;
+        if running {
+            self.Pause()?;
+        }
+
+        let res = f(self);
+        if running {
+            self.Resume()?;
+        }
+
+        return res;
+    }
+
+    // SnapshotLayer copies the writable layer of the root filesystem of the
+    // container to the snapshot name, the container is paused meanwhile.
+    pub fn SnapshotLayer(&mut self, name: &str) -> Result<()> {
+        info!("Snapshot the layer of container {} to {}", self.ID, name);
+        self.RequireStatus(
+            "snapshot the layer of",
+            &[Status::Created, Status::Running, Status::Paused],
+        )?;
+
+        let dir = self.LayerSnapshotDir(name)?;
+        let rootfs = self.RootfsPath();
+        return self.WhilePaused(|_| SnapshotLayer(&rootfs, &dir));
+    }
+
+    // RollbackLayer restores the writable layer of the root filesystem of the
+    // container to the snapshot name. A running container is paused
+    // meanwhile, the guest drops the dirents it looked up in the old layer
+    // before the layer is swapped and after.
+    pub fn RollbackLayer(&mut self, name: &str) -> Result<()> {
+        info!("Roll back the layer of container {} to {}", self.ID, name);
+        self.RequireStatus(
+            "roll back the layer of",
+            &[
+                Status::Created,
+                Status::Running,
+                Status::Paused,
+                Status::Stopped,
+            ],
+        )?;
+
+        let dir = self.LayerSnapshotDir(name)?;
+        let rootfs = self.RootfsPath();
+        let flush = self.Status != Status::Stopped;
+        if flush && self.Sandbox.as_ref().unwrap().ApiVersion()? < 3 {
+            return Err(Error::Common(format!(
+                "the sandbox of container {} can't roll back its layer",
+                self.ID
+            )));
+        }
+
+        if !flush {
+            return RollbackLayer(&rootfs, &dir);
+        }
+
+        return self.WhilePaused(|c| {
+            let sandbox = c.Sandbox.as_ref().unwrap();
+            // the guest closes the host files of the dirents, the overlay
+            // drops the dentries of the old layer then
+            sandbox.FlushRootfs(&c.ID)?;
+            RollbackLayer(&rootfs, &dir)?;
+            return sandbox.FlushRootfs(&c.ID);
+        });
+    }
+
+    pub fn LayerSnapshots(&self) -> Result<Vec<String>> {
+        return LayerSnapshots(&Join(&self.Root, LAYER_SNAPSHOTS_DIR));
+    }
+
     // Start starts running the containerized process inside the sandbox.
     pub fn Start(&mut self) -> Result<()> {
         info!("Start container {}", &self.ID);
@@ -952,6 +1113,8 @@ impl Container {
             }
         }
 
+        DetachLoopDevices(&self.LoopDevices);
+
         if Path::new(&self.Root).exists() {
             info!("deleting container root directory...");
             let res = fs::remove_dir_all(&self.Root);
@@ -0,0 +1,477 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::ffi::CString;
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::Command;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::path::*;
+
+// The snapshots of the writable layer of a container. The root filesystem of
+// a container is the overlay mount of its image snapshotter, the writable
+// layer is the upper directory of the overlay: the files the container
+// created, changed or deleted (as whiteouts). A snapshot is a copy of the
+// upper directory, reflinked where the host filesystem can, kept in the
+// metadata directory of the container with the path of the upper directory,
+// so it goes with the container.
+//
+// With the overlay unmounted, the snapshot is copied next to the upper
+// directory and the two are exchanged in one rename, the upper directory is
+// either the old layer or the restored one. The overlay of the host holds on
+// to the upper directory it was mounted with, so with the overlay mounted the
+// content of the upper directory is swapped in place instead: the snapshot is
+// copied next to it, the entries of the old layer are moved out and the ones
+// of the copy are moved in, then the dentries the overlay cached of the old
+// layer are dropped. The container is paused and the guest has dropped its
+// dirents meanwhile, a file it still holds open stays the one of the old
+// layer.
+pub const LAYER_SNAPSHOTS_DIR: &str = "layer-snapshots";
+
+// the copy of the upper directory and the file with its path in a snapshot
+const SNAPSHOT_LAYER: &str = "layer";
+const SNAPSHOT_UPPERDIR: &str = "upperdir";
+
+// TopMount returns the filesystem type and the super block options of the
+// mount on top at path, if any.
+fn TopMount(path: &str) -> Result<Option<(String, String)>> {
+    let path = Clean(path);
+    let f = File::open("/proc/self/mountinfo")
+        .map_err(|e| Error::IOError(format!("open mountinfo fail {:?}", e)))?;
+
+    // the last mount at path is the one on top
+    let mut top = None;
+    for line in BufReader::new(f).lines() {
+        let l = line.map_err(|e| Error::IOError(format!("read mountinfo fail {:?}", e)))?;
+        let sep = match l.find(" - ") {
+            None => continue,
+            Some(s) => s,
+        };
+
+        let pre: Vec<&str> = l[..sep].split(' ').collect();
+        let post: Vec<&str> = l[sep + 3..].split(' ').collect();
+        if pre.len() < 5 || post.len() < 3 || pre[4] != path {
+            continue;
+        }
+
+        top = Some((post[0].to_string(), post[2].to_string()));
+    }
+
+    return Ok(top);
+}
+
+// UpperDir returns the upper directory of the overlay mounted at rootfs.
+pub fn UpperDir(rootfs: &str) -> Result<String> {
+    if let Some((fstype, opts)) = TopMount(rootfs)? {
+        if fstype == "overlay" {
+            for opt in opts.split(',') {
+                if let Some(dir) = opt.strip_prefix("upperdir=") {
+                    return Ok(dir.to_string());
+                }
+            }
+        }
+    }
+
+    return Err(Error::Common(format!(
+        "the root filesystem {} is not an overlay with a writable layer",
+        rootfs
+    )));
+}
+
+// UpperDirUsage returns the bytes of the files in the writable layer of the
+// overlay mounted at rootfs, 0 if it is not an overlay. The files are counted
+// by size as the guest charges them to the ephemeral storage.
+pub fn UpperDirUsage(rootfs: &str) -> u64 {
+    let dir = match UpperDir(rootfs) {
+        Err(_) => return 0,
+        Ok(dir) => dir,
+    };
+
+    let dev = match fs::symlink_metadata(&dir) {
+        Err(_) => return 0,
+        Ok(m) => m.dev(),
+    };
+
+    let mut used = 0;
+    let mut dirs = vec![dir];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Err(_) => continue,
+            Ok(entries) => entries,
+        };
+
+        for entry in entries.flatten() {
+            let m = match entry.path().symlink_metadata() {
+                Err(_) => continue,
+                Ok(m) => m,
+            };
+
+            if m.is_dir() && m.dev() == dev {
+                dirs.push(entry.path().to_string_lossy().to_string());
+            } else if m.is_file() {
+                used += m.len();
+            }
+        }
+    }
+
+    return used;
+}
+
+// ValidSnapshotName checks the name is a single path component.
+pub fn ValidSnapshotName(name: &str) -> Result<()> {
+    if name.len() == 0 || name == "." || name == ".." || name.contains('/') {
+        return Err(Error::Common(format!("invalid snapshot name {:?}", name)));
+    }
+
+    return Ok(());
+}
+
+// CopyTree copies the content of src to dst, which exists, with the modes,
+// the owners, the xattrs and the whiteouts of the overlay.
+fn CopyTree(src: &str, dst: &str) -> Result<()> {
+    let output = Command::new("cp")
+        .args(&["-a", "--reflink=auto", &format!("{}/.", src), dst])
+        .output()
+        .map_err(|e| Error::IOError(format!("run cp fail {:?}", e)))?;
+
+    if !output.status.success() {
+        return Err(Error::Common(format!(
+            "copy {} to {} fail: {}",
+            src,
+            dst,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    return Ok(());
+}
+
+// ExchangeDirs swaps the directories a and b in one rename.
+fn ExchangeDirs(a: &str, b: &str) -> Result<()> {
+    let ca = CString::new(a).map_err(|e| Error::Common(format!("{:?}", e)))?;
+    let cb = CString::new(b).map_err(|e| Error::Common(format!("{:?}", e)))?;
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            ca.as_ptr(),
+            libc::AT_FDCWD,
+            cb.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+
+    if ret < 0 {
+        return Err(Error::IOError(format!(
+            "exchange {} and {} fail {:?}",
+            a,
+            b,
+            std::io::Error::last_os_error()
+        )));
+    }
+
+    return Ok(());
+}
+
+// RestoreDir replaces the content of dir with a copy of src. The copy is
+// staged next to dir, on the same filesystem, and swapped with it, a failure
+// leaves dir as it was.
+fn RestoreDir(src: &str, dir: &str) -> Result<()> {
+    let staging = format!("{}.rollback", Clean(dir));
+
+    // the leftover of an interrupted rollback
+    if Path::new(&staging).exists() {
+        fs::remove_dir_all(&staging)
+            .map_err(|e| Error::IOError(format!("remove {} fail {:?}", staging, e)))?;
+    }
+
+    fs::create_dir(&staging)
+        .map_err(|e| Error::IOError(format!("create {} fail {:?}", staging, e)))?;
+    if let Err(e) = CopyTree(src, &staging).and_then(|_| ExchangeDirs(&staging, dir)) {
+        fs::remove_dir_all(&staging).ok();
+        return Err(e);
+    }
+
+    // the old content is in staging now
+    if let Err(e) = fs::remove_dir_all(&staging) {
+        error!("remove the old layer {} fail {:?}", staging, e);
+    }
+
+    return Ok(());
+}
+
+// MoveEntries moves the entries of the directory src to the directory dst,
+// both on the same filesystem.
+fn MoveEntries(src: &str, dst: &str) -> Result<()> {
+    let entries =
+        fs::read_dir(src).map_err(|e| Error::IOError(format!("read {} fail {:?}", src, e)))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::IOError(format!("read {} fail {:?}", src, e)))?;
+        let to = Path::new(dst).join(entry.file_name());
+        fs::rename(entry.path(), &to).map_err(|e| {
+            Error::IOError(format!("move {:?} to {:?} fail {:?}", entry.path(), to, e))
+        })?;
+    }
+
+    return Ok(());
+}
+
+// RestoreDirInPlace replaces the content of dir with a copy of src and keeps
+// dir itself, for a dir held by a mounted overlay. The old content is moved
+// to a staging directory next to dir first, a failure to move the copy in
+// moves it back.
+fn RestoreDirInPlace(src: &str, dir: &str) -> Result<()> {
+    let staging = format!("{}.rollback", Clean(dir));
+    let old = format!("{}.old", Clean(dir));
+    for d in &[&staging, &old] {
+        // the leftover of an interrupted rollback
+        if Path::new(d).exists() {
+            fs::remove_dir_all(d)
+                .map_err(|e| Error::IOError(format!("remove {} fail {:?}", d, e)))?;
+        }
+
+        fs::create_dir(d).map_err(|e| Error::IOError(format!("create {} fail {:?}", d, e)))?;
+    }
+
+    let mut res = CopyTree(src, &staging).and_then(|_| MoveEntries(dir, &old));
+    if res.is_ok() {
+        res = MoveEntries(&staging, dir);
+        if res.is_err() {
+            // drop the partial copy and put the old layer back
+            let restore = fs::remove_dir_all(dir)
+                .map_err(|e| Error::IOError(format!("clear {} fail {:?}", dir, e)))
+                .and_then(|_| {
+                    fs::create_dir(dir)
+                        .map_err(|e| Error::IOError(format!("create {} fail {:?}", dir, e)))
+                })
+                .and_then(|_| MoveEntries(&old, dir));
+            if let Err(e) = restore {
+                error!("restore the old layer {} from {} fail {:?}", dir, old, e);
+                fs::remove_dir_all(&staging).ok();
+                return res;
+            }
+        }
+    } else {
+        MoveEntries(&old, dir).ok();
+    }
+
+    fs::remove_dir_all(&staging).ok();
+    if let Err(e) = fs::remove_dir_all(&old) {
+        error!("remove the old layer {} fail {:?}", old, e);
+    }
+
+    return res;
+}
+
+// DropDentries drops the unused dentries and inodes of the host, the overlay
+// looks up the restored layer again then.
+fn DropDentries() {
+    unsafe {
+        libc::sync();
+    }
+
+    if let Err(e) = fs::write("/proc/sys/vm/drop_caches", "2") {
+        error!("drop the dentries of the host fail {:?}", e);
+    }
+}
+
+// SnapshotLayer copies the writable layer of rootfs to the snapshot dir.
+pub fn SnapshotLayer(rootfs: &str, dir: &str) -> Result<()> {
+    let upper = UpperDir(rootfs)?;
+    if Path::new(dir).exists() {
+        return Err(Error::Common(format!("snapshot {} already exists", dir)));
+    }
+
+    let layer = Join(dir, SNAPSHOT_LAYER);
+    fs::create_dir_all(&layer)
+        .map_err(|e| Error::IOError(format!("create {} fail {:?}", layer, e)))?;
+    let res = CopyTree(&upper, &layer).and_then(|_| {
+        fs::write(Join(dir, SNAPSHOT_UPPERDIR), &upper)
+            .map_err(|e| Error::IOError(format!("write snapshot {} fail {:?}", dir, e)))
+    });
+
+    if res.is_err() {
+        fs::remove_dir_all(dir).ok();
+    }
+
+    return res;
+}
+
+// RollbackLayer restores the writable layer of rootfs to the snapshot dir.
+// The layer of a mounted overlay is swapped in place, the one of an unmounted
+// overlay is exchanged.
+pub fn RollbackLayer(rootfs: &str, dir: &str) -> Result<()> {
+    if !Path::new(dir).is_dir() {
+        return Err(Error::Common(format!("snapshot {} doesn't exist", dir)));
+    }
+
+    let upper = fs::read_to_string(Join(dir, SNAPSHOT_UPPERDIR))
+        .map_err(|e| Error::IOError(format!("read snapshot {} fail {:?}", dir, e)))?;
+    let upper = upper.trim_end();
+    let layer = Join(dir, SNAPSHOT_LAYER);
+
+    let mounted = match TopMount(rootfs)? {
+        Some((fstype, _)) => fstype == "overlay",
+        None => false,
+    };
+    if !mounted {
+        return RestoreDir(&layer, upper);
+    }
+
+    // the overlay may be mounted with another layer than the snapshot one,
+    // e.g. the container is recreated with a new snapshotter key
+    let current = UpperDir(rootfs)?;
+    if Clean(&current) != Clean(upper) {
+        return Err(Error::Common(format!(
+            "the layer {} of the root filesystem {} is not the one {} of the snapshot",
+            current, rootfs, upper
+        )));
+    }
+
+    RestoreDirInPlace(&layer, upper)?;
+    DropDentries();
+    return Ok(());
+}
+
+// LayerSnapshots returns the names of the snapshots in dir.
+pub fn LayerSnapshots(dir: &str) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(names),
+        Err(e) => return Err(Error::IOError(format!("read {} fail {:?}", dir, e))),
+        Ok(entries) => entries,
+    };
+
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::IOError(format!("read {} fail {:?}", dir, e)))?;
+        names.push(entry.file_name().to_string_lossy().to_string());
+    }
+
+    names.sort();
+    return Ok(names);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    fn TestDir(name: &str) -> String {
+        let dir = format!(
+            "{}/quark-layer-{}-{}",
+            std::env::temp_dir().to_str().unwrap(),
+            name,
+            std::process::id()
+        );
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        return dir;
+    }
+
+    #[test]
+    fn TestValidSnapshotName() {
+        assert!(ValidSnapshotName("base").is_ok());
+        assert!(ValidSnapshotName("").is_err());
+        assert!(ValidSnapshotName(".").is_err());
+        assert!(ValidSnapshotName("..").is_err());
+        assert!(ValidSnapshotName("a/b").is_err());
+    }
+
+    #[test]
+    fn TestRestoreDir() {
+        let root = TestDir("restore");
+        let src = Join(&root, "src");
+        let upper = Join(&root, "upper");
+        fs::create_dir_all(Join(&src, "etc")).unwrap();
+        fs::write(Join(&src, "etc/hosts"), "snapshot").unwrap();
+        fs::create_dir_all(Join(&upper, "tmp")).unwrap();
+        fs::write(Join(&upper, "tmp/new"), "after").unwrap();
+        fs::write(Join(&upper, "hosts"), "after").unwrap();
+
+        RestoreDir(&src, &upper).unwrap();
+        assert_eq!(fs::read_to_string(Join(&upper, "etc/hosts")).unwrap(), "snapshot");
+        assert!(!Path::new(&Join(&upper, "tmp")).exists());
+        assert!(!Path::new(&Join(&upper, "hosts")).exists());
+        assert!(!Path::new(&format!("{}.rollback", upper)).exists());
+        // the snapshot is kept
+        assert_eq!(fs::read_to_string(Join(&src, "etc/hosts")).unwrap(), "snapshot");
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn TestRestoreDirFail() {
+        let root = TestDir("restore-fail");
+        let upper = Join(&root, "upper");
+        fs::create_dir_all(&upper).unwrap();
+        fs::write(Join(&upper, "file"), "kept").unwrap();
+
+        assert!(RestoreDir(&Join(&root, "missing"), &upper).is_err());
+        assert_eq!(fs::read_to_string(Join(&upper, "file")).unwrap(), "kept");
+        assert!(!Path::new(&format!("{}.rollback", upper)).exists());
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn TestRestoreDirInPlace() {
+        let root = TestDir("restore-in-place");
+        let src = Join(&root, "src");
+        let upper = Join(&root, "upper");
+        fs::create_dir_all(Join(&src, "etc")).unwrap();
+        fs::write(Join(&src, "etc/hosts"), "snapshot").unwrap();
+        fs::create_dir_all(Join(&upper, "etc")).unwrap();
+        fs::write(Join(&upper, "etc/hosts"), "after").unwrap();
+        fs::write(Join(&upper, "new"), "after").unwrap();
+
+        let ino = fs::metadata(&upper).unwrap().ino();
+        RestoreDirInPlace(&src, &upper).unwrap();
+        // the directory the overlay holds is kept
+        assert_eq!(fs::metadata(&upper).unwrap().ino(), ino);
+        assert_eq!(fs::read_to_string(Join(&upper, "etc/hosts")).unwrap(), "snapshot");
+        assert!(!Path::new(&Join(&upper, "new")).exists());
+        assert!(!Path::new(&format!("{}.rollback", upper)).exists());
+        assert!(!Path::new(&format!("{}.old", upper)).exists());
+
+        // a missing snapshot leaves the layer as it was
+        assert!(RestoreDirInPlace(&Join(&root, "missing"), &upper).is_err());
+        assert_eq!(fs::read_to_string(Join(&upper, "etc/hosts")).unwrap(), "snapshot");
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn TestRollbackLayer() {
+        let root = TestDir("rollback");
+        let upper = Join(&root, "upper");
+        let snapshot = Join(&root, "snapshot");
+        fs::create_dir_all(Join(&snapshot, SNAPSHOT_LAYER)).unwrap();
+        fs::write(Join(&Join(&snapshot, SNAPSHOT_LAYER), "file"), "snapshot").unwrap();
+        fs::write(Join(&snapshot, SNAPSHOT_UPPERDIR), &upper).unwrap();
+        fs::create_dir_all(&upper).unwrap();
+        fs::write(Join(&upper, "file"), "after").unwrap();
+
+        // the root filesystem is not mounted
+        RollbackLayer(&Join(&root, "rootfs"), &snapshot).unwrap();
+        assert_eq!(fs::read_to_string(Join(&upper, "file")).unwrap(), "snapshot");
+
+        fs::remove_dir_all(&root).ok();
+    }
+}
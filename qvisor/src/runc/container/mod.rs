@@ -16,6 +16,7 @@ pub mod cgroups;
 pub mod container;
 pub mod exec_hook;
 pub mod hook;
+pub mod layer;
 pub mod mounts;
 pub mod nix_ext;
 pub mod status;
@@ -29,6 +29,7 @@ use std::path::{Path, PathBuf};
 
 use super::super::super::qlib::common::*;
 use super::super::oci::{LinuxDevice, LinuxDeviceType, Mount, Spec};
+use super::super::runtime::etc_files::*;
 use super::cgroups;
 use super::nix_ext::*;
 
@@ -83,6 +84,10 @@ pub fn init_rootfs(spec: &Spec, rootfs: &str, cpath: &str, bind_devices: bool) -
             let msg = format!("invalid mount destination: {}", m.destination);
             return Err(Error::Common(msg));
         }
+        if IsVirtualEtcMount(spec, m) {
+            info!("{} is served by the guest", m.destination);
+            continue;
+        }
         let (flags, data) = parse_mount(m);
         if m.typ == "cgroup" {
             mount_cgroups(m, rootfs, flags, &data, &linux.mount_label, cpath)?;
@@ -407,6 +412,10 @@ pub fn create_devices(devices: &[LinuxDevice], bind: bool) -> Result<()> {
             let msg = format!("{} is not a valid device path", dev.path);
             return Err(Error::Common(msg));
         }
+        // e.g. /dev/infiniband/uverbs0 of an injected device
+        if let Some(dir) = Path::new(&dev.path[1..]).parent() {
+            create_dir_all(dir).map_err(|e| Error::IOError(format!("io error is {:?}", e)))?;
+        }
         op(dev)?;
     }
     umask(old);
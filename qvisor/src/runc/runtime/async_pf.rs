@@ -0,0 +1,59 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kvm_bindings::*;
+use kvm_ioctls::{Kvm, VmFd};
+
+use super::super::super::qlib::common::*;
+use super::tsc_timer::*;
+
+// The async page faults of the guest, see qlib/kernel/arch/x86_64/async_pf.rs.
+// KVM sends the page ready of an async page fault as an interrupt of the local
+// APIC, so they need the local APICs in KVM as the TSC deadline timer.
+pub const KVM_CAP_ASYNC_PF_INT: u32 = 183;
+
+const KVM_CPUID_FEATURES: u32 = 0x40000001;
+const KVM_FEATURE_ASYNC_PF: u32 = 1 << 4;
+const KVM_FEATURE_ASYNC_PF_INT: u32 = 1 << 14;
+
+// the time in ms an idle vcpu with async page faults waits in qvisor before
+// it goes back to KVM_RUN to get their page ready
+pub const ASYNC_PF_WAIT_MS: i32 = 1;
+
+// EnableAsyncPageFault exposes the async page faults to the vcpus, it has to
+// be called before the vcpus are created. It returns false if the host
+// doesn't support them.
+pub fn EnableAsyncPageFault(kvm: &Kvm, vmfd: &VmFd, cpuid: &mut CpuId) -> Result<bool> {
+    if !CheckExtension(kvm, KVM_CAP_ASYNC_PF_INT) {
+        info!("async page fault: not supported by the host");
+        return Ok(false);
+    }
+
+    let features = KVM_FEATURE_ASYNC_PF | KVM_FEATURE_ASYNC_PF_INT;
+    let supported = cpuid
+        .as_slice()
+        .iter()
+        .any(|e| e.function == KVM_CPUID_FEATURES && e.eax & features == features);
+    if !supported {
+        info!("async page fault: not in the kvm cpuid");
+        return Ok(false);
+    }
+
+    if !EnableX2Apic(kvm, vmfd, cpuid)? {
+        return Ok(false);
+    }
+
+    info!("async page fault: enabled");
+    return Ok(true);
+}
@@ -0,0 +1,136 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use spin::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use super::super::super::qlib::audit::*;
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::kernel::SHARESPACE;
+use super::super::super::ucall::usocket::*;
+use super::vm::IsRunning;
+
+// The exporter of the audit of the guest syscalls, see qlib/audit.rs. A client
+// subscribes with UCallReq::AuditStream on the control socket, the connection
+// then carries one json AuditEvent per line, and {"dropped":N} when the guest
+// queue overflowed. The events are dropped while there is no client. A client
+// which blocks the stream for a second is disconnected, a partial line would
+// break the stream for it.
+
+const AUDIT_EXPORT_PERIOD: Duration = Duration::from_millis(100);
+
+lazy_static! {
+    pub static ref AUDIT_SUBSCRIBERS: Mutex<Vec<USocket>> = Mutex::new(Vec::new());
+}
+
+pub fn Subscribe(usock: USocket) {
+    let timeout = libc::timeval {
+        tv_sec: 1,
+        tv_usec: 0,
+    };
+    unsafe {
+        libc::setsockopt(
+            usock.socket,
+            libc::SOL_SOCKET,
+            libc::SO_SNDTIMEO,
+            &timeout as *const _ as *const libc::c_void,
+            core::mem::size_of::<libc::timeval>() as u32,
+        );
+    }
+
+    AUDIT_SUBSCRIBERS.lock().push(usock);
+}
+
+pub fn StartAuditExporter() -> Result<()> {
+    thread::Builder::new()
+        .name("audit".to_string())
+        .spawn(move || {
+            while IsRunning() {
+                Export();
+                thread::sleep(AUDIT_EXPORT_PERIOD);
+            }
+        })
+        .map_err(|e| Error::IOError(format!("audit: spawn thread fail {:?}", e)))?;
+
+    return Ok(());
+}
+
+fn Export() {
+    let events = SHARESPACE.audit.Drain();
+    let dropped = SHARESPACE.audit.Dropped();
+
+    // the subscribers are taken out of the list for the sends, which can
+    // block, a new subscriber gets the events of the next period
+    let mut subscribers = core::mem::take(&mut *AUDIT_SUBSCRIBERS.lock());
+    if subscribers.len() == 0 {
+        return;
+    }
+
+    let mut buf = Vec::new();
+    for event in &events {
+        match serde_json::to_vec(event) {
+            Err(e) => error!("audit: serialize {:?} fail {:?}", event, e),
+            Ok(line) => {
+                buf.extend_from_slice(&line);
+                buf.push(b'\n');
+            }
+        }
+    }
+
+    if dropped > 0 {
+        buf.extend_from_slice(format!("{{\"dropped\":{}}}\n", dropped).as_bytes());
+    }
+
+    if buf.len() > 0 {
+        subscribers.retain(|usock| {
+            if let Err(e) = Send(usock.socket, &buf) {
+                info!("audit: disconnect the subscriber {} {:?}", usock.socket, e);
+                usock.Drop();
+                return false;
+            }
+
+            return true;
+        });
+    }
+
+    AUDIT_SUBSCRIBERS.lock().append(&mut subscribers);
+}
+
+fn Send(fd: i32, buf: &[u8]) -> Result<()> {
+    let mut offset = 0;
+    while offset < buf.len() {
+        let cnt = unsafe {
+            libc::send(
+                fd,
+                &buf[offset] as *const _ as *const libc::c_void,
+                buf.len() - offset,
+                libc::MSG_NOSIGNAL,
+            )
+        };
+
+        if cnt < 0 {
+            let errno = errno::errno().0;
+            if errno == libc::EINTR {
+                continue;
+            }
+
+            return Err(Error::SysError(errno));
+        }
+
+        offset += cnt as usize;
+    }
+
+    return Ok(());
+}
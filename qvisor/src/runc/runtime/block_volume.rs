@@ -0,0 +1,334 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::os::unix::io::AsRawFd;
+
+use super::super::super::qlib::common::*;
+use super::super::oci::*;
+
+// The block volumes backed by a disk image. A raw block volume of a pod is a
+// host block device in the devices of the spec, the guest /dev serves it as a
+// host backed file. A disk image comes as a mount with the loop option:
+//   {"destination": "/dev/vdb", "type": "bind", "source": "/images/db.img",
+//    "options": ["loop"]}
+// attaches the image to a loop device with the partition scan, the guest sees
+// the image at /dev/vdb and its partitions at /dev/vdb1, /dev/vdb2, ...
+//   {"destination": "/data", "type": "ext4", "source": "/images/db.img",
+//    "options": ["loop", "offset=1048576"]}
+// mounts the file system of the image, or the one of the partition at the
+// offset, at /data: the loop device is mounted with the other mounts of the
+// rootfs on the host and the guest sees it in the rootfs. The file system of
+// a host block device, e.g. the partition /dev/sdb1, is mounted the same way
+// without a loop device. The loop devices are detached when the container is
+// destroyed.
+
+const LOOP_CONTROL: &str = "/dev/loop-control";
+
+const LOOP_SET_FD: u64 = 0x4c00;
+const LOOP_CLR_FD: u64 = 0x4c01;
+const LOOP_SET_STATUS64: u64 = 0x4c04;
+const LOOP_CTL_GET_FREE: u64 = 0x4c82;
+
+const LO_FLAGS_PARTSCAN: u32 = 8;
+
+// another attach may take the free loop device first
+const LOOP_ATTACH_RETRY: usize = 8;
+
+#[repr(C)]
+struct LoopInfo64 {
+    device: u64,
+    inode: u64,
+    rdevice: u64,
+    offset: u64,
+    sizelimit: u64,
+    number: u32,
+    encryptType: u32,
+    encryptKeySize: u32,
+    flags: u32,
+    fileName: [u8; 64],
+    cryptName: [u8; 64],
+    encryptKey: [u8; 32],
+    init: [u64; 2],
+}
+
+// ApplyBlockVolumes attaches the disk images of the loop mounts of the spec to
+// loop devices and returns the loop devices with their images.
+pub fn ApplyBlockVolumes(spec: &mut Spec) -> Result<Vec<(String, String)>> {
+    let mut loops = Vec::new();
+    match ApplyLoopMounts(spec, &mut loops) {
+        Ok(()) => return Ok(loops),
+        Err(e) => {
+            DetachLoopDevices(&loops);
+            return Err(e);
+        }
+    }
+}
+
+fn ApplyLoopMounts(spec: &mut Spec, loops: &mut Vec<(String, String)>) -> Result<()> {
+    let mounts = core::mem::take(&mut spec.mounts);
+    for mut m in mounts {
+        if !m.options.iter().any(|o| o.as_str() == "loop") {
+            spec.mounts.push(m);
+            continue;
+        }
+
+        let isDevice = m.destination.starts_with("/dev/");
+        if m.destination.contains("..") || (!isDevice && m.typ.as_str() == "bind") {
+            return Err(Error::Common(format!(
+                "block volume: invalid loop mount {} of {}",
+                m.destination, m.source
+            )));
+        }
+
+        // the path the loop device shows as its backing file
+        let image = match fs::canonicalize(&m.source) {
+            Ok(p) if p.is_file() => p.to_string_lossy().to_string(),
+            _ => {
+                return Err(Error::Common(format!(
+                    "block volume: {} is not a disk image",
+                    m.source
+                )))
+            }
+        };
+
+        let readonly = m.options.iter().any(|o| o.as_str() == "ro");
+        let (offset, sizelimit) = LoopOptions(&m.options)?;
+        let dev = AttachLoop(&image, readonly, offset, sizelimit)?;
+        loops.push((dev.clone(), image.clone()));
+        info!("block volume: {} attached to {}", image, dev);
+
+        if isDevice {
+            AddLoopDevices(spec, &m.destination, &dev, readonly)?;
+            continue;
+        }
+
+        m.source = dev;
+        m.options.retain(|o| {
+            o.as_str() != "loop" && !o.starts_with("offset=") && !o.starts_with("sizelimit=")
+        });
+        spec.mounts.push(m);
+    }
+
+    return Ok(());
+}
+
+// LoopOptions returns the offset and the size limit in bytes of the loop
+// options, as the ones of mount(8).
+fn LoopOptions(options: &[String]) -> Result<(u64, u64)> {
+    let mut offset = 0;
+    let mut sizelimit = 0;
+    for o in options {
+        let (key, v) = match o.find('=') {
+            None => continue,
+            Some(i) => (&o[..i], &o[i + 1..]),
+        };
+
+        let v = match key {
+            "offset" | "sizelimit" => match v.parse::<u64>() {
+                Ok(v) => v,
+                Err(_) => {
+                    return Err(Error::Common(format!(
+                        "block volume: invalid loop option {}",
+                        o
+                    )))
+                }
+            },
+            _ => continue,
+        };
+
+        if key == "offset" {
+            offset = v;
+        } else {
+            sizelimit = v;
+        }
+    }
+
+    return Ok((offset, sizelimit));
+}
+
+// AttachLoop attaches the image to a free loop device and returns the path of
+// the loop device. A read only image is opened read only, the loop device is
+// read only then.
+fn AttachLoop(image: &str, readonly: bool, offset: u64, sizelimit: u64) -> Result<String> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(!readonly)
+        .open(image)
+        .map_err(|e| Error::IOError(format!("block volume: open {} fail {:?}", image, e)))?;
+    let ctl = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(LOOP_CONTROL)
+        .map_err(|e| {
+            Error::IOError(format!("block volume: open {} fail {:?}", LOOP_CONTROL, e))
+        })?;
+
+    for _ in 0..LOOP_ATTACH_RETRY {
+        let n = unsafe { libc::ioctl(ctl.as_raw_fd(), LOOP_CTL_GET_FREE) };
+        if n < 0 {
+            return Err(Error::SysError(errno::errno().0));
+        }
+
+        let dev = format!("/dev/loop{}", n);
+        let loopDev = OpenOptions::new()
+            .read(true)
+            .write(!readonly)
+            .open(&dev)
+            .map_err(|e| Error::IOError(format!("block volume: open {} fail {:?}", dev, e)))?;
+
+        let ret = unsafe { libc::ioctl(loopDev.as_raw_fd(), LOOP_SET_FD, file.as_raw_fd()) };
+        if ret < 0 {
+            let errno = errno::errno().0;
+            if errno == libc::EBUSY {
+                continue;
+            }
+
+            return Err(Error::SysError(errno));
+        }
+
+        let mut info: LoopInfo64 = unsafe { core::mem::zeroed() };
+        info.offset = offset;
+        info.sizelimit = sizelimit;
+        info.flags = LO_FLAGS_PARTSCAN;
+        let name = image.as_bytes();
+        let len = name.len().min(info.fileName.len() - 1);
+        info.fileName[..len].copy_from_slice(&name[..len]);
+
+        let ret = unsafe {
+            libc::ioctl(loopDev.as_raw_fd(), LOOP_SET_STATUS64, &info as *const _ as u64)
+        };
+        if ret < 0 {
+            let errno = errno::errno().0;
+            unsafe {
+                libc::ioctl(loopDev.as_raw_fd(), LOOP_CLR_FD, 0);
+            }
+            return Err(Error::SysError(errno));
+        }
+
+        return Ok(dev);
+    }
+
+    return Err(Error::Common(format!(
+        "block volume: no free loop device for {}",
+        image
+    )));
+}
+
+// AddLoopDevices adds the loop device to the devices of the spec at the path
+// and its partition n at the path with n, e.g. /dev/vdb1.
+fn AddLoopDevices(spec: &mut Spec, path: &str, dev: &str, readonly: bool) -> Result<()> {
+    let name = &dev["/dev/".len()..];
+    let mut nodes = vec![(path.to_string(), DevNumbers(&format!("/sys/block/{}/dev", name))?)];
+
+    let sep = if path.ends_with(|c: char| c.is_ascii_digit()) {
+        "p"
+    } else {
+        ""
+    };
+    let prefix = format!("{}p", name);
+    if let Ok(entries) = fs::read_dir(format!("/sys/block/{}", name)) {
+        for e in entries.filter_map(|e| e.ok()) {
+            let part = e.file_name().to_string_lossy().to_string();
+            if !part.starts_with(&prefix) || part[prefix.len()..].parse::<u32>().is_err() {
+                continue;
+            }
+
+            nodes.push((
+                format!("{}{}{}", path, sep, &part[prefix.len()..]),
+                DevNumbers(&format!("/sys/block/{}/{}/dev", name, part))?,
+            ));
+        }
+    }
+
+    let linux = match spec.linux.as_mut() {
+        None => {
+            return Err(Error::Common(format!(
+                "block volume: no linux config for {}",
+                path
+            )))
+        }
+        Some(linux) => linux,
+    };
+
+    for (path, (major, minor)) in nodes {
+        if let Some(resources) = linux.resources.as_mut() {
+            resources.devices.push(LinuxDeviceCgroup {
+                allow: true,
+                typ: LinuxDeviceType::b,
+                major: Some(major as i64),
+                minor: Some(minor as i64),
+                access: if readonly { "r" } else { "rw" }.to_string(),
+            });
+        }
+
+        info!("block volume: {} is {}:{}", path, major, minor);
+        linux.devices.retain(|d| d.path != path);
+        linux.devices.push(LinuxDevice {
+            path: path,
+            typ: LinuxDeviceType::b,
+            major: major,
+            minor: minor,
+            file_mode: Some(if readonly { 0o440 } else { 0o660 }),
+            uid: None,
+            gid: None,
+        });
+    }
+
+    return Ok(());
+}
+
+// DevNumbers returns the major and the minor of a sysfs dev file.
+fn DevNumbers(file: &str) -> Result<(u64, u64)> {
+    let s = fs::read_to_string(file)
+        .map_err(|e| Error::IOError(format!("block volume: read {} fail {:?}", file, e)))?;
+    let mut numbers = s.trim().split(':').map(|n| n.parse::<u64>());
+    match (numbers.next(), numbers.next()) {
+        (Some(Ok(major)), Some(Ok(minor))) => return Ok((major, minor)),
+        _ => {
+            return Err(Error::Common(format!(
+                "block volume: invalid {} {}",
+                file, s
+            )))
+        }
+    }
+}
+
+// DetachLoopDevices detaches the loop devices still backed by their images,
+// the number of a detached one may be taken by another image. A loop device
+// still mounted is detached when it is unmounted.
+pub fn DetachLoopDevices(loops: &[(String, String)]) {
+    for (dev, image) in loops {
+        let backing = format!("/sys/block/{}/loop/backing_file", &dev["/dev/".len()..]);
+        match fs::read_to_string(&backing) {
+            Ok(f) if f.trim() == image.as_str() => (),
+            _ => continue,
+        }
+
+        let file = match OpenOptions::new().read(true).open(dev) {
+            Err(e) => {
+                info!("block volume: open {} fail {:?}", dev, e);
+                continue;
+            }
+            Ok(f) => f,
+        };
+
+        let ret = unsafe { libc::ioctl(file.as_raw_fd(), LOOP_CLR_FD, 0) };
+        if ret < 0 && errno::errno().0 != libc::ENXIO {
+            info!("block volume: detach {} fail {:?}", dev, errno::errno());
+        }
+    }
+}
+
@@ -0,0 +1,391 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::fs::MetadataExt;
+
+use super::super::super::qlib::common::*;
+use super::super::super::HOST_CONFIG;
+use super::super::oci::*;
+
+// Container Device Interface. Behind a RuntimeClass, the device plugins of
+// the RDMA NICs and the GPUs name the devices they allocate to a container
+// with CDI: containerd resolves them into the OCI config when its CDI is
+// enabled, otherwise they come as annotations which quark resolves itself:
+//   cdi.k8s.io/<plugin>: comma separated devices, e.g. "nvidia.com/gpu=0"
+// A pod sets any annotation, so only the devices the operator allows with
+// cdi.allowed_devices of the config file are resolved from the annotations,
+// the others are left to containerd.
+// A device is looked up in the CDI specs of /etc/cdi and /var/run/cdi, the
+// latter wins for a kind in both. The container edits of the device and of
+// its spec are applied to the OCI config: the env, the device nodes, the
+// mounts and the additional gids. The device nodes go to the guest /dev as
+// host backed files, the mounts are host bind mounts the guest sees in the
+// rootfs as the other ones. The hooks are not run, the guest doesn't run
+// host processes. Only the json specs are read.
+pub const CDI_ANNOTATION_PREFIX: &str = "cdi.k8s.io/";
+pub const CDI_SPEC_DIRS: [&str; 2] = ["/etc/cdi", "/var/run/cdi"];
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct CdiDeviceNode {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default, rename = "hostPath")]
+    pub host_path: String,
+    #[serde(default, rename = "type")]
+    pub typ: String,
+    #[serde(default)]
+    pub major: Option<u64>,
+    #[serde(default)]
+    pub minor: Option<u64>,
+    #[serde(default, rename = "fileMode")]
+    pub file_mode: Option<u32>,
+    #[serde(default)]
+    pub permissions: String,
+    #[serde(default)]
+    pub uid: Option<u32>,
+    #[serde(default)]
+    pub gid: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct CdiMount {
+    #[serde(default, rename = "hostPath")]
+    pub host_path: String,
+    #[serde(default, rename = "containerPath")]
+    pub container_path: String,
+    #[serde(default, rename = "type")]
+    pub typ: String,
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct CdiContainerEdits {
+    #[serde(default)]
+    pub env: Vec<String>,
+    #[serde(default, rename = "deviceNodes")]
+    pub device_nodes: Vec<CdiDeviceNode>,
+    #[serde(default)]
+    pub mounts: Vec<CdiMount>,
+    #[serde(default, rename = "additionalGids")]
+    pub additional_gids: Vec<u32>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct CdiDevice {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, rename = "containerEdits")]
+    pub container_edits: CdiContainerEdits,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct CdiSpec {
+    #[serde(default, rename = "cdiVersion")]
+    pub cdi_version: String,
+    #[serde(default)]
+    pub kind: String,
+    #[serde(default)]
+    pub devices: Vec<CdiDevice>,
+    #[serde(default, rename = "containerEdits")]
+    pub container_edits: CdiContainerEdits,
+}
+
+// CdiRegistry is the CDI specs of the host by kind.
+#[derive(Debug, Default)]
+pub struct CdiRegistry {
+    pub specs: BTreeMap<String, CdiSpec>,
+}
+
+impl CdiRegistry {
+    pub fn Load() -> Self {
+        let mut registry = Self::default();
+        for dir in CDI_SPEC_DIRS.iter() {
+            let entries = match fs::read_dir(dir) {
+                Err(_) => continue,
+                Ok(entries) => entries,
+            };
+
+            let mut paths: Vec<_> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+            paths.sort();
+            for path in paths {
+                if path.extension().map_or(true, |ext| ext != "json") {
+                    continue;
+                }
+
+                let spec: CdiSpec = match fs::read_to_string(&path)
+                    .map_err(|e| format!("{:?}", e))
+                    .and_then(|s| serde_json::from_str(&s).map_err(|e| format!("{:?}", e)))
+                {
+                    Ok(spec) => spec,
+                    Err(e) => {
+                        info!("cdi: skip the spec {:?}, {}", path, e);
+                        continue;
+                    }
+                };
+
+                registry.specs.insert(spec.kind.clone(), spec);
+            }
+        }
+
+        return registry;
+    }
+
+    // Resolve returns the edits of the qualified device name, e.g.
+    // "nvidia.com/gpu=0", the ones of its spec first.
+    pub fn Resolve(&self, name: &str) -> Result<(&CdiContainerEdits, &CdiContainerEdits)> {
+        let (kind, dev) = match name.find('=') {
+            None => {
+                return Err(Error::Common(format!(
+                    "cdi: {} is not a qualified device name",
+                    name
+                )))
+            }
+            Some(i) => (&name[..i], &name[i + 1..]),
+        };
+
+        let spec = match self.specs.get(kind) {
+            None => return Err(Error::Common(format!("cdi: no spec of the kind {}", kind))),
+            Some(spec) => spec,
+        };
+
+        for d in &spec.devices {
+            if d.name.as_str() == dev {
+                return Ok((&spec.container_edits, &d.container_edits));
+            }
+        }
+
+        return Err(Error::Common(format!("cdi: unresolvable device {}", name)));
+    }
+}
+
+// CdiAllowed returns whether the operator allows the device name, either by
+// its name or by its kind.
+pub fn CdiAllowed(allowed: &[String], name: &str) -> bool {
+    let kind = name.split('=').next().unwrap();
+    return allowed.iter().any(|a| a.as_str() == name || a.as_str() == kind);
+}
+
+// CdiDevices returns the allowed devices the annotations of the spec ask for.
+pub fn CdiDevices(spec: &Spec, allowed: &[String]) -> Vec<String> {
+    let mut devices = Vec::new();
+    for (key, v) in &spec.annotations {
+        if !key.starts_with(CDI_ANNOTATION_PREFIX) {
+            continue;
+        }
+
+        for name in v.split(',').map(|n| n.trim()).filter(|n| n.len() > 0) {
+            if !CdiAllowed(allowed, name) {
+                info!("cdi: skip {} of {}, it is not allowed", name, key);
+                continue;
+            }
+
+            if !devices.iter().any(|d: &String| d.as_str() == name) {
+                devices.push(name.to_string());
+            }
+        }
+    }
+
+    return devices;
+}
+
+// ApplyCdiDevices applies the edits of the allowed CDI devices of the
+// annotations to the spec, the edits of a spec are applied once.
+pub fn ApplyCdiDevices(spec: &mut Spec) -> Result<()> {
+    let devices = CdiDevices(spec, &HOST_CONFIG.CdiAllowedDevices);
+    if devices.len() == 0 {
+        return Ok(());
+    }
+
+    let registry = CdiRegistry::Load();
+    let mut kinds = Vec::new();
+    for name in &devices {
+        let (specEdits, devEdits) = registry.Resolve(name)?;
+        let kind = name.split('=').next().unwrap();
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+            ApplyEdits(spec, specEdits)?;
+        }
+
+        ApplyEdits(spec, devEdits)?;
+        info!("cdi: injected {}", name);
+    }
+
+    return Ok(());
+}
+
+fn ApplyEdits(spec: &mut Spec, edits: &CdiContainerEdits) -> Result<()> {
+    for env in &edits.env {
+        let key = env.split('=').next().unwrap();
+        spec.process
+            .env
+            .retain(|e| e.split('=').next().unwrap() != key);
+        spec.process.env.push(env.to_string());
+    }
+
+    for gid in &edits.additional_gids {
+        if !spec.process.user.additional_gids.contains(gid) {
+            spec.process.user.additional_gids.push(*gid);
+        }
+    }
+
+    for m in &edits.mounts {
+        if !m.container_path.starts_with('/') || m.container_path.contains("..") {
+            return Err(Error::Common(format!(
+                "cdi: invalid mount destination {}",
+                m.container_path
+            )));
+        }
+
+        let typ = if m.typ.len() == 0 { "bind" } else { &m.typ };
+        let mut options = m.options.clone();
+        // the bind of the mount is in its flags
+        if typ == "bind" && !options.iter().any(|o| o == "bind" || o == "rbind") {
+            options.push("rbind".to_string());
+        }
+
+        spec.mounts.retain(|o| o.destination != m.container_path);
+        spec.mounts.push(Mount {
+            destination: m.container_path.clone(),
+            typ: typ.to_string(),
+            source: m.host_path.clone(),
+            options: options,
+        });
+    }
+
+    let linux = match spec.linux.as_mut() {
+        None => return Ok(()),
+        Some(linux) => linux,
+    };
+
+    for node in &edits.device_nodes {
+        let dev = DeviceFromNode(node)?;
+        if let Some(resources) = linux.resources.as_mut() {
+            resources.devices.push(LinuxDeviceCgroup {
+                allow: true,
+                typ: dev.typ,
+                major: Some(dev.major as i64),
+                minor: Some(dev.minor as i64),
+                access: if node.permissions.len() == 0 {
+                    "rwm".to_string()
+                } else {
+                    node.permissions.clone()
+                },
+            });
+        }
+
+        linux.devices.retain(|d| d.path != dev.path);
+        linux.devices.push(dev);
+    }
+
+    return Ok(());
+}
+
+// DeviceFromNode fills the type and the numbers a device node leaves to the
+// host device.
+fn DeviceFromNode(node: &CdiDeviceNode) -> Result<LinuxDevice> {
+    if !node.path.starts_with("/dev/") || node.path.contains("..") {
+        return Err(Error::Common(format!(
+            "cdi: {} is not a valid device path",
+            node.path
+        )));
+    }
+
+    let hostPath = if node.host_path.len() > 0 {
+        &node.host_path
+    } else {
+        &node.path
+    };
+
+    let mut dev = LinuxDevice {
+        path: node.path.clone(),
+        typ: LinuxDeviceType::c,
+        major: node.major.unwrap_or(0),
+        minor: node.minor.unwrap_or(0),
+        file_mode: node.file_mode,
+        uid: node.uid,
+        gid: node.gid,
+    };
+
+    // the guest node is made with the numbers of the host node at hostPath,
+    // the ones of the spec are only used when it has no hostPath
+    let typ = node.typ.as_str();
+    if node.host_path.len() == 0
+        && typ.len() > 0
+        && node.major.is_some()
+        && node.minor.is_some()
+    {
+        dev.typ = match typ {
+            "b" => LinuxDeviceType::b,
+            "c" | "u" => LinuxDeviceType::c,
+            "p" => LinuxDeviceType::p,
+            _ => {
+                return Err(Error::Common(format!(
+                    "cdi: invalid type {} of {}",
+                    typ, node.path
+                )))
+            }
+        };
+        return Ok(dev);
+    }
+
+    let meta = fs::metadata(hostPath)
+        .map_err(|e| Error::IOError(format!("cdi: stat {} fail {:?}", hostPath, e)))?;
+    let ft = meta.file_type();
+    dev.typ = if ft.is_block_device() {
+        LinuxDeviceType::b
+    } else if ft.is_char_device() {
+        LinuxDeviceType::c
+    } else if ft.is_fifo() {
+        LinuxDeviceType::p
+    } else {
+        return Err(Error::Common(format!("cdi: {} is not a device", hostPath)));
+    };
+
+    let rdev = meta.rdev();
+    if node.host_path.len() > 0 {
+        dev.major = Major(rdev);
+        dev.minor = Minor(rdev);
+    } else {
+        dev.major = node.major.unwrap_or(Major(rdev));
+        dev.minor = node.minor.unwrap_or(Minor(rdev));
+    }
+    if dev.file_mode.is_none() {
+        dev.file_mode = Some(meta.mode() & 0o777);
+    }
+
+    return Ok(dev);
+}
+
+fn Major(rdev: u64) -> u64 {
+    return ((rdev >> 8) & 0xfff) | ((rdev >> 32) & !0xfff);
+}
+
+fn Minor(rdev: u64) -> u64 {
+    return (rdev & 0xff) | ((rdev >> 12) & !0xff);
+}
+
+// GuestDevices returns the device nodes of the spec the guest /dev shows as
+// host backed files, the ones the guest has itself are skipped by the guest.
+// The raw block volumes of the pod are among them, the guest reads and writes
+// a block device at the offset and serves its size and discard ioctls.
+pub fn GuestDevices(spec: &Spec) -> Vec<String> {
+    return match spec.linux.as_ref() {
+        None => Vec::new(),
+        Some(linux) => linux.devices.iter().map(|d| d.path.clone()).collect(),
+    };
+}
@@ -0,0 +1,418 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use std::fs;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::config::*;
+use super::super::oci::*;
+
+// The config file of qvisor, /etc/quark/config.toml. The settings are grouped
+// in sections, e.g.
+//   [log]
+//   debug_level = "Error"
+//   [uring]
+//   io = true
+//   wait_us = 50
+// The keys not in the file keep their default. A sandbox overrides a setting
+// the operator lets it ([config] overridable) with the annotation
+// quark.config.<section>.<key>, e.g. "quark.config.log.level" = "Complex". The
+// old /etc/quark/config.json is still read when there is no config.toml.
+pub const CONFIG_TOML_FILE: &str = "/etc/quark/config.toml";
+pub const CONFIG_ANNOTATION_PREFIX: &str = "quark.config.";
+
+// the <section>.<key> of the config file and its field of Config
+const CONFIG_KEYS: &[(&str, &str)] = &[
+    ("log.debug_level", "DebugLevel"),
+    ("log.level", "LogLevel"),
+    ("log.type", "LogType"),
+    ("log.per_sandbox", "PerSandboxLog"),
+    ("network.async_accept", "AsyncAccept"),
+    ("network.udp_offload", "UdpOffload"),
+    ("network.checksum_offload", "ChecksumOffload"),
+    ("network.recv_steering", "RecvSteering"),
+    ("network.somaxconn", "SoMaxConn"),
+    ("network.syn_cookies", "SynCookies"),
+    ("network.pause_keepalive", "PauseKeepalive"),
+    ("network.pause_keepalive_idle", "PauseKeepaliveIdle"),
+    ("rdma.enable", "EnableRDMA"),
+    ("rdma.port", "RDMAPort"),
+    ("uring.io", "UringIO"),
+    ("uring.buf", "UringBuf"),
+    ("uring.statx", "UringStatx"),
+    ("uring.aio", "EnableAIO"),
+    ("uring.wait_us", "UringWaitUs"),
+    ("memory.kernel_size", "KernelMemSize"),
+    ("memory.kernel_pagetable", "KernelPagetable"),
+    ("memory.high", "MemoryHigh"),
+    ("memory.mmap_read", "MmapRead"),
+    ("memory.dirty_ring_size", "DirtyRingSize"),
+    ("memory.async_page_fault", "AsyncPageFault"),
+    ("memory.hibernate_idle_sec", "HibernateIdleSec"),
+    ("memory.zswap_max_pool_percent", "ZswapMaxPoolPercent"),
+    ("memory.ksm", "Ksm"),
+    ("memory.ksm_anon", "KsmAnon"),
+    ("file.buf_write", "FileBufWrite"),
+    ("file.dirty_background_ratio", "DirtyBackgroundRatio"),
+    ("file.dirty_ratio", "DirtyRatio"),
+    ("file.strict_durability", "StrictDurability"),
+    ("file.ephemeral_storage", "EphemeralStorage"),
+    ("file.readdir_cache", "ReaddirCache"),
+    ("file.posix_acl", "PosixAcl"),
+    ("file.inotify", "EnableInotify"),
+    ("file.prefetch_max_mb", "PrefetchMaxMb"),
+    ("runtime.reserve_cpu_count", "ReserveCpuCount"),
+    ("runtime.shim_mode", "ShimMode"),
+    ("runtime.qcall_latency_threads", "QcallLatencyThreads"),
+    ("runtime.qcall_bulk_threads", "QcallBulkThreads"),
+    ("runtime.builtin_init", "BuiltinInit"),
+    ("runtime.halt_poll_ns", "HaltPollNs"),
+    ("runtime.halt_poll_grow", "HaltPollGrow"),
+    ("runtime.halt_poll_shrink", "HaltPollShrink"),
+    ("runtime.tsc_deadline_timer", "TscDeadlineTimer"),
+    ("runtime.timer_dispatch", "TimerDispatch"),
+    ("runtime.usage_period_ms", "UsagePeriodMs"),
+    ("runtime.mitigations_off", "MitigationsOff"),
+    ("debug.print_exception", "PrintException"),
+    ("debug.audit_classes", "AuditClasses"),
+    ("debug.perf", "PerfDebug"),
+    ("debug.oops_kill", "OopsKill"),
+    ("debug.syscall_fuzz", "SyscallFuzz"),
+];
+
+// the keys of the settings of qvisor on the host, see HostConfig
+const HOST_KEYS: &[&str] = &[
+    "pcap.dir",
+    "cdi.allowed_devices",
+    "tls.cert",
+    "tls.key",
+    "filter.file",
+    "config.overridable",
+    "egress.rules",
+    "egress.dns",
+];
+
+// the keys a sandbox overrides when the config file doesn't list them
+pub const DEFAULT_OVERRIDABLE_KEYS: &[&str] = &[
+    "log.level",
+    "network.somaxconn",
+    "file.readdir_cache",
+    "file.inotify",
+];
+
+// the keys only the config file sets, the operator can't let a sandbox
+// override them
+const OPERATOR_ONLY_KEYS: &[&str] = &[
+    "memory.hibernate_idle_sec",
+    "memory.zswap_max_pool_percent",
+    "debug.audit_classes",
+    "memory.ksm",
+    "memory.ksm_anon",
+    "debug.syscall_fuzz",
+    "runtime.halt_poll_ns",
+    "runtime.halt_poll_grow",
+    "runtime.halt_poll_shrink",
+    "runtime.usage_period_ms",
+    "file.ephemeral_storage",
+    "runtime.mitigations_off",
+    "memory.high",
+    "network.checksum_offload",
+];
+
+fn FieldOfKey(key: &str) -> Result<&'static str> {
+    for (k, field) in CONFIG_KEYS {
+        if *k == key {
+            return Ok(field);
+        }
+    }
+
+    return Err(Error::Common(format!("config: unknown key {}", key)));
+}
+
+// the json of a toml scalar, Config is deserialized from json
+fn TomlToJson(key: &str, v: &toml::Value) -> Result<String> {
+    match v {
+        toml::Value::String(s) => return Ok(serde_json::to_string(s).unwrap()),
+        toml::Value::Integer(i) => return Ok(i.to_string()),
+        toml::Value::Float(f) => return Ok(f.to_string()),
+        toml::Value::Boolean(b) => return Ok(b.to_string()),
+        _ => {
+            return Err(Error::Common(format!(
+                "config: {} is not a string, a number or a bool",
+                key
+            )))
+        }
+    }
+}
+
+// Merge returns the config with the fields set to the json values.
+fn Merge(config: &Config, fields: &[(String, String)]) -> Result<Config> {
+    let json =
+        serde_json::to_string(config).map_err(|e| Error::Common(format!("config: {:?}", e)))?;
+    let mut value: serde_json::Value =
+        serde_json::from_str(&json).map_err(|e| Error::Common(format!("config: {:?}", e)))?;
+    match value {
+        serde_json::Value::Object(ref mut obj) => {
+            for (field, json) in fields {
+                let v = serde_json::from_str(json)
+                    .map_err(|e| Error::Common(format!("config: {} is {:?}", field, e)))?;
+                obj.insert(field.to_string(), v);
+            }
+        }
+        _ => return Err(Error::Common("config: not an object".to_string())),
+    }
+
+    let json =
+        serde_json::to_string(&value).map_err(|e| Error::Common(format!("config: {:?}", e)))?;
+    return serde_json::from_str(&json)
+        .map_err(|e| Error::Common(format!("config: invalid value {:?}", e)));
+}
+
+// ConfigFromJson returns the config of the old json file, the fields not in
+// the file keep their default.
+pub fn ConfigFromJson(contents: &str) -> Result<Config> {
+    let value: serde_json::Value =
+        serde_json::from_str(contents).map_err(|e| Error::Common(format!("config: {:?}", e)))?;
+    let obj = match value {
+        serde_json::Value::Object(obj) => obj,
+        _ => return Err(Error::Common("config: not an object".to_string())),
+    };
+
+    let mut fields = Vec::new();
+    for (field, v) in &obj {
+        let json =
+            serde_json::to_string(v).map_err(|e| Error::Common(format!("config: {:?}", e)))?;
+        fields.push((field.to_string(), json));
+    }
+
+    return Merge(&Config::default(), &fields);
+}
+
+// ConfigFromToml returns the config of the file content, over the default.
+pub fn ConfigFromToml(contents: &str) -> Result<Config> {
+    let table = contents
+        .parse::<toml::Value>()
+        .map_err(|e| Error::Common(format!("config: {}", e)))?;
+    let sections = match table.as_table() {
+        None => return Err(Error::Common("config: not a table".to_string())),
+        Some(t) => t,
+    };
+
+    let mut fields = Vec::new();
+    for (section, keys) in sections {
+        let keys = match keys.as_table() {
+            None => {
+                return Err(Error::Common(format!(
+                    "config: {} is not a section",
+                    section
+                )))
+            }
+            Some(t) => t,
+        };
+
+        for (key, v) in keys {
+            let key = format!("{}.{}", section, key);
+            if HOST_KEYS.contains(&key.as_str()) {
+                continue;
+            }
+            fields.push((FieldOfKey(&key)?.to_string(), TomlToJson(&key, v)?));
+        }
+    }
+
+    return Merge(&Config::default(), &fields);
+}
+
+// ApplyConfigAnnotations sets the config overrides of the sandbox, of the keys
+// in overridable.
+pub fn ApplyConfigAnnotations(
+    config: &mut Config,
+    spec: &Spec,
+    overridable: &[String],
+) -> Result<()> {
+    let mut fields = Vec::new();
+    for (name, v) in &spec.annotations {
+        if !name.starts_with(CONFIG_ANNOTATION_PREFIX) {
+            continue;
+        }
+
+        let key = &name[CONFIG_ANNOTATION_PREFIX.len()..];
+        if OPERATOR_ONLY_KEYS.contains(&key) || !overridable.iter().any(|k| k == key) {
+            return Err(Error::Common(format!(
+                "config: {} can't be overridden by a sandbox",
+                key
+            )));
+        }
+
+        // the value is a toml value, an unquoted string is taken as is
+        let value = match format!("v = {}", v).parse::<toml::Value>() {
+            Ok(t) => t["v"].clone(),
+            Err(_) => toml::Value::String(v.to_string()),
+        };
+
+        fields.push((FieldOfKey(key)?.to_string(), TomlToJson(name, &value)?));
+    }
+
+    if fields.len() == 0 {
+        return Ok(());
+    }
+
+    *config = Merge(config, &fields)?;
+    info!("config: sandbox overrides {:?}", fields);
+    return Ok(());
+}
+
+// The settings of qvisor on the host only, the host paths and the policies the
+// operator gives the sandboxes. They are not in Config, which is shared with
+// the guest, and a sandbox can't override them:
+//   [pcap]
+//   dir = "/var/log/quark/pcap"
+//   [cdi]
+//   allowed_devices = ["nvidia.com/gpu"]
+//   [tls]
+//   cert = "/etc/quark/tls/cert.pem"
+//   key = "/etc/quark/tls/key.pem"
+//   [filter]
+//   file = "/etc/quark/filter.bpf"
+//   [config]
+//   overridable = ["log.level", "network.somaxconn"]
+//   [egress]
+//   rules = "allow 10.0.0.0/8; allow host *.example.com port 443; deny all"
+//   dns = ["10.96.0.10"]
+#[derive(Debug, Clone)]
+pub struct HostConfig {
+    // PcapDir is the directory of the packet captures, empty disables them.
+    pub PcapDir: String,
+    // CdiAllowedDevices is the CDI devices, by qualified name or by kind, a
+    // sandbox may ask for with the cdi.k8s.io annotations.
+    pub CdiAllowedDevices: Vec<String>,
+    // TlsCert and TlsKey are the PEM certificate chain and private key of the
+    // TLS termination offload, empty disables it.
+    pub TlsCert: String,
+    pub TlsKey: String,
+    // FilterFile is the program of the packet filter of the sandboxes, empty
+    // disables it.
+    pub FilterFile: String,
+    // OverridableKeys is the keys of Config a sandbox may override with an
+    // annotation.
+    pub OverridableKeys: Vec<String>,
+    // EgressRules is the egress policy of the sandboxes, see
+    // vmspace/egress_policy.rs, and EgressDns the resolvers its host rules
+    // trust.
+    pub EgressRules: String,
+    pub EgressDns: Vec<String>,
+}
+
+impl Default for HostConfig {
+    fn default() -> Self {
+        return Self {
+            PcapDir: String::new(),
+            CdiAllowedDevices: Vec::new(),
+            TlsCert: String::new(),
+            TlsKey: String::new(),
+            FilterFile: String::new(),
+            OverridableKeys: DEFAULT_OVERRIDABLE_KEYS
+                .iter()
+                .map(|k| k.to_string())
+                .collect(),
+            EgressRules: String::new(),
+            EgressDns: Vec::new(),
+        };
+    }
+}
+
+impl HostConfig {
+    pub fn Load() -> Self {
+        let contents = match fs::read_to_string(CONFIG_TOML_FILE) {
+            Err(_) => return Self::default(),
+            Ok(c) => c,
+        };
+
+        return Self::FromToml(&contents).expect("configuration wrong format");
+    }
+
+    pub fn FromToml(contents: &str) -> Result<Self> {
+        let table = contents
+            .parse::<toml::Value>()
+            .map_err(|e| Error::Common(format!("config: {}", e)))?;
+
+        let mut config = Self::default();
+        if let Some(dir) = table.get("pcap").and_then(|s| s.get("dir")) {
+            config.PcapDir = TomlString("pcap.dir", dir)?;
+        }
+
+        if let Some(devices) = table.get("cdi").and_then(|s| s.get("allowed_devices")) {
+            config.CdiAllowedDevices = TomlStrings("cdi.allowed_devices", devices)?;
+        }
+
+        if let Some(cert) = table.get("tls").and_then(|s| s.get("cert")) {
+            config.TlsCert = TomlString("tls.cert", cert)?;
+        }
+
+        if let Some(key) = table.get("tls").and_then(|s| s.get("key")) {
+            config.TlsKey = TomlString("tls.key", key)?;
+        }
+
+        if let Some(file) = table.get("filter").and_then(|s| s.get("file")) {
+            config.FilterFile = TomlString("filter.file", file)?;
+        }
+
+        if let Some(keys) = table.get("config").and_then(|s| s.get("overridable")) {
+            let keys = TomlStrings("config.overridable", keys)?;
+            for key in &keys {
+                FieldOfKey(key)?;
+                if OPERATOR_ONLY_KEYS.contains(&key.as_str()) {
+                    return Err(Error::Common(format!(
+                        "config: {} can't be overridden by a sandbox",
+                        key
+                    )));
+                }
+            }
+            config.OverridableKeys = keys;
+        }
+
+        if let Some(rules) = table.get("egress").and_then(|s| s.get("rules")) {
+            config.EgressRules = TomlString("egress.rules", rules)?;
+        }
+
+        if let Some(dns) = table.get("egress").and_then(|s| s.get("dns")) {
+            config.EgressDns = TomlStrings("egress.dns", dns)?;
+        }
+
+        return Ok(config);
+    }
+}
+
+fn TomlString(key: &str, v: &toml::Value) -> Result<String> {
+    match v.as_str() {
+        None => return Err(Error::Common(format!("config: {} is not a string", key))),
+        Some(s) => return Ok(s.to_string()),
+    }
+}
+
+fn TomlStrings(key: &str, v: &toml::Value) -> Result<Vec<String>> {
+    let array = match v.as_array() {
+        None => return Err(Error::Common(format!("config: {} is not an array", key))),
+        Some(a) => a,
+    };
+
+    let mut strings = Vec::with_capacity(array.len());
+    for v in array {
+        strings.push(TomlString(key, v)?);
+    }
+
+    return Ok(strings);
+}
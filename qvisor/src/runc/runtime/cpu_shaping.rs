@@ -0,0 +1,160 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use kvm_bindings::CpuId;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::cpuid::*;
+use super::super::oci::*;
+
+// CPU shaping of the sandbox. The cpuid the vcpus see is filtered before it is
+// set in KVM, so the applications probing the cpu flags, e.g. the runtimes
+// picking AVX-512 code paths, see the same cpu on all the nodes. The guest
+// builds /proc/cpuinfo from the same cpuid, so its flags and model name
+// match. It is enabled per sandbox with annotations:
+//   quark.cpu.flags.disable: comma separated cpuinfo flags to hide, a flag
+//     ending with '*' hides all the flags with the prefix, e.g. "avx512*,rtm".
+//     xsave, osxsave and fsgsbase are needed by the guest kernel and rejected
+//   quark.cpu.model: the model name, at most 48 bytes
+pub const CPU_FLAGS_DISABLE_ANNOTATION: &str = "quark.cpu.flags.disable";
+pub const CPU_MODEL_ANNOTATION: &str = "quark.cpu.model";
+
+const BRAND_STRING_LEN: usize = 48;
+
+// the features the vcpus are set up with, CR4.OSXSAVE and CR4.FSGSBASE are set
+// at the boot and the guest kernel saves the fpu state with xsave, KVM rejects
+// the CR4 when the cpuid hides them
+const REQUIRED_FEATURES: [X86Feature; 3] = [
+    X86Feature::X86FeatureXSAVE,
+    X86Feature::X86FeatureOSXSAVE,
+    X86Feature::X86FeatureFSGSBase,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuShaping {
+    pub disabled: Vec<Feature>,
+    pub model: Option<String>,
+}
+
+impl CpuShaping {
+    pub fn FromSpec(spec: &Spec) -> Result<Option<Self>> {
+        let mut disabled = Vec::new();
+        if let Some(flags) = spec.annotations.get(CPU_FLAGS_DISABLE_ANNOTATION) {
+            for flag in flags.split(',').map(|f| f.trim()).filter(|f| f.len() > 0) {
+                let features = FeaturesByName(flag);
+                if features.len() == 0 {
+                    return Err(Error::Common(format!(
+                        "{}: unknown cpu flag {}",
+                        CPU_FLAGS_DISABLE_ANNOTATION, flag
+                    )));
+                }
+
+                for f in &features {
+                    if REQUIRED_FEATURES.iter().any(|r| *r as i32 == f.0) {
+                        return Err(Error::Common(format!(
+                            "{}: {} can't be disabled, the guest kernel needs it",
+                            CPU_FLAGS_DISABLE_ANNOTATION,
+                            f.String()
+                        )));
+                    }
+                }
+
+                disabled.extend(features);
+            }
+        }
+
+        let model = match spec.annotations.get(CPU_MODEL_ANNOTATION) {
+            None => None,
+            Some(m) => {
+                if m.len() > BRAND_STRING_LEN {
+                    return Err(Error::Common(format!(
+                        "{}: {} is longer than {} bytes",
+                        CPU_MODEL_ANNOTATION, m, BRAND_STRING_LEN
+                    )));
+                }
+
+                Some(m.to_string())
+            }
+        };
+
+        if disabled.len() == 0 && model.is_none() {
+            return Ok(None);
+        }
+
+        return Ok(Some(Self {
+            disabled: disabled,
+            model: model,
+        }));
+    }
+
+    // Apply filters the cpuid which is set in the vcpus.
+    pub fn Apply(&self, cpuid: &mut CpuId) {
+        let entries = cpuid.as_mut_slice();
+        for f in &self.disabled {
+            let (function, index, reg, mask) = match f.CpuidLocation() {
+                None => continue,
+                Some(loc) => loc,
+            };
+
+            let mut locs = vec![(function, index, reg, mask)];
+            // the AMD cpus duplicate some block 1 features in 0x80000001 edx
+            if function == 1 && reg == 3 && (mask & X86Feature::Block6DuplicateMask as u32) != 0 {
+                locs.push((ExtendedFunction::extendedFeatures as u32, 0, 3, mask));
+            }
+
+            for (function, index, reg, mask) in locs {
+                for e in entries.iter_mut() {
+                    if e.function != function || e.index != index {
+                        continue;
+                    }
+
+                    match reg {
+                        0 => e.eax &= !mask,
+                        1 => e.ebx &= !mask,
+                        2 => e.ecx &= !mask,
+                        _ => e.edx &= !mask,
+                    }
+                }
+            }
+
+            info!("cpu shaping: {} disabled", f.String());
+        }
+
+        if let Some(model) = &self.model {
+            let mut brand = [0u8; BRAND_STRING_LEN];
+            brand[..model.len()].copy_from_slice(model.as_bytes());
+            for e in entries.iter_mut() {
+                if e.function < BRAND_STRING_FUNCTION || e.function > BRAND_STRING_FUNCTION + 2 {
+                    continue;
+                }
+
+                let off = ((e.function - BRAND_STRING_FUNCTION) * 16) as usize;
+                let reg = |i: usize| -> u32 {
+                    let mut b = [0u8; 4];
+                    b.copy_from_slice(&brand[off + i * 4..off + i * 4 + 4]);
+                    return u32::from_le_bytes(b);
+                };
+
+                e.eax = reg(0);
+                e.ebx = reg(1);
+                e.ecx = reg(2);
+                e.edx = reg(3);
+            }
+
+            info!("cpu shaping: model name {}", model);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use super::super::super::qlib::common::*;
+use super::super::super::vmspace::teardown::*;
+use super::super::oci::*;
+use super::super::specutils::namespace::*;
+use super::qos::Interfaces;
+
+// The checksum offload of the interfaces of the sandbox. The guest sockets are
+// host sockets, so the checksums of the sandbox traffic are computed and
+// verified by the host stack in the network namespace of the sandbox. It does
+// them in software for an interface without the checksum offload, e.g. a veth
+// some CNI plugins set up with tx-checksumming off, and they show up high in
+// the profiles of the network heavy workloads. When the device is set up, the
+// tx and rx checksumming of each interface the sandbox asks for is negotiated
+// with its driver: an offload the driver supports is turned on, the host stack
+// leaves the checksum to the NIC then, and the one it refuses is kept in
+// software. The other interfaces keep the settings of the operator, and the
+// offloads turned on are turned off again at the teardown, the network
+// namespace may outlive the sandbox.
+//
+// The operator allows it with network.checksum_offload of the config, the
+// sandbox asks for it with the annotation:
+//   quark.network.checksum_offload: comma separated interface names
+pub const CHECKSUM_OFFLOAD_ANNOTATION: &str = "quark.network.checksum_offload";
+
+const SIOCETHTOOL: u64 = 0x8946;
+
+const ETHTOOL_GRXCSUM: u32 = 0x14;
+const ETHTOOL_SRXCSUM: u32 = 0x15;
+const ETHTOOL_GTXCSUM: u32 = 0x16;
+const ETHTOOL_STXCSUM: u32 = 0x17;
+
+#[repr(C)]
+struct EthtoolValue {
+    cmd: u32,
+    data: u32,
+}
+
+#[repr(C)]
+struct IfReq {
+    name: [u8; libc::IFNAMSIZ],
+    data: u64,
+    pad: [u8; 16],
+}
+
+// NegotiateChecksumOffload turns on the checksum offloads the drivers of the
+// interfaces of the annotation support. It is called from the sandbox process,
+// i.e. inside the network namespace. An interface the driver doesn't let
+// change stays as it is.
+pub fn NegotiateChecksumOffload(spec: &Spec) -> Result<()> {
+    let devs: Vec<String> = match spec.annotations.get(CHECKSUM_OFFLOAD_ANNOTATION) {
+        None => return Ok(()),
+        Some(devs) => devs
+            .split(',')
+            .map(|d| d.trim().to_string())
+            .filter(|d| d.len() > 0)
+            .collect(),
+    };
+
+    // without its own network namespace the sandbox uses the interfaces of
+    // the host, don't touch them
+    if GetNS(LinuxNamespaceType::network, spec).is_none() {
+        info!(
+            "checksum offload: the sandbox has no network namespace, {} is ignored",
+            CHECKSUM_OFFLOAD_ANNOTATION
+        );
+        return Ok(());
+    }
+
+    let interfaces = Interfaces();
+    for dev in &devs {
+        if !interfaces.contains(dev) {
+            return Err(Error::Common(format!(
+                "{}: no interface {} in the sandbox",
+                CHECKSUM_OFFLOAD_ANNOTATION, dev
+            )));
+        }
+    }
+
+    let sock = Socket()?;
+    // the offloads turned on, (interface, set command)
+    let mut changed = Vec::new();
+    for dev in devs {
+        let tx = Negotiate(sock, &dev, ETHTOOL_GTXCSUM, ETHTOOL_STXCSUM, &mut changed);
+        let rx = Negotiate(sock, &dev, ETHTOOL_GRXCSUM, ETHTOOL_SRXCSUM, &mut changed);
+        info!("checksum offload: {} tx {} rx {}", dev, tx, rx);
+    }
+
+    unsafe {
+        libc::close(sock);
+    }
+
+    if changed.len() > 0 {
+        TEARDOWN.Register("checksum offload", Box::new(move || Restore(&changed)));
+    }
+
+    return Ok(());
+}
+
+// Restore turns the offloads turned on by the negotiation off again.
+fn Restore(changed: &[(String, u32)]) {
+    let sock = match Socket() {
+        Ok(sock) => sock,
+        Err(e) => {
+            error!("checksum offload: restore fail {:?}", e);
+            return;
+        }
+    };
+
+    for (dev, set) in changed {
+        if let Err(e) = Ethtool(sock, dev, *set, 0) {
+            error!("checksum offload: {} restore {:x} fail {:?}", dev, set, e);
+        }
+    }
+
+    unsafe {
+        libc::close(sock);
+    }
+}
+
+fn Socket() -> Result<i32> {
+    let sock = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if sock < 0 {
+        return Err(Error::SysError(errno::errno().0));
+    }
+
+    return Ok(sock);
+}
+
+// Negotiate returns whether the offload of the get/set commands is on after
+// it is asked to be, the offload it turns on is added to changed.
+fn Negotiate(
+    sock: i32,
+    dev: &str,
+    get: u32,
+    set: u32,
+    changed: &mut Vec<(String, u32)>,
+) -> bool {
+    match Ethtool(sock, dev, get, 0) {
+        Err(e) => {
+            info!("checksum offload: {} get {:x} fail {:?}", dev, get, e);
+            return false;
+        }
+        Ok(0) => (),
+        Ok(_) => return true,
+    }
+
+    if let Err(e) = Ethtool(sock, dev, set, 1) {
+        info!("checksum offload: {} set {:x} fail {:?}", dev, set, e);
+        return false;
+    }
+
+    changed.push((dev.to_string(), set));
+
+    // the driver may take the request without turning it on
+    return Ethtool(sock, dev, get, 0).unwrap_or(0) != 0;
+}
+
+fn Ethtool(sock: i32, dev: &str, cmd: u32, data: u32) -> Result<u32> {
+    if dev.len() >= libc::IFNAMSIZ {
+        return Err(Error::SysError(libc::EINVAL));
+    }
+
+    let mut val = EthtoolValue {
+        cmd: cmd,
+        data: data,
+    };
+
+    let mut req = IfReq {
+        name: [0; libc::IFNAMSIZ],
+        data: &mut val as *mut _ as u64,
+        pad: [0; 16],
+    };
+    req.name[..dev.len()].copy_from_slice(dev.as_bytes());
+
+    let ret = unsafe { libc::ioctl(sock, SIOCETHTOOL, &mut req as *mut _ as u64) };
+    if ret < 0 {
+        return Err(Error::SysError(errno::errno().0));
+    }
+
+    return Ok(val.data);
+}
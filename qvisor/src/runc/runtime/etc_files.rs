@@ -0,0 +1,192 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::fs;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::loader::*;
+use super::super::oci::*;
+
+// Virtual /etc files. The /etc/resolv.conf, /etc/hosts and /etc/hostname of a
+// container are usually bind mounts of files the runtime writes when the
+// sandbox is created, so a change of the pod DNS config needs a restart. With
+// the virtualization the guest serves the three files from memory instead of
+// the bind mounts, the content is taken from the sources of the bind mounts or
+// built from the annotations, and `quark etc-files` replaces it at runtime.
+// It is enabled per sandbox with annotations:
+//   quark.etc.virtualize: "true" to enable
+//   quark.dns.nameservers: comma separated nameservers, e.g. "10.96.0.10"
+//   quark.dns.searches: comma separated search domains
+//   quark.dns.options: comma separated resolver options, e.g. "ndots:5"
+pub const ETC_VIRTUALIZE_ANNOTATION: &str = "quark.etc.virtualize";
+pub const DNS_NAMESERVERS_ANNOTATION: &str = "quark.dns.nameservers";
+pub const DNS_SEARCHES_ANNOTATION: &str = "quark.dns.searches";
+pub const DNS_OPTIONS_ANNOTATION: &str = "quark.dns.options";
+
+// The machine-id of the sandbox, the containers of an image with a baked
+// /etc/machine-id would share it and the ones of a distroless image have none.
+// It is set per sandbox with an annotation, also without the virtualization of
+// the other files:
+//   quark.machine-id: 32 lowercase hex characters
+pub const MACHINE_ID_ANNOTATION: &str = "quark.machine-id";
+
+pub fn EtcVirtualized(spec: &Spec) -> bool {
+    match spec.annotations.get(ETC_VIRTUALIZE_ANNOTATION) {
+        Some(v) => return v.as_str() == "true",
+        None => return false,
+    }
+}
+
+fn IsEtcFile(path: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    return path == ETC_RESOLV_CONF || path == ETC_HOSTS || path == ETC_HOSTNAME;
+}
+
+// IsVirtualEtcMount returns whether the mount is replaced by a virtual /etc
+// file, such a mount is not done on the host.
+pub fn IsVirtualEtcMount(spec: &Spec, m: &Mount) -> bool {
+    if m.typ.as_str() != "bind" {
+        return false;
+    }
+
+    if m.destination.trim_end_matches('/') == ETC_MACHINE_ID {
+        return spec.annotations.get(MACHINE_ID_ANNOTATION).is_some();
+    }
+
+    return IsEtcFile(&m.destination) && EtcVirtualized(spec);
+}
+
+// MachineId returns the content of the /etc/machine-id of the annotation.
+pub fn MachineId(spec: &Spec) -> Result<Option<String>> {
+    let id = match spec.annotations.get(MACHINE_ID_ANNOTATION) {
+        None => return Ok(None),
+        Some(id) => id.trim(),
+    };
+
+    // the format of machine-id(5), systemd refuses the other ones
+    let valid = id.len() == 32
+        && id
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+    if !valid || id.chars().all(|c| c == '0') {
+        return Err(Error::Common(format!(
+            "{}: invalid machine-id {:?}",
+            MACHINE_ID_ANNOTATION, id
+        )));
+    }
+
+    return Ok(Some(format!("{}\n", id)));
+}
+
+fn List(s: &str) -> Vec<&str> {
+    return s
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| v.len() > 0)
+        .collect();
+}
+
+// ResolvConf builds a resolv.conf, the nameservers are in the order given.
+pub fn ResolvConf(nameservers: &[&str], searches: &[&str], options: &[&str]) -> String {
+    let mut conf = String::new();
+    for ns in nameservers {
+        conf += &format!("nameserver {}\n", ns);
+    }
+
+    if searches.len() > 0 {
+        conf += &format!("search {}\n", searches.join(" "));
+    }
+
+    if options.len() > 0 {
+        conf += &format!("options {}\n", options.join(" "));
+    }
+
+    return conf;
+}
+
+pub fn Hosts(hostname: &str) -> String {
+    let mut hosts =
+        String::from("127.0.0.1\tlocalhost\n::1\tlocalhost ip6-localhost ip6-loopback\n");
+    if hostname.len() > 0 {
+        hosts += &format!("127.0.1.1\t{}\n", hostname);
+    }
+
+    return hosts;
+}
+
+fn ReadFile(path: &str) -> Result<String> {
+    return fs::read_to_string(path)
+        .map_err(|e| Error::IOError(format!("read {} fail {:?}", path, e)));
+}
+
+// BindSource returns the content of the source of the bind mount at dest.
+fn BindSource(spec: &Spec, dest: &str) -> Result<Option<String>> {
+    for m in &spec.mounts {
+        if m.typ.as_str() == "bind" && m.destination.trim_end_matches('/') == dest {
+            return Ok(Some(ReadFile(&m.source)?));
+        }
+    }
+
+    return Ok(None);
+}
+
+// EtcFilesFromSpec returns the virtual /etc files of the container, None if
+// they are not virtualized and there is no machine-id.
+pub fn EtcFilesFromSpec(spec: &Spec) -> Result<Option<EtcFiles>> {
+    let machineId = MachineId(spec)?;
+    if !EtcVirtualized(spec) {
+        return Ok(machineId.map(|id| EtcFiles {
+            MachineId: Some(id),
+            ..Default::default()
+        }));
+    }
+
+    let annotation = |name: &str| -> Vec<&str> {
+        match spec.annotations.get(name) {
+            None => Vec::new(),
+            Some(v) => List(v),
+        }
+    };
+
+    let nameservers = annotation(DNS_NAMESERVERS_ANNOTATION);
+    let resolvConf = if nameservers.len() > 0 {
+        Some(ResolvConf(
+            &nameservers,
+            &annotation(DNS_SEARCHES_ANNOTATION),
+            &annotation(DNS_OPTIONS_ANNOTATION),
+        ))
+    } else {
+        BindSource(spec, ETC_RESOLV_CONF)?
+    };
+
+    let hosts = match BindSource(spec, ETC_HOSTS)? {
+        Some(h) => h,
+        None => Hosts(&spec.hostname),
+    };
+
+    let hostname = match BindSource(spec, ETC_HOSTNAME)? {
+        Some(h) => Some(h),
+        None if spec.hostname.len() > 0 => Some(format!("{}\n", spec.hostname)),
+        None => None,
+    };
+
+    return Ok(Some(EtcFiles {
+        ResolvConf: resolvConf,
+        Hosts: Some(hosts),
+        Hostname: hostname,
+        MachineId: machineId,
+    }));
+}
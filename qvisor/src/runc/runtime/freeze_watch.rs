@@ -0,0 +1,96 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::thread;
+use std::time::Duration;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::kernel::kernel::timer::TIMER_STORE;
+use super::super::super::qlib::kernel::IOURING;
+use super::super::super::FD_NOTIFIER;
+use super::super::super::KERNEL_IO_THREAD;
+use super::super::super::SHARE_SPACE;
+use super::usage_page::ClockNs;
+use super::vm::IsRunning;
+
+// The external freeze of the sandbox. The cgroup freezer of the host, e.g. a
+// pause of the container runtime not going through quark, or a SIGSTOP stop
+// all the threads of qvisor at once, the guest gets no chance to quiesce its
+// io before. The host io goes on meanwhile: the data of the sockets and the
+// completions of the uring pile up, and on the thaw the timers of the guest,
+// which count the frozen time, are due together with them. The watch thread
+// ticks every FREEZE_TICK, a tick late by FREEZE_GAP tells the sandbox was
+// frozen. The timers are then held while the completions and the host events
+// of the freeze are processed, so that a timeout doesn't fire on a socket
+// whose data arrived meanwhile, and they fire once the io is quiet.
+const FREEZE_TICK_MS: u64 = 100;
+const FREEZE_GAP_NS: u64 = 1_000_000_000;
+
+// the time the timers are held at most, and the time without io after which
+// the io of the freeze is taken as processed
+const THAW_TIMEOUT_NS: u64 = 200_000_000;
+const THAW_IDLE_NS: u64 = 2_000_000;
+
+pub struct FreezeWatch {}
+
+impl FreezeWatch {
+    pub fn Start() -> Result<()> {
+        thread::Builder::new()
+            .name("freeze watch".to_string())
+            .spawn(move || {
+                let mut last = ClockNs(libc::CLOCK_MONOTONIC);
+                while IsRunning() {
+                    thread::sleep(Duration::from_millis(FREEZE_TICK_MS));
+                    let now = ClockNs(libc::CLOCK_MONOTONIC);
+                    let late = (now - last).saturating_sub(FREEZE_TICK_MS * 1_000_000);
+                    if late >= FREEZE_GAP_NS {
+                        info!("freeze watch: the sandbox was frozen for {} ms", late / 1_000_000);
+                        Self::Thaw();
+                    }
+
+                    last = ClockNs(libc::CLOCK_MONOTONIC);
+                }
+            })
+            .map_err(|e| Error::IOError(format!("freeze watch: spawn thread fail {:?}", e)))?;
+
+        return Ok(());
+    }
+
+    // Thaw processes the io of the freeze with the timers held.
+    fn Thaw() {
+        TIMER_STORE.Hold(true);
+
+        let start = ClockNs(libc::CLOCK_MONOTONIC);
+        let mut lastActive = start;
+        loop {
+            let count = IOURING.DrainCompletionQueue() + FD_NOTIFIER.HostEpollWait() as usize;
+            let now = ClockNs(libc::CLOCK_MONOTONIC);
+            if count > 0 {
+                lastActive = now;
+            } else if now - lastActive >= THAW_IDLE_NS {
+                break;
+            }
+
+            if now - start >= THAW_TIMEOUT_NS {
+                break;
+            }
+
+            thread::yield_now();
+        }
+
+        TIMER_STORE.Hold(false);
+        // the io thread fires the timers due meanwhile
+        KERNEL_IO_THREAD.Wakeup(&SHARE_SPACE);
+    }
+}
@@ -0,0 +1,865 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::os::unix::fs::FileExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::kernel::kernel::loadavg::RunnableTasks;
+use super::super::super::qlib::kernel::PAGE_MGR;
+use super::super::super::qlib::kernel::SHARESPACE;
+use super::super::super::qlib::linux_def::*;
+use super::super::super::ucall::ucall::*;
+use super::super::super::ucall::ucall_client::*;
+use super::super::super::vmspace::dirty_ring::DIRTY_RING;
+use super::super::super::vmspace::teardown::*;
+use super::super::cgroup::*;
+use super::usage_page::ClockNs;
+use super::vm::IsRunning;
+
+// Hibernation of an idle sandbox. When the sandbox has had no runnable task,
+// no task waiting for the host IO and next to no cpu time for HibernateIdleSec
+// seconds, qvisor pauses it, writes the pages of the guest page pool lz4
+// compressed to <HIBERNATE_DIR>/<sandbox id>, releases them and resumes it.
+// The pause only stops the vcpus, the host threads of qvisor, e.g. the rdma,
+// the tls proxy, the dns and the async socket completions, may still write
+// the guest memory. So the freezer cgroup of the sandbox is frozen from the
+// image till the release, with the hibernating thread moved out of it, and
+// the pages written meanwhile anyway, as told by their soft dirty bit, or not
+// read back as written are kept.
+// With the dirty ring tracking on, the image is pre-copied while the sandbox
+// still runs, and only the pages its vcpus wrote meanwhile, as harvested from
+// the dirty ring, and the ones allocated since are copied again with the
+// sandbox frozen.
+// The heap is registered with a userfaultfd first: the next touch of a
+// released page, by a vcpu or by the host, e.g. for an inbound connection or
+// an exec, faults it back in from the image, and the first such fault reads
+// the rest of the image back in the background. The pages of the heap never
+// touched are served as zero pages till then.
+pub const HIBERNATE_DIR: &str = "/var/lib/quark/hibernate";
+
+// the cpu time of qvisor per second under which the sandbox is idle
+const IDLE_CPU_NS: u64 = 10 * 1_000_000;
+
+const UFFD_API: u64 = 0xaa;
+const UFFDIO_API: u64 = 0xc018aa3f;
+const UFFDIO_REGISTER: u64 = 0xc020aa00;
+const UFFDIO_UNREGISTER: u64 = 0x8010aa01;
+const UFFDIO_COPY: u64 = 0xc028aa03;
+const UFFDIO_ZEROPAGE: u64 = 0xc020aa04;
+const UFFDIO_REGISTER_MODE_MISSING: u64 = 1;
+const UFFD_EVENT_PAGEFAULT: u8 = 0x12;
+const UFFD_MSG_SIZE: usize = 32;
+
+#[repr(C)]
+#[derive(Default)]
+struct UffdioApi {
+    api: u64,
+    features: u64,
+    ioctls: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct UffdioRange {
+    start: u64,
+    len: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct UffdioRegister {
+    range: UffdioRange,
+    mode: u64,
+    ioctls: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct UffdioCopy {
+    dst: u64,
+    src: u64,
+    len: u64,
+    mode: u64,
+    copy: i64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct UffdioZeropage {
+    range: UffdioRange,
+    mode: u64,
+    zeropage: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PageEntry {
+    pub addr: u64,
+    pub offset: u64,
+    // the compressed length, PAGE_SIZE for a page stored as is
+    pub len: u32,
+}
+
+// HibernateImage is the image of the released pages, sorted by address. The
+// fault handler uses it without allocating: an allocation could touch a
+// released page of the heap and wait for the handler itself.
+pub struct HibernateImage {
+    pub file: File,
+    pub path: String,
+    pub entries: Vec<PageEntry>,
+}
+
+impl HibernateImage {
+    pub fn Find(&self, addr: u64) -> Option<&PageEntry> {
+        return match self.entries.binary_search_by_key(&addr, |e| e.addr) {
+            Ok(i) => Some(&self.entries[i]),
+            Err(_) => None,
+        };
+    }
+
+    // Load reads the page of the entry from the image, it returns false if
+    // the page can't be read.
+    pub fn Load(&self, entry: &PageEntry, page: &mut [u8; MemoryDef::PAGE_SIZE as usize]) -> bool {
+        let mut compressed = [0u8; MemoryDef::PAGE_SIZE as usize];
+        let len = entry.len as usize;
+        if self
+            .file
+            .read_exact_at(&mut compressed[..len], entry.offset)
+            .is_err()
+        {
+            return false;
+        }
+
+        if len == page.len() {
+            page.copy_from_slice(&compressed);
+            return true;
+        }
+
+        return match lz4_flex::block::decompress_into(&compressed[..len], page) {
+            Ok(n) => n == page.len(),
+            Err(_) => false,
+        };
+    }
+
+    // Serve resolves the fault on the page at addr, it returns false if the
+    // page can't be read from the image.
+    pub fn Serve(&self, uffd: i32, addr: u64) -> bool {
+        let entry = match self.Find(addr) {
+            None => {
+                let mut zero = UffdioZeropage::default();
+                zero.range.start = addr;
+                zero.range.len = MemoryDef::PAGE_SIZE;
+                let ret = unsafe { libc::ioctl(uffd, UFFDIO_ZEROPAGE, &mut zero) };
+                return ret == 0 || errno::errno().0 == libc::EEXIST;
+            }
+            Some(entry) => entry,
+        };
+
+        let mut page = [0u8; MemoryDef::PAGE_SIZE as usize];
+        if !self.Load(entry, &mut page) {
+            return false;
+        }
+
+        let mut copy = UffdioCopy {
+            dst: addr,
+            src: &page[0] as *const _ as u64,
+            len: MemoryDef::PAGE_SIZE,
+            mode: 0,
+            copy: 0,
+        };
+        let ret = unsafe { libc::ioctl(uffd, UFFDIO_COPY, &mut copy) };
+        return ret == 0 || errno::errno().0 == libc::EEXIST;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum HibernateState {
+    Awake = 0,
+    Hibernated = 1,
+    Waking = 2,
+}
+
+#[derive(Default)]
+pub struct Hibernator {
+    pub state: AtomicU64,
+    // the sandbox is paused by a user, it is hibernated without a pause
+    pub userPaused: AtomicBool,
+    // the pause in progress is the one of the hibernation
+    pub selfPause: AtomicBool,
+    pub rehydrate: Mutex<Option<thread::Thread>>,
+}
+
+lazy_static! {
+    pub static ref HIBERNATOR: Hibernator = Hibernator::default();
+}
+
+impl Hibernator {
+    pub fn State(&self) -> HibernateState {
+        return match self.state.load(Ordering::Acquire) {
+            0 => HibernateState::Awake,
+            1 => HibernateState::Hibernated,
+            _ => HibernateState::Waking,
+        };
+    }
+
+    fn SetState(&self, state: HibernateState) {
+        self.state.store(state as u64, Ordering::Release);
+    }
+
+    // NotePause records a pause or a resume of the sandbox by a user.
+    pub fn NotePause(&self, paused: bool) {
+        if !self.selfPause.load(Ordering::Acquire) {
+            self.userPaused.store(paused, Ordering::Release);
+        }
+    }
+
+    // Wake starts reading the image back in, it doesn't wait for it.
+    pub fn Wake(&self) {
+        let woken = self
+            .state
+            .compare_exchange(
+                HibernateState::Hibernated as u64,
+                HibernateState::Waking as u64,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok();
+        if !woken {
+            return;
+        }
+
+        if let Some(t) = self.rehydrate.lock().as_ref() {
+            t.unpark();
+        }
+    }
+
+    // Start starts the thread watching the idleness of the sandbox.
+    pub fn Start(id: &str, idleSec: u64) -> Result<()> {
+        let id = id.to_string();
+        let path = format!("{}/{}", HIBERNATE_DIR, id);
+        TEARDOWN.Register(
+            "hibernate image",
+            Box::new(move || {
+                let _ = fs::remove_file(&path);
+            }),
+        );
+
+        thread::Builder::new()
+            .name("hibernate".to_string())
+            .spawn(move || {
+                let mut idle = 0;
+                let mut cpuTime = ClockNs(libc::CLOCK_PROCESS_CPUTIME_ID);
+                while IsRunning() {
+                    thread::sleep(Duration::from_secs(1));
+                    let now = ClockNs(libc::CLOCK_PROCESS_CPUTIME_ID);
+                    let busy = now - cpuTime >= IDLE_CPU_NS
+                        || RunnableTasks() > 0
+                        || SHARESPACE.stats.IoWaitTasks() > 0;
+                    cpuTime = now;
+
+                    if busy || HIBERNATOR.State() != HibernateState::Awake {
+                        idle = 0;
+                        continue;
+                    }
+
+                    idle += 1;
+                    if idle < idleSec {
+                        continue;
+                    }
+
+                    idle = 0;
+                    if let Err(e) = HIBERNATOR.Hibernate(&id) {
+                        info!("hibernate: sandbox {} not hibernated {:?}", &id, e);
+                    }
+                    cpuTime = ClockNs(libc::CLOCK_PROCESS_CPUTIME_ID);
+                }
+            })
+            .map_err(|e| Error::IOError(format!("hibernate: spawn thread fail {:?}", e)))?;
+
+        return Ok(());
+    }
+
+    fn Pause(&self, id: &str, pause: bool) -> Result<()> {
+        if self.userPaused.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        let req = if pause {
+            UCallReq::Pause
+        } else {
+            UCallReq::Unpause
+        };
+
+        self.selfPause.store(true, Ordering::Release);
+        let ret = UCallClient::Init(&ControlSocketAddr(id)).and_then(|client| client.Call(&req));
+        self.selfPause.store(false, Ordering::Release);
+        ret?;
+        return Ok(());
+    }
+
+    pub fn Hibernate(&self, id: &str) -> Result<()> {
+        let precopy = if DIRTY_RING.Enabled() {
+            Some(PreCopy::New(id)?)
+        } else {
+            None
+        };
+
+        if let Err(e) = self.Pause(id, true) {
+            if let Some(precopy) = precopy {
+                let _ = fs::remove_file(&precopy.image.path);
+            }
+            return Err(e);
+        }
+
+        let ret = self.HibernatePaused(id, precopy);
+        let resume = self.Pause(id, false);
+        ret?;
+        return resume;
+    }
+
+    fn HibernatePaused(&self, id: &str, precopy: Option<PreCopy>) -> Result<()> {
+        // a task waiting for the host IO may have a host op in flight on one
+        // of the pages
+        if SHARESPACE.stats.IoWaitTasks() > 0 {
+            if let Some(precopy) = precopy {
+                let _ = fs::remove_file(&precopy.image.path);
+            }
+            return Err(Error::Common("a task waits for the host IO".to_string()));
+        }
+
+        let addrs = HeapAddrs();
+        let freezer = match SandboxFreezer::Freeze() {
+            Ok(freezer) => freezer,
+            Err(e) => {
+                if let Some(precopy) = precopy {
+                    let _ = fs::remove_file(&precopy.image.path);
+                }
+                return Err(e);
+            }
+        };
+        let ret = ReleasePages(id, &addrs, precopy);
+        freezer.Thaw();
+        let (image, uffd, released) = ret?;
+
+        // the image holds the guest entropy pool
+        SHARESPACE.entropyEpoch.fetch_add(1, Ordering::AcqRel);
+
+        let image = Arc::new(image);
+        self.SetState(HibernateState::Hibernated);
+
+        let faultImage = image.clone();
+        let handler = thread::Builder::new()
+            .name("uffd".to_string())
+            .spawn(move || HandleFaults(uffd, &faultImage));
+        if let Err(e) = handler {
+            // the pages are read back here then, the faults raised meanwhile
+            // wait for the unregistration
+            RestoreAll(uffd, &image);
+            self.SetState(HibernateState::Awake);
+            Unregister(uffd);
+            let _ = fs::remove_file(&image.path);
+            return Err(Error::IOError(format!(
+                "hibernate: spawn thread fail {:?}",
+                e
+            )));
+        }
+
+        let rehydrateImage = image.clone();
+        let rehydrate = thread::Builder::new()
+            .name("rehydrate".to_string())
+            .spawn(move || Rehydrate(uffd, &rehydrateImage));
+        match rehydrate {
+            Ok(t) => *self.rehydrate.lock() = Some(t.thread().clone()),
+            Err(e) => {
+                // the fault handler unregisters the heap once awake
+                RestoreAll(uffd, &image);
+                self.SetState(HibernateState::Awake);
+                let _ = fs::remove_file(&image.path);
+                return Err(Error::IOError(format!(
+                    "hibernate: spawn thread fail {:?}",
+                    e
+                )));
+            }
+        }
+
+        info!(
+            "hibernate: sandbox {} released {} pages, {} pages in the image",
+            id,
+            released,
+            image.entries.len()
+        );
+        return Ok(());
+    }
+}
+
+// HeapAddrs returns the sorted addresses of the pages of the page pool in the
+// heap.
+fn HeapAddrs() -> Vec<u64> {
+    let heapStart = MemoryDef::HEAP_OFFSET;
+    let heapEnd = MemoryDef::HEAP_OFFSET + MemoryDef::HEAP_SIZE;
+    let mut addrs: Vec<u64> = PAGE_MGR
+        .AllocatedAddrs()
+        .into_iter()
+        .filter(|a| *a >= heapStart && *a < heapEnd)
+        .collect();
+    addrs.sort();
+    return addrs;
+}
+
+// PreCopy is the image of the pages at addrs written while the sandbox runs.
+struct PreCopy {
+    image: HibernateImage,
+    addrs: Vec<u64>,
+}
+
+impl PreCopy {
+    fn New(id: &str) -> Result<Self> {
+        // the pages written from now on are the ones harvested after the pause
+        DIRTY_RING.Harvest();
+        let addrs = HeapAddrs();
+        let image = WriteImage(id, &addrs)?;
+        return Ok(Self {
+            image: image,
+            addrs: addrs,
+        });
+    }
+
+    // Update brings the image up to date with the pages at addrs, the sandbox
+    // is frozen: the pages the vcpus wrote since the pre-copy and the ones
+    // allocated since are copied again. The pages the host wrote meanwhile
+    // are not seen by KVM, ReleasePages keeps the ones which don't match the
+    // image.
+    fn Update(self, addrs: &[u64]) -> Result<HibernateImage> {
+        let mut dirty = DIRTY_RING.Harvest();
+        dirty.sort();
+
+        let mut image = self.image;
+        let stale: Vec<u64> = addrs
+            .iter()
+            .filter(|a| dirty.binary_search(a).is_ok() || self.addrs.binary_search(a).is_err())
+            .cloned()
+            .collect();
+        image.entries.retain(|e| {
+            stale.binary_search(&e.addr).is_err() && addrs.binary_search(&e.addr).is_ok()
+        });
+
+        let offset = match image.file.metadata() {
+            Ok(m) => m.len(),
+            Err(e) => {
+                let _ = fs::remove_file(&image.path);
+                return Err(Error::IOError(format!(
+                    "hibernate: stat {} fail {:?}",
+                    &image.path, e
+                )));
+            }
+        };
+        let entries = WritePages(&image.file, &image.path, &stale, offset)?;
+        image.entries.extend(entries);
+        image.entries.sort_by_key(|e| e.addr);
+
+        info!(
+            "hibernate: {} of {} pages copied again after the pre-copy",
+            stale.len(),
+            addrs.len()
+        );
+        return Ok(image);
+    }
+}
+
+// SandboxFreezer freezes the freezer cgroup of the sandbox, the calling thread
+// is moved to a sibling cgroup till the thaw to go on meanwhile.
+struct SandboxFreezer {
+    path: String,
+    hibernatePath: String,
+    tid: i32,
+}
+
+impl SandboxFreezer {
+    pub fn Freeze() -> Result<Self> {
+        let paths = LoadPaths("self")?;
+        let path = match paths.get("freezer") {
+            Some(p) if p.trim_end_matches('/').len() > 0 => p.trim_end_matches('/').to_string(),
+            _ => {
+                return Err(Error::Common(
+                    "hibernate: the sandbox has no freezer cgroup".to_string(),
+                ))
+            }
+        };
+
+        let path = format!("{}/freezer{}", CGROUP_ROOT, path);
+        let hibernatePath = format!("{}-hibernate", path);
+        fs::create_dir_all(&hibernatePath).map_err(|e| {
+            Error::IOError(format!("hibernate: create {} fail {:?}", &hibernatePath, e))
+        })?;
+
+        let tid = unsafe { libc::gettid() };
+        SetValue(&hibernatePath, "tasks", &tid.to_string())?;
+        let freezer = Self {
+            path: path,
+            hibernatePath: hibernatePath,
+            tid: tid,
+        };
+
+        if let Err(e) = freezer.SetState("FROZEN") {
+            freezer.Thaw();
+            return Err(e);
+        }
+
+        return Ok(freezer);
+    }
+
+    fn SetState(&self, state: &str) -> Result<()> {
+        SetValue(&self.path, "freezer.state", state)?;
+
+        // the freezer stays in FREEZING till all the tasks are frozen
+        for i in 0..7 {
+            if GetValue(&self.path, "freezer.state")?.trim() == state {
+                return Ok(());
+            }
+
+            thread::sleep(Duration::from_millis(10 << i));
+        }
+
+        return Err(Error::Common(format!(
+            "hibernate: cgroup {} freezer doesn't reach {}",
+            &self.path, state
+        )));
+    }
+
+    pub fn Thaw(self) {
+        if let Err(e) = self.SetState("THAWED") {
+            error!("hibernate: thaw the sandbox fail {:?}", e);
+        }
+
+        if let Err(e) = SetValue(&self.path, "tasks", &self.tid.to_string()) {
+            error!("hibernate: move back to the sandbox cgroup fail {:?}", e);
+        }
+        let _ = fs::remove_dir(&self.hibernatePath);
+    }
+}
+
+// ReleasePages writes the image of the pages at addrs, or updates the pre-copy
+// of it, and releases them with the heap registered to the userfaultfd
+// returned, the sandbox is frozen. The pages written after the soft dirty bits
+// are cleared or not read back from the image as they are are kept and left
+// out of the image.
+fn ReleasePages(
+    id: &str,
+    addrs: &[u64],
+    precopy: Option<PreCopy>,
+) -> Result<(HibernateImage, i32, usize)> {
+    if let Err(e) = WriteFile("/proc/self/clear_refs", "4") {
+        if let Some(precopy) = precopy {
+            let _ = fs::remove_file(&precopy.image.path);
+        }
+        return Err(e);
+    }
+
+    let mut image = match precopy {
+        None => WriteImage(id, addrs)?,
+        Some(precopy) => precopy.Update(addrs)?,
+    };
+
+    let dirty = match SoftDirty(addrs) {
+        Ok(dirty) => dirty,
+        Err(e) => {
+            let _ = fs::remove_file(&image.path);
+            return Err(e);
+        }
+    };
+
+    let mut clean = Vec::with_capacity(addrs.len());
+    let mut entries = image.entries.iter().peekable();
+    let mut page = [0u8; MemoryDef::PAGE_SIZE as usize];
+    for (i, addr) in addrs.iter().enumerate() {
+        let entry = match entries.peek() {
+            Some(e) if e.addr == *addr => entries.next(),
+            _ => None,
+        };
+
+        if dirty[i] {
+            continue;
+        }
+
+        let mem =
+            unsafe { std::slice::from_raw_parts(*addr as *const u8, MemoryDef::PAGE_SIZE as usize) };
+        let ok = match entry {
+            // a pre-copied zero page may have been written by the host since
+            None => mem.iter().all(|b| *b == 0),
+            Some(entry) => image.Load(entry, &mut page) && &page[..] == mem,
+        };
+        if ok {
+            clean.push(*addr);
+        }
+    }
+    image
+        .entries
+        .retain(|e| clean.binary_search(&e.addr).is_ok());
+
+    let uffd = match RegisterHeap() {
+        Ok(uffd) => uffd,
+        Err(e) => {
+            let _ = fs::remove_file(&image.path);
+            return Err(e);
+        }
+    };
+
+    // the pages of the image are released, the zero ones are not in it
+    let mut released = 0;
+    let mut i = 0;
+    while i < clean.len() {
+        let mut j = i + 1;
+        while j < clean.len() && clean[j] == clean[j - 1] + MemoryDef::PAGE_SIZE {
+            j += 1;
+        }
+
+        let ret = unsafe {
+            libc::madvise(
+                clean[i] as *mut libc::c_void,
+                ((j - i) as u64 * MemoryDef::PAGE_SIZE) as usize,
+                libc::MADV_DONTNEED,
+            )
+        };
+        if ret == 0 {
+            released += j - i;
+        }
+        i = j;
+    }
+
+    return Ok((image, uffd, released));
+}
+
+// the soft dirty bit of an entry of /proc/self/pagemap
+const PM_SOFT_DIRTY: u64 = 1 << 55;
+
+// SoftDirty returns whether the pages at addrs have been written since the
+// soft dirty bits were cleared.
+fn SoftDirty(addrs: &[u64]) -> Result<Vec<bool>> {
+    let pagemap = File::open("/proc/self/pagemap")
+        .map_err(|e| Error::IOError(format!("hibernate: open pagemap fail {:?}", e)))?;
+
+    let mut dirty = Vec::with_capacity(addrs.len());
+    let mut entry = [0u8; 8];
+    for addr in addrs {
+        pagemap
+            .read_exact_at(&mut entry, *addr / MemoryDef::PAGE_SIZE * 8)
+            .map_err(|e| Error::IOError(format!("hibernate: read pagemap fail {:?}", e)))?;
+        dirty.push(u64::from_ne_bytes(entry) & PM_SOFT_DIRTY != 0);
+    }
+
+    return Ok(dirty);
+}
+
+fn WriteImage(id: &str, addrs: &[u64]) -> Result<HibernateImage> {
+    fs::create_dir_all(HIBERNATE_DIR)
+        .map_err(|e| Error::IOError(format!("hibernate: create {} fail {:?}", HIBERNATE_DIR, e)))?;
+
+    let path = format!("{}/{}", HIBERNATE_DIR, id);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)
+        .map_err(|e| Error::IOError(format!("hibernate: open {} fail {:?}", &path, e)))?;
+
+    let entries = WritePages(&file, &path, addrs, 0)?;
+    return Ok(HibernateImage {
+        file: file,
+        path: path,
+        entries: entries,
+    });
+}
+
+// WritePages writes the pages at addrs to the image file from offset and
+// returns their entries, the zero pages are left out. The file is removed if
+// the write fails.
+fn WritePages(file: &File, path: &str, addrs: &[u64], offset: u64) -> Result<Vec<PageEntry>> {
+    let pageSize = MemoryDef::PAGE_SIZE as usize;
+    let mut buf = vec![0u8; lz4_flex::block::get_maximum_output_size(pageSize)];
+    let mut entries = Vec::with_capacity(addrs.len());
+    let mut offset = offset;
+    for addr in addrs {
+        let page = unsafe { std::slice::from_raw_parts(*addr as *const u8, pageSize) };
+        if page.iter().all(|b| *b == 0) {
+            continue;
+        }
+
+        let data = match lz4_flex::block::compress_into(page, &mut buf) {
+            Ok(n) if n < pageSize => &buf[..n],
+            _ => page,
+        };
+
+        if let Err(e) = file.write_all_at(data, offset) {
+            let _ = fs::remove_file(path);
+            return Err(Error::IOError(format!(
+                "hibernate: write {} fail {:?}",
+                path, e
+            )));
+        }
+
+        entries.push(PageEntry {
+            addr: *addr,
+            offset: offset,
+            len: data.len() as u32,
+        });
+        offset += data.len() as u64;
+    }
+
+    return Ok(entries);
+}
+
+fn RegisterHeap() -> Result<i32> {
+    let uffd =
+        unsafe { libc::syscall(libc::SYS_userfaultfd, libc::O_CLOEXEC | libc::O_NONBLOCK) as i32 };
+    if uffd < 0 {
+        return Err(Error::SysError(errno::errno().0));
+    }
+
+    let mut api = UffdioApi {
+        api: UFFD_API,
+        ..Default::default()
+    };
+    let mut reg = UffdioRegister {
+        range: UffdioRange {
+            start: MemoryDef::HEAP_OFFSET,
+            len: MemoryDef::HEAP_SIZE,
+        },
+        mode: UFFDIO_REGISTER_MODE_MISSING,
+        ioctls: 0,
+    };
+
+    let ret = unsafe {
+        if libc::ioctl(uffd, UFFDIO_API, &mut api) < 0 {
+            -1
+        } else {
+            libc::ioctl(uffd, UFFDIO_REGISTER, &mut reg)
+        }
+    };
+    if ret < 0 {
+        let errno = errno::errno().0;
+        unsafe {
+            libc::close(uffd);
+        }
+        return Err(Error::SysError(errno));
+    }
+
+    return Ok(uffd);
+}
+
+fn Unregister(uffd: i32) {
+    let mut range = UffdioRange {
+        start: MemoryDef::HEAP_OFFSET,
+        len: MemoryDef::HEAP_SIZE,
+    };
+    unsafe {
+        libc::ioctl(uffd, UFFDIO_UNREGISTER, &mut range);
+        libc::close(uffd);
+    }
+}
+
+// HandleFaults serves the faults on the heap till the sandbox is awake, then
+// it unregisters the heap. The faults raised in between wait for the
+// unregistration and are handled by the host as the other ones.
+fn HandleFaults(uffd: i32, image: &HibernateImage) {
+    let mut msg = [0u8; UFFD_MSG_SIZE];
+    while HIBERNATOR.State() != HibernateState::Awake {
+        let mut pfd = libc::pollfd {
+            fd: uffd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ret = unsafe { libc::poll(&mut pfd, 1, 100) };
+        if ret <= 0 {
+            continue;
+        }
+
+        let n = unsafe { libc::read(uffd, &mut msg[0] as *mut _ as *mut libc::c_void, msg.len()) };
+        if n != UFFD_MSG_SIZE as isize || msg[0] != UFFD_EVENT_PAGEFAULT {
+            continue;
+        }
+
+        let mut addr = [0u8; 8];
+        addr.copy_from_slice(&msg[16..24]);
+        let addr = u64::from_ne_bytes(addr) & !(MemoryDef::PAGE_SIZE - 1);
+        if image.Find(addr).is_some() {
+            HIBERNATOR.Wake();
+        }
+
+        // the faulting thread can't go on without the page, the read is
+        // retried: the page was read back before it was released, so only
+        // the host IO fails here. Nothing is logged, that could allocate and
+        // fault on a released page of the heap itself.
+        while !image.Serve(uffd, addr) {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    Unregister(uffd);
+}
+
+// RestoreAll reads the whole image back in.
+fn RestoreAll(uffd: i32, image: &HibernateImage) {
+    for entry in &image.entries {
+        while !image.Serve(uffd, entry.addr) {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+// Rehydrate reads the image back in once the sandbox is woken.
+fn Rehydrate(uffd: i32, image: &HibernateImage) {
+    while HIBERNATOR.State() == HibernateState::Hibernated {
+        if !IsRunning() {
+            HIBERNATOR.SetState(HibernateState::Awake);
+            return;
+        }
+
+        thread::park_timeout(Duration::from_secs(1));
+    }
+
+    let start = ClockNs(libc::CLOCK_MONOTONIC);
+    for entry in &image.entries {
+        if !image.Serve(uffd, entry.addr) {
+            // the sandbox stays waking, the fault handler keeps serving the
+            // rest of the pages on demand
+            error!(
+                "hibernate: can't restore the page {:x}, the rest is restored on demand",
+                entry.addr
+            );
+            return;
+        }
+    }
+
+    HIBERNATOR.SetState(HibernateState::Awake);
+    let _ = fs::remove_file(&image.path);
+    info!(
+        "hibernate: {} pages restored in {} ms",
+        image.entries.len(),
+        (ClockNs(libc::CLOCK_MONOTONIC) - start) / 1_000_000
+    );
+}
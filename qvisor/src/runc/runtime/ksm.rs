@@ -0,0 +1,101 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::kernel::SHARESPACE;
+use super::super::super::qlib::mem::ksm::*;
+use super::super::super::QUARK_CONFIG;
+use super::vm::IsRunning;
+
+// The host side of the KSM hints of the guest, see qlib/mem/ksm.rs. The
+// thread marks the blocks the guest hinted MADV_MERGEABLE, and marks them
+// MADV_UNMERGEABLE again when ksm is turned off by a reconfig. ksmd only
+// scans the mergeable memory when /sys/kernel/mm/ksm/run is 1, the node
+// decides how much cpu it spends on the merging.
+const KSM_PERIOD: Duration = Duration::from_secs(1);
+const KSM_SYSFS: &str = "/sys/kernel/mm/ksm";
+
+pub struct KsmMarker {
+    // the blocks marked mergeable, a bit per block as the hints
+    pub merged: [u64; KSM_WORD_COUNT],
+}
+
+impl KsmMarker {
+    pub fn Start() -> Result<()> {
+        if !Path::new(KSM_SYSFS).exists() {
+            info!("ksm: the host kernel has no ksm, the hints are ignored");
+            return Ok(());
+        }
+
+        let mut marker = Self {
+            merged: [0; KSM_WORD_COUNT],
+        };
+
+        thread::Builder::new()
+            .name("ksm".to_string())
+            .spawn(move || {
+                while IsRunning() {
+                    marker.Update();
+                    thread::sleep(KSM_PERIOD);
+                }
+            })
+            .map_err(|e| Error::IOError(format!("ksm: spawn thread fail {:?}", e)))?;
+
+        return Ok(());
+    }
+
+    pub fn Update(&mut self) {
+        let enabled = {
+            let config = QUARK_CONFIG.lock();
+            config.Ksm || config.KsmAnon
+        };
+
+        for idx in 0..KSM_WORD_COUNT {
+            if enabled {
+                let new = SHARESPACE.ksm.Word(idx) & !self.merged[idx];
+                self.Advise(idx, new, libc::MADV_MERGEABLE);
+                self.merged[idx] |= new;
+            } else {
+                // the hints are dropped, the guest hints again once ksm is
+                // turned on
+                SHARESPACE.ksm.TakeWord(idx);
+                self.Advise(idx, self.merged[idx], libc::MADV_UNMERGEABLE);
+                self.merged[idx] = 0;
+            }
+        }
+    }
+
+    fn Advise(&self, idx: usize, bits: u64, advice: i32) {
+        let mut bits = bits;
+        while bits != 0 {
+            let bit = bits.trailing_zeros() as usize;
+            bits &= bits - 1;
+
+            let addr = KsmHints::BlockAddr(idx, bit);
+            let ret = unsafe { libc::madvise(addr as _, KSM_BLOCK_SIZE as usize, advice) };
+            if ret < 0 {
+                error!(
+                    "ksm: madvise {:x} advice {} fail {}",
+                    addr,
+                    advice,
+                    errno::errno().0
+                );
+            }
+        }
+    }
+}
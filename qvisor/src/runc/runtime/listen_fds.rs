@@ -0,0 +1,191 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::env;
+
+use super::super::super::qlib::common::*;
+
+// Socket activation. systemd, or whatever hands over pre-bound sockets in the
+// same way, starts quark with the listening sockets at fd 3, 4, ... and
+// LISTEN_FDS set to their count. The sockets are kept open through the boot
+// process and given to the root container process from fd 3 with LISTEN_FDS,
+// LISTEN_PID and LISTEN_FDNAMES set for it, so the application can use
+// sd_listen_fds(3) as it does on the host.
+pub const SD_LISTEN_FDS_START: i32 = 3;
+
+pub const LISTEN_FDS_ENV: &str = "LISTEN_FDS";
+pub const LISTEN_PID_ENV: &str = "LISTEN_PID";
+pub const LISTEN_FDNAMES_ENV: &str = "LISTEN_FDNAMES";
+
+// ListenFds returns the fds passed by the socket activation, it checks they
+// are sockets so a stale LISTEN_FDS inherited through the environment is
+// ignored. With checkPid the activation must be for this process, i.e.
+// LISTEN_PID is its pid, else the variables are left by an activated ancestor.
+pub fn ListenFds(checkPid: bool) -> Vec<i32> {
+    if checkPid {
+        let pid = unsafe { libc::getpid() };
+        match env::var(LISTEN_PID_ENV) {
+            Ok(p) if p.parse::<i32>() == Ok(pid) => (),
+            Ok(p) => {
+                if env::var(LISTEN_FDS_ENV).is_ok() {
+                    info!(
+                        "socket activation: {}={} is not this process {}, ignore {}",
+                        LISTEN_PID_ENV, p, pid, LISTEN_FDS_ENV
+                    );
+                }
+                return Vec::new();
+            }
+            Err(_) => {
+                if env::var(LISTEN_FDS_ENV).is_ok() {
+                    info!(
+                        "socket activation: no {}, ignore {}",
+                        LISTEN_PID_ENV, LISTEN_FDS_ENV
+                    );
+                }
+                return Vec::new();
+            }
+        }
+    }
+
+    let cnt = match env::var(LISTEN_FDS_ENV) {
+        Err(_) => return Vec::new(),
+        Ok(n) => match n.parse::<i32>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                error!("socket activation: invalid {}={}", LISTEN_FDS_ENV, n);
+                return Vec::new();
+            }
+        },
+    };
+
+    let fds: Vec<i32> = (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + cnt).collect();
+    for fd in &fds {
+        if !IsSocket(*fd) {
+            error!("socket activation: fd {} is not a socket, ignore {}", fd, LISTEN_FDS_ENV);
+            return Vec::new();
+        }
+    }
+
+    return fds;
+}
+
+fn IsSocket(fd: i32) -> bool {
+    let mut stat: libc::stat = unsafe { core::mem::zeroed() };
+    let ret = unsafe { libc::fstat(fd, &mut stat) };
+    return ret == 0 && (stat.st_mode & libc::S_IFMT) == libc::S_IFSOCK;
+}
+
+fn ClearListenEnv() {
+    env::remove_var(LISTEN_FDS_ENV);
+    env::remove_var(LISTEN_PID_ENV);
+    env::remove_var(LISTEN_FDNAMES_ENV);
+}
+
+// KeepListenFds clears FD_CLOEXEC of the activation sockets so they survive the
+// exec of the boot process. It runs in the process quark is started as, the
+// boot process has another pid and takes the sockets without the pid check,
+// the variables are cleared here when they are not for quark.
+pub fn KeepListenFds() -> Result<()> {
+    let fds = ListenFds(true);
+    if fds.len() == 0 {
+        ClearListenEnv();
+    }
+
+    for fd in fds {
+        let ret = unsafe { libc::fcntl(fd, libc::F_SETFD, 0) };
+        if ret < 0 {
+            return Err(Error::SysError(errno::errno().0));
+        }
+    }
+
+    return Ok(());
+}
+
+// TakeListenFds moves the activation sockets away from the low fd numbers, the
+// returned fds are close on exec and nonblocking as the other host sockets of
+// the guest. The guest takes the inet sockets only, a socket of another
+// family, e.g. an AF_UNIX one, is closed and left out with its name; the
+// names of the returned fds are returned with them.
+pub fn TakeListenFds() -> Result<(Vec<i32>, Option<String>)> {
+    let names: Option<Vec<String>> = env::var(LISTEN_FDNAMES_ENV)
+        .ok()
+        .map(|names| names.split(':').map(|n| n.to_string()).collect());
+    let mut fds = Vec::new();
+    let mut keptNames = Vec::new();
+    for (i, fd) in ListenFds(false).into_iter().enumerate() {
+        let family = Family(fd);
+        if family != libc::AF_INET && family != libc::AF_INET6 {
+            error!(
+                "socket activation: fd {} is of the family {}, only AF_INET and AF_INET6 sockets are supported, skip it",
+                fd, family
+            );
+            unsafe {
+                libc::close(fd);
+            }
+            continue;
+        }
+
+        if let Some(name) = names.as_ref().and_then(|names| names.get(i)) {
+            keptNames.push(name.clone());
+        }
+
+        let newfd = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+        if newfd < 0 {
+            return Err(Error::SysError(errno::errno().0));
+        }
+
+        unsafe {
+            let flags = libc::fcntl(newfd, libc::F_GETFL);
+            libc::fcntl(newfd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+            libc::close(fd);
+        }
+
+        fds.push(newfd);
+    }
+
+    // the fds are gone, don't let the environment point at them any more
+    ClearListenEnv();
+
+    if fds.len() > 0 {
+        info!("socket activation: take {} listening sockets", fds.len());
+    }
+
+    let names = match names {
+        Some(_) if keptNames.len() == fds.len() => Some(keptNames.join(":")),
+        _ => None,
+    };
+
+    return Ok((fds, names));
+}
+
+fn Family(fd: i32) -> i32 {
+    let mut family: i32 = -1;
+    let mut len = core::mem::size_of::<i32>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_DOMAIN,
+            &mut family as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret < 0 {
+        return -1;
+    }
+
+    return family;
+}
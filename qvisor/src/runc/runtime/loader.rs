@@ -46,7 +46,14 @@ pub struct Args {
 
     pub ControlSock: i32,
 
+    // ControlHostSock is the listen socket of the requests served on the host.
+    pub ControlHostSock: i32,
+
     pub Rootfs: String,
 
     pub RDMASvcCliSock: i32,
+
+    // EphemeralUsed is the bytes in the writable layer of the root filesystem
+    // at the boot.
+    pub EphemeralUsed: u64,
 }
@@ -0,0 +1,129 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::kernel::arch::x86_64::mitigations::*;
+use super::super::oci::*;
+
+// Speculative execution mitigations of the sandbox. Security sensitive
+// tenants opt into the stronger isolation, the others may opt out of the
+// speculation controls of the host for performance. It is set per sandbox
+// with the annotation quark.mitigations, a comma separated list of:
+//   ibpb: the guest does an IBPB when a vcpu switches to another address
+//     space, the host does one when it switches the vcpu threads out
+//   l1d-flush: the guest flushes the L1D before returning to the application,
+//     the host flushes it when it switches the vcpu threads out (the host
+//     kernel needs l1d_flush=on)
+//   mds: the guest clears the cpu buffers before returning to the application
+//   ssbd: speculative store bypass is disabled for the vcpu threads
+// The operator opts all the sandboxes out with runtime.mitigations_off of the
+// config, the speculation controls of the vcpu threads are then relaxed where
+// the host allows it, unless the annotation of a sandbox asks for some.
+pub const MITIGATIONS_ANNOTATION: &str = "quark.mitigations";
+
+const PR_SET_SPECULATION_CTRL: i32 = 53;
+const PR_SPEC_STORE_BYPASS: u64 = 0;
+const PR_SPEC_INDIRECT_BRANCH: u64 = 1;
+const PR_SPEC_L1D_FLUSH: u64 = 2;
+const PR_SPEC_ENABLE: u64 = 1 << 1;
+const PR_SPEC_FORCE_DISABLE: u64 = 1 << 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Mitigations {
+    // MITIGATION_* of the guest kernel
+    pub guest: u64,
+    pub ssbd: bool,
+    pub off: bool,
+}
+
+impl Mitigations {
+    pub fn FromSpec(spec: &Spec, off: bool) -> Result<Option<Self>> {
+        let v = match spec.annotations.get(MITIGATIONS_ANNOTATION) {
+            None if off => {
+                return Ok(Some(Self {
+                    off: true,
+                    ..Default::default()
+                }))
+            }
+            None => return Ok(None),
+            Some(v) => v,
+        };
+
+        let mut m = Self::default();
+        for name in v.split(',').map(|n| n.trim()).filter(|n| n.len() > 0) {
+            match name {
+                "ibpb" => m.guest |= MITIGATION_IBPB,
+                "l1d-flush" => m.guest |= MITIGATION_L1D_FLUSH,
+                "mds" => m.guest |= MITIGATION_MDS,
+                "ssbd" => m.ssbd = true,
+                "off" => {
+                    return Err(Error::Common(format!(
+                        "{}: off is set by the operator with runtime.mitigations_off",
+                        MITIGATIONS_ANNOTATION
+                    )))
+                }
+                _ => {
+                    return Err(Error::Common(format!(
+                        "{}: unknown mitigation {}",
+                        MITIGATIONS_ANNOTATION, name
+                    )))
+                }
+            }
+        }
+
+        return Ok(Some(m));
+    }
+
+    fn SetSpeculationCtrl(name: &str, which: u64, ctrl: u64) {
+        let ret = unsafe { libc::prctl(PR_SET_SPECULATION_CTRL, which, ctrl, 0, 0) };
+        if ret < 0 {
+            info!(
+                "mitigations: set {} speculation control {:x} fail {}",
+                name,
+                ctrl,
+                errno::errno().0
+            );
+        }
+    }
+
+    // ApplyHost sets the speculation controls of the qvisor process, the vcpu
+    // threads created after it inherit them. The host may not allow the
+    // control, e.g. with the mitigation forced on, it is then left as is.
+    pub fn ApplyHost(&self) {
+        if self.off {
+            Self::SetSpeculationCtrl("indirect branch", PR_SPEC_INDIRECT_BRANCH, PR_SPEC_ENABLE);
+            Self::SetSpeculationCtrl("store bypass", PR_SPEC_STORE_BYPASS, PR_SPEC_ENABLE);
+            info!("mitigations: off");
+            return;
+        }
+
+        if self.guest & MITIGATION_IBPB != 0 {
+            Self::SetSpeculationCtrl(
+                "indirect branch",
+                PR_SPEC_INDIRECT_BRANCH,
+                PR_SPEC_FORCE_DISABLE,
+            );
+        }
+
+        if self.guest & MITIGATION_L1D_FLUSH != 0 {
+            Self::SetSpeculationCtrl("l1d flush", PR_SPEC_L1D_FLUSH, PR_SPEC_ENABLE);
+        }
+
+        if self.ssbd {
+            Self::SetSpeculationCtrl("store bypass", PR_SPEC_STORE_BYPASS, PR_SPEC_FORCE_DISABLE);
+        }
+
+        info!("mitigations: {:?}", self);
+    }
+}
@@ -12,10 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod async_pf;
+pub mod audit;
+pub mod block_volume;
+pub mod cdi;
+pub mod config_file;
 pub mod console;
+pub mod cpu_shaping;
+pub mod csum_offload;
+pub mod etc_files;
+pub mod freeze_watch;
 pub mod fs;
+pub mod hibernate;
+pub mod ksm;
+pub mod listen_fds;
 pub mod loader;
+pub mod mitigations;
+pub mod net_sysctl;
+pub mod pcap;
+pub mod prefetch;
+pub mod qos;
 pub mod sandbox_process;
+pub mod shm;
 pub mod signal_handle;
+pub mod tls_proxy;
+pub mod tsc_timer;
+pub mod usage_page;
 pub mod util;
 pub mod vm;
@@ -0,0 +1,70 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+
+use super::super::super::qlib::config::*;
+use super::super::oci::*;
+use super::super::specutils::namespace::*;
+
+// The TCP listeners of the sandbox are host sockets, the host stack of the
+// network namespace of the sandbox takes their SYNs. A SYN flood fills the
+// SYN queue of a listener and then the accept backlog, which the guest
+// bounds as the host does: the guest stops to accept when its accept queue
+// of the listener is full. The SYN cookies of the namespace keep taking the
+// new connections then without state, the sandbox degrades instead of
+// dropping them or holding memory for each one.
+const TCP_SYNCOOKIES: &str = "/proc/sys/net/ipv4/tcp_syncookies";
+const SOMAXCONN: &str = "/proc/sys/net/core/somaxconn";
+
+// SetListenSysctls sets the SYN cookies and the somaxconn of the sandbox
+// network namespace, and then the net sysctls of spec.linux.sysctl, so that
+// the ones of the spec win over the defaults of the config. It is called from
+// the sandbox process, i.e. inside the network namespace, a sysctl which can't
+// be set is kept.
+pub fn SetListenSysctls(spec: &Spec, config: &Config) {
+    // without its own network namespace the sandbox uses the one of the
+    // host, don't touch it
+    if GetNS(LinuxNamespaceType::network, spec).is_none() {
+        return;
+    }
+
+    // 1 sends the cookies when the SYN queue overflows
+    if config.SynCookies {
+        SetSysctl(TCP_SYNCOOKIES, "1");
+    }
+
+    if config.SoMaxConn > 0 {
+        SetSysctl(SOMAXCONN, &config.SoMaxConn.to_string());
+    }
+
+    // only the net sysctls are per network namespace, the others are left
+    // to the guest
+    let linux = spec.linux.as_ref().unwrap();
+    for (key, val) in &linux.sysctl {
+        if !key.starts_with("net.") || key.contains('/') || key.contains("..") {
+            continue;
+        }
+
+        let path = format!("/proc/sys/{}", key.replace('.', "/"));
+        SetSysctl(&path, val);
+    }
+}
+
+fn SetSysctl(path: &str, val: &str) {
+    match fs::write(path, val) {
+        Ok(()) => info!("sysctl: {} = {}", path, val),
+        Err(e) => info!("sysctl: set {} to {} fail {:?}", path, val, e),
+    }
+}
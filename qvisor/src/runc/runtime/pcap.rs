@@ -0,0 +1,352 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::io::FromRawFd;
+use std::thread;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use super::super::super::qlib::common::*;
+use super::super::oci::*;
+use super::super::super::HOST_CONFIG;
+use super::vm::IsRunning;
+
+// The sandbox sockets are host sockets in the network namespace of the sandbox,
+// so the packets are captured there with an AF_PACKET socket and streamed as
+// pcapng to a file in the capture directory of the operator, pcap.dir of the
+// config file. It is enabled per sandbox with annotations:
+//   quark.pcap.file: name of the capture file in the capture directory
+//   quark.pcap.snaplen: max bytes kept per packet, default 65535
+// An existing capture file is truncated, e.g. when the sandbox restarts, but
+// it has to be a regular file.
+pub const PCAP_FILE_ANNOTATION: &str = "quark.pcap.file";
+pub const PCAP_SNAPLEN_ANNOTATION: &str = "quark.pcap.snaplen";
+
+const DEFAULT_SNAPLEN: u32 = 65535;
+
+const BLOCK_SHB: u32 = 0x0A0D0D0A;
+const BLOCK_IDB: u32 = 0x00000001;
+const BLOCK_EPB: u32 = 0x00000006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_IF_NAME: u16 = 2;
+
+const LINKTYPE_ETHERNET: u16 = 1;
+const LINKTYPE_RAW: u16 = 101;
+
+const ARPHRD_ETHER: u16 = 1;
+const ARPHRD_LOOPBACK: u16 = 772;
+
+#[derive(Debug)]
+pub struct PcapCapture {
+    pub file: String,
+    pub snaplen: u32,
+}
+
+impl PcapCapture {
+    pub fn FromSpec(spec: &Spec) -> Result<Option<Self>> {
+        let file = match spec.annotations.get(PCAP_FILE_ANNOTATION) {
+            None => return Ok(None),
+            Some(f) => f.to_string(),
+        };
+
+        if HOST_CONFIG.PcapDir.len() == 0 {
+            return Err(Error::Common(format!(
+                "{} is set but there is no capture directory",
+                PCAP_FILE_ANNOTATION
+            )));
+        }
+
+        if file.len() == 0 || file == "." || file == ".." || file.contains('/') {
+            return Err(Error::Common(format!(
+                "invalid {} value {}",
+                PCAP_FILE_ANNOTATION, file
+            )));
+        }
+
+        let snaplen = match spec.annotations.get(PCAP_SNAPLEN_ANNOTATION) {
+            None => DEFAULT_SNAPLEN,
+            Some(s) => match s.parse::<u32>() {
+                Ok(n) if n > 0 => n,
+                _ => {
+                    return Err(Error::Common(format!(
+                        "invalid {} value {}",
+                        PCAP_SNAPLEN_ANNOTATION, s
+                    )))
+                }
+            },
+        };
+
+        return Ok(Some(Self {
+            file: file,
+            snaplen: snaplen,
+        }));
+    }
+
+    // Open creates or truncates the capture file in the capture directory,
+    // without following a symlink. The open doesn't block on a fifo, which is
+    // then refused as any file which is not a regular file.
+    fn Open(&self) -> Result<File> {
+        let dir = CString::new(HOST_CONFIG.PcapDir.as_str())
+            .map_err(|e| Error::Common(format!("{:?}", e)))?;
+        let name =
+            CString::new(self.file.as_str()).map_err(|e| Error::Common(format!("{:?}", e)))?;
+
+        let dirfd = unsafe {
+            libc::open(
+                dir.as_ptr(),
+                libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC,
+            )
+        };
+        if dirfd < 0 {
+            return Err(Error::SysError(errno::errno().0));
+        }
+
+        let fd = unsafe {
+            libc::openat(
+                dirfd,
+                name.as_ptr(),
+                libc::O_WRONLY
+                    | libc::O_CREAT
+                    | libc::O_NOFOLLOW
+                    | libc::O_NONBLOCK
+                    | libc::O_CLOEXEC,
+                0o600,
+            )
+        };
+        let errno = errno::errno().0;
+        unsafe {
+            libc::close(dirfd);
+        }
+
+        if fd < 0 {
+            return Err(Error::SysError(errno));
+        }
+
+        let file = unsafe { File::from_raw_fd(fd) };
+        let meta = file
+            .metadata()
+            .map_err(|e| Error::IOError(format!("pcap: stat {} fail {:?}", &self.file, e)))?;
+        if !meta.file_type().is_file() {
+            return Err(Error::Common(format!(
+                "pcap: {} is not a regular file",
+                &self.file
+            )));
+        }
+
+        // the file is truncated only once it is known to be a regular file
+        file.set_len(0)
+            .map_err(|e| Error::IOError(format!("pcap: truncate {} fail {:?}", &self.file, e)))?;
+
+        return Ok(file);
+    }
+
+    // Start opens the capture file and the capture socket and streams the
+    // packets from a thread.
+    pub fn Start(self) -> Result<()> {
+        let out = self.Open()?;
+        let proto = (libc::ETH_P_ALL as u16).to_be() as i32;
+        let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW | libc::SOCK_CLOEXEC, proto) };
+        if fd < 0 {
+            return Err(Error::SysError(errno::errno().0));
+        }
+
+        // wake up every second to check whether the sandbox is still running
+        let timeout = libc::timeval {
+            tv_sec: 1,
+            tv_usec: 0,
+        };
+        unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const _ as *const libc::c_void,
+                core::mem::size_of::<libc::timeval>() as u32,
+            );
+        }
+
+        info!("pcap: capture sandbox traffic to {}", &self.file);
+        thread::Builder::new()
+            .name("pcap".to_string())
+            .spawn(move || {
+                if let Err(e) = self.Capture(fd, out) {
+                    error!("pcap: capture to {} stopped {:?}", &self.file, e);
+                }
+                unsafe {
+                    libc::close(fd);
+                }
+            })
+            .map_err(|e| Error::IOError(format!("pcap: spawn thread fail {:?}", e)))?;
+
+        return Ok(());
+    }
+
+    fn Capture(&self, fd: i32, mut out: File) -> Result<()> {
+        let mut writer = PcapngWriter::default();
+        let mut buf = vec![0u8; self.snaplen as usize];
+        out.write_all(&writer.SectionHeader())
+            .map_err(|e| Error::IOError(format!("{:?}", e)))?;
+
+        while IsRunning() {
+            let mut addr: libc::sockaddr_ll = unsafe { core::mem::zeroed() };
+            let mut addrlen = core::mem::size_of::<libc::sockaddr_ll>() as u32;
+            // MSG_TRUNC returns the real length of the packet
+            let len = unsafe {
+                libc::recvfrom(
+                    fd,
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    libc::MSG_TRUNC,
+                    &mut addr as *mut _ as *mut libc::sockaddr,
+                    &mut addrlen,
+                )
+            };
+
+            if len < 0 {
+                let errno = errno::errno().0;
+                if errno == libc::EAGAIN || errno == libc::EINTR {
+                    continue;
+                }
+                return Err(Error::SysError(errno));
+            }
+
+            let origLen = len as usize;
+            let capLen = if origLen > buf.len() { buf.len() } else { origLen };
+
+            let mut block = Vec::new();
+            writer.Interface(&mut block, addr.sll_ifindex, addr.sll_hatype, self.snaplen);
+            writer.Packet(&mut block, addr.sll_ifindex, &buf[..capLen], origLen);
+            out.write_all(&block)
+                .map_err(|e| Error::IOError(format!("{:?}", e)))?;
+        }
+
+        return Ok(());
+    }
+}
+
+// PcapngWriter encodes the pcapng blocks, an interface description block is
+// written the first time a packet of the interface is seen.
+#[derive(Default)]
+pub struct PcapngWriter {
+    interfaces: HashMap<i32, u32>,
+}
+
+fn PutU16(buf: &mut Vec<u8>, v: u16) {
+    buf.extend_from_slice(&v.to_ne_bytes());
+}
+
+fn PutU32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_ne_bytes());
+}
+
+fn Pad(buf: &mut Vec<u8>) {
+    while buf.len() % 4 != 0 {
+        buf.push(0);
+    }
+}
+
+// Block wraps the body with the block type and the two block lengths.
+fn Block(typ: u32, body: &[u8]) -> Vec<u8> {
+    let len = (12 + body.len()) as u32;
+    let mut buf = Vec::with_capacity(len as usize);
+    PutU32(&mut buf, typ);
+    PutU32(&mut buf, len);
+    buf.extend_from_slice(body);
+    PutU32(&mut buf, len);
+    return buf;
+}
+
+impl PcapngWriter {
+    pub fn SectionHeader(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        PutU32(&mut body, BYTE_ORDER_MAGIC);
+        PutU16(&mut body, 1);
+        PutU16(&mut body, 0);
+        // the section length is unknown
+        body.extend_from_slice(&(-1i64).to_ne_bytes());
+        return Block(BLOCK_SHB, &body);
+    }
+
+    pub fn Interface(&mut self, out: &mut Vec<u8>, ifindex: i32, hatype: u16, snaplen: u32) {
+        if self.interfaces.contains_key(&ifindex) {
+            return;
+        }
+
+        let id = self.interfaces.len() as u32;
+        self.interfaces.insert(ifindex, id);
+
+        // loopback frames have a zeroed ethernet header, tun devices have none
+        let linkType = match hatype {
+            ARPHRD_ETHER | ARPHRD_LOOPBACK => LINKTYPE_ETHERNET,
+            _ => LINKTYPE_RAW,
+        };
+
+        let mut body = Vec::new();
+        PutU16(&mut body, linkType);
+        PutU16(&mut body, 0);
+        PutU32(&mut body, snaplen);
+
+        let name = IfName(ifindex);
+        if name.len() > 0 {
+            PutU16(&mut body, OPT_IF_NAME);
+            PutU16(&mut body, name.len() as u16);
+            body.extend_from_slice(name.as_bytes());
+            Pad(&mut body);
+            PutU16(&mut body, OPT_ENDOFOPT);
+            PutU16(&mut body, 0);
+        }
+
+        out.extend_from_slice(&Block(BLOCK_IDB, &body));
+    }
+
+    pub fn Packet(&self, out: &mut Vec<u8>, ifindex: i32, data: &[u8], origLen: usize) {
+        let id = self.interfaces[&ifindex];
+
+        // the default timestamp resolution is microsecond
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        let mut body = Vec::with_capacity(20 + data.len() + 3);
+        PutU32(&mut body, id);
+        PutU32(&mut body, (now >> 32) as u32);
+        PutU32(&mut body, now as u32);
+        PutU32(&mut body, data.len() as u32);
+        PutU32(&mut body, origLen as u32);
+        body.extend_from_slice(data);
+        Pad(&mut body);
+
+        out.extend_from_slice(&Block(BLOCK_EPB, &body));
+    }
+}
+
+fn IfName(ifindex: i32) -> String {
+    let mut name = [0u8; libc::IF_NAMESIZE];
+    let ret = unsafe { libc::if_indextoname(ifindex as u32, name.as_mut_ptr() as *mut libc::c_char) };
+    if ret.is_null() {
+        return String::new();
+    }
+
+    let len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+    return String::from_utf8_lossy(&name[..len]).to_string();
+}
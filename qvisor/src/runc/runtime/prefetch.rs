@@ -0,0 +1,542 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use lazy_static::lazy_static;
+use spin::Mutex;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::fs;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::thread;
+use std::time::Instant;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::loader::Process;
+use super::super::super::QUARK_CONFIG;
+use super::super::oci::*;
+
+// The read ahead of the entrypoint of a container at its creation. The file
+// pages the guest maps or reads are the ones of the host page cache, so a
+// thread of qvisor reads ahead the files the process loads first while the
+// guest boots or the container is set up:
+// - the entrypoint, found with the PATH of the process;
+// - the interpreter of a script entrypoint;
+// - its interpreter and the libraries it needs, recursively;
+// - the files of the JVM under JAVA_HOME;
+// - the files and the directories of the quark.prefetch annotation, a ':'
+//   separated list of paths in the container, for the other runtimes.
+// The paths are resolved in the root of the container. PrefetchMaxMb in the
+// config bounds the bytes read ahead, 0 turns it off.
+pub const PREFETCH_ANNOTATION: &str = "quark.prefetch";
+
+const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+// the default search dirs of the dynamic linker, the ld.so.cache isn't read
+const LIB_DIRS: [&str; 7] = [
+    "/lib/x86_64-linux-gnu",
+    "/usr/lib/x86_64-linux-gnu",
+    "/lib64",
+    "/usr/lib64",
+    "/lib",
+    "/usr/lib",
+    "/usr/local/lib",
+];
+
+// the files of a JAVA_HOME the JVM loads at its start
+const JAVA_FILES: [&str; 3] = ["lib/libjli.so", "lib/server/libjvm.so", "lib/modules"];
+
+const RESOLVE_NO_MAGICLINKS: u64 = 0x02;
+const RESOLVE_IN_ROOT: u64 = 0x10;
+
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+const PT_LOAD: u32 = 1;
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_STRTAB: u64 = 5;
+const DT_RPATH: u64 = 15;
+const DT_RUNPATH: u64 = 29;
+
+lazy_static! {
+    // the dir the roots of the containers are in, "/" once qvisor pivoted
+    pub static ref CONTAINERS_DIR: Mutex<String> = Mutex::new("/".to_string());
+}
+
+#[repr(C)]
+struct OpenHow {
+    flags: u64,
+    mode: u64,
+    resolve: u64,
+}
+
+pub fn PrefetchFromSpec(spec: &Spec) -> Vec<String> {
+    match spec.annotations.get(PREFETCH_ANNOTATION) {
+        None => return Vec::new(),
+        Some(v) => {
+            return v
+                .split(':')
+                .map(|p| p.trim())
+                .filter(|p| p.len() > 0)
+                .map(|p| p.to_string())
+                .collect()
+        }
+    }
+}
+
+pub struct Prefetcher {
+    pub root: File,
+    // the bytes left to read ahead
+    pub budget: u64,
+    // the candidates of the entrypoint, the first one found is the one
+    pub cmds: Vec<String>,
+    pub libs: VecDeque<String>,
+    pub paths: Vec<String>,
+    // the (dev, ino) of the files read ahead
+    pub seen: BTreeSet<(u64, u64)>,
+    pub files: usize,
+}
+
+impl Prefetcher {
+    // Start reads ahead the files of the process in the background.
+    pub fn Start(process: &Process) {
+        let maxMb = QUARK_CONFIG.lock().PrefetchMaxMb;
+        if maxMb == 0 {
+            return;
+        }
+
+        let dir = CONTAINERS_DIR.lock().clone();
+        let root = format!("{}/{}", dir.trim_end_matches('/'), &process.ID);
+        let root = match OpenRoot(&root) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("prefetch: open the root {} fail {:?}", &root, e);
+                return;
+            }
+        };
+
+        let mut prefetcher = Self {
+            root: root,
+            budget: maxMb << 20,
+            cmds: Vec::new(),
+            libs: VecDeque::new(),
+            paths: Vec::new(),
+            seen: BTreeSet::new(),
+            files: 0,
+        };
+        prefetcher.AddProcess(process);
+
+        let id = process.ID.clone();
+        let ret = thread::Builder::new()
+            .name("prefetch".to_string())
+            .spawn(move || {
+                let start = Instant::now();
+                prefetcher.Run();
+                info!(
+                    "prefetch: {} files of {} in {:?}",
+                    prefetcher.files,
+                    &id,
+                    start.elapsed()
+                );
+            });
+        if let Err(e) = ret {
+            error!("prefetch: spawn thread fail {:?}", e);
+        }
+    }
+
+    fn AddProcess(&mut self, process: &Process) {
+        let env = |name: &str| -> Option<String> {
+            let prefix = format!("{}=", name);
+            for e in &process.Envs {
+                if e.starts_with(&prefix) {
+                    return Some(e[prefix.len()..].to_string());
+                }
+            }
+            return None;
+        };
+
+        if let Some(cmd) = process.Args.get(0) {
+            if cmd.starts_with('/') {
+                self.cmds.push(cmd.to_string());
+            } else if cmd.contains('/') {
+                self.cmds
+                    .push(format!("{}/{}", process.Cwd.trim_end_matches('/'), cmd));
+            } else {
+                let path = env("PATH").unwrap_or(DEFAULT_PATH.to_string());
+                for dir in path.split(':').filter(|d| d.len() > 0) {
+                    self.cmds
+                        .push(format!("{}/{}", dir.trim_end_matches('/'), cmd));
+                }
+            }
+        }
+
+        if let Some(javaHome) = env("JAVA_HOME") {
+            for f in &JAVA_FILES {
+                self.libs
+                    .push_back(format!("{}/{}", javaHome.trim_end_matches('/'), f));
+            }
+        }
+
+        self.paths = process.Prefetch.clone();
+    }
+
+    pub fn Run(&mut self) {
+        let cmds = core::mem::take(&mut self.cmds);
+        for cmd in &cmds {
+            if self.Elf(cmd) {
+                break;
+            }
+        }
+
+        while let Some(lib) = self.libs.pop_front() {
+            if self.budget == 0 {
+                return;
+            }
+
+            self.Elf(&lib);
+        }
+
+        let paths = core::mem::take(&mut self.paths);
+        for path in &paths {
+            self.Path(path, 0);
+        }
+    }
+
+    // Elf reads ahead the file and queues the interpreter and the libraries
+    // of an elf, it returns whether the file exists.
+    fn Elf(&mut self, path: &str) -> bool {
+        let file = match self.Open(path) {
+            None => return false,
+            Some(f) => f,
+        };
+
+        if !self.ReadAhead(&file) {
+            return true;
+        }
+
+        match ElfDeps(&file) {
+            Err(_) => {
+                if let Some(interp) = Shebang(&file) {
+                    self.libs.push_back(interp);
+                }
+            }
+            Ok((interp, needed, runpath)) => {
+                if let Some(interp) = interp {
+                    self.libs.push_back(interp);
+                }
+
+                for lib in needed {
+                    if let Some(lib) = ResolveLib(&self.root, path, &lib, &runpath) {
+                        self.libs.push_back(lib);
+                    }
+                }
+            }
+        }
+
+        return true;
+    }
+
+    fn Path(&mut self, path: &str, depth: usize) {
+        if self.budget == 0 || depth > 8 {
+            return;
+        }
+
+        let file = match self.Open(path) {
+            None => return,
+            Some(f) => f,
+        };
+
+        let meta = match file.metadata() {
+            Err(_) => return,
+            Ok(m) => m,
+        };
+
+        if !meta.is_dir() {
+            self.ReadAhead(&file);
+            return;
+        }
+
+        let entries = match fs::read_dir(format!("/proc/self/fd/{}", file.as_raw_fd())) {
+            Err(_) => return,
+            Ok(entries) => entries,
+        };
+
+        let mut names: Vec<String> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        for name in names {
+            self.Path(
+                &format!("{}/{}", path.trim_end_matches('/'), name),
+                depth + 1,
+            );
+        }
+    }
+
+    fn Open(&self, path: &str) -> Option<File> {
+        return OpenInRoot(&self.root, path);
+    }
+
+    // ReadAhead reads ahead a regular file once, it returns false if the
+    // file was read ahead already or isn't a regular file.
+    fn ReadAhead(&mut self, file: &File) -> bool {
+        let meta = match file.metadata() {
+            Err(_) => return false,
+            Ok(m) => m,
+        };
+
+        if !meta.is_file() || !self.seen.insert((meta.dev(), meta.ino())) {
+            return false;
+        }
+
+        let len = core::cmp::min(meta.len(), self.budget);
+        self.budget -= len;
+        self.files += 1;
+        let ret = unsafe { libc::readahead(file.as_raw_fd(), 0, len as usize) };
+        if ret < 0 {
+            info!("prefetch: readahead fail {}", errno::errno().0);
+        }
+
+        return true;
+    }
+}
+
+// OpenInRoot opens the path in root, its absolute symlinks are resolved in
+// root too. The path is opened with O_PATH first, so that a device or a fifo
+// of the rootfs isn't opened for real, only a regular file is opened again
+// for read. A directory is returned as its O_PATH file, which fstat and
+// /proc/self/fd take.
+pub fn OpenInRoot(root: &File, path: &str) -> Option<File> {
+    let cpath = CString::new(path).ok()?;
+    let how = OpenHow {
+        flags: (libc::O_PATH | libc::O_CLOEXEC) as u64,
+        mode: 0,
+        resolve: RESOLVE_IN_ROOT | RESOLVE_NO_MAGICLINKS,
+    };
+
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_openat2,
+            root.as_raw_fd(),
+            cpath.as_ptr(),
+            &how as *const _ as u64,
+            core::mem::size_of::<OpenHow>(),
+        )
+    };
+    if fd < 0 {
+        return None;
+    }
+
+    let file = unsafe { File::from_raw_fd(fd as i32) };
+    let meta = file.metadata().ok()?;
+    if meta.is_dir() {
+        return Some(file);
+    }
+
+    if !meta.is_file() {
+        return None;
+    }
+
+    // the reopen of the fd can't be redirected to another file
+    let fdpath = CString::new(format!("/proc/self/fd/{}", file.as_raw_fd())).ok()?;
+    let fd = unsafe { libc::open(fdpath.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return None;
+    }
+
+    return Some(unsafe { File::from_raw_fd(fd) });
+}
+
+// ResolveLib returns the path in root of the library a needed entry of the elf
+// at path names, it is looked up in the run paths then in the default dirs of
+// the dynamic linker.
+pub fn ResolveLib(root: &File, path: &str, lib: &str, runpath: &[String]) -> Option<String> {
+    if lib.contains('/') {
+        return Some(lib.to_string());
+    }
+
+    let origin = match path.rfind('/') {
+        Some(i) => &path[..i],
+        None => "/",
+    };
+
+    let dirs = runpath
+        .iter()
+        .map(|d| d.replace("$ORIGIN", origin))
+        .chain(LIB_DIRS.iter().map(|d| d.to_string()));
+    for dir in dirs {
+        let candidate = format!("{}/{}", dir.trim_end_matches('/'), lib);
+        if OpenInRoot(root, &candidate).is_some() {
+            return Some(candidate);
+        }
+    }
+
+    return None;
+}
+
+pub fn OpenRoot(path: &str) -> Result<File> {
+    let cpath = CString::new(path).map_err(|e| Error::Common(format!("{:?}", e)))?;
+    let fd = unsafe {
+        libc::open(
+            cpath.as_ptr(),
+            libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(Error::SysError(errno::errno().0));
+    }
+
+    return Ok(unsafe { File::from_raw_fd(fd) });
+}
+
+// ReadAt reads up to len bytes at offset.
+fn ReadAt(file: &File, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+    let cnt = file
+        .read_at(&mut buf, offset)
+        .map_err(|e| Error::IOError(format!("{:?}", e)))?;
+    buf.truncate(cnt);
+    return Ok(buf);
+}
+
+fn U16(buf: &[u8], off: usize) -> u16 {
+    return u16::from_le_bytes([buf[off], buf[off + 1]]);
+}
+
+fn U32(buf: &[u8], off: usize) -> u32 {
+    let mut b = [0; 4];
+    b.copy_from_slice(&buf[off..off + 4]);
+    return u32::from_le_bytes(b);
+}
+
+fn U64(buf: &[u8], off: usize) -> u64 {
+    let mut b = [0; 8];
+    b.copy_from_slice(&buf[off..off + 8]);
+    return u64::from_le_bytes(b);
+}
+
+fn CStr(buf: &[u8], off: usize) -> Option<String> {
+    let s = buf.get(off..)?;
+    let end = s.iter().position(|&c| c == 0)?;
+    return Some(String::from_utf8_lossy(&s[..end]).to_string());
+}
+
+// Shebang returns the interpreter of a script.
+fn Shebang(file: &File) -> Option<String> {
+    let buf = ReadAt(file, 0, 256).ok()?;
+    if !buf.starts_with(b"#!") {
+        return None;
+    }
+
+    let end = buf.iter().position(|&c| c == b'\n')?;
+    let line = String::from_utf8_lossy(&buf[2..end]).to_string();
+    return line.split_whitespace().next().map(|s| s.to_string());
+}
+
+// ElfDeps returns the interpreter, the needed libraries and the run paths
+// of a little endian elf64, the only elf of x86_64.
+pub fn ElfDeps(file: &File) -> Result<(Option<String>, Vec<String>, Vec<String>)> {
+    let hdr = ReadAt(file, 0, 64)?;
+    if hdr.len() < 64 || &hdr[0..4] != b"\x7fELF" || hdr[4] != 2 || hdr[5] != 1 {
+        return Err(Error::WrongELFFormat);
+    }
+
+    let phoff = U64(&hdr, 32);
+    let phentsize = U16(&hdr, 54) as usize;
+    let phnum = U16(&hdr, 56) as usize;
+    if phentsize < 56 || phnum > 128 {
+        return Err(Error::WrongELFFormat);
+    }
+
+    let phdrs = ReadAt(file, phoff, phentsize * phnum)?;
+    if phdrs.len() < phentsize * phnum {
+        return Err(Error::WrongELFFormat);
+    }
+    let mut interp = None;
+    let mut dynamic = None;
+    // (vaddr, offset, filesz) of the PT_LOAD segments
+    let mut loads = Vec::new();
+    for i in 0..phnum {
+        let ph = &phdrs[i * phentsize..];
+        let offset = U64(ph, 8);
+        let vaddr = U64(ph, 16);
+        let filesz = U64(ph, 32);
+        match U32(ph, 0) {
+            PT_INTERP if filesz < 4096 => {
+                interp = CStr(&ReadAt(file, offset, filesz as usize)?, 0);
+            }
+            PT_DYNAMIC if filesz < 1 << 20 => dynamic = Some((offset, filesz)),
+            PT_LOAD => loads.push((vaddr, offset, filesz)),
+            _ => (),
+        }
+    }
+
+    let mut needed = Vec::new();
+    let mut runpath = Vec::new();
+    let (offset, filesz) = match dynamic {
+        None => return Ok((interp, needed, runpath)),
+        Some(d) => d,
+    };
+
+    let dyns = ReadAt(file, offset, filesz as usize)?;
+    let mut strtab = None;
+    let mut strs = Vec::new();
+    for i in 0..dyns.len() / 16 {
+        let tag = U64(&dyns, i * 16);
+        let val = U64(&dyns, i * 16 + 8);
+        match tag {
+            DT_NULL => break,
+            DT_STRTAB => strtab = Some(val),
+            DT_NEEDED | DT_RPATH | DT_RUNPATH => strs.push((tag, val)),
+            _ => (),
+        }
+    }
+
+    let strtab = match strtab {
+        None => return Ok((interp, needed, runpath)),
+        Some(s) => s,
+    };
+
+    let strOffset = match loads
+        .iter()
+        .find(|(vaddr, _, filesz)| strtab >= *vaddr && strtab < vaddr + filesz)
+    {
+        None => return Ok((interp, needed, runpath)),
+        Some((vaddr, offset, _)) => strtab - vaddr + offset,
+    };
+
+    for (tag, val) in strs {
+        // the names and the run paths are short
+        let s = match ReadAt(file, strOffset + val, 256)
+            .ok()
+            .and_then(|b| CStr(&b, 0))
+        {
+            None => continue,
+            Some(s) => s,
+        };
+
+        if tag == DT_NEEDED {
+            needed.push(s);
+        } else {
+            runpath.extend(s.split(':').filter(|d| d.len() > 0).map(|d| d.to_string()));
+        }
+    }
+
+    return Ok((interp, needed, runpath));
+}
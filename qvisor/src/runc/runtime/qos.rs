@@ -0,0 +1,258 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::process::Command;
+
+use super::super::super::qlib::common::*;
+use super::super::oci::*;
+use super::super::specutils::namespace::*;
+
+// Egress shaping of the sandbox. The guest sockets send through the host
+// sockets in the network namespace of the sandbox, so the traffic is shaped on
+// the interfaces of that namespace: a htb class is the token bucket of the
+// whole sandbox and a fq qdisc under it queues every connection separately so
+// one of them can't starve the others. It is enabled per sandbox with
+// annotations:
+//   quark.qos.egress.rate: rate limit, e.g. 100mbit, 1gbit, 500kbit
+//   quark.qos.egress.burst: bucket size in bytes, e.g. 64kb, default 10ms of rate
+//   quark.qos.egress.fairqueue: "false" to keep a single fifo
+pub const QOS_RATE_ANNOTATION: &str = "quark.qos.egress.rate";
+pub const QOS_BURST_ANNOTATION: &str = "quark.qos.egress.burst";
+pub const QOS_FAIRQUEUE_ANNOTATION: &str = "quark.qos.egress.fairqueue";
+
+const MIN_BURST: u64 = 16 * 1024;
+
+// memory.high of the sandbox, e.g. 768mb. Over it the guest throttles the
+// tasks instead of running into the memory limit. The MemoryHigh of the
+// config, if any, is the ceiling: the annotation can only lower it.
+pub const MEMORY_HIGH_ANNOTATION: &str = "quark.memory.high";
+
+// the ephemeral-storage limit of the sandbox, e.g. 2gb. The writes to the root
+// filesystem and the tmpfs mounts fail with ENOSPC past it.
+pub const EPHEMERAL_STORAGE_ANNOTATION: &str = "quark.storage.ephemeral";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EgressQos {
+    // bits per second
+    pub rate: u64,
+    // bytes
+    pub burst: u64,
+    pub fairQueue: bool,
+}
+
+// ParseRate parses a tc style rate, the unit is bit per second.
+pub fn ParseRate(s: &str) -> Result<u64> {
+    let units: [(&str, u64); 4] = [
+        ("gbit", 1_000_000_000),
+        ("mbit", 1_000_000),
+        ("kbit", 1_000),
+        ("bit", 1),
+    ];
+
+    return ParseUnit(s, &units);
+}
+
+// ParseSize parses a tc style size in bytes.
+pub fn ParseSize(s: &str) -> Result<u64> {
+    let units: [(&str, u64); 4] = [("gb", 1 << 30), ("mb", 1 << 20), ("kb", 1 << 10), ("b", 1)];
+
+    return ParseUnit(s, &units);
+}
+
+fn ParseUnit(s: &str, units: &[(&str, u64)]) -> Result<u64> {
+    let s = s.trim().to_lowercase();
+    let (num, mul) = match units.iter().find(|(u, _)| s.ends_with(u)) {
+        None => (s.as_str(), 1),
+        Some((u, mul)) => (&s[..s.len() - u.len()], *mul),
+    };
+
+    match num.trim().parse::<u64>() {
+        Ok(n) if n > 0 => match n.checked_mul(mul) {
+            Some(v) => return Ok(v),
+            None => return Err(Error::Common(format!("value {} overflows", s))),
+        },
+        _ => return Err(Error::Common(format!("invalid value {}", s))),
+    }
+}
+
+// MemoryHighFromSpec returns the memory.high annotation in MB, capped by the
+// memory.high of the config.
+pub fn MemoryHighFromSpec(spec: &Spec, ceiling: u64) -> Result<Option<u64>> {
+    let high = match spec.annotations.get(MEMORY_HIGH_ANNOTATION) {
+        None => return Ok(None),
+        Some(h) => ParseSize(h)
+            .map_err(|e| Error::Common(format!("{}: {:?}", MEMORY_HIGH_ANNOTATION, e)))?,
+    };
+
+    let mut mb = (high >> 20) + (high & ((1 << 20) - 1) != 0) as u64;
+    if ceiling > 0 && mb > ceiling {
+        info!(
+            "{} {}MB is above the memory.high {}MB of the config, the config is kept",
+            MEMORY_HIGH_ANNOTATION, mb, ceiling
+        );
+        mb = ceiling;
+    }
+
+    info!("memory.high of the sandbox is {}MB", mb);
+    return Ok(Some(mb));
+}
+
+// EphemeralStorageFromSpec returns the ephemeral storage annotation in MB. The
+// limit of the config, if any, is the ceiling: the annotation can only lower
+// it.
+pub fn EphemeralStorageFromSpec(spec: &Spec, ceiling: u64) -> Result<Option<u64>> {
+    let limit = match spec.annotations.get(EPHEMERAL_STORAGE_ANNOTATION) {
+        None => return Ok(None),
+        Some(l) => ParseSize(l)
+            .map_err(|e| Error::Common(format!("{}: {:?}", EPHEMERAL_STORAGE_ANNOTATION, e)))?,
+    };
+
+    let mut mb = (limit >> 20) + (limit & ((1 << 20) - 1) != 0) as u64;
+    if ceiling > 0 && mb > ceiling {
+        info!(
+            "{} {}MB is above the limit {}MB of the config, the limit is kept",
+            EPHEMERAL_STORAGE_ANNOTATION, mb, ceiling
+        );
+        mb = ceiling;
+    }
+
+    info!("ephemeral storage limit of the sandbox is {}MB", mb);
+    return Ok(Some(mb));
+}
+
+impl EgressQos {
+    pub fn FromSpec(spec: &Spec) -> Result<Option<Self>> {
+        let rate = match spec.annotations.get(QOS_RATE_ANNOTATION) {
+            None => return Ok(None),
+            Some(r) => ParseRate(r)
+                .map_err(|e| Error::Common(format!("{}: {:?}", QOS_RATE_ANNOTATION, e)))?,
+        };
+
+        let burst = match spec.annotations.get(QOS_BURST_ANNOTATION) {
+            None => {
+                let burst = rate / 8 / 100;
+                if burst < MIN_BURST {
+                    MIN_BURST
+                } else {
+                    burst
+                }
+            }
+            Some(b) => ParseSize(b)
+                .map_err(|e| Error::Common(format!("{}: {:?}", QOS_BURST_ANNOTATION, e)))?,
+        };
+
+        let fairQueue = match spec.annotations.get(QOS_FAIRQUEUE_ANNOTATION) {
+            None => true,
+            Some(v) => v.as_str() != "false",
+        };
+
+        // without its own network namespace the sandbox uses the interfaces of
+        // the host, don't touch them
+        let ns = GetNS(LinuxNamespaceType::network, spec);
+        if ns.is_none() {
+            return Err(Error::Common(format!(
+                "{} needs a network namespace",
+                QOS_RATE_ANNOTATION
+            )));
+        }
+
+        return Ok(Some(Self {
+            rate: rate,
+            burst: burst,
+            fairQueue: fairQueue,
+        }));
+    }
+
+    // Apply installs the shaping on all the interfaces but the loopback. It is
+    // called from the sandbox process, i.e. inside the network namespace.
+    pub fn Apply(&self) -> Result<()> {
+        for dev in Interfaces() {
+            if dev.as_str() == "lo" {
+                continue;
+            }
+
+            self.ApplyDev(&dev)?;
+            info!(
+                "qos: {} egress rate {}bit burst {}b fairqueue {}",
+                dev, self.rate, self.burst, self.fairQueue
+            );
+        }
+
+        return Ok(());
+    }
+
+    fn ApplyDev(&self, dev: &str) -> Result<()> {
+        let rate = format!("{}bit", self.rate);
+        let burst = format!("{}b", self.burst);
+
+        Tc(&["qdisc", "replace", "dev", dev, "root", "handle", "1:", "htb", "default", "1"])?;
+        Tc(&[
+            "class", "replace", "dev", dev, "parent", "1:", "classid", "1:1", "htb", "rate",
+            rate.as_str(), "ceil", rate.as_str(), "burst", burst.as_str(), "cburst",
+            burst.as_str(),
+        ])?;
+
+        if self.fairQueue {
+            Tc(&["qdisc", "replace", "dev", dev, "parent", "1:1", "handle", "10:", "fq"])?;
+        }
+
+        return Ok(());
+    }
+}
+
+fn Tc(args: &[&str]) -> Result<()> {
+    let output = Command::new("tc")
+        .args(args)
+        .output()
+        .map_err(|e| Error::IOError(format!("run tc fail {:?}", e)))?;
+
+    if !output.status.success() {
+        return Err(Error::Common(format!(
+            "tc {} fail: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    return Ok(());
+}
+
+// Interfaces returns the names of the interfaces of the current network namespace.
+pub fn Interfaces() -> Vec<String> {
+    let mut names = Vec::new();
+    unsafe {
+        let list = libc::if_nameindex();
+        if list.is_null() {
+            return names;
+        }
+
+        let mut i = 0;
+        loop {
+            let ifn = *list.offset(i);
+            if ifn.if_index == 0 || ifn.if_name.is_null() {
+                break;
+            }
+
+            let name = std::ffi::CStr::from_ptr(ifn.if_name);
+            names.push(name.to_string_lossy().to_string());
+            i += 1;
+        }
+
+        libc::if_freenameindex(list);
+    }
+
+    return names;
+}
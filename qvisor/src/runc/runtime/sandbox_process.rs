@@ -41,15 +41,19 @@ use super::super::super::qlib::unix_socket;
 use super::super::super::ucall::ucall::*;
 use super::super::super::ucall::usocket::*;
 use super::super::super::util::*;
+use super::super::super::vmspace::teardown::*;
 use super::super::super::QUARK_CONFIG;
 use super::super::cmd::config::*;
 use super::super::container::container::*;
+use super::super::container::layer::*;
 use super::super::container::mounts::*;
 use super::super::container::nix_ext::*;
 use super::super::oci::*;
 use super::super::shim::container_io::*;
 use super::super::specutils::specutils::*;
 use super::console::*;
+use super::etc_files::*;
+use super::listen_fds::*;
 use super::loader::*;
 use super::signal_handle::*;
 use super::util::*;
@@ -118,13 +122,13 @@ impl SandboxProcess {
         action: RunAction,
         id: &str,
         bundleDir: &str,
+        spec: &Spec,
         pivot: bool,
     ) -> Result<Self> {
-        let specfile = Join(bundleDir, "config.json");
-
+        // the spec of the container, the CDI devices are applied to it
         let mut process = SandboxProcess {
             eventfd: 0,
-            spec: Spec::load(&specfile).unwrap(),
+            spec: spec.clone(),
             bundleDir: bundleDir.to_string(),
             conf: gCfg.Copy(),
             userLog: "".to_string(),
@@ -157,7 +161,13 @@ impl SandboxProcess {
         return Ok(process);
     }
 
-    pub fn Run(&self, controlSock: i32, rdmaSvcCliSock: i32) {
+    pub fn Run(
+        &self,
+        controlSock: i32,
+        controlHostSock: i32,
+        rdmaSvcCliSock: i32,
+        ephemeralUsed: u64,
+    ) {
         let id = &self.containerId;
         let sid = unsafe {
             //signal (SIGHUP, SIG_IGN);
@@ -169,6 +179,7 @@ impl SandboxProcess {
         }
 
         PrepareHandler().unwrap();
+        InstallPanicHook();
 
         let kvmfd = Kvm::open_with_cloexec(false).expect("can't open kvm");
         let mut args = Args::default();
@@ -180,7 +191,9 @@ impl SandboxProcess {
         args.Pivot = self.pivot;
         args.Rootfs = Join(QUARK_SANDBOX_ROOT_PATH, id.as_str());
         args.ControlSock = controlSock;
+        args.ControlHostSock = controlHostSock;
         args.RDMASvcCliSock = rdmaSvcCliSock;
+        args.EphemeralUsed = ephemeralUsed;
 
         let exitStatus = match VirtualMachine::Init(args) {
             Ok(mut vm) => {
@@ -193,7 +206,7 @@ impl SandboxProcess {
             }
         };
 
-        unsafe { libc::_exit(exitStatus) }
+        Exit(exitStatus);
     }
 
     /// Root path for this sandbox on host fs, rootfs for containers running in this sandbox should be mount inside this dir
@@ -357,6 +370,10 @@ impl SandboxProcess {
                 let msg = format!("invalid mount destination: {}", m.destination);
                 return Err(Error::Common(msg));
             }
+            if IsVirtualEtcMount(spec, m) {
+                info!("{} is served by the guest", m.destination);
+                continue;
+            }
             let (flags, data) = parse_mount(m);
             if m.typ == "cgroup" {
                 //mount_cgroups(m, rootfs, flags, &data, &linux.mount_label, cpath)?;
@@ -479,6 +496,9 @@ impl SandboxProcess {
               }
           }*/
 
+        // socket activation, the listening sockets are inherited by the boot process
+        KeepListenFds()?;
+
         let child = cmd.spawn().expect("Boot command failed to start");
 
         {
@@ -581,14 +601,21 @@ impl SandboxProcess {
 
         let addr = ControlSocketAddr(&self.containerId);
         let controlSock = USocket::CreateServerSocket(&addr).expect("can't create control sock");
+        let hostAddr = ControlHostSocketAddr(&self.containerId);
+        let controlHostSock =
+            USocket::CreateServerSocket(&hostAddr).expect("can't create control host sock");
         let mut rdmaSvcCliSock = 0;
         if QUARK_CONFIG.lock().EnableRDMA {
             rdmaSvcCliSock = unix_socket::UnixSocket::NewClient("/tmp/rdma_srv").unwrap();
         }
+        // what a previous run of the container left in its writable layer
+        // counts to the ephemeral storage, the layer is out of reach once the
+        // root filesystem is set up
+        let ephemeralUsed = UpperDirUsage(&self.Rootfs);
         self.MakeSandboxRootDirectory()?;
         self.EnableNamespace()?;
 
-        self.Run(controlSock, rdmaSvcCliSock);
+        self.Run(controlSock, controlHostSock, rdmaSvcCliSock, ephemeralUsed);
         panic!("Child: should never reach here");
     }
 
@@ -0,0 +1,88 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::path::Clean;
+use super::super::oci::*;
+
+// The size of the /dev/shm of a container. The guest mounts its own tmpfs at
+// /dev/shm unless it is a volume of the user, the /dev/shm mount of the spec
+// only gives its size then: the size option of a tmpfs mount, e.g. the
+// --shm-size of docker. A bind mount, e.g. the sandbox shm of a pod, has no
+// size, the quark.shm.size annotation
+// sets it then, with the syntax of the size option: bytes with a k, m or g
+// suffix, or a percent of the sandbox memory with a % suffix. Without both the
+// size is half of the sandbox memory as the default of tmpfs.
+pub const SHM_SIZE_ANNOTATION: &str = "quark.shm.size";
+
+const DEV_SHM: &str = "/dev/shm";
+
+// ShmVolumeFromSpec returns whether the /dev/shm of the spec is a volume of the
+// user, e.g. an emptyDir of the pod or a host directory, it is mounted in the
+// guest instead of the tmpfs then. The /dev/shm the runtime sets up is a tmpfs,
+// or a bind mount of the shm of the pod sandbox from the state directory of
+// the runtime, e.g. .../sandboxes/<id>/shm of containerd, .../mounts/shm of
+// docker or .../userdata/shm of cri-o.
+pub fn ShmVolumeFromSpec(spec: &Spec) -> bool {
+    for m in &spec.mounts {
+        if Clean(&m.destination) != DEV_SHM || m.typ == "tmpfs" {
+            continue;
+        }
+
+        let source = Clean(&m.source);
+        let runtimeShm = source.ends_with("/shm") && !source.contains("/volumes/");
+        if !runtimeShm {
+            return true;
+        }
+    }
+
+    return false;
+}
+
+// ValidShmSize checks the size as the guest parses it.
+fn ValidShmSize(size: &str) -> bool {
+    let num = match size.as_bytes().last() {
+        None => return false,
+        Some(b'k') | Some(b'K') | Some(b'm') | Some(b'M') | Some(b'g') | Some(b'G')
+        | Some(b'%') => &size[..size.len() - 1],
+        Some(_) => size,
+    };
+
+    return num.parse::<u64>().is_ok();
+}
+
+pub fn ShmSizeFromSpec(spec: &Spec) -> Result<String> {
+    let mut size = String::new();
+    for m in &spec.mounts {
+        if Clean(&m.destination) != DEV_SHM || m.typ != "tmpfs" {
+            continue;
+        }
+
+        for opt in &m.options {
+            if let Some(s) = opt.strip_prefix("size=") {
+                size = s.to_string();
+            }
+        }
+    }
+
+    if let Some(s) = spec.annotations.get(SHM_SIZE_ANNOTATION) {
+        size = s.trim().to_string();
+    }
+
+    if size.len() > 0 && !ValidShmSize(&size) {
+        return Err(Error::Common(format!("invalid /dev/shm size {}", size)));
+    }
+
+    return Ok(size);
+}
@@ -0,0 +1,575 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use super::super::super::qlib::common::*;
+use super::super::oci::*;
+
+// TLS termination offload. qvisor listens on the TLS ports in the network
+// namespace of the sandbox, does the handshake and the record crypto, and
+// hands the plaintext side of each connection to the guest listener of the
+// plain port as a unix socket, so it is accepted without a loopback hop. The
+// guest application serves plain TCP and never sees the key. The operator
+// gives the key material in the config file ([tls] cert and key), a sandbox
+// only picks the ports with an annotation:
+//   quark.tls.ports: tls:plain port pairs, e.g. "443:8080,8443:9090"
+// It needs qvisor built with the "tls" feature.
+pub const TLS_PORTS_ANNOTATION: &str = "quark.tls.ports";
+
+// the connections the proxy serves at a time, the new ones over it are closed
+pub const TLS_MAX_CONNECTIONS: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub struct TlsOffload {
+    // (tls port, plain port)
+    pub ports: Vec<(u16, u16)>,
+}
+
+pub fn ParsePorts(s: &str) -> Result<Vec<(u16, u16)>> {
+    let mut ports = Vec::new();
+    for pair in s.split(',').map(|p| p.trim()).filter(|p| p.len() > 0) {
+        let parts: Vec<&str> = pair.split(':').collect();
+        let port = |p: &str| -> Result<u16> {
+            match p.parse::<u16>() {
+                Ok(p) if p > 0 => return Ok(p),
+                _ => return Err(Error::Common(format!("invalid tls port pair {}", pair))),
+            }
+        };
+
+        if parts.len() != 2 {
+            return Err(Error::Common(format!("invalid tls port pair {}", pair)));
+        }
+
+        ports.push((port(parts[0])?, port(parts[1])?));
+    }
+
+    if ports.len() == 0 {
+        return Err(Error::Common(format!("{} is empty", TLS_PORTS_ANNOTATION)));
+    }
+
+    return Ok(ports);
+}
+
+impl TlsOffload {
+    pub fn FromSpec(spec: &Spec) -> Result<Option<Self>> {
+        let ports = match spec.annotations.get(TLS_PORTS_ANNOTATION) {
+            None => return Ok(None),
+            Some(p) => ParsePorts(p)?,
+        };
+
+        return Ok(Some(Self { ports: ports }));
+    }
+
+    #[cfg(not(feature = "tls"))]
+    pub fn Start(self) -> Result<()> {
+        return Err(Error::Common(format!(
+            "{} is set but quark is built without the tls feature",
+            TLS_PORTS_ANNOTATION
+        )));
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn Start(self) -> Result<()> {
+        return tls::Start(self);
+    }
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use alloc::sync::Arc;
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::io::{BufReader, ErrorKind, Read, Write};
+    use std::net::{Shutdown, TcpListener, TcpStream};
+    use std::os::unix::io::{AsRawFd, RawFd};
+    use std::os::unix::net::UnixStream;
+    use std::sync::mpsc::{channel, Sender};
+    use std::thread;
+
+    use rustls::{Certificate, PrivateKey, ServerConfig, ServerConnection};
+    use rustls_pemfile::Item;
+
+    use super::super::super::super::qlib::common::*;
+    use super::super::super::super::qlib::control_msg::TlsAcceptArgs;
+    use super::super::super::super::ucall::ucall::*;
+    use super::super::super::super::ucall::ucall_client::*;
+    use super::super::super::super::vmspace::packet_filter::*;
+    use super::super::super::super::HOST_CONFIG;
+    use super::super::super::super::ROOT_CONTAINER_ID;
+    use super::{TlsOffload, TLS_MAX_CONNECTIONS, TLS_PORTS_ANNOTATION};
+
+    const BUF_SIZE: usize = 16 * 1024;
+    const MAX_EVENTS: usize = 64;
+
+    fn LoadConfig(certPath: &str, keyPath: &str) -> Result<ServerConfig> {
+        let open = |path: &str| -> Result<BufReader<File>> {
+            let f = File::open(path)
+                .map_err(|e| Error::IOError(format!("open {} fail {:?}", path, e)))?;
+            return Ok(BufReader::new(f));
+        };
+
+        let mut certs = Vec::new();
+        let mut reader = open(certPath)?;
+        for item in rustls_pemfile::read_all(&mut reader)
+            .map_err(|e| Error::IOError(format!("read {} fail {:?}", certPath, e)))?
+        {
+            if let Item::X509Certificate(c) = item {
+                certs.push(Certificate(c));
+            }
+        }
+
+        let mut key = None;
+        let mut reader = open(keyPath)?;
+        for item in rustls_pemfile::read_all(&mut reader)
+            .map_err(|e| Error::IOError(format!("read {} fail {:?}", keyPath, e)))?
+        {
+            match item {
+                Item::RSAKey(k) | Item::PKCS8Key(k) | Item::ECKey(k) => {
+                    key = Some(PrivateKey(k));
+                    break;
+                }
+                _ => (),
+            }
+        }
+
+        if certs.len() == 0 {
+            return Err(Error::Common(format!("no certificate in {}", certPath)));
+        }
+
+        let key = match key {
+            None => return Err(Error::Common(format!("no private key in {}", keyPath))),
+            Some(k) => k,
+        };
+
+        return ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| Error::Common(format!("tls config fail {:?}", e)));
+    }
+
+    pub fn Start(cfg: TlsOffload) -> Result<()> {
+        let (cert, key) = (HOST_CONFIG.TlsCert.clone(), HOST_CONFIG.TlsKey.clone());
+        if cert.len() == 0 || key.len() == 0 {
+            return Err(Error::Common(format!(
+                "{} is set but tls.cert and tls.key are not configured",
+                TLS_PORTS_ANNOTATION
+            )));
+        }
+
+        let config = Arc::new(LoadConfig(&cert, &key)?);
+        let mut proxy = Proxy::New(config)?;
+        for (tlsPort, plainPort) in cfg.ports {
+            let listener = TcpListener::bind(("::", tlsPort))
+                .or_else(|_| TcpListener::bind(("0.0.0.0", tlsPort)))
+                .map_err(|e| Error::IOError(format!("tls: bind {} fail {:?}", tlsPort, e)))?;
+            listener.set_nonblocking(true).map_err(IOErr)?;
+            info!("tls: terminate port {} to guest port {}", tlsPort, plainPort);
+            proxy.AddListener(listener, plainPort)?;
+        }
+
+        thread::Builder::new()
+            .name("tls-proxy".to_string())
+            .spawn(move || proxy.Run())
+            .map_err(|e| Error::IOError(format!("tls: spawn thread fail {:?}", e)))?;
+
+        return Ok(());
+    }
+
+    fn IOErr(e: std::io::Error) -> Error {
+        return Error::IOError(format!("{:?}", e));
+    }
+
+    fn WouldBlock(e: &std::io::Error) -> bool {
+        return e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted;
+    }
+
+    // the plaintext end of a connection for the guest, its plain port and the
+    // address of the client
+    type HandoffReq = (UnixStream, u16, Vec<u8>);
+
+    // StartHandoff starts the thread handing the connections to the guest. A
+    // handoff is a call on the control socket, which waits for the guest, the
+    // epoll loop of the proxy doesn't wait on it.
+    fn StartHandoff() -> Result<Sender<HandoffReq>> {
+        let (tx, rx) = channel::<HandoffReq>();
+        thread::Builder::new()
+            .name("tls-handoff".to_string())
+            .spawn(move || {
+                for (guest, plainPort, addr) in rx {
+                    // the guest end is closed on a failure, the proxy sees
+                    // the connection hung up
+                    if let Err(e) = Handoff(guest.as_raw_fd(), plainPort, addr) {
+                        info!("tls: hand off to {} fail {:?}", plainPort, e);
+                    }
+                }
+            })
+            .map_err(|e| Error::IOError(format!("tls: spawn thread fail {:?}", e)))?;
+
+        return Ok(tx);
+    }
+
+    // Handoff gives the plaintext end of a connection to the guest, which
+    // queues it on the listener of the plain port.
+    fn Handoff(fd: RawFd, plainPort: u16, addr: Vec<u8>) -> Result<()> {
+        let req = UCallReq::TlsAccept(TlsAcceptArgs {
+            port: plainPort,
+            fd: fd,
+            addr: addr,
+        });
+
+        let id = ROOT_CONTAINER_ID.lock().clone();
+        UCallClient::Init(&ControlSocketAddr(&id)).and_then(|client| client.Call(&req))?;
+        return Ok(());
+    }
+
+    struct Conn {
+        tcp: TcpStream,
+        // the local end of the socket pair, the guest has the other end
+        plain: UnixStream,
+        guest: Option<UnixStream>,
+        conn: ServerConnection,
+        plainPort: u16,
+        // the plaintext from the client the guest hasn't taken yet
+        pending: Vec<u8>,
+        tlsOpen: bool,
+        plainOpen: bool,
+        plainShut: bool,
+    }
+
+    impl Conn {
+        fn Done(&self) -> bool {
+            return !self.tlsOpen
+                && self.pending.len() == 0
+                && !self.plainOpen
+                && !self.conn.wants_write();
+        }
+
+        // Pump moves the data as far as the sockets take it without blocking.
+        fn Pump(&mut self, buf: &mut [u8], handoff: &Sender<HandoffReq>) -> Result<()> {
+            if self.tlsOpen && self.pending.len() == 0 {
+                match self.conn.read_tls(&mut self.tcp) {
+                    Ok(0) => self.tlsOpen = false,
+                    Ok(_) => (),
+                    Err(e) if WouldBlock(&e) => (),
+                    Err(e) => return Err(IOErr(e)),
+                }
+
+                if let Err(e) = self.conn.process_new_packets() {
+                    // send the alert before the connection is dropped
+                    self.conn.write_tls(&mut self.tcp).ok();
+                    return Err(Error::Common(format!("tls: {:?}", e)));
+                }
+
+                loop {
+                    match self.conn.reader().read(buf) {
+                        // 0 is the close_notify of the client
+                        Ok(0) => {
+                            self.tlsOpen = false;
+                            break;
+                        }
+                        Ok(cnt) => self.pending.extend_from_slice(&buf[..cnt]),
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                        Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                            self.tlsOpen = false;
+                            break;
+                        }
+                        Err(e) => return Err(IOErr(e)),
+                    }
+                }
+            }
+
+            if self.guest.is_some() && !self.conn.is_handshaking() {
+                let guest = self.guest.take().unwrap();
+                let addr = SockName(self.tcp.as_raw_fd(), true);
+                handoff
+                    .send((guest, self.plainPort, addr))
+                    .map_err(|_| Error::Common(format!("tls: the handoff thread is gone")))?;
+            }
+
+            if self.guest.is_some() {
+                if !self.tlsOpen {
+                    return Err(Error::Common(format!("closed in the handshake")));
+                }
+                return self.FlushTls();
+            }
+
+            while self.pending.len() > 0 {
+                match self.plain.write(&self.pending) {
+                    Ok(cnt) => {
+                        self.pending.drain(..cnt);
+                    }
+                    Err(e) if WouldBlock(&e) => break,
+                    Err(e) => return Err(IOErr(e)),
+                }
+            }
+
+            if !self.tlsOpen && self.pending.len() == 0 && !self.plainShut {
+                self.plain.shutdown(Shutdown::Write).ok();
+                self.plainShut = true;
+            }
+
+            // don't take more from the guest than the client takes
+            if self.plainOpen && !self.conn.wants_write() {
+                match self.plain.read(buf) {
+                    Ok(0) => {
+                        self.plainOpen = false;
+                        self.conn.send_close_notify();
+                    }
+                    Ok(cnt) => self.conn.writer().write_all(&buf[..cnt]).map_err(IOErr)?,
+                    Err(e) if WouldBlock(&e) => (),
+                    Err(e) => return Err(IOErr(e)),
+                }
+            }
+
+            return self.FlushTls();
+        }
+
+        fn FlushTls(&mut self) -> Result<()> {
+            while self.conn.wants_write() {
+                match self.conn.write_tls(&mut self.tcp) {
+                    Ok(_) => (),
+                    Err(e) if WouldBlock(&e) => break,
+                    Err(e) => return Err(IOErr(e)),
+                }
+            }
+
+            return Ok(());
+        }
+
+        // the events the connection waits for on the tls socket and on the
+        // plaintext socket
+        fn Interest(&self) -> (u32, u32) {
+            let mut tcp = 0;
+            if self.tlsOpen && self.pending.len() == 0 {
+                tcp |= libc::EPOLLIN;
+            }
+            if self.conn.wants_write() {
+                tcp |= libc::EPOLLOUT;
+            }
+
+            let mut plain = 0;
+            if self.guest.is_none() {
+                if self.plainOpen && !self.conn.wants_write() {
+                    plain |= libc::EPOLLIN;
+                }
+                if self.pending.len() > 0 {
+                    plain |= libc::EPOLLOUT;
+                }
+            }
+
+            return (tcp as u32, plain as u32);
+        }
+    }
+
+    struct Proxy {
+        epfd: RawFd,
+        config: Arc<ServerConfig>,
+        handoff: Sender<HandoffReq>,
+        // listener fd -> (listener, plain port)
+        listeners: HashMap<RawFd, (TcpListener, u16)>,
+        // tls socket fd -> connection
+        conns: HashMap<RawFd, Conn>,
+        // plaintext socket fd -> tls socket fd
+        plains: HashMap<RawFd, RawFd>,
+    }
+
+    impl Proxy {
+        fn New(config: Arc<ServerConfig>) -> Result<Self> {
+            let epfd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+            if epfd < 0 {
+                return Err(Error::SysError(errno::errno().0));
+            }
+
+            let handoff = match StartHandoff() {
+                Ok(tx) => tx,
+                Err(e) => {
+                    unsafe {
+                        libc::close(epfd);
+                    }
+                    return Err(e);
+                }
+            };
+
+            return Ok(Self {
+                epfd: epfd,
+                config: config,
+                handoff: handoff,
+                listeners: HashMap::new(),
+                conns: HashMap::new(),
+                plains: HashMap::new(),
+            });
+        }
+
+        fn Ctl(&self, op: i32, fd: RawFd, events: u32) -> Result<()> {
+            let mut ev = libc::epoll_event {
+                events: events,
+                u64: fd as u64,
+            };
+
+            let ret = unsafe { libc::epoll_ctl(self.epfd, op, fd, &mut ev) };
+            if ret < 0 {
+                return Err(Error::SysError(errno::errno().0));
+            }
+
+            return Ok(());
+        }
+
+        fn AddListener(&mut self, listener: TcpListener, plainPort: u16) -> Result<()> {
+            let fd = listener.as_raw_fd();
+            self.Ctl(libc::EPOLL_CTL_ADD, fd, libc::EPOLLIN as u32)?;
+            self.listeners.insert(fd, (listener, plainPort));
+            return Ok(());
+        }
+
+        fn Run(mut self) {
+            let mut events = vec![libc::epoll_event { events: 0, u64: 0 }; MAX_EVENTS];
+            let mut buf = vec![0u8; BUF_SIZE];
+            loop {
+                let cnt = unsafe {
+                    libc::epoll_wait(self.epfd, events.as_mut_ptr(), MAX_EVENTS as i32, -1)
+                };
+                if cnt < 0 {
+                    let errno = errno::errno().0;
+                    if errno == libc::EINTR {
+                        continue;
+                    }
+                    error!("tls: epoll_wait fail {}", errno);
+                    return;
+                }
+
+                for i in 0..cnt as usize {
+                    let fd = events[i].u64 as RawFd;
+                    let mask = events[i].events;
+                    if self.listeners.contains_key(&fd) {
+                        self.Accept(fd);
+                        continue;
+                    }
+
+                    let tcpfd = match self.plains.get(&fd) {
+                        Some(tcpfd) => *tcpfd,
+                        None => fd,
+                    };
+
+                    let (ret, done, (tcp, plain), plainfd, plainPort) =
+                        match self.conns.get_mut(&tcpfd) {
+                            // closed by an earlier event of the same batch
+                            None => continue,
+                            Some(conn) => {
+                                let ret = conn.Pump(&mut buf, &self.handoff);
+                                (
+                                    ret,
+                                    conn.Done(),
+                                    conn.Interest(),
+                                    conn.plain.as_raw_fd(),
+                                    conn.plainPort,
+                                )
+                            }
+                        };
+
+                    let hup = mask & (libc::EPOLLHUP | libc::EPOLLERR) as u32 != 0;
+                    match ret {
+                        Err(e) => {
+                            info!("tls: connection to {} closed {:?}", plainPort, e);
+                            self.Close(tcpfd);
+                        }
+                        // a hung up socket is readable forever, don't spin on it
+                        Ok(()) if done || hup => self.Close(tcpfd),
+                        Ok(()) => {
+                            let ret = self
+                                .Ctl(libc::EPOLL_CTL_MOD, tcpfd, tcp)
+                                .and_then(|_| self.Ctl(libc::EPOLL_CTL_MOD, plainfd, plain));
+                            if ret.is_err() {
+                                self.Close(tcpfd);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        fn Accept(&mut self, fd: RawFd) {
+            let plainPort = self.listeners.get(&fd).unwrap().1;
+            loop {
+                let tcp = match self.listeners.get(&fd).unwrap().0.accept() {
+                    Ok((s, _)) => s,
+                    Err(e) if WouldBlock(&e) => return,
+                    Err(e) => {
+                        error!("tls: accept on port {} fail {:?}", plainPort, e);
+                        return;
+                    }
+                };
+
+                if self.conns.len() >= TLS_MAX_CONNECTIONS {
+                    info!("tls: {} connections, drop the new one", self.conns.len());
+                    continue;
+                }
+
+                if let Some(filter) = GetPacketFilter() {
+                    if !filter.IngressConnection(tcp.as_raw_fd()) {
+                        continue;
+                    }
+                }
+
+                if let Err(e) = self.AddConn(tcp, plainPort) {
+                    info!("tls: connection to {} fail {:?}", plainPort, e);
+                }
+            }
+        }
+
+        fn AddConn(&mut self, tcp: TcpStream, plainPort: u16) -> Result<()> {
+            tcp.set_nonblocking(true).map_err(IOErr)?;
+            let (plain, guest) = UnixStream::pair().map_err(IOErr)?;
+            plain.set_nonblocking(true).map_err(IOErr)?;
+            let conn = ServerConnection::new(self.config.clone())
+                .map_err(|e| Error::Common(format!("tls: {:?}", e)))?;
+
+            let tcpfd = tcp.as_raw_fd();
+            let plainfd = plain.as_raw_fd();
+            self.Ctl(libc::EPOLL_CTL_ADD, tcpfd, libc::EPOLLIN as u32)?;
+            if let Err(e) = self.Ctl(libc::EPOLL_CTL_ADD, plainfd, 0) {
+                self.Ctl(libc::EPOLL_CTL_DEL, tcpfd, 0).ok();
+                return Err(e);
+            }
+
+            self.plains.insert(plainfd, tcpfd);
+            self.conns.insert(
+                tcpfd,
+                Conn {
+                    tcp: tcp,
+                    plain: plain,
+                    guest: Some(guest),
+                    conn: conn,
+                    plainPort: plainPort,
+                    pending: Vec::new(),
+                    tlsOpen: true,
+                    plainOpen: true,
+                    plainShut: false,
+                },
+            );
+
+            return Ok(());
+        }
+
+        fn Close(&mut self, tcpfd: RawFd) {
+            if let Some(conn) = self.conns.remove(&tcpfd) {
+                let plainfd = conn.plain.as_raw_fd();
+                self.plains.remove(&plainfd);
+                self.Ctl(libc::EPOLL_CTL_DEL, tcpfd, 0).ok();
+                self.Ctl(libc::EPOLL_CTL_DEL, plainfd, 0).ok();
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use kvm_bindings::*;
+use kvm_ioctls::{Kvm, VmFd};
+
+use super::super::super::qlib::common::*;
+
+// The TSC deadline timer of the vcpus, see qlib/kernel/arch/x86_64/tsc_timer.rs.
+// The local APICs are kept in KVM with the split irqchip, the PIC and the
+// IOAPIC stay in qvisor, which has none, so KVM_INTERRUPT still injects the
+// interrupts of qvisor as the external interrupts of the LINT0 of the APIC.
+pub const KVM_CAP_TSC_DEADLINE_TIMER: u32 = 72;
+pub const KVM_CAP_SPLIT_IRQCHIP: u32 = 121;
+
+const KVM_CHECK_EXTENSION: u64 = 0xae03;
+
+// the IOAPIC routes reserved by the split irqchip
+const SPLIT_IRQCHIP_ROUTES: u64 = 24;
+
+// cpuid 1 ecx
+const CPUID_1_ECX_X2APIC: u32 = 1 << 21;
+const CPUID_1_ECX_TSC_DEADLINE: u32 = 1 << 24;
+// cpuid 0x80000007 edx
+const CPUID_INVARIANT_TSC: u32 = 1 << 8;
+
+// the split irqchip of the vm is on
+static SPLIT_IRQCHIP: AtomicBool = AtomicBool::new(false);
+
+pub fn CheckExtension(kvm: &Kvm, cap: u32) -> bool {
+    let ret = unsafe { libc::ioctl(kvm.as_raw_fd(), KVM_CHECK_EXTENSION, cap as u64) };
+    return ret > 0;
+}
+
+// EnableX2Apic creates the local APICs of the vm and exposes the x2APIC to
+// the vcpus, it has to be called before the vcpus are created. It returns
+// false if the host doesn't support the split irqchip.
+pub fn EnableX2Apic(kvm: &Kvm, vmfd: &VmFd, cpuid: &mut CpuId) -> Result<bool> {
+    if SPLIT_IRQCHIP.load(Ordering::Relaxed) {
+        return Ok(true);
+    }
+
+    if !CheckExtension(kvm, KVM_CAP_SPLIT_IRQCHIP) {
+        info!("x2apic: split irqchip not supported by the host");
+        return Ok(false);
+    }
+
+    let mut cap: kvm_enable_cap = Default::default();
+    cap.cap = KVM_CAP_SPLIT_IRQCHIP;
+    cap.args[0] = SPLIT_IRQCHIP_ROUTES;
+    vmfd.enable_cap(&cap)
+        .map_err(|e| Error::IOError(format!("x2apic: split irqchip fail {:?}", e)))?;
+
+    for e in cpuid.as_mut_slice().iter_mut() {
+        if e.function == 1 {
+            e.ecx |= CPUID_1_ECX_X2APIC;
+        }
+    }
+
+    SPLIT_IRQCHIP.store(true, Ordering::Relaxed);
+    return Ok(true);
+}
+
+// EnableTscDeadlineTimer exposes the TSC deadline timer of the x2APIC to the
+// vcpus, it has to be called before the vcpus are created. It returns false
+// if the host doesn't support it, the guest keeps the timer of qvisor then.
+pub fn EnableTscDeadlineTimer(kvm: &Kvm, vmfd: &VmFd, cpuid: &mut CpuId) -> Result<bool> {
+    if !CheckExtension(kvm, KVM_CAP_TSC_DEADLINE_TIMER) {
+        info!("tsc deadline timer: not supported by the host");
+        return Ok(false);
+    }
+
+    if !EnableX2Apic(kvm, vmfd, cpuid)? {
+        return Ok(false);
+    }
+
+    let mut invariant = false;
+    for e in cpuid.as_mut_slice().iter_mut() {
+        if e.function == 1 {
+            e.ecx |= CPUID_1_ECX_TSC_DEADLINE;
+        } else if e.function == 0x80000007 {
+            // KVM reports the invariant TSC only when the host TSC is stable
+            // across the cpus and the sleep states
+            invariant = e.edx & CPUID_INVARIANT_TSC != 0;
+        }
+    }
+
+    info!("tsc deadline timer: enabled, invariant tsc {}", invariant);
+    return Ok(true);
+}
@@ -0,0 +1,211 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use std::ffi::CString;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::kernel::kernel::loadavg::RunnableTasks;
+use super::super::super::qlib::kernel::PAGE_MGR;
+use super::super::super::qlib::kernel::SHARESPACE;
+use super::super::super::qlib::linux_def::*;
+use super::super::super::qlib::usage::sandbox::*;
+use super::super::super::vmspace::teardown::*;
+use super::super::super::QUARK_CONFIG;
+use super::vm::IsRunning;
+
+// The usage page of the sandbox, see qlib/usage/sandbox.rs. It is the file
+// <USAGE_PAGE_DIR>/<sandbox id>, the agents map it read only. qvisor updates
+// it every UsagePeriodMs from a thread which doesn't enter the guest, the
+// file is removed when the sandbox is torn down.
+pub const USAGE_PAGE_DIR: &str = "/var/run/quark/usage";
+
+// the guest runnable time integrated by the publisher, for the stats of the
+// control socket
+pub static GUEST_RUNNABLE: AtomicU64 = AtomicU64::new(0);
+
+pub struct UsagePublisher {
+    pub page: &'static mut UsagePage,
+    pub usage: SandboxUsage,
+    // the guest ready tasks are sampled, their runnable time is integrated
+    pub guestRunnable: u64,
+}
+
+impl UsagePublisher {
+    pub fn Start(id: &str, periodMs: u64) -> Result<()> {
+        fs::create_dir_all(USAGE_PAGE_DIR).map_err(|e| {
+            Error::IOError(format!("usage: create {} fail {:?}", USAGE_PAGE_DIR, e))
+        })?;
+
+        let path = format!("{}/{}", USAGE_PAGE_DIR, id);
+        let page = MapPage(&path)?;
+        page.Init();
+
+        TEARDOWN.Register(
+            "usage page",
+            Box::new(move || {
+                let _ = fs::remove_file(&path);
+            }),
+        );
+
+        let mut publisher = Self {
+            page: page,
+            usage: SandboxUsage::default(),
+            guestRunnable: 0,
+        };
+
+        thread::Builder::new()
+            .name("usage".to_string())
+            .spawn(move || {
+                let period = Duration::from_millis(periodMs);
+                while IsRunning() {
+                    publisher.Publish();
+                    thread::sleep(period);
+                }
+            })
+            .map_err(|e| Error::IOError(format!("usage: spawn thread fail {:?}", e)))?;
+
+        return Ok(());
+    }
+
+    pub fn Publish(&mut self) {
+        let now = ClockNs(libc::CLOCK_MONOTONIC);
+        if self.usage.Timestamp != 0 && now > self.usage.Timestamp {
+            let ready = SHARESPACE.scheduler.GlobalReadyTaskCnt();
+            let ready = if ready > 0 { ready as u64 } else { 0 };
+            self.guestRunnable += ready * (now - self.usage.Timestamp);
+            GUEST_RUNNABLE.store(self.guestRunnable, Ordering::Relaxed);
+        }
+
+        self.usage = CurrentUsage(now, self.guestRunnable);
+        self.page.Publish(&self.usage);
+    }
+}
+
+// CurrentUsage returns the usage of the sandbox at now, the guest runnable
+// time is the one integrated by the usage publisher.
+pub fn CurrentUsage(now: u64, guestRunnable: u64) -> SandboxUsage {
+    let memLimit = {
+        let config = QUARK_CONFIG.lock();
+        if config.MemoryHigh > 0 {
+            config.MemoryHigh * MemoryDef::ONE_MB
+        } else {
+            config.KernelMemSize * MemoryDef::ONE_GB
+        }
+    };
+
+    return SandboxUsage {
+        Timestamp: now,
+        WorkingSet: PAGE_MGR.AllocatedPages() * MemoryDef::PAGE_SIZE
+            + SHARESPACE.stats.ZswapBytes(),
+        MemoryLimit: memLimit,
+        CpuTime: ClockNs(libc::CLOCK_PROCESS_CPUTIME_ID),
+        RunnableTime: guestRunnable + VcpuWaitTime(),
+        IoWaitTime: SHARESPACE.stats.IoWaitTime(),
+        RunnableTasks: RunnableTasks(),
+        IoWaitTasks: SHARESPACE.stats.IoWaitTasks(),
+    };
+}
+
+// SnapshotUsage returns the usage of the sandbox now, the guest runnable time
+// is 0 if the usage page is off.
+pub fn SnapshotUsage() -> SandboxUsage {
+    return CurrentUsage(
+        ClockNs(libc::CLOCK_MONOTONIC),
+        GUEST_RUNNABLE.load(Ordering::Relaxed),
+    );
+}
+
+fn MapPage(path: &str) -> Result<&'static mut UsagePage> {
+    let cstr = CString::new(path).unwrap();
+    let fd = unsafe {
+        libc::open(
+            cstr.as_ptr(),
+            libc::O_RDWR | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
+            0o644,
+        )
+    };
+    if fd < 0 {
+        return Err(Error::SysError(errno::errno().0));
+    }
+
+    let ret = unsafe { libc::ftruncate(fd, USAGE_PAGE_SIZE as i64) };
+    if ret < 0 {
+        let errno = errno::errno().0;
+        unsafe {
+            libc::close(fd);
+        }
+        return Err(Error::SysError(errno));
+    }
+
+    let addr = unsafe {
+        libc::mmap(
+            core::ptr::null_mut(),
+            USAGE_PAGE_SIZE,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            fd,
+            0,
+        )
+    };
+    let errno = errno::errno().0;
+    unsafe {
+        libc::close(fd);
+    }
+
+    if addr == libc::MAP_FAILED {
+        return Err(Error::SysError(errno));
+    }
+
+    return Ok(unsafe { &mut *(addr as *mut UsagePage) });
+}
+
+pub fn ClockNs(clock: libc::clockid_t) -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(clock, &mut ts);
+    }
+
+    return ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64;
+}
+
+// VcpuWaitTime returns the ns the threads of qvisor, the vcpus among them,
+// were runnable and waiting for a host cpu, the second field of schedstat.
+fn VcpuWaitTime() -> u64 {
+    let entries = match fs::read_dir("/proc/self/task") {
+        Err(_) => return 0,
+        Ok(entries) => entries,
+    };
+
+    let mut wait = 0;
+    for e in entries.filter_map(|e| e.ok()) {
+        let schedstat = match fs::read_to_string(e.path().join("schedstat")) {
+            Err(_) => continue,
+            Ok(s) => s,
+        };
+
+        if let Some(v) = schedstat.split_whitespace().nth(1) {
+            wait += v.parse::<u64>().unwrap_or(0);
+        }
+    }
+
+    return wait;
+}
@@ -29,6 +29,7 @@ use crate::qlib::MAX_VCPU_COUNT;
 use super::super::super::elf_loader::*;
 use super::super::super::kvm_vcpu::*;
 use super::super::super::print::LOG;
+use super::super::super::print::BOOT_CONSOLE;
 use super::super::super::qlib::addr;
 use super::super::super::qlib::common::*;
 use super::super::super::qlib::kernel::kernel::futex;
@@ -46,14 +47,36 @@ use super::super::super::qlib::pagetable::PageTables;
 use super::super::super::qlib::perf_tunning::*;
 use super::super::super::qlib::task_mgr::*;
 use super::super::super::qlib::ShareSpace;
+use super::super::super::runc::runtime::async_pf::*;
+use super::super::super::runc::runtime::audit::*;
+use super::super::super::runc::runtime::config_file::*;
+use super::super::super::runc::runtime::cpu_shaping::*;
+use super::super::super::runc::runtime::csum_offload::*;
+use super::super::super::runc::runtime::freeze_watch::*;
+use super::super::super::ucall::ucall_server::StartHostServer;
+use super::super::super::runc::runtime::hibernate::*;
+use super::super::super::runc::runtime::ksm::*;
 use super::super::super::runc::runtime::loader::*;
+use super::super::super::runc::runtime::mitigations::*;
+use super::super::super::runc::runtime::net_sysctl::*;
+use super::super::super::runc::runtime::pcap::*;
+use super::super::super::runc::runtime::qos::*;
+use super::super::super::runc::runtime::tls_proxy::*;
+use super::super::super::runc::runtime::tsc_timer::*;
+use super::super::super::runc::runtime::usage_page::*;
 use super::super::super::syncmgr;
+use super::super::super::vmspace::dirty_ring::DIRTY_RING;
+use super::super::super::vmspace::egress_policy::*;
+use super::super::super::vmspace::packet_filter::*;
+use super::super::super::vmspace::policy_route::*;
+use super::super::super::vmspace::qcall_pool::QCALL_POOL;
+use super::super::super::vmspace::teardown::*;
 use super::super::super::vmspace::*;
 use super::super::super::SHARE_SPACE;
 use super::super::super::SHARE_SPACE_STRUCT;
 use super::super::super::{
-    ThreadId, KERNEL_IO_THREAD, PMA_KEEPER, QUARK_CONFIG, ROOT_CONTAINER_ID, THREAD_ID, URING_MGR,
-    VCPU, VMS,
+    ThreadId, HOST_CONFIG, KERNEL_IO_THREAD, PMA_KEEPER, QUARK_CONFIG, ROOT_CONTAINER_ID, THREAD_ID,
+    URING_MGR, VCPU, VMS,
 };
 
 lazy_static! {
@@ -116,7 +139,7 @@ impl VirtualMachine {
             guest_phys_addr: phyAddr,
             memory_size: pageMmapsize,
             userspace_addr: hostAddr,
-            flags: 0, //kvm_bindings::KVM_MEM_LOG_DIRTY_PAGES,
+            flags: DIRTY_RING.MemRegionFlags(),
         };
 
         unsafe {
@@ -125,6 +148,7 @@ impl VirtualMachine {
                 .map_err(|e| Error::IOError(format!("io::error is {:?}", e)))?;
         }
 
+        DIRTY_RING.AddSlot(slotId, phyAddr, pageMmapsize);
         return Ok(());
     }
 
@@ -233,12 +257,66 @@ impl VirtualMachine {
         PerfGoto(PerfType::Other);
 
         *ROOT_CONTAINER_ID.lock() = args.ID.clone();
+        ApplyConfigAnnotations(
+            &mut QUARK_CONFIG.lock(),
+            &args.Spec,
+            &HOST_CONFIG.OverridableKeys,
+        )?;
         if QUARK_CONFIG.lock().PerSandboxLog {
             LOG.Reset(&args.ID[0..12]);
+            BOOT_CONSOLE.Reset(&args.ID[0..12]);
         }
 
         let kvmfd = args.KvmFd;
 
+        let ceiling = QUARK_CONFIG.lock().MemoryHigh;
+        if let Some(high) = MemoryHighFromSpec(&args.Spec, ceiling)? {
+            QUARK_CONFIG.lock().MemoryHigh = high;
+        }
+
+        let ceiling = QUARK_CONFIG.lock().EphemeralStorage;
+        if let Some(limit) = EphemeralStorageFromSpec(&args.Spec, ceiling)? {
+            QUARK_CONFIG.lock().EphemeralStorage = limit;
+        }
+        QUARK_CONFIG.lock().EphemeralStorageUsed = args.EphemeralUsed;
+
+        // the capture is a debug aid, the sandbox boots without it
+        match PcapCapture::FromSpec(&args.Spec) {
+            Err(e) => error!("pcap: capture not started {:?}", e),
+            Ok(None) => (),
+            Ok(Some(capture)) => {
+                if let Err(e) = capture.Start() {
+                    error!("pcap: capture not started {:?}", e);
+                }
+            }
+        }
+
+        if let Some(filter) = PacketFilter::Load()? {
+            filter.Install();
+        }
+
+        if let Some(policy) = RoutePolicy::FromSpec(&args.Spec)? {
+            policy.Install();
+        }
+
+        if let Some(policy) = EgressPolicy::FromSpec(&args.Spec)? {
+            policy.Install();
+        }
+
+        if QUARK_CONFIG.lock().ChecksumOffload {
+            NegotiateChecksumOffload(&args.Spec)?;
+        }
+
+        SetListenSysctls(&args.Spec, &QUARK_CONFIG.lock());
+
+        if let Some(qos) = EgressQos::FromSpec(&args.Spec)? {
+            qos.Apply()?;
+        }
+
+        if let Some(tls) = TlsOffload::FromSpec(&args.Spec)? {
+            tls.Start()?;
+        }
+
         /*if QUARK_CONFIG.lock().EnableRDMA {
             // use default rdma device
             let rdmaDeviceName = "";
@@ -253,6 +331,17 @@ impl VirtualMachine {
         let kernelMemRegionSize = QUARK_CONFIG.lock().KernelMemSize;
         let controlSock = args.ControlSock;
         let rdmaSvcCliSock = args.RDMASvcCliSock;
+        if rdmaSvcCliSock > 0 {
+            // the rdma service releases the QPs, MRs and memfds of the sandbox
+            // when the connection is shut down, a sandbox which hangs on its
+            // exit, e.g. dumping its core, holds them otherwise
+            TEARDOWN.Register(
+                "rdma connection",
+                Box::new(move || unsafe {
+                    libc::shutdown(rdmaSvcCliSock, libc::SHUT_RDWR);
+                }),
+            );
+        }
 
         let umask = Self::Umask();
         info!(
@@ -262,10 +351,19 @@ impl VirtualMachine {
 
         let kvm = unsafe { Kvm::from_raw_fd(kvmfd) };
 
-        let kvm_cpuid = kvm
+        let mut kvm_cpuid = kvm
             .get_supported_cpuid(kvm_bindings::KVM_MAX_CPUID_ENTRIES)
             .unwrap();
 
+        if let Some(shaping) = CpuShaping::FromSpec(&args.Spec)? {
+            shaping.Apply(&mut kvm_cpuid);
+        }
+
+        let off = QUARK_CONFIG.lock().MitigationsOff;
+        if let Some(mitigations) = Mitigations::FromSpec(&args.Spec, off)? {
+            mitigations.ApplyHost();
+        }
+
         let vm_fd = kvm
             .create_vm()
             .map_err(|e| Error::IOError(format!("io::error is {:?}", e)))?;
@@ -278,6 +376,22 @@ impl VirtualMachine {
             panic!("KVM_CAP_IMMEDIATE_EXIT not supported");
         }
 
+        let dirtyRingSize = QUARK_CONFIG.lock().DirtyRingSize;
+        DIRTY_RING.Enable(&kvm, &vm_fd, dirtyRingSize)?;
+
+        if QUARK_CONFIG.lock().TscDeadlineTimer
+            && !EnableTscDeadlineTimer(&kvm, &vm_fd, &mut kvm_cpuid)?
+        {
+            // the guest can't use the APIC of a vm without the irqchip
+            QUARK_CONFIG.lock().TscDeadlineTimer = false;
+        }
+
+        if QUARK_CONFIG.lock().AsyncPageFault
+            && !EnableAsyncPageFault(&kvm, &vm_fd, &mut kvm_cpuid)?
+        {
+            QUARK_CONFIG.lock().AsyncPageFault = false;
+        }
+
         let mut elf = KernelELF::New()?;
         Self::SetMemRegion(
             1,
@@ -375,6 +489,7 @@ impl VirtualMachine {
             )?);
             // enable cpuid in host
             vcpu.vcpu.set_cpuid2(&kvm_cpuid).unwrap();
+            DIRTY_RING.AddVcpu(vcpu.vcpu.as_raw_fd())?;
             VMS.lock().vcpus.push(vcpu.clone());
             vcpus.push(vcpu);
         }
@@ -413,6 +528,48 @@ impl VirtualMachine {
 
         syncmgr::SyncMgr::WaitShareSpaceReady();
         info!("shareSpace ready...");
+        let (latencyThreads, bulkThreads) = {
+            let config = QUARK_CONFIG.lock();
+            (config.QcallLatencyThreads, config.QcallBulkThreads)
+        };
+        QCALL_POOL.Start(latencyThreads, bulkThreads)?;
+
+        let usagePeriodMs = QUARK_CONFIG.lock().UsagePeriodMs;
+        if usagePeriodMs > 0 {
+            let id = ROOT_CONTAINER_ID.lock().clone();
+            if let Err(e) = UsagePublisher::Start(&id, usagePeriodMs) {
+                error!("usage: publish the usage page fail {:?}", e);
+            }
+        }
+
+        // ksm can be turned on by a reconfig, the marker runs anyway
+        if let Err(e) = KsmMarker::Start() {
+            error!("ksm: start fail {:?}", e);
+        }
+
+        if QUARK_CONFIG.lock().AuditClasses != 0 {
+            if let Err(e) = StartAuditExporter() {
+                error!("audit: start the exporter fail {:?}", e);
+            }
+        }
+
+        let hibernateIdleSec = QUARK_CONFIG.lock().HibernateIdleSec;
+        if hibernateIdleSec > 0 {
+            let id = ROOT_CONTAINER_ID.lock().clone();
+            if let Err(e) = Hibernator::Start(&id, hibernateIdleSec) {
+                error!("hibernate: start fail {:?}", e);
+            }
+        }
+
+        if let Err(e) = FreezeWatch::Start() {
+            error!("freeze watch: start fail {:?}", e);
+        }
+
+        let controlHostSock = VMS.lock().args.as_ref().unwrap().ControlHostSock;
+        if let Err(e) = StartHostServer(controlHostSock) {
+            error!("control host: start fail {:?}", e);
+        }
+
         for i in 1..self.vcpus.len() {
             let cpu = self.vcpus[i].clone();
 
@@ -25,9 +25,12 @@ use std::{thread, time};
 //use super::super::super::qlib::auth::cap_set::*;
 use super::super::super::qlib::auth::id::*;
 use super::super::super::qlib::common::*;
+use super::super::super::qlib::config::{Config, ConfigUpdate};
 use super::super::super::qlib::control_msg::*;
 use super::super::super::qlib::linux_def::*;
 use super::super::super::qlib::loader;
+use super::super::super::qlib::loader::EtcFiles;
+use super::super::super::qlib::usage::sandbox::SandboxUsage;
 use super::super::super::qlib::*;
 use super::super::super::ucall::ucall::*;
 use super::super::super::ucall::ucall_client::*;
@@ -38,9 +41,13 @@ use super::super::cmd::config::*;
 use super::super::container::container::*;
 use super::super::oci;
 use super::super::oci::*;
+use super::super::runtime::cdi::*;
 use super::super::runtime::console::*;
+use super::super::runtime::etc_files::*;
 use super::super::runtime::fs::FsImageMounter;
+use super::super::runtime::prefetch::*;
 use super::super::runtime::sandbox_process::*;
+use super::super::runtime::shm::*;
 use super::super::specutils::specutils;
 
 use super::super::shim::container_io::*;
@@ -209,7 +216,7 @@ impl Sandbox {
         let process = &spec.process;
         let terminal = process.terminal;
 
-        let process = SandboxProcess::New(conf, action, id, bundleDir, pivot)?;
+        let process = SandboxProcess::New(conf, action, id, bundleDir, spec, pivot)?;
         //let pid = process.Fork()?;
         let (pid, console) = process.Execv(terminal, consoleSocket, detach)?;
 
@@ -223,6 +230,7 @@ impl Sandbox {
     pub fn New1(
         id: &str,
         action: RunAction,
+        spec: &Spec,
         conf: &GlobalConfig,
         bundleDir: &str,
         io: &ContainerIO,
@@ -238,7 +246,7 @@ impl Sandbox {
 
         //let pid = CreateSandboxProcess(conf, id, bundleDir, ptyfd, autoStart)?;
 
-        let process = SandboxProcess::New(conf, action, id, bundleDir, pivot)?;
+        let process = SandboxProcess::New(conf, action, id, bundleDir, spec, pivot)?;
         //let pid = process.Fork()?;
         let pid = process.Execv1(io)?;
 
@@ -272,9 +280,11 @@ impl Sandbox {
 
         let req = UCallReq::Pause;
 
-        let _resp = client.Call(&req)?;
-
-        return Ok(());
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::UCallRespErr(e) => return Err(Error::Common(e)),
+            _ => return Ok(()),
+        }
     }
 
     pub fn Unpause(&self, cid: &str) -> Result<()> {
@@ -289,6 +299,108 @@ impl Sandbox {
         return Ok(());
     }
 
+    pub fn UpdateConfig(&self, update: &ConfigUpdate) -> Result<Config> {
+        info!("Update config of sandbox {}: {:?}", self.ID, update);
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::UpdateConfig(*update);
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::UpdateConfigResp(config) => Ok(config),
+            UCallResp::UCallRespErr(e) => Err(Error::Common(e)),
+            resp => {
+                panic!("UpdateConfig get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    pub fn UpdateEtcFiles(&self, files: &EtcFiles) -> Result<()> {
+        info!("Update /etc files of sandbox {}: {:?}", self.ID, files);
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::UpdateEtcFiles(files.clone());
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::UpdateEtcFilesResp => Ok(()),
+            UCallResp::UCallRespErr(e) => Err(Error::Common(e)),
+            resp => {
+                panic!("UpdateEtcFiles get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    pub fn ProcessResources(&self, args: &ProcessResourcesArgs) -> Result<ProcessResources> {
+        info!("Process resources of sandbox {}: {:?}", self.ID, args);
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::ProcessResources(args.clone());
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::ProcessResourcesResp(resources) => Ok(resources),
+            UCallResp::UCallRespErr(e) => Err(Error::Common(e)),
+            resp => {
+                panic!("ProcessResources get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    pub fn FlushRootfs(&self, cid: &str) -> Result<()> {
+        info!("Flush the root filesystem of container {}", cid);
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::FlushRootfs(cid.to_string());
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::FlushRootfsResp => Ok(()),
+            UCallResp::UCallRespErr(e) => Err(Error::Common(e)),
+            resp => {
+                panic!("FlushRootfs get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    pub fn ApiVersion(&self) -> Result<u32> {
+        let client = self.HostConnect()?;
+
+        let resp = client.Call(&UCallReq::Version)?;
+        match resp {
+            UCallResp::VersionResp(version) => Ok(version),
+            resp => {
+                panic!("ApiVersion get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    // Stats returns the resource usage of the sandbox, served by qvisor
+    // without the guest, the sandbox may be paused.
+    pub fn Stats(&self) -> Result<SandboxUsage> {
+        let client = self.HostConnect()?;
+
+        let resp = client.Call(&UCallReq::Stats)?;
+        match resp {
+            UCallResp::StatsResp(usage) => Ok(usage),
+            resp => {
+                panic!("Stats get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    pub fn Config(&self) -> Result<Config> {
+        let client = self.HostConnect()?;
+
+        let resp = client.Call(&UCallReq::Config)?;
+        match resp {
+            UCallResp::ConfigResp(config) => Ok(config),
+            resp => {
+                panic!("Config get unknow resp {:?}", resp);
+            }
+        }
+    }
+
     pub fn Processes(&self, cid: &str) -> Result<Vec<ProcessInfo>> {
         info!(
             "Getting processes for container {} in sandbox {}",
@@ -307,6 +419,36 @@ impl Sandbox {
         }
     }
 
+    pub fn Tasks(&self, cid: &str) -> Result<Vec<TaskInfo>> {
+        info!("Getting tasks for container {} in sandbox {}", cid, self.ID);
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::Tasks(cid.to_string());
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::TasksResp(tasks) => Ok(tasks),
+            resp => {
+                panic!("Tasks get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    pub fn Sockets(&self, cid: &str) -> Result<Vec<SocketInfo>> {
+        info!("Getting sockets for container {} in sandbox {}", cid, self.ID);
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::Sockets(cid.to_string());
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::SocketsResp(sockets) => Ok(sockets),
+            resp => {
+                panic!("Sockets get unknow resp {:?}", resp);
+            }
+        }
+    }
+
     pub fn StartRootContainer(&self) -> Result<()> {
         let client = self.SandboxConnect()?;
 
@@ -366,6 +508,7 @@ impl Sandbox {
         mounter.MountContainerFs(bundleDir, spec, id)?;
         let client = self.SandboxConnect()?;
         // to avoid sharing the spec structure with qkernel, construct the process spec from oci Spec.
+        let ioMax = IoMaxFromSpec(spec);
         let process = loader::Process {
             UID: spec.process.user.uid,
             GID: spec.process.user.gid,
@@ -380,6 +523,13 @@ impl Sandbox {
             ID: id.to_string(),
             Caps: specutils::Capabilities(false, &spec.process.capabilities),
             Root: format!("{}{}", "/", id),
+            EtcFiles: EtcFilesFromSpec(spec)?,
+            Devices: GuestDevices(spec),
+            IoMaxPartitions: IoMaxPartitions(&ioMax),
+            IoMax: ioMax,
+            Prefetch: PrefetchFromSpec(spec),
+            ShmSize: ShmSizeFromSpec(spec)?,
+            ShmVolume: ShmVolumeFromSpec(spec),
             ..Default::default()
         };
 
@@ -620,6 +770,20 @@ impl Sandbox {
         return Ok(client);
     }
 
+    // HostConnect connects to the socket of the requests qvisor serves on the
+    // host, a sandbox before the host socket serves them on the control
+    // socket.
+    pub fn HostConnect(&self) -> Result<UCallClient> {
+        let addr = ControlHostSocketAddr(&self.ID);
+        match UCallClient::Init(&addr) {
+            Ok(client) => return Ok(client),
+            Err(e) => {
+                info!("HostConnect connect {:?} fail {:?}", &addr, e);
+                return self.SandboxConnect();
+            }
+        }
+    }
+
     // IsRunning returns true if the sandbox is running.
     pub fn IsRunning(&self) -> bool {
         if self.Pid != 0 {
@@ -7,9 +7,16 @@ use std::fs::File;
 
 use super::super::super::qlib::auth::cap_set::*;
 use super::super::super::qlib::common::*;
+use super::super::super::qlib::config::Config;
+use super::super::super::qlib::kernel::version::UTS_LEN;
+use super::super::super::qlib::linux::time::SECOND;
 use super::super::super::qlib::linux_def::*;
 use super::super::super::qlib::path::*;
 use super::super::oci::*;
+use super::super::runtime::config_file::*;
+use super::super::runtime::cpu_shaping::*;
+use super::super::runtime::mitigations::*;
+use super::super::super::HOST_CONFIG;
 use super::fs::*;
 
 pub const EXE_PATH: &str = "/proc/self/exe";
@@ -39,6 +46,60 @@ const CONTAINERD_CONTAINER_TYPE_SANDBOX: &str = "sandbox";
 // is not the first container in the sandbox.
 const CONTAINERD_SANDBOX_IDANNOTATION: &str = "io.kubernetes.cri.sandbox-id";
 
+// the kernel release and version uname(2) reports, e.g. "5.15.0", for the
+// applications that gate features on the kernel version.
+pub const UNAME_RELEASE_ANNOTATION: &str = "quark.uname.release";
+pub const UNAME_VERSION_ANNOTATION: &str = "quark.uname.version";
+
+// UnameFromSpec returns the release and the version of the annotations, empty
+// if they are not set.
+pub fn UnameFromSpec(spec: &Spec) -> Result<(String, String)> {
+    let get = |name: &str| -> Result<String> {
+        match spec.annotations.get(name) {
+            None => return Ok("".to_string()),
+            Some(v) if v.len() > UTS_LEN => {
+                return Err(Error::Common(format!(
+                    "{} is longer than {} bytes",
+                    name, UTS_LEN
+                )))
+            }
+            Some(v) => return Ok(v.to_string()),
+        }
+    };
+
+    return Ok((
+        get(UNAME_RELEASE_ANNOTATION)?,
+        get(UNAME_VERSION_ANNOTATION)?,
+    ));
+}
+
+// the offsets in seconds of the monotonic and the boottime clocks of the
+// sandbox, i.e. of its root time namespace. The clocks start from 0 when the
+// sandbox boots, e.g. an offset of 86400 makes the sandbox look up for a day.
+pub const TIME_MONOTONIC_OFFSET_ANNOTATION: &str = "quark.time.monotonic.offset";
+pub const TIME_BOOTTIME_OFFSET_ANNOTATION: &str = "quark.time.boottime.offset";
+
+// TimeOffsetsFromSpec returns the monotonic and the boottime offsets of the
+// annotations in nanoseconds, 0 if they are not set.
+pub fn TimeOffsetsFromSpec(spec: &Spec) -> Result<(i64, i64)> {
+    let get = |name: &str| -> Result<i64> {
+        let v = match spec.annotations.get(name) {
+            None => return Ok(0),
+            Some(v) => v,
+        };
+
+        match v.trim().parse::<i64>() {
+            Ok(secs) if secs >= 0 && secs <= i64::MAX / 2 / SECOND => return Ok(secs * SECOND),
+            _ => return Err(Error::Common(format!("{}: invalid offset {}", name, v))),
+        }
+    };
+
+    return Ok((
+        get(TIME_MONOTONIC_OFFSET_ANNOTATION)?,
+        get(TIME_BOOTTIME_OFFSET_ANNOTATION)?,
+    ));
+}
+
 // ValidateSpec validates that the spec is compatible with qvisor.
 pub fn ValidateSpec(spec: &Spec) -> Result<()> {
     // Mandatory fields.
@@ -98,6 +159,16 @@ pub fn ValidateSpec(spec: &Spec) -> Result<()> {
         ValidateMount(m)?;
     }
 
+    UnameFromSpec(spec)?;
+    TimeOffsetsFromSpec(spec)?;
+    CpuShaping::FromSpec(spec)?;
+    Mitigations::FromSpec(spec)?;
+    ApplyConfigAnnotations(
+        &mut Config::default(),
+        spec,
+        &HOST_CONFIG.OverridableKeys,
+    )?;
+
     // Two annotations are use by containerd to support multi-container pods.
     //   "io.kubernetes.cri.container-type"
     //   "io.kubernetes.cri.sandbox-id"
@@ -196,6 +267,7 @@ pub fn IsSupportedDevMount(m: Mount) -> bool {
         "/dev/full",
         "/dev/random",
         "/dev/urandom",
+        "/dev/hwrng",
         "/dev/shm",
         "/dev/pts",
         "/dev/ptmx",
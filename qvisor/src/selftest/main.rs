@@ -0,0 +1,709 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(non_snake_case)]
+
+// quark-selftest is the guest side of `quark selftest`. It runs a suite of
+// syscall and netstack checks, LTP like but only the basic behavior the
+// workloads rely on, and writes a line per check to the result file:
+// "<pass|fail|unsupported>\t<name>\t<detail>". It is a separate binary as the
+// quark binary maps its heap at a fixed address and doesn't run in a guest.
+// A check is unsupported when the sandbox reports the feature missing, e.g.
+// ENOSYS, and fails when the feature misbehaves.
+
+use std::env;
+use std::ffi::CString;
+use std::fs;
+use std::io::{Read, Write};
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+enum Failure {
+    Unsupported(String),
+    Fail(String),
+}
+
+type CheckResult = std::result::Result<String, Failure>;
+
+const CHECKS: &[(&str, fn() -> CheckResult)] = &[
+    ("pipe", CheckPipe),
+    ("fork_wait", CheckForkWait),
+    ("mmap", CheckMmap),
+    ("memfd", CheckMemfd),
+    ("futex", CheckFutex),
+    ("clock", CheckClock),
+    ("getrandom", CheckGetrandom),
+    ("eventfd", CheckEventfd),
+    ("epoll", CheckEpoll),
+    ("timerfd", CheckTimerfd),
+    ("signalfd", CheckSignalfd),
+    ("inotify", CheckInotify),
+    ("procfs", CheckProcfs),
+    ("unix_socketpair", CheckUnixSocketpair),
+    ("tcp_loopback", CheckTcpLoopback),
+    ("tcp6_loopback", CheckTcp6Loopback),
+    ("udp_loopback", CheckUdpLoopback),
+    ("io_uring", CheckIoUring),
+];
+
+fn Errno() -> i32 {
+    return std::io::Error::last_os_error().raw_os_error().unwrap_or(0);
+}
+
+fn ErrnoStr(errno: i32) -> String {
+    return std::io::Error::from_raw_os_error(errno).to_string();
+}
+
+// Sys checks the return of a libc call, the errors of a missing feature make
+// the check unsupported.
+fn Sys(ret: i64, what: &str) -> std::result::Result<i64, Failure> {
+    if ret >= 0 {
+        return Ok(ret);
+    }
+
+    let errno = Errno();
+    match errno {
+        libc::ENOSYS | libc::EOPNOTSUPP | libc::EAFNOSUPPORT | libc::EPROTONOSUPPORT => {
+            return Err(Failure::Unsupported(format!(
+                "{}: {}",
+                what,
+                ErrnoStr(errno)
+            )));
+        }
+        _ => return Err(Failure::Fail(format!("{}: {}", what, ErrnoStr(errno)))),
+    }
+}
+
+// Create checks the return of a call creating an object, only the errors of
+// a missing syscall or object type make the check unsupported. An EINVAL is a
+// failure, the flags passed are all supported by the sandbox.
+fn Create(ret: i64, what: &str) -> std::result::Result<i64, Failure> {
+    if ret >= 0 {
+        return Ok(ret);
+    }
+
+    let errno = Errno();
+    match errno {
+        libc::ENOSYS | libc::EOPNOTSUPP | libc::EAFNOSUPPORT => {
+            return Err(Failure::Unsupported(format!(
+                "{}: {}",
+                what,
+                ErrnoStr(errno)
+            )));
+        }
+        _ => return Err(Failure::Fail(format!("{}: {}", what, ErrnoStr(errno)))),
+    }
+}
+
+fn IoErr(e: std::io::Error, what: &str) -> Failure {
+    return match e.raw_os_error() {
+        Some(libc::ENOSYS)
+        | Some(libc::EOPNOTSUPP)
+        | Some(libc::EAFNOSUPPORT)
+        | Some(libc::EADDRNOTAVAIL) => Failure::Unsupported(format!("{}: {}", what, e)),
+        _ => Failure::Fail(format!("{}: {}", what, e)),
+    };
+}
+
+fn Expect(cond: bool, msg: &str) -> std::result::Result<(), Failure> {
+    if !cond {
+        return Err(Failure::Fail(msg.to_string()));
+    }
+
+    return Ok(());
+}
+
+fn Close(fds: &[i32]) {
+    for fd in fds {
+        unsafe {
+            libc::close(*fd);
+        }
+    }
+}
+
+fn WriteAll(fd: i32, buf: &[u8], what: &str) -> std::result::Result<(), Failure> {
+    let n = Sys(
+        unsafe { libc::write(fd, buf.as_ptr() as _, buf.len()) } as i64,
+        what,
+    )?;
+    return Expect(
+        n as usize == buf.len(),
+        &format!("{}: short write {}", what, n),
+    );
+}
+
+fn ReadU64(fd: i32, what: &str) -> std::result::Result<u64, Failure> {
+    let mut val: u64 = 0;
+    let n = Sys(
+        unsafe { libc::read(fd, &mut val as *mut u64 as _, 8) } as i64,
+        what,
+    )?;
+    Expect(n == 8, &format!("{}: short read {}", what, n))?;
+    return Ok(val);
+}
+
+fn CheckPipe() -> CheckResult {
+    let mut fds = [0i32; 2];
+    Sys(
+        unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } as i64,
+        "pipe2",
+    )?;
+    let res = (|| {
+        WriteAll(fds[1], b"quark", "write")?;
+        let mut buf = [0u8; 8];
+        let n = Sys(
+            unsafe { libc::read(fds[0], buf.as_mut_ptr() as _, buf.len()) } as i64,
+            "read",
+        )?;
+        Expect(
+            &buf[..n as usize] == b"quark",
+            "read back a different message",
+        )?;
+        return Ok("".to_string());
+    })();
+    Close(&fds);
+    return res;
+}
+
+fn CheckForkWait() -> CheckResult {
+    let pid = Sys(unsafe { libc::fork() } as i64, "fork")? as i32;
+    if pid == 0 {
+        unsafe { libc::_exit(7) };
+    }
+
+    let mut status = 0;
+    Sys(
+        unsafe { libc::waitpid(pid, &mut status, 0) } as i64,
+        "waitpid",
+    )?;
+    Expect(
+        libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 7,
+        &format!("child exit status {:x}, expect 7", status),
+    )?;
+    return Ok("".to_string());
+}
+
+fn CheckMmap() -> CheckResult {
+    let len = 4 * 4096;
+    let addr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    if addr == libc::MAP_FAILED {
+        return Err(Failure::Fail(format!("mmap: {}", ErrnoStr(Errno()))));
+    }
+
+    let res = (|| {
+        let slice = unsafe { std::slice::from_raw_parts_mut(addr as *mut u8, len) };
+        Expect(
+            slice.iter().all(|b| *b == 0),
+            "anonymous memory isn't zeroed",
+        )?;
+        slice[len - 1] = 0x5a;
+        Sys(
+            unsafe { libc::mprotect(addr, len, libc::PROT_READ) } as i64,
+            "mprotect",
+        )?;
+        Expect(slice[len - 1] == 0x5a, "memory changed by mprotect")?;
+        return Ok("".to_string());
+    })();
+    unsafe {
+        libc::munmap(addr, len);
+    }
+    return res;
+}
+
+fn CheckMemfd() -> CheckResult {
+    let name = CString::new("selftest").unwrap();
+    let fd = Create(
+        unsafe { libc::syscall(libc::SYS_memfd_create, name.as_ptr(), libc::MFD_CLOEXEC) },
+        "memfd_create",
+    )? as i32;
+    let res = (|| {
+        Sys(unsafe { libc::ftruncate(fd, 4096) } as i64, "ftruncate")?;
+        let addr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                4096,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(Failure::Fail(format!("mmap: {}", ErrnoStr(Errno()))));
+        }
+
+        unsafe {
+            *(addr as *mut u64) = 0x0123456789abcdef;
+            libc::munmap(addr, 4096);
+        }
+
+        let mut val: u64 = 0;
+        Sys(
+            unsafe { libc::pread(fd, &mut val as *mut u64 as _, 8, 0) } as i64,
+            "pread",
+        )?;
+        Expect(
+            val == 0x0123456789abcdef,
+            "shared mapping not seen by pread",
+        )?;
+        return Ok("".to_string());
+    })();
+    Close(&[fd]);
+    return res;
+}
+
+fn CheckFutex() -> CheckResult {
+    let word: u32 = 1;
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            &word as *const u32,
+            libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+            0,
+            std::ptr::null::<libc::timespec>(),
+        )
+    };
+    Expect(
+        ret == -1 && Errno() == libc::EAGAIN,
+        "FUTEX_WAIT on a changed value doesn't return EAGAIN",
+    )?;
+
+    let woken = Sys(
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                &word as *const u32,
+                libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                1,
+            )
+        },
+        "FUTEX_WAKE",
+    )?;
+    Expect(woken == 0, &format!("FUTEX_WAKE woke {} waiters", woken))?;
+    return Ok("".to_string());
+}
+
+fn ClockGettime(clock: libc::clockid_t, what: &str) -> std::result::Result<i64, Failure> {
+    let mut ts: libc::timespec = unsafe { mem::zeroed() };
+    Create(unsafe { libc::clock_gettime(clock, &mut ts) } as i64, what)?;
+    return Ok(ts.tv_sec as i64 * 1_000_000_000 + ts.tv_nsec as i64);
+}
+
+fn CheckClock() -> CheckResult {
+    let start = ClockGettime(libc::CLOCK_MONOTONIC, "CLOCK_MONOTONIC")?;
+    std::thread::sleep(Duration::from_millis(10));
+    let end = ClockGettime(libc::CLOCK_MONOTONIC, "CLOCK_MONOTONIC")?;
+    Expect(
+        end - start >= 10_000_000,
+        &format!("slept 10ms, CLOCK_MONOTONIC advanced {}ns", end - start),
+    )?;
+
+    ClockGettime(libc::CLOCK_BOOTTIME, "CLOCK_BOOTTIME")?;
+    let realtime = ClockGettime(libc::CLOCK_REALTIME, "CLOCK_REALTIME")?;
+    // 2020-01-01
+    Expect(
+        realtime > 1_577_836_800 * 1_000_000_000,
+        "CLOCK_REALTIME is before 2020",
+    )?;
+    return Ok("".to_string());
+}
+
+fn CheckGetrandom() -> CheckResult {
+    let mut buf = [0u8; 32];
+    let n = Sys(
+        unsafe { libc::syscall(libc::SYS_getrandom, buf.as_mut_ptr(), buf.len(), 0) },
+        "getrandom",
+    )?;
+    Expect(
+        n as usize == buf.len(),
+        &format!("getrandom returned {} bytes", n),
+    )?;
+    Expect(buf.iter().any(|b| *b != 0), "getrandom returned zeros")?;
+    return Ok("".to_string());
+}
+
+fn CheckEventfd() -> CheckResult {
+    let fd = Create(
+        unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) } as i64,
+        "eventfd",
+    )? as i32;
+    let res = (|| {
+        WriteAll(fd, &3u64.to_ne_bytes(), "write")?;
+        WriteAll(fd, &4u64.to_ne_bytes(), "write")?;
+        let val = ReadU64(fd, "read")?;
+        Expect(val == 7, &format!("counter is {}, expect 7", val))?;
+        let ret = unsafe { libc::read(fd, [0u8; 8].as_mut_ptr() as _, 8) };
+        Expect(
+            ret == -1 && Errno() == libc::EAGAIN,
+            "read of a zero counter doesn't return EAGAIN",
+        )?;
+        return Ok("".to_string());
+    })();
+    Close(&[fd]);
+    return res;
+}
+
+fn CheckEpoll() -> CheckResult {
+    let epfd = Create(
+        unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) } as i64,
+        "epoll_create1",
+    )? as i32;
+    let mut fds = [-1i32; 2];
+    let res = (|| {
+        Sys(
+            unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } as i64,
+            "pipe2",
+        )?;
+        let mut ev = libc::epoll_event {
+            events: libc::EPOLLIN as u32,
+            u64: 42,
+        };
+        Sys(
+            unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_ADD, fds[0], &mut ev) } as i64,
+            "epoll_ctl",
+        )?;
+
+        let mut events: [libc::epoll_event; 4] = unsafe { mem::zeroed() };
+        let n = Sys(
+            unsafe { libc::epoll_wait(epfd, events.as_mut_ptr(), 4, 0) } as i64,
+            "epoll_wait",
+        )?;
+        Expect(n == 0, "empty pipe reported readable")?;
+
+        WriteAll(fds[1], b"x", "write")?;
+        let n = Sys(
+            unsafe { libc::epoll_wait(epfd, events.as_mut_ptr(), 4, 1000) } as i64,
+            "epoll_wait",
+        )?;
+        let data = events[0].u64;
+        Expect(n == 1 && data == 42, "pipe write not reported")?;
+        return Ok("".to_string());
+    })();
+    Close(&[epfd, fds[0], fds[1]]);
+    return res;
+}
+
+fn CheckTimerfd() -> CheckResult {
+    let fd = Create(
+        unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, libc::TFD_CLOEXEC) } as i64,
+        "timerfd_create",
+    )? as i32;
+    let res = (|| {
+        let spec = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            it_value: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 10_000_000,
+            },
+        };
+        Sys(
+            unsafe { libc::timerfd_settime(fd, 0, &spec, std::ptr::null_mut()) } as i64,
+            "timerfd_settime",
+        )?;
+        let expirations = ReadU64(fd, "read")?;
+        Expect(
+            expirations == 1,
+            &format!("one shot timer expired {} times", expirations),
+        )?;
+        return Ok("".to_string());
+    })();
+    Close(&[fd]);
+    return res;
+}
+
+fn CheckSignalfd() -> CheckResult {
+    let mut mask: libc::sigset_t = unsafe { mem::zeroed() };
+    let mut old: libc::sigset_t = unsafe { mem::zeroed() };
+    unsafe {
+        libc::sigemptyset(&mut mask);
+        libc::sigaddset(&mut mask, libc::SIGUSR1);
+    }
+    Sys(
+        unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &mask, &mut old) } as i64,
+        "sigprocmask",
+    )?;
+
+    let res = (|| {
+        let fd = Create(
+            unsafe { libc::signalfd(-1, &mask, libc::SFD_CLOEXEC) } as i64,
+            "signalfd",
+        )? as i32;
+        let res = (|| {
+            Sys(
+                unsafe {
+                    libc::syscall(
+                        libc::SYS_tgkill,
+                        libc::getpid(),
+                        libc::syscall(libc::SYS_gettid),
+                        libc::SIGUSR1,
+                    )
+                },
+                "tgkill",
+            )?;
+            let mut info: libc::signalfd_siginfo = unsafe { mem::zeroed() };
+            let size = mem::size_of::<libc::signalfd_siginfo>();
+            let n = Sys(
+                unsafe { libc::read(fd, &mut info as *mut _ as _, size) } as i64,
+                "read",
+            )?;
+            Expect(
+                n as usize == size && info.ssi_signo == libc::SIGUSR1 as u32,
+                &format!("read signal {}, expect SIGUSR1", info.ssi_signo),
+            )?;
+            return Ok("".to_string());
+        })();
+        Close(&[fd]);
+        return res;
+    })();
+
+    unsafe {
+        libc::pthread_sigmask(libc::SIG_SETMASK, &old, std::ptr::null_mut());
+    }
+    return res;
+}
+
+fn CheckInotify() -> CheckResult {
+    let fd = Create(
+        unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) } as i64,
+        "inotify_init1",
+    )? as i32;
+    let dir = env::temp_dir().join(format!("selftest-inotify-{}", process::id()));
+    let res = (|| {
+        fs::create_dir_all(&dir).map_err(|e| IoErr(e, "mkdir"))?;
+        let path = CString::new(dir.to_string_lossy().to_string()).unwrap();
+        Sys(
+            unsafe { libc::inotify_add_watch(fd, path.as_ptr(), libc::IN_CREATE) } as i64,
+            "inotify_add_watch",
+        )?;
+        fs::write(dir.join("file"), b"x").map_err(|e| IoErr(e, "create"))?;
+
+        let mut buf = [0u8; 4096];
+        let n = Sys(
+            unsafe { libc::read(fd, buf.as_mut_ptr() as _, buf.len()) } as i64,
+            "read",
+        )?;
+        Expect(
+            n as usize >= mem::size_of::<libc::inotify_event>(),
+            "no event for the created file",
+        )?;
+        let event = unsafe { &*(buf.as_ptr() as *const libc::inotify_event) };
+        Expect(event.mask & libc::IN_CREATE != 0, "event isn't IN_CREATE")?;
+        return Ok("".to_string());
+    })();
+    Close(&[fd]);
+    fs::remove_dir_all(&dir).ok();
+    return res;
+}
+
+fn CheckProcfs() -> CheckResult {
+    let status =
+        fs::read_to_string("/proc/self/status").map_err(|e| IoErr(e, "/proc/self/status"))?;
+    let pid = format!("{}", process::id());
+    Expect(
+        status
+            .lines()
+            .any(|l| l.starts_with("Pid:") && l[4..].trim() == pid),
+        "/proc/self/status has a different pid",
+    )?;
+
+    let exe = fs::read_link("/proc/self/exe").map_err(|e| IoErr(e, "/proc/self/exe"))?;
+    let arg0 = env::args().next().unwrap_or_default();
+    Expect(
+        exe.file_name() == Path::new(&arg0).file_name(),
+        &format!("/proc/self/exe is {:?}, expect {}", exe, arg0),
+    )?;
+    return Ok("".to_string());
+}
+
+fn CheckUnixSocketpair() -> CheckResult {
+    let mut fds = [0i32; 2];
+    Create(
+        unsafe {
+            libc::socketpair(
+                libc::AF_UNIX,
+                libc::SOCK_STREAM | libc::SOCK_CLOEXEC,
+                0,
+                fds.as_mut_ptr(),
+            )
+        } as i64,
+        "socketpair",
+    )?;
+    let res = (|| {
+        WriteAll(fds[0], b"ping", "write")?;
+        let mut buf = [0u8; 8];
+        let n = Sys(
+            unsafe { libc::read(fds[1], buf.as_mut_ptr() as _, buf.len()) } as i64,
+            "read",
+        )?;
+        Expect(
+            &buf[..n as usize] == b"ping",
+            "read back a different message",
+        )?;
+
+        let mut cred: libc::ucred = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+        Sys(
+            unsafe {
+                libc::getsockopt(
+                    fds[1],
+                    libc::SOL_SOCKET,
+                    libc::SO_PEERCRED,
+                    &mut cred as *mut _ as _,
+                    &mut len,
+                )
+            } as i64,
+            "SO_PEERCRED",
+        )?;
+        Expect(
+            cred.pid == process::id() as i32,
+            &format!("SO_PEERCRED pid {}", cred.pid),
+        )?;
+        return Ok("".to_string());
+    })();
+    Close(&fds);
+    return res;
+}
+
+fn TcpLoopback(addr: SocketAddr) -> CheckResult {
+    let listener = TcpListener::bind(addr).map_err(|e| IoErr(e, "bind"))?;
+    let addr = listener.local_addr().map_err(|e| IoErr(e, "getsockname"))?;
+    let mut client = TcpStream::connect_timeout(&addr, Duration::from_secs(5))
+        .map_err(|e| IoErr(e, "connect"))?;
+    let (mut server, peer) = listener.accept().map_err(|e| IoErr(e, "accept"))?;
+    Expect(
+        peer == client.local_addr().map_err(|e| IoErr(e, "getsockname"))?,
+        "accept returned a different peer address",
+    )?;
+
+    // larger than a segment, to cover the segmentation and the window
+    let data: Vec<u8> = (0..256 * 1024).map(|i| i as u8).collect();
+    let sent = data.clone();
+    let writer = std::thread::spawn(move || -> std::io::Result<()> {
+        client.write_all(&sent)?;
+        client.shutdown(std::net::Shutdown::Write)?;
+        return Ok(());
+    });
+
+    server
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .map_err(|e| IoErr(e, "SO_RCVTIMEO"))?;
+    let mut received = Vec::new();
+    server
+        .read_to_end(&mut received)
+        .map_err(|e| IoErr(e, "read"))?;
+    writer.join().unwrap().map_err(|e| IoErr(e, "write"))?;
+    Expect(
+        received == data,
+        &format!(
+            "received {} of {} bytes or corrupted",
+            received.len(),
+            data.len()
+        ),
+    )?;
+    return Ok(format!("{}", addr));
+}
+
+fn CheckTcpLoopback() -> CheckResult {
+    return TcpLoopback(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)));
+}
+
+fn CheckTcp6Loopback() -> CheckResult {
+    return TcpLoopback(SocketAddr::from((Ipv6Addr::LOCALHOST, 0)));
+}
+
+fn CheckUdpLoopback() -> CheckResult {
+    let server = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).map_err(|e| IoErr(e, "bind"))?;
+    let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).map_err(|e| IoErr(e, "bind"))?;
+    let addr = server.local_addr().map_err(|e| IoErr(e, "getsockname"))?;
+    client
+        .send_to(b"datagram", addr)
+        .map_err(|e| IoErr(e, "sendto"))?;
+
+    server
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .map_err(|e| IoErr(e, "SO_RCVTIMEO"))?;
+    let mut buf = [0u8; 64];
+    let (n, peer) = server
+        .recv_from(&mut buf)
+        .map_err(|e| IoErr(e, "recvfrom"))?;
+    Expect(&buf[..n] == b"datagram", "received a different datagram")?;
+    Expect(
+        peer == client.local_addr().map_err(|e| IoErr(e, "getsockname"))?,
+        "recvfrom returned a different peer address",
+    )?;
+    return Ok(format!("{}", addr));
+}
+
+fn CheckIoUring() -> CheckResult {
+    let mut params = [0u8; 120];
+    let fd = Create(
+        unsafe { libc::syscall(libc::SYS_io_uring_setup, 4, params.as_mut_ptr()) },
+        "io_uring_setup",
+    )? as i32;
+    Close(&[fd]);
+    return Ok("".to_string());
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        eprintln!("usage: {} <result file>", args[0]);
+        process::exit(2);
+    }
+
+    // a line is written as soon as the check is done, so the host still gets
+    // the results before a check which hangs or crashes the suite
+    let mut out = match fs::File::create(&args[1]) {
+        Err(e) => {
+            eprintln!("create {} fail {:?}", &args[1], e);
+            process::exit(2);
+        }
+        Ok(f) => f,
+    };
+
+    let mut failed = 0;
+    for (name, check) in CHECKS {
+        let (status, detail) = match check() {
+            Ok(detail) => ("pass", detail),
+            Err(Failure::Unsupported(detail)) => ("unsupported", detail),
+            Err(Failure::Fail(detail)) => {
+                failed += 1;
+                ("fail", detail)
+            }
+        };
+
+        let line = format!("{}\t{}\t{}\n", status, name, detail.replace('\n', " "));
+        if let Err(e) = out.write_all(line.as_bytes()) {
+            eprintln!("write {} fail {:?}", &args[1], e);
+            process::exit(2);
+        }
+    }
+
+    process::exit(if failed > 0 { 1 } else { 0 });
+}
@@ -14,7 +14,9 @@
 
 use alloc::string::String;
 
+use super::super::qlib::config::*;
 use super::super::qlib::control_msg::*;
+use super::super::qlib::loader::EtcFiles;
 use super::super::runc::container::container::*;
 
 // ControlSocketAddr generates an abstract unix socket name for the given ID.
@@ -22,9 +24,36 @@ pub fn ControlSocketAddr(id: &str) -> String {
     return format!("\x00qvisor-sandbox.{}", id);
 }
 
+// ControlHostSocketAddr is the socket of the requests qvisor serves without
+// the guest, they are answered while the sandbox is paused too.
+pub fn ControlHostSocketAddr(id: &str) -> String {
+    return format!("\x00qvisor-sandbox-host.{}", id);
+}
+
 pub const UCALL_BUF_LEN: usize = 4096;
+
+// The version of the control API of the sandbox. A request is a u32 length in
+// the native byte order followed by the json of a UCallMsg, the response is
+// framed the same with a UCallResp. The requests and the responses are only
+// added to, a client checks the version before it sends a request the sandbox
+// may not know.
+//
+// 1: Version, Stats and Config
+// 2: AuditStream
+// 3: FlushRootfs
+// 4: TlsAccept
+// 5: the version of the request, the host socket
+pub const UCALL_API_VERSION: u32 = 5;
 type Cid = String;
 
+// UCallMsg is a request with the version of the API of the client. A request
+// of a client before version 5 is a bare UCallReq.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UCallMsg<R> {
+    pub version: u32,
+    pub req: R,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum UCallReq {
     RootContainerStart(RootContainerStart),
@@ -32,6 +61,8 @@ pub enum UCallReq {
     Pause,
     Unpause,
     Ps(Cid),
+    Tasks(Cid),
+    Sockets(Cid),
     WaitContainer(Cid),
     WaitPid(WaitPid),
     Signal(SignalArgs),
@@ -39,6 +70,15 @@ pub enum UCallReq {
     CreateSubContainer(CreateArgs),
     StartSubContainer(StartArgs),
     WaitAll,
+    UpdateConfig(ConfigUpdate),
+    UpdateEtcFiles(EtcFiles),
+    ProcessResources(ProcessResourcesArgs),
+    Version,
+    Stats,
+    Config,
+    AuditStream,
+    FlushRootfs(Cid),
+    TlsAccept(TlsAcceptArgs),
 }
 
 impl FileDescriptors for UCallReq {
@@ -53,6 +93,7 @@ impl FileDescriptors for UCallReq {
                     return Some(&args.fds);
                 }
             }
+            UCallReq::TlsAccept(args) => return Some(core::slice::from_ref(&args.fd)),
             _ => return None,
         }
     }
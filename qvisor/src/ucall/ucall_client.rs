@@ -29,9 +29,17 @@ impl UCallClient {
         return Ok(Self { sock: sock });
     }
 
+    fn Serialize(req: &UCallReq) -> Result<Vec<u8>> {
+        let msg = UCallMsg {
+            version: UCALL_API_VERSION,
+            req: req,
+        };
+        return serde_json::to_vec(&msg)
+            .map_err(|e| Error::Common(format!("UCallClient ser error is {:?}", e)));
+    }
+
     pub fn Call(&self, req: &UCallReq) -> Result<UCallResp> {
-        let reqArr = serde_json::to_vec(req)
-            .map_err(|e| Error::Common(format!("UCallClient ser error is {:?}", e)))?;
+        let reqArr = Self::Serialize(req)?;
         let fds = req.GetFds();
         match fds {
             None => self.sock.WriteLen(reqArr.len(), &[])?,
@@ -55,8 +63,7 @@ impl UCallClient {
     }
 
     pub fn StreamCall(&self, req: &UCallReq) -> Result<()> {
-        let reqArr = serde_json::to_vec(req)
-            .map_err(|e| Error::Common(format!("UCallClient ser error is {:?}", e)))?;
+        let reqArr = Self::Serialize(req)?;
         let fds = req.GetFds();
         match fds {
             None => self.sock.WriteLen(reqArr.len(), &[])?,
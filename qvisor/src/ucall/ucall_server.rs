@@ -15,19 +15,30 @@
 use crate::qlib::kernel::GlobalIOMgr;
 
 use super::super::qlib::common::*;
+use super::super::qlib::config::*;
 use super::super::qlib::control_msg::*;
 use super::super::qlib::linux_def::*;
 use super::super::qlib::loader;
+use super::super::qlib::loader::EtcFiles;
 use super::super::runc::container::container::*;
+use super::super::runc::runtime::audit;
+use super::super::runc::runtime::hibernate::*;
+use super::super::runc::runtime::prefetch::*;
+use super::super::runc::runtime::usage_page::SnapshotUsage;
+use super::super::vmspace::keepalive_offload::*;
 use super::super::vmspace::*;
+use super::super::QUARK_CONFIG;
 use super::super::URING_MGR;
 use super::ucall::*;
 use super::usocket::*;
 
-pub fn ReadControlMsg(fd: i32) -> Result<ControlMsg> {
+// ReadControlMsg reads the request of the connection, it returns None for a
+// request qvisor has served without the guest.
+pub fn ReadControlMsg(fd: i32) -> Result<Option<ControlMsg>> {
     let usock = USocket { socket: fd };
 
-    let (mut req, fds) = match usock.GetReq() {
+    let req = Authenticate(&usock).and_then(|()| usock.GetReq());
+    let (mut req, fds) = match req {
         Ok((req, fds)) => ((req, fds)),
         Err(e) => {
             let err = UCallResp::UCallRespErr(format!("{:?}", e));
@@ -37,8 +48,159 @@ pub fn ReadControlMsg(fd: i32) -> Result<ControlMsg> {
         }
     };
 
-    let msg = ProcessReqHandler(&mut req, &fds);
-    return msg;
+    if let UCallReq::AuditStream = req {
+        return AuditStreamHandler(usock).map(|()| None);
+    }
+
+    if let Some(resp) = HostReqHandler(&req) {
+        let ret = usock.SendResp(&resp);
+        usock.Drop();
+        ret?;
+        return Ok(None);
+    }
+
+    let msg = ProcessReqHandler(&mut req, &fds)?;
+    return Ok(Some(msg));
+}
+
+// Authenticate checks the peer of the control socket. The socket is abstract,
+// any process of the network namespace of qvisor can connect to it, only root
+// and the user qvisor runs as can control the sandbox.
+pub fn Authenticate(usock: &USocket) -> Result<()> {
+    let cred = usock.PeerCred()?;
+    let euid = unsafe { libc::geteuid() };
+    if cred.uid != 0 && cred.uid != euid {
+        info!(
+            "control socket: reject the request of pid {} uid {}",
+            cred.pid, cred.uid
+        );
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    return Ok(());
+}
+
+// the time the host server waits for the request and the response of a
+// connection, a client doesn't block the other ones longer
+const HOST_CONN_TIMEOUT_SEC: i64 = 1;
+
+// StartHostServer serves the requests which don't need the guest on a socket
+// of their own from a host thread, they are answered while the guest is
+// paused or busy.
+pub fn StartHostServer(sock: i32) -> Result<()> {
+    let flags = unsafe { libc::fcntl(sock, libc::F_GETFL) };
+    let ret = unsafe { libc::fcntl(sock, libc::F_SETFL, flags & !libc::O_NONBLOCK) };
+    if flags < 0 || ret < 0 {
+        return Err(Error::SysError(errno::errno().0));
+    }
+
+    std::thread::Builder::new()
+        .name("control host".to_string())
+        .spawn(move || loop {
+            let fd = unsafe {
+                libc::accept4(
+                    sock,
+                    core::ptr::null_mut(),
+                    core::ptr::null_mut(),
+                    libc::SOCK_CLOEXEC,
+                )
+            };
+            if fd < 0 {
+                let errno = errno::errno().0;
+                if errno != SysErr::EINTR && errno != SysErr::ECONNABORTED {
+                    error!("control host: accept fail {}", errno);
+                    return;
+                }
+                continue;
+            }
+
+            if let Err(e) = HostConn(fd) {
+                info!("control host: request fail {:?}", e);
+            }
+
+            unsafe {
+                libc::close(fd);
+            }
+        })
+        .map_err(|e| Error::IOError(format!("control host: spawn thread fail {:?}", e)))?;
+
+    return Ok(());
+}
+
+fn HostConn(fd: i32) -> Result<()> {
+    let timeout = libc::timeval {
+        tv_sec: HOST_CONN_TIMEOUT_SEC,
+        tv_usec: 0,
+    };
+    for opt in &[libc::SO_RCVTIMEO, libc::SO_SNDTIMEO] {
+        let ret = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                *opt,
+                &timeout as *const _ as *const libc::c_void,
+                core::mem::size_of::<libc::timeval>() as u32,
+            )
+        };
+        if ret < 0 {
+            return Err(Error::SysError(errno::errno().0));
+        }
+    }
+
+    // the connection is closed by the caller, USocket::Drop is for the
+    // connections of the uring
+    let usock = USocket { socket: fd };
+    let resp = match Authenticate(&usock).and_then(|()| usock.GetReq()) {
+        Ok((req, fds)) => {
+            for fd in fds {
+                unsafe {
+                    libc::close(fd);
+                }
+            }
+
+            match HostReqHandler(&req) {
+                Some(resp) => resp,
+                None => UCallResp::UCallRespErr(format!(
+                    "{:?} is served by the control socket",
+                    req
+                )),
+            }
+        }
+        Err(e) => UCallResp::UCallRespErr(format!("{:?}", e)),
+    };
+
+    return usock.SendResp(&resp);
+}
+
+// HostReqHandler serves the requests which don't need the guest. The clients
+// before the host socket send them to the control socket, they are served
+// there too.
+pub fn HostReqHandler(req: &UCallReq) -> Option<UCallResp> {
+    match req {
+        UCallReq::Version => return Some(UCallResp::VersionResp(UCALL_API_VERSION)),
+        UCallReq::Stats => return Some(UCallResp::StatsResp(SnapshotUsage())),
+        UCallReq::Config => return Some(UCallResp::ConfigResp(*QUARK_CONFIG.lock())),
+        _ => return None,
+    }
+}
+
+// AuditStreamHandler keeps the connection open for the audit exporter, it is
+// closed by the exporter.
+pub fn AuditStreamHandler(usock: USocket) -> Result<()> {
+    if QUARK_CONFIG.lock().AuditClasses == 0 {
+        let err = UCallResp::UCallRespErr("the audit is off".to_string());
+        let ret = usock.SendResp(&err);
+        usock.Drop();
+        return ret;
+    }
+
+    if let Err(e) = usock.SendResp(&UCallResp::AuditStreamResp) {
+        usock.Drop();
+        return Err(e);
+    }
+
+    audit::Subscribe(usock);
+    return Ok(());
 }
 
 pub fn RootContainerStartHandler(start: &RootContainerStart) -> Result<ControlMsg> {
@@ -50,6 +212,8 @@ pub fn RootContainerStartHandler(start: &RootContainerStart) -> Result<ControlMs
 }
 
 pub fn ExecProcessHandler(execArgs: &mut ExecArgs, fds: &[i32]) -> Result<ControlMsg> {
+    // the exec faults the pages of a hibernated sandbox in anyway
+    HIBERNATOR.Wake();
     execArgs.SetFds(fds);
 
     let mut process = loader::Process::default();
@@ -81,11 +245,28 @@ pub fn ExecProcessHandler(execArgs: &mut ExecArgs, fds: &[i32]) -> Result<Contro
 }
 
 pub fn PauseHandler() -> Result<ControlMsg> {
+    let config = *QUARK_CONFIG.lock();
+    if config.PauseKeepalive && config.PauseKeepaliveIdle > 0 {
+        KEEPALIVE_OFFLOAD.lock().Offload(config.PauseKeepaliveIdle);
+    }
+
+    HIBERNATOR.NotePause(true);
+
     let msg = ControlMsg::New(Payload::Pause);
     return Ok(msg);
 }
 
+// PauseAbort is called by the guest when it fails the pause, the sandbox runs
+// on and the connections get their own keepalives back.
+pub fn PauseAbort() {
+    KEEPALIVE_OFFLOAD.lock().Restore();
+    HIBERNATOR.NotePause(false);
+}
+
 pub fn UnpauseHandler() -> Result<ControlMsg> {
+    KEEPALIVE_OFFLOAD.lock().Restore();
+    HIBERNATOR.NotePause(false);
+
     let msg = ControlMsg::New(Payload::Unpause);
     return Ok(msg);
 }
@@ -95,6 +276,16 @@ pub fn PsHandler(cid: &str) -> Result<ControlMsg> {
     return Ok(msg);
 }
 
+pub fn TasksHandler(cid: &str) -> Result<ControlMsg> {
+    let msg = ControlMsg::New(Payload::Tasks(cid.to_string()));
+    return Ok(msg);
+}
+
+pub fn SocketsHandler(cid: &str) -> Result<ControlMsg> {
+    let msg = ControlMsg::New(Payload::Sockets(cid.to_string()));
+    return Ok(msg);
+}
+
 pub fn WaitHandler(cid: &str) -> Result<ControlMsg> {
     let msg = ControlMsg::New(Payload::WaitContainer(cid.to_string()));
     return Ok(msg);
@@ -153,10 +344,52 @@ pub fn CreateSubContainerHandler(args: &mut CreateArgs, fds: &[i32]) -> Result<C
 }
 
 pub fn StartSubContainerHandler(args: &mut StartArgs) -> Result<ControlMsg> {
+    Prefetcher::Start(&args.process);
     let msg = ControlMsg::New(Payload::StartSubContainer(args.clone()));
     return Ok(msg);
 }
 
+pub fn UpdateConfigHandler(update: &ConfigUpdate) -> Result<ControlMsg> {
+    // qvisor keeps its own copy of the config, the guest side one is updated by qkernel
+    let changed = QUARK_CONFIG.lock().Update(update);
+    info!("UpdateConfig {:?}, changed: {}", update, changed);
+
+    let msg = ControlMsg::New(Payload::UpdateConfig(*update));
+    return Ok(msg);
+}
+
+pub fn UpdateEtcFilesHandler(files: &EtcFiles) -> Result<ControlMsg> {
+    let msg = ControlMsg::New(Payload::UpdateEtcFiles(files.clone()));
+    return Ok(msg);
+}
+
+pub fn ProcessResourcesHandler(args: &ProcessResourcesArgs) -> Result<ControlMsg> {
+    let msg = ControlMsg::New(Payload::ProcessResources(args.clone()));
+    return Ok(msg);
+}
+
+pub fn FlushRootfsHandler(cid: &str) -> Result<ControlMsg> {
+    let msg = ControlMsg::New(Payload::FlushRootfs(cid.to_string()));
+    return Ok(msg);
+}
+
+pub fn TlsAcceptHandler(args: &mut TlsAcceptArgs, fds: &[i32]) -> Result<ControlMsg> {
+    if fds.len() != 1 {
+        return Err(Error::Common(format!("TlsAccept gets {} fds", fds.len())));
+    }
+
+    args.fd = fds[0];
+    if let Err(e) = URING_MGR.lock().Addfd(args.fd) {
+        unsafe {
+            libc::close(args.fd);
+        }
+        return Err(e);
+    }
+    GlobalIOMgr().AddSocket(args.fd);
+    let msg = ControlMsg::New(Payload::TlsAccept(args.clone()));
+    return Ok(msg);
+}
+
 pub fn ProcessReqHandler(req: &mut UCallReq, fds: &[i32]) -> Result<ControlMsg> {
     let msg = match req {
         UCallReq::RootContainerStart(start) => RootContainerStartHandler(start)?,
@@ -164,6 +397,8 @@ pub fn ProcessReqHandler(req: &mut UCallReq, fds: &[i32]) -> Result<ControlMsg>
         UCallReq::Pause => PauseHandler()?,
         UCallReq::Unpause => UnpauseHandler()?,
         UCallReq::Ps(cid) => PsHandler(cid)?,
+        UCallReq::Tasks(cid) => TasksHandler(cid)?,
+        UCallReq::Sockets(cid) => SocketsHandler(cid)?,
         UCallReq::WaitContainer(cid) => WaitHandler(cid)?,
         UCallReq::WaitPid(waitpid) => WaitPidHandler(waitpid)?,
         UCallReq::Signal(signalArgs) => SignalHandler(signalArgs)?,
@@ -171,6 +406,14 @@ pub fn ProcessReqHandler(req: &mut UCallReq, fds: &[i32]) -> Result<ControlMsg>
         UCallReq::CreateSubContainer(args) => CreateSubContainerHandler(args, fds)?,
         UCallReq::StartSubContainer(args) => StartSubContainerHandler(args)?,
         UCallReq::WaitAll => WaitAll()?,
+        UCallReq::UpdateConfig(update) => UpdateConfigHandler(update)?,
+        UCallReq::UpdateEtcFiles(files) => UpdateEtcFilesHandler(files)?,
+        UCallReq::ProcessResources(args) => ProcessResourcesHandler(args)?,
+        UCallReq::FlushRootfs(cid) => FlushRootfsHandler(cid)?,
+        UCallReq::TlsAccept(args) => TlsAcceptHandler(args, fds)?,
+        UCallReq::Version | UCallReq::Stats | UCallReq::Config | UCallReq::AuditStream => {
+            return Err(Error::Common(format!("{:?} is served by qvisor", req)))
+        }
     };
 
     return Ok(msg);
@@ -250,14 +250,60 @@ impl USocket {
         return Ok((len as usize, fds));
     }
 
+    // PeerCred returns the pid, the uid and the gid of the peer at the connect.
+    pub fn PeerCred(&self) -> Result<ucred> {
+        let mut cred = ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = core::mem::size_of::<ucred>() as socklen_t;
+        let ret = unsafe {
+            getsockopt(
+                self.socket,
+                SOL_SOCKET,
+                SO_PEERCRED,
+                &mut cred as *mut _ as *mut c_void,
+                &mut len,
+            )
+        };
+
+        if ret < 0 {
+            return Err(Error::SysError(errno::errno().0 as i32));
+        }
+
+        return Ok(cred);
+    }
+
     pub fn GetReq(&self) -> Result<(UCallReq, Vec<i32>)> {
         let (len, fds) = self.ReadLen()?;
         let mut buf: [u8; UCALL_BUF_LEN] = [0; UCALL_BUF_LEN];
 
-        assert!(len < UCALL_BUF_LEN, "UCallClient::Call req is too long");
+        if len >= UCALL_BUF_LEN {
+            for fd in &fds {
+                unsafe {
+                    close(*fd);
+                }
+            }
+            return Err(Error::Common(format!("UCallSrv req of {} bytes is too long", len)));
+        }
+
         self.ReadAll(&mut buf[0..len])?;
-        let req: UCallReq = serde_json::from_slice(&buf[0..len])
-            .map_err(|e| Error::Common(format!("UCallSrv deser error is {:?}", e)))?;
+        let req = match serde_json::from_slice::<UCallMsg<serde_json::Value>>(&buf[0..len]) {
+            Ok(msg) => match serde_json::from_value::<UCallReq>(msg.req) {
+                Ok(req) => req,
+                Err(_) if msg.version > UCALL_API_VERSION => {
+                    return Err(Error::Common(format!(
+                        "UCallSrv req of version {}, the sandbox serves version {}",
+                        msg.version, UCALL_API_VERSION
+                    )))
+                }
+                Err(e) => return Err(Error::Common(format!("UCallSrv deser error is {:?}", e))),
+            },
+            // a client before the version of the request
+            Err(_) => serde_json::from_slice::<UCallReq>(&buf[0..len])
+                .map_err(|e| Error::Common(format!("UCallSrv deser error is {:?}", e)))?,
+        };
 
         return Ok((req, fds));
     }
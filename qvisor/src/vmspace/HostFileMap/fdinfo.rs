@@ -22,6 +22,9 @@ use crate::qlib::kernel::GlobalIOMgr;
 //use super::socket_info::*;
 //use super::rdma_socket::*;
 use super::super::super::util::*;
+use super::super::egress_policy::*;
+use super::super::packet_filter::*;
+use super::super::policy_route::*;
 use super::super::qlib::common::*;
 use super::super::*;
 //use super::super::FD_NOTIFIER;
@@ -219,24 +222,74 @@ impl FdInfo {
             return SysRet(newfd as i64);
         }
 
+        if let Some(filter) = GetPacketFilter() {
+            if !filter.IngressConnection(newfd) {
+                unsafe {
+                    close(newfd);
+                }
+                return -SysErr::ECONNABORTED as i64;
+            }
+        }
+
         let hostfd = GlobalIOMgr().AddSocket(newfd);
         URING_MGR.lock().Addfd(newfd).unwrap();
         return SysRet(hostfd as i64);
     }
 
     pub fn Connect(sockfd: i32, addr: u64, addrlen: u32) -> i64 {
+        if let Some(filter) = GetPacketFilter() {
+            if !filter.Egress(sockfd, addr, addrlen) {
+                return -SysErr::EPERM as i64;
+            }
+        }
+
+        if let Some(policy) = GetEgressPolicy() {
+            if !policy.Egress(addr, addrlen) {
+                return -SysErr::EPERM as i64;
+            }
+        }
+
+        if let Some(policy) = GetRoutePolicy() {
+            policy.Connect(sockfd, addr, addrlen);
+        }
+
         let ret = unsafe { connect(sockfd, addr as *const sockaddr, addrlen as socklen_t) };
 
         return SysRet(ret as i64);
     }
 
     pub fn RecvMsg(sockfd: i32, msghdr: u64, flags: i32) -> i64 {
-        let ret = unsafe { recvmsg(sockfd, msghdr as *mut msghdr, flags as c_int) };
+        let ret = if let Some(filter) = GetPacketFilter() {
+            RecvMsgFiltered(&filter, sockfd, msghdr, flags)
+        } else {
+            let ret = unsafe { recvmsg(sockfd, msghdr as *mut msghdr, flags as c_int) };
+            SysRet(ret as i64)
+        };
 
-        return SysRet(ret as i64);
+        if ret > 0 && flags & MSG_ERRQUEUE == 0 {
+            if let Some(policy) = GetEgressPolicy() {
+                policy.RecvMsg(sockfd, msghdr, ret as usize);
+            }
+        }
+
+        return ret;
     }
 
     pub fn SendMsg(sockfd: i32, msghdr: u64, flags: i32) -> i64 {
+        if let Some(filter) = GetPacketFilter() {
+            let hdr = unsafe { &*(msghdr as *const msghdr) };
+            if !filter.Egress(sockfd, hdr.msg_name as u64, hdr.msg_namelen) {
+                return -SysErr::EPERM as i64;
+            }
+        }
+
+        if let Some(policy) = GetEgressPolicy() {
+            let hdr = unsafe { &*(msghdr as *const msghdr) };
+            if !policy.Egress(hdr.msg_name as u64, hdr.msg_namelen) {
+                return -SysErr::EPERM as i64;
+            }
+        }
+
         let ret = unsafe { sendmsg(sockfd, msghdr as *mut msghdr, flags as c_int) };
 
         return SysRet(ret as i64);
@@ -292,6 +345,12 @@ impl FdInfo {
             ret
         };
 
+        if ret == 0 {
+            if let Some(policy) = GetRoutePolicy() {
+                policy.Bind(sockfd, sockaddr, addrlen);
+            }
+        }
+
         return SysRet(ret as i64);
     }
 
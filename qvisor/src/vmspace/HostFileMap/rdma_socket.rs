@@ -106,7 +106,7 @@ impl RDMAServerSock {
             IO_MGR().AddWait(fd, EVENT_READ | EVENT_WRITE);
 
             if !super::rdma_socket::RDMA_ENABLE {
-                let (trigger, tmp) = acceptQueue.lock().EnqSocket(fd, tcpAddr, len, socketBuf);
+                let (trigger, tmp) = acceptQueue.lock().EnqSocket(fd, tcpAddr, len, Some(socketBuf));
                 hasSpace = tmp;
 
                 if trigger {
@@ -526,7 +526,7 @@ impl RDMADataSock {
                     serverSock.fd,
                     serverSock.addr,
                     serverSock.len,
-                    serverSock.sockBuf.clone(),
+                    Some(serverSock.sockBuf.clone()),
                 );
 
                 if trigger {
@@ -0,0 +1,264 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::Ordering;
+use std::os::unix::io::AsRawFd;
+use std::sync::Mutex;
+
+use kvm_bindings::*;
+use kvm_ioctls::{Kvm, VmFd};
+
+use super::super::qlib::common::*;
+use super::super::qlib::linux_def::*;
+
+// The dirty page tracking of the guest memory with the KVM dirty ring. KVM
+// pushes the guest frame of each page a vcpu writes to the ring of the vcpu,
+// the ring is shared with qvisor so collecting the dirty pages doesn't copy
+// and clear a bitmap of the whole memory as KVM_GET_DIRTY_LOG. The collected
+// pages are kept in a bitmap per memory slot till they are harvested by the
+// pre-copy of the hibernation image.
+// The pages the host writes to, e.g. the buffers of the host IO, are not seen
+// by KVM, the hibernation compares them with the image instead.
+pub const KVM_CAP_DIRTY_LOG_RING: u32 = 192;
+pub const KVM_EXIT_DIRTY_RING_FULL: u32 = 31;
+
+const KVM_CHECK_EXTENSION: u64 = 0xae03;
+const KVM_RESET_DIRTY_RINGS: u64 = 0xaec7;
+// the offset in pages of the dirty ring in the mmap of the vcpu fd
+const KVM_DIRTY_LOG_PAGE_OFFSET: i64 = 64;
+
+const KVM_DIRTY_GFN_F_DIRTY: u32 = 1 << 0;
+const KVM_DIRTY_GFN_F_RESET: u32 = 1 << 1;
+
+#[repr(C)]
+struct KvmDirtyGfn {
+    flags: AtomicU32,
+    slot: u32,
+    offset: u64,
+}
+
+struct DirtyRing {
+    addr: u64,
+    // the next entry to collect
+    fetch: u32,
+}
+
+struct DirtySlot {
+    phyAddr: u64,
+    bitmap: Vec<u64>,
+}
+
+impl DirtySlot {
+    fn Set(&mut self, page: u64) {
+        let idx = (page / 64) as usize;
+        if idx < self.bitmap.len() {
+            self.bitmap[idx] |= 1 << (page % 64);
+        }
+    }
+}
+
+#[derive(Default)]
+struct DirtyRingState {
+    vmfd: i32,
+    entries: u32,
+    rings: Vec<DirtyRing>,
+    slots: BTreeMap<u32, DirtySlot>,
+}
+
+pub struct DirtyPageTracker {
+    enabled: AtomicBool,
+    state: Mutex<DirtyRingState>,
+}
+
+lazy_static! {
+    pub static ref DIRTY_RING: DirtyPageTracker = DirtyPageTracker::New();
+}
+
+impl DirtyPageTracker {
+    pub fn New() -> Self {
+        return Self {
+            enabled: AtomicBool::new(false),
+            state: Mutex::new(DirtyRingState::default()),
+        };
+    }
+
+    pub fn Enabled(&self) -> bool {
+        return self.enabled.load(Ordering::Acquire);
+    }
+
+    // Enable turns on the dirty ring of the vm with entries per vcpu, it has
+    // to be called before the vcpus are created. It returns false if the host
+    // doesn't support the dirty ring.
+    pub fn Enable(&self, kvm: &Kvm, vmfd: &VmFd, entries: u32) -> Result<bool> {
+        if entries == 0 {
+            return Ok(false);
+        }
+
+        let maxBytes = unsafe {
+            libc::ioctl(
+                kvm.as_raw_fd(),
+                KVM_CHECK_EXTENSION,
+                KVM_CAP_DIRTY_LOG_RING as u64,
+            )
+        };
+        if maxBytes <= 0 {
+            info!("dirty ring: not supported by the host");
+            return Ok(false);
+        }
+
+        let size = core::mem::size_of::<KvmDirtyGfn>() as u64;
+        let mut entries = (entries as u64).next_power_of_two();
+        // the ring takes at least a page
+        if entries * size < MemoryDef::PAGE_SIZE {
+            entries = MemoryDef::PAGE_SIZE / size;
+        }
+        if entries * size > maxBytes as u64 {
+            entries = maxBytes as u64 / size;
+        }
+
+        let mut cap: kvm_enable_cap = Default::default();
+        cap.cap = KVM_CAP_DIRTY_LOG_RING;
+        cap.args[0] = entries * size;
+        vmfd.enable_cap(&cap)
+            .map_err(|e| Error::IOError(format!("dirty ring: enable fail {:?}", e)))?;
+
+        let mut state = self.state.lock().unwrap();
+        state.vmfd = vmfd.as_raw_fd();
+        state.entries = entries as u32;
+        self.enabled.store(true, Ordering::Release);
+        info!("dirty ring: {} entries per vcpu", entries);
+        return Ok(true);
+    }
+
+    // MemRegionFlags returns the flags of the memory slots tracked.
+    pub fn MemRegionFlags(&self) -> u32 {
+        if self.Enabled() {
+            return KVM_MEM_LOG_DIRTY_PAGES;
+        }
+
+        return 0;
+    }
+
+    pub fn AddSlot(&self, slot: u32, phyAddr: u64, size: u64) {
+        if !self.Enabled() {
+            return;
+        }
+
+        let pages = size / MemoryDef::PAGE_SIZE;
+        let mut bitmap = Vec::new();
+        bitmap.resize(((pages + 63) / 64) as usize, 0);
+        self.state.lock().unwrap().slots.insert(
+            slot,
+            DirtySlot {
+                phyAddr: phyAddr,
+                bitmap: bitmap,
+            },
+        );
+    }
+
+    // AddVcpu maps the dirty ring of the vcpu.
+    pub fn AddVcpu(&self, vcpufd: i32) -> Result<()> {
+        if !self.Enabled() {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let len = state.entries as usize * core::mem::size_of::<KvmDirtyGfn>();
+        let addr = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                vcpufd,
+                KVM_DIRTY_LOG_PAGE_OFFSET * MemoryDef::PAGE_SIZE as i64,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(Error::SysError(errno::errno().0));
+        }
+
+        state.rings.push(DirtyRing {
+            addr: addr as u64,
+            fetch: 0,
+        });
+        return Ok(());
+    }
+
+    // Collect moves the dirty pages of the rings to the bitmaps and lets KVM
+    // reuse the entries. A vcpu exits with a full ring till it is collected.
+    pub fn Collect(&self) {
+        if !self.Enabled() {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let mask = state.entries - 1;
+        let mut count = 0;
+        for ring in &mut state.rings {
+            loop {
+                let gfn = unsafe {
+                    &*((ring.addr as *const KvmDirtyGfn).offset((ring.fetch & mask) as isize))
+                };
+                if gfn.flags.load(Ordering::Acquire) & KVM_DIRTY_GFN_F_DIRTY == 0 {
+                    break;
+                }
+
+                // the upper 16 bits are the address space
+                if let Some(slot) = state.slots.get_mut(&(gfn.slot & 0xffff)) {
+                    slot.Set(gfn.offset);
+                }
+
+                gfn.flags.store(KVM_DIRTY_GFN_F_RESET, Ordering::Release);
+                ring.fetch = ring.fetch.wrapping_add(1);
+                count += 1;
+            }
+        }
+
+        if count > 0 {
+            let ret = unsafe { libc::ioctl(state.vmfd, KVM_RESET_DIRTY_RINGS) };
+            if ret < 0 {
+                error!("dirty ring: reset fail {}", errno::errno().0);
+            }
+        }
+    }
+
+    // Harvest returns the guest physical addresses of the pages written since
+    // the last harvest, the first one returns all the pages written since the
+    // tracking started.
+    pub fn Harvest(&self) -> Vec<u64> {
+        self.Collect();
+
+        let mut pages = Vec::new();
+        let mut state = self.state.lock().unwrap();
+        for slot in state.slots.values_mut() {
+            for (i, word) in slot.bitmap.iter_mut().enumerate() {
+                let mut bits = *word;
+                *word = 0;
+                while bits != 0 {
+                    let bit = bits.trailing_zeros() as u64;
+                    bits &= bits - 1;
+                    pages.push(slot.phyAddr + (i as u64 * 64 + bit) * MemoryDef::PAGE_SIZE);
+                }
+            }
+        }
+
+        return pages;
+    }
+}
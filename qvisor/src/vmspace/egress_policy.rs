@@ -0,0 +1,757 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use lazy_static::lazy_static;
+use libc::*;
+use spin::Mutex;
+use std::fs;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::super::qlib::common::*;
+use super::super::qlib::path::Clean;
+use super::super::qlib::rcu::*;
+use super::super::runc::oci::*;
+use super::super::HOST_CONFIG;
+use super::packet_filter::{Family, SockAddr, SockName, SocketProtocol};
+use super::policy_route::Cidr;
+
+// The egress policy of the sandbox, the destinations the applications may
+// connect(2) and sendmsg(2) to. The operator sets the policy of the sandboxes
+// in the config file ([egress] rules), a sandbox narrows it with an
+// annotation, a destination must be allowed by both:
+//   "quark.egress.rules" = "allow 10.96.0.0/12; allow host *.github.com port 443; deny all"
+// The rules of a policy are tried in order, the first one matching the
+// destination allows or denies it, a destination no rule matches is allowed.
+// A rule is "allow|deny <cidr>|all [port <port>]" or "allow|deny host <name>
+// [port <port>]", "*.example.com" matches the names under example.com. The
+// loopback and unspecified addresses are not egress and always allowed.
+//
+// The host name of an address is learnt from the DNS answers the sandbox
+// receives from its resolvers, the application connects to the address it
+// looked up, so a host rule matches an address only after its lookup. The
+// resolvers are the ones of the config file ([egress] dns). When the rules of
+// the operator have no host rule, the host rules of the sandbox may trust the
+// nameservers of the /etc/resolv.conf mounted in the container, or the
+// "quark.egress.dns" annotation, e.g. "10.96.0.10", the answers from any
+// other address are not trusted.
+pub const EGRESS_RULES_ANNOTATION: &str = "quark.egress.rules";
+pub const EGRESS_DNS_ANNOTATION: &str = "quark.egress.dns";
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+const DNS_PORT: u16 = 53;
+const DNS_TYPE_A: u16 = 1;
+const DNS_TYPE_AAAA: u16 = 28;
+
+// an answer is kept at least this long, the application may connect a while
+// after the lookup
+const DNS_MIN_TTL: u64 = 60;
+const DNS_MAX_ADDRS: usize = 4096;
+
+lazy_static! {
+    pub static ref EGRESS_POLICY: QRcu<Option<Arc<EgressPolicy>>> = QRcu::New(None);
+}
+
+pub fn GetEgressPolicy() -> Option<Arc<EgressPolicy>> {
+    return EGRESS_POLICY.Read().as_ref().clone();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Cidr(Cidr),
+    Host(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct EgressRule {
+    pub allow: bool,
+    pub target: Target,
+    // 0 for any port
+    pub port: u16,
+}
+
+impl EgressRule {
+    pub fn Parse(s: &str) -> Result<Self> {
+        let invalid = || Error::Common(format!("egress: invalid rule {}", s));
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let allow = match words.get(0) {
+            Some(&"allow") => true,
+            Some(&"deny") => false,
+            _ => return Err(invalid()),
+        };
+
+        let (target, rest) = match words.get(1) {
+            None => return Err(invalid()),
+            Some(&"host") => match words.get(2) {
+                None => return Err(invalid()),
+                Some(name) => {
+                    let name = name.trim_end_matches('.').to_lowercase();
+                    (Target::Host(name), &words[3..])
+                }
+            },
+            Some(cidr) => (Target::Cidr(Cidr::Parse(cidr)?), &words[2..]),
+        };
+
+        let port = match rest {
+            [] => 0,
+            ["port", p] => match p.parse::<u16>() {
+                Ok(p) if p > 0 => p,
+                _ => return Err(invalid()),
+            },
+            _ => return Err(invalid()),
+        };
+
+        return Ok(Self {
+            allow: allow,
+            target: target,
+            port: port,
+        });
+    }
+
+    pub fn Matches(&self, addr: &[u8], port: u16, names: &[String]) -> bool {
+        if self.port != 0 && self.port != port {
+            return false;
+        }
+
+        match &self.target {
+            Target::Cidr(cidr) => return cidr.Contains(addr),
+            Target::Host(pattern) => return names.iter().any(|n| HostMatches(pattern, n)),
+        }
+    }
+}
+
+pub fn HostMatches(pattern: &str, name: &str) -> bool {
+    let domain = match pattern.strip_prefix("*.") {
+        None => return pattern == name,
+        Some(d) => d,
+    };
+
+    return name.len() > domain.len() + 1
+        && name.ends_with(domain)
+        && name.as_bytes()[name.len() - domain.len() - 1] == b'.';
+}
+
+// DnsCache keeps the names the addresses were looked up with until their
+// answers expire.
+#[derive(Default)]
+pub struct DnsCache {
+    pub addrs: BTreeMap<Vec<u8>, Vec<(String, Instant)>>,
+}
+
+impl DnsCache {
+    pub fn Names(&self, addr: &[u8]) -> Vec<String> {
+        let now = Instant::now();
+        match self.addrs.get(addr) {
+            None => return Vec::new(),
+            Some(names) => {
+                return names
+                    .iter()
+                    .filter(|(_, expiry)| *expiry > now)
+                    .map(|(name, _)| name.clone())
+                    .collect()
+            }
+        }
+    }
+
+    pub fn Add(&mut self, addr: Vec<u8>, name: &str, ttl: u32) {
+        let now = Instant::now();
+        let expiry = now + Duration::from_secs(core::cmp::max(ttl as u64, DNS_MIN_TTL));
+
+        if self.addrs.len() >= DNS_MAX_ADDRS && !self.addrs.contains_key(&addr) {
+            self.addrs.retain(|_, names| {
+                names.retain(|(_, e)| *e > now);
+                names.len() > 0
+            });
+
+            if self.addrs.len() >= DNS_MAX_ADDRS {
+                info!("egress: dns cache full, drop the answer for {}", name);
+                return;
+            }
+        }
+
+        let names = self.addrs.entry(addr).or_default();
+        match names.iter_mut().find(|(n, _)| n == name) {
+            None => names.push((name.to_string(), expiry)),
+            Some(entry) => entry.1 = core::cmp::max(entry.1, expiry),
+        }
+    }
+}
+
+// Allowed returns whether the first rule matching the destination allows it.
+pub fn Allowed(rules: &[EgressRule], addr: &[u8], port: u16, names: &[String]) -> bool {
+    match rules.iter().find(|r| r.Matches(addr, port, names)) {
+        None => return true,
+        Some(r) => return r.allow,
+    }
+}
+
+pub fn ParseRules(rules: &str) -> Result<Vec<EgressRule>> {
+    let mut ret = Vec::new();
+    for rule in rules.split(';').map(|r| r.trim()).filter(|r| r.len() > 0) {
+        ret.push(EgressRule::Parse(rule)?);
+    }
+
+    return Ok(ret);
+}
+
+fn HasHostRule(rules: &[EgressRule]) -> bool {
+    return rules.iter().any(|r| match r.target {
+        Target::Host(_) => true,
+        Target::Cidr(_) => false,
+    });
+}
+
+#[derive(Default)]
+pub struct EgressPolicy {
+    // the rules of the operator
+    pub rules: Vec<EgressRule>,
+    // the rules of the sandbox
+    pub sandboxRules: Vec<EgressRule>,
+    // the addresses of the resolvers, empty without host rules
+    pub resolvers: Vec<Vec<u8>>,
+    pub dns: Mutex<DnsCache>,
+
+    pub allowed: AtomicU64,
+    pub denied: AtomicU64,
+}
+
+impl EgressPolicy {
+    pub fn FromSpec(spec: &Spec) -> Result<Option<Self>> {
+        let mut policy = Self::default();
+        policy.rules = ParseRules(&HOST_CONFIG.EgressRules)?;
+        if let Some(rules) = spec.annotations.get(EGRESS_RULES_ANNOTATION) {
+            policy.sandboxRules = ParseRules(rules)?;
+        }
+
+        if policy.rules.len() == 0 && policy.sandboxRules.len() == 0 {
+            return Ok(None);
+        }
+
+        if HOST_CONFIG.EgressDns.len() > 0 {
+            for s in &HOST_CONFIG.EgressDns {
+                match s.parse::<IpAddr>() {
+                    Ok(ip) => policy.resolvers.push(IpBytes(&ip)),
+                    Err(_) => {
+                        return Err(Error::Common(format!("egress: invalid resolver {}", s)))
+                    }
+                }
+            }
+        } else if HasHostRule(&policy.rules) {
+            return Err(Error::Common(format!(
+                "egress: host rules without a resolver, set egress.dns of the config"
+            )));
+        } else if HasHostRule(&policy.sandboxRules) {
+            // the names only narrow the rules of the sandbox
+            policy.resolvers = Resolvers(spec)?;
+            if policy.resolvers.len() == 0 {
+                return Err(Error::Common(format!(
+                    "egress: host rules without a resolver, set {}",
+                    EGRESS_DNS_ANNOTATION
+                )));
+            }
+        }
+
+        info!(
+            "egress: {} rules, {} sandbox rules, {} resolvers",
+            policy.rules.len(),
+            policy.sandboxRules.len(),
+            policy.resolvers.len()
+        );
+        return Ok(Some(policy));
+    }
+
+    pub fn Install(self) {
+        EGRESS_POLICY.Write(Some(Arc::new(self)));
+    }
+
+    // Egress checks the destination address of a connect(2) or sendmsg(2).
+    pub fn Egress(&self, addr: u64, addrlen: u32) -> bool {
+        if addr == 0 {
+            return true;
+        }
+
+        let dst = unsafe { core::slice::from_raw_parts(addr as *const u8, addrlen as usize) };
+        let family = Family(dst);
+        if family != AF_INET && family != AF_INET6 {
+            return true;
+        }
+
+        let (ip, port) = SockAddr(dst, family);
+        if IsLocal(&ip) {
+            return true;
+        }
+
+        let names = if self.resolvers.len() > 0 {
+            self.dns.lock().Names(&ip)
+        } else {
+            Vec::new()
+        };
+
+        let allow = Allowed(&self.rules, &ip, port, &names)
+            && Allowed(&self.sandboxRules, &ip, port, &names);
+
+        if allow {
+            self.allowed.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.denied.fetch_add(1, Ordering::Relaxed);
+            info!(
+                "egress: deny {} port {} {:?}, {} denied",
+                IpString(&ip),
+                port,
+                names,
+                self.denied.load(Ordering::Relaxed)
+            );
+        }
+
+        return allow;
+    }
+
+    // RecvMsg learns the names of the addresses from a DNS answer of the
+    // resolvers, the ret bytes received in the iovecs of the msghdr.
+    pub fn RecvMsg(&self, fd: i32, msghdr: u64, ret: usize) {
+        if self.resolvers.len() == 0 {
+            return;
+        }
+
+        let hdr = unsafe { &*(msghdr as *const msghdr) };
+        let src = if !hdr.msg_name.is_null() && hdr.msg_namelen >= 4 {
+            unsafe {
+                core::slice::from_raw_parts(hdr.msg_name as *const u8, hdr.msg_namelen as usize)
+            }
+            .to_vec()
+        } else {
+            SockName(fd, true)
+        };
+
+        let family = Family(&src);
+        if family != AF_INET && family != AF_INET6 {
+            return;
+        }
+
+        let (ip, port) = SockAddr(&src, family);
+        if port != DNS_PORT || !self.resolvers.contains(&ip) {
+            return;
+        }
+
+        // the answers over tcp are length prefixed and may be split, the
+        // resolvers of the libc only fall back to tcp for the truncated ones
+        if SocketProtocol(fd) != IPPROTO_UDP || hdr.msg_iov.is_null() {
+            return;
+        }
+
+        let iovs = unsafe { core::slice::from_raw_parts(hdr.msg_iov, hdr.msg_iovlen as usize) };
+        let mut msg = Vec::with_capacity(ret);
+        for iov in iovs {
+            let len = core::cmp::min(iov.iov_len, ret - msg.len());
+            msg.extend_from_slice(unsafe {
+                core::slice::from_raw_parts(iov.iov_base as *const u8, len)
+            });
+            if msg.len() == ret {
+                break;
+            }
+        }
+
+        let answers = ParseDnsAnswers(&msg);
+        let mut dns = self.dns.lock();
+        for (name, addr, ttl) in answers {
+            dns.Add(addr, &name, ttl);
+        }
+    }
+}
+
+// Resolvers returns the addresses of the annotation, or the nameservers of
+// the /etc/resolv.conf mounted in the container.
+fn Resolvers(spec: &Spec) -> Result<Vec<Vec<u8>>> {
+    let mut resolvers = Vec::new();
+    if let Some(dns) = spec.annotations.get(EGRESS_DNS_ANNOTATION) {
+        for s in dns.split(';').map(|s| s.trim()).filter(|s| s.len() > 0) {
+            match s.parse::<IpAddr>() {
+                Ok(ip) => resolvers.push(IpBytes(&ip)),
+                Err(_) => return Err(Error::Common(format!("egress: invalid resolver {}", s))),
+            }
+        }
+
+        return Ok(resolvers);
+    }
+
+    for m in &spec.mounts {
+        if Clean(&m.destination) != RESOLV_CONF {
+            continue;
+        }
+
+        let text = fs::read_to_string(&m.source)
+            .map_err(|e| Error::IOError(format!("read {} fail {:?}", m.source, e)))?;
+        for line in text.lines() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() < 2 || words[0] != "nameserver" {
+                continue;
+            }
+
+            if let Ok(ip) = words[1].parse::<IpAddr>() {
+                resolvers.push(IpBytes(&ip));
+            }
+        }
+    }
+
+    return Ok(resolvers);
+}
+
+fn IpBytes(ip: &IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(a) => return a.octets().to_vec(),
+        IpAddr::V6(a) => return a.octets().to_vec(),
+    }
+}
+
+fn IpString(addr: &[u8]) -> String {
+    if addr.len() == 4 {
+        return Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3]).to_string();
+    }
+
+    let mut octets = [0u8; 16];
+    octets.copy_from_slice(&addr[..16]);
+    return Ipv6Addr::from(octets).to_string();
+}
+
+// IsLocal returns whether the address is a loopback or unspecified one.
+fn IsLocal(addr: &[u8]) -> bool {
+    if addr.len() == 4 {
+        return addr[0] == 127 || addr.iter().all(|b| *b == 0);
+    }
+
+    return addr[..15].iter().all(|b| *b == 0) && (addr[15] == 0 || addr[15] == 1);
+}
+
+// ParseDnsAnswers returns the A and AAAA records of a DNS response, with the
+// name of the question and the name of the record which differs from it
+// after a CNAME.
+pub fn ParseDnsAnswers(msg: &[u8]) -> Vec<(String, Vec<u8>, u32)> {
+    let mut answers = Vec::new();
+    if msg.len() < 12 {
+        return answers;
+    }
+
+    // a response without error to one question
+    let qdcount = u16::from_be_bytes([msg[4], msg[5]]);
+    let ancount = u16::from_be_bytes([msg[6], msg[7]]);
+    if msg[2] & 0x80 == 0 || msg[3] & 0x0f != 0 || qdcount != 1 {
+        return answers;
+    }
+
+    let (qname, next) = match DnsName(msg, 12) {
+        None => return answers,
+        Some(n) => n,
+    };
+
+    // the type and class of the question
+    let mut off = next + 4;
+    for _ in 0..ancount {
+        let (owner, next) = match DnsName(msg, off) {
+            None => break,
+            Some(n) => n,
+        };
+
+        if next + 10 > msg.len() {
+            break;
+        }
+
+        let typ = u16::from_be_bytes([msg[next], msg[next + 1]]);
+        let ttl = u32::from_be_bytes([msg[next + 4], msg[next + 5], msg[next + 6], msg[next + 7]]);
+        let rdlen = u16::from_be_bytes([msg[next + 8], msg[next + 9]]) as usize;
+        let rdata = next + 10;
+        if rdata + rdlen > msg.len() {
+            break;
+        }
+
+        if (typ == DNS_TYPE_A && rdlen == 4) || (typ == DNS_TYPE_AAAA && rdlen == 16) {
+            let addr = msg[rdata..rdata + rdlen].to_vec();
+            if owner != qname {
+                answers.push((owner, addr.clone(), ttl));
+            }
+            answers.push((qname.clone(), addr, ttl));
+        }
+
+        off = rdata + rdlen;
+    }
+
+    return answers;
+}
+
+// DnsName reads the name at off of the message, lower cased, it returns the
+// name and the offset after it.
+fn DnsName(msg: &[u8], off: usize) -> Option<(String, usize)> {
+    let mut name = String::new();
+    let mut pos = off;
+    let mut end = None;
+    // the compression pointers followed, bounded against the loops
+    let mut jumps = 0;
+
+    loop {
+        let len = *msg.get(pos)? as usize;
+        if len == 0 {
+            if end.is_none() {
+                end = Some(pos + 1);
+            }
+            break;
+        }
+
+        if len & 0xc0 == 0xc0 {
+            if end.is_none() {
+                end = Some(pos + 2);
+            }
+
+            jumps += 1;
+            if jumps > 16 {
+                return None;
+            }
+
+            pos = ((len & 0x3f) << 8) | *msg.get(pos + 1)? as usize;
+            continue;
+        }
+
+        if len & 0xc0 != 0 {
+            return None;
+        }
+
+        let label = msg.get(pos + 1..pos + 1 + len)?;
+        if name.len() > 0 {
+            name.push('.');
+        }
+        name.push_str(&String::from_utf8_lossy(label).to_lowercase());
+        pos += 1 + len;
+    }
+
+    return Some((name, end?));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a response to the question of the name with the answers
+    fn Response(name: &[u8], ancount: u16, answers: &[u8]) -> Vec<u8> {
+        let mut msg = vec![0x12, 0x34, 0x81, 0x80, 0, 1];
+        msg.extend_from_slice(&ancount.to_be_bytes());
+        msg.extend_from_slice(&[0, 0, 0, 0]);
+        msg.extend_from_slice(name);
+        msg.extend_from_slice(&[0, 1, 0, 1]);
+        msg.extend_from_slice(answers);
+        return msg;
+    }
+
+    // an A record of the owner with the ttl
+    fn ARecord(owner: &[u8], ttl: u32, addr: [u8; 4]) -> Vec<u8> {
+        let mut rr = owner.to_vec();
+        rr.extend_from_slice(&[0, 1, 0, 1]);
+        rr.extend_from_slice(&ttl.to_be_bytes());
+        rr.extend_from_slice(&[0, 4]);
+        rr.extend_from_slice(&addr);
+        return rr;
+    }
+
+    const EXAMPLE: &[u8] = b"\x03www\x07example\x03com\x00";
+
+    #[test]
+    fn TestParseRule() {
+        let rule = EgressRule::Parse("allow 10.0.0.0/8 port 443").unwrap();
+        assert!(rule.allow);
+        assert_eq!(rule.port, 443);
+        assert!(rule.Matches(&[10, 1, 2, 3], 443, &[]));
+        assert!(!rule.Matches(&[10, 1, 2, 3], 80, &[]));
+        assert!(!rule.Matches(&[11, 1, 2, 3], 443, &[]));
+
+        let rule = EgressRule::Parse("deny host WWW.Example.com.").unwrap();
+        assert!(!rule.allow);
+        assert_eq!(rule.target, Target::Host("www.example.com".to_string()));
+        assert_eq!(rule.port, 0);
+
+        assert!(EgressRule::Parse("").is_err());
+        assert!(EgressRule::Parse("accept 10.0.0.0/8").is_err());
+        assert!(EgressRule::Parse("allow").is_err());
+        assert!(EgressRule::Parse("allow host").is_err());
+        assert!(EgressRule::Parse("allow 10.0.0.0/8 port").is_err());
+        assert!(EgressRule::Parse("allow 10.0.0.0/8 port 0").is_err());
+        assert!(EgressRule::Parse("allow 10.0.0.0/8 port 65536").is_err());
+        assert!(EgressRule::Parse("allow 10.0.0.0/8 to 443").is_err());
+    }
+
+    #[test]
+    fn TestNarrow() {
+        let operator = ParseRules("allow 10.0.0.0/8; deny all").unwrap();
+        let sandbox = ParseRules("deny 10.1.0.0/16").unwrap();
+        let allowed = |addr: &[u8]| {
+            Allowed(&operator, addr, 80, &[]) && Allowed(&sandbox, addr, 80, &[])
+        };
+
+        assert!(allowed(&[10, 2, 0, 1]));
+        assert!(!allowed(&[10, 1, 0, 1]));
+        // the sandbox can't allow what the operator denies
+        let sandbox = ParseRules("allow all").unwrap();
+        assert!(Allowed(&sandbox, &[8, 8, 8, 8], 80, &[]));
+        assert!(!Allowed(&operator, &[8, 8, 8, 8], 80, &[]));
+    }
+
+    #[test]
+    fn TestHostMatches() {
+        assert!(HostMatches("example.com", "example.com"));
+        assert!(!HostMatches("example.com", "www.example.com"));
+        assert!(HostMatches("*.example.com", "www.example.com"));
+        assert!(HostMatches("*.example.com", "a.b.example.com"));
+        assert!(!HostMatches("*.example.com", "example.com"));
+        assert!(!HostMatches("*.example.com", "badexample.com"));
+        assert!(!HostMatches("*.example.com", ".example.com"));
+    }
+
+    #[test]
+    fn TestDnsName() {
+        let msg = Response(EXAMPLE, 0, &[]);
+        assert_eq!(
+            DnsName(&msg, 12),
+            Some(("www.example.com".to_string(), 12 + EXAMPLE.len()))
+        );
+
+        // a pointer to the question
+        let mut msg = Response(EXAMPLE, 0, &[]);
+        let off = msg.len();
+        msg.extend_from_slice(&[0xc0, 12]);
+        assert_eq!(
+            DnsName(&msg, off),
+            Some(("www.example.com".to_string(), off + 2))
+        );
+
+        // a label and a pointer to the suffix of the question
+        let off = msg.len();
+        msg.extend_from_slice(b"\x03ftp\xc0\x10");
+        assert_eq!(
+            DnsName(&msg, off),
+            Some(("ftp.example.com".to_string(), off + 6))
+        );
+    }
+
+    #[test]
+    fn TestDnsNameLoop() {
+        // a pointer to itself
+        let mut msg = Response(EXAMPLE, 0, &[]);
+        let off = msg.len();
+        msg.extend_from_slice(&[0xc0, off as u8]);
+        assert_eq!(DnsName(&msg, off), None);
+
+        // two pointers to each other
+        let off = msg.len();
+        msg.extend_from_slice(&[0xc0, off as u8 + 2, 0xc0, off as u8]);
+        assert_eq!(DnsName(&msg, off), None);
+
+        // a label before a pointer back to it
+        let off = msg.len();
+        msg.extend_from_slice(&[1, b'a', 0xc0, off as u8]);
+        assert_eq!(DnsName(&msg, off), None);
+    }
+
+    #[test]
+    fn TestDnsNameTruncated() {
+        let msg = Response(EXAMPLE, 0, &[]);
+        // the label is cut
+        assert_eq!(DnsName(&msg[..14], 12), None);
+        // no terminating label
+        assert_eq!(DnsName(&msg[..12 + EXAMPLE.len() - 1], 12), None);
+        // the pointer is cut
+        let mut msg = msg.clone();
+        let off = msg.len();
+        msg.push(0xc0);
+        assert_eq!(DnsName(&msg, off), None);
+        // a pointer past the end
+        msg.push(0xff);
+        assert_eq!(DnsName(&msg, off), None);
+        // the reserved label types
+        assert_eq!(DnsName(&[0x40, 0], 0), None);
+        assert_eq!(DnsName(&[0x80, 0], 0), None);
+    }
+
+    #[test]
+    fn TestParseDnsAnswers() {
+        let mut answers = ARecord(&[0xc0, 12], 300, [1, 2, 3, 4]);
+        answers.extend_from_slice(&ARecord(&[0xc0, 12], 30, [5, 6, 7, 8]));
+        let msg = Response(EXAMPLE, 2, &answers);
+        assert_eq!(
+            ParseDnsAnswers(&msg),
+            vec![
+                ("www.example.com".to_string(), vec![1, 2, 3, 4], 300),
+                ("www.example.com".to_string(), vec![5, 6, 7, 8], 30),
+            ]
+        );
+
+        // the owner of a record after a CNAME is learnt too
+        let answers = ARecord(b"\x03cdn\xc0\x10", 60, [9, 9, 9, 9]);
+        let msg = Response(EXAMPLE, 1, &answers);
+        assert_eq!(
+            ParseDnsAnswers(&msg),
+            vec![
+                ("cdn.example.com".to_string(), vec![9, 9, 9, 9], 60),
+                ("www.example.com".to_string(), vec![9, 9, 9, 9], 60),
+            ]
+        );
+
+        // not a response, an error, two questions
+        let mut bad = msg.clone();
+        bad[2] &= !0x80;
+        assert!(ParseDnsAnswers(&bad).is_empty());
+        let mut bad = msg.clone();
+        bad[3] |= 3;
+        assert!(ParseDnsAnswers(&bad).is_empty());
+        let mut bad = msg.clone();
+        bad[5] = 2;
+        assert!(ParseDnsAnswers(&bad).is_empty());
+    }
+
+    #[test]
+    fn TestParseDnsAnswersTruncated() {
+        let mut answers = ARecord(&[0xc0, 12], 300, [1, 2, 3, 4]);
+        answers.extend_from_slice(&ARecord(&[0xc0, 12], 300, [5, 6, 7, 8]));
+        let msg = Response(EXAMPLE, 2, &answers);
+        let first = msg.len() - answers.len() / 2;
+
+        // the answers before the cut are kept
+        for len in first..msg.len() {
+            assert_eq!(
+                ParseDnsAnswers(&msg[..len]),
+                vec![("www.example.com".to_string(), vec![1, 2, 3, 4], 300)]
+            );
+        }
+
+        for len in 0..first {
+            assert!(ParseDnsAnswers(&msg[..len]).is_empty());
+        }
+
+        // more answers than in the message
+        let mut msg = msg.clone();
+        msg[7] = 10;
+        assert_eq!(ParseDnsAnswers(&msg).len(), 2);
+
+        // a record length past the end
+        let mut msg = Response(EXAMPLE, 1, &ARecord(&[0xc0, 12], 300, [1, 2, 3, 4]));
+        let len = msg.len();
+        msg[len - 5] = 0xff;
+        assert!(ParseDnsAnswers(&msg).is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::qlib::kernel::LoadVcpuFreq;
+use super::super::qlib::kernel::Tsc;
+
+// The profile of the VM exits of a vcpu. Each exit to qvisor is counted by its
+// reason with the time qvisor takes to handle it, from the return of KVM_RUN
+// to the next KVM_RUN. The counts are kept since the start and since the last
+// dump, the dump of SIGUSR1 prints both. The exits handled in KVM without
+// coming back to qvisor, e.g. the EPT violations of the guest page faults or
+// the MSR accesses, are only seen in the KVM stats of the vcpu, the dump
+// prints them too when the host kernel has KVM_GET_STATS_FD (5.14+).
+
+// the user space MSR exits, with KVM_CAP_X86_USER_SPACE_MSR
+pub const KVM_EXIT_X86_RDMSR: u32 = 29;
+pub const KVM_EXIT_X86_WRMSR: u32 = 30;
+
+const KVM_GET_STATS_FD: u64 = 0xaece;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(usize)]
+pub enum ExitReason {
+    // the hypercalls the guest waits in, i.e. its idle: HLT, HYPERCALL_HLT,
+    // HYPERCALL_IOWAIT and HYPERCALL_VCPU_WAIT
+    Hlt = 0,
+    // the other hypercalls, an IO port write
+    IoOut,
+    IoIn,
+    // the EPT violations of the guest physical addresses out of the memory slots
+    Mmio,
+    Msr,
+    DirtyRingFull,
+    // the vcpu kicked by a signal, e.g. for an interrupt or a dump
+    Interrupt,
+    Other,
+}
+
+pub const EXIT_REASON_COUNT: usize = ExitReason::Other as usize + 1;
+
+pub const EXIT_REASON_NAMES: [&str; EXIT_REASON_COUNT] = [
+    "hlt",
+    "io_out",
+    "io_in",
+    "mmio",
+    "msr",
+    "dirty_ring_full",
+    "interrupt",
+    "other",
+];
+
+#[derive(Default)]
+pub struct ExitCounter {
+    pub count: AtomicU64,
+    // the tsc cycles of the handling
+    pub cycles: AtomicU64,
+    // the longest handling since the last dump
+    pub maxCycles: AtomicU64,
+    // count and cycles at the last dump
+    pub lastCount: AtomicU64,
+    pub lastCycles: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct ExitStats {
+    pub counters: [ExitCounter; EXIT_REASON_COUNT],
+}
+
+// CyclesToNs converts the tsc cycles to ns with the vcpu frequency.
+pub fn CyclesToNs(cycles: u64) -> u64 {
+    let freq = LoadVcpuFreq();
+    if freq <= 0 {
+        return 0;
+    }
+
+    return (cycles as u128 * 1_000_000_000 / freq as u128) as u64;
+}
+
+impl ExitStats {
+    // Start returns the tsc at the exit.
+    #[inline]
+    pub fn Start() -> u64 {
+        return Tsc::RawRdtsc() as u64;
+    }
+
+    // Record adds an exit, the handling of which started at start.
+    #[inline]
+    pub fn Record(&self, reason: ExitReason, start: u64) {
+        let cycles = (Tsc::RawRdtsc() as u64).wrapping_sub(start);
+        let counter = &self.counters[reason as usize];
+        counter.count.fetch_add(1, Ordering::Relaxed);
+        counter.cycles.fetch_add(cycles, Ordering::Relaxed);
+        counter.maxCycles.fetch_max(cycles, Ordering::Relaxed);
+    }
+
+    // Dump returns the exits since the start and, as int_*, the ones of the
+    // interval since the last dump, one line per reason. It starts a new interval.
+    pub fn Dump(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!(
+            "{:<16} {:>12} {:>12} {:>12} {:>10} {:>10} {:>10}\n",
+            "reason", "count", "avg_ns", "int_count", "int_avg_ns", "int_max_ns", "int_ns"
+        ));
+        for i in 0..EXIT_REASON_COUNT {
+            let counter = &self.counters[i];
+            let count = counter.count.load(Ordering::Relaxed);
+            let cycles = counter.cycles.load(Ordering::Relaxed);
+            let maxCycles = counter.maxCycles.swap(0, Ordering::Relaxed);
+            let lastCount = counter.lastCount.swap(count, Ordering::Relaxed);
+            let lastCycles = counter.lastCycles.swap(cycles, Ordering::Relaxed);
+            if count == 0 {
+                continue;
+            }
+
+            let intervalCount = count - lastCount;
+            let intervalCycles = cycles - lastCycles;
+            let intervalAvg = if intervalCount == 0 {
+                0
+            } else {
+                CyclesToNs(intervalCycles) / intervalCount
+            };
+            out.push_str(&format!(
+                "{:<16} {:>12} {:>12} {:>12} {:>10} {:>10} {:>10}\n",
+                EXIT_REASON_NAMES[i],
+                count,
+                CyclesToNs(cycles) / count,
+                intervalCount,
+                intervalAvg,
+                CyclesToNs(maxCycles),
+                CyclesToNs(intervalCycles),
+            ));
+        }
+
+        return out;
+    }
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct KvmStatsHeader {
+    flags: u32,
+    nameSize: u32,
+    numDesc: u32,
+    idOffset: u32,
+    descOffset: u32,
+    dataOffset: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct KvmStatsDesc {
+    flags: u32,
+    exponent: i16,
+    size: u16,
+    offset: u32,
+    bucketSize: u32,
+    // followed by the name of nameSize bytes
+}
+
+// the KVM stats of a vcpu which count the exits handled in the kernel too
+const KVM_EXIT_STATS: &[&str] = &[
+    "exits",
+    "io_exits",
+    "mmio_exits",
+    "halt_exits",
+    "irq_exits",
+    "irq_window_exits",
+    "signal_exits",
+    "request_irq_exits",
+    "pf_taken",
+    "pf_fixed",
+    "halt_successful_poll",
+    "halt_wakeup",
+];
+
+fn PRead(fd: i32, buf: &mut [u8], offset: u64) -> bool {
+    let ret = unsafe {
+        libc::pread(
+            fd,
+            buf.as_mut_ptr() as *mut libc::c_void,
+            buf.len(),
+            offset as libc::off_t,
+        )
+    };
+    return ret == buf.len() as isize;
+}
+
+// KvmExitStats returns the exit counters of the KVM binary stats of the vcpu,
+// none if the host kernel doesn't have them.
+pub fn KvmExitStats(vcpuFd: i32) -> Option<Vec<(String, u64)>> {
+    let fd = unsafe { libc::ioctl(vcpuFd, KVM_GET_STATS_FD) };
+    if fd < 0 {
+        return None;
+    }
+    defer!(unsafe {
+        libc::close(fd);
+    });
+
+    let mut header = KvmStatsHeader::default();
+    let buf = unsafe {
+        core::slice::from_raw_parts_mut(
+            &mut header as *mut _ as *mut u8,
+            size_of::<KvmStatsHeader>(),
+        )
+    };
+    if !PRead(fd, buf, 0) {
+        return None;
+    }
+
+    let descSize = size_of::<KvmStatsDesc>() + header.nameSize as usize;
+    let mut descs = vec![0u8; descSize * header.numDesc as usize];
+    if !PRead(fd, &mut descs, header.descOffset as u64) {
+        return None;
+    }
+
+    let mut stats = Vec::new();
+    for i in 0..header.numDesc as usize {
+        let desc = unsafe {
+            core::ptr::read_unaligned(&descs[i * descSize] as *const _ as *const KvmStatsDesc)
+        };
+        let name = &descs[i * descSize + size_of::<KvmStatsDesc>()..(i + 1) * descSize];
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        let name = String::from_utf8_lossy(&name[..len]).to_string();
+        if !KVM_EXIT_STATS.contains(&name.as_str()) || desc.size != 1 {
+            continue;
+        }
+
+        let mut val = [0u8; 8];
+        if !PRead(fd, &mut val, (header.dataOffset + desc.offset) as u64) {
+            return None;
+        }
+
+        stats.push((name, u64::from_ne_bytes(val)));
+    }
+
+    return Some(stats);
+}
@@ -19,6 +19,7 @@ use alloc::collections::VecDeque;
 
 use crate::vmspace::kernel::GlobalIOMgr;
 use super::super::qlib::common::*;
+use super::super::qlib::linux::time::SECOND;
 use super::super::qlib::linux_def::*;
 use super::super::qlib::mutex::QMutex;
 use super::super::qlib::uring::porting::*;
@@ -130,6 +131,48 @@ impl IoUring {
         })
     }
 
+    // WaitCompletion waits in the host kernel until minComplete completions
+    // are in the completion queue or the timeout (ns) expires. It returns at
+    // once if the host kernel has no timeout for io_uring_enter.
+    pub fn WaitCompletion(&self, minComplete: u32, timeout: i64) -> Result<()> {
+        if !self.params.is_feature_ext_arg() {
+            return Ok(());
+        }
+
+        let ts = sys::__kernel_timespec {
+            tv_sec: timeout / SECOND,
+            tv_nsec: timeout % SECOND,
+        };
+
+        let arg = sys::io_uring_getevents_arg {
+            sigmask: 0,
+            sigmask_sz: 0,
+            pad: 0,
+            ts: &ts as *const _ as u64,
+        };
+
+        let ret = IOUringEnterWithArg(
+            self.fd.as_raw_fd(),
+            0,
+            minComplete,
+            sys::IORING_ENTER_GETEVENTS | sys::IORING_ENTER_EXT_ARG,
+            &arg as *const _ as u64,
+            core::mem::size_of::<sys::io_uring_getevents_arg>(),
+        );
+
+        if ret < 0 {
+            let errno = -ret as i32;
+            // the timeout expires or a signal comes before the completions
+            if errno == SysErr::ETIME || errno == SysErr::EINTR {
+                return Ok(());
+            }
+
+            return Err(Error::SysError(errno));
+        }
+
+        return Ok(());
+    }
+
     /// Initiate asynchronous I/O.
     #[inline]
     pub fn submit(&self) -> Result<usize> {
@@ -336,3 +379,26 @@ pub fn IOUringEnter(fd: i32, toSubmit: u32, minComplete: u32, flags: u32) -> i64
 
     return res;
 }
+
+pub fn IOUringEnterWithArg(
+    fd: i32,
+    toSubmit: u32,
+    minComplete: u32,
+    flags: u32,
+    arg: u64,
+    argSize: usize,
+) -> i64 {
+    let res = unsafe {
+        syscall6(
+            NR_IO_URING_ENTER,
+            fd as usize,
+            toSubmit as usize,
+            minComplete as usize,
+            flags as usize,
+            arg as usize,
+            argSize,
+        ) as i64
+    };
+
+    return res;
+}
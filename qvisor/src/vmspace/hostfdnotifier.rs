@@ -19,7 +19,7 @@ use crate::qlib::kernel::GlobalIOMgr;
 use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 //use super::super::qlib::qmsg::input::*;
-//use super::super::SHARE_SPACE;
+use super::super::SHARE_SPACE;
 
 #[repr(C)]
 #[repr(packed)]
@@ -92,10 +92,13 @@ impl HostFdNotifier {
         let nfds = unsafe { epoll_wait(epollfd, addr as _, Self::MAX_EVENTS as i32, 0) };
 
         if nfds > 0 {
+            let steering = &SHARE_SPACE.recvSteering;
             for e in &events[0..nfds as usize] {
                 let fd = e.U64 as i32;
                 let event = e.Event as EventMask;
-                Self::FdNotify(fd, event);
+                if !steering.Steer(&SHARE_SPACE.scheduler, fd, event) {
+                    Self::FdNotify(fd, event);
+                }
             }
         }
 
@@ -0,0 +1,202 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::mem::size_of;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::qlib::fileinfo::*;
+use crate::vmspace::kernel::GlobalIOMgr;
+
+// The keepalive offload of the paused sandbox. The guest TCP connections are
+// host sockets, the host stack keeps answering the keepalive probes and the
+// data of their peers while the sandbox is paused or snapshotted, but the
+// connection goes silent from its side: a NAT, a load balancer or a peer with
+// an idle timeout drops it before the resume. At the pause, qvisor hands the
+// keepalives of the idle connections the guest has designated, the
+// established ones with SO_KEEPALIVE, to the host stack with the interval of
+// the config, and it puts their own keepalive options back at the resume. The
+// heartbeats of an application protocol can't be answered without the guest.
+
+// the offsets of tcpi_state and tcpi_unacked in tcp_info
+const TCPI_STATE_OFFSET: usize = 0;
+const TCPI_UNACKED_OFFSET: usize = 24;
+const TCP_INFO_SIZE: usize = 104;
+const TCP_ESTABLISHED: u8 = 1;
+
+#[derive(Debug, Clone, Copy)]
+pub struct KeepaliveOpts {
+    pub idle: i32,
+    pub intvl: i32,
+    pub cnt: i32,
+}
+
+#[derive(Default)]
+pub struct KeepaliveOffload {
+    // the offloaded host fds with their keepalive options before the pause
+    pub saved: Vec<(i32, KeepaliveOpts)>,
+}
+
+lazy_static! {
+    pub static ref KEEPALIVE_OFFLOAD: Mutex<KeepaliveOffload> =
+        Mutex::new(KeepaliveOffload::default());
+}
+
+impl KeepaliveOffload {
+    // Offload sets the keepalive interval of the designated connections to
+    // idle seconds, it returns the count offloaded.
+    pub fn Offload(&mut self, idle: i32) -> usize {
+        // a pause without a resume, e.g. a failed one retried
+        self.Restore();
+
+        let fds: Vec<i32> = {
+            let fdTbl = GlobalIOMgr().fdTbl.lock();
+            fdTbl
+                .map
+                .iter()
+                .filter(|(_, fdInfo)| match fdInfo.SockInfo() {
+                    SockInfo::Socket => true,
+                    _ => false,
+                })
+                .map(|(fd, _)| *fd)
+                .collect()
+        };
+
+        for fd in fds {
+            if !Designated(fd) {
+                continue;
+            }
+
+            let opts = match GetOpts(fd) {
+                None => continue,
+                Some(opts) => opts,
+            };
+
+            let offload = KeepaliveOpts {
+                idle: idle,
+                intvl: idle,
+                cnt: opts.cnt,
+            };
+
+            if SetOpts(fd, &offload) {
+                self.saved.push((fd, opts));
+            } else {
+                SetOpts(fd, &opts);
+            }
+        }
+
+        info!("keepalive offload: {} connections", self.saved.len());
+        return self.saved.len();
+    }
+
+    // Restore puts the keepalive options of the offloaded connections back.
+    pub fn Restore(&mut self) {
+        for (fd, opts) in self.saved.drain(..) {
+            // the guest can't close the fd while it is paused, it is checked
+            // against a close of the teardown
+            if GlobalIOMgr().GetByHost(fd).is_none() {
+                continue;
+            }
+
+            SetOpts(fd, &opts);
+        }
+    }
+}
+
+// Designated returns whether the socket is an idle established TCP connection
+// the guest has turned the keepalive of on.
+fn Designated(fd: i32) -> bool {
+    let mut keepalive: i32 = 0;
+    if !GetOpt(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE, &mut keepalive) || keepalive == 0 {
+        return false;
+    }
+
+    let mut info = [0u8; TCP_INFO_SIZE];
+    let mut len = TCP_INFO_SIZE as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info[0] as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if ret < 0 || (len as usize) < TCPI_UNACKED_OFFSET + 4 {
+        return false;
+    }
+
+    if info[TCPI_STATE_OFFSET] != TCP_ESTABLISHED {
+        return false;
+    }
+
+    // a connection with data in flight isn't idle, its retransmission keeps
+    // it alive or resets it anyway
+    let mut unacked = [0u8; 4];
+    unacked.copy_from_slice(&info[TCPI_UNACKED_OFFSET..TCPI_UNACKED_OFFSET + 4]);
+    return u32::from_ne_bytes(unacked) == 0;
+}
+
+fn GetOpts(fd: i32) -> Option<KeepaliveOpts> {
+    let mut opts = KeepaliveOpts {
+        idle: 0,
+        intvl: 0,
+        cnt: 0,
+    };
+
+    if !GetOpt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, &mut opts.idle)
+        || !GetOpt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, &mut opts.intvl)
+        || !GetOpt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPCNT, &mut opts.cnt)
+    {
+        return None;
+    }
+
+    return Some(opts);
+}
+
+fn SetOpts(fd: i32, opts: &KeepaliveOpts) -> bool {
+    return SetOpt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, opts.idle)
+        && SetOpt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, opts.intvl)
+        && SetOpt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPCNT, opts.cnt);
+}
+
+fn GetOpt(fd: i32, level: i32, name: i32, val: &mut i32) -> bool {
+    let mut len = size_of::<i32>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            level,
+            name,
+            val as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    return ret == 0;
+}
+
+fn SetOpt(fd: i32, level: i32, name: i32, val: i32) -> bool {
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &val as *const _ as *const libc::c_void,
+            size_of::<i32>() as libc::socklen_t,
+        )
+    };
+
+    return ret == 0;
+}
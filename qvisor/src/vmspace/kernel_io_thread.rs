@@ -56,6 +56,8 @@ impl KIOThread {
         count += KVMVcpu::GuestMsgProcess(sharespace);
         count += IOURING.IOUring().HostSubmit().unwrap();
         count += FD_NOTIFIER.HostEpollWait() as usize;
+        count += sharespace.recvSteering.ProcessBusy(&sharespace.scheduler);
+        count += sharespace.timerDispatch.ProcessBusy(&sharespace.scheduler);
         count += IOURING.IOUring().HostSubmit().unwrap();
 
         sharespace.CheckVcpuTimeout();
@@ -65,11 +67,20 @@ impl KIOThread {
 
     pub fn Process(sharespace: &ShareSpace) {
         let mut start = TSC.Rdtsc();
+        let waitNs = QUARK_CONFIG.lock().UringWaitUs as i64 * 1000;
 
         while !sharespace.Shutdown() {
             let count = Self::ProcessOnce(sharespace);
             if count > 0 {
                 start = TSC.Rdtsc()
+            } else if waitNs > 0 {
+                // nothing to do, sleep in the host kernel till a completion
+                // comes instead of spinning, the guest messages wait at most
+                // waitNs
+                IOURING
+                    .IOUring()
+                    .WaitCompletion(1, waitNs)
+                    .expect("KIOThread::Process WaitCompletion fail");
             }
 
             if TSC.Rdtsc() - start >= IO_WAIT_CYCLES {
@@ -13,14 +13,23 @@
 // limitations under the License.
 
 pub mod HostFileMap;
+pub mod dirty_ring;
+pub mod egress_policy;
+pub mod exit_stats;
 //pub mod TimerMgr;
 pub mod host_pma_keeper;
 pub mod host_uring;
 pub mod hostfdnotifier;
+pub mod keepalive_offload;
 pub mod kernel_io_thread;
 pub mod limits;
+pub mod netdev;
+pub mod packet_filter;
+pub mod policy_route;
+pub mod qcall_pool;
 pub mod random;
 pub mod syscall;
+pub mod teardown;
 pub mod time;
 pub mod uringMgr;
 
@@ -50,8 +59,15 @@ use super::qlib::qmsg::*;
 use super::qlib::task_mgr::*;
 use super::qlib::linux::membarrier::*;
 use super::qlib::*;
+use super::runc::cgroup::{IoMaxFromSpec, IoMaxPartitions};
 use super::runc::container::mounts::*;
+use super::runc::runtime::cdi::*;
+use super::runc::runtime::etc_files::*;
+use super::runc::runtime::listen_fds::*;
 use super::runc::runtime::loader::*;
+use super::runc::runtime::mitigations::*;
+use super::runc::runtime::prefetch::*;
+use super::runc::runtime::shm::*;
 use super::runc::specutils::specutils::*;
 //use super::qlib::socket_buf::*;
 use self::limits::*;
@@ -219,6 +235,53 @@ impl VMSpace {
         process.Caps = Capabilities(false, &spec.process.capabilities);
 
         process.HostName = spec.hostname.to_string();
+        match UnameFromSpec(spec) {
+            Ok((release, version)) => {
+                process.KernelRelease = release;
+                process.KernelVersion = version;
+            }
+            Err(e) => {
+                error!("LoadProcessKernel: {:?}", e);
+                return -SysErr::EINVAL as i64;
+            }
+        }
+        match TimeOffsetsFromSpec(spec) {
+            Ok((monotonic, boottime)) => {
+                process.MonotonicOffset = monotonic;
+                process.BoottimeOffset = boottime;
+            }
+            Err(e) => {
+                error!("LoadProcessKernel: {:?}", e);
+                return -SysErr::EINVAL as i64;
+            }
+        }
+        match Mitigations::FromSpec(spec, false) {
+            Ok(m) => process.Mitigations = m.map_or(0, |m| m.guest),
+            Err(e) => {
+                error!("LoadProcessKernel: {:?}", e);
+                return -SysErr::EINVAL as i64;
+            }
+        }
+        process.EtcFiles = match EtcFilesFromSpec(spec) {
+            Ok(files) => files,
+            Err(e) => {
+                error!("LoadProcessKernel: virtual /etc files fail {:?}", e);
+                return -SysErr::EINVAL as i64;
+            }
+        };
+
+        process.Devices = GuestDevices(spec);
+        process.IoMax = IoMaxFromSpec(spec);
+        process.IoMaxPartitions = IoMaxPartitions(&process.IoMax);
+        process.Prefetch = PrefetchFromSpec(spec);
+        process.ShmVolume = ShmVolumeFromSpec(spec);
+        process.ShmSize = match ShmSizeFromSpec(spec) {
+            Ok(size) => size,
+            Err(e) => {
+                error!("LoadProcessKernel: {:?}", e);
+                return -SysErr::EINVAL as i64;
+            }
+        };
 
         process.NumCpu = self.vcpuCount as u32;
         process.ExecId = Some("".to_string());
@@ -236,6 +299,27 @@ impl VMSpace {
 
             process.Stdiofds[i] = hostfd;
         }
+
+        let (listenFds, fdNames) = match TakeListenFds() {
+            Ok(ret) => ret,
+            Err(Error::SysError(e)) => return -e as i64,
+            Err(_) => return -SysErr::EINVAL as i64,
+        };
+
+        if listenFds.len() > 0 {
+            // LISTEN_PID is set by the guest when the pid of the process is known
+            process.Envs.retain(|e| !e.starts_with("LISTEN_"));
+            process.Envs.push(format!("{}={}", LISTEN_FDS_ENV, listenFds.len()));
+            if let Some(names) = fdNames {
+                process.Envs.push(format!("{}={}", LISTEN_FDNAMES_ENV, names));
+            }
+        }
+
+        for osfd in listenFds {
+            URING_MGR.lock().Addfd(osfd).unwrap();
+            let hostfd = GlobalIOMgr().AddSocket(osfd);
+            process.ListenFds.push(hostfd);
+        }
         process.Root = format!("/{}", &process.ID);
         //process.Root = "/".to_string();
 
@@ -243,8 +327,13 @@ impl VMSpace {
 
         if self.pivot {
             self.PivotRoot(&rootfs);
+        } else {
+            *CONTAINERS_DIR.lock() = rootfs.clone();
         }
 
+        // the guest boots meanwhile
+        Prefetcher::Start(&process);
+
         //error!("LoadProcessKernel proces is {:?}", &process);
 
         let vec: Vec<u8> = serde_json::to_vec(&process).expect("LoadProcessKernel ser fail...");
@@ -564,6 +653,17 @@ impl VMSpace {
             None => return -SysErr::EBADF as i64,
         };
 
+        // read(2) of a udp socket doesn't return the source, recvmsg it for
+        // the filter
+        if let Some(filter) = packet_filter::GetPacketFilter() {
+            if packet_filter::SocketProtocol(fd) == IPPROTO_UDP {
+                let mut hdr: msghdr = unsafe { core::mem::zeroed() };
+                hdr.msg_iov = iovs as *mut iovec;
+                hdr.msg_iovlen = iovcnt as usize;
+                return packet_filter::RecvMsgFiltered(&filter, fd, &mut hdr as *mut _ as u64, 0);
+            }
+        }
+
         let ret = unsafe { readv(fd as c_int, iovs as *const iovec, iovcnt) as i64 };
 
         return Self::GetRet(ret as i64);
@@ -660,6 +760,17 @@ impl VMSpace {
     }
 
     pub fn NewSocket(fd: i32) -> i64 {
+        // the connection is accepted by io_uring, drop it before the guest
+        // queues it
+        if let Some(filter) = packet_filter::GetPacketFilter() {
+            if !filter.IngressConnection(fd) {
+                unsafe {
+                    close(fd);
+                }
+                return -SysErr::ECONNABORTED as i64;
+            }
+        }
+
         GlobalIOMgr().AddSocket(fd);
         URING_MGR.lock().Addfd(fd).unwrap();
         return 0;
@@ -1140,7 +1251,8 @@ impl VMSpace {
     pub fn ReadControlMsg(fd: i32, addr: u64, len: usize) -> i64 {
         match super::ucall::ucall_server::ReadControlMsg(fd) {
             Err(_e) => return -1,
-            Ok(msg) => {
+            Ok(None) => return -1,
+            Ok(Some(msg)) => {
                 let vec: Vec<u8> = serde_json::to_vec(&msg).expect("SendControlMsg ser fail...");
                 let buff = {
                     let ptr = addr as *mut u8;
@@ -0,0 +1,221 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::mem::size_of;
+use std::slice;
+
+use super::super::qlib::common::*;
+use super::super::qlib::linux::netdevice::*;
+
+// The interfaces of the sandbox network namespace for the /sys/class/net of
+// the guest. The guest sockets are host sockets, the interfaces and their
+// counters are the ones of the host stack in the namespace, qvisor gets them
+// with a rtnetlink link dump, which doesn't need the sysfs of the host.
+
+const RTM_NEWLINK: u16 = 16;
+const RTM_GETLINK: u16 = 18;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_DUMP: u16 = 0x300;
+
+const IFLA_ADDRESS: u16 = 1;
+const IFLA_IFNAME: u16 = 3;
+const IFLA_MTU: u16 = 4;
+const IFLA_TXQLEN: u16 = 13;
+const IFLA_OPERSTATE: u16 = 16;
+const IFLA_STATS64: u16 = 23;
+const IFLA_CARRIER: u16 = 33;
+
+const NLMSG_HDR_LEN: usize = 16;
+const IFINFOMSG_LEN: usize = 16;
+const RTA_HDR_LEN: usize = 4;
+
+#[repr(C)]
+struct LinkReq {
+    // struct nlmsghdr
+    len: u32,
+    typ: u16,
+    flags: u16,
+    seq: u32,
+    pid: u32,
+    // struct ifinfomsg
+    family: u8,
+    pad: u8,
+    ifiType: u16,
+    index: i32,
+    ifiFlags: u32,
+    change: u32,
+}
+
+fn Align4(len: usize) -> usize {
+    return (len + 3) & !3;
+}
+
+fn U16(buf: &[u8], off: usize) -> u16 {
+    return u16::from_ne_bytes([buf[off], buf[off + 1]]);
+}
+
+fn U32(buf: &[u8], off: usize) -> u32 {
+    let mut v = [0u8; 4];
+    v.copy_from_slice(&buf[off..off + 4]);
+    return u32::from_ne_bytes(v);
+}
+
+// NetDevs fills the NetDevInfo array of the guest at addr, it returns the
+// count of the interfaces or the negative errno.
+pub fn NetDevs(addr: u64, count: usize) -> i64 {
+    let devs = unsafe { slice::from_raw_parts_mut(addr as *mut NetDevInfo, count) };
+    match LinkDump(devs) {
+        Ok(n) => return n as i64,
+        Err(Error::SysError(e)) => return -e as i64,
+        Err(e) => {
+            info!("netdev: link dump fail {:?}", e);
+            return -libc::EIO as i64;
+        }
+    }
+}
+
+fn LinkDump(devs: &mut [NetDevInfo]) -> Result<usize> {
+    let sock = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if sock < 0 {
+        return Err(Error::SysError(errno::errno().0));
+    }
+
+    let ret = LinkDumpOn(sock, devs);
+    unsafe {
+        libc::close(sock);
+    }
+
+    return ret;
+}
+
+fn LinkDumpOn(sock: i32, devs: &mut [NetDevInfo]) -> Result<usize> {
+    let req = LinkReq {
+        len: size_of::<LinkReq>() as u32,
+        typ: RTM_GETLINK,
+        flags: NLM_F_REQUEST | NLM_F_DUMP,
+        seq: 1,
+        pid: 0,
+        family: libc::AF_UNSPEC as u8,
+        pad: 0,
+        ifiType: 0,
+        index: 0,
+        ifiFlags: 0,
+        change: 0,
+    };
+
+    let ret = unsafe {
+        libc::send(
+            sock,
+            &req as *const _ as *const libc::c_void,
+            size_of::<LinkReq>(),
+            0,
+        )
+    };
+    if ret < 0 {
+        return Err(Error::SysError(errno::errno().0));
+    }
+
+    let mut count = 0;
+    let mut buf = vec![0u8; 32 * 1024];
+    loop {
+        let len = unsafe { libc::recv(sock, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+        if len < 0 {
+            return Err(Error::SysError(errno::errno().0));
+        }
+
+        let len = len as usize;
+        let mut off = 0;
+        while off + NLMSG_HDR_LEN <= len {
+            let msgLen = U32(&buf, off) as usize;
+            let typ = U16(&buf, off + 4);
+            if msgLen < NLMSG_HDR_LEN || off + msgLen > len {
+                return Err(Error::Common(format!(
+                    "netdev: bad netlink message len {}",
+                    msgLen
+                )));
+            }
+
+            match typ {
+                NLMSG_DONE => return Ok(count),
+                NLMSG_ERROR => {
+                    let errno = U32(&buf, off + NLMSG_HDR_LEN) as i32;
+                    return Err(Error::SysError(-errno));
+                }
+                RTM_NEWLINK => {
+                    if count < devs.len() {
+                        devs[count] = ParseLink(&buf[off + NLMSG_HDR_LEN..off + msgLen]);
+                        count += 1;
+                    }
+                }
+                _ => (),
+            }
+
+            off += Align4(msgLen);
+        }
+    }
+}
+
+fn ParseLink(msg: &[u8]) -> NetDevInfo {
+    let mut dev = NetDevInfo::default();
+    if msg.len() < IFINFOMSG_LEN {
+        return dev;
+    }
+
+    dev.Type = U16(msg, 2);
+    dev.Index = U32(msg, 4) as i32;
+    dev.Flags = U32(msg, 8);
+
+    let mut off = IFINFOMSG_LEN;
+    while off + RTA_HDR_LEN <= msg.len() {
+        let rtaLen = U16(msg, off) as usize;
+        let typ = U16(msg, off + 2);
+        if rtaLen < RTA_HDR_LEN || off + rtaLen > msg.len() {
+            break;
+        }
+
+        let data = &msg[off + RTA_HDR_LEN..off + rtaLen];
+        match typ {
+            IFLA_IFNAME => {
+                let len = data.iter().position(|&c| c == 0).unwrap_or(data.len());
+                let len = core::cmp::min(len, IFNAMSIZ - 1);
+                dev.Name[..len].copy_from_slice(&data[..len]);
+            }
+            IFLA_ADDRESS => {
+                let len = core::cmp::min(data.len(), MAX_ADDR_LEN);
+                dev.Addr[..len].copy_from_slice(&data[..len]);
+                dev.AddrLen = len as u32;
+            }
+            IFLA_MTU if data.len() >= 4 => dev.Mtu = U32(data, 0),
+            IFLA_TXQLEN if data.len() >= 4 => dev.TxQueueLen = U32(data, 0),
+            IFLA_OPERSTATE if data.len() >= 1 => dev.OperState = data[0],
+            IFLA_CARRIER if data.len() >= 1 => dev.Carrier = data[0],
+            IFLA_STATS64 if data.len() >= size_of::<NetDevStats>() => {
+                dev.Stats = unsafe { (data.as_ptr() as *const NetDevStats).read_unaligned() };
+            }
+            _ => (),
+        }
+
+        off += Align4(rtaLen);
+    }
+
+    return dev;
+}
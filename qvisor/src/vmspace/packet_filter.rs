@@ -0,0 +1,335 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use lazy_static::lazy_static;
+use libc::*;
+use std::fs;
+
+use super::super::qlib::bpf;
+use super::super::qlib::bpf::BPFInstruction;
+use super::super::qlib::common::*;
+use super::super::qlib::rcu::*;
+use super::super::HOST_CONFIG;
+
+// The sandbox edge filter. A classic BPF program, in the "tcpdump -ddd" format
+// and compiled for raw IP (tcpdump -y RAW), is run by qvisor over a synthetic
+// IP/TCP/UDP header built from the addresses of the host socket:
+//   egress: connect(2) and sendmsg(2) with a destination address
+//   ingress: the connections accepted by the qcall and by io_uring, and the
+//   datagrams received by udp sockets with recvmsg(2) and read(2)
+// The data of a connected tcp socket, which goes over the io_uring socket
+// buffers, is not checked, its addresses are checked at connect or accept.
+// A return value of 0 drops the connection or the datagram. The operator
+// enables it for all the sandboxes with the file of the program in the config
+// file ([filter] file).
+
+lazy_static! {
+    pub static ref PACKET_FILTER: QRcu<Option<Arc<PacketFilter>>> = QRcu::New(None);
+}
+
+pub fn GetPacketFilter() -> Option<Arc<PacketFilter>> {
+    return PACKET_FILTER.Read().as_ref().clone();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Ingress,
+    Egress,
+}
+
+#[derive(Default)]
+pub struct PacketFilter {
+    pub prog: Vec<BPFInstruction>,
+
+    pub allowed: AtomicU64,
+    pub dropped: AtomicU64,
+}
+
+impl PacketFilter {
+    pub fn Load() -> Result<Option<Self>> {
+        let file = &HOST_CONFIG.FilterFile;
+        if file.len() == 0 {
+            return Ok(None);
+        }
+
+        let text = fs::read_to_string(file)
+            .map_err(|e| Error::IOError(format!("read filter {} fail {:?}", file, e)))?;
+        let prog = bpf::Parse(&text)?;
+        info!(
+            "packet filter: load {} instructions from {}",
+            prog.len(),
+            file
+        );
+
+        return Ok(Some(Self {
+            prog: prog,
+            ..Default::default()
+        }));
+    }
+
+    pub fn Install(self) {
+        PACKET_FILTER.Write(Some(Arc::new(self)));
+    }
+
+    // Check runs the program over the packet going from src to dst of the socket.
+    pub fn Check(&self, fd: i32, dir: Direction, src: &[u8], dst: &[u8]) -> bool {
+        let proto = SocketProtocol(fd);
+        let pkt = match SyntheticPacket(proto, src, dst) {
+            // not an ip socket
+            None => return true,
+            Some(pkt) => pkt,
+        };
+
+        let allow = bpf::Exec(&self.prog, &pkt) != 0;
+        if allow {
+            self.allowed.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            info!(
+                "packet filter: drop {:?} {} packet, {} dropped",
+                dir,
+                proto,
+                self.dropped.load(Ordering::Relaxed)
+            );
+        }
+
+        return allow;
+    }
+
+    // Egress checks the packet from the local address of fd to the address.
+    pub fn Egress(&self, fd: i32, addr: u64, addrlen: u32) -> bool {
+        if addr == 0 {
+            return true;
+        }
+
+        let dst = unsafe { core::slice::from_raw_parts(addr as *const u8, addrlen as usize) };
+        let src = SockName(fd, false);
+        return self.Check(fd, Direction::Egress, &src, dst);
+    }
+
+    // Ingress checks the packet from the address to the local address of fd.
+    pub fn Ingress(&self, fd: i32, addr: &[u8]) -> bool {
+        // a stream socket doesn't return the source address of the data
+        if addr.len() == 0 {
+            return true;
+        }
+
+        let dst = SockName(fd, false);
+        return self.Check(fd, Direction::Ingress, addr, &dst);
+    }
+
+    // IngressConnection checks an accepted connection with its peer address.
+    pub fn IngressConnection(&self, fd: i32) -> bool {
+        let src = SockName(fd, true);
+        return self.Ingress(fd, &src);
+    }
+}
+
+pub fn SocketProtocol(fd: i32) -> i32 {
+    let mut proto: i32 = 0;
+    let mut len = core::mem::size_of::<i32>() as socklen_t;
+    let ret = unsafe {
+        getsockopt(
+            fd,
+            SOL_SOCKET,
+            SO_PROTOCOL,
+            &mut proto as *mut _ as *mut c_void,
+            &mut len,
+        )
+    };
+
+    if ret < 0 {
+        return 0;
+    }
+
+    return proto;
+}
+
+pub fn SockName(fd: i32, peer: bool) -> Vec<u8> {
+    let mut addr: sockaddr_storage = unsafe { core::mem::zeroed() };
+    let mut len = core::mem::size_of::<sockaddr_storage>() as socklen_t;
+    let ret = unsafe {
+        if peer {
+            getpeername(fd, &mut addr as *mut _ as *mut sockaddr, &mut len)
+        } else {
+            getsockname(fd, &mut addr as *mut _ as *mut sockaddr, &mut len)
+        }
+    };
+
+    if ret < 0 {
+        return Vec::new();
+    }
+
+    let bytes = unsafe {
+        core::slice::from_raw_parts(&addr as *const _ as *const u8, len as usize)
+    };
+    return bytes.to_vec();
+}
+
+// SockAddr returns the address and the port of a sockaddr_in or sockaddr_in6.
+// An unknown address is the unspecified address.
+pub fn SockAddr(addr: &[u8], family: i32) -> (Vec<u8>, u16) {
+    let fam = if addr.len() >= 2 {
+        u16::from_ne_bytes([addr[0], addr[1]]) as i32
+    } else {
+        family
+    };
+
+    let port = if addr.len() >= 4 {
+        u16::from_be_bytes([addr[2], addr[3]])
+    } else {
+        0
+    };
+
+    if fam == AF_INET && addr.len() >= 8 {
+        return (addr[4..8].to_vec(), port);
+    }
+
+    if fam == AF_INET6 && addr.len() >= 24 {
+        // an IPv4-mapped address is filtered as IPv4
+        if family == AF_INET {
+            return (addr[20..24].to_vec(), port);
+        }
+        return (addr[8..24].to_vec(), port);
+    }
+
+    let len = if family == AF_INET { 4 } else { 16 };
+    return (vec![0; len], port);
+}
+
+pub fn Family(addr: &[u8]) -> i32 {
+    if addr.len() < 2 {
+        return AF_UNSPEC;
+    }
+
+    let fam = u16::from_ne_bytes([addr[0], addr[1]]) as i32;
+    if fam == AF_INET6 && addr.len() >= 24 {
+        let mapped = addr[8..18].iter().all(|b| *b == 0) && addr[18] == 0xff && addr[19] == 0xff;
+        if mapped {
+            return AF_INET;
+        }
+    }
+
+    return fam;
+}
+
+// SyntheticPacket builds the IP header and the first bytes of the TCP/UDP header
+// of a packet going from src to dst. A TCP packet is the SYN of the connection.
+fn SyntheticPacket(proto: i32, src: &[u8], dst: &[u8]) -> Option<Vec<u8>> {
+    let family = Family(dst);
+    if family != AF_INET && family != AF_INET6 {
+        return None;
+    }
+
+    let (srcAddr, srcPort) = SockAddr(src, family);
+    let (dstAddr, dstPort) = SockAddr(dst, family);
+
+    let l4Len = match proto {
+        IPPROTO_TCP => 20,
+        IPPROTO_UDP => 8,
+        _ => 0,
+    };
+
+    let mut pkt = Vec::new();
+    if family == AF_INET {
+        let total = (20 + l4Len) as u16;
+        pkt.push(0x45);
+        pkt.push(0);
+        pkt.extend_from_slice(&total.to_be_bytes());
+        pkt.extend_from_slice(&[0, 0, 0, 0]);
+        pkt.push(64);
+        pkt.push(proto as u8);
+        pkt.extend_from_slice(&[0, 0]);
+        pkt.extend_from_slice(&srcAddr);
+        pkt.extend_from_slice(&dstAddr);
+    } else {
+        pkt.extend_from_slice(&[0x60, 0, 0, 0]);
+        pkt.extend_from_slice(&(l4Len as u16).to_be_bytes());
+        pkt.push(proto as u8);
+        pkt.push(64);
+        pkt.extend_from_slice(&srcAddr);
+        pkt.extend_from_slice(&dstAddr);
+    }
+
+    if l4Len > 0 {
+        pkt.extend_from_slice(&srcPort.to_be_bytes());
+        pkt.extend_from_slice(&dstPort.to_be_bytes());
+    }
+
+    if proto == IPPROTO_TCP {
+        // seq, ack, data offset and SYN
+        pkt.extend_from_slice(&[0; 8]);
+        pkt.push(0x50);
+        pkt.push(0x02);
+        pkt.extend_from_slice(&[0; 6]);
+    } else if proto == IPPROTO_UDP {
+        pkt.extend_from_slice(&(l4Len as u16).to_be_bytes());
+        pkt.extend_from_slice(&[0, 0]);
+    }
+
+    return Some(pkt);
+}
+
+// RecvMsgFiltered drops the datagrams the filter rejects. The source address is
+// needed for the check, use a local buffer when the caller doesn't ask for it.
+pub fn RecvMsgFiltered(filter: &PacketFilter, sockfd: i32, msghdr: u64, flags: i32) -> i64 {
+    let hdr = unsafe { &mut *(msghdr as *mut msghdr) };
+    let mut local: sockaddr_storage = unsafe { core::mem::zeroed() };
+    let name = hdr.msg_name;
+    let namelen = hdr.msg_namelen;
+
+    loop {
+        if name.is_null() {
+            hdr.msg_name = &mut local as *mut _ as *mut c_void;
+            hdr.msg_namelen = core::mem::size_of::<sockaddr_storage>() as socklen_t;
+        } else {
+            hdr.msg_namelen = namelen;
+        }
+
+        let ret = unsafe { recvmsg(sockfd, hdr, flags) };
+        let err = errno::errno().0;
+        let addr = if ret < 0 {
+            Vec::new()
+        } else {
+            unsafe {
+                core::slice::from_raw_parts(hdr.msg_name as *const u8, hdr.msg_namelen as usize)
+            }
+            .to_vec()
+        };
+
+        if name.is_null() {
+            hdr.msg_name = name;
+            hdr.msg_namelen = 0;
+        }
+
+        if ret < 0 {
+            return -err as i64;
+        }
+
+        if filter.Ingress(sockfd, &addr) {
+            return ret as i64;
+        }
+
+        // a peeked datagram is still queued, read it to drop it
+        if flags & MSG_PEEK != 0 {
+            unsafe {
+                recv(sockfd, core::ptr::null_mut(), 0, MSG_DONTWAIT);
+            }
+        }
+    }
+}
@@ -0,0 +1,390 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use lazy_static::lazy_static;
+use libc::*;
+use std::ffi::CString;
+use std::net::IpAddr;
+
+use super::super::qlib::common::*;
+use super::super::qlib::rcu::*;
+use super::super::runc::oci::*;
+use super::packet_filter::{Family, SockAddr, SockName};
+
+// The routing policy of a multi-NIC sandbox, the "ip rule" of the pod network
+// namespace for the sockets of the sandbox. The rules are tried in order, the
+// first one whose selectors match the socket looks up the destination in its
+// table, a table without a route for it passes to the next rule:
+//   "quark.route.rules" = "from 10.1.0.0/24 table 100; fwmark 7 table 200"
+//   "quark.route.table.100" = "default dev net1; 192.168.0.0/16 dev eth0"
+// The selectors are "from <cidr>", "to <cidr>" and "fwmark <mark>", "all" or
+// a missing selector matches any. The route found binds the host socket to
+// its device as SO_BINDTODEVICE, when the socket is bound to an address and
+// when it connects. A socket the application bound to a device keeps it.
+// An unconnected datagram socket takes the device of its bound address only.
+pub const ROUTE_RULES_ANNOTATION: &str = "quark.route.rules";
+pub const ROUTE_TABLE_ANNOTATION_PREFIX: &str = "quark.route.table.";
+
+const SO_BINDTOIFINDEX: i32 = 62;
+
+lazy_static! {
+    pub static ref ROUTE_POLICY: QRcu<Option<Arc<RoutePolicy>>> = QRcu::New(None);
+}
+
+pub fn GetRoutePolicy() -> Option<Arc<RoutePolicy>> {
+    return ROUTE_POLICY.Read().as_ref().clone();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cidr {
+    // 4 or 16 bytes, empty for "all"
+    pub addr: Vec<u8>,
+    pub prefix: usize,
+}
+
+impl Cidr {
+    pub fn Parse(s: &str) -> Result<Self> {
+        if s == "all" || s == "default" {
+            return Ok(Self {
+                addr: Vec::new(),
+                prefix: 0,
+            });
+        }
+
+        let (ip, prefix) = match s.find('/') {
+            None => (s, None),
+            Some(idx) => (&s[..idx], Some(&s[idx + 1..])),
+        };
+
+        let addr = match ip.parse::<IpAddr>() {
+            Ok(IpAddr::V4(a)) => a.octets().to_vec(),
+            Ok(IpAddr::V6(a)) => a.octets().to_vec(),
+            Err(_) => return Err(Error::Common(format!("route: invalid address {}", s))),
+        };
+
+        let max = addr.len() * 8;
+        let prefix = match prefix {
+            None => max,
+            Some(p) => match p.parse::<usize>() {
+                Ok(p) if p <= max => p,
+                _ => return Err(Error::Common(format!("route: invalid prefix {}", s))),
+            },
+        };
+
+        return Ok(Self {
+            addr: addr,
+            prefix: prefix,
+        });
+    }
+
+    pub fn Contains(&self, addr: &[u8]) -> bool {
+        if self.addr.len() == 0 {
+            return true;
+        }
+
+        if self.addr.len() != addr.len() {
+            return false;
+        }
+
+        let bytes = self.prefix / 8;
+        if self.addr[..bytes] != addr[..bytes] {
+            return false;
+        }
+
+        let bits = self.prefix % 8;
+        if bits == 0 {
+            return true;
+        }
+
+        let mask = 0xffu8 << (8 - bits);
+        return self.addr[bytes] & mask == addr[bytes] & mask;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub dst: Cidr,
+    pub dev: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub from: Cidr,
+    pub to: Cidr,
+    pub fwmark: Option<u32>,
+    pub table: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct RoutePolicy {
+    pub rules: Vec<Rule>,
+    pub tables: BTreeMap<u32, Vec<Route>>,
+
+    pub bound: AtomicU64,
+}
+
+impl RoutePolicy {
+    pub fn FromSpec(spec: &Spec) -> Result<Option<Self>> {
+        let rules = match spec.annotations.get(ROUTE_RULES_ANNOTATION) {
+            None => return Ok(None),
+            Some(r) => r,
+        };
+
+        let mut policy = Self::default();
+        for rule in rules.split(';').map(|r| r.trim()).filter(|r| r.len() > 0) {
+            policy.rules.push(ParseRule(rule)?);
+        }
+
+        for rule in &policy.rules {
+            if policy.tables.contains_key(&rule.table) {
+                continue;
+            }
+
+            let name = format!("{}{}", ROUTE_TABLE_ANNOTATION_PREFIX, rule.table);
+            let routes = match spec.annotations.get(&name) {
+                None => return Err(Error::Common(format!("route: no table {}", rule.table))),
+                Some(r) => r,
+            };
+
+            let mut table = Vec::new();
+            for route in routes.split(';').map(|r| r.trim()).filter(|r| r.len() > 0) {
+                table.push(ParseRoute(route)?);
+            }
+
+            policy.tables.insert(rule.table, table);
+        }
+
+        info!(
+            "route: {} rules, {} tables",
+            policy.rules.len(),
+            policy.tables.len()
+        );
+        return Ok(Some(policy));
+    }
+
+    pub fn Install(self) {
+        ROUTE_POLICY.Write(Some(Arc::new(self)));
+    }
+
+    // Lookup returns the device of the route from src to dst, dst is none
+    // for a socket not connected which takes the default route.
+    pub fn Lookup(&self, src: &[u8], dst: Option<&[u8]>, mark: u32) -> Option<&str> {
+        for rule in &self.rules {
+            if !rule.from.Contains(src) {
+                continue;
+            }
+
+            if rule.fwmark.is_some() && rule.fwmark != Some(mark) {
+                continue;
+            }
+
+            match dst {
+                Some(dst) if !rule.to.Contains(dst) => continue,
+                None if rule.to.addr.len() > 0 => continue,
+                _ => (),
+            }
+
+            let table = match self.tables.get(&rule.table) {
+                None => continue,
+                Some(t) => t,
+            };
+
+            // the longest prefix of the family of the address
+            let len = src.len();
+            let mut best: Option<&Route> = None;
+            for route in table {
+                if route.dst.addr.len() > 0 && route.dst.addr.len() != len {
+                    continue;
+                }
+
+                let found = match dst {
+                    Some(dst) => route.dst.Contains(dst),
+                    None => route.dst.prefix == 0,
+                };
+
+                if found && best.map_or(true, |b| route.dst.prefix > b.dst.prefix) {
+                    best = Some(route);
+                }
+            }
+
+            if let Some(route) = best {
+                return Some(&route.dev);
+            }
+        }
+
+        return None;
+    }
+
+    // Bind binds the socket bound to addr to the device of its route.
+    pub fn Bind(&self, fd: i32, addr: u64, addrlen: u32) {
+        let src = unsafe { core::slice::from_raw_parts(addr as *const u8, addrlen as usize) };
+        let family = Family(src);
+        if family != AF_INET && family != AF_INET6 {
+            return;
+        }
+
+        let (src, _) = SockAddr(src, family);
+        // the rules of an unspecified address are taken at connect
+        if src.iter().all(|b| *b == 0) {
+            return;
+        }
+
+        self.Apply(fd, &src, None);
+    }
+
+    // Connect binds the socket connecting to addr to the device of its route.
+    pub fn Connect(&self, fd: i32, addr: u64, addrlen: u32) {
+        if addr == 0 {
+            return;
+        }
+
+        let dst = unsafe { core::slice::from_raw_parts(addr as *const u8, addrlen as usize) };
+        let family = Family(dst);
+        if family != AF_INET && family != AF_INET6 {
+            return;
+        }
+
+        let (dst, _) = SockAddr(dst, family);
+        let (src, _) = SockAddr(&SockName(fd, false), family);
+        self.Apply(fd, &src, Some(&dst));
+    }
+
+    fn Apply(&self, fd: i32, src: &[u8], dst: Option<&[u8]>) {
+        if SockOptI32(fd, SO_BINDTOIFINDEX).unwrap_or(0) != 0 {
+            return;
+        }
+
+        let mark = SockOptI32(fd, SO_MARK).unwrap_or(0) as u32;
+        let dev = match self.Lookup(src, dst, mark) {
+            None => return,
+            Some(dev) => dev,
+        };
+
+        let name = CString::new(dev).unwrap();
+        let ret = unsafe {
+            setsockopt(
+                fd,
+                SOL_SOCKET,
+                SO_BINDTODEVICE,
+                name.as_ptr() as *const c_void,
+                name.as_bytes_with_nul().len() as socklen_t,
+            )
+        };
+
+        if ret < 0 {
+            error!("route: bind fd {} to {} fail {}", fd, dev, errno::errno().0);
+            return;
+        }
+
+        self.bound.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn SockOptI32(fd: i32, name: i32) -> Option<i32> {
+    let mut val: i32 = 0;
+    let mut len = core::mem::size_of::<i32>() as socklen_t;
+    let ret = unsafe {
+        getsockopt(
+            fd,
+            SOL_SOCKET,
+            name,
+            &mut val as *mut _ as *mut c_void,
+            &mut len,
+        )
+    };
+
+    if ret < 0 {
+        return None;
+    }
+
+    return Some(val);
+}
+
+// ParseRule parses "[from <cidr>] [to <cidr>] [fwmark <mark>] table <id>".
+fn ParseRule(s: &str) -> Result<Rule> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    if words.len() % 2 != 0 {
+        return Err(Error::Common(format!("route: invalid rule {}", s)));
+    }
+
+    let mut rule = Rule {
+        from: Cidr::Parse("all")?,
+        to: Cidr::Parse("all")?,
+        fwmark: None,
+        table: 0,
+    };
+    let mut table = None;
+
+    for pair in words.chunks(2) {
+        match pair[0] {
+            "from" => rule.from = Cidr::Parse(pair[1])?,
+            "to" => rule.to = Cidr::Parse(pair[1])?,
+            "fwmark" => {
+                let mark = ParseNumber(pair[1])
+                    .ok_or_else(|| Error::Common(format!("route: invalid fwmark {}", s)))?;
+                rule.fwmark = Some(mark);
+            }
+            "table" => {
+                table = Some(
+                    pair[1]
+                        .parse::<u32>()
+                        .map_err(|_| Error::Common(format!("route: invalid table {}", s)))?,
+                )
+            }
+            _ => return Err(Error::Common(format!("route: invalid rule {}", s))),
+        }
+    }
+
+    rule.table = match table {
+        None => return Err(Error::Common(format!("route: rule without table {}", s))),
+        Some(t) => t,
+    };
+
+    return Ok(rule);
+}
+
+// ParseRoute parses "<cidr> dev <ifname>", the device has to be in the sandbox.
+fn ParseRoute(s: &str) -> Result<Route> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    if words.len() != 3 || words[1] != "dev" {
+        return Err(Error::Common(format!("route: invalid route {}", s)));
+    }
+
+    let dev = words[2];
+    let name =
+        CString::new(dev).map_err(|_| Error::Common(format!("route: invalid device {}", s)))?;
+    if dev.len() >= IFNAMSIZ || unsafe { if_nametoindex(name.as_ptr()) } == 0 {
+        return Err(Error::Common(format!("route: no device {}", dev)));
+    }
+
+    return Ok(Route {
+        dst: Cidr::Parse(words[0])?,
+        dev: dev.to_string(),
+    });
+}
+
+fn ParseNumber(s: &str) -> Option<u32> {
+    if s.starts_with("0x") {
+        return u32::from_str_radix(&s[2..], 16).ok();
+    }
+
+    return s.parse::<u32>().ok();
+}
@@ -0,0 +1,216 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::VecDeque;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::thread;
+
+use super::super::kvm_vcpu::KVMVcpu;
+use super::super::qlib::common::*;
+use super::super::qlib::qmsg::*;
+use super::super::SHARE_SPACE;
+
+// The host thread pool of the blocking qcalls. The qcalls are processed by the
+// thread draining the guest messages, a qcall blocking in the host, e.g. a
+// large read on a slow filesystem, holds up all the messages behind it. The
+// ones which may block are handed to the pool, they only do host syscalls on
+// the host fds. A qcall the guest sends with the global lock is processed in
+// place under it, the pool doesn't serialize its threads. The pool has two
+// lanes:
+//   latency: stat, open, readlink and the like, which a task waits for in the
+//     hot path
+//   bulk: file read/write, fsync and the like, which may take long
+// Each lane has its own threads so a burst of bulk IO doesn't starve the
+// latency ones. An idle latency thread helps the bulk lane, but at most half
+// of them do so, the others are always free for the latency qcalls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QcallLane {
+    Latency,
+    Bulk,
+}
+
+impl QcallLane {
+    // Of returns the lane of the qcall, None for the ones processed in place.
+    pub fn Of(msg: &Msg) -> Option<Self> {
+        match msg {
+            Msg::Fstat(_)
+            | Msg::Fstatat(_)
+            | Msg::Fstatfs(_)
+            | Msg::TryOpenAt(_)
+            | Msg::ReadLinkAt(_)
+            | Msg::FAccessAt(_)
+            | Msg::FGetXattr(_)
+            | Msg::FListXattr(_) => return Some(Self::Latency),
+            Msg::IORead(_)
+            | Msg::IOReadAt(_)
+            | Msg::IOWrite(_)
+            | Msg::IOWriteAt(_)
+            | Msg::IOAppend(_)
+            | Msg::FSync(_)
+            | Msg::FDataSync(_)
+            | Msg::SyncFileRange(_)
+            | Msg::SyncFs(_)
+            | Msg::Fallocate(_)
+            | Msg::Ftruncate(_) => return Some(Self::Bulk),
+            _ => return None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct QcallQueues {
+    latency: VecDeque<u64>,
+    bulk: VecDeque<u64>,
+
+    // the latency threads processing a bulk qcall
+    borrowed: usize,
+}
+
+pub struct QcallPool {
+    queues: Mutex<QcallQueues>,
+    cond: Condvar,
+    started: AtomicBool,
+    maxBorrowed: AtomicUsize,
+}
+
+lazy_static! {
+    pub static ref QCALL_POOL: QcallPool = QcallPool::New();
+}
+
+impl QcallPool {
+    pub fn New() -> Self {
+        return Self {
+            queues: Mutex::new(QcallQueues::default()),
+            cond: Condvar::new(),
+            started: AtomicBool::new(false),
+            maxBorrowed: AtomicUsize::new(0),
+        };
+    }
+
+    // Start starts the threads of the lanes, with no latency thread the pool
+    // is off and all the qcalls are processed in place.
+    pub fn Start(&'static self, latencyThreads: usize, bulkThreads: usize) -> Result<()> {
+        if latencyThreads == 0 {
+            return Ok(());
+        }
+
+        self.maxBorrowed
+            .store(latencyThreads / 2, Ordering::Relaxed);
+        for i in 0..latencyThreads {
+            thread::Builder::new()
+                .name(format!("qcall-l{}", i))
+                .spawn(move || self.Worker(QcallLane::Latency))
+                .map_err(|e| Error::IOError(format!("qcall pool: spawn thread fail {:?}", e)))?;
+        }
+
+        for i in 0..bulkThreads {
+            thread::Builder::new()
+                .name(format!("qcall-b{}", i))
+                .spawn(move || self.Worker(QcallLane::Bulk))
+                .map_err(|e| Error::IOError(format!("qcall pool: spawn thread fail {:?}", e)))?;
+        }
+
+        self.started.store(true, Ordering::Release);
+        info!(
+            "qcall pool: {} latency threads, {} bulk threads",
+            latencyThreads, bulkThreads
+        );
+        return Ok(());
+    }
+
+    // Dispatch queues the qcall of the QMsg at addr to its lane, it returns
+    // false if the qcall has to be processed in place, e.g. under the global
+    // lock.
+    pub fn Dispatch(&self, addr: u64) -> bool {
+        if !self.started.load(Ordering::Acquire) {
+            return false;
+        }
+
+        let qmsg = unsafe { &*(addr as *const QMsg) };
+        if qmsg.globalLock {
+            return false;
+        }
+
+        let lane = match QcallLane::Of(qmsg.msg) {
+            None => return false,
+            Some(lane) => lane,
+        };
+
+        {
+            let mut queues = self.queues.lock().unwrap();
+            match lane {
+                QcallLane::Latency => queues.latency.push_back(addr),
+                QcallLane::Bulk => queues.bulk.push_back(addr),
+            }
+        }
+
+        self.cond.notify_all();
+        return true;
+    }
+
+    // Next blocks till there is a qcall for a thread of the lane, it returns
+    // the qcall and whether a latency thread borrowed it from the bulk lane.
+    fn Next(&self, lane: QcallLane) -> (u64, bool) {
+        let maxBorrowed = self.maxBorrowed.load(Ordering::Relaxed);
+        let mut queues = self.queues.lock().unwrap();
+        loop {
+            match lane {
+                QcallLane::Latency => {
+                    if let Some(addr) = queues.latency.pop_front() {
+                        return (addr, false);
+                    }
+
+                    if queues.borrowed < maxBorrowed {
+                        if let Some(addr) = queues.bulk.pop_front() {
+                            queues.borrowed += 1;
+                            return (addr, true);
+                        }
+                    }
+                }
+                QcallLane::Bulk => {
+                    if let Some(addr) = queues.bulk.pop_front() {
+                        return (addr, false);
+                    }
+                }
+            }
+
+            queues = self.cond.wait(queues).unwrap();
+        }
+    }
+
+    fn Worker(&self, lane: QcallLane) {
+        loop {
+            let (addr, borrowed) = self.Next(lane);
+            let qmsg = unsafe { &mut *(addr as *mut QMsg) };
+            let taskId = qmsg.taskId;
+            qmsg.ret = KVMVcpu::qCall(qmsg.msg);
+
+            if borrowed {
+                self.queues.lock().unwrap().borrowed -= 1;
+                // another latency thread may borrow the next bulk qcall
+                self.cond.notify_all();
+            }
+
+            if taskId.Addr() != 0 {
+                SHARE_SPACE
+                    .scheduler
+                    .ScheduleQ(taskId, taskId.Queue(), true);
+            }
+        }
+    }
+}
@@ -59,3 +59,37 @@ impl RandGen {
         return res;
     }
 }
+
+// HostEntropy backs the guest rng device (HYPERCALL_RNG) with the host kernel
+// rng. GRND_NONBLOCK keeps a vcpu from blocking on a host whose entropy pool
+// is not initialized yet, the read fails with EIO in that case as the one of
+// a hardware rng which has no entropy to give.
+pub fn HostEntropy(data: &mut [u8]) -> i64 {
+    let mut filled = 0;
+    while filled < data.len() {
+        let ret = unsafe {
+            getrandom(
+                data[filled..].as_mut_ptr() as *mut c_void,
+                data.len() - filled,
+                GRND_NONBLOCK,
+            )
+        };
+
+        if ret < 0 {
+            let errno = errno::errno().0;
+            if errno == EINTR {
+                continue;
+            }
+
+            if errno == EAGAIN {
+                return -EIO as i64;
+            }
+
+            return -errno as i64;
+        }
+
+        filled += ret as usize;
+    }
+
+    return filled as i64;
+}
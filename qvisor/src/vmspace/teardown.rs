@@ -0,0 +1,129 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+// The teardown of the host resources of the sandbox. qvisor leaves through
+// many paths: the exit of the guest, its panic or OOM, an unexpected vm exit
+// and the panic of any qvisor thread. Each of them goes through Exit, which
+// releases the resources registered here in the reverse order of their
+// registration before the process is gone. The host fds, the io_uring
+// registrations and the mappings of the process are released by the host
+// kernel when it is gone, they are not registered. The registered ones are
+// the resources which outlive the process, e.g. the files it leaves on the
+// host, and the connection to the rdma service: its shutdown makes the
+// service release the QPs, MRs and memfds it holds for the sandbox.
+//
+// The teardown runs once and only try locks, it can be on the path of a
+// panic which holds any of the locks.
+
+pub type Release = Box<dyn FnOnce() + Send>;
+
+pub struct Resource {
+    pub id: u64,
+    pub name: &'static str,
+    pub release: Release,
+}
+
+pub struct TeardownMgr {
+    pub resources: Mutex<Vec<Resource>>,
+    pub nextId: AtomicU64,
+    pub done: AtomicBool,
+}
+
+lazy_static! {
+    pub static ref TEARDOWN: TeardownMgr = TeardownMgr::New();
+}
+
+impl TeardownMgr {
+    pub fn New() -> Self {
+        return Self {
+            resources: Mutex::new(Vec::new()),
+            nextId: AtomicU64::new(1),
+            done: AtomicBool::new(false),
+        };
+    }
+
+    // Register adds a resource released at the teardown, it returns the id
+    // to unregister it.
+    pub fn Register(&self, name: &'static str, release: Release) -> u64 {
+        let id = self.nextId.fetch_add(1, Ordering::Relaxed);
+        self.resources.lock().push(Resource {
+            id: id,
+            name: name,
+            release: release,
+        });
+        return id;
+    }
+
+    // Unregister removes the resource without releasing it, for a resource
+    // the sandbox has released itself.
+    pub fn Unregister(&self, id: u64) -> bool {
+        let mut resources = self.resources.lock();
+        match resources.iter().position(|r| r.id == id) {
+            None => return false,
+            Some(idx) => {
+                resources.remove(idx);
+                return true;
+            }
+        }
+    }
+
+    pub fn Teardown(&self, reason: &str) {
+        if self.done.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let resources = match self.resources.try_lock() {
+            None => {
+                error!("teardown: {}, resources locked, not released", reason);
+                Vec::new()
+            }
+            Some(mut resources) => core::mem::replace(&mut *resources, Vec::new()),
+        };
+
+        let count = resources.len();
+        for r in resources.into_iter().rev() {
+            info!("teardown: release {}", r.name);
+            (r.release)();
+        }
+
+        info!("teardown: {}, {} resources released", reason, count);
+    }
+}
+
+// Exit tears down the sandbox and exits the process with the code.
+pub fn Exit(code: i32) -> ! {
+    TEARDOWN.Teardown(&format!("exit {}", code));
+    unsafe { libc::_exit(code) }
+}
+
+// InstallPanicHook makes the panic of any thread of the sandbox abort it with
+// the teardown, after the panic message. A vcpu or io thread which unwinds
+// alone leaves the sandbox hung with its resources. The abort keeps the core
+// dump of the panic.
+pub fn InstallPanicHook() {
+    let prev = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        prev(info);
+        TEARDOWN.Teardown("panic");
+        std::process::abort();
+    }));
+}
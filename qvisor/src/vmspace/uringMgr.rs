@@ -15,6 +15,7 @@
 use alloc::vec::Vec;
 
 use super::super::qlib::common::*;
+use super::super::qlib::linux_def::*;
 use super::super::qlib::uring::sys::sys::*;
 use super::super::qlib::uring::*;
 
@@ -153,9 +154,9 @@ impl UringMgr {
     }
 
     pub fn Addfd(&mut self, fd: i32) -> Result<()> {
-        if fd as usize >= self.fds.len() {
+        if fd < 0 || fd as usize >= self.fds.len() {
             error!("Addfd out of bound fd {}", fd);
-            panic!("Addfd out of bound fd {}", fd)
+            return Err(Error::SysError(SysErr::EBADF));
         }
         self.fds[fd as usize] = fd;
 
@@ -165,7 +166,12 @@ impl UringMgr {
             fds: self.fds[fd as usize..].as_ptr() as _,
         };
 
-        return self.Register(IORING_REGISTER_FILES_UPDATE, &fu as *const _ as u64, 1);
+        let ret = self.Register(IORING_REGISTER_FILES_UPDATE, &fu as *const _ as u64, 1);
+        if ret.is_err() {
+            self.fds[fd as usize] = -1;
+        }
+
+        return ret;
     }
 
     pub fn Removefd(&mut self, fd: i32) -> Result<()> {
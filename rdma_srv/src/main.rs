@@ -748,6 +748,7 @@ fn main() -> io::Result<()> {
 
         for ev in &events {
             // print!("u64: {}, events: {:x}", ev.U64, ev.Events);
+            let mut closed = None;
             let event_data = fds.get(&(ev.U64 as i32));
             match event_data {
                 Some(FdType::TCPSocketServer) => {
@@ -854,6 +855,8 @@ fn main() -> io::Result<()> {
                         match ret {
                             Ok((size, _fds)) => {
                                 if size == 0 {
+                                    // the client is gone, e.g. the sandbox crashed
+                                    closed = Some(conn_sock.as_raw_fd());
                                     break;
                                 }
                                 if body == 1 {
@@ -909,6 +912,12 @@ fn main() -> io::Result<()> {
                 }
             }
 
+            if let Some(fd) = closed {
+                ReleaseContainer(epoll_fd, fd);
+                fds.remove(&fd);
+                close(fd);
+            }
+
             //println!("Finish processing fd: {}, event: {}", ev.U64, ev.Events);
         }
     }
@@ -945,6 +954,25 @@ fn InitContainer(conn_sock: &UnixSocket) {
         .unwrap();
 }
 
+fn ReleaseContainer(epoll_fd: RawFd, sockfd: RawFd) {
+    let _ = syscall!(epoll_ctl(
+        epoll_fd,
+        libc::EPOLL_CTL_DEL,
+        sockfd,
+        ptr::null_mut()
+    ));
+
+    let agent = RDMA_SRV
+        .agents
+        .lock()
+        .values()
+        .find(|agent| agent.sockfd == sockfd)
+        .cloned();
+    if let Some(agent) = agent {
+        agent.Release();
+    }
+}
+
 fn get_local_ip() -> u32 {
     let _my_local_ip = local_ip().unwrap();
 
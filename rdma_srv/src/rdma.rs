@@ -1156,6 +1156,15 @@ impl MemoryRegion {
     pub fn RKey(&self) -> u32 {
         return unsafe { (*self.0).rkey };
     }
+
+    // Dereg deregisters the region, the drop keeps it registered
+    pub fn Dereg(&self) {
+        if !self.0.is_null() {
+            unsafe {
+                rdmaffi::ibv_dereg_mr(self.0);
+            }
+        }
+    }
 }
 
 unsafe impl Send for MemoryRegion {}
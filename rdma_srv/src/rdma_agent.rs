@@ -63,12 +63,33 @@ pub struct RDMAAgentIntern {
     pub ioBufIdMgr: Mutex<IdMgr>,
 
     pub keys: Vec<[u32; 2]>,
+    // the registration of the memory region shared with client
+    pub mr: MemoryRegion,
     // TODO: indexes allocated for io buffer.
 
     //sockfd -> sockInfo
     // pub sockInfos: Mutex<HashMap<u32, SockInfo>>,
 }
 
+impl Drop for RDMAAgentIntern {
+    fn drop(&mut self) {
+        // the dummy agent has no shared memory region
+        if self.shareMemRegion.addr == 0 {
+            return;
+        }
+
+        self.mr.Dereg();
+        unsafe {
+            libc::munmap(
+                self.shareMemRegion.addr as *mut libc::c_void,
+                self.shareMemRegion.len as usize,
+            );
+            libc::close(self.client_memfd);
+            libc::close(self.client_eventfd);
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct RDMAAgent(Arc<RDMAAgentIntern>);
 
@@ -115,10 +136,14 @@ impl RDMAAgent {
             sockfd: connSock,
             client_memfd: memfd,
             client_eventfd: clientEventfd,
-            shareMemRegion: MemRegion { addr: 0, len: 0 },
+            shareMemRegion: MemRegion {
+                addr: addr as u64,
+                len: size as u64,
+            },
             shareRegion: Mutex::new(shareRegion),
             ioBufIdMgr: Mutex::new(IdMgr::Init(0, 20)),
             keys: vec![[mr.LKey(), mr.RKey()]],
+            mr: mr,
         }))
     }
 
@@ -136,9 +161,43 @@ impl RDMAAgent {
             },
             ioBufIdMgr: Mutex::new(IdMgr::Init(0, 0)),
             keys: vec![[0, 0]],
+            mr: MemoryRegion::default(),
         }))
     }
 
+    // Release removes the channels and the listening endpoints of the agent
+    // when its client is gone, e.g. a crashed sandbox. The shared memory
+    // region is released when the last reference of the agent is dropped, a
+    // channel in flight may still use it. The QPs are the ones of the
+    // connections to the other nodes, shared by the agents, and are kept.
+    pub fn Release(&self) {
+        RDMA_SRV.agents.lock().remove(&self.id);
+
+        let channels: Vec<u32> = RDMA_SRV
+            .channels
+            .lock()
+            .iter()
+            .filter(|(_, channel)| channel.agent.id == self.id)
+            .map(|(channelId, _)| *channelId)
+            .collect();
+        for channelId in &channels {
+            RDMA_SRV.channels.lock().remove(channelId);
+            RDMA_SRV.channelIdMgr.lock().Remove(*channelId);
+        }
+
+        RDMA_SRV
+            .srvEndPoints
+            .lock()
+            .retain(|_, endpoint| endpoint.agentId != self.id);
+
+        RDMA_SRV.agentIdMgr.lock().Remove(self.id);
+        println!(
+            "release agent {} with {} channels",
+            self.id,
+            channels.len()
+        );
+    }
+
     pub fn CreateServerRDMAChannel(
         &self,
         connectRequest: &ConnectRequest,
@@ -101,8 +101,7 @@ extern "C" time_t time(time_t* t) __attribute__((weak, alias("__vdso_time")));
 // __vdso_getcpu() implements getcpu()
 extern "C" long __vdso_getcpu(unsigned* cpu, unsigned* node,
                               struct getcpu_cache* cache) {
-  // No optimizations yet, just make the real system call.
-  return sys_getcpu(cpu, node, cache);
+  return GetCpu(cpu, node);
 }
 extern "C" long getcpu(unsigned* cpu, unsigned* node,
                        struct getcpu_cache* cache)
@@ -40,6 +40,8 @@ struct params {
   int64_t realtime_base_cycles;
   int64_t realtime_base_ref;
   uint64_t realtime_frequency;
+
+  uint64_t getcpu_ready;
 };
 
 // Returns a pointer to the global parameter page.
@@ -155,4 +157,28 @@ int ClockMonotonic(struct timespec* ts) {
   return 0;
 }
 
+#if __x86_64__
+
+// GetCpu() is the VDSO implementation of getcpu(). The sandbox kernel sets the
+// TSC_AUX of each vcpu to (node << 12) | cpu, so rdtscp returns the vcpu the
+// caller runs on.
+int GetCpu(unsigned* cpu, unsigned* node) {
+  struct params* params = get_params();
+  if (!params->getcpu_ready) {
+    return sys_getcpu(cpu, node, nullptr);
+  }
+
+  uint32_t aux;
+  asm volatile("rdtscp" : "=c"(aux) : : "eax", "edx");
+  if (cpu) {
+    *cpu = aux & 0xfff;
+  }
+  if (node) {
+    *node = aux >> 12;
+  }
+  return 0;
+}
+
+#endif
+
 }  // namespace vdso
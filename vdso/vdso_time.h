@@ -21,6 +21,9 @@ namespace vdso {
 
 int ClockRealtime(struct timespec* ts);
 int ClockMonotonic(struct timespec* ts);
+#if __x86_64__
+int GetCpu(unsigned* cpu, unsigned* node);
+#endif
 
 }  // namespace vdso
 